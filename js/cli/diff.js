@@ -0,0 +1,74 @@
+/**
+ * Prints the differences between two token whitelist accounts
+ *
+ * Usage: npm run diff -- <whitelist a> <whitelist b> [--json]
+ *
+ * Exits with 1 when the whitelists differ, so it can gate deploy scripts.
+ *
+ * @flow
+ */
+
+import {Connection, PublicKey} from '@solana/web3.js';
+
+import {getTokenWhitelist, diffTokenWhitelists} from '../client/token-whitelist';
+import {url} from '../url';
+
+function isEmpty(diff: Object): boolean {
+  return (
+    diff.header.length === 0 &&
+    diff.added.length === 0 &&
+    diff.removed.length === 0 &&
+    diff.changed.length === 0
+  );
+}
+
+function toJson(diff: Object): string {
+  return JSON.stringify(
+    {
+      header: diff.header,
+      added: diff.added.map(([key, amount]) => ({key, amount: amount.toString()})),
+      removed: diff.removed.map(([key, amount]) => ({key, amount: amount.toString()})),
+      changed: diff.changed.map(([key, a, b]) => ({key, a: a.toString(), b: b.toString()})),
+    },
+    null,
+    2,
+  );
+}
+
+function printTable(diff: Object) {
+  if (isEmpty(diff)) {
+    console.log('whitelists identical');
+    return;
+  }
+  diff.header.forEach(({field, a, b}) => console.log(`field   ${field.padEnd(20)} ${a} -> ${b}`));
+  diff.removed.forEach(([key, amount]) => console.log(`removed ${key.padEnd(44)} ${amount.toString()}`));
+  diff.changed.forEach(([key, a, b]) =>
+    console.log(`changed ${key.padEnd(44)} ${a.toString()} -> ${b.toString()}`),
+  );
+  diff.added.forEach(([key, amount]) => console.log(`added   ${key.padEnd(44)} ${amount.toString()}`));
+}
+
+async function main(): Promise<number> {
+  const [addressA, addressB] = process.argv.slice(2).filter(arg => !arg.startsWith('--'));
+  if (!addressA || !addressB) {
+    throw new Error('Usage: npm run diff -- <whitelist a> <whitelist b> [--json]');
+  }
+  const connection = new Connection(url, 'recent');
+  const diff = diffTokenWhitelists(
+    await getTokenWhitelist(connection, new PublicKey(addressA)),
+    await getTokenWhitelist(connection, new PublicKey(addressB)),
+  );
+  if (process.argv.includes('--json')) {
+    console.log(toJson(diff));
+  } else {
+    printTable(diff);
+  }
+  return isEmpty(diff) ? 0 : 1;
+}
+
+main()
+  .catch(err => {
+    console.error(err);
+    return -1;
+  })
+  .then(code => process.exit(code));
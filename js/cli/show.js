@@ -0,0 +1,33 @@
+/**
+ * Prints a summary of a token whitelist account
+ *
+ * Usage: npm run show -- <token whitelist account>
+ *
+ * @flow
+ */
+
+import {Connection, PublicKey} from '@solana/web3.js';
+
+import {
+  getTokenWhitelist,
+  summarizeTokenWhitelist,
+  formatWhitelistSummary,
+} from '../client/token-whitelist';
+import {url} from '../url';
+
+async function main() {
+  const address = process.argv[2];
+  if (!address) {
+    throw new Error('Usage: npm run show -- <token whitelist account>');
+  }
+  const connection = new Connection(url, 'recent');
+  const tokenWhitelist = await getTokenWhitelist(connection, new PublicKey(address));
+  console.log(formatWhitelistSummary(summarizeTokenWhitelist(tokenWhitelist)));
+}
+
+main()
+  .catch(err => {
+    console.error(err);
+    process.exit(-1);
+  })
+  .then(() => process.exit());
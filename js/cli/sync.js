@@ -0,0 +1,147 @@
+/**
+ * Syncs a token whitelist account to a CSV file of `address,allocation` lines
+ *
+ * Usage: npm run sync -- --whitelist <account> --keypair <owner keypair file> --file list.csv
+ *          [--apply] [--max-removal-percent 10] [--allow-mass-removal]
+ *          [--compute-unit-limit <units>] [--compute-unit-price <micro-lamports>] [--compute-budget auto]
+ *          [--yes]
+ *
+ * Without --apply (or with --dry-run) only the plan is printed. Re-running after a partial
+ * failure only sends the changes still missing. Adds and updates to a zero allocation, usually
+ * a missing amount in the file, are confirmed before sending unless --yes is passed.
+ *
+ * @flow
+ */
+
+import BN from 'bn.js';
+import fs from 'mz/fs';
+import readline from 'readline';
+import {Account, Connection, PublicKey, Transaction} from '@solana/web3.js';
+
+import {
+  getTokenWhitelist,
+  planReconcile,
+  reconcileInstructions,
+} from '../client/token-whitelist';
+import {sendAndConfirmTransaction} from '../client/util/send-and-confirm-transaction';
+import {withComputeBudget} from '../client/util/compute-budget';
+import type {ComputeBudget} from '../client/util/compute-budget';
+import {TOKEN_WHITELIST_PROGRAM_ID} from '../client/pubkeys';
+import {url} from '../url';
+
+const BATCH_SIZE = 5; // instructions per transaction
+
+function option(name: string): ?string {
+  const index = process.argv.indexOf(name);
+  return index === -1 ? null : process.argv[index + 1];
+}
+
+function required(name: string): string {
+  const value = option(name);
+  if (!value) {
+    throw new Error(`Missing ${name}`);
+  }
+  return value;
+}
+
+async function readDesired(file: string): Promise<Map<string, BN>> {
+  const desired = new Map();
+  const lines = (await fs.readFile(file, 'utf8')).split(/\r?\n/);
+  lines.forEach((line, index) => {
+    const [address, amount] = line.split(',').map(field => field.trim());
+    if (!address || (index === 0 && !/^\d+$/.test(amount))) {
+      return; // blank line or header
+    }
+    if (!/^\d+$/.test(amount)) {
+      throw new Error(`${file}:${index + 1}: invalid allocation '${amount}'`);
+    }
+    desired.set(new PublicKey(address).toBase58(), new BN(amount));
+  });
+  return desired;
+}
+
+async function confirm(question: string): Promise<boolean> {
+  const rl = readline.createInterface({input: process.stdin, output: process.stdout});
+  const answer = await new Promise(resolve => rl.question(`${question} [y/N] `, resolve));
+  rl.close();
+  return /^y(es)?$/i.test(answer.trim());
+}
+
+function computeBudget(): ?ComputeBudget {
+  if (option('--compute-budget') === 'auto') {
+    return 'auto';
+  }
+  const unitLimit = option('--compute-unit-limit');
+  const unitPrice = option('--compute-unit-price');
+  if (unitLimit == null && unitPrice == null) {
+    return null;
+  }
+  return {
+    unitLimit: unitLimit == null ? undefined : Number(unitLimit),
+    unitPrice: unitPrice == null ? undefined : Number(unitPrice),
+  };
+}
+
+async function main() {
+  const whitelist = new PublicKey(required('--whitelist'));
+  const owner = new Account(JSON.parse(await fs.readFile(required('--keypair'))));
+  const desired = await readDesired(required('--file'));
+  const apply = process.argv.includes('--apply') && !process.argv.includes('--dry-run');
+
+  const connection = new Connection(url, 'recent');
+  const plan = planReconcile(
+    await getTokenWhitelist(connection, whitelist),
+    desired,
+    Number(option('--max-removal-percent') || 10),
+    process.argv.includes('--allow-mass-removal'),
+  );
+  plan.removes.forEach(([key, amount]) => console.log(`remove ${key} (${amount.toString()})`));
+  plan.updates.forEach(([key, current, amount]) =>
+    console.log(`update ${key} ${current.toString()} -> ${amount.toString()}`),
+  );
+  plan.adds.forEach(([key, amount]) => console.log(`add    ${key} ${amount.toString()}`));
+  plan.skipped.forEach(key => console.log(`skip   ${key} (not a pubkey)`));
+
+  const instructions = reconcileInstructions(
+    plan,
+    TOKEN_WHITELIST_PROGRAM_ID,
+    owner.publicKey,
+    whitelist,
+  );
+  if (instructions.length === 0) {
+    console.log('whitelist in sync');
+    return;
+  }
+  if (!apply) {
+    console.log('dry run, pass --apply to send');
+    return;
+  }
+  const zeroAmounts =
+    plan.adds.filter(([, amount]) => amount.isZero()).length +
+    plan.updates.filter(([, , amount]) => amount.isZero()).length;
+  if (
+    zeroAmounts > 0 &&
+    !process.argv.includes('--yes') &&
+    !(await confirm(`${zeroAmounts} entries get a zero allocation, send anyway?`))
+  ) {
+    console.log('aborted');
+    return;
+  }
+  for (let i = 0; i < instructions.length; i += BATCH_SIZE) {
+    const transaction = await withComputeBudget(
+      connection,
+      new Transaction().add(...instructions.slice(i, i + BATCH_SIZE)),
+      [owner],
+      computeBudget(),
+    );
+    const signature = await sendAndConfirmTransaction('sync', connection, transaction, owner);
+    console.log('sent', signature);
+  }
+}
+
+main()
+  .catch(err => {
+    console.error(err);
+    process.exit(-1);
+  })
+  .then(() => process.exit());
@@ -6,6 +6,7 @@ import assert from 'assert';
 import BN from 'bn.js';
 import {Buffer} from 'buffer';
 import * as BufferLayout from 'buffer-layout';
+import {keccak_256} from 'js-sha3';
 import type {
   Connection,
   TransactionSignature,
@@ -17,6 +18,7 @@ import {
   Transaction,
   TransactionInstruction,
   SYSVAR_RENT_PUBKEY,
+  SYSVAR_SLOT_HASHES_PUBKEY,
 } from '@solana/web3.js';
 
 import * as Layout from './layout';
@@ -69,6 +71,28 @@ export const TOKEN_WHITELIST_ACCOUNT_DATA_LAYOUT = BufferLayout.struct([
   Layout.uint64("maxWhitelistSize"),
 ]);
 
+/**
+ * Return data of GetWhitelistStats. createdAtSlot and creator are zero for whitelists initialized
+ * before the program recorded them; programs without the CREATION_RECORD feature bit return the
+ * first three fields alone.
+ */
+export const WHITELIST_STATS_LAYOUT = BufferLayout.struct([
+  Layout.uint64("totalAdds"),
+  Layout.uint64("totalRemoves"),
+  Layout.uint64("lastMutationSlot"),
+  Layout.uint64("createdAtSlot"),
+  Layout.publicKey("creator"),
+]);
+
+/**
+ * Return data of GetRequiredRent, the bytes to allocate for a new whitelist and their rent
+ * exempt minimum
+ */
+export const REQUIRED_RENT_LAYOUT = BufferLayout.struct([
+  Layout.uint64("space"),
+  Layout.uint64("lamports"),
+]);
+
 /**
  * A program to exchange tokens against a pool of liquidity
  */
@@ -237,6 +261,7 @@ export class TokenWhitelist {
     initAuthority: PublicKey,
     tokenWhitelistPubkey: PublicKey,
   ): TransactionInstruction {
+    checkWhitelistSize(whitelistSize);
     const dataLayout = BufferLayout.struct([
       BufferLayout.u8('instruction'),
       Layout.uint64('max_whitelist_size'),
@@ -263,6 +288,69 @@ export class TokenWhitelist {
     });
   }
 
+  /**
+   * Init Token Whitelist With Units
+   *
+   * @param whitelistSize Maximum number of whitelist accounts
+   * @param allocationDecimals Decimals of allocation amounts, null to copy them from the mint
+   * @param initAuthority Account calling the init whitelist
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param allocationMint SPL Token mint the decimals are copied from, or null
+   * @param onlyWallets Whether only system-owned wallets may be added
+   * @param selfZeroAllowed Whether an account may zero its own allocation without the owner signing
+   */
+  static initTokenWhitelistWithUnitsInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    whitelistSize: number | Numberu64,
+    allocationDecimals: number | null,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    allocationMint: PublicKey | null,
+    onlyWallets: boolean = false,
+    selfZeroAllowed: boolean = true,
+  ): TransactionInstruction {
+    checkWhitelistSize(whitelistSize);
+    const fields = [
+      BufferLayout.u8('instruction'),
+      Layout.uint64('max_whitelist_size'),
+      BufferLayout.u8('has_decimals'),
+    ];
+    if (allocationDecimals !== null) {
+      fields.push(BufferLayout.u8('allocation_decimals'));
+    }
+    const flags = (onlyWallets ? 1 : 0) | (selfZeroAllowed ? 0 : 2);
+    if (flags !== 0) {
+      fields.push(BufferLayout.u8('flags'));
+    }
+    const dataLayout = BufferLayout.struct(fields);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 15, // InitTokenWhitelistWithUnits instruction
+        max_whitelist_size: new Numberu64(whitelistSize).toBuffer(),
+        has_decimals: allocationDecimals === null ? 0 : 1,
+        allocation_decimals: allocationDecimals,
+        flags,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
+    ];
+    if (allocationMint !== null) {
+      keys.push({pubkey: allocationMint, isSigner: false, isWritable: false});
+    }
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
   /**
    * Add Whitelist To Map
    *
@@ -329,13 +417,16 @@ export class TokenWhitelist {
     allocationAmount: number | Numberu64,
     initAuthority: PublicKey,
     tokenWhitelistPubkey: PublicKey,
+    mint?: PublicKey,
+    campaignId?: Buffer,
+    expectedNonce?: number | Numberu64,
   ): TransactionInstruction {
     const dataLayout = BufferLayout.struct([
       BufferLayout.u8('instruction'),
       Layout.uint64('allocation_amount'),
     ]);
 
-    const data = Buffer.alloc(dataLayout.span);
+    let data = Buffer.alloc(dataLayout.span);
     dataLayout.encode(
       {
         instruction: 1, // AddToWhitelist instruction
@@ -343,12 +434,30 @@ export class TokenWhitelist {
       },
       data,
     );
+    if (campaignId !== undefined) {
+      // 8 byte campaign the entry is attributed to
+      if (campaignId.length !== 8) {
+        throw new Error('campaign id must be 8 bytes');
+      }
+      data = Buffer.concat([data, campaignId]);
+    }
+    if (expectedNonce !== undefined && !new Numberu64(expectedNonce).isZero()) {
+      // mutation nonce the whitelist must still be at, after a campaign even when none is set
+      if (campaignId === undefined) {
+        data = Buffer.concat([data, Buffer.alloc(8)]);
+      }
+      data = Buffer.concat([data, new Numberu64(expectedNonce).toBuffer()]);
+    }
 
     const keys = [
       {pubkey: initAuthority, isSigner: true, isWritable: false},
       {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
       {pubkey: accountToAdd, isSigner: false, isWritable: false},
     ];
+    if (mint) {
+      // mint of a Token-2022 account, required under strict token checks
+      keys.push({pubkey: mint, isSigner: false, isWritable: false});
+    }
     return new TransactionInstruction({
       keys,
       programId: tokenWhitelistProgramId,
@@ -362,11 +471,13 @@ export class TokenWhitelist {
    * @param initAuthority Account calling the init whitelist
    * @param accountToRemove Account to be removed from whitelist
    * @param tokenWhitelistAccount Token Whitelist Account
+   * @param memo Reason logged with the removal, at most 64 bytes of UTF-8
    */
   async removeFromWhitelist(
     initAuthority: Account,
     accountToRemove: PublicKey,
     tokenWhitelistAccount: PublicKey,
+    memo?: string,
   ): Promise<TransactionSignature> {
     return await sendAndConfirmTransaction(
       'RemoveFromWhitelist',
@@ -377,6 +488,7 @@ export class TokenWhitelist {
           accountToRemove,
           initAuthority.publicKey,
           tokenWhitelistAccount,
+          memo,
         ),
       ),
       this.payer,
@@ -389,18 +501,30 @@ export class TokenWhitelist {
     accountToRemove: PublicKey,
     initAuthority: PublicKey,
     tokenWhitelistPubkey: PublicKey,
+    memo?: string,
+    expectedNonce?: number | Numberu64,
   ): TransactionInstruction {
     const dataLayout = BufferLayout.struct([
       BufferLayout.u8('instruction'),
     ]);
 
-    const data = Buffer.alloc(dataLayout.span);
+    let data = Buffer.alloc(dataLayout.span);
     dataLayout.encode(
       {
         instruction: 2, // RemoveFromWhitelist instruction
       },
       data,
     );
+    if (memo !== undefined) {
+      data = Buffer.concat([data, encodeMemo(memo)]);
+    }
+    if (expectedNonce !== undefined && !new Numberu64(expectedNonce).isZero()) {
+      // mutation nonce the whitelist must still be at, after a 255 length byte standing for no memo
+      if (memo === undefined) {
+        data = Buffer.concat([data, Buffer.from([255])]);
+      }
+      data = Buffer.concat([data, new Numberu64(expectedNonce).toBuffer()]);
+    }
 
     const keys = [
       {pubkey: initAuthority, isSigner: true, isWritable: false},
@@ -471,4 +595,2771 @@ export class TokenWhitelist {
       data,
     });
   }
+
+  /**
+   * Approve Delegate
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param delegate Account allowed to add to the whitelist on behalf of the owner
+   * @param maxAdds Number of adds the delegate can make
+   * @param maxAllocationPerAdd Maximum allocation amount per add in base tokens
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async approveDelegate(
+    initAuthority: Account,
+    delegate: PublicKey,
+    maxAdds: number,
+    maxAllocationPerAdd: number | Numberu64,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'ApproveDelegate',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.approveDelegateInstruction(
+          this.tokenWhitelistProgramId,
+          delegate,
+          maxAdds,
+          maxAllocationPerAdd,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static approveDelegateInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    delegate: PublicKey,
+    maxAdds: number,
+    maxAllocationPerAdd: number | Numberu64,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('max_adds'),
+      Layout.uint64('max_allocation_per_add'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 5, // ApproveDelegate instruction
+        max_adds: maxAdds,
+        max_allocation_per_add: new Numberu64(maxAllocationPerAdd).toBuffer(),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: delegate, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Revoke Delegate
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async revokeDelegate(
+    initAuthority: Account,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'RevokeDelegate',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.revokeDelegateInstruction(
+          this.tokenWhitelistProgramId,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static revokeDelegateInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 6, // RevokeDelegate instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Configure Registration
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param registrationEndSlot Registration is open before this slot
+   * @param registrationAllocation Allocation amount given to self registered accounts
+   * @param registrationDepositLamports Refundable deposit paid by self registered accounts
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async configureRegistration(
+    initAuthority: Account,
+    registrationEndSlot: number | Numberu64,
+    registrationAllocation: number | Numberu64,
+    registrationDepositLamports: number | Numberu64,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'ConfigureRegistration',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.configureRegistrationInstruction(
+          this.tokenWhitelistProgramId,
+          registrationEndSlot,
+          registrationAllocation,
+          registrationDepositLamports,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static configureRegistrationInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    registrationEndSlot: number | Numberu64,
+    registrationAllocation: number | Numberu64,
+    registrationDepositLamports: number | Numberu64,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('registration_end_slot'),
+      Layout.uint64('registration_allocation'),
+      Layout.uint64('registration_deposit_lamports'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 7, // ConfigureRegistration instruction
+        registration_end_slot: new Numberu64(registrationEndSlot).toBuffer(),
+        registration_allocation: new Numberu64(registrationAllocation).toBuffer(),
+        registration_deposit_lamports: new Numberu64(registrationDepositLamports).toBuffer(),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Register Self
+   *
+   * @param registrant Account adding itself to the whitelist and paying the deposit
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async registerSelf(
+    registrant: Account,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'RegisterSelf',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.registerSelfInstruction(
+          this.tokenWhitelistProgramId,
+          registrant.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      registrant,
+    );
+  }
+
+  static registerSelfInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    registrant: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 8, // RegisterSelf instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: registrant, isSigner: true, isWritable: true},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Refund Deposit
+   *
+   * @param authority Registered account after registration closes, or whitelist owner
+   * @param registeredAccount Registered account to receive the deposit
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async refundDeposit(
+    authority: Account,
+    tokenWhitelistAccount: PublicKey,
+    registeredAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'RefundDeposit',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.refundDepositInstruction(
+          this.tokenWhitelistProgramId,
+          authority.publicKey,
+          tokenWhitelistAccount,
+          registeredAccount,
+        ),
+      ),
+      this.payer,
+      authority,
+    );
+  }
+
+  static refundDepositInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    authority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    registeredAccount: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 9, // RefundDeposit instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: authority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: registeredAccount, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Run Lottery
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param winners Number of accounts to keep in the whitelist
+   * @param seed 32 byte seed mixed with the most recent slot hash
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async runLottery(
+    initAuthority: Account,
+    winners: number | Numberu64,
+    seed: Buffer,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'RunLottery',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.runLotteryInstruction(
+          this.tokenWhitelistProgramId,
+          winners,
+          seed,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static runLotteryInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    winners: number | Numberu64,
+    seed: Buffer,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('winners'),
+      BufferLayout.blob(32, 'seed'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 10, // RunLottery instruction
+        winners: new Numberu64(winners).toBuffer(),
+        seed: seed,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: SYSVAR_SLOT_HASHES_PUBKEY, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Consume Limits
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param maxConsumePerTx Maximum amount consumed by one instruction, 0 for unlimited
+   * @param minSlotsBetweenConsumes Slots an account must wait between consumes, 0 for no cooldown
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async setConsumeLimits(
+    initAuthority: Account,
+    maxConsumePerTx: number | Numberu64,
+    minSlotsBetweenConsumes: number | Numberu64,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'SetConsumeLimits',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.setConsumeLimitsInstruction(
+          this.tokenWhitelistProgramId,
+          maxConsumePerTx,
+          minSlotsBetweenConsumes,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static setConsumeLimitsInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    maxConsumePerTx: number | Numberu64,
+    minSlotsBetweenConsumes: number | Numberu64,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('max_consume_per_tx'),
+      Layout.uint64('min_slots_between_consumes'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 11, // SetConsumeLimits instruction
+        max_consume_per_tx: new Numberu64(maxConsumePerTx).toBuffer(),
+        min_slots_between_consumes: new Numberu64(minSlotsBetweenConsumes).toBuffer(),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Consume Allocation
+   *
+   * @param accountOwner Whitelisted account consuming its allocation
+   * @param amount Amount in base tokens to deduct from the allocation
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async consumeAllocation(
+    accountOwner: Account,
+    amount: number | Numberu64,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'ConsumeAllocation',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.consumeAllocationInstruction(
+          this.tokenWhitelistProgramId,
+          amount,
+          accountOwner.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      accountOwner,
+    );
+  }
+
+  static consumeAllocationInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    amount: number | Numberu64,
+    accountOwner: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('amount'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 12, // ConsumeAllocation instruction
+        amount: new Numberu64(amount).toBuffer(),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: accountOwner, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * List Entries, meant to be simulated: the page of up to `limit` entries starting at `offset`
+   * in key order is Borsh encoded in the return data together with the total entry count. Key
+   * order compares the base58 strings of the keys, which differs from their byte order
+   *
+   * @param offset Index in key order of the first entry returned
+   * @param limit Number of entries returned, at most 25
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param reader Owner or delegate signing, once the whitelist restricts enumeration
+   */
+  static listEntriesInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    offset: number,
+    limit: number,
+    tokenWhitelistPubkey: PublicKey,
+    reader?: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('offset'),
+      BufferLayout.u8('limit'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 13, // ListEntries instruction
+        offset,
+        limit,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+    ];
+    if (reader) {
+      // owner or delegate, required once the whitelist restricts enumeration
+      keys.push({pubkey: reader, isSigner: true, isWritable: false});
+    }
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * List Entries By Campaign, meant to be simulated: like List Entries over the entries
+   * attributed to `campaignId`, the total counting that campaign only
+   *
+   * @param campaignId 8 byte campaign, all zeroes for entries added without one
+   * @param offset Index in key order, within the campaign, of the first entry returned
+   * @param limit Number of entries returned, at most 25
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param reader Owner or delegate signing, once the whitelist restricts enumeration
+   */
+  static listEntriesByCampaignInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    campaignId: Buffer,
+    offset: number,
+    limit: number,
+    tokenWhitelistPubkey: PublicKey,
+    reader?: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.blob(8, 'campaign_id'),
+      BufferLayout.u32('offset'),
+      BufferLayout.u8('limit'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 34, // ListEntriesByCampaign instruction
+        campaign_id: campaignId,
+        offset,
+        limit,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+    ];
+    if (reader) {
+      // owner or delegate, required once the whitelist restricts enumeration
+      keys.push({pubkey: reader, isSigner: true, isWritable: false});
+    }
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Compute Entries Hash, meant to be simulated: the keccak hash of the Borsh encoded entries
+   * is returned in the return data, to compare with the hash stored in the extension
+   *
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static computeEntriesHashInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 14, // ComputeEntriesHash instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Allocation Bounds
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param minAllocation Smallest allocation amount accepted on add, 0 for unbounded
+   * @param maxAllocation Largest allocation amount accepted on add, 0 for unbounded
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async setAllocationBounds(
+    initAuthority: Account,
+    minAllocation: number | Numberu64,
+    maxAllocation: number | Numberu64,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'SetAllocationBounds',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.setAllocationBoundsInstruction(
+          this.tokenWhitelistProgramId,
+          minAllocation,
+          maxAllocation,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static setAllocationBoundsInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    minAllocation: number | Numberu64,
+    maxAllocation: number | Numberu64,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('min_allocation'),
+      Layout.uint64('max_allocation'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 16, // SetAllocationBounds instruction
+        min_allocation: new Numberu64(minAllocation).toBuffer(),
+        max_allocation: new Numberu64(maxAllocation).toBuffer(),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Add To Whitelist For Round: sets the allocation of an account in one round, round 0
+   * being the allocation AddToWhitelist sets
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param accountToAdd Account to be added to whitelist
+   * @param allocationAmount Allocation in the round
+   * @param round Round 0 to 3, or 255 for the active round
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async addToWhitelistForRound(
+    initAuthority: Account,
+    accountToAdd: PublicKey,
+    allocationAmount: number | Numberu64,
+    round: number,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'AddToWhitelistForRound',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.addToWhitelistForRoundInstruction(
+          this.tokenWhitelistProgramId,
+          accountToAdd,
+          allocationAmount,
+          round,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static addToWhitelistForRoundInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    accountToAdd: PublicKey,
+    allocationAmount: number | Numberu64,
+    round: number,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('allocation_amount'),
+      BufferLayout.u8('round'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 17, // AddToWhitelistForRound instruction
+        allocation_amount: new Numberu64(allocationAmount).toBuffer(),
+        round,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: accountToAdd, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Consume Allocation For Round
+   *
+   * @param accountOwner Whitelisted account consuming its allocation
+   * @param amount Amount in base tokens to deduct from the allocation
+   * @param round Round 0 to 3, or 255 for the active round
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async consumeAllocationForRound(
+    accountOwner: Account,
+    amount: number | Numberu64,
+    round: number,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'ConsumeAllocationForRound',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.consumeAllocationForRoundInstruction(
+          this.tokenWhitelistProgramId,
+          amount,
+          round,
+          accountOwner.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      accountOwner,
+    );
+  }
+
+  static consumeAllocationForRoundInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    amount: number | Numberu64,
+    round: number,
+    accountOwner: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('amount'),
+      BufferLayout.u8('round'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 18, // ConsumeAllocationForRound instruction
+        amount: new Numberu64(amount).toBuffer(),
+        round,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: accountOwner, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Active Round, the round consumed from when 255 is given
+   *
+   * @param initAuthority Account calling the init whitelist
+   * @param round Round 0 to 3
+   * @param tokenWhitelistAccount Token Whitelist Account
+   */
+  async setActiveRound(
+    initAuthority: Account,
+    round: number,
+    tokenWhitelistAccount: PublicKey,
+  ): Promise<TransactionSignature> {
+    return await sendAndConfirmTransaction(
+      'SetActiveRound',
+      this.connection,
+      new Transaction().add(
+        TokenWhitelist.setActiveRoundInstruction(
+          this.tokenWhitelistProgramId,
+          round,
+          initAuthority.publicKey,
+          tokenWhitelistAccount,
+        ),
+      ),
+      this.payer,
+      initAuthority,
+    );
+  }
+
+  static setActiveRoundInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    round: number,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('round'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 19, // SetActiveRound instruction
+        round,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Get Allocation, meant to be simulated: the allocation of the account in the round is
+   * returned as a little endian u64 in the return data
+   *
+   * @param account Whitelisted account
+   * @param round Round 0 to 3, or 255 for the active round
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static getAllocationInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    account: PublicKey,
+    round: number,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('round'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 20, // GetAllocation instruction
+        round,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: account, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Assert Membership Across, fails unless the account meets the flagged requirements:
+   * being in the first whitelist and being absent from the second one
+   *
+   * @param firstWhitelistPubkey Whitelist the account has to be in
+   * @param secondWhitelistPubkey Whitelist the account has to be absent from
+   * @param account Account checked
+   * @param requireInFirst Whether the account has to be in the first whitelist
+   * @param requireAbsentInSecond Whether the account has to be absent from the second whitelist
+   */
+  static assertMembershipAcrossInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    firstWhitelistPubkey: PublicKey,
+    secondWhitelistPubkey: PublicKey,
+    account: PublicKey,
+    requireInFirst: boolean,
+    requireAbsentInSecond: boolean,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('require_in_first'),
+      BufferLayout.u8('require_absent_in_second'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 21, // AssertMembershipAcross instruction
+        require_in_first: requireInFirst ? 1 : 0,
+        require_absent_in_second: requireAbsentInSecond ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: firstWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: secondWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: account, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  static archiveWhitelistInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    destinationAccount: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 22, // ArchiveWhitelist instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: destinationAccount, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  static getVersionInstruction(
+    tokenWhitelistProgramId: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 23, // GetVersion instruction
+      },
+      data,
+    );
+
+    return new TransactionInstruction({
+      keys: [],
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Instruction to simulate for the REQUIRED_RENT_LAYOUT return data, sizing the account of a
+   * whitelist of up to maxWhitelistSize entries
+   */
+  static getRequiredRentInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    maxWhitelistSize: number | Numberu64,
+  ): TransactionInstruction {
+    checkWhitelistSize(maxWhitelistSize);
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('max_whitelist_size'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 53, // GetRequiredRent instruction
+        max_whitelist_size: new Numberu64(maxWhitelistSize).toBuffer(),
+      },
+      data,
+    );
+
+    return new TransactionInstruction({
+      keys: [],
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  static setStrictTokenChecksInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    strictTokenChecks: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('strict_token_checks'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 24, // SetStrictTokenChecks instruction
+        strict_token_checks: strictTokenChecks ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Merkle Root
+   *
+   * @param root Root of the tree over (wallet, allocation) leaves
+   * @param hashKind Hash the tree was built with, HASH_KIND_KECCAK or HASH_KIND_SHA256
+   */
+  static setMerkleRootInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    root: Buffer,
+    hashKind: number,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.blob(32, 'root'),
+      BufferLayout.u8('hash_kind'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 25, // SetMerkleRoot instruction
+        root,
+        hash_kind: hashKind,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Verify Merkle Membership, fails unless the proof leads from the leaf of the wallet and
+   * its allocation at the leaf index to the stored root
+   *
+   * @param leafIndex Position of the leaf in the tree
+   * @param proof Sibling nodes from the leaf up, 32 bytes each
+   */
+  static verifyMerkleMembershipInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    wallet: PublicKey,
+    leafIndex: number,
+    allocation: number | Numberu64,
+    proof: Array<Buffer>,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('leaf_index'),
+      Layout.uint64('allocation'),
+      BufferLayout.u8('proof_length'),
+      BufferLayout.blob(32 * proof.length, 'proof'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 26, // VerifyMerkleMembership instruction
+        leaf_index: leafIndex,
+        allocation: new Numberu64(allocation).toBuffer(),
+        proof_length: proof.length,
+        proof: Buffer.concat(proof),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: wallet, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Verify Merkle Membership Batch, verifies every claim and sets the bit of its leaf in the
+   * claim bitmap, failing as a whole if any proof is invalid or any leaf was claimed before.
+   * Every wallet signs.
+   *
+   * @param items Claims as {wallet, leafIndex, allocation, proof}, proof being sibling nodes
+   * from the leaf up
+   * @param claimBitmapPubkey Claim bitmap of the whitelist
+   */
+  static verifyMerkleMembershipBatchInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    items: Array<{
+      wallet: PublicKey,
+      leafIndex: number,
+      allocation: number | Numberu64,
+      proof: Array<Buffer>,
+    }>,
+    tokenWhitelistPubkey: PublicKey,
+    claimBitmapPubkey: PublicKey,
+  ): TransactionInstruction {
+    const parts = [Buffer.from([27, items.length])]; // VerifyMerkleMembershipBatch instruction
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: claimBitmapPubkey, isSigner: false, isWritable: true},
+    ];
+    for (const {wallet, leafIndex, allocation, proof} of items) {
+      const leafIndexBuffer = Buffer.alloc(4);
+      leafIndexBuffer.writeUInt32LE(leafIndex);
+      parts.push(
+        wallet.toBuffer(),
+        leafIndexBuffer,
+        new Numberu64(allocation).toBuffer(),
+        Buffer.from([proof.length]),
+        ...proof,
+      );
+      if (!keys.some(key => key.pubkey.equals(wallet))) {
+        keys.push({pubkey: wallet, isSigner: true, isWritable: false});
+      }
+    }
+    const data = Buffer.concat(parts);
+    if (data.length > MAX_MERKLE_BATCH_DATA_LEN) {
+      throw new Error(
+        `merkle batch of ${data.length} bytes exceeds ${MAX_MERKLE_BATCH_DATA_LEN} bytes`,
+      );
+    }
+
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Init Claim Bitmap, the bitmap account created beforehand with claimBitmapLen(leafCount)
+   * bytes and owned by the program
+   *
+   * @param leafCount Leaves of the tree under the current merkle root
+   */
+  static initClaimBitmapInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    leafCount: number,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    claimBitmapPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('leaf_count'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 28, // InitClaimBitmap instruction
+        leaf_count: leafCount,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: claimBitmapPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  static assertOwnerInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    expectedOwner: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 29, // AssertOwner instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: expectedOwner, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Only Wallets
+   *
+   * @param onlyWallets Whether adds refuse program derived addresses and accounts not owned by the system program
+   */
+  static setOnlyWalletsInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    onlyWallets: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('only_wallets'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 30, // SetOnlyWallets instruction
+        only_wallets: onlyWallets ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Top Up Rent
+   *
+   * @param funder Account paying what the whitelist lacks to be rent exempt
+   */
+  static topUpRentInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    funder: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 31, // TopUpRent instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: funder, isSigner: true, isWritable: true},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Address of the config account of a whitelist, see initConfigInstruction
+   */
+  static async findConfigAddress(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): Promise<PublicKey> {
+    const [address] = await PublicKey.findProgramAddress(
+      [Buffer.from('config'), tokenWhitelistPubkey.toBuffer()],
+      tokenWhitelistProgramId,
+    );
+    return address;
+  }
+
+  /**
+   * Init Config
+   *
+   * Moves the policy of the whitelist to its config account. Instructions reading or setting
+   * policy then need the config account appended to their keys.
+   *
+   * @param initAuthority Owner of the whitelist, pays the rent of the config account
+   * @param configPubkey Config account, from findConfigAddress
+   */
+  static initConfigInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    configPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 32, // InitConfig instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: true},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: configPubkey, isSigner: false, isWritable: true},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Self Zero Allowed
+   *
+   * @param selfZeroAllowed Whether an account may zero its own allocation without the owner signing
+   */
+  static setSelfZeroAllowedInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    selfZeroAllowed: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('self_zero_allowed'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 33, // SetSelfZeroAllowed instruction
+        self_zero_allowed: selfZeroAllowed ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Zero Allocation Allowed
+   *
+   * @param allowZeroAllocation Whether the owner and the delegate may add an entry with a zero allocation
+   */
+  static setZeroAllocationAllowedInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    allowZeroAllocation: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('allow_zero_allocation'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 36, // SetZeroAllocationAllowed instruction
+        allow_zero_allocation: allowZeroAllocation ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Token Gate: RegisterSelf, and with gateOwnerAdds adds by the owner or the delegate, then
+   * need a token account of the gating mint held by the account being whitelisted, passed after
+   * the other accounts. A zero gatingMinimum clears the gate
+   *
+   * @param gatingMint Mint whose balance is required
+   * @param gatingMinimum Smallest balance accepted, in base units of the gating mint
+   * @param gateOwnerAdds Whether adds by the owner and the delegate are gated too
+   */
+  static setTokenGateInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    gatingMint: PublicKey,
+    gatingMinimum: number | Numberu64,
+    gateOwnerAdds: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.publicKey('gating_mint'),
+      Layout.uint64('gating_minimum'),
+      BufferLayout.u8('gate_owner_adds'),
+    ]);
+
+    const minimum = new Numberu64(gatingMinimum);
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 37, // SetTokenGate instruction
+        gating_mint: gatingMint.toBuffer(),
+        gating_minimum: minimum.toBuffer(),
+        gate_owner_adds: gateOwnerAdds ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    if (!minimum.isZero()) {
+      keys.push({pubkey: gatingMint, isSigner: false, isWritable: false});
+    }
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Repair State, owner only: rebuilds the map of a whitelist that no longer decodes from the
+   * entries that still parse. Simulate it first, the return data holds the Borsh encoded
+   * report of entries recovered and dropped. Healthy whitelists are left untouched
+   *
+   * @param initAuthority Owner of the whitelist
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static repairStateInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 35, // RepairState instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Inspect State: logs the header of the whitelist and up to maxEntriesToLog of its entries,
+   * one key=allocation line each, for reading the account from an explorer
+   *
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param maxEntriesToLog Entries logged, capped by the program at MAX_INSPECT_ENTRIES
+   * @param reader Owner or delegate signing, once the whitelist restricts enumeration
+   */
+  static inspectStateInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    maxEntriesToLog: number,
+    reader?: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('max_entries_to_log'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 39, // InspectState instruction
+        max_entries_to_log: Math.min(maxEntriesToLog, 255),
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+    ];
+    if (reader) {
+      // owner or delegate, required once the whitelist restricts enumeration
+      keys.push({pubkey: reader, isSigner: true, isWritable: false});
+    }
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Address of the ProgramData account of a program deployed with the upgradeable loader, see
+   * migrateStateLayoutInstruction
+   */
+  static async findProgramDataAddress(
+    tokenWhitelistProgramId: PublicKey,
+  ): Promise<PublicKey> {
+    const [address] = await PublicKey.findProgramAddress(
+      [tokenWhitelistProgramId.toBuffer()],
+      BPF_LOADER_UPGRADEABLE_PROGRAM_ID,
+    );
+    return address;
+  }
+
+  /**
+   * Migrate State Layout, upgrade authority only: rewrites a whitelist written by an older
+   * program in the current layout. Whitelists already in it are left untouched
+   *
+   * @param upgradeAuthority Upgrade authority of the program
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param programDataPubkey ProgramData account, from findProgramDataAddress
+   */
+  static migrateStateLayoutInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    upgradeAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    programDataPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 38, // MigrateStateLayout instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: upgradeAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: programDataPubkey, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Effective Allocation: simulated, returns the u64 (le) the account may consume right now in
+   * the active round, its remaining allocation capped by the consume limits and zero while
+   * its consume cooldown runs
+   *
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param account Whitelisted account
+   */
+  static effectiveAllocationInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    account: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 40, // EffectiveAllocation instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: account, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Get Whitelist Stats: simulated, returns the adds and removes the whitelist counted, the
+   * slot of the latest, and the slot and signer of its creation, decoded with
+   * WHITELIST_STATS_LAYOUT
+   *
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static getWhitelistStatsInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 45, // GetWhitelistStats instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Add To Whitelist With Attestation: Add To Whitelist recording a 32 byte attestation with
+   * the entry, e.g. the hash of the KYC record it was checked against. All zeroes keeps the
+   * attestation the entry held
+   *
+   * @param accountToAdd Account to be added to the whitelist
+   * @param allocationAmount Maximum allocation amount in base tokens
+   * @param attestation 32 byte reference to an off-chain attestation
+   * @param initAuthority Owner of the whitelist or its approved delegate
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static addToWhitelistWithAttestationInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    accountToAdd: PublicKey,
+    allocationAmount: number | Numberu64,
+    attestation: Buffer,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    if (attestation.length !== 32) {
+      throw new Error('attestation must be 32 bytes');
+    }
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('allocation_amount'),
+      BufferLayout.blob(32, 'attestation'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 46, // AddToWhitelistWithAttestation instruction
+        allocation_amount: new Numberu64(allocationAmount).toBuffer(),
+        attestation,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: accountToAdd, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Attestation: replaces the attestation of a whitelisted account, all zeroes clearing it
+   * unless attestations are required
+   *
+   * @param account Whitelisted account
+   * @param attestation 32 byte reference to an off-chain attestation
+   * @param initAuthority Owner of the whitelist or its approved delegate
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static setAttestationInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    account: PublicKey,
+    attestation: Buffer,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    if (attestation.length !== 32) {
+      throw new Error('attestation must be 32 bytes');
+    }
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.blob(32, 'attestation'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 47, // SetAttestation instruction
+        attestation,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: account, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Attestation Required
+   *
+   * @param requireAttestation Whether adds must leave every entry with an attestation, self
+   * registration being refused meanwhile
+   */
+  static setAttestationRequiredInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    requireAttestation: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('require_attestation'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 48, // SetAttestationRequired instruction
+        require_attestation: requireAttestation ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * List Attested Entries, meant to be simulated: like List Entries, each entry followed by its
+   * 32 byte attestation, all zeroes for entries without one
+   *
+   * @param offset Index in key order of the first entry returned
+   * @param limit Number of entries returned, at most 14
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   * @param reader Owner or delegate signing, once the whitelist restricts enumeration
+   */
+  static listAttestedEntriesInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    offset: number,
+    limit: number,
+    tokenWhitelistPubkey: PublicKey,
+    reader?: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('offset'),
+      BufferLayout.u8('limit'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 49, // ListAttestedEntries instruction
+        offset,
+        limit,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+    ];
+    if (reader) {
+      // owner or delegate, required once the whitelist restricts enumeration
+      keys.push({pubkey: reader, isSigner: true, isWritable: false});
+    }
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Rotate Whitelisted Key: move the entry of a whitelisted wallet, with its allocation,
+   * attestation and campaigns, to a new wallet, signed by both
+   *
+   * @param currentAccount Whitelisted wallet giving up its entry
+   * @param newAccount Wallet taking over the entry, not whitelisted yet
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static rotateWhitelistedKeyInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    currentAccount: PublicKey,
+    newAccount: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 50, // RotateWhitelistedKey instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: currentAccount, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: newAccount, isSigner: true, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Assert Not In Other: link sibling whitelists in place of the ones linked before. Adds
+   * then fail for accounts already in any of them and must pass them all, read-only, after
+   * their other accounts. No siblings unlinks them
+   *
+   * @param siblingWhitelists Sibling whitelists, at most 4
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static assertNotInOtherInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    siblingWhitelists: Array<PublicKey>,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 51, // AssertNotInOther instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      ...siblingWhitelists.map(pubkey => ({pubkey, isSigner: false, isWritable: false})),
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Owner Entry Allowed
+   *
+   * @param allowOwnerEntry Whether the whitelist owner may be added or register, the whitelist
+   * itself and the program never may
+   */
+  static setOwnerEntryAllowedInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    allowOwnerEntry: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('allow_owner_entry'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 52, // SetOwnerEntryAllowed instruction
+        allow_owner_entry: allowOwnerEntry ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Entry Note Commitment
+   *
+   * @param account Whitelisted account the note is about
+   * @param commitment noteCommitment(note, salt), 32 zero bytes clearing it
+   * @param initAuthority Whitelist owner
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static setEntryNoteCommitmentInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    account: PublicKey,
+    commitment: Buffer,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    if (commitment.length !== 32) {
+      throw new Error('commitment must be 32 bytes');
+    }
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.blob(32, 'commitment'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 54, // SetEntryNoteCommitment instruction
+        commitment,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: account, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Reveal Entry Note, failing unless note and salt hash to the commitment of the entry and
+   * logging the note in an EntryNoteRevealed event otherwise
+   *
+   * @param account Whitelisted account the note is about
+   * @param note At most 128 bytes
+   * @param salt The 16 bytes the commitment was computed with
+   */
+  static revealEntryNoteInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    account: PublicKey,
+    note: Buffer,
+    salt: Buffer,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    checkNote(note, salt);
+    const data = Buffer.concat([Buffer.from([55, note.length]), note, salt]); // RevealEntryNote instruction
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: account, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Registration Rate Limit
+   *
+   * Caps RegisterSelf at maxRegistrationsPerWindow per window of windowSlots slots, 0 lifting
+   * the cap. Adds by the owner or the delegate are not counted.
+   *
+   * @param initAuthority Whitelist owner
+   * @param tokenWhitelistPubkey Token Whitelist Account
+   */
+  static setRegistrationRateLimitInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    maxRegistrationsPerWindow: number,
+    windowSlots: number,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u16('max_registrations_per_window'),
+      BufferLayout.u32('window_slots'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 56, // SetRegistrationRateLimit instruction
+        max_registrations_per_window: maxRegistrationsPerWindow,
+        window_slots: windowSlots,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Set Enumeration Restricted
+   *
+   * @param restrictEnumeration Whether listing entries and inspecting the state need the owner
+   * or the delegate to sign, see the reader of listEntriesInstruction
+   */
+  static setEnumerationRestrictedInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    restrictEnumeration: boolean,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u8('restrict_enumeration'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 57, // SetEnumerationRestricted instruction
+        restrict_enumeration: restrictEnumeration ? 1 : 0,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Tree authority of a whitelist, signing for its compressed tree
+   */
+  static async findTreeAuthority(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+  ): Promise<PublicKey> {
+    const [address] = await PublicKey.findProgramAddress(
+      [Buffer.from('tree-authority'), tokenWhitelistPubkey.toBuffer()],
+      tokenWhitelistProgramId,
+    );
+    return address;
+  }
+
+  /**
+   * Init Compressed Whitelist
+   *
+   * Initializes an empty tree under the tree authority of the whitelist and makes it the
+   * compressed tree of the whitelist
+   *
+   * @param treePubkey Account of COMPRESSED_TREE_SPACE bytes owned by the compression program
+   * @param treeAuthority Tree authority, from findTreeAuthority
+   */
+  static initCompressedWhitelistInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    treePubkey: PublicKey,
+    treeAuthority: PublicKey,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 41, // InitCompressedWhitelist instruction
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: initAuthority, isSigner: true, isWritable: false},
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+      {pubkey: treePubkey, isSigner: false, isWritable: true},
+      {pubkey: treeAuthority, isSigner: false, isWritable: false},
+      {pubkey: COMPRESSION_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: NOOP_PROGRAM_ID, isSigner: false, isWritable: false},
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Append Compressed Entry, appends the leaf of the account at the next leaf index
+   *
+   * @param account Account being whitelisted
+   * @param treeAuthority Tree authority, from findTreeAuthority
+   */
+  static appendCompressedEntryInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    account: PublicKey,
+    treePubkey: PublicKey,
+    treeAuthority: PublicKey,
+    allocationAmount: number | Numberu64,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      Layout.uint64('allocation_amount'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 42, // AppendCompressedEntry instruction
+        allocation_amount: new Numberu64(allocationAmount).toBuffer(),
+      },
+      data,
+    );
+
+    const keys = compressedWriteKeys(initAuthority, tokenWhitelistPubkey, account, treePubkey, treeAuthority);
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Replace Compressed Entry, gives the leaf of the account at the leaf index a new allocation
+   *
+   * @param leaf {leafIndex, allocation, root, proof} of the leaf as it is, proof being sibling
+   * nodes from the leaf up and root a recent root of the tree
+   */
+  static replaceCompressedEntryInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    initAuthority: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    account: PublicKey,
+    treePubkey: PublicKey,
+    treeAuthority: PublicKey,
+    leaf: {
+      leafIndex: number,
+      allocation: number | Numberu64,
+      root: Buffer,
+      proof: Array<Buffer>,
+    },
+    allocationAmount: number | Numberu64,
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('leaf_index'),
+      Layout.uint64('previous_allocation'),
+      Layout.uint64('allocation_amount'),
+      BufferLayout.blob(32, 'root'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 43, // ReplaceCompressedEntry instruction
+        leaf_index: leaf.leafIndex,
+        previous_allocation: new Numberu64(leaf.allocation).toBuffer(),
+        allocation_amount: new Numberu64(allocationAmount).toBuffer(),
+        root: leaf.root,
+      },
+      data,
+    );
+
+    const keys = [
+      ...compressedWriteKeys(initAuthority, tokenWhitelistPubkey, account, treePubkey, treeAuthority),
+      ...proofKeys(leaf.proof),
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+
+  /**
+   * Verify Compressed Membership, fails unless the proof leads from the leaf of the wallet and
+   * its allocation at the leaf index to a recent root of the tree
+   *
+   * @param leaf {leafIndex, allocation, root, proof}, proof being sibling nodes from the leaf up
+   */
+  static verifyCompressedMembershipInstruction(
+    tokenWhitelistProgramId: PublicKey,
+    tokenWhitelistPubkey: PublicKey,
+    treePubkey: PublicKey,
+    wallet: PublicKey,
+    leaf: {
+      leafIndex: number,
+      allocation: number | Numberu64,
+      root: Buffer,
+      proof: Array<Buffer>,
+    },
+  ): TransactionInstruction {
+    const dataLayout = BufferLayout.struct([
+      BufferLayout.u8('instruction'),
+      BufferLayout.u32('leaf_index'),
+      Layout.uint64('allocation'),
+      BufferLayout.blob(32, 'root'),
+    ]);
+
+    const data = Buffer.alloc(dataLayout.span);
+    dataLayout.encode(
+      {
+        instruction: 44, // VerifyCompressedMembership instruction
+        leaf_index: leaf.leafIndex,
+        allocation: new Numberu64(leaf.allocation).toBuffer(),
+        root: leaf.root,
+      },
+      data,
+    );
+
+    const keys = [
+      {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: false},
+      {pubkey: wallet, isSigner: false, isWritable: false},
+      {pubkey: treePubkey, isSigner: false, isWritable: false},
+      {pubkey: COMPRESSION_PROGRAM_ID, isSigner: false, isWritable: false},
+      ...proofKeys(leaf.proof),
+    ];
+    return new TransactionInstruction({
+      keys,
+      programId: tokenWhitelistProgramId,
+      data,
+    });
+  }
+}
+
+const MAX_MEMO_LEN = 64; // longest memo in bytes the program accepts
+export const MAX_NOTE_LEN = 128; // longest note RevealEntryNote discloses
+const MAX_MERKLE_BATCH_DATA_LEN = 992; // longest VerifyMerkleMembershipBatch data
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey(
+  'BPFLoaderUpgradeab1e11111111111111111111111',
+); // loader owning the ProgramData account of the program
+export const COMPRESSION_PROGRAM_ID = new PublicKey(
+  'cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK',
+); // spl-account-compression, owner of compressed trees
+const NOOP_PROGRAM_ID = new PublicKey(
+  'noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV',
+); // spl-noop, logging the changelogs of compressed trees
+export const COMPRESSED_TREE_SPACE = 44280; // tree account size, compression::tree_space
+export const MAX_INSPECT_ENTRIES = 32; // most entries InspectState logs
+export const MAX_ENTRIES = 91; // ceiling of maxWhitelistSize, TokenWhitelist::MAX_ENTRIES
+export const UNLIMITED_WHITELIST_SIZE = new Numberu64('ffffffffffffffff', 16); // maxWhitelistSize limiting nothing
+export const HASH_KIND_KECCAK = 0; // hash kinds of a Merkle root, see merkle::HashKind
+export const HASH_KIND_SHA256 = 1;
+const CLAIM_BITMAP_HEADER = 69; // discriminator, whitelist, merkle root and leaf count
+
+/**
+ * Account size of a claim bitmap over leafCount leaves, one bit per leaf index
+ */
+export function claimBitmapLen(leafCount: number): number {
+  return CLAIM_BITMAP_HEADER + Math.ceil(leafCount / 8);
+}
+
+/**
+ * Whether the leaf at leafIndex is claimed in the claim bitmap account data
+ */
+export function isLeafClaimed(data: Buffer, leafIndex: number): boolean {
+  const leafCount = data.readUInt32LE(CLAIM_BITMAP_HEADER - 4);
+  if (data[0] !== 3 || leafIndex >= leafCount) {
+    throw new Error(`leaf ${leafIndex} is not tracked by this claim bitmap`);
+  }
+  return (data[CLAIM_BITMAP_HEADER + (leafIndex >> 3)] & (1 << (leafIndex & 7))) !== 0;
+}
+
+/**
+ * Refuse a whitelist size the program would reject at init, see TokenWhitelist::check_max_whitelist_size
+ */
+function checkWhitelistSize(whitelistSize: number | Numberu64) {
+  const size = new Numberu64(whitelistSize);
+  if (size.isZero()) {
+    throw new Error('whitelist size is 0, pass UNLIMITED_WHITELIST_SIZE for no limit');
+  }
+  if (!size.eq(UNLIMITED_WHITELIST_SIZE) && size.gtn(MAX_ENTRIES)) {
+    throw new Error(`whitelist size is ${whitelistSize.toString()}, at most ${MAX_ENTRIES} are allowed`);
+  }
+}
+
+/**
+ * Most entries a whitelist takes, null when its maxWhitelistSize limits nothing: for
+ * UNLIMITED_WHITELIST_SIZE and for the zero of whitelists initialized before init refused it
+ */
+export function entryLimit(maxWhitelistSize: Numberu64): number | null {
+  if (maxWhitelistSize.isZero() || maxWhitelistSize.eq(UNLIMITED_WHITELIST_SIZE)) {
+    return null;
+  }
+  return maxWhitelistSize.toNumber();
+}
+
+/**
+ * Encode a memo as its length byte followed by its UTF-8 bytes
+ */
+function encodeMemo(memo: string): Buffer {
+  const bytes = Buffer.from(memo, 'utf8');
+  if (bytes.length > MAX_MEMO_LEN) {
+    throw new Error(`memo is ${bytes.length} bytes, at most ${MAX_MEMO_LEN} are allowed`);
+  }
+  return Buffer.concat([Buffer.from([bytes.length]), bytes]);
+}
+
+/**
+ * Commitment SetEntryNoteCommitment stores for a note, the keccak hash of the note followed by
+ * its 16 byte salt, as state::note_commitment computes it
+ */
+export function noteCommitment(note: Buffer, salt: Buffer): Buffer {
+  checkNote(note, salt);
+  return Buffer.from(keccak_256.update(note).update(salt).arrayBuffer());
+}
+
+function checkNote(note: Buffer, salt: Buffer) {
+  if (note.length > MAX_NOTE_LEN) {
+    throw new Error(`note is ${note.length} bytes, at most ${MAX_NOTE_LEN} are allowed`);
+  }
+  if (salt.length !== 16) {
+    throw new Error('salt must be 16 bytes');
+  }
+}
+
+const ACCOUNT_STATE_SPACE = 5161; // size of the fixed whitelist layout
+const MAP_OFFSET = 41; // map length prefix follows isInitialized, initPubkey and maxWhitelistSize
+
+/**
+ * Read the allocation units from the Borsh encoded extension following the fixed layout,
+ * skipping the fields before them. Extensions written by older programs end early and leave
+ * the defaults in place.
+ *
+ * @param data Token Whitelist account data
+ */
+function decodeAllocationUnits(data: Buffer): Object {
+  const units = {allocationDecimals: 0, allocationMint: null};
+  if (data.length < ACCOUNT_STATE_SPACE + 4) {
+    return units;
+  }
+  const end = ACCOUNT_STATE_SPACE + 4 + data.readUInt32LE(ACCOUNT_STATE_SPACE);
+  let offset = ACCOUNT_STATE_SPACE + 4;
+  const skipOption = size => {
+    offset += data[offset] === 1 ? 1 + size : 1;
+  };
+  const skipMap = () => {
+    const entries = data.readUInt32LE(offset);
+    offset += 4;
+    for (let i = 0; i < entries; i++) {
+      offset += 4 + data.readUInt32LE(offset) + 8;
+    }
+  };
+  const fields = [
+    () => skipOption(44), // delegate
+    () => skipOption(24), // registration
+    skipMap, // registrationDeposits
+    () => skipOption(80), // lottery
+    () => (offset += 16), // consumeLimits
+    skipMap, // lastConsumeSlots
+    () => (offset += 32), // entriesHash
+    () => (units.allocationDecimals = data[offset++]),
+    () => {
+      if (data[offset] === 1) {
+        units.allocationMint = new PublicKey(data.slice(offset + 1, offset + 33));
+      }
+    },
+  ];
+  for (const field of fields) {
+    if (offset >= end) {
+      break;
+    }
+    field();
+  }
+  return units;
+}
+
+/**
+ * Decode the whitelist header and map from raw account data
+ *
+ * @param data Token Whitelist account data
+ */
+export function decodeTokenWhitelist(data: Buffer): Object {
+  assert(data.length >= ACCOUNT_STATE_SPACE, `Invalid account length: ${data.length}`);
+  const header = TOKEN_WHITELIST_ACCOUNT_DATA_LAYOUT.decode(data);
+  const whitelist = new Map();
+  if (data.readUInt32LE(MAP_OFFSET) > 0) {
+    let offset = MAP_OFFSET + 4;
+    const entries = data.readUInt32LE(offset);
+    offset += 4;
+    for (let i = 0; i < entries; i++) {
+      const keyLength = data.readUInt32LE(offset);
+      offset += 4;
+      const key = data.slice(offset, offset + keyLength).toString('utf8');
+      offset += keyLength;
+      whitelist.set(key, Numberu64.fromBuffer(data.slice(offset, offset + 8)));
+      offset += 8;
+    }
+  }
+  return {
+    isInitialized: header.isInitialized === 1,
+    initPubkey: new PublicKey(header.initPubkey),
+    maxWhitelistSize: Numberu64.fromBuffer(header.maxWhitelistSize),
+    whitelist,
+    ...decodeAllocationUnits(data),
+  };
+}
+
+/**
+ * Fetch and decode a token whitelist account
+ *
+ * @param connection The connection to use
+ * @param tokenWhitelistAccount Token Whitelist Account
+ */
+export async function getTokenWhitelist(
+  connection: Connection,
+  tokenWhitelistAccount: PublicKey,
+): Promise<Object> {
+  const accountInfo = await connection.getAccountInfo(tokenWhitelistAccount);
+  if (accountInfo === null) {
+    throw new Error('Failed to find token whitelist account');
+  }
+  return decodeTokenWhitelist(Buffer.from(accountInfo.data));
+}
+
+/**
+ * Aggregate statistics of a decoded whitelist, mirrors TokenWhitelist::summary in the program crate
+ *
+ * @param tokenWhitelist Decoded token whitelist
+ */
+export function summarizeTokenWhitelist(tokenWhitelist: Object): Object {
+  const allocations = [...tokenWhitelist.whitelist.values()].sort((a, b) => a.cmp(b));
+  const count = allocations.length;
+  let medianAllocation = null;
+  if (count % 2 === 1) {
+    medianAllocation = allocations[(count - 1) / 2];
+  } else if (count > 0) {
+    const low = allocations[count / 2 - 1];
+    const high = allocations[count / 2];
+    medianAllocation = low.add(high.sub(low).divn(2));
+  }
+  const maxWhitelistSize = tokenWhitelist.maxWhitelistSize;
+  const limit = entryLimit(maxWhitelistSize);
+  return {
+    owner: tokenWhitelist.initPubkey,
+    entries: count,
+    maxWhitelistSize,
+    entryLimit: limit,
+    totalAllocation: allocations.reduce((total, amount) => total.add(amount), new BN(0)),
+    minAllocation: count > 0 ? allocations[0] : null,
+    maxAllocation: count > 0 ? allocations[count - 1] : null,
+    medianAllocation,
+    utilization: limit === null ? null : (count * 100) / limit,
+    allocationDecimals: tokenWhitelist.allocationDecimals,
+    allocationMint: tokenWhitelist.allocationMint,
+  };
+}
+
+/**
+ * Render a whitelist summary the same way as the program crate's Display impl
+ *
+ * @param summary Output of summarizeTokenWhitelist
+ */
+export function formatWhitelistSummary(summary: Object): string {
+  const utilization =
+    summary.utilization === null ? '' : ` (${summary.utilization.toFixed(2)}%)`;
+  const allocation =
+    summary.entries === 0
+      ? 'none'
+      : `total ${summary.totalAllocation.toString()}, min ${summary.minAllocation.toString()}, ` +
+        `median ${summary.medianAllocation.toString()}, max ${summary.maxAllocation.toString()}`;
+  const mint =
+    summary.allocationMint === null ? '' : ` (mint ${summary.allocationMint.toBase58()})`;
+  return [
+    `owner:      ${summary.owner.toBase58()}`,
+    `entries:    ${summary.entries} / ${summary.entryLimit === null ? 'unlimited' : summary.entryLimit}${utilization}`,
+    `units:      ${summary.allocationDecimals} decimals${mint}`,
+    `allocation: ${allocation}`,
+  ].join('\n');
+}
+
+/**
+ * Differences turning decoded whitelist `a` into decoded whitelist `b`, mirrors
+ * client::diff::diff_whitelists in the program crate for the fields decoded here
+ *
+ * @param a Decoded token whitelist
+ * @param b Decoded token whitelist
+ */
+export function diffTokenWhitelists(a: Object, b: Object): Object {
+  const diff = {header: [], added: [], removed: [], changed: []};
+  const compare = (field, valueA, valueB) => {
+    if (valueA !== valueB) {
+      diff.header.push({field, a: valueA, b: valueB});
+    }
+  };
+  const mint = tokenWhitelist =>
+    tokenWhitelist.allocationMint === null ? 'None' : tokenWhitelist.allocationMint.toBase58();
+  compare('initialized', String(a.isInitialized), String(b.isInitialized));
+  compare('owner', a.initPubkey.toBase58(), b.initPubkey.toBase58());
+  compare('max_whitelist_size', a.maxWhitelistSize.toString(), b.maxWhitelistSize.toString());
+  compare('allocation_decimals', String(a.allocationDecimals), String(b.allocationDecimals));
+  compare('allocation_mint', mint(a), mint(b));
+
+  const keys = [...new Set([...a.whitelist.keys(), ...b.whitelist.keys()])].sort();
+  for (const key of keys) {
+    const amountA = a.whitelist.get(key);
+    const amountB = b.whitelist.get(key);
+    if (amountB === undefined) {
+      diff.removed.push([key, amountA]);
+    } else if (amountA === undefined) {
+      diff.added.push([key, amountB]);
+    } else if (!amountA.eq(amountB)) {
+      diff.changed.push([key, amountA, amountB]);
+    }
+  }
+  return diff;
+}
+
+/**
+ * Changes needed to turn a decoded whitelist into the desired entries, mirrors
+ * client::reconcile::plan_reconcile in the program crate
+ *
+ * @param tokenWhitelist Decoded token whitelist
+ * @param desired Desired allocation by base58 account address
+ * @param maxRemovalPercent Share of the current entries above which removals are refused
+ * @param allowMassRemoval Skip the maxRemovalPercent check
+ */
+export function planReconcile(
+  tokenWhitelist: Object,
+  desired: Map<string, BN>,
+  maxRemovalPercent: number = 10,
+  allowMassRemoval: boolean = false,
+): Object {
+  const plan = {adds: [], updates: [], removes: [], skipped: []};
+  const current = new Map();
+  for (const [key, amount] of tokenWhitelist.whitelist) {
+    try {
+      current.set(new PublicKey(key).toBase58(), amount);
+    } catch (err) {
+      plan.skipped.push(key);
+    }
+  }
+  for (const [key, amount] of desired) {
+    const currentAmount = current.get(key);
+    if (currentAmount === undefined) {
+      plan.adds.push([key, amount]);
+    } else if (!currentAmount.eq(amount)) {
+      plan.updates.push([key, currentAmount, amount]);
+    }
+  }
+  for (const [key, amount] of current) {
+    if (!desired.has(key)) {
+      plan.removes.push([key, amount]);
+    }
+  }
+  const entries = tokenWhitelist.whitelist.size;
+  if (!allowMassRemoval && plan.removes.length * 100 > maxRemovalPercent * entries) {
+    throw new Error(`Refusing to remove ${plan.removes.length} of ${entries} entries`);
+  }
+  return plan;
+}
+
+/**
+ * Instructions applying a reconcile plan, removals first so capacity is freed before adding
+ *
+ * @param plan Output of planReconcile
+ * @param tokenWhitelistProgramId Token Whitelist Program Id
+ * @param initAuthority Whitelist owner
+ * @param tokenWhitelistPubkey Token Whitelist Account
+ */
+export function reconcileInstructions(
+  plan: Object,
+  tokenWhitelistProgramId: PublicKey,
+  initAuthority: PublicKey,
+  tokenWhitelistPubkey: PublicKey,
+): Array<TransactionInstruction> {
+  return [
+    ...plan.removes.map(([key]) =>
+      TokenWhitelist.removeFromWhitelistInstruction(
+        tokenWhitelistProgramId,
+        new PublicKey(key),
+        initAuthority,
+        tokenWhitelistPubkey,
+      ),
+    ),
+    ...[...plan.updates.map(([key, , amount]) => [key, amount]), ...plan.adds].map(
+      ([key, amount]) =>
+        TokenWhitelist.addToWhitelistInstruction(
+          tokenWhitelistProgramId,
+          new PublicKey(key),
+          new Numberu64(amount),
+          initAuthority,
+          tokenWhitelistPubkey,
+        ),
+    ),
+  ];
+}
+
+/**
+ * Keys of AppendCompressedEntry and ReplaceCompressedEntry ahead of any proof
+ */
+function compressedWriteKeys(
+  initAuthority: PublicKey,
+  tokenWhitelistPubkey: PublicKey,
+  account: PublicKey,
+  treePubkey: PublicKey,
+  treeAuthority: PublicKey,
+): Array<{pubkey: PublicKey, isSigner: boolean, isWritable: boolean}> {
+  return [
+    {pubkey: initAuthority, isSigner: true, isWritable: false},
+    {pubkey: tokenWhitelistPubkey, isSigner: false, isWritable: true},
+    {pubkey: account, isSigner: false, isWritable: false},
+    {pubkey: treePubkey, isSigner: false, isWritable: true},
+    {pubkey: treeAuthority, isSigner: false, isWritable: false},
+    {pubkey: COMPRESSION_PROGRAM_ID, isSigner: false, isWritable: false},
+    {pubkey: NOOP_PROGRAM_ID, isSigner: false, isWritable: false},
+  ];
+}
+
+/**
+ * Proof nodes as the compression program takes them, the key of a read-only account each
+ */
+function proofKeys(
+  proof: Array<Buffer>,
+): Array<{pubkey: PublicKey, isSigner: boolean, isWritable: boolean}> {
+  return proof.map(node => ({pubkey: new PublicKey(node), isSigner: false, isWritable: false}));
 }
@@ -0,0 +1,76 @@
+// @flow
+
+import {ComputeBudgetProgram, Transaction} from '@solana/web3.js';
+import type {
+  Account,
+  Connection,
+  TransactionInstruction,
+} from '@solana/web3.js';
+
+const AUTO_UNIT_LIMIT_MARGIN = 10; // percent on top of the simulated consumption
+const AUTO_UNIT_LIMIT_MIN = 1000;
+const MAX_UNIT_LIMIT = 1400000;
+
+/**
+ * Compute budget of a transaction: fixed unit limit and/or price (micro-lamports per unit),
+ * or 'auto' to size the limit from a simulation and the price from recent prioritization fees
+ */
+export type ComputeBudget = 'auto' | {unitLimit?: number, unitPrice?: number};
+
+/**
+ * Budget instructions for a limit and price, the limit always comes first
+ */
+export function computeBudgetInstructions(
+  unitLimit: ?number,
+  unitPrice: ?number,
+): Array<TransactionInstruction> {
+  const instructions = [];
+  if (unitLimit != null) {
+    instructions.push(ComputeBudgetProgram.setComputeUnitLimit({units: unitLimit}));
+  }
+  if (unitPrice != null) {
+    instructions.push(ComputeBudgetProgram.setComputeUnitPrice({microLamports: unitPrice}));
+  }
+  return instructions;
+}
+
+/**
+ * Returns the transaction with the compute budget instructions prepended
+ */
+export async function withComputeBudget(
+  connection: Connection,
+  transaction: Transaction,
+  signers: Array<Account>,
+  budget: ?ComputeBudget,
+): Promise<Transaction> {
+  let instructions = [];
+  if (budget === 'auto') {
+    const {value} = await connection.simulateTransaction(transaction, signers);
+    if (value.err) {
+      throw new Error(`Simulation failed: ${JSON.stringify(value.err)}`);
+    }
+    const unitLimit =
+      value.unitsConsumed == null
+        ? null
+        : Math.min(
+            Math.max(
+              Math.floor((value.unitsConsumed * (100 + AUTO_UNIT_LIMIT_MARGIN)) / 100),
+              AUTO_UNIT_LIMIT_MIN,
+            ),
+            MAX_UNIT_LIMIT,
+          );
+    const writable = transaction.instructions
+      .flatMap(instruction => instruction.keys)
+      .filter(key => key.isWritable)
+      .map(key => key.pubkey);
+    const fees = (
+      await connection.getRecentPrioritizationFees({lockedWritableAccounts: writable})
+    )
+      .map(fee => fee.prioritizationFee)
+      .sort((a, b) => a - b);
+    instructions = computeBudgetInstructions(unitLimit, fees.length ? fees[Math.floor(fees.length / 2)] : 0);
+  } else if (budget) {
+    instructions = computeBudgetInstructions(budget.unitLimit, budget.unitPrice);
+  }
+  return new Transaction().add(...instructions, ...transaction.instructions);
+}
@@ -0,0 +1,136 @@
+//! Host side cost of reading and writing whitelist accounts, as paid by indexers decoding many of
+//! them. Run with `cargo bench --bench serialization`.
+//!
+//! `unpack_header` against `unpack_from_slice` bounds what a zero-copy or fixed-record layout
+//! could save a reader: the header read touches no entry, so the gap between the two is the
+//! cost of allocating and decoding the String keys of the map.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use solana_program::pubkey::Pubkey;
+use solr_token_whitelist::state::TokenWhitelist;
+
+/// Account size of the whitelists the program tests create
+const ACCOUNT_SPACE: usize = 10240;
+/// Map sizes benchmarked, 90 being close to the map capacity
+const SIZES: [usize; 3] = [10, 50, 90];
+
+fn whitelist(entries: usize) -> TokenWhitelist {
+    let mut whitelist = TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: Pubkey::new_from_array([7; 32]),
+        max_whitelist_size: 100,
+        ..TokenWhitelist::default()
+    };
+    for i in 0..entries {
+        let mut key = [0; 32];
+        key[..8].copy_from_slice(&(i as u64 + 1).to_le_bytes());
+        whitelist.add_keypair(&Pubkey::new_from_array(key).to_string(), &(i as u64 * 1_000));
+    }
+    whitelist.update_entries_hash(ACCOUNT_SPACE);
+    whitelist
+}
+
+fn account_data(whitelist: &TokenWhitelist) -> Vec<u8> {
+    let mut data = vec![0; ACCOUNT_SPACE];
+    whitelist.pack_into_slice(&mut data).unwrap();
+    data
+}
+
+fn bench_unpack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unpack_from_slice");
+    for entries in SIZES {
+        let data = account_data(&whitelist(entries));
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &data, |b, data| {
+            b.iter(|| TokenWhitelist::unpack_from_slice(black_box(data)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_unpack_header(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unpack_header");
+    for entries in SIZES {
+        let data = account_data(&whitelist(entries));
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &data, |b, data| {
+            b.iter(|| TokenWhitelist::unpack_header(black_box(data)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_pack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pack_into_slice");
+    for entries in SIZES {
+        let whitelist = whitelist(entries);
+        let mut data = vec![0; ACCOUNT_SPACE];
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &whitelist, |b, whitelist| {
+            b.iter(|| whitelist.pack_into_slice(black_box(&mut data)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+    for entries in SIZES {
+        let whitelist = whitelist(entries);
+        let data = account_data(&whitelist);
+        // a key mid-map, with entries behind it to move
+        let key = whitelist.whitelist_map.keys().nth(entries / 2).unwrap().clone();
+        let mut removed = whitelist.clone();
+        removed.drop_key(&key);
+        group.bench_function(BenchmarkId::new("repack", entries), |b| {
+            b.iter(|| {
+                let (mut removed, mut data) = (removed.clone(), data.clone());
+                removed.update_entries_hash(ACCOUNT_SPACE);
+                removed.pack_into_slice(black_box(&mut data)).unwrap()
+            })
+        });
+        group.bench_function(BenchmarkId::new("in_place", entries), |b| {
+            b.iter(|| {
+                let (mut removed, mut data) = (removed.clone(), data.clone());
+                removed.pack_removal_into_slice(black_box(&mut data), &key).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_contains_key(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contains_key");
+    for entries in SIZES {
+        let mut whitelist = whitelist(entries);
+        // the last key in key order, and one that is absent
+        let present = whitelist.whitelist_map.keys().last().unwrap().clone();
+        let absent = Pubkey::new_from_array([255; 32]).to_string();
+        group.bench_function(BenchmarkId::new("present", entries), |b| {
+            b.iter(|| whitelist.contains_key(black_box(&present)))
+        });
+        group.bench_function(BenchmarkId::new("absent", entries), |b| {
+            b.iter(|| whitelist.contains_key(black_box(&absent)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_map_bytes_remaining(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map_bytes_remaining");
+    for entries in SIZES {
+        let whitelist = whitelist(entries);
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &whitelist, |b, whitelist| {
+            b.iter(|| black_box(whitelist).map_bytes_remaining())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_unpack,
+    bench_unpack_header,
+    bench_pack,
+    bench_remove,
+    bench_contains_key,
+    bench_map_bytes_remaining
+);
+criterion_main!(benches);
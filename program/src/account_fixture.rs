@@ -0,0 +1,97 @@
+//! `AccountInfo`s over owned buffers for running the processor natively, without a bank
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Epoch,
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+use crate::{processor::Processor, state::TokenWhitelist};
+
+/// Program id passed to the processor by [run_instruction](fn.run_instruction.html)
+pub const FIXTURE_PROGRAM_ID: Pubkey = Pubkey::new_from_array([0x57; 32]);
+
+/// Read-only, unsigned and empty until configured
+#[derive(Clone, Debug)]
+pub struct AccountFixture {
+    pub key: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+}
+
+impl AccountFixture {
+    pub fn new(key: Pubkey) -> Self {
+        AccountFixture {
+            key,
+            is_signer: false,
+            is_writable: false,
+            lamports: 0,
+            data: vec![],
+            owner: Pubkey::default(),
+        }
+    }
+
+    pub fn signer(mut self) -> Self {
+        self.is_signer = true;
+        self
+    }
+
+    pub fn writable(mut self) -> Self {
+        self.is_writable = true;
+        self
+    }
+
+    pub fn lamports(mut self, lamports: u64) -> Self {
+        self.lamports = lamports;
+        self
+    }
+
+    /// Zeroed data of `len` bytes, an uninitialized whitelist when owned by the program
+    pub fn data_len(mut self, len: usize) -> Self {
+        self.data = vec![0; len];
+        self
+    }
+
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    pub fn owner(mut self, owner: Pubkey) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// Packs the whitelist into the data set by [data_len](#method.data_len)
+    pub fn whitelist(mut self, state: &TokenWhitelist) -> Self {
+        state.pack_into_slice(&mut self.data).unwrap();
+        self
+    }
+
+    pub fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            Epoch::default(),
+        )
+    }
+
+    /// Decodes the data as a whitelist
+    pub fn state(&self) -> TokenWhitelist {
+        TokenWhitelist::unpack_from_slice(&self.data).unwrap()
+    }
+}
+
+/// Runs `Processor::process` over the fixtures, in order, leaving their changes in place
+pub fn run_instruction(ix_data: &[u8], fixtures: &mut [AccountFixture]) -> ProgramResult {
+    let accounts: Vec<AccountInfo> = fixtures.iter_mut().map(AccountFixture::info).collect();
+    Processor::process(&FIXTURE_PROGRAM_ID, &accounts, ix_data)
+}
@@ -0,0 +1,33 @@
+//! Reading the accounts of an instruction in the order its handler expects them
+
+use solana_program::{account_info::AccountInfo, msg, program_error::ProgramError};
+
+/// Takes the next account of the instruction. A client leaving it out gets
+/// NotEnoughAccountKeys, with `role`, the name the handler gives the account, logged so the
+/// missing one can be told apart from the accounts around it.
+pub fn expect_account<'a, 'info, I: Iterator<Item = &'a AccountInfo<'info>>>(
+    iter: &mut I,
+    role: &'static str,
+) -> Result<I::Item, ProgramError> {
+    iter.next().ok_or_else(|| {
+        msg!("missing account: {}", role);
+        ProgramError::NotEnoughAccountKeys
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_fixture::AccountFixture;
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn test_expect_account() {
+        let key = Pubkey::new_unique();
+        let mut fixture = AccountFixture::new(key);
+        let accounts = [fixture.info()];
+        let iter = &mut accounts.iter();
+        assert_eq!(expect_account(iter, "whitelist_account").unwrap().key, &key);
+        assert_eq!(expect_account(iter, "destination").unwrap_err(), ProgramError::NotEnoughAccountKeys);
+    }
+}
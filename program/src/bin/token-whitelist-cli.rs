@@ -0,0 +1,137 @@
+//! Command line tools for whitelist accounts, built with the `client` feature:
+//!
+//! ```text
+//! cargo run --features client --bin token-whitelist-cli -- watch <whitelist> [--json]
+//!     [--slot-commitment processed|confirmed|finalized] [--url <rpc url>] [--ws-url <websocket url>]
+//! cargo run --features client --bin token-whitelist-cli -- show <whitelist> [--url <rpc url>]
+//! ```
+//!
+//! `watch` prints a timestamped line per change of the whitelist until interrupted, and a
+//! resync line whenever the websocket dropped and changes may have been missed. `show` prints
+//! the summary of the whitelist: owner, creation, entries, churn and allocations.
+
+use futures::StreamExt;
+use solana_program::pubkey::Pubkey;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solr_token_whitelist::client::{
+    fetch::fetch_whitelist,
+    watch::{render, utc_timestamp, watch_whitelist, websocket_url},
+};
+
+use std::{process::exit, str::FromStr};
+
+const USAGE: &str = "Usage: token-whitelist-cli watch <whitelist> [--json] \
+    [--slot-commitment processed|confirmed|finalized] [--url <rpc url>] [--ws-url <websocket url>]
+       token-whitelist-cli show <whitelist> [--url <rpc url>]";
+const DEFAULT_URL: &str = "http://127.0.0.1:8899";
+
+struct WatchArgs {
+    whitelist: Pubkey,
+    json: bool,
+    commitment: CommitmentConfig,
+    url: String,
+    ws_url: String,
+}
+
+fn parse_watch_args(args: &[String]) -> Result<WatchArgs, String> {
+    let mut whitelist = None;
+    let mut json = false;
+    let mut commitment = CommitmentConfig::confirmed();
+    let mut url = DEFAULT_URL.to_string();
+    let mut ws_url = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--json" => json = true,
+            "--slot-commitment" => {
+                let level = value()?;
+                commitment = CommitmentConfig {
+                    commitment: CommitmentLevel::from_str(&level).map_err(|_| format!("unknown commitment {}", level))?,
+                };
+            }
+            "--url" => url = value()?,
+            "--ws-url" => ws_url = Some(value()?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            address if whitelist.is_none() => {
+                whitelist = Some(Pubkey::from_str(address).map_err(|_| format!("{} is not a pubkey", address))?);
+            }
+            extra => return Err(format!("unexpected argument {}", extra)),
+        }
+    }
+    let whitelist = whitelist.ok_or("missing whitelist")?;
+    let ws_url = ws_url.unwrap_or_else(|| websocket_url(&url));
+    Ok(WatchArgs {whitelist, json, commitment, url, ws_url})
+}
+
+struct ShowArgs {
+    whitelist: Pubkey,
+    url: String,
+}
+
+fn parse_show_args(args: &[String]) -> Result<ShowArgs, String> {
+    let mut whitelist = None;
+    let mut url = DEFAULT_URL.to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--url" => url = args.next().cloned().ok_or_else(|| format!("{} needs a value", arg))?,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            address if whitelist.is_none() => {
+                whitelist = Some(Pubkey::from_str(address).map_err(|_| format!("{} is not a pubkey", address))?);
+            }
+            extra => return Err(format!("unexpected argument {}", extra)),
+        }
+    }
+    let whitelist = whitelist.ok_or("missing whitelist")?;
+    Ok(ShowArgs {whitelist, url})
+}
+
+enum Command {
+    Watch(WatchArgs),
+    Show(ShowArgs),
+}
+
+fn show(args: ShowArgs) {
+    let rpc = RpcClient::new_with_commitment(args.url, CommitmentConfig::confirmed());
+    match fetch_whitelist(&rpc, &args.whitelist) {
+        Ok(whitelist) => println!("{}", whitelist.state.summary()),
+        Err(error) => {
+            eprintln!("{}", error);
+            exit(1);
+        }
+    }
+}
+
+async fn watch(args: WatchArgs) {
+    if !args.json {
+        println!("{} watching {} at {:?}", utc_timestamp(), args.whitelist, args.commitment.commitment);
+    }
+    let mut events = Box::pin(watch_whitelist(args.url, args.ws_url, args.whitelist, args.commitment));
+    while let Some(event) = events.next().await {
+        for line in render(&event, &utc_timestamp(), args.json) {
+            println!("{}", line);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match args.split_first() {
+        Some((command, rest)) if command == "watch" => parse_watch_args(rest).map(Command::Watch),
+        Some((command, rest)) if command == "show" => parse_show_args(rest).map(Command::Show),
+        _ => Err("unknown command".to_string()),
+    };
+    let command = command.unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        exit(2);
+    });
+    match command {
+        Command::Watch(watch_args) => {
+            let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+            runtime.block_on(watch(watch_args));
+        }
+        Command::Show(show_args) => show(show_args),
+    }
+}
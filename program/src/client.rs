@@ -0,0 +1,754 @@
+//! Off-chain helpers for sending whitelist transactions, enabled by the `client` feature
+
+use solana_client::{
+    client_error::ClientError as RpcClientError,
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_program::{
+    address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    message::CompileError,
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    signature::{Keypair, Signature},
+    signer::SignerError,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use thiserror::Error;
+use crate::{
+    error::TokenWhitelistError,
+    instruction::TokenWhitelistInstruction,
+    rules,
+    state::{TokenWhitelist, WhitelistConfig, MAP_ENTRY_OVERHEAD, NO_ATTESTATION, NO_CAMPAIGN},
+};
+#[cfg(feature = "merkle")]
+use crate::merkle::HashKind;
+#[cfg(feature = "compression")]
+use crate::{compression, state::CompressedTree};
+
+use compute_budget::{
+    auto_unit_limit, auto_unit_price, build_transaction, compute_budget_instructions,
+    writable_accounts, ComputeBudget,
+};
+use lookup_table::build_versioned_transaction;
+use rpc::WhitelistRpc;
+use std::collections::{btree_map::Entry, BTreeMap};
+
+pub mod allocations;
+pub mod bulk;
+pub mod cache;
+pub mod capture;
+pub mod claims;
+pub mod closeout;
+#[cfg(feature = "compression")]
+pub mod compressed;
+pub mod compute_budget;
+pub mod diff;
+pub mod entries;
+pub mod fetch;
+pub mod inclusion;
+pub mod lookup_table;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock;
+pub mod nonce;
+pub mod offline;
+pub mod planner;
+pub mod reconcile;
+pub mod rent;
+pub mod rpc;
+pub mod subscribe;
+pub mod version;
+pub mod watch;
+
+/// Options for [send_with_preflight](fn.send_with_preflight.html)
+#[derive(Clone, Copy, Debug)]
+pub struct PreflightOpts {
+    pub program_id: Pubkey, // only instructions for this program are checked locally
+    pub allow_overwrite: bool, // accept adds that replace the allocation of an existing entry
+    pub skip_simulation: bool, // broadcast right after the local checks
+    pub compute_budget: ComputeBudget, // budget instructions prepended to the transaction
+}
+
+impl PreflightOpts {
+    pub fn new(program_id: Pubkey) -> Self {
+        PreflightOpts {
+            program_id,
+            allow_overwrite: false,
+            skip_simulation: false,
+            compute_budget: ComputeBudget::Default,
+        }
+    }
+}
+
+/// Options for the official deployment at crate::id()
+impl Default for PreflightOpts {
+    fn default() -> Self {
+        Self::new(crate::id())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// Rejected by the program, or by the local checks mirroring it
+    #[error("token whitelist {} error {}: {0}", .0.category(), .0.code())]
+    Program(TokenWhitelistError),
+    /// Simulation failed without a whitelist error, nothing was sent
+    #[error("simulation failed: {transaction_error}")]
+    Simulation {
+        transaction_error: TransactionError,
+        logs: Vec<String>,
+    },
+    /// The plan removes more entries than allowed
+    #[error("refusing to remove {removals} of {entries} entries")]
+    MassRemoval { removals: u64, entries: u64 },
+    /// Account data could not be decoded as a whitelist or lookup table
+    #[error("invalid account {0}")]
+    InvalidAccount(Pubkey),
+    /// Simulation returned no return data from the program, or data that does not decode
+    #[error("invalid return data")]
+    InvalidReturnData,
+    /// The transaction could not be decoded or holds no whitelist instruction
+    #[error("invalid transaction {0}")]
+    InvalidTransaction(Signature),
+    /// Replaying the captured instruction does not reproduce the accounts fetched after it
+    #[error("capture of {signature} does not replay: account {index} {mismatch}")]
+    CaptureMismatch {
+        signature: Signature,
+        index: usize,
+        mismatch: String,
+    },
+    /// Not a base64 encoded transaction
+    #[error("invalid encoded transaction")]
+    InvalidEncoding,
+    /// The signature is not one the key must make over the message
+    #[error("invalid signature for {0}")]
+    InvalidSignature(Pubkey),
+    /// The leaves an indexer returned for a compressed tree do not hash to its current root,
+    /// it missed or has yet to see some of the latest changes
+    #[error("indexer is behind compressed tree {0}")]
+    StaleIndexer(Pubkey),
+    /// The node had not reached the slot asked for by the deadline, `slot` being the latest
+    /// context slot it answered at
+    #[error("slot {min_slot} not reached in time, last context slot {slot:?}")]
+    SlotNotReached { min_slot: u64, slot: Option<u64> },
+    /// The instructions do not compile into a v0 message with the given lookup tables
+    #[error("message compilation failed: {0}")]
+    Compile(CompileError),
+    #[error(transparent)]
+    Signer(SignerError),
+    #[error(transparent)]
+    Rpc(Box<RpcClientError>),
+}
+
+impl From<TokenWhitelistError> for ClientError {
+    fn from(error: TokenWhitelistError) -> Self {
+        ClientError::Program(error)
+    }
+}
+
+impl From<CompileError> for ClientError {
+    fn from(error: CompileError) -> Self {
+        ClientError::Compile(error)
+    }
+}
+
+impl From<SignerError> for ClientError {
+    fn from(error: SignerError) -> Self {
+        ClientError::Signer(error)
+    }
+}
+
+impl From<RpcClientError> for ClientError {
+    fn from(error: RpcClientError) -> Self {
+        match error.get_transaction_error().as_ref().and_then(decode_transaction_error) {
+            Some(error) => ClientError::Program(error),
+            None => ClientError::Rpc(Box::new(error)),
+        }
+    }
+}
+
+/// Runs the local checks, simulates the transaction and only then broadcasts it, with the
+/// compute budget instructions of `opts` first; `ComputeBudget::Auto` sizes them from the
+/// simulation
+pub fn send_with_preflight<R: WhitelistRpc>(
+    rpc: &R,
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    opts: PreflightOpts,
+) -> Result<Signature, ClientError> {
+    preflight_and_send(rpc, instructions, opts, |budget, blockhash| {
+        Ok(build_transaction(keypair, budget, instructions, blockhash).into())
+    })
+}
+
+/// Same as [send_with_preflight](fn.send_with_preflight.html), sending a v0 transaction that
+/// resolves accounts through the lookup tables
+pub fn send_versioned_with_preflight<R: WhitelistRpc>(
+    rpc: &R,
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    opts: PreflightOpts,
+) -> Result<Signature, ClientError> {
+    preflight_and_send(rpc, instructions, opts, |budget, blockhash| {
+        build_versioned_transaction(keypair, budget, instructions, lookup_tables, blockhash)
+    })
+}
+
+fn preflight_and_send<R: WhitelistRpc>(
+    rpc: &R,
+    instructions: &[Instruction],
+    opts: PreflightOpts,
+    build: impl Fn(&[Instruction], Hash) -> Result<VersionedTransaction, ClientError>,
+) -> Result<Signature, ClientError> {
+    let mut whitelists = BTreeMap::new();
+    for instruction in instructions.iter().filter(|ix| ix.program_id == opts.program_id) {
+        if let Some(meta) = instruction.accounts.get(1) {
+            if let Entry::Vacant(entry) = whitelists.entry(meta.pubkey) {
+                let data = rpc.get_account(&meta.pubkey)?.data;
+                let mut state = TokenWhitelist::unpack_from_slice(&data)
+                    .map_err(|_| ClientError::InvalidAccount(meta.pubkey))?;
+                // the program checks the policy held by the config account, so do the same here
+                if let Some(bump) = state.extension.config_bump {
+                    let config = WhitelistConfig::address(&opts.program_id, &meta.pubkey, bump)
+                        .map_err(|_| ClientError::InvalidAccount(meta.pubkey))?;
+                    let data = rpc.get_account(&config)?.data;
+                    WhitelistConfig::unpack_from_slice(&data)
+                        .map_err(|_| ClientError::InvalidAccount(config))?
+                        .policy
+                        .apply(&mut state.extension);
+                }
+                entry.insert(state);
+            }
+        }
+    }
+    check_instructions(&mut whitelists, instructions, &opts)?;
+
+    let blockhash = rpc.get_latest_blockhash()?;
+    let budget = match opts.compute_budget {
+        ComputeBudget::Default => vec![],
+        ComputeBudget::Fixed { unit_limit, unit_price } => compute_budget_instructions(unit_limit, unit_price),
+        ComputeBudget::Auto => {
+            let units_consumed = simulate(rpc, &build(&[], blockhash)?)?.units_consumed;
+            compute_budget_instructions(
+                units_consumed.map(auto_unit_limit),
+                Some(auto_unit_price(rpc, &writable_accounts(instructions))?),
+            )
+        }
+    };
+    let transaction = build(&budget, blockhash)?;
+    if !opts.skip_simulation && opts.compute_budget != ComputeBudget::Auto {
+        simulate(rpc, &transaction)?;
+    }
+    rpc.send_and_confirm(&transaction)
+}
+
+/// Simulates a transaction, decoding a failure into a whitelist error where possible
+pub(crate) fn simulate<R: WhitelistRpc>(
+    rpc: &R,
+    transaction: &VersionedTransaction,
+) -> Result<RpcSimulateTransactionResult, ClientError> {
+    let result = rpc.simulate(transaction)?;
+    if let Some(transaction_error) = result.err {
+        let logs = result.logs.unwrap_or_default();
+        return Err(match decode_simulation_error(&transaction_error, &logs) {
+            Some(error) => ClientError::Program(error),
+            None => ClientError::Simulation { transaction_error, logs },
+        });
+    }
+    Ok(result)
+}
+
+/// Mirrors the on-chain owner, capacity and duplicate entry checks against decoded whitelists,
+/// applying adds and removes in order so later instructions see the earlier ones
+pub fn check_instructions(
+    whitelists: &mut BTreeMap<Pubkey, TokenWhitelist>,
+    instructions: &[Instruction],
+    opts: &PreflightOpts,
+) -> Result<(), TokenWhitelistError> {
+    for instruction in instructions.iter().filter(|ix| ix.program_id == opts.program_id) {
+        check_instruction(whitelists, instruction, opts)?;
+    }
+    Ok(())
+}
+
+fn check_instruction(
+    whitelists: &mut BTreeMap<Pubkey, TokenWhitelist>,
+    instruction: &Instruction,
+    opts: &PreflightOpts,
+) -> Result<(), TokenWhitelistError> {
+    let payload = TokenWhitelistInstruction::unpack(&instruction.data)
+        .map_err(|_| TokenWhitelistError::InvalidInstruction)?;
+    match payload {
+        TokenWhitelistInstruction::InitTokenWhitelist {..}
+        | TokenWhitelistInstruction::InitTokenWhitelistWithUnits {..}
+        | TokenWhitelistInstruction::ListEntries {..}
+        | TokenWhitelistInstruction::ListEntriesByCampaign {..}
+        | TokenWhitelistInstruction::GetAllocation {..}
+        | TokenWhitelistInstruction::AssertMembershipAcross {..}
+        | TokenWhitelistInstruction::AssertOwner {}
+        | TokenWhitelistInstruction::TopUpRent {}
+        | TokenWhitelistInstruction::ComputeEntriesHash {}
+        | TokenWhitelistInstruction::GetVersion {}
+        | TokenWhitelistInstruction::GetRequiredRent {..}
+        | TokenWhitelistInstruction::RevealEntryNote {..}
+        | TokenWhitelistInstruction::VerifyMerkleMembership {..}
+        | TokenWhitelistInstruction::VerifyMerkleMembershipBatch {..}
+        | TokenWhitelistInstruction::InspectState {..}
+        | TokenWhitelistInstruction::EffectiveAllocation {}
+        | TokenWhitelistInstruction::GetWhitelistStats {}
+        | TokenWhitelistInstruction::ListAttestedEntries {..}
+        | TokenWhitelistInstruction::VerifyCompressedMembership {..} => return Ok(()),
+        _ => {}
+    }
+    let account = |index: usize| {
+        instruction.accounts.get(index)
+            .map(|meta| meta.pubkey)
+            .ok_or(TokenWhitelistError::InvalidInstruction)
+    };
+    let authority = account(0)?;
+    let state = whitelists.get_mut(&account(1)?).ok_or(TokenWhitelistError::TokenWhitelistNotInit)?;
+    if !state.is_initialized {
+        return Err(TokenWhitelistError::TokenWhitelistNotInit);
+    }
+
+    match payload {
+        TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id, expected_nonce} => {
+            rules::check_mutation_nonce(state, expected_nonce)?;
+            check_add(state, authority, &account(2)?.to_string(), allocation_amount, 0, campaign_id, opts)?;
+            rules::check_target_identity(state, &account(1)?, &opts.program_id, &account(2)?)?;
+            rules::check_attestation(state, &account(2)?.to_string(), NO_ATTESTATION)?;
+        }
+        TokenWhitelistInstruction::AddToWhitelistForRound {allocation_amount, round, campaign_id, expected_nonce} => {
+            rules::check_mutation_nonce(state, expected_nonce)?;
+            check_add(state, authority, &account(2)?.to_string(), allocation_amount, round, campaign_id, opts)?;
+            rules::check_target_identity(state, &account(1)?, &opts.program_id, &account(2)?)?;
+            rules::check_attestation(state, &account(2)?.to_string(), NO_ATTESTATION)?;
+        }
+        TokenWhitelistInstruction::AddToWhitelistWithAttestation {allocation_amount, attestation} => {
+            let key = account(2)?.to_string();
+            check_add(state, authority, &key, allocation_amount, 0, NO_CAMPAIGN, opts)?;
+            rules::check_target_identity(state, &account(1)?, &opts.program_id, &account(2)?)?;
+            rules::check_attestation(state, &key, attestation)?;
+            if attestation != NO_ATTESTATION {
+                state.set_attestation(&key, attestation);
+            }
+        }
+        TokenWhitelistInstruction::SetAttestation {attestation} => {
+            let wallet = account(2)?;
+            rules::check_set_attestation(state, &authority, &wallet, attestation)?;
+            state.set_attestation(&wallet.to_string(), attestation);
+        }
+        TokenWhitelistInstruction::SetEntryNoteCommitment {commitment} => {
+            if authority != state.init_pubkey {
+                return Err(TokenWhitelistError::InvalidAuthority);
+            }
+            let key = account(2)?.to_string();
+            if !state.whitelist_map.contains_key(&key) {
+                return Err(TokenWhitelistError::AccountNotWhitelisted);
+            }
+            state.set_note_commitment(&key, commitment);
+        }
+        TokenWhitelistInstruction::RotateWhitelistedKey {} => {
+            let new_account = account(2)?;
+            rules::check_rotate_key(state, &authority, &new_account)?;
+            rules::check_target_identity(state, &account(1)?, &opts.program_id, &new_account)?;
+            state.rotate_key(&authority.to_string(), &new_account.to_string())?;
+        }
+        TokenWhitelistInstruction::RemoveFromWhitelist {expected_nonce, ..} => {
+            if authority != state.init_pubkey {
+                return Err(TokenWhitelistError::TokenWhitelistNotOwner);
+            }
+            rules::check_mutation_nonce(state, expected_nonce)?;
+            // the program writes nothing for an account that is not whitelisted
+            if state.drop_key(&account(2)?.to_string()).is_none() {
+                return Ok(());
+            }
+        }
+        TokenWhitelistInstruction::CloseWhitelistAccount {}
+        | TokenWhitelistInstruction::ApproveDelegate {..}
+        | TokenWhitelistInstruction::RevokeDelegate {}
+        | TokenWhitelistInstruction::ConfigureRegistration {..}
+        | TokenWhitelistInstruction::RunLottery {..}
+        | TokenWhitelistInstruction::SetConsumeLimits {..}
+        | TokenWhitelistInstruction::SetRegistrationRateLimit {..}
+        | TokenWhitelistInstruction::SetEnumerationRestricted {..}
+        | TokenWhitelistInstruction::SetAllocationBounds {..}
+        | TokenWhitelistInstruction::SetActiveRound {..}
+        | TokenWhitelistInstruction::ArchiveWhitelist {}
+        | TokenWhitelistInstruction::SetStrictTokenChecks {..}
+        | TokenWhitelistInstruction::SetOnlyWallets {..}
+        | TokenWhitelistInstruction::SetSelfZeroAllowed {..}
+        | TokenWhitelistInstruction::SetZeroAllocationAllowed {..}
+        | TokenWhitelistInstruction::SetTokenGate {..}
+        | TokenWhitelistInstruction::SetAttestationRequired {..}
+        | TokenWhitelistInstruction::AssertNotInOther {}
+        | TokenWhitelistInstruction::SetOwnerEntryAllowed {..}
+        | TokenWhitelistInstruction::InitConfig {}
+        | TokenWhitelistInstruction::RepairState {}
+        | TokenWhitelistInstruction::SetMerkleRoot {..}
+        | TokenWhitelistInstruction::InitClaimBitmap {..}
+        | TokenWhitelistInstruction::InitCompressedWhitelist {}
+        | TokenWhitelistInstruction::AppendCompressedEntry {..}
+        | TokenWhitelistInstruction::ReplaceCompressedEntry {..} if authority != state.init_pubkey => {
+            return Err(TokenWhitelistError::InvalidAuthority);
+        }
+        TokenWhitelistInstruction::SetAllocationToZero {..}
+            if state.extension.self_zero_disabled
+                && !instruction.accounts.iter().any(|meta| meta.pubkey == state.init_pubkey && meta.is_signer) =>
+        {
+            return Err(TokenWhitelistError::OwnerSignatureRequired);
+        }
+        TokenWhitelistInstruction::InitConfig {} if state.extension.config_bump.is_some() => {
+            return Err(TokenWhitelistError::ConfigAlreadyInitialized);
+        }
+        #[cfg(feature = "merkle")]
+        TokenWhitelistInstruction::SetMerkleRoot {hash_kind, ..} => {
+            HashKind::from_u8(hash_kind)?;
+        }
+        TokenWhitelistInstruction::InitCompressedWhitelist {} if state.extension.compressed_tree.is_some() => {
+            return Err(TokenWhitelistError::CompressedTreeAlreadyInitialized);
+        }
+        #[cfg(feature = "compression")]
+        TokenWhitelistInstruction::InitCompressedWhitelist {} => {
+            let (_, authority_bump) = compression::find_tree_authority(&opts.program_id, &account(1)?);
+            state.extension.compressed_tree = Some(CompressedTree {tree: account(2)?, authority_bump, leaf_count: 0});
+        }
+        #[cfg(feature = "compression")]
+        TokenWhitelistInstruction::AppendCompressedEntry {allocation_amount} => {
+            let mut compressed_tree = rules::check_compressed_append(state, &account(3)?, allocation_amount)?;
+            rules::check_target_identity(state, &account(1)?, &opts.program_id, &account(2)?)?;
+            compressed_tree.leaf_count += 1;
+            state.extension.compressed_tree = Some(compressed_tree);
+        }
+        #[cfg(feature = "compression")]
+        TokenWhitelistInstruction::ReplaceCompressedEntry {leaf_index, allocation_amount, ..} => {
+            rules::check_compressed_replace(state, &account(3)?, leaf_index, allocation_amount)?;
+        }
+        _ => {}
+    }
+    // the instruction writes the whitelist, a nonce still 0 is one the account does not track
+    if state.extension.mutation_nonce != 0 {
+        state.extension.mutation_nonce += 1;
+    }
+    Ok(())
+}
+
+fn check_add(
+    state: &mut TokenWhitelist,
+    authority: Pubkey,
+    key: &str,
+    allocation_amount: u64,
+    round: u8,
+    campaign_id: [u8; 8],
+    opts: &PreflightOpts,
+) -> Result<(), TokenWhitelistError> {
+    let round = rules::check_add(state, &authority, allocation_amount, round)?;
+    if state.whitelist_map.contains_key(key) {
+        if !opts.allow_overwrite {
+            return Err(TokenWhitelistError::AlreadyRegistered);
+        }
+    } else {
+        state.check_new_key(key)?;
+        // the program packs the map into a fixed region and fails the store of an entry past it
+        if key.len() + MAP_ENTRY_OVERHEAD > state.map_bytes_remaining() {
+            return Err(TokenWhitelistError::StateInvariantViolation);
+        }
+    }
+    if authority != state.init_pubkey {
+        if let Some(delegate) = state.extension.delegate.as_mut() {
+            delegate.use_add(allocation_amount)?;
+        }
+    }
+    state.set_round_allocation(key, round, allocation_amount);
+    if campaign_id != NO_CAMPAIGN {
+        state.set_campaign(key, campaign_id);
+    }
+    Ok(())
+}
+
+/// Extracts the program error from a failed simulation, from the transaction error or,
+/// failing that, from the logs
+pub fn decode_simulation_error(
+    transaction_error: &TransactionError,
+    logs: &[String],
+) -> Option<TokenWhitelistError> {
+    decode_transaction_error(transaction_error)
+        .or_else(|| TokenWhitelistError::from_transaction_logs(logs))
+}
+
+pub(crate) fn decode_transaction_error(transaction_error: &TransactionError) -> Option<TokenWhitelistError> {
+    match transaction_error {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            TokenWhitelistError::from_u32(*code)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::MockRpc;
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_program::instruction::AccountMeta;
+    use solana_sdk::signature::Signer;
+
+    fn decode_hex(fixture: &str) -> Vec<u8> {
+        let hex: Vec<u8> = fixture.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        hex.chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn whitelists(fixture: &str) -> (Pubkey, BTreeMap<Pubkey, TokenWhitelist>) {
+        let whitelist = Pubkey::new_unique();
+        let state = TokenWhitelist::unpack_from_slice(&decode_hex(fixture)).unwrap();
+        (whitelist, BTreeMap::from([(whitelist, state)]))
+    }
+
+    fn add(program_id: Pubkey, authority: Pubkey, whitelist: Pubkey, account: Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            program_id,
+            &TokenWhitelistInstruction::AddToWhitelist {
+                allocation_amount: 100,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 0,
+            }.pack(),
+            vec![
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(whitelist, false),
+                AccountMeta::new_readonly(account, false),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_check_owner() {
+        let opts = PreflightOpts::new(Pubkey::new_unique());
+        let (whitelist, mut whitelists) = whitelists(include_str!("../tests/fixtures/account_small_map.hex"));
+        let owner = Pubkey::new_from_array([7; 32]);
+
+        let ix = add(opts.program_id, owner, whitelist, Pubkey::new_unique());
+        check_instructions(&mut whitelists, &[ix], &opts).unwrap();
+
+        let ix = add(opts.program_id, Pubkey::new_unique(), whitelist, Pubkey::new_unique());
+        let error = check_instructions(&mut whitelists, &[ix], &opts).unwrap_err();
+        assert!(matches!(error, TokenWhitelistError::TokenWhitelistNotOwner));
+    }
+
+    #[test]
+    fn test_check_duplicate() {
+        let mut opts = PreflightOpts::new(Pubkey::new_unique());
+        let (whitelist, mut whitelists) = whitelists(include_str!("../tests/fixtures/account_small_map.hex"));
+        let owner = Pubkey::new_from_array([7; 32]);
+        let existing = Pubkey::new_from_array([1; 32]);
+
+        let ix = add(opts.program_id, owner, whitelist, existing);
+        let error = check_instructions(&mut whitelists, std::slice::from_ref(&ix), &opts).unwrap_err();
+        assert!(matches!(error, TokenWhitelistError::AlreadyRegistered));
+
+        opts.allow_overwrite = true;
+        check_instructions(&mut whitelists, &[ix], &opts).unwrap();
+    }
+
+    #[test]
+    fn test_check_capacity() {
+        let opts = PreflightOpts::new(Pubkey::new_unique());
+        let (whitelist, mut whitelists) = whitelists(include_str!("../tests/fixtures/account_near_capacity.hex"));
+        let owner = Pubkey::new_from_array([7; 32]);
+
+        // wallets with base58 keys of 44 characters, the longest a key takes
+        let wallets: Vec<Pubkey> = (0..=u8::MAX)
+            .map(|index| {
+                let mut bytes = [0xff; 32];
+                bytes[31] = index;
+                Pubkey::new_from_array(bytes)
+            })
+            .collect();
+        let fitting = whitelists[&whitelist].map_bytes_remaining() / (44 + MAP_ENTRY_OVERHEAD);
+        let instructions: Vec<Instruction> = wallets[..=fitting]
+            .iter()
+            .map(|wallet| add(opts.program_id, owner, whitelist, *wallet))
+            .collect();
+        check_instructions(&mut whitelists.clone(), &instructions[..fitting], &opts).unwrap();
+        let error = check_instructions(&mut whitelists.clone(), &instructions, &opts).unwrap_err();
+        assert!(matches!(error, TokenWhitelistError::StateInvariantViolation));
+
+        // like the program, the preflight leaves max_whitelist_size unenforced
+        let state = whitelists.get_mut(&whitelist).unwrap();
+        state.max_whitelist_size = state.whitelist_map.len() as u64;
+        check_instructions(&mut whitelists, &instructions[..1], &opts).unwrap();
+    }
+
+    #[test]
+    fn test_check_attestation_required() {
+        use crate::instruction::{add_to_whitelist_with_attestation, set_attestation};
+
+        let mut opts = PreflightOpts::new(Pubkey::new_unique());
+        opts.allow_overwrite = true;
+        let (whitelist, mut whitelists) = whitelists(include_str!("../tests/fixtures/account_small_map.hex"));
+        whitelists.get_mut(&whitelist).unwrap().extension.require_attestation = true;
+        let (owner, wallet) = (Pubkey::new_from_array([7; 32]), Pubkey::new_unique());
+
+        let ix = add(opts.program_id, owner, whitelist, wallet);
+        let error = check_instructions(&mut whitelists.clone(), std::slice::from_ref(&ix), &opts).unwrap_err();
+        assert!(matches!(error, TokenWhitelistError::AttestationRequired));
+
+        // once attested, the entry takes adds carrying none
+        let attested = add_to_whitelist_with_attestation(&opts.program_id, &owner, &whitelist, &wallet, 100, [3; 32]);
+        check_instructions(&mut whitelists, &[attested, ix], &opts).unwrap();
+        assert_eq!(whitelists[&whitelist].attestation(&wallet.to_string()), [3; 32]);
+
+        let clear = set_attestation(&opts.program_id, &owner, &whitelist, &wallet, NO_ATTESTATION);
+        let error = check_instructions(&mut whitelists, &[clear], &opts).unwrap_err();
+        assert!(matches!(error, TokenWhitelistError::AttestationRequired));
+    }
+
+    #[test]
+    fn test_check_target_identity() {
+        let opts = PreflightOpts::new(Pubkey::new_unique());
+        let (whitelist, mut whitelists) = whitelists(include_str!("../tests/fixtures/account_small_map.hex"));
+        let owner = Pubkey::new_from_array([7; 32]);
+
+        for target in [whitelist, opts.program_id] {
+            let ix = add(opts.program_id, owner, whitelist, target);
+            let error = check_instructions(&mut whitelists.clone(), &[ix], &opts).unwrap_err();
+            assert!(matches!(error, TokenWhitelistError::InvalidWhitelistTarget));
+        }
+
+        let ix = add(opts.program_id, owner, whitelist, owner);
+        check_instructions(&mut whitelists.clone(), std::slice::from_ref(&ix), &opts).unwrap();
+        whitelists.get_mut(&whitelist).unwrap().extension.owner_entry_disabled = true;
+        let error = check_instructions(&mut whitelists, &[ix], &opts).unwrap_err();
+        assert!(matches!(error, TokenWhitelistError::InvalidWhitelistTarget));
+    }
+
+    #[test]
+    fn test_check_ignores_other_programs() {
+        let opts = PreflightOpts::new(Pubkey::new_unique());
+        let (whitelist, mut whitelists) = whitelists(include_str!("../tests/fixtures/account_small_map.hex"));
+
+        let ix = add(Pubkey::new_unique(), Pubkey::new_unique(), whitelist, Pubkey::new_unique());
+        check_instructions(&mut whitelists, &[ix], &opts).unwrap();
+    }
+
+    fn mock_whitelist(opts: &PreflightOpts, owner: &Keypair) -> (MockRpc, Pubkey) {
+        let rpc = MockRpc::new();
+        let whitelist = Pubkey::new_unique();
+        rpc.set_whitelist(whitelist, opts.program_id, &TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: owner.pubkey(),
+            max_whitelist_size: 10,
+            ..TokenWhitelist::default()
+        });
+        (rpc, whitelist)
+    }
+
+    #[test]
+    fn test_send_with_preflight() {
+        let opts = PreflightOpts::new(Pubkey::new_unique());
+        let owner = Keypair::new();
+        let (rpc, whitelist) = mock_whitelist(&opts, &owner);
+        let account = Pubkey::new_unique();
+
+        let ix = add(opts.program_id, owner.pubkey(), whitelist, account);
+        send_with_preflight(&rpc, &owner, &[ix], opts).unwrap();
+        assert_eq!(rpc.simulated().len(), 1);
+        assert_eq!(rpc.whitelist(&whitelist).whitelist_map.get(&account.to_string()), Some(&100));
+
+        // rejected by the local checks, nothing is simulated or sent
+        let ix = add(opts.program_id, Keypair::new().pubkey(), whitelist, Pubkey::new_unique());
+        let error = send_with_preflight(&rpc, &owner, &[ix], opts).unwrap_err();
+        assert!(matches!(error, ClientError::Program(TokenWhitelistError::TokenWhitelistNotOwner)));
+        assert_eq!(rpc.simulated().len(), 1);
+        assert_eq!(rpc.sent().len(), 1);
+    }
+
+    #[test]
+    fn test_send_with_preflight_simulation_error() {
+        let opts = PreflightOpts::new(Pubkey::new_unique());
+        let owner = Keypair::new();
+        let (rpc, whitelist) = mock_whitelist(&opts, &owner);
+        let code = TokenWhitelistError::Overflow as u32;
+        rpc.push_simulation(RpcSimulateTransactionResult {
+            err: Some(TransactionError::InstructionError(0, InstructionError::Custom(code))),
+            logs: None,
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+            inner_instructions: None,
+        });
+
+        let ix = add(opts.program_id, owner.pubkey(), whitelist, Pubkey::new_unique());
+        let error = send_with_preflight(&rpc, &owner, &[ix], opts).unwrap_err();
+        assert!(matches!(error, ClientError::Program(TokenWhitelistError::Overflow)));
+        assert!(rpc.sent().is_empty());
+    }
+
+    #[test]
+    fn test_send_with_auto_compute_budget() {
+        let opts = PreflightOpts {
+            compute_budget: ComputeBudget::Auto,
+            ..PreflightOpts::new(Pubkey::new_unique())
+        };
+        let owner = Keypair::new();
+        let (rpc, whitelist) = mock_whitelist(&opts, &owner);
+        rpc.set_prioritization_fees(vec![9, 1, 5]);
+        rpc.push_simulation(RpcSimulateTransactionResult {
+            err: None,
+            logs: None,
+            accounts: None,
+            units_consumed: Some(20_000),
+            return_data: None,
+            inner_instructions: None,
+        });
+
+        let ix = add(opts.program_id, owner.pubkey(), whitelist, Pubkey::new_unique());
+        send_with_preflight(&rpc, &owner, &[ix], opts).unwrap();
+        let sent = rpc.sent();
+        let instructions = sent[0].message.instructions();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].data, compute_budget_instructions(Some(22_000), None)[0].data);
+        assert_eq!(instructions[1].data, compute_budget_instructions(None, Some(5))[0].data);
+    }
+
+    #[test]
+    fn test_decode_simulation_error() {
+        let code = TokenWhitelistError::InvalidAuthority as u32;
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(code));
+        assert!(matches!(
+            decode_simulation_error(&error, &[]),
+            Some(TokenWhitelistError::InvalidAuthority)
+        ));
+
+        let logs = vec![
+            "Program log: Instruction: AddToWhitelist".to_string(),
+            format!("Program 11111111111111111111111111111111 failed: custom program error: 0x{:x}", code),
+        ];
+        assert!(matches!(
+            decode_simulation_error(&TransactionError::AccountNotFound, &logs),
+            Some(TokenWhitelistError::InvalidAuthority)
+        ));
+        assert!(decode_simulation_error(&TransactionError::AccountNotFound, &[]).is_none());
+    }
+
+    #[test]
+    fn test_client_error_from_rpc() {
+        let code = TokenWhitelistError::TokenWhitelistNotOwner as u32;
+        let error = RpcClientError::from(TransactionError::InstructionError(0, InstructionError::Custom(code)));
+        assert!(matches!(
+            ClientError::from(error),
+            ClientError::Program(TokenWhitelistError::TokenWhitelistNotOwner)
+        ));
+        assert_eq!(
+            ClientError::Program(TokenWhitelistError::TokenWhitelistNotOwner).to_string(),
+            "token whitelist authority error 3: Signer Not Token Whitelist Owner"
+        );
+
+        let error = RpcClientError::from(TransactionError::AccountNotFound);
+        assert!(matches!(ClientError::from(error), ClientError::Rpc(_)));
+    }
+}
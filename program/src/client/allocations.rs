@@ -0,0 +1,115 @@
+//! Allocations of many wallets resolved against a single read of their whitelist, for pages
+//! checking a batch of wallets at once
+
+use solana_program::pubkey::Pubkey;
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    error::TokenWhitelistError,
+    rules,
+    state::{TokenWhitelist, ACTIVE_ROUND},
+};
+
+use std::collections::HashMap;
+
+/// Allocation of each of `wallets` in the active round, None for those not whitelisted. The
+/// whitelist is read once however many wallets are asked for, where a get_allocation per wallet
+/// reads the whole account each time.
+pub fn get_allocations_bulk<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    wallets: &[Pubkey],
+) -> Result<HashMap<Pubkey, Option<u64>>, ClientError> {
+    let account = rpc.get_optional_account(whitelist)?.ok_or(ClientError::InvalidAccount(*whitelist))?;
+    let state = TokenWhitelist::unpack_from_slice(&account.data).map_err(|_| ClientError::InvalidAccount(*whitelist))?;
+    Ok(allocations(&state, wallets)?)
+}
+
+/// Allocation of each of `wallets` in the active round of a whitelist already read, None for
+/// those not whitelisted
+pub fn allocations(
+    state: &TokenWhitelist,
+    wallets: &[Pubkey],
+) -> Result<HashMap<Pubkey, Option<u64>>, TokenWhitelistError> {
+    wallets.iter()
+        .map(|wallet| match rules::allocation(state, wallet, ACTIVE_ROUND) {
+            Ok(allocation) => Ok((*wallet, Some(allocation))),
+            Err(TokenWhitelistError::AccountNotWhitelisted) => Ok((*wallet, None)),
+            Err(error) => Err(error),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{
+        cache::{CacheConfig, WhitelistCache},
+        mock::MockRpc,
+    };
+    use solana_sdk::account::Account;
+
+    use std::time::Duration;
+
+    fn decode_hex(fixture: &str) -> Vec<u8> {
+        let hex: Vec<u8> = fixture.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        hex.chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    /// A mock holding the near capacity fixture, entries [i; 32] at i * 1_000_000 for i in 1..=90
+    fn setup() -> (MockRpc, Pubkey) {
+        let data = decode_hex(include_str!("../../tests/fixtures/account_near_capacity.hex"));
+        let (rpc, whitelist) = (MockRpc::new(), Pubkey::new_unique());
+        rpc.set_account(whitelist, Account {data, owner: Pubkey::new_unique(), ..Account::default()});
+        (rpc, whitelist)
+    }
+
+    #[test]
+    fn test_bulk_matches_fixture() {
+        let (rpc, whitelist) = setup();
+        let listed: Vec<Pubkey> = (1..=90).map(|i| Pubkey::new_from_array([i; 32])).collect();
+        let unlisted: Vec<Pubkey> = (0..110).map(|_| Pubkey::new_unique()).collect();
+        let wallets: Vec<Pubkey> = listed.iter().chain(&unlisted).copied().collect();
+
+        let allocations = get_allocations_bulk(&rpc, &whitelist, &wallets).unwrap();
+        assert_eq!(allocations.len(), 200);
+        for (i, wallet) in (1..).zip(&listed) {
+            assert_eq!(allocations[wallet], Some(i * 1_000_000));
+        }
+        assert!(unlisted.iter().all(|wallet| allocations[wallet].is_none()));
+        assert_eq!(rpc.account_reads(), 1);
+    }
+
+    #[test]
+    fn test_bulk_reads_once() {
+        let (rpc, whitelist) = setup();
+        let wallets: Vec<Pubkey> = (1..=200).map(|i| Pubkey::new_from_array([i; 32])).collect();
+
+        // a lookup per wallet with nothing cached reads the account every time
+        let uncached = WhitelistCache::new(CacheConfig {ttl: Duration::ZERO, max_slot_lag: None});
+        for wallet in &wallets {
+            uncached.get_allocation(&rpc, &whitelist, wallet).unwrap();
+        }
+        assert_eq!(rpc.account_reads(), 200);
+
+        let bulk = get_allocations_bulk(&rpc, &whitelist, &wallets).unwrap();
+        assert_eq!(rpc.account_reads(), 201);
+        for wallet in &wallets {
+            assert_eq!(bulk[wallet], uncached.get_allocation(&rpc, &whitelist, wallet).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_bulk_refuses_missing_whitelist() {
+        let (rpc, whitelist) = (MockRpc::new(), Pubkey::new_unique());
+        assert!(matches!(
+            get_allocations_bulk(&rpc, &whitelist, &[Pubkey::new_unique()]),
+            Err(ClientError::InvalidAccount(missing)) if missing == whitelist
+        ));
+        assert!(matches!(
+            allocations(&TokenWhitelist::default(), &[Pubkey::new_unique()]),
+            Err(TokenWhitelistError::TokenWhitelistNotInit)
+        ));
+    }
+}
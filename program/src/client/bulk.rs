@@ -0,0 +1,344 @@
+//! Sending many adds with confirmation polling, blockhash refresh and safe resends
+
+use solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use crate::{
+    client::{
+        decode_transaction_error,
+        planner::{plan_batches, BatchLimits, PlanWarning},
+        rpc::WhitelistRpc,
+        ClientError,
+    },
+    error::TokenWhitelistError,
+    instruction::add_to_whitelist,
+    state::TokenWhitelist,
+};
+
+use std::{collections::BTreeMap, thread::sleep, time::Duration};
+
+/// Options for [BulkSender](struct.BulkSender.html)
+#[derive(Clone, Copy, Debug)]
+pub struct BulkOpts {
+    pub program_id: Pubkey,
+    pub batch_size: usize, // most adds per transaction, fewer when they would not fit
+    pub max_attempts: u32, // sends of an add, each with a fresh blockhash
+    pub max_polls: u32, // status polls per attempt before resending
+    pub initial_backoff: Duration, // wait before the first poll, doubled after every poll
+    pub max_backoff: Duration,
+}
+
+impl BulkOpts {
+    pub fn new(program_id: Pubkey) -> Self {
+        BulkOpts {
+            program_id,
+            batch_size: 5,
+            max_attempts: 3,
+            max_polls: 10,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+/// Options for the official deployment at crate::id()
+impl Default for BulkOpts {
+    fn default() -> Self {
+        Self::new(crate::id())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BulkOutcome {
+    /// Confirmed in the transaction with this signature
+    Landed(Signature),
+    /// Found on chain with the intended allocation before a resend, nothing was sent again
+    AlreadyApplied,
+    /// Refused by the program, not retried
+    Rejected(TokenWhitelistError),
+    /// Failed for another reason, not retried
+    Failed(TransactionError),
+    /// Left out by the planner, never sent
+    Unplannable(PlanWarning),
+    /// Still missing after the last attempt
+    GaveUp,
+}
+
+/// Outcome of every add, keyed by the wallet being added
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BulkReport {
+    pub outcomes: BTreeMap<Pubkey, BulkOutcome>,
+}
+
+impl BulkReport {
+    /// Wallets whose entry is on chain, sent now or found already applied
+    pub fn applied(&self) -> Vec<Pubkey> {
+        self.outcomes.iter()
+            .filter(|(_, outcome)| matches!(outcome, BulkOutcome::Landed(_) | BulkOutcome::AlreadyApplied))
+            .map(|(wallet, _)| *wallet)
+            .collect()
+    }
+
+    /// Wallets that were rejected, failed or given up on
+    pub fn unapplied(&self) -> Vec<Pubkey> {
+        self.outcomes.iter()
+            .filter(|(_, outcome)| !matches!(outcome, BulkOutcome::Landed(_) | BulkOutcome::AlreadyApplied))
+            .map(|(wallet, _)| *wallet)
+            .collect()
+    }
+}
+
+/// Sends adds to one whitelist, resending unlanded ones with a fresh blockhash
+pub struct BulkSender<'a, R: WhitelistRpc> {
+    rpc: &'a R,
+    owner: &'a Keypair,
+    whitelist: Pubkey,
+    opts: BulkOpts,
+}
+
+impl<'a, R: WhitelistRpc> BulkSender<'a, R> {
+    pub fn new(rpc: &'a R, owner: &'a Keypair, whitelist: Pubkey, opts: BulkOpts) -> Self {
+        BulkSender { rpc, owner, whitelist, opts }
+    }
+
+    /// Sends the adds, the last allocation wins for a repeated wallet. Every attempt fetches the
+    /// whitelist first, settles adds that landed unobserved instead of resending them and plans
+    /// the rest against the capacity left, see [plan_batches](../planner/fn.plan_batches.html).
+    pub fn send_adds(&self, adds: &[(Pubkey, u64)]) -> BulkReport {
+        let mut pending: BTreeMap<Pubkey, u64> = adds.iter().copied().collect();
+        let mut report = BulkReport::default();
+        let limits = BatchLimits {
+            max_adds: self.opts.batch_size,
+            ..BatchLimits::new(self.opts.program_id, self.owner.pubkey(), self.whitelist)
+        };
+
+        for _ in 0..self.opts.max_attempts {
+            // resending unchecked could double-apply, spend the attempt instead
+            let state = match self.fetch_whitelist() {
+                Ok(state) => state,
+                Err(_) => continue,
+            };
+            pending.retain(|wallet, amount| {
+                if state.whitelist_map.get(&wallet.to_string()) == Some(amount) {
+                    report.outcomes.insert(*wallet, BulkOutcome::AlreadyApplied);
+                    return false;
+                }
+                true
+            });
+            if pending.is_empty() {
+                break;
+            }
+            let entries: Vec<(Pubkey, u64)> = pending.iter().map(|(wallet, amount)| (*wallet, *amount)).collect();
+            let plan = match plan_batches(&state, &entries, &limits) {
+                Ok(plan) => plan,
+                Err(_) => continue,
+            };
+            for (wallet, warning) in plan.warnings {
+                settle(&mut pending, &mut report, &[wallet], BulkOutcome::Unplannable(warning));
+            }
+            let blockhash = match self.rpc.get_latest_blockhash() {
+                Ok(blockhash) => blockhash,
+                Err(_) => continue,
+            };
+
+            let mut in_flight = Vec::new();
+            for batch in &plan.batches {
+                let wallets: Vec<Pubkey> = batch.adds.iter().map(|(wallet, _)| *wallet).collect();
+                match self.rpc.send(&self.sign(&batch.adds, blockhash)) {
+                    Ok(signature) => in_flight.push((signature, wallets)),
+                    Err(ClientError::Program(error)) => {
+                        settle(&mut pending, &mut report, &wallets, BulkOutcome::Rejected(error));
+                    }
+                    Err(ClientError::Simulation { transaction_error, .. }) => {
+                        settle(&mut pending, &mut report, &wallets, BulkOutcome::Failed(transaction_error));
+                    }
+                    Err(_) => {} // resent with the next blockhash
+                }
+            }
+            self.confirm(&mut in_flight, blockhash, &mut pending, &mut report);
+        }
+
+        for wallet in pending.keys() {
+            report.outcomes.insert(*wallet, BulkOutcome::GaveUp);
+        }
+        report
+    }
+
+    /// Polls with exponential backoff until every transaction resolved, the blockhash
+    /// expired or the polls ran out
+    fn confirm(
+        &self,
+        in_flight: &mut Vec<(Signature, Vec<Pubkey>)>,
+        blockhash: Hash,
+        pending: &mut BTreeMap<Pubkey, u64>,
+        report: &mut BulkReport,
+    ) {
+        let mut backoff = self.opts.initial_backoff;
+        for _ in 0..self.opts.max_polls {
+            if in_flight.is_empty() {
+                return;
+            }
+            sleep(backoff);
+            backoff = (backoff * 2).min(self.opts.max_backoff);
+
+            // checked before the statuses so a transaction landing at expiry is still seen
+            let expired = !self.rpc.is_blockhash_valid(&blockhash).unwrap_or(true);
+            let signatures: Vec<Signature> = in_flight.iter().map(|(signature, _)| *signature).collect();
+            if let Ok(statuses) = self.rpc.get_signature_statuses(&signatures) {
+                let mut index = 0;
+                in_flight.retain(|(signature, wallets)| {
+                    let status = statuses.get(index).cloned().flatten();
+                    index += 1;
+                    let outcome = match status {
+                        None => return true,
+                        Some(Ok(())) => BulkOutcome::Landed(*signature),
+                        Some(Err(error)) => match decode_transaction_error(&error) {
+                            Some(error) => BulkOutcome::Rejected(error),
+                            None => BulkOutcome::Failed(error),
+                        },
+                    };
+                    settle(pending, report, wallets, outcome);
+                    false
+                });
+            }
+            if expired {
+                return;
+            }
+        }
+    }
+
+    fn sign(&self, batch: &[(Pubkey, u64)], blockhash: Hash) -> VersionedTransaction {
+        let instructions: Vec<Instruction> = batch.iter()
+            .map(|(wallet, amount)| {
+                add_to_whitelist(&self.opts.program_id, &self.owner.pubkey(), &self.whitelist, wallet, *amount)
+            })
+            .collect();
+        Transaction::new_signed_with_payer(&instructions, Some(&self.owner.pubkey()), &[self.owner], blockhash).into()
+    }
+
+    fn fetch_whitelist(&self) -> Result<TokenWhitelist, ClientError> {
+        let data = self.rpc.get_account(&self.whitelist)?.data;
+        TokenWhitelist::unpack_from_slice(&data).map_err(|_| ClientError::InvalidAccount(self.whitelist))
+    }
+}
+
+fn settle(pending: &mut BTreeMap<Pubkey, u64>, report: &mut BulkReport, wallets: &[Pubkey], outcome: BulkOutcome) {
+    for wallet in wallets {
+        pending.remove(wallet);
+        report.outcomes.insert(*wallet, outcome.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockRpc, MockSend};
+    use solana_program::instruction::InstructionError;
+
+    fn setup(sends: &[MockSend]) -> (MockRpc, BulkOpts, Pubkey) {
+        let opts = BulkOpts {
+            batch_size: 2,
+            max_polls: 3,
+            initial_backoff: Duration::ZERO,
+            ..BulkOpts::new(Pubkey::new_unique())
+        };
+        let whitelist = Pubkey::new_unique();
+        let rpc = MockRpc::new();
+        rpc.set_whitelist(whitelist, opts.program_id, &TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 100,
+            ..TokenWhitelist::default()
+        });
+        for send in sends {
+            rpc.push_send(send.clone());
+        }
+        (rpc, opts, whitelist)
+    }
+
+    fn adds(count: u8) -> Vec<(Pubkey, u64)> {
+        (1..=count).map(|i| (Pubkey::new_from_array([i; 32]), 100 * i as u64)).collect()
+    }
+
+    #[test]
+    fn test_all_land() {
+        let (rpc, opts, whitelist) = setup(&[]);
+        let owner = Keypair::new();
+        let report = BulkSender::new(&rpc, &owner, whitelist, opts).send_adds(&adds(3));
+        assert_eq!(report.applied().len(), 3);
+        assert_eq!(rpc.sent().len(), 2);
+        assert_eq!(rpc.whitelist(&whitelist).whitelist_map.len(), 3);
+    }
+
+    #[test]
+    fn test_resend_with_fresh_blockhash() {
+        let (rpc, opts, whitelist) = setup(&[MockSend::Drop, MockSend::Land]);
+        let owner = Keypair::new();
+        let report = BulkSender::new(&rpc, &owner, whitelist, opts).send_adds(&adds(2));
+
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 2);
+        assert_ne!(sent[0].message.recent_blockhash(), sent[1].message.recent_blockhash());
+        assert!(report.outcomes.values().all(|outcome| *outcome == BulkOutcome::Landed(sent[1].signatures[0])));
+    }
+
+    #[test]
+    fn test_no_double_apply() {
+        let (rpc, opts, whitelist) = setup(&[MockSend::LandUnobserved, MockSend::Drop]);
+        let owner = Keypair::new();
+        let entries = adds(4);
+        let report = BulkSender::new(&rpc, &owner, whitelist, opts).send_adds(&entries);
+
+        // the unobserved batch is found on chain, only the dropped one is sent again
+        assert_eq!(rpc.sent().len(), 3);
+        assert_eq!(report.outcomes[&entries[0].0], BulkOutcome::AlreadyApplied);
+        assert_eq!(report.outcomes[&entries[1].0], BulkOutcome::AlreadyApplied);
+        assert!(matches!(report.outcomes[&entries[2].0], BulkOutcome::Landed(_)));
+        assert!(matches!(report.outcomes[&entries[3].0], BulkOutcome::Landed(_)));
+    }
+
+    #[test]
+    fn test_rejected_not_retried() {
+        let code = TokenWhitelistError::TokenWhitelistSizeExceeds as u32;
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(code));
+        let (rpc, opts, whitelist) = setup(&[MockSend::Fail(error)]);
+        let owner = Keypair::new();
+        let entries = adds(3);
+        let report = BulkSender::new(&rpc, &owner, whitelist, opts).send_adds(&entries);
+
+        assert_eq!(rpc.sent().len(), 2);
+        assert!(matches!(
+            report.outcomes[&entries[0].0],
+            BulkOutcome::Rejected(TokenWhitelistError::TokenWhitelistSizeExceeds)
+        ));
+        assert_eq!(report.unapplied(), vec![entries[0].0, entries[1].0]);
+        assert_eq!(report.applied(), vec![entries[2].0]);
+    }
+
+    #[test]
+    fn test_gave_up() {
+        let (rpc, opts, whitelist) = setup(&[MockSend::Unavailable, MockSend::Drop, MockSend::Drop]);
+        let owner = Keypair::new();
+        let entries = adds(1);
+        let report = BulkSender::new(&rpc, &owner, whitelist, opts).send_adds(&entries);
+
+        assert_eq!(rpc.sent().len(), 2);
+        assert_eq!(report.outcomes[&entries[0].0], BulkOutcome::GaveUp);
+    }
+
+    #[test]
+    fn test_past_capacity_not_sent() {
+        let (rpc, opts, whitelist) = setup(&[]);
+        let mut state = rpc.whitelist(&whitelist);
+        state.max_whitelist_size = 2;
+        rpc.set_whitelist(whitelist, opts.program_id, &state);
+        let owner = Keypair::new();
+        let entries = adds(3);
+        let report = BulkSender::new(&rpc, &owner, whitelist, opts).send_adds(&entries);
+
+        assert_eq!(rpc.sent().len(), 1);
+        assert_eq!(report.applied(), vec![entries[0].0, entries[1].0]);
+        assert_eq!(report.outcomes[&entries[2].0], BulkOutcome::Unplannable(PlanWarning::NoCapacity));
+    }
+}
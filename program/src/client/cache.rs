@@ -0,0 +1,314 @@
+//! Cached whitelist reads for servers answering allocation lookups on every request
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    error::TokenWhitelistError,
+    instruction::TokenWhitelistInstruction,
+    rules,
+    state::{TokenWhitelist, ACTIVE_ROUND, NO_CAMPAIGN},
+};
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/// When a cached whitelist is read again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheConfig {
+    /// Age after which a whitelist is read again
+    pub ttl: Duration,
+    /// Slots the node may advance past the slot a whitelist was read at before it is read
+    /// again. Checking costs a getSlot per lookup, None skips it and relies on the TTL alone.
+    pub max_slot_lag: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedWhitelist {
+    state: Arc<TokenWhitelist>,
+    slot: u64,
+    fetched_at: Instant,
+}
+
+/// Decoded whitelists keyed by address. Lookups take the RPC client as an argument, so one
+/// cache can be shared between handlers, and the cache is Send + Sync whatever the client.
+#[derive(Debug)]
+pub struct WhitelistCache {
+    config: CacheConfig,
+    whitelists: RwLock<HashMap<Pubkey, CachedWhitelist>>,
+}
+
+impl WhitelistCache {
+    pub fn new(config: CacheConfig) -> Self {
+        WhitelistCache {config, whitelists: RwLock::new(HashMap::new())}
+    }
+
+    /// The whitelist at `pubkey`, read through `rpc` unless the cached copy is still fresh
+    pub fn get_whitelist<R: WhitelistRpc>(&self, rpc: &R, pubkey: &Pubkey) -> Result<Arc<TokenWhitelist>, ClientError> {
+        if let Some(state) = self.fresh(rpc, pubkey)? {
+            return Ok(state);
+        }
+        let (account, slot) = rpc.get_optional_account_with_slot(pubkey)?;
+        let account = account.ok_or(ClientError::InvalidAccount(*pubkey))?;
+        let state = Arc::new(
+            TokenWhitelist::unpack_from_slice(&account.data).map_err(|_| ClientError::InvalidAccount(*pubkey))?,
+        );
+        let cached = CachedWhitelist {state: state.clone(), slot, fetched_at: Instant::now()};
+        self.whitelists.write().unwrap().insert(*pubkey, cached);
+        Ok(state)
+    }
+
+    /// Allocation of `wallet` in the active round, None while it is not whitelisted
+    pub fn get_allocation<R: WhitelistRpc>(
+        &self,
+        rpc: &R,
+        whitelist: &Pubkey,
+        wallet: &Pubkey,
+    ) -> Result<Option<u64>, ClientError> {
+        let state = self.get_whitelist(rpc, whitelist)?;
+        match rules::allocation(&state, wallet, ACTIVE_ROUND) {
+            Ok(allocation) => Ok(Some(allocation)),
+            Err(TokenWhitelistError::AccountNotWhitelisted) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Drops the cached copy, the next lookup reads the whitelist again
+    pub fn invalidate(&self, pubkey: &Pubkey) {
+        self.whitelists.write().unwrap().remove(pubkey);
+    }
+
+    /// Applies the instructions of a transaction that landed at `slot` to the cached whitelists,
+    /// so the sender sees its own writes without reading them back. Adds, removes and zeroed
+    /// allocations are applied; any other instruction of `program_id` drops the whitelist it
+    /// writes, as does an instruction the cached state refuses.
+    pub fn apply_confirmed(&self, program_id: &Pubkey, instructions: &[Instruction], slot: u64) {
+        let mut whitelists = self.whitelists.write().unwrap();
+        for instruction in instructions.iter().filter(|instruction| instruction.program_id == *program_id) {
+            let whitelist = match instruction.accounts.get(1) {
+                Some(meta) => meta.pubkey,
+                None => continue,
+            };
+            let cached = match whitelists.get_mut(&whitelist) {
+                Some(cached) => cached,
+                None => continue,
+            };
+            let applied = instruction.accounts.get(2).and_then(|wallet| {
+                let state = Arc::make_mut(&mut cached.state);
+                apply(state, &instruction.data, &wallet.pubkey.to_string())
+            });
+            match applied {
+                Some(()) => {
+                    cached.slot = cached.slot.max(slot);
+                    cached.fetched_at = Instant::now();
+                }
+                None => {
+                    whitelists.remove(&whitelist);
+                }
+            }
+        }
+    }
+
+    /// The cached copy while within the TTL and the slot lag
+    fn fresh<R: WhitelistRpc>(&self, rpc: &R, pubkey: &Pubkey) -> Result<Option<Arc<TokenWhitelist>>, ClientError> {
+        let cached = match self.whitelists.read().unwrap().get(pubkey) {
+            Some(cached) if cached.fetched_at.elapsed() < self.config.ttl => cached.clone(),
+            _ => return Ok(None),
+        };
+        if let Some(max_slot_lag) = self.config.max_slot_lag {
+            if rpc.get_slot()?.saturating_sub(cached.slot) > max_slot_lag {
+                return Ok(None);
+            }
+        }
+        Ok(Some(cached.state))
+    }
+}
+
+/// Applies an add, remove or zeroed allocation of `wallet` as the program would, None for any
+/// other instruction or one the state refuses
+fn apply(state: &mut TokenWhitelist, data: &[u8], wallet: &String) -> Option<()> {
+    match TokenWhitelistInstruction::unpack(data).ok()? {
+        TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id, expected_nonce} => {
+            rules::check_mutation_nonce(state, expected_nonce).ok()?;
+            add(state, wallet, allocation_amount, 0, campaign_id)?;
+        }
+        TokenWhitelistInstruction::AddToWhitelistForRound {allocation_amount, round, campaign_id, expected_nonce} => {
+            rules::check_mutation_nonce(state, expected_nonce).ok()?;
+            add(state, wallet, allocation_amount, round, campaign_id)?;
+        }
+        TokenWhitelistInstruction::RemoveFromWhitelist {expected_nonce, ..} => {
+            rules::check_mutation_nonce(state, expected_nonce).ok()?;
+            // the program writes nothing for a wallet that is not whitelisted
+            if state.drop_key(wallet).is_none() {
+                return Some(());
+            }
+        }
+        TokenWhitelistInstruction::SetAllocationToZero {..} => {
+            state.add_keypair(wallet, &0);
+        }
+        _ => return None,
+    }
+    // a nonce still 0 is one the account does not track
+    if state.extension.mutation_nonce != 0 {
+        state.extension.mutation_nonce += 1;
+    }
+    Some(())
+}
+
+fn add(state: &mut TokenWhitelist, wallet: &str, allocation_amount: u64, round: u8, campaign_id: [u8; 8]) -> Option<()> {
+    let round = state.resolve_round(round).ok()?;
+    state.set_round_allocation(wallet, round, allocation_amount);
+    if campaign_id != NO_CAMPAIGN {
+        state.set_campaign(wallet, campaign_id);
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::mock::MockRpc,
+        instruction::{add_to_whitelist, remove_from_whitelist},
+    };
+    use solana_program::instruction::AccountMeta;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn cache(ttl: Duration, max_slot_lag: Option<u64>) -> WhitelistCache {
+        WhitelistCache::new(CacheConfig {ttl, max_slot_lag})
+    }
+
+    /// A mock holding one whitelist with `wallet` at 500
+    fn setup(wallet: &Pubkey) -> (MockRpc, Pubkey, Pubkey, Pubkey) {
+        let (program_id, owner, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut state = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: owner,
+            max_whitelist_size: 10,
+            ..TokenWhitelist::default()
+        };
+        state.add_keypair(&wallet.to_string(), &500);
+        let rpc = MockRpc::new();
+        rpc.set_whitelist(whitelist, program_id, &state);
+        (rpc, program_id, owner, whitelist)
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let wallet = Pubkey::new_unique();
+        let (rpc, program_id, _, whitelist) = setup(&wallet);
+        let cache = cache(TTL, None);
+
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &wallet).unwrap(), Some(500));
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &Pubkey::new_unique()).unwrap(), None);
+        assert_eq!(rpc.account_reads(), 1);
+
+        // a change made elsewhere stays unseen until invalidated
+        let mut state = rpc.whitelist(&whitelist);
+        state.add_keypair(&wallet.to_string(), &900);
+        rpc.set_whitelist(whitelist, program_id, &state);
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &wallet).unwrap(), Some(500));
+        cache.invalidate(&whitelist);
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &wallet).unwrap(), Some(900));
+        assert_eq!(rpc.account_reads(), 2);
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let wallet = Pubkey::new_unique();
+        let (rpc, _, _, whitelist) = setup(&wallet);
+        let cache = cache(Duration::from_millis(10), None);
+
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        assert_eq!(rpc.account_reads(), 1);
+        std::thread::sleep(Duration::from_millis(20));
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        assert_eq!(rpc.account_reads(), 2);
+    }
+
+    #[test]
+    fn test_slot_lag() {
+        let wallet = Pubkey::new_unique();
+        let (rpc, _, _, whitelist) = setup(&wallet);
+        let cache = cache(TTL, Some(10));
+
+        rpc.set_slot(100);
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        rpc.set_slot(110);
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        assert_eq!(rpc.account_reads(), 1);
+        rpc.set_slot(111);
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        assert_eq!(rpc.account_reads(), 2);
+        // read again at 111, fresh until 121
+        rpc.set_slot(121);
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        assert_eq!(rpc.account_reads(), 2);
+    }
+
+    #[test]
+    fn test_missing_whitelist_not_cached() {
+        let rpc = MockRpc::new();
+        let (cache, whitelist) = (cache(TTL, None), Pubkey::new_unique());
+        assert!(matches!(
+            cache.get_whitelist(&rpc, &whitelist),
+            Err(ClientError::InvalidAccount(missing)) if missing == whitelist
+        ));
+        assert!(cache.get_whitelist(&rpc, &whitelist).is_err());
+        assert_eq!(rpc.account_reads(), 2);
+    }
+
+    #[test]
+    fn test_apply_confirmed() {
+        let wallet = Pubkey::new_unique();
+        let (rpc, program_id, owner, whitelist) = setup(&wallet);
+        let cache = cache(TTL, None);
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+
+        let added = Pubkey::new_unique();
+        cache.apply_confirmed(
+            &program_id,
+            &[
+                add_to_whitelist(&program_id, &owner, &whitelist, &added, 700),
+                Instruction::new_with_bytes(
+                    program_id,
+                    &TokenWhitelistInstruction::SetAllocationToZero {memo: None}.pack(),
+                    vec![
+                        AccountMeta::new_readonly(wallet, true),
+                        AccountMeta::new(whitelist, false),
+                        AccountMeta::new_readonly(wallet, false),
+                    ],
+                ),
+                // another program's instruction with the same accounts is ignored
+                remove_from_whitelist(&Pubkey::new_unique(), &owner, &whitelist, &added),
+            ],
+            5,
+        );
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &added).unwrap(), Some(700));
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &wallet).unwrap(), Some(0));
+
+        cache.apply_confirmed(&program_id, &[remove_from_whitelist(&program_id, &owner, &whitelist, &added)], 6);
+        assert_eq!(cache.get_allocation(&rpc, &whitelist, &added).unwrap(), None);
+        assert_eq!(rpc.account_reads(), 1);
+
+        // an instruction the cache does not mirror drops the whitelist
+        let consume = Instruction::new_with_bytes(
+            program_id,
+            &TokenWhitelistInstruction::ConsumeAllocation {amount: 1}.pack(),
+            vec![AccountMeta::new_readonly(wallet, true), AccountMeta::new(whitelist, false)],
+        );
+        cache.apply_confirmed(&program_id, &[consume], 7);
+        cache.get_whitelist(&rpc, &whitelist).unwrap();
+        assert_eq!(rpc.account_reads(), 2);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<WhitelistCache>();
+    }
+}
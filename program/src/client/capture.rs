@@ -0,0 +1,136 @@
+//! Recording landed whitelist instructions as replay fixtures
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{account::Account, signature::Signature};
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    replay::{ReplayAccount, ReplayFixture},
+};
+
+/// Records the instruction of `program_id` in a landed transaction as a replay case. RPC nodes
+/// keep no account history, so `pre` holds the accounts the instruction writes as fetched before
+/// the transaction was sent, any other account is fetched now and taken as unchanged. The case
+/// is replayed before being returned, which catches a stale `pre` or a later write.
+pub fn capture_fixture<R: WhitelistRpc>(
+    rpc: &R,
+    signature: &Signature,
+    program_id: &Pubkey,
+    pre: &[(Pubkey, Account)],
+) -> Result<ReplayFixture, ClientError> {
+    let transaction = rpc.get_transaction(signature)?;
+    let message = &transaction.message;
+    let keys = message.static_account_keys();
+    let mut instructions = message.instructions().iter()
+        .filter(|instruction| keys.get(instruction.program_id_index as usize) == Some(program_id));
+    let instruction = match (instructions.next(), instructions.next()) {
+        (Some(instruction), None) => instruction,
+        // later instructions would see the accounts the first one left behind
+        _ => return Err(ClientError::InvalidTransaction(*signature)),
+    };
+
+    let mut accounts = Vec::new();
+    for &index in &instruction.accounts {
+        let index = index as usize;
+        // accounts loaded from lookup tables are not resolved
+        let pubkey = *keys.get(index).ok_or(ClientError::InvalidTransaction(*signature))?;
+        let post = rpc.get_account(&pubkey)?;
+        let before = pre.iter()
+            .find(|(key, _)| *key == pubkey)
+            .map_or(&post, |(_, account)| account);
+        accounts.push(ReplayAccount {
+            pubkey,
+            is_signer: message.is_signer(index),
+            is_writable: message.is_maybe_writable(index),
+            owner: before.owner,
+            pre_lamports: before.lamports,
+            pre_data: before.data.clone(),
+            post_lamports: post.lamports,
+            post_data: post.data,
+        });
+    }
+
+    let fixture = ReplayFixture {
+        program_id: *program_id,
+        instruction: instruction.data.clone(),
+        accounts,
+    };
+    let (result, after) = fixture.replay();
+    let mismatch = match result {
+        Ok(()) => fixture.mismatch(&after),
+        Err(error) => Some((0, format!("fails with {}", error))),
+    };
+    match mismatch {
+        Some((index, mismatch)) => Err(ClientError::CaptureMismatch {signature: *signature, index, mismatch}),
+        None => Ok(fixture),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::mock::MockRpc, instruction::add_to_whitelist, state::TokenWhitelist};
+    use solana_program::hash::Hash;
+    use solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+
+    fn setup() -> (MockRpc, Pubkey, Keypair, Pubkey) {
+        let program_id = Pubkey::new_unique();
+        let owner = Keypair::new();
+        let whitelist = Pubkey::new_unique();
+        let rpc = MockRpc::new();
+        rpc.set_whitelist(whitelist, program_id, &TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: owner.pubkey(),
+            max_whitelist_size: 10,
+            ..TokenWhitelist::default()
+        });
+        // a whitelist without lamports would count as closed and its data go unchecked
+        let mut account = rpc.get_account(&whitelist).unwrap();
+        account.lamports = 1_000_000;
+        rpc.set_account(whitelist, account);
+        rpc.set_account(owner.pubkey(), Account {lamports: 1_000_000, ..Account::default()});
+        (rpc, program_id, owner, whitelist)
+    }
+
+    fn send_add(rpc: &MockRpc, program_id: &Pubkey, owner: &Keypair, whitelist: &Pubkey, wallet: &Pubkey) -> Signature {
+        let add = add_to_whitelist(program_id, &owner.pubkey(), whitelist, wallet, 500);
+        let transaction = Transaction::new_signed_with_payer(&[add], Some(&owner.pubkey()), &[owner], Hash::new_unique());
+        rpc.send(&transaction.into()).unwrap()
+    }
+
+    #[test]
+    fn test_capture_replays() {
+        let (rpc, program_id, owner, whitelist) = setup();
+        let wallet = Pubkey::new_unique();
+        rpc.set_account(wallet, Account::default());
+        let pre = vec![(whitelist, rpc.get_account(&whitelist).unwrap())];
+        let signature = send_add(&rpc, &program_id, &owner, &whitelist, &wallet);
+
+        let fixture = capture_fixture(&rpc, &signature, &program_id, &pre).unwrap();
+        assert_eq!(fixture.accounts.len(), 3);
+        assert_eq!(fixture.accounts[1].pre_data, pre[0].1.data);
+        assert_eq!(fixture.accounts[1].post_data, rpc.get_account(&whitelist).unwrap().data);
+        assert_eq!(ReplayFixture::parse(&fixture.to_text()).unwrap(), fixture);
+    }
+
+    #[test]
+    fn test_capture_after_later_write() {
+        let (rpc, program_id, owner, whitelist) = setup();
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(first, Account::default());
+        let pre = vec![(whitelist, rpc.get_account(&whitelist).unwrap())];
+        let signature = send_add(&rpc, &program_id, &owner, &whitelist, &first);
+        send_add(&rpc, &program_id, &owner, &whitelist, &second);
+
+        assert!(matches!(
+            capture_fixture(&rpc, &signature, &program_id, &pre),
+            Err(ClientError::CaptureMismatch {index: 1, ..})
+        ));
+        assert!(matches!(
+            capture_fixture(&rpc, &signature, &Pubkey::new_unique(), &pre),
+            Err(ClientError::InvalidTransaction(_))
+        ));
+    }
+}
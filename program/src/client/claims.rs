@@ -0,0 +1,71 @@
+//! Claimed status of merkle whitelist leaves, read from their claim bitmap
+
+use solana_program::pubkey::Pubkey;
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    error::TokenWhitelistError,
+    state::ClaimBitmap,
+};
+
+/// Whether the leaf at `leaf_index` was claimed. Indices past the leaf count of the bitmap are
+/// refused as an invalid instruction, as a claim of them would be.
+pub fn is_claimed<R: WhitelistRpc>(rpc: &R, bitmap: &Pubkey, leaf_index: u32) -> Result<bool, ClientError> {
+    let data = rpc.get_account(bitmap)?.data;
+    let claim_bitmap = ClaimBitmap::unpack_from_slice(&data).map_err(|_| ClientError::InvalidAccount(*bitmap))?;
+    if leaf_index >= claim_bitmap.leaf_count {
+        return Err(TokenWhitelistError::InvalidInstruction.into());
+    }
+    ClaimBitmap::is_claimed(&data, leaf_index).map_err(|_| ClientError::InvalidAccount(*bitmap))
+}
+
+/// Indices of every claimed leaf, in order
+pub fn claimed_leaves<R: WhitelistRpc>(rpc: &R, bitmap: &Pubkey) -> Result<Vec<u32>, ClientError> {
+    let data = rpc.get_account(bitmap)?.data;
+    let claim_bitmap = ClaimBitmap::unpack_from_slice(&data).map_err(|_| ClientError::InvalidAccount(*bitmap))?;
+    Ok((0..claim_bitmap.leaf_count)
+        .filter(|&leaf_index| ClaimBitmap::is_claimed(&data, leaf_index).unwrap_or(false))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::mock::MockRpc, state::claim_bitmap_len};
+    use solana_sdk::account::Account;
+
+    fn bitmap_account(rpc: &MockRpc, leaf_count: u32, claimed: &[u32]) -> Pubkey {
+        let bitmap = Pubkey::new_unique();
+        let mut data = vec![0; claim_bitmap_len(leaf_count)];
+        ClaimBitmap {whitelist: Pubkey::new_unique(), root: [1; 32], leaf_count}.pack_into_slice(&mut data).unwrap();
+        for &leaf_index in claimed {
+            ClaimBitmap::set_claimed(&mut data, leaf_index).unwrap();
+        }
+        rpc.set_account(bitmap, Account {data, ..Account::default()});
+        bitmap
+    }
+
+    #[test]
+    fn test_is_claimed() {
+        let rpc = MockRpc::new();
+        let bitmap = bitmap_account(&rpc, 4096, &[7, 8, 4095]);
+        for leaf_index in [7, 8, 4095] {
+            assert!(is_claimed(&rpc, &bitmap, leaf_index).unwrap());
+        }
+        for leaf_index in [0, 6, 9, 4094] {
+            assert!(!is_claimed(&rpc, &bitmap, leaf_index).unwrap());
+        }
+        assert!(matches!(
+            is_claimed(&rpc, &bitmap, 4096),
+            Err(ClientError::Program(TokenWhitelistError::InvalidInstruction))
+        ));
+        assert_eq!(claimed_leaves(&rpc, &bitmap).unwrap(), vec![7, 8, 4095]);
+    }
+
+    #[test]
+    fn test_is_claimed_needs_bitmap() {
+        let rpc = MockRpc::new();
+        let whitelist = Pubkey::new_unique();
+        rpc.set_whitelist(whitelist, Pubkey::new_unique(), &Default::default());
+        assert!(matches!(is_claimed(&rpc, &whitelist, 0), Err(ClientError::InvalidAccount(key)) if key == whitelist));
+    }
+}
@@ -0,0 +1,292 @@
+//! Close-out report of a sale: the allocation of every wallet against the tokens actually
+//! transferred to it, for reconciling once the sale is over
+
+use solana_program::pubkey::Pubkey;
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    error::TokenWhitelistError,
+    rules,
+    state::{validate_key, TokenWhitelist, ACTIVE_ROUND},
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// How the tokens a wallet received compare with its allocation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseoutStatus {
+    Matched,
+    OverDelivered, // more transferred than the allocation
+    UnderDelivered, // less transferred than the allocation, down to nothing
+    NotWhitelisted, // transfers to a wallet outside the whitelist
+}
+
+impl fmt::Display for CloseoutStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CloseoutStatus::Matched => "matched",
+            CloseoutStatus::OverDelivered => "over_delivered",
+            CloseoutStatus::UnderDelivered => "under_delivered",
+            CloseoutStatus::NotWhitelisted => "not_whitelisted",
+        })
+    }
+}
+
+/// One wallet of a [CloseoutReport](struct.CloseoutReport.html)
+#[derive(Clone, Debug, PartialEq)]
+pub struct CloseoutRow {
+    pub wallet: Pubkey,
+    pub allocation: Option<u64>, // in the active round, None outside the whitelist
+    pub transferred: u128, // sum of the transfers to the wallet
+    pub transfers: u32, // transfer rows naming the wallet
+    pub duplicate_transfers: u32, // rows repeating the wallet and amount of an earlier one, still summed
+    pub status: CloseoutStatus,
+}
+
+/// Allocations joined against the token transfers of a sale, see
+/// [generate_closeout_report](fn.generate_closeout_report.html)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CloseoutReport {
+    pub rows: Vec<CloseoutRow>, // every whitelisted wallet and every wallet transferred to, in pubkey order
+    pub skipped: Vec<String>, // entries whose key is not a pubkey, nothing can be transferred to them
+    pub total_allocation: u128,
+    pub total_transferred: u128,
+}
+
+impl CloseoutReport {
+    /// Rows whose transfers do not match their allocation
+    pub fn discrepancies(&self) -> impl Iterator<Item = &CloseoutRow> {
+        self.rows.iter().filter(|row| row.status != CloseoutStatus::Matched)
+    }
+
+    /// One line per row after a header, the allocation left empty outside the whitelist
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("wallet,allocation,transferred,transfers,duplicate_transfers,status\n");
+        for row in &self.rows {
+            let allocation = row.allocation.map_or(String::new(), |allocation| allocation.to_string());
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                row.wallet, allocation, row.transferred, row.transfers, row.duplicate_transfers, row.status
+            ));
+        }
+        csv
+    }
+
+    /// The report as one JSON object, amounts as strings so that no reader rounds them
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self.rows
+            .iter()
+            .map(|row| {
+                let allocation = row.allocation.map_or("null".to_string(), |allocation| format!(r#""{}""#, allocation));
+                format!(
+                    r#"{{"wallet":"{}","allocation":{},"transferred":"{}","transfers":{},"duplicate_transfers":{},"status":"{}"}}"#,
+                    row.wallet, allocation, row.transferred, row.transfers, row.duplicate_transfers, row.status
+                )
+            })
+            .collect();
+        let skipped: Vec<String> = self.skipped.iter().map(|key| format!(r#""{}""#, escape(key))).collect();
+        format!(
+            r#"{{"total_allocation":"{}","total_transferred":"{}","rows":[{}],"skipped":[{}]}}"#,
+            self.total_allocation,
+            self.total_transferred,
+            rows.join(","),
+            skipped.join(",")
+        )
+    }
+}
+
+/// Reads the whitelist and joins its allocations in the active round against `token_transfers`,
+/// the (wallet, amount) of every transfer of the sale. The whitelist keeps no consumed amount,
+/// ConsumeAllocation draws the allocation down, so a whitelist consumed from reports what is left.
+pub fn generate_closeout_report<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    token_transfers: &[(Pubkey, u64)],
+) -> Result<CloseoutReport, ClientError> {
+    let account = rpc.get_optional_account(whitelist)?.ok_or(ClientError::InvalidAccount(*whitelist))?;
+    let state = TokenWhitelist::unpack_from_slice(&account.data).map_err(|_| ClientError::InvalidAccount(*whitelist))?;
+    Ok(closeout_report(&state, token_transfers)?)
+}
+
+/// Joins the allocations of a whitelist already read against `token_transfers`
+pub fn closeout_report(
+    state: &TokenWhitelist,
+    token_transfers: &[(Pubkey, u64)],
+) -> Result<CloseoutReport, TokenWhitelistError> {
+    rules::check_initialized(state)?;
+    let round = state.resolve_round(ACTIVE_ROUND)?;
+
+    let mut report = CloseoutReport::default();
+    let mut allocations = BTreeMap::new();
+    for key in state.whitelist_map.keys() {
+        match validate_key(key) {
+            Ok(wallet) => {
+                let allocation = state.round_allocation(key, round).unwrap_or(0);
+                report.total_allocation += allocation as u128;
+                allocations.insert(wallet, allocation);
+            }
+            Err(_) => report.skipped.push(key.clone()),
+        }
+    }
+
+    // wallet -> (transferred, transfers, duplicate transfers)
+    let mut received: BTreeMap<Pubkey, (u128, u32, u32)> = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    for &(wallet, amount) in token_transfers {
+        let totals = received.entry(wallet).or_default();
+        totals.0 += amount as u128;
+        totals.1 += 1;
+        if !seen.insert((wallet, amount)) {
+            totals.2 += 1;
+        }
+        report.total_transferred += amount as u128;
+    }
+
+    let wallets: BTreeSet<Pubkey> = allocations.keys().chain(received.keys()).copied().collect();
+    for wallet in wallets {
+        let allocation = allocations.get(&wallet).copied();
+        let (transferred, transfers, duplicate_transfers) = received.get(&wallet).copied().unwrap_or_default();
+        let status = match allocation {
+            None => CloseoutStatus::NotWhitelisted,
+            Some(allocation) if transferred > allocation as u128 => CloseoutStatus::OverDelivered,
+            Some(allocation) if transferred < allocation as u128 => CloseoutStatus::UnderDelivered,
+            Some(_) => CloseoutStatus::Matched,
+        };
+        report.rows.push(CloseoutRow {wallet, allocation, transferred, transfers, duplicate_transfers, status});
+    }
+    Ok(report)
+}
+
+/// Escapes a string for a JSON string literal
+fn escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockRpc;
+
+    fn wallet(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    /// Wallets [1; 32] to [4; 32] granted 100 to 400
+    fn whitelist() -> TokenWhitelist {
+        let mut state = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: wallet(7),
+            max_whitelist_size: 10,
+            ..TokenWhitelist::default()
+        };
+        for byte in 1..=4 {
+            state.add_keypair(&wallet(byte).to_string(), &(byte as u64 * 100));
+        }
+        state
+    }
+
+    fn row(report: &CloseoutReport, wallet: Pubkey) -> &CloseoutRow {
+        report.rows.iter().find(|row| row.wallet == wallet).unwrap()
+    }
+
+    #[test]
+    fn test_closeout_flags_discrepancies() {
+        let transfers = [(wallet(1), 100), (wallet(2), 150), (wallet(2), 100), (wallet(3), 50), (wallet(9), 25)];
+        let report = closeout_report(&whitelist(), &transfers).unwrap();
+
+        assert_eq!(report.rows.len(), 5);
+        assert_eq!(row(&report, wallet(1)).status, CloseoutStatus::Matched);
+        assert_eq!(row(&report, wallet(2)).status, CloseoutStatus::OverDelivered);
+        assert_eq!(row(&report, wallet(2)).transferred, 250);
+        assert_eq!(row(&report, wallet(3)).status, CloseoutStatus::UnderDelivered);
+        // a wallet never paid is under delivered too
+        assert_eq!(
+            *row(&report, wallet(4)),
+            CloseoutRow {
+                wallet: wallet(4),
+                allocation: Some(400),
+                transferred: 0,
+                transfers: 0,
+                duplicate_transfers: 0,
+                status: CloseoutStatus::UnderDelivered,
+            }
+        );
+        assert_eq!(row(&report, wallet(9)).allocation, None);
+        assert_eq!(row(&report, wallet(9)).status, CloseoutStatus::NotWhitelisted);
+        assert_eq!(report.discrepancies().count(), 4);
+        assert_eq!((report.total_allocation, report.total_transferred), (1_000, 425));
+    }
+
+    #[test]
+    fn test_closeout_counts_duplicate_rows() {
+        // the same row twice still sums, flagged for a look at the transfer export
+        let transfers = [(wallet(1), 50), (wallet(1), 50), (wallet(1), 50), (wallet(2), 200)];
+        let report = closeout_report(&whitelist(), &transfers).unwrap();
+        let first = row(&report, wallet(1));
+        assert_eq!((first.transferred, first.transfers, first.duplicate_transfers), (150, 3, 2));
+        assert_eq!(first.status, CloseoutStatus::OverDelivered);
+        assert_eq!(row(&report, wallet(2)).duplicate_transfers, 0);
+        assert_eq!(row(&report, wallet(2)).status, CloseoutStatus::Matched);
+    }
+
+    #[test]
+    fn test_closeout_edge_cases() {
+        // nothing transferred, an overflowing total and a key that is not a pubkey
+        let mut state = whitelist();
+        state.add_keypair(&"not a pubkey".to_string(), &5);
+        let report = closeout_report(&state, &[]).unwrap();
+        assert_eq!(report.skipped, vec!["not a pubkey".to_string()]);
+        assert!(report.rows.iter().all(|row| row.status == CloseoutStatus::UnderDelivered));
+
+        let transfers = [(wallet(9), u64::MAX), (wallet(9), u64::MAX)];
+        let report = closeout_report(&whitelist(), &transfers).unwrap();
+        assert_eq!(row(&report, wallet(9)).transferred, 2 * u64::MAX as u128);
+        assert_eq!(row(&report, wallet(9)).duplicate_transfers, 1);
+
+        assert_eq!(closeout_report(&TokenWhitelist::default(), &[]), Err(TokenWhitelistError::TokenWhitelistNotInit));
+    }
+
+    #[test]
+    fn test_closeout_serializers() {
+        let report = closeout_report(&whitelist(), &[(wallet(1), 100), (wallet(9), 5)]).unwrap();
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 5);
+        assert_eq!(lines[0], "wallet,allocation,transferred,transfers,duplicate_transfers,status");
+        assert!(lines.contains(&format!("{},100,100,1,0,matched", wallet(1)).as_str()));
+        assert!(lines.contains(&format!("{},,5,1,0,not_whitelisted", wallet(9)).as_str()));
+
+        let json = report.to_json();
+        assert!(json.starts_with(r#"{"total_allocation":"1000","total_transferred":"105","rows":[{"#));
+        assert!(json.contains(&format!(
+            r#"{{"wallet":"{}","allocation":null,"transferred":"5","transfers":1,"duplicate_transfers":0,"status":"not_whitelisted"}}"#,
+            wallet(9)
+        )));
+        assert!(json.ends_with(r#"],"skipped":[]}"#));
+    }
+
+    #[test]
+    fn test_generate_closeout_report_reads_whitelist() {
+        let (rpc, whitelist_pubkey) = (MockRpc::new(), Pubkey::new_unique());
+        rpc.set_whitelist(whitelist_pubkey, Pubkey::new_unique(), &whitelist());
+        let transfers = [(wallet(1), 100)];
+        let report = generate_closeout_report(&rpc, &whitelist_pubkey, &transfers).unwrap();
+        assert_eq!(report, closeout_report(&whitelist(), &transfers).unwrap());
+        assert_eq!(rpc.account_reads(), 1);
+
+        assert!(matches!(
+            generate_closeout_report(&rpc, &Pubkey::new_unique(), &transfers),
+            Err(ClientError::InvalidAccount(_))
+        ));
+    }
+}
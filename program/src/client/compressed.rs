@@ -0,0 +1,316 @@
+//! Proofs for compressed whitelists, built from the entries an indexer kept of their trees
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction};
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    compression::{self, TreeMirror},
+    error::TokenWhitelistError,
+    event::WhitelistEvent,
+    instruction::{init_compressed_whitelist, replace_compressed_entry, verify_compressed_membership, CompressedLeaf},
+    state::{CompressedTree, TokenWhitelist},
+};
+
+/// Source of the entries of a compressed tree in leaf order, each with its current allocation.
+/// The tree only keeps hashes, the entries are known from the events the program logs on every
+/// append and replacement, see [CompressedIndex](struct.CompressedIndex.html).
+pub trait LeafIndexer {
+    fn compressed_entries(&self, tree: &Pubkey) -> Result<Vec<(Pubkey, u64)>, ClientError>;
+}
+
+/// Entries of the compressed tree of one whitelist, rebuilt from its events
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompressedIndex {
+    pub whitelist: Pubkey,
+    pub tree: Pubkey,
+    pub entries: Vec<(Pubkey, u64)>, // wallet and allocation of each leaf, in leaf order
+}
+
+impl CompressedIndex {
+    pub fn new(whitelist: Pubkey, tree: Pubkey) -> Self {
+        CompressedIndex {whitelist, tree, entries: Vec::new()}
+    }
+
+    /// Applies an event, in the order the events were logged. Events of other whitelists or
+    /// kinds are skipped. False for an event that does not follow from the entries, one was
+    /// missed and the index has to be rebuilt.
+    pub fn apply(&mut self, event: &WhitelistEvent) -> bool {
+        match *event {
+            WhitelistEvent::CompressedEntryAppended {whitelist, wallet, leaf_index, allocation}
+                if whitelist == self.whitelist =>
+            {
+                if leaf_index as usize != self.entries.len() {
+                    return false;
+                }
+                self.entries.push((wallet, allocation));
+            }
+            WhitelistEvent::CompressedEntryReplaced {whitelist, wallet, leaf_index, previous_allocation, allocation}
+                if whitelist == self.whitelist =>
+            {
+                match self.entries.get_mut(leaf_index as usize) {
+                    Some(entry) if *entry == (wallet, previous_allocation) => entry.1 = allocation,
+                    _ => return false,
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+impl LeafIndexer for CompressedIndex {
+    fn compressed_entries(&self, tree: &Pubkey) -> Result<Vec<(Pubkey, u64)>, ClientError> {
+        if *tree != self.tree {
+            return Err(ClientError::InvalidAccount(*tree));
+        }
+        Ok(self.entries.clone())
+    }
+}
+
+/// Instructions creating `tree`, funded by `payer` and signed by the tree keypair, and making it
+/// the compressed tree of `whitelist`
+pub fn init_compressed_whitelist_instructions(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    whitelist_owner: &Pubkey,
+    whitelist: &Pubkey,
+    tree: &Pubkey,
+    rent: &Rent,
+) -> Vec<Instruction> {
+    let space = compression::tree_space();
+    vec![
+        system_instruction::create_account(
+            payer,
+            tree,
+            rent.minimum_balance(space),
+            space as u64,
+            &compression::COMPRESSION_PROGRAM_ID,
+        ),
+        init_compressed_whitelist(program_id, whitelist_owner, whitelist, tree),
+    ]
+}
+
+/// Leaf of `wallet` in the compressed tree of `whitelist`, with its proof to the current root
+/// of the tree. The entries of `indexer` are checked against the chain first: their count
+/// against the leaf count of the whitelist, their root against the one of the tree account. A
+/// wallet appended more than once is proven by its latest leaf.
+pub fn compressed_leaf<R: WhitelistRpc, I: LeafIndexer>(
+    rpc: &R,
+    indexer: &I,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+) -> Result<(CompressedTree, CompressedLeaf), ClientError> {
+    let state = TokenWhitelist::unpack_from_slice(&rpc.get_account(whitelist)?.data)
+        .map_err(|_| ClientError::InvalidAccount(*whitelist))?;
+    let compressed_tree = state.extension.compressed_tree.ok_or(TokenWhitelistError::CompressedTreeMismatch)?;
+    let entries = indexer.compressed_entries(&compressed_tree.tree)?;
+    let root = compression::current_root(&rpc.get_account(&compressed_tree.tree)?.data)
+        .ok_or(ClientError::InvalidAccount(compressed_tree.tree))?;
+
+    let mirror = TreeMirror::new(&entries);
+    if entries.len() != compressed_tree.leaf_count as usize || mirror.root() != root {
+        return Err(ClientError::StaleIndexer(compressed_tree.tree));
+    }
+    let leaf_index = entries.iter()
+        .rposition(|(key, _)| key == wallet)
+        .ok_or(TokenWhitelistError::AccountNotWhitelisted)?;
+    let leaf = CompressedLeaf {
+        leaf_index: leaf_index as u32,
+        allocation: entries[leaf_index].1,
+        root,
+        proof: mirror.proof(leaf_index).unwrap(),
+    };
+    Ok((compressed_tree, leaf))
+}
+
+/// Creates a VerifyCompressedMembership instruction for `wallet`, see
+/// [compressed_leaf](fn.compressed_leaf.html)
+pub fn verify_compressed_membership_instruction<R: WhitelistRpc, I: LeafIndexer>(
+    rpc: &R,
+    indexer: &I,
+    program_id: &Pubkey,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+) -> Result<Instruction, ClientError> {
+    let (compressed_tree, leaf) = compressed_leaf(rpc, indexer, whitelist, wallet)?;
+    Ok(verify_compressed_membership(program_id, whitelist, &compressed_tree.tree, wallet, &leaf))
+}
+
+/// Creates a ReplaceCompressedEntry instruction giving `wallet` the allocation
+/// `allocation_amount`, see [compressed_leaf](fn.compressed_leaf.html)
+pub fn replace_compressed_entry_instruction<R: WhitelistRpc, I: LeafIndexer>(
+    rpc: &R,
+    indexer: &I,
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+    allocation_amount: u64,
+) -> Result<Instruction, ClientError> {
+    let (compressed_tree, leaf) = compressed_leaf(rpc, indexer, whitelist, wallet)?;
+    Ok(replace_compressed_entry(
+        program_id,
+        whitelist_owner,
+        whitelist,
+        &compressed_tree.tree,
+        wallet,
+        &leaf,
+        allocation_amount,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::mock::MockRpc, instruction::TokenWhitelistInstruction};
+    use solana_sdk::account::Account;
+
+    // a tree at active index 0 keeps its root right after the header and the three counters
+    const ROOT_OFFSET: usize = 56 + 24;
+
+    struct Setup {
+        rpc: MockRpc,
+        program_id: Pubkey,
+        index: CompressedIndex,
+    }
+
+    /// A compressed whitelist of `count` appended wallets, its tree holding their root
+    fn setup(count: u8) -> Setup {
+        let (rpc, program_id) = (MockRpc::new(), Pubkey::new_unique());
+        let mut index = CompressedIndex::new(Pubkey::new_unique(), Pubkey::new_unique());
+        for i in 0..count {
+            let event = WhitelistEvent::CompressedEntryAppended {
+                whitelist: index.whitelist,
+                wallet: Pubkey::new_from_array([i + 1; 32]),
+                leaf_index: i as u32,
+                allocation: 100 * i as u64,
+            };
+            assert!(index.apply(&event));
+        }
+        let mut state = TokenWhitelist {is_initialized: true, max_whitelist_size: 10, ..TokenWhitelist::default()};
+        state.extension.compressed_tree =
+            Some(CompressedTree {tree: index.tree, authority_bump: 255, leaf_count: count as u32});
+        rpc.set_whitelist(index.whitelist, program_id, &state);
+        set_root(&rpc, &index.tree, TreeMirror::new(&index.entries).root());
+        Setup {rpc, program_id, index}
+    }
+
+    fn set_root(rpc: &MockRpc, tree: &Pubkey, root: [u8; 32]) {
+        let mut data = vec![0; compression::tree_space()];
+        data[ROOT_OFFSET..ROOT_OFFSET + 32].copy_from_slice(&root);
+        rpc.set_account(*tree, Account {data, owner: compression::COMPRESSION_PROGRAM_ID, ..Account::default()});
+    }
+
+    #[test]
+    fn test_verify_instruction() {
+        let Setup {rpc, program_id, index} = setup(5);
+        let wallet = Pubkey::new_from_array([3; 32]);
+        let instruction = verify_compressed_membership_instruction(&rpc, &index, &program_id, &index.whitelist, &wallet)
+            .unwrap();
+        let root = TreeMirror::new(&index.entries).root();
+        assert_eq!(
+            TokenWhitelistInstruction::unpack(&instruction.data).unwrap(),
+            TokenWhitelistInstruction::VerifyCompressedMembership {leaf_index: 2, allocation: 200, root}
+        );
+        assert_eq!(instruction.accounts.len(), 4 + compression::TREE_DEPTH as usize);
+        assert_eq!(instruction.accounts[2].pubkey, index.tree);
+
+        assert!(matches!(
+            verify_compressed_membership_instruction(&rpc, &index, &program_id, &index.whitelist, &Pubkey::new_unique()),
+            Err(ClientError::Program(TokenWhitelistError::AccountNotWhitelisted))
+        ));
+    }
+
+    #[test]
+    fn test_replace_follows_events() {
+        let Setup {rpc, program_id, mut index} = setup(3);
+        let (owner, wallet) = (Pubkey::new_unique(), Pubkey::new_from_array([2; 32]));
+        let instruction =
+            replace_compressed_entry_instruction(&rpc, &index, &program_id, &owner, &index.whitelist, &wallet, 700)
+                .unwrap();
+        assert!(matches!(
+            TokenWhitelistInstruction::unpack(&instruction.data).unwrap(),
+            TokenWhitelistInstruction::ReplaceCompressedEntry {
+                leaf_index: 1,
+                previous_allocation: 100,
+                allocation_amount: 700,
+                ..
+            }
+        ));
+
+        let replaced = WhitelistEvent::CompressedEntryReplaced {
+            whitelist: index.whitelist,
+            wallet,
+            leaf_index: 1,
+            previous_allocation: 100,
+            allocation: 700,
+        };
+        assert!(index.apply(&replaced));
+        assert_eq!(index.entries[1], (wallet, 700));
+        // replaying it finds the leaf at the new allocation already
+        assert!(!index.apply(&replaced));
+
+        set_root(&rpc, &index.tree, TreeMirror::new(&index.entries).root());
+        let (_, leaf) = compressed_leaf(&rpc, &index, &index.whitelist, &wallet).unwrap();
+        assert_eq!(leaf.allocation, 700);
+    }
+
+    #[test]
+    fn test_stale_indexer() {
+        let Setup {rpc, program_id, index} = setup(4);
+        let wallet = Pubkey::new_from_array([1; 32]);
+
+        // the indexer has yet to see the last append
+        let mut behind = index.clone();
+        behind.entries.pop();
+        assert!(matches!(
+            verify_compressed_membership_instruction(&rpc, &behind, &program_id, &index.whitelist, &wallet),
+            Err(ClientError::StaleIndexer(tree)) if tree == index.tree
+        ));
+
+        // or a replacement, the count matches and the root does not
+        set_root(&rpc, &index.tree, [9; 32]);
+        assert!(matches!(
+            verify_compressed_membership_instruction(&rpc, &index, &program_id, &index.whitelist, &wallet),
+            Err(ClientError::StaleIndexer(_))
+        ));
+    }
+
+    #[test]
+    fn test_index_skips_other_events() {
+        let mut index = CompressedIndex::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let wallet = Pubkey::new_unique();
+        let appended = |whitelist, leaf_index| WhitelistEvent::CompressedEntryAppended {
+            whitelist,
+            wallet,
+            leaf_index,
+            allocation: 100,
+        };
+        assert!(index.apply(&appended(Pubkey::new_unique(), 0)));
+        assert!(index.apply(&WhitelistEvent::EntryRemoved {account: wallet, allocation: 100, memo: None}));
+        assert!(index.entries.is_empty());
+
+        // a missed append leaves a gap
+        assert!(!index.apply(&appended(index.whitelist, 1)));
+        assert!(index.apply(&appended(index.whitelist, 0)));
+        assert_eq!(index.entries, vec![(wallet, 100)]);
+        assert!(matches!(index.compressed_entries(&Pubkey::new_unique()), Err(ClientError::InvalidAccount(_))));
+    }
+
+    #[test]
+    fn test_init_instructions() {
+        let [program_id, payer, owner, whitelist, tree] = [(); 5].map(|_| Pubkey::new_unique());
+        let instructions =
+            init_compressed_whitelist_instructions(&program_id, &payer, &owner, &whitelist, &tree, &Rent::default());
+        assert_eq!(
+            instructions[0],
+            system_instruction::create_account(
+                &payer,
+                &tree,
+                Rent::default().minimum_balance(compression::tree_space()),
+                compression::tree_space() as u64,
+                &compression::COMPRESSION_PROGRAM_ID,
+            )
+        );
+        assert_eq!(instructions[1], init_compressed_whitelist(&program_id, &owner, &whitelist, &tree));
+    }
+}
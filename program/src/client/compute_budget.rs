@@ -0,0 +1,145 @@
+//! Compute unit limit and priority fee instructions prepended when sending
+
+use solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use crate::client::{rpc::WhitelistRpc, ClientError};
+
+/// Extra units on top of the simulated consumption, covering the budget instructions themselves
+const AUTO_UNIT_LIMIT_MARGIN: u64 = 10; // percent
+const AUTO_UNIT_LIMIT_MIN: u32 = 1_000;
+/// Most units a transaction may request
+pub const MAX_UNIT_LIMIT: u32 = 1_400_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ComputeBudget {
+    /// Leave the runtime defaults
+    #[default]
+    Default,
+    /// Fixed unit limit and price (micro-lamports per unit), either can be left out
+    Fixed { unit_limit: Option<u32>, unit_price: Option<u64> },
+    /// Limit from a simulation of the transaction, price from recent prioritization fees
+    Auto,
+}
+
+/// Budget instructions for a limit and price, the limit always comes first
+pub fn compute_budget_instructions(unit_limit: Option<u32>, unit_price: Option<u64>) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    if let Some(unit_limit) = unit_limit {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+    }
+    if let Some(unit_price) = unit_price {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(unit_price));
+    }
+    instructions
+}
+
+/// Largest budget instructions `budget` can produce, reserved when sizing batches
+pub fn reserved_budget_instructions(budget: ComputeBudget) -> Vec<Instruction> {
+    match budget {
+        ComputeBudget::Default => vec![],
+        _ => compute_budget_instructions(Some(MAX_UNIT_LIMIT), Some(u64::MAX)),
+    }
+}
+
+/// Signs a transaction with the budget instructions ahead of the given ones
+pub fn build_transaction(
+    payer: &Keypair,
+    budget: &[Instruction],
+    instructions: &[Instruction],
+    blockhash: Hash,
+) -> Transaction {
+    let instructions: Vec<Instruction> = budget.iter().chain(instructions).cloned().collect();
+    Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &[payer], blockhash)
+}
+
+/// Unit limit for a simulated consumption, with margin
+pub fn auto_unit_limit(units_consumed: u64) -> u32 {
+    let units = units_consumed.saturating_mul(100 + AUTO_UNIT_LIMIT_MARGIN) / 100;
+    units.clamp(AUTO_UNIT_LIMIT_MIN as u64, MAX_UNIT_LIMIT as u64) as u32
+}
+
+/// Median of the recent prioritization fees paid for the written accounts
+pub fn auto_unit_price<R: WhitelistRpc>(rpc: &R, writable: &[Pubkey]) -> Result<u64, ClientError> {
+    let mut fees = rpc.get_recent_prioritization_fees(writable)?;
+    fees.sort_unstable();
+    Ok(fees.get(fees.len() / 2).copied().unwrap_or(0))
+}
+
+/// Accounts the instructions write, the ones whose recent fees matter
+pub fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = instructions.iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| meta.is_writable)
+        .map(|meta| meta.pubkey)
+        .collect();
+    accounts.sort();
+    accounts.dedup();
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::instruction::AccountMeta;
+    use solana_sdk::compute_budget;
+
+    #[test]
+    fn test_build_transaction_order() {
+        let payer = Keypair::new();
+        let program_id = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[1],
+            vec![AccountMeta::new(Pubkey::new_unique(), false)],
+        );
+        let budget = compute_budget_instructions(Some(50_000), Some(1_000));
+        let transaction = build_transaction(&payer, &budget, &[instruction], Hash::default());
+
+        let message = &transaction.message;
+        assert_eq!(message.instructions.len(), 3);
+        let program = |index: usize| *message.instructions[index].program_id(&message.account_keys);
+        assert_eq!(program(0), compute_budget::id());
+        assert_eq!(message.instructions[0].data, ComputeBudgetInstruction::set_compute_unit_limit(50_000).data);
+        assert_eq!(program(1), compute_budget::id());
+        assert_eq!(message.instructions[1].data, ComputeBudgetInstruction::set_compute_unit_price(1_000).data);
+        assert_eq!(program(2), program_id);
+    }
+
+    #[test]
+    fn test_compute_budget_instructions() {
+        assert!(compute_budget_instructions(None, None).is_empty());
+        assert_eq!(
+            compute_budget_instructions(None, Some(5)),
+            vec![ComputeBudgetInstruction::set_compute_unit_price(5)]
+        );
+        assert_eq!(
+            compute_budget_instructions(Some(7), None),
+            vec![ComputeBudgetInstruction::set_compute_unit_limit(7)]
+        );
+    }
+
+    #[test]
+    fn test_auto_unit_limit() {
+        assert_eq!(auto_unit_limit(0), AUTO_UNIT_LIMIT_MIN);
+        assert_eq!(auto_unit_limit(20_000), 22_000);
+        assert_eq!(auto_unit_limit(u64::MAX), MAX_UNIT_LIMIT);
+    }
+
+    #[test]
+    fn test_writable_accounts() {
+        let written = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![
+                AccountMeta::new_readonly(Pubkey::new_unique(), true),
+                AccountMeta::new(written, false),
+            ],
+        );
+        assert_eq!(writable_accounts(&[instruction.clone(), instruction]), vec![written]);
+    }
+}
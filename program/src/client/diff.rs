@@ -0,0 +1,155 @@
+//! Differences between two whitelists, such as a staging and a production one
+
+use solana_program::pubkey::Pubkey;
+use crate::state::TokenWhitelist;
+
+use std::fmt;
+
+/// Header field holding different values in the two whitelists, both rendered as text
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub a: String,
+    pub b: String,
+}
+
+/// What turns whitelist `a` into whitelist `b`, entries keyed as stored in the map
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WhitelistDiff {
+    pub header: Vec<FieldChange>,
+    pub added: Vec<(String, u64)>, // only in b
+    pub removed: Vec<(String, u64)>, // only in a
+    pub changed: Vec<(String, u64, u64)>, // key, allocation in a and in b
+}
+
+impl WhitelistDiff {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the owner, size and policy fields, then the entries in key order
+pub fn diff_whitelists(a: &TokenWhitelist, b: &TokenWhitelist) -> WhitelistDiff {
+    let mut diff = WhitelistDiff::default();
+    let mut compare = |field: &'static str, a: String, b: String| {
+        if a != b {
+            diff.header.push(FieldChange {field, a, b});
+        }
+    };
+    compare("initialized", a.is_initialized.to_string(), b.is_initialized.to_string());
+    compare("owner", a.init_pubkey.to_string(), b.init_pubkey.to_string());
+    compare("max_whitelist_size", a.max_whitelist_size.to_string(), b.max_whitelist_size.to_string());
+    let (ext_a, ext_b) = (&a.extension, &b.extension);
+    compare("allocation_decimals", ext_a.allocation_decimals.to_string(), ext_b.allocation_decimals.to_string());
+    let mint = |mint: Option<Pubkey>| mint.map_or("None".to_string(), |mint| mint.to_string());
+    compare("allocation_mint", mint(ext_a.allocation_mint), mint(ext_b.allocation_mint));
+    compare("allocation_bounds", format!("{:?}", ext_a.allocation_bounds), format!("{:?}", ext_b.allocation_bounds));
+    compare("consume_limits", format!("{:?}", ext_a.consume_limits), format!("{:?}", ext_b.consume_limits));
+    compare("active_round", ext_a.active_round.to_string(), ext_b.active_round.to_string());
+    compare("strict_token_checks", ext_a.strict_token_checks.to_string(), ext_b.strict_token_checks.to_string());
+    compare("delegate", format!("{:?}", ext_a.delegate), format!("{:?}", ext_b.delegate));
+    compare("registration", format!("{:?}", ext_a.registration), format!("{:?}", ext_b.registration));
+    compare("merkle_root", format!("{:?}", ext_a.merkle_root), format!("{:?}", ext_b.merkle_root));
+    compare("only_wallets", ext_a.only_wallets.to_string(), ext_b.only_wallets.to_string());
+    compare("config_bump", format!("{:?}", ext_a.config_bump), format!("{:?}", ext_b.config_bump));
+    compare("self_zero_disabled", ext_a.self_zero_disabled.to_string(), ext_b.self_zero_disabled.to_string());
+    compare(
+        "zero_allocation_disabled",
+        ext_a.zero_allocation_disabled.to_string(),
+        ext_b.zero_allocation_disabled.to_string(),
+    );
+    compare("token_gate", format!("{:?}", ext_a.token_gate), format!("{:?}", ext_b.token_gate));
+
+    for (key, &amount_a) in a.whitelist_map.iter() {
+        match b.whitelist_map.get(key) {
+            None => diff.removed.push((key.clone(), amount_a)),
+            Some(&amount_b) if amount_b != amount_a => diff.changed.push((key.clone(), amount_a, amount_b)),
+            Some(_) => {}
+        }
+    }
+    diff.added = b.whitelist_map.iter()
+        .filter(|(key, _)| !a.whitelist_map.contains_key(*key))
+        .map(|(key, &amount)| (key.clone(), amount))
+        .collect();
+    diff
+}
+
+impl fmt::Display for WhitelistDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "whitelists identical");
+        }
+        for change in &self.header {
+            writeln!(f, "field   {:<20} {} -> {}", change.field, change.a, change.b)?;
+        }
+        for (key, amount) in &self.removed {
+            writeln!(f, "removed {:<44} {}", key, amount)?;
+        }
+        for (key, a, b) in &self.changed {
+            writeln!(f, "changed {:<44} {} -> {}", key, a, b)?;
+        }
+        for (key, amount) in &self.added {
+            writeln!(f, "added   {:<44} {}", key, amount)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn whitelist(entries: &[(u8, u64)]) -> TokenWhitelist {
+        TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: Pubkey::new_from_array([9; 32]),
+            max_whitelist_size: 50,
+            whitelist_map: entries.iter().map(|&(i, amount)| (key(i), amount)).collect(),
+            ..TokenWhitelist::default()
+        }
+    }
+
+    fn key(i: u8) -> String {
+        Pubkey::new_from_array([i; 32]).to_string()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let a = whitelist(&[(1, 100), (2, 200)]);
+        let diff = diff_whitelists(&a, &a.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "whitelists identical\n");
+    }
+
+    #[test]
+    fn test_diff_disjoint() {
+        let diff = diff_whitelists(&whitelist(&[(1, 100), (2, 200)]), &whitelist(&[(3, 300)]));
+        assert_eq!(diff.removed, vec![(key(1), 100), (key(2), 200)]);
+        assert_eq!(diff.added, vec![(key(3), 300)]);
+        assert!(diff.changed.is_empty());
+        assert!(diff.header.is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_allocation() {
+        let diff = diff_whitelists(&whitelist(&[(1, 100), (2, 200)]), &whitelist(&[(1, 100), (2, 250)]));
+        assert_eq!(diff.changed, vec![(key(2), 200, 250)]);
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.to_string(), format!("changed {:<44} 200 -> 250\n", key(2)));
+    }
+
+    #[test]
+    fn test_diff_header() {
+        let a = whitelist(&[(1, 100)]);
+        let mut b = a.clone();
+        b.init_pubkey = Pubkey::new_from_array([8; 32]);
+        b.max_whitelist_size = 60;
+        b.extension.strict_token_checks = true;
+        let diff = diff_whitelists(&a, &b);
+        let fields: Vec<_> = diff.header.iter().map(|change| change.field).collect();
+        assert_eq!(fields, vec!["owner", "max_whitelist_size", "strict_token_checks"]);
+        assert_eq!(diff.header[1], FieldChange {field: "max_whitelist_size", a: "50".to_string(), b: "60".to_string()});
+        assert!(!diff.is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+//! Reading entries page by page from simulated ListEntries instructions or from a fetched
+//! whitelist, and hashing them like ComputeEntriesHash
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use borsh::BorshDeserialize;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_program::{message::Message, pubkey::Pubkey};
+use solana_sdk::transaction::Transaction;
+use crate::{
+    client::{rpc::WhitelistRpc, simulate, ClientError},
+    instruction,
+    state::{hash_entries_map, validate_key, EntriesPage, TokenWhitelist, MAX_LIST_ENTRIES},
+};
+
+use std::collections::BTreeMap;
+
+/// Decodes the page a simulated ListEntries instruction of `program_id` returned
+pub fn decode_entries_page(
+    program_id: &Pubkey,
+    result: &RpcSimulateTransactionResult,
+) -> Result<EntriesPage, ClientError> {
+    let return_data = result.return_data.as_ref().ok_or(ClientError::InvalidReturnData)?;
+    if return_data.program_id != program_id.to_string() {
+        return Err(ClientError::InvalidReturnData);
+    }
+    let data = BASE64_STANDARD.decode(&return_data.data.0).map_err(|_| ClientError::InvalidReturnData)?;
+    EntriesPage::try_from_slice(&data).map_err(|_| ClientError::InvalidReturnData)
+}
+
+/// Every entry of the whitelist in key order, one simulation per page. Key order compares the
+/// base58 strings, as [entries_in_key_order](../../state/struct.TokenWhitelist.html#method.entries_in_key_order)
+/// does; sort by pubkey for byte order. `payer` only pays the fee of the simulated transactions,
+/// it must exist but never signs. It is passed as the reader too, so a whitelist restricting
+/// enumeration is listed when it is the owner or the delegate.
+pub fn fetch_all_entries_via_simulation<R: WhitelistRpc>(
+    rpc: &R,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    whitelist: &Pubkey,
+) -> Result<Vec<(Pubkey, u64)>, ClientError> {
+    let blockhash = rpc.get_latest_blockhash()?;
+    let mut entries = Vec::new();
+    loop {
+        let list = instruction::list_entries(program_id, whitelist, entries.len() as u32, MAX_LIST_ENTRIES as u8);
+        let list = instruction::with_reader(list, payer);
+        let message = Message::new_with_blockhash(&[list], Some(payer), &blockhash);
+        let result = simulate(rpc, &Transaction::new_unsigned(message).into())?;
+        let page = decode_entries_page(program_id, &result)?;
+        if page.entries.is_empty() {
+            return Ok(entries);
+        }
+        entries.extend(page.entries);
+        if entries.len() >= page.total as usize {
+            return Ok(entries);
+        }
+    }
+}
+
+/// Entries of a fetched whitelist, see [list_entries](fn.list_entries.html)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryListing {
+    pub entries: Vec<(Pubkey, u64)>, // in key order
+    pub invalid_keys: Vec<(String, u64)>, // keys that are no pubkey, with their allocation
+}
+
+/// Every entry of a fetched whitelist in key order. Unlike ListEntries, which fails on the
+/// first key that is no pubkey, such keys are set apart for the caller to report or repair.
+pub fn list_entries(state: &TokenWhitelist) -> EntryListing {
+    let mut listing = EntryListing::default();
+    for (key, &amount) in state.whitelist_map.iter() {
+        match validate_key(key) {
+            Ok(account) => listing.entries.push((account, amount)),
+            Err(_) => listing.invalid_keys.push((key.clone(), amount)),
+        }
+    }
+    listing
+}
+
+/// The hash ComputeEntriesHash returns and `entries_hash` holds for a whitelist with these entries
+pub fn hash_entries(entries: &BTreeMap<Pubkey, u64>) -> [u8; 32] {
+    let whitelist_map = entries.iter().map(|(key, amount)| (key.to_string(), *amount)).collect();
+    hash_entries_map(&whitelist_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::mock::MockRpc, instruction::TokenWhitelistInstruction};
+
+    #[test]
+    fn test_fetch_all_entries_via_simulation() {
+        let program_id = Pubkey::new_unique();
+        let whitelist = Pubkey::new_unique();
+        let state = TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 50,
+            whitelist_map: (1..=50u64)
+                .map(|i| (Pubkey::new_unique().to_string(), i * 1_000))
+                .collect(),
+            ..TokenWhitelist::default()
+        };
+        let rpc = MockRpc::new();
+        rpc.set_whitelist(whitelist, program_id, &state);
+
+        let entries = fetch_all_entries_via_simulation(&rpc, &program_id, &Pubkey::new_unique(), &whitelist).unwrap();
+        assert_eq!(entries, state.entries_in_key_order().unwrap());
+
+        let offsets: Vec<u32> = rpc.simulated().iter()
+            .map(|transaction| {
+                match TokenWhitelistInstruction::unpack(&transaction.message.instructions()[0].data).unwrap() {
+                    TokenWhitelistInstruction::ListEntries {offset, ..} => offset,
+                    instruction => panic!("unexpected {:?}", instruction),
+                }
+            })
+            .collect();
+        assert_eq!(offsets, vec![0, 25]);
+    }
+
+    #[test]
+    fn test_list_entries_with_invalid_keys() {
+        let account = Pubkey::new_unique();
+        let mut state = TokenWhitelist {is_initialized: true, max_whitelist_size: 50, ..TokenWhitelist::default()};
+        state.add_keypair(&account.to_string(), &100);
+        state.add_keypair(&"not a pubkey".to_string(), &200);
+        state.add_keypair(&format!("1{}", account), &300);
+
+        let listing = list_entries(&state);
+        assert_eq!(listing.entries, vec![(account, 100)]);
+        assert_eq!(
+            listing.invalid_keys,
+            vec![(format!("1{}", account), 300), ("not a pubkey".to_string(), 200)]
+        );
+    }
+
+    #[test]
+    fn test_decode_entries_page_from_other_program() {
+        let rpc = MockRpc::new();
+        let whitelist = Pubkey::new_unique();
+        rpc.set_whitelist(whitelist, Pubkey::new_unique(), &TokenWhitelist {
+            is_initialized: true,
+            ..TokenWhitelist::default()
+        });
+        assert!(matches!(
+            fetch_all_entries_via_simulation(&rpc, &Pubkey::new_unique(), &Pubkey::new_unique(), &whitelist),
+            Err(ClientError::InvalidReturnData)
+        ));
+    }
+}
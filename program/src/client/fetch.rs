@@ -0,0 +1,176 @@
+//! Whitelist reads with the slot they were answered at, for jobs matching a whitelist against
+//! what it held as of a given slot, e.g. an announced snapshot
+
+use solana_client::{
+    client_error::ClientErrorKind,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    rpc_request::RpcError,
+};
+use solana_program::pubkey::Pubkey;
+use crate::{
+    client::{
+        rpc::{ReadConfig, WhitelistRpc},
+        ClientError,
+    },
+    state::TokenWhitelist,
+};
+
+use std::time::{Duration, Instant};
+
+/// A decoded whitelist and the context slot of the response it was read from
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhitelistWithContext {
+    pub state: TokenWhitelist,
+    pub slot: u64,
+}
+
+/// The whitelist at the client commitment
+pub fn fetch_whitelist<R: WhitelistRpc>(rpc: &R, whitelist: &Pubkey) -> Result<WhitelistWithContext, ClientError> {
+    fetch_whitelist_with_config(rpc, whitelist, ReadConfig::default())
+}
+
+/// The whitelist at the commitment of `config`, refused by the node with an RPC error until it
+/// reached the `min_context_slot` of `config`
+pub fn fetch_whitelist_with_config<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    config: ReadConfig,
+) -> Result<WhitelistWithContext, ClientError> {
+    let (account, slot) = rpc.get_optional_account_with_config(whitelist, config)?;
+    let account = account.ok_or(ClientError::InvalidAccount(*whitelist))?;
+    let state = TokenWhitelist::unpack_from_slice(&account.data).map_err(|_| ClientError::InvalidAccount(*whitelist))?;
+    Ok(WhitelistWithContext {state, slot})
+}
+
+/// The whitelist as of `min_slot` or later, at the client commitment. A node behind `min_slot`
+/// is asked again every `poll_interval`, as are failed reads, until `timeout` passes; then
+/// SlotNotReached is returned, or the error of the last read when it failed otherwise. A missing
+/// or undecodable whitelist is returned at once.
+pub fn fetch_whitelist_at_least<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    min_slot: u64,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<WhitelistWithContext, ClientError> {
+    let deadline = Instant::now() + timeout;
+    let config = ReadConfig {min_context_slot: Some(min_slot), ..ReadConfig::default()};
+    let mut last_slot = None;
+    loop {
+        // the error reported should the deadline pass before the next read
+        let pending = match fetch_whitelist_with_config(rpc, whitelist, config) {
+            // a node ignoring min_context_slot answers from behind
+            Ok(fetched) if fetched.slot >= min_slot => return Ok(fetched),
+            Ok(fetched) => {
+                last_slot = Some(fetched.slot);
+                ClientError::SlotNotReached {min_slot, slot: last_slot}
+            }
+            Err(error) if is_min_context_slot_not_reached(&error) => {
+                ClientError::SlotNotReached {min_slot, slot: last_slot}
+            }
+            Err(error @ ClientError::InvalidAccount(_)) => return Err(error),
+            Err(error) => error,
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(pending);
+        }
+        std::thread::sleep(poll_interval.min(remaining));
+    }
+}
+
+fn is_min_context_slot_not_reached(error: &ClientError) -> bool {
+    match error {
+        ClientError::Rpc(error) => matches!(
+            error.kind(),
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {code, ..})
+                if *code == JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockRpc;
+    use solana_sdk::{account::Account, commitment_config::CommitmentConfig};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    fn setup() -> (MockRpc, Pubkey, TokenWhitelist) {
+        let (rpc, whitelist) = (MockRpc::new(), Pubkey::new_unique());
+        let mut state = TokenWhitelist {is_initialized: true, max_whitelist_size: 8, ..TokenWhitelist::default()};
+        state.add_keypair(&Pubkey::new_unique().to_string(), &100);
+        rpc.set_whitelist(whitelist, Pubkey::new_unique(), &state);
+        (rpc, whitelist, state)
+    }
+
+    #[test]
+    fn test_fetch_whitelist_with_context() {
+        let (rpc, whitelist, state) = setup();
+        rpc.set_slot(120);
+        assert_eq!(fetch_whitelist(&rpc, &whitelist).unwrap(), WhitelistWithContext {state: state.clone(), slot: 120});
+
+        let config = ReadConfig {commitment: Some(CommitmentConfig::finalized()), min_context_slot: Some(100)};
+        assert_eq!(fetch_whitelist_with_config(&rpc, &whitelist, config).unwrap().slot, 120);
+        assert_eq!(rpc.read_configs(), vec![ReadConfig::default(), config]);
+
+        let config = ReadConfig {min_context_slot: Some(121), ..ReadConfig::default()};
+        assert!(is_min_context_slot_not_reached(&fetch_whitelist_with_config(&rpc, &whitelist, config).unwrap_err()));
+        assert!(matches!(
+            fetch_whitelist(&rpc, &Pubkey::new_unique()),
+            Err(ClientError::InvalidAccount(_))
+        ));
+    }
+
+    #[test]
+    fn test_fetch_at_least_waits_for_lagging_node() {
+        let (rpc, whitelist, state) = setup();
+        rpc.set_slot(205);
+        rpc.push_context_slots(&[190, 195, 199]);
+        let fetched = fetch_whitelist_at_least(&rpc, &whitelist, 200, TIMEOUT, POLL_INTERVAL).unwrap();
+        assert_eq!(fetched, WhitelistWithContext {state, slot: 205});
+        assert_eq!(rpc.account_reads(), 4);
+
+        // failed reads are retried like a lagging node
+        rpc.push_context_slots(&[150]);
+        rpc.fail_account_reads(2);
+        assert_eq!(fetch_whitelist_at_least(&rpc, &whitelist, 200, TIMEOUT, POLL_INTERVAL).unwrap().slot, 205);
+    }
+
+    #[test]
+    fn test_fetch_at_least_times_out() {
+        let (rpc, whitelist, _) = setup();
+        rpc.set_slot(150);
+        let timeout = Duration::from_millis(20);
+        assert!(matches!(
+            fetch_whitelist_at_least(&rpc, &whitelist, 200, timeout, POLL_INTERVAL),
+            Err(ClientError::SlotNotReached {min_slot: 200, slot: None})
+        ));
+
+        rpc.fail_account_reads(u32::MAX);
+        assert!(matches!(
+            fetch_whitelist_at_least(&rpc, &whitelist, 200, timeout, POLL_INTERVAL),
+            Err(ClientError::Rpc(_))
+        ));
+    }
+
+    #[test]
+    fn test_fetch_at_least_missing_whitelist() {
+        let (rpc, _, _) = setup();
+        let missing = Pubkey::new_unique();
+        assert!(matches!(
+            fetch_whitelist_at_least(&rpc, &missing, 0, TIMEOUT, POLL_INTERVAL),
+            Err(ClientError::InvalidAccount(account)) if account == missing
+        ));
+
+        rpc.set_account(missing, Account {data: vec![0; 16], ..Account::default()});
+        assert!(matches!(
+            fetch_whitelist_at_least(&rpc, &missing, 0, TIMEOUT, POLL_INTERVAL),
+            Err(ClientError::InvalidAccount(_))
+        ));
+        assert_eq!(rpc.account_reads(), 2);
+    }
+}
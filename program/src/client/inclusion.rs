@@ -0,0 +1,238 @@
+//! Waiting for a wallet to show up in a whitelist, e.g. after it registered itself
+
+use solana_program::pubkey::Pubkey;
+use thiserror::Error;
+use crate::{
+    client::{rpc::WhitelistRpc, ClientError},
+    error::TokenWhitelistError,
+    rules,
+    state::{TokenWhitelist, ACTIVE_ROUND},
+};
+
+use std::{
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
+
+#[derive(Error, Debug)]
+pub enum WaitError {
+    /// The deadline passed with the wallet absent, or below the awaited allocation
+    #[error("not whitelisted in time, last allocation seen {allocation:?}")]
+    Timeout { allocation: Option<u64> },
+    /// The whitelist account does not exist at the client commitment
+    #[error("whitelist account {0} not found")]
+    WhitelistMissing(Pubkey),
+    /// The account is not an initialized whitelist
+    #[error("invalid whitelist account {0}")]
+    InvalidWhitelist(Pubkey),
+    /// The last read before the deadline failed. Failed reads are retried until then.
+    #[error(transparent)]
+    Rpc(ClientError),
+}
+
+/// Blocks until `wallet` is whitelisted and returns its allocation in the active round. The
+/// whitelist is read every `poll_interval` at the commitment the client was built with, so a
+/// confirmed client reports an add sooner than a finalized one.
+pub fn await_whitelisted<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<u64, WaitError> {
+    await_allocation_at_least(rpc, whitelist, wallet, 0, timeout, poll_interval)
+}
+
+/// Like [await_whitelisted](fn.await_whitelisted.html), until the allocation reaches `min`
+pub fn await_allocation_at_least<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+    min: u64,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<u64, WaitError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match poll(rpc, whitelist, wallet, min, deadline, poll_interval) {
+            ControlFlow::Break(result) => return result,
+            ControlFlow::Continue(wait) => std::thread::sleep(wait),
+        }
+    }
+}
+
+/// [await_whitelisted](fn.await_whitelisted.html) sleeping on the tokio timer between reads. The
+/// reads themselves still block, which the nonblocking client only allows on a multi-threaded
+/// runtime.
+pub async fn await_whitelisted_async<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<u64, WaitError> {
+    await_allocation_at_least_async(rpc, whitelist, wallet, 0, timeout, poll_interval).await
+}
+
+/// [await_allocation_at_least](fn.await_allocation_at_least.html) sleeping on the tokio timer
+/// between reads
+pub async fn await_allocation_at_least_async<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+    min: u64,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<u64, WaitError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match poll(rpc, whitelist, wallet, min, deadline, poll_interval) {
+            ControlFlow::Break(result) => return result,
+            ControlFlow::Continue(wait) => tokio::time::sleep(wait).await,
+        }
+    }
+}
+
+/// Reads the whitelist once, breaking with the result once done or past the deadline and
+/// otherwise continuing with the time to sleep before the next read
+fn poll<R: WhitelistRpc>(
+    rpc: &R,
+    whitelist: &Pubkey,
+    wallet: &Pubkey,
+    min: u64,
+    deadline: Instant,
+    poll_interval: Duration,
+) -> ControlFlow<Result<u64, WaitError>, Duration> {
+    // the error reported should the deadline pass before the next read
+    let pending = match rpc.get_optional_account(whitelist) {
+        Ok(Some(account)) => match allocation(&account.data, whitelist, wallet) {
+            Ok(Some(allocation)) if allocation >= min => return ControlFlow::Break(Ok(allocation)),
+            Ok(allocation) => WaitError::Timeout {allocation},
+            Err(error) => return ControlFlow::Break(Err(error)),
+        },
+        Ok(None) => return ControlFlow::Break(Err(WaitError::WhitelistMissing(*whitelist))),
+        Err(error) => WaitError::Rpc(error),
+    };
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return ControlFlow::Break(Err(pending));
+    }
+    ControlFlow::Continue(poll_interval.min(remaining))
+}
+
+/// Allocation of `wallet` in the active round, None while it is not whitelisted
+fn allocation(data: &[u8], whitelist: &Pubkey, wallet: &Pubkey) -> Result<Option<u64>, WaitError> {
+    let state = TokenWhitelist::unpack_from_slice(data).map_err(|_| WaitError::InvalidWhitelist(*whitelist))?;
+    match rules::allocation(&state, wallet, ACTIVE_ROUND) {
+        Ok(allocation) => Ok(Some(allocation)),
+        Err(TokenWhitelistError::AccountNotWhitelisted) => Ok(None),
+        Err(_) => Err(WaitError::InvalidWhitelist(*whitelist)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockRpc, MOCK_WHITELIST_LEN};
+    use solana_sdk::account::Account;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    fn whitelist_account(entries: &[(Pubkey, u64)]) -> Account {
+        let mut state = TokenWhitelist {is_initialized: true, max_whitelist_size: 8, ..TokenWhitelist::default()};
+        for (wallet, amount) in entries {
+            state.add_keypair(&wallet.to_string(), amount);
+        }
+        let mut data = vec![0; MOCK_WHITELIST_LEN];
+        state.pack_into_slice(&mut data).unwrap();
+        Account {data, ..Account::default()}
+    }
+
+    #[test]
+    fn test_delayed_inclusion() {
+        let rpc = MockRpc::new();
+        let (whitelist, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(whitelist, whitelist_account(&[]));
+        rpc.set_account_after(3, whitelist, whitelist_account(&[(wallet, 500)]));
+        assert_eq!(await_whitelisted(&rpc, &whitelist, &wallet, TIMEOUT, POLL_INTERVAL).unwrap(), 500);
+    }
+
+    #[test]
+    fn test_transient_rpc_failures() {
+        let rpc = MockRpc::new();
+        let (whitelist, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(whitelist, whitelist_account(&[]));
+        rpc.set_account_after(1, whitelist, whitelist_account(&[(wallet, 500)]));
+        rpc.fail_account_reads(4);
+        assert_eq!(await_whitelisted(&rpc, &whitelist, &wallet, TIMEOUT, POLL_INTERVAL).unwrap(), 500);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let rpc = MockRpc::new();
+        let (whitelist, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(whitelist, whitelist_account(&[]));
+        let timeout = Duration::from_millis(20);
+        assert!(matches!(
+            await_whitelisted(&rpc, &whitelist, &wallet, timeout, POLL_INTERVAL),
+            Err(WaitError::Timeout {allocation: None})
+        ));
+
+        // an RPC that never answers is reported as such
+        rpc.fail_account_reads(u32::MAX);
+        assert!(matches!(
+            await_whitelisted(&rpc, &whitelist, &wallet, timeout, POLL_INTERVAL),
+            Err(WaitError::Rpc(_))
+        ));
+    }
+
+    #[test]
+    fn test_whitelist_missing() {
+        let rpc = MockRpc::new();
+        let whitelist = Pubkey::new_unique();
+        assert!(matches!(
+            await_whitelisted(&rpc, &whitelist, &Pubkey::new_unique(), TIMEOUT, POLL_INTERVAL),
+            Err(WaitError::WhitelistMissing(missing)) if missing == whitelist
+        ));
+
+        rpc.set_account(whitelist, Account {data: vec![0; MOCK_WHITELIST_LEN], ..Account::default()});
+        assert!(matches!(
+            await_whitelisted(&rpc, &whitelist, &Pubkey::new_unique(), TIMEOUT, POLL_INTERVAL),
+            Err(WaitError::InvalidWhitelist(_))
+        ));
+    }
+
+    #[test]
+    fn test_allocation_at_least() {
+        let rpc = MockRpc::new();
+        let (whitelist, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(whitelist, whitelist_account(&[(wallet, 100)]));
+        assert!(matches!(
+            await_allocation_at_least(&rpc, &whitelist, &wallet, 500, Duration::from_millis(20), POLL_INTERVAL),
+            Err(WaitError::Timeout {allocation: Some(100)})
+        ));
+
+        rpc.set_account_after(2, whitelist, whitelist_account(&[(wallet, 750)]));
+        assert_eq!(await_allocation_at_least(&rpc, &whitelist, &wallet, 500, TIMEOUT, POLL_INTERVAL).unwrap(), 750);
+    }
+
+    #[test]
+    fn test_async_variants() {
+        let rpc = MockRpc::new();
+        let (whitelist, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(whitelist, whitelist_account(&[]));
+        rpc.set_account_after(2, whitelist, whitelist_account(&[(wallet, 500)]));
+        rpc.fail_account_reads(1);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            assert_eq!(await_whitelisted_async(&rpc, &whitelist, &wallet, TIMEOUT, POLL_INTERVAL).await.unwrap(), 500);
+            let timeout = Duration::from_millis(20);
+            assert!(matches!(
+                await_allocation_at_least_async(&rpc, &whitelist, &wallet, 1_000, timeout, POLL_INTERVAL).await,
+                Err(WaitError::Timeout {allocation: Some(500)})
+            ));
+        });
+    }
+}
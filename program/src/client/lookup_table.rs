@@ -0,0 +1,219 @@
+//! Address lookup tables and v0 transactions for batches that outgrow legacy transactions
+
+use solana_program::{
+    address_lookup_table::{
+        instruction::{create_lookup_table, extend_lookup_table as extend_instruction},
+        state::AddressLookupTable,
+        AddressLookupTableAccount,
+    },
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, Message, VersionedMessage},
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    packet::PACKET_DATA_SIZE,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, VersionedTransaction},
+};
+use crate::client::{rpc::WhitelistRpc, ClientError};
+
+/// Accounts a transaction may lock, lookups included
+pub const MAX_TRANSACTION_ACCOUNTS: usize = 64;
+/// Addresses appended by one extend transaction, keeping it under the packet size
+const EXTEND_CHUNK_SIZE: usize = 20;
+
+/// Creates a lookup table owned by `payer` holding the whitelist account and its program id.
+/// Addresses become usable in the slot after they are added.
+pub fn create_lookup_table_for_whitelist<R: WhitelistRpc>(
+    rpc: &R,
+    payer: &Keypair,
+    whitelist: &Pubkey,
+) -> Result<Pubkey, ClientError> {
+    let program_id = rpc.get_account(whitelist)?.owner;
+    let recent_slot = rpc.get_finalized_slot()?;
+    let (create, lookup_table) = create_lookup_table(payer.pubkey(), payer.pubkey(), recent_slot);
+    let extend = extend_instruction(
+        lookup_table,
+        payer.pubkey(),
+        Some(payer.pubkey()),
+        vec![*whitelist, program_id],
+    );
+    send(rpc, payer, &[create, extend])?;
+    Ok(lookup_table)
+}
+
+/// Appends the addresses missing from the table, typically the targets of a large batch
+pub fn extend_lookup_table<R: WhitelistRpc>(
+    rpc: &R,
+    payer: &Keypair,
+    lookup_table: &Pubkey,
+    addresses: &[Pubkey],
+) -> Result<Vec<Signature>, ClientError> {
+    let existing = fetch_lookup_table(rpc, lookup_table)?.addresses;
+    let mut missing: Vec<Pubkey> = addresses.iter()
+        .filter(|address| !existing.contains(address))
+        .copied()
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    let mut signatures = Vec::new();
+    for chunk in missing.chunks(EXTEND_CHUNK_SIZE) {
+        let extend = extend_instruction(*lookup_table, payer.pubkey(), Some(payer.pubkey()), chunk.to_vec());
+        signatures.push(send(rpc, payer, &[extend])?);
+    }
+    Ok(signatures)
+}
+
+/// Fetches and decodes a lookup table for compiling v0 messages
+pub fn fetch_lookup_table<R: WhitelistRpc>(
+    rpc: &R,
+    lookup_table: &Pubkey,
+) -> Result<AddressLookupTableAccount, ClientError> {
+    let data = rpc.get_account(lookup_table)?.data;
+    let table = AddressLookupTable::deserialize(&data)
+        .map_err(|_| ClientError::InvalidAccount(*lookup_table))?;
+    Ok(AddressLookupTableAccount {
+        key: *lookup_table,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+fn send<R: WhitelistRpc>(rpc: &R, payer: &Keypair, instructions: &[Instruction]) -> Result<Signature, ClientError> {
+    let blockhash = rpc.get_latest_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &[payer], blockhash);
+    rpc.send_and_confirm(&transaction.into())
+}
+
+/// Signs a v0 transaction with the budget instructions ahead of the given ones
+pub fn build_versioned_transaction(
+    payer: &Keypair,
+    budget: &[Instruction],
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedTransaction, ClientError> {
+    let instructions: Vec<Instruction> = budget.iter().chain(instructions).cloned().collect();
+    let message = v0::Message::try_compile(&payer.pubkey(), &instructions, lookup_tables, blockhash)?;
+    Ok(VersionedTransaction::try_new(VersionedMessage::V0(message), &[payer])?)
+}
+
+/// Serialized size and number of locked accounts of a transaction, legacy without lookup tables
+pub fn transaction_size(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<(usize, usize), ClientError> {
+    let message = if lookup_tables.is_empty() {
+        VersionedMessage::Legacy(Message::new_with_blockhash(instructions, Some(payer), &Hash::default()))
+    } else {
+        VersionedMessage::V0(v0::Message::try_compile(payer, instructions, lookup_tables, Hash::default())?)
+    };
+    let accounts = match &message {
+        VersionedMessage::Legacy(message) => message.account_keys.len(),
+        VersionedMessage::V0(message) => message.account_keys.len() + message.address_table_lookups.iter()
+            .map(|lookup| lookup.writable_indexes.len() + lookup.readonly_indexes.len())
+            .sum::<usize>(),
+    };
+    let signatures = message.header().num_required_signatures as usize;
+    // short_vec length prefix of the signatures, below 128 of them
+    Ok((1 + signatures * 64 + message.serialize().len(), accounts))
+}
+
+/// Splits instructions into the fewest in-order batches that fit a transaction along with
+/// `budget`, packing more per batch when the lookup tables hold their accounts
+pub fn plan_batches(
+    payer: &Pubkey,
+    budget: &[Instruction],
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<Vec<Vec<Instruction>>, ClientError> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<Instruction> = budget.to_vec();
+    for instruction in instructions {
+        batch.push(instruction.clone());
+        let (size, accounts) = transaction_size(payer, &batch, lookup_tables)?;
+        if (size > PACKET_DATA_SIZE || accounts > MAX_TRANSACTION_ACCOUNTS) && batch.len() > budget.len() + 1 {
+            batch.pop();
+            batches.push(batch.split_off(budget.len()));
+            batch.push(instruction.clone());
+        }
+    }
+    if batch.len() > budget.len() {
+        batches.push(batch.split_off(budget.len()));
+    }
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::compute_budget::compute_budget_instructions, instruction::add_to_whitelist};
+
+    fn adds(program_id: &Pubkey, owner: &Pubkey, whitelist: &Pubkey, targets: &[Pubkey]) -> Vec<Instruction> {
+        targets.iter()
+            .map(|target| add_to_whitelist(program_id, owner, whitelist, target, 100))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_batches_with_lookup_table() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let whitelist = Pubkey::new_unique();
+        let targets: Vec<Pubkey> = (0..200).map(|_| Pubkey::new_unique()).collect();
+        let instructions = adds(&program_id, &owner, &whitelist, &targets);
+        let budget = compute_budget_instructions(Some(1_400_000), Some(1_000));
+
+        let legacy = plan_batches(&owner, &budget, &instructions, &[]).unwrap();
+        let mut addresses = vec![whitelist, program_id];
+        addresses.extend_from_slice(&targets);
+        let lookup_table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses };
+        let versioned = plan_batches(&owner, &budget, &instructions, std::slice::from_ref(&lookup_table)).unwrap();
+
+        // targets resolved through the table cost an index instead of a full key
+        assert!(versioned[0].len() > 2 * legacy[0].len());
+        for (batches, lookup_tables) in [(&legacy, vec![]), (&versioned, vec![lookup_table])] {
+            assert_eq!(batches.concat(), instructions);
+            for batch in batches.iter() {
+                let batch: Vec<Instruction> = budget.iter().chain(batch).cloned().collect();
+                let (size, accounts) = transaction_size(&owner, &batch, &lookup_tables).unwrap();
+                assert!(size <= PACKET_DATA_SIZE);
+                assert!(accounts <= MAX_TRANSACTION_ACCOUNTS);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transaction_size_matches_signed() {
+        let payer = Keypair::new();
+        let program_id = Pubkey::new_unique();
+        let whitelist = Pubkey::new_unique();
+        let targets: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let instructions = adds(&program_id, &payer.pubkey(), &whitelist, &targets);
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![whitelist, program_id, targets[0]],
+        };
+
+        let transaction = build_versioned_transaction(
+            &payer,
+            &[],
+            &instructions,
+            std::slice::from_ref(&lookup_table),
+            Hash::default(),
+        ).unwrap();
+        let signed = transaction.signatures.len() * 64 + 1 + transaction.message.serialize().len();
+        let (size, accounts) = transaction_size(&payer.pubkey(), &instructions, &[lookup_table]).unwrap();
+        assert_eq!(size, signed);
+        // payer, whitelist, program id and the ten targets
+        assert_eq!(accounts, 13);
+    }
+
+    #[test]
+    fn test_plan_batches_empty() {
+        let payer = Pubkey::new_unique();
+        assert!(plan_batches(&payer, &compute_budget_instructions(Some(1), None), &[], &[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+//! In-memory [WhitelistRpc](../rpc/trait.WhitelistRpc.html) with programmable responses,
+//! enabled by the `test-utils` feature
+
+use solana_client::{
+    client_error::ClientError as RpcClientError,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    rpc_request::{RpcError, RpcResponseErrorData},
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_program::{hash::Hash, message::VersionedMessage, pubkey::Pubkey, rent::Rent};
+use borsh::BorshSerialize;
+use solana_sdk::{
+    account::Account,
+    signature::Signature,
+    transaction::{TransactionError, VersionedTransaction},
+    transaction_context::TransactionReturnData,
+};
+use crate::{
+    client::{
+        rpc::{ReadConfig, WhitelistRpc},
+        ClientError,
+    },
+    instruction::TokenWhitelistInstruction,
+    rules,
+    state::{ProgramVersion, RequiredRent, TokenWhitelist, NO_CAMPAIGN},
+};
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+};
+
+/// Account size used by [set_whitelist](struct.MockRpc.html#method.set_whitelist)
+pub const MOCK_WHITELIST_LEN: usize = 8192;
+
+/// What happens to the next transaction sent, `Land` once the queue is empty
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockSend {
+    /// Adds and removes are applied to the stored whitelists and the status is reported
+    Land,
+    /// Never lands and its blockhash expires
+    Drop,
+    /// Applied like `Land` but the status is never reported
+    LandUnobserved,
+    /// Lands with this error, nothing is applied
+    Fail(TransactionError),
+    /// The RPC node cannot be reached, nothing is broadcast
+    Unavailable,
+}
+
+#[derive(Default)]
+pub struct MockRpc {
+    accounts: RefCell<HashMap<Pubkey, Account>>,
+    sends: RefCell<VecDeque<MockSend>>,
+    simulations: RefCell<VecDeque<RpcSimulateTransactionResult>>,
+    sent: RefCell<Vec<VersionedTransaction>>,
+    simulated: RefCell<Vec<VersionedTransaction>>,
+    statuses: RefCell<HashMap<Signature, Result<(), TransactionError>>>,
+    expired: RefCell<HashSet<Hash>>,
+    prioritization_fees: RefCell<Vec<u64>>,
+    slot: Cell<u64>,
+    context_slots: RefCell<VecDeque<u64>>,
+    read_configs: RefCell<Vec<ReadConfig>>,
+    failing_reads: Cell<u32>,
+    account_reads: Cell<u32>,
+    delayed_accounts: RefCell<Vec<(u32, Pubkey, Account)>>,
+}
+
+impl MockRpc {
+    pub fn new() -> Self {
+        MockRpc::default()
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.accounts.borrow_mut().insert(pubkey, account);
+    }
+
+    /// Stores a whitelist owned by `program_id`
+    pub fn set_whitelist(&self, pubkey: Pubkey, program_id: Pubkey, state: &TokenWhitelist) {
+        let mut data = vec![0; MOCK_WHITELIST_LEN];
+        state.pack_into_slice(&mut data).unwrap();
+        self.set_account(pubkey, Account { data, owner: program_id, ..Account::default() });
+    }
+
+    /// Stores `account` once `reads` more account reads were answered, as a change that takes
+    /// that long to reach the client commitment
+    pub fn set_account_after(&self, reads: u32, pubkey: Pubkey, account: Account) {
+        self.delayed_accounts.borrow_mut().push((reads, pubkey, account));
+    }
+
+    /// Fails the next `reads` account reads as if the node could not be reached
+    pub fn fail_account_reads(&self, reads: u32) {
+        self.failing_reads.set(reads);
+    }
+
+    pub fn whitelist(&self, pubkey: &Pubkey) -> TokenWhitelist {
+        TokenWhitelist::unpack_from_slice(&self.accounts.borrow()[pubkey].data).unwrap()
+    }
+
+    /// Queues the behaviour of the next send
+    pub fn push_send(&self, send: MockSend) {
+        self.sends.borrow_mut().push_back(send);
+    }
+
+    /// Queues the result of the next simulation, which otherwise succeeds and returns the page
+    /// of the last ListEntries instruction
+    pub fn push_simulation(&self, result: RpcSimulateTransactionResult) {
+        self.simulations.borrow_mut().push_back(result);
+    }
+
+    pub fn set_prioritization_fees(&self, fees: Vec<u64>) {
+        *self.prioritization_fees.borrow_mut() = fees;
+    }
+
+    /// Slot reported by every slot read and account read
+    pub fn set_slot(&self, slot: u64) {
+        self.slot.set(slot);
+    }
+
+    /// Context slots reported by the next account reads in turn, as a node lagging behind the
+    /// slot set with `set_slot` that later account reads report
+    pub fn push_context_slots(&self, slots: &[u64]) {
+        self.context_slots.borrow_mut().extend(slots);
+    }
+
+    /// Configs of the account reads made with a ReadConfig so far
+    pub fn read_configs(&self) -> Vec<ReadConfig> {
+        self.read_configs.borrow().clone()
+    }
+
+    /// Account reads answered or failed so far
+    pub fn account_reads(&self) -> u32 {
+        self.account_reads.get()
+    }
+
+    /// Transactions sent so far, dropped and failed ones included
+    pub fn sent(&self) -> Vec<VersionedTransaction> {
+        self.sent.borrow().clone()
+    }
+
+    pub fn simulated(&self) -> Vec<VersionedTransaction> {
+        self.simulated.borrow().clone()
+    }
+
+    /// Applies the adds and removes of the transaction to the stored whitelists
+    fn apply(&self, transaction: &VersionedTransaction) {
+        let keys = static_keys(transaction);
+        for instruction in transaction.message.instructions() {
+            let key = |index: usize| instruction.accounts.get(index).and_then(|&i| keys.get(i as usize)).copied();
+            let (whitelist, wallet) = match (key(1), key(2)) {
+                (Some(whitelist), Some(wallet)) => (whitelist, wallet.to_string()),
+                _ => continue,
+            };
+            let mut accounts = self.accounts.borrow_mut();
+            let account = match accounts.get_mut(&whitelist) {
+                Some(account) if keys.get(instruction.program_id_index as usize) == Some(&account.owner) => account,
+                _ => continue,
+            };
+            let mut state = match TokenWhitelist::unpack_from_slice(&account.data) {
+                Ok(state) => state,
+                Err(_) => continue,
+            };
+            match TokenWhitelistInstruction::unpack(&instruction.data) {
+                Ok(TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id, expected_nonce})
+                    if rules::check_mutation_nonce(&state, expected_nonce).is_ok() =>
+                {
+                    state.add_keypair(&wallet, &allocation_amount);
+                    if campaign_id != NO_CAMPAIGN {
+                        state.set_campaign(&wallet, campaign_id);
+                    }
+                    state.record_add(account.data.len(), self.slot.get());
+                }
+                Ok(TokenWhitelistInstruction::RemoveFromWhitelist {expected_nonce, ..})
+                    if rules::check_mutation_nonce(&state, expected_nonce).is_ok() =>
+                {
+                    // the program writes nothing for a wallet that is not whitelisted
+                    if state.drop_key(&wallet).is_none() {
+                        continue;
+                    }
+                    state.record_remove(account.data.len(), self.slot.get());
+                }
+                _ => continue,
+            }
+            state.record_mutation(account.data.len());
+            state.update_entries_hash(account.data.len());
+            state.pack_into_slice(&mut account.data).unwrap();
+        }
+    }
+
+    /// Return data of the last GetVersion, GetRequiredRent under the default rent, or ListEntries
+    /// or ListEntriesByCampaign over a stored whitelist, instruction
+    fn return_data(&self, transaction: &VersionedTransaction) -> Option<TransactionReturnData> {
+        let keys = static_keys(transaction);
+        transaction.message.instructions().iter().rev().find_map(|instruction| {
+            let (campaign_id, offset, limit) = match TokenWhitelistInstruction::unpack(&instruction.data) {
+                Ok(TokenWhitelistInstruction::ListEntries {offset, limit}) => (None, offset, limit),
+                Ok(TokenWhitelistInstruction::ListEntriesByCampaign {campaign_id, offset, limit}) => {
+                    (Some(campaign_id), offset, limit)
+                }
+                Ok(TokenWhitelistInstruction::GetVersion {}) => {
+                    let program_id = *keys.get(instruction.program_id_index as usize)?;
+                    let data = ProgramVersion::current().try_to_vec().unwrap();
+                    return Some(TransactionReturnData { program_id, data });
+                }
+                Ok(TokenWhitelistInstruction::GetRequiredRent {max_whitelist_size}) => {
+                    let sized_entries = TokenWhitelist::check_max_whitelist_size(max_whitelist_size).ok()?;
+                    let program_id = *keys.get(instruction.program_id_index as usize)?;
+                    let space = TokenWhitelist::space_for(sized_entries);
+                    let required_rent = RequiredRent {space: space as u64, lamports: Rent::default().minimum_balance(space)};
+                    return Some(TransactionReturnData { program_id, data: required_rent.try_to_vec().unwrap() });
+                }
+                _ => return None,
+            };
+            let program_id = *keys.get(instruction.program_id_index as usize)?;
+            let whitelist = keys.get(*instruction.accounts.first()? as usize)?;
+            let accounts = self.accounts.borrow();
+            let account = accounts.get(whitelist).filter(|account| account.owner == program_id)?;
+            let state = TokenWhitelist::unpack_from_slice(&account.data).ok()?;
+            let page = match campaign_id {
+                Some(campaign_id) => state.campaign_entries_page(campaign_id, offset, limit),
+                None => state.entries_page(offset, limit),
+            };
+            let page = page.ok()?;
+            Some(TransactionReturnData { program_id, data: page.try_to_vec().unwrap() })
+        })
+    }
+
+    /// Counts an account read, storing the delayed accounts that are due and failing it while
+    /// reads are set to fail
+    fn read_account(&self, pubkey: &Pubkey) -> Result<Option<Account>, ClientError> {
+        self.account_reads.set(self.account_reads.get() + 1);
+        let mut delayed = self.delayed_accounts.borrow_mut();
+        delayed.retain_mut(|(reads, key, account)| {
+            if *reads > 0 {
+                *reads -= 1;
+                return true;
+            }
+            self.accounts.borrow_mut().insert(*key, account.clone());
+            false
+        });
+        if self.failing_reads.get() > 0 {
+            self.failing_reads.set(self.failing_reads.get() - 1);
+            let error = io::Error::new(io::ErrorKind::ConnectionRefused, "mock rpc unavailable");
+            return Err(RpcClientError::from(error).into());
+        }
+        Ok(self.accounts.borrow().get(pubkey).cloned())
+    }
+
+    /// Slot the next account read is answered at
+    fn context_slot(&self) -> u64 {
+        self.context_slots.borrow_mut().pop_front().unwrap_or_else(|| self.slot.get())
+    }
+
+    /// Runs the next queued behaviour, returning the signature once broadcast
+    fn broadcast(&self, transaction: &VersionedTransaction) -> Result<(Signature, MockSend), ClientError> {
+        let send = self.sends.borrow_mut().pop_front().unwrap_or(MockSend::Land);
+        if send == MockSend::Unavailable {
+            let error = io::Error::new(io::ErrorKind::ConnectionRefused, "mock rpc unavailable");
+            return Err(RpcClientError::from(error).into());
+        }
+        self.sent.borrow_mut().push(transaction.clone());
+        let signature = transaction.signatures[0];
+        match &send {
+            MockSend::Land => {
+                self.apply(transaction);
+                self.statuses.borrow_mut().insert(signature, Ok(()));
+            }
+            MockSend::LandUnobserved => self.apply(transaction),
+            MockSend::Drop => {
+                self.expired.borrow_mut().insert(*transaction.message.recent_blockhash());
+            }
+            MockSend::Fail(error) => {
+                self.statuses.borrow_mut().insert(signature, Err(error.clone()));
+            }
+            MockSend::Unavailable => unreachable!(),
+        }
+        Ok((signature, send))
+    }
+}
+
+impl WhitelistRpc for MockRpc {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Account, ClientError> {
+        self.read_account(pubkey)?
+            .ok_or_else(|| RpcClientError::from(io::Error::new(io::ErrorKind::NotFound, pubkey.to_string())).into())
+    }
+
+    fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>, ClientError> {
+        self.read_account(pubkey)
+    }
+
+    fn get_optional_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, u64), ClientError> {
+        Ok((self.read_account(pubkey)?, self.context_slot()))
+    }
+
+    /// Refused as a node does while its context slot is below `min_context_slot`
+    fn get_optional_account_with_config(
+        &self,
+        pubkey: &Pubkey,
+        config: ReadConfig,
+    ) -> Result<(Option<Account>, u64), ClientError> {
+        self.read_configs.borrow_mut().push(config);
+        let account = self.read_account(pubkey)?;
+        let slot = self.context_slot();
+        match config.min_context_slot {
+            Some(min_context_slot) if slot < min_context_slot => Err(RpcClientError::from(RpcError::RpcResponseError {
+                code: JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+                message: "Minimum context slot has not been reached".to_string(),
+                data: RpcResponseErrorData::Empty,
+            })
+            .into()),
+            _ => Ok((account, slot)),
+        }
+    }
+
+    fn get_slot(&self) -> Result<u64, ClientError> {
+        Ok(self.slot.get())
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Hash, ClientError> {
+        Ok(Hash::new_unique())
+    }
+
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool, ClientError> {
+        Ok(!self.expired.borrow().contains(blockhash))
+    }
+
+    fn get_finalized_slot(&self) -> Result<u64, ClientError> {
+        Ok(self.slot.get())
+    }
+
+    fn get_recent_prioritization_fees(&self, _accounts: &[Pubkey]) -> Result<Vec<u64>, ClientError> {
+        Ok(self.prioritization_fees.borrow().clone())
+    }
+
+    fn simulate(&self, transaction: &VersionedTransaction) -> Result<RpcSimulateTransactionResult, ClientError> {
+        self.simulated.borrow_mut().push(transaction.clone());
+        Ok(self.simulations.borrow_mut().pop_front().unwrap_or_else(|| RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(vec![]),
+            accounts: None,
+            units_consumed: Some(0),
+            return_data: self.return_data(transaction).map(Into::into),
+            inner_instructions: None,
+        }))
+    }
+
+    fn send(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError> {
+        Ok(self.broadcast(transaction)?.0)
+    }
+
+    fn send_and_confirm(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError> {
+        match self.broadcast(transaction)? {
+            (signature, MockSend::Land) => Ok(signature),
+            (_, MockSend::Fail(error)) => Err(RpcClientError::from(error).into()),
+            _ => Err(RpcClientError::from(TransactionError::BlockhashNotFound).into()),
+        }
+    }
+
+    fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<Result<(), TransactionError>>>, ClientError> {
+        let statuses = self.statuses.borrow();
+        Ok(signatures.iter().map(|signature| statuses.get(signature).cloned()).collect())
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<VersionedTransaction, ClientError> {
+        self.sent.borrow().iter()
+            .find(|transaction| transaction.signatures.first() == Some(signature))
+            .cloned()
+            .ok_or_else(|| RpcClientError::from(io::Error::new(io::ErrorKind::NotFound, signature.to_string())).into())
+    }
+}
+
+fn static_keys(transaction: &VersionedTransaction) -> Vec<Pubkey> {
+    match &transaction.message {
+        VersionedMessage::Legacy(message) => message.account_keys.clone(),
+        // lookups are not resolved, only static keys are seen
+        VersionedMessage::V0(message) => message.account_keys.clone(),
+    }
+}
@@ -0,0 +1,156 @@
+//! Durable nonces for admin transactions that wait on a slow approval before being sent
+
+use solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction, system_program};
+use solana_sdk::{
+    nonce::{state::Versions, State},
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use crate::client::{offline::build_unsigned_nonce_transaction, rpc::WhitelistRpc, ClientError};
+
+/// Sends of a nonce transaction, the nonce is fetched again before each
+const MAX_NONCE_ATTEMPTS: u32 = 3;
+
+/// Creates a rent exempt nonce account with `payer` as its authority
+pub fn create_nonce_for_whitelist_admin<R: WhitelistRpc>(rpc: &R, payer: &Keypair) -> Result<Pubkey, ClientError> {
+    let nonce_account = Keypair::new();
+    let lamports = Rent::default().minimum_balance(State::size());
+    let instructions =
+        system_instruction::create_nonce_account(&payer.pubkey(), &nonce_account.pubkey(), &payer.pubkey(), lamports);
+    let blockhash = rpc.get_latest_blockhash()?;
+    let transaction =
+        Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &[payer, &nonce_account], blockhash);
+    rpc.send_and_confirm(&transaction.into())?;
+    Ok(nonce_account.pubkey())
+}
+
+/// Nonce stored in the account, refused unless `nonce_authority` may advance it
+pub fn fetch_nonce<R: WhitelistRpc>(
+    rpc: &R,
+    nonce_account: &Pubkey,
+    nonce_authority: &Pubkey,
+) -> Result<Hash, ClientError> {
+    let account = rpc.get_account(nonce_account)?;
+    if account.owner != system_program::id() {
+        return Err(ClientError::InvalidAccount(*nonce_account));
+    }
+    let versions: Versions = bincode::deserialize(&account.data).map_err(|_| ClientError::InvalidAccount(*nonce_account))?;
+    match versions.state() {
+        State::Initialized(data) if data.authority == *nonce_authority => Ok(data.blockhash()),
+        _ => Err(ClientError::InvalidAccount(*nonce_account)),
+    }
+}
+
+/// Sends the instructions behind an advance of `nonce_account`, with its stored nonce as the
+/// blockhash. `nonce_authority` pays the fee, `signers` are the other keys the instructions need.
+/// A nonce advanced since it was fetched shows as an unknown blockhash, the transaction is then
+/// built again with the nonce fetched anew.
+pub fn send_with_durable_nonce<R: WhitelistRpc>(
+    rpc: &R,
+    nonce_account: &Pubkey,
+    nonce_authority: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<Signature, ClientError> {
+    let mut all_signers = vec![nonce_authority];
+    all_signers.extend_from_slice(signers);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let nonce = fetch_nonce(rpc, nonce_account, &nonce_authority.pubkey())?;
+        let mut transaction = build_unsigned_nonce_transaction(
+            instructions,
+            &nonce_authority.pubkey(),
+            nonce_account,
+            &nonce_authority.pubkey(),
+            nonce,
+        );
+        transaction.try_sign(&all_signers, nonce)?;
+        match rpc.send_and_confirm(&transaction.into()) {
+            Err(error) if attempt < MAX_NONCE_ATTEMPTS && is_stale_nonce(&error) => continue,
+            result => return result,
+        }
+    }
+}
+
+fn is_stale_nonce(error: &ClientError) -> bool {
+    match error {
+        ClientError::Rpc(error) => error.get_transaction_error() == Some(TransactionError::BlockhashNotFound),
+        ClientError::Simulation {transaction_error, ..} => *transaction_error == TransactionError::BlockhashNotFound,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::mock::{MockRpc, MockSend},
+        instruction::add_to_whitelist,
+    };
+    use solana_sdk::{
+        account::Account,
+        nonce::state::{Data, DurableNonce},
+    };
+
+    fn setup(authority: &Pubkey) -> (MockRpc, Pubkey, Hash) {
+        let rpc = MockRpc::new();
+        let nonce_account = Pubkey::new_unique();
+        let data = Data::new(*authority, DurableNonce::from_blockhash(&Hash::new_unique()), 5_000);
+        let nonce = data.blockhash();
+        let versions = Versions::new(State::Initialized(data));
+        rpc.set_account(nonce_account, Account {
+            lamports: 1_447_680,
+            data: bincode::serialize(&versions).unwrap(),
+            owner: system_program::id(),
+            ..Account::default()
+        });
+        (rpc, nonce_account, nonce)
+    }
+
+    #[test]
+    fn test_nonce_instruction_first() {
+        let owner = Keypair::new();
+        let (rpc, nonce_account, nonce) = setup(&owner.pubkey());
+        let add = add_to_whitelist(&Pubkey::new_unique(), &owner.pubkey(), &Pubkey::new_unique(), &Pubkey::new_unique(), 1);
+        send_with_durable_nonce(&rpc, &nonce_account, &owner, &[add], &[]).unwrap();
+
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 1);
+        let message = &sent[0].message;
+        assert_eq!(*message.recent_blockhash(), nonce);
+        let keys = message.static_account_keys();
+        let advance = &message.instructions()[0];
+        assert_eq!(keys[advance.program_id_index as usize], system_program::id());
+        assert_eq!(keys[advance.accounts[0] as usize], nonce_account);
+        assert_eq!(message.instructions().len(), 2);
+        assert!(sent[0].verify_with_results().iter().all(|verified| *verified));
+    }
+
+    #[test]
+    fn test_stale_nonce_refetched() {
+        let owner = Keypair::new();
+        let (rpc, nonce_account, nonce) = setup(&owner.pubkey());
+        rpc.push_send(MockSend::Fail(TransactionError::BlockhashNotFound));
+        send_with_durable_nonce(&rpc, &nonce_account, &owner, &[], &[]).unwrap();
+        assert_eq!(rpc.sent().len(), 2);
+        assert!(rpc.sent().iter().all(|transaction| *transaction.message.recent_blockhash() == nonce));
+
+        for _ in 0..MAX_NONCE_ATTEMPTS {
+            rpc.push_send(MockSend::Fail(TransactionError::BlockhashNotFound));
+        }
+        assert!(send_with_durable_nonce(&rpc, &nonce_account, &owner, &[], &[]).is_err());
+        assert_eq!(rpc.sent().len(), 2 + MAX_NONCE_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn test_fetch_nonce_checks_authority() {
+        let owner = Pubkey::new_unique();
+        let (rpc, nonce_account, nonce) = setup(&owner);
+        assert_eq!(fetch_nonce(&rpc, &nonce_account, &owner).unwrap(), nonce);
+        assert!(matches!(
+            fetch_nonce(&rpc, &nonce_account, &Pubkey::new_unique()),
+            Err(ClientError::InvalidAccount(_))
+        ));
+    }
+}
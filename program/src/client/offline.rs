@@ -0,0 +1,152 @@
+//! Offline signing: unsigned transactions exported as base64, signed elsewhere and put back together
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use solana_program::{hash::Hash, instruction::Instruction, message::Message, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Signature, Signer},
+    transaction::Transaction,
+};
+use crate::client::ClientError;
+
+/// A transaction to be signed later, for a recent blockhash. Every signature is left at its
+/// default until [apply_signature](fn.apply_signature.html) fills it in.
+pub fn build_unsigned_transaction(
+    instructions: &[Instruction],
+    fee_payer: &Pubkey,
+    recent_blockhash: Hash,
+) -> Transaction {
+    let message = Message::new_with_blockhash(instructions, Some(fee_payer), &recent_blockhash);
+    Transaction::new_unsigned(message)
+}
+
+/// A transaction to be signed later that does not expire: it advances `nonce_account` first and
+/// is valid for as long as the account stores `nonce`
+pub fn build_unsigned_nonce_transaction(
+    instructions: &[Instruction],
+    fee_payer: &Pubkey,
+    nonce_account: &Pubkey,
+    nonce_authority: &Pubkey,
+    nonce: Hash,
+) -> Transaction {
+    let mut message = Message::new_with_nonce(instructions.to_vec(), Some(fee_payer), nonce_account, nonce_authority);
+    message.recent_blockhash = nonce;
+    Transaction::new_unsigned(message)
+}
+
+/// Base64 of the wire encoding, signed or not
+pub fn encode_transaction(transaction: &Transaction) -> String {
+    BASE64_STANDARD.encode(bincode::serialize(transaction).unwrap())
+}
+
+/// Reads back an exported transaction, surrounding whitespace of a pasted one included
+pub fn decode_transaction(encoded: &str) -> Result<Transaction, ClientError> {
+    let data = BASE64_STANDARD.decode(encoded.trim()).map_err(|_| ClientError::InvalidEncoding)?;
+    bincode::deserialize(&data).map_err(|_| ClientError::InvalidEncoding)
+}
+
+/// Signs the message without touching the transaction, on the machine holding the key
+pub fn sign_offline<S: Signer + ?Sized>(transaction: &Transaction, signer: &S) -> Result<Signature, ClientError> {
+    Ok(signer.try_sign_message(&transaction.message_data())?)
+}
+
+/// Places a detached signature in the slot of `pubkey`, after checking that it signs the message
+pub fn apply_signature(
+    transaction: &mut Transaction,
+    pubkey: &Pubkey,
+    signature: Signature,
+) -> Result<(), ClientError> {
+    let signers = transaction.message.header.num_required_signatures as usize;
+    let index = transaction.message.account_keys[..signers].iter()
+        .position(|key| key == pubkey)
+        .ok_or(ClientError::InvalidSignature(*pubkey))?;
+    if !signature.verify(pubkey.as_ref(), &transaction.message_data()) {
+        return Err(ClientError::InvalidSignature(*pubkey));
+    }
+    transaction.signatures[index] = signature;
+    Ok(())
+}
+
+/// Signers whose signature is still missing, empty once the transaction can be broadcast
+pub fn missing_signers(transaction: &Transaction) -> Vec<Pubkey> {
+    transaction.signatures.iter()
+        .zip(&transaction.message.account_keys)
+        .filter(|(signature, _)| **signature == Signature::default())
+        .map(|(_, pubkey)| *pubkey)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::add_to_whitelist;
+    use solana_program::sanitize::Sanitize;
+    use solana_sdk::signature::Keypair;
+
+    fn add(owner: &Pubkey) -> Instruction {
+        add_to_whitelist(&Pubkey::new_unique(), owner, &Pubkey::new_unique(), &Pubkey::new_unique(), 100)
+    }
+
+    #[test]
+    fn test_offline_round_trip() {
+        let (owner, fee_payer) = (Keypair::new(), Keypair::new());
+        let unsigned = build_unsigned_transaction(&[add(&owner.pubkey())], &fee_payer.pubkey(), Hash::new_unique());
+        assert_eq!(missing_signers(&unsigned), vec![fee_payer.pubkey(), owner.pubkey()]);
+        let exported = encode_transaction(&unsigned);
+
+        // on the air-gapped machine only the exported text and the owner key are at hand
+        let offline = decode_transaction(&exported).unwrap();
+        let owner_signature = sign_offline(&offline, &owner).unwrap();
+
+        let mut transaction = decode_transaction(&exported).unwrap();
+        apply_signature(&mut transaction, &owner.pubkey(), owner_signature).unwrap();
+        assert_eq!(missing_signers(&transaction), vec![fee_payer.pubkey()]);
+        let fee_payer_signature = sign_offline(&transaction, &fee_payer).unwrap();
+        apply_signature(&mut transaction, &fee_payer.pubkey(), fee_payer_signature).unwrap();
+
+        let transaction = decode_transaction(&encode_transaction(&transaction)).unwrap();
+        transaction.sanitize().unwrap();
+        transaction.verify().unwrap();
+        assert_eq!(transaction.message, unsigned.message);
+    }
+
+    #[test]
+    fn test_apply_signature_checks_signer() {
+        let owner = Keypair::new();
+        let mut transaction = build_unsigned_transaction(&[add(&owner.pubkey())], &owner.pubkey(), Hash::new_unique());
+        let stranger = Keypair::new();
+        let signature = sign_offline(&transaction, &stranger).unwrap();
+        assert!(matches!(
+            apply_signature(&mut transaction, &stranger.pubkey(), signature),
+            Err(ClientError::InvalidSignature(_))
+        ));
+        // signed by the owner but over another message
+        let other = build_unsigned_transaction(&[], &owner.pubkey(), Hash::new_unique());
+        let signature = sign_offline(&other, &owner).unwrap();
+        assert!(matches!(
+            apply_signature(&mut transaction, &owner.pubkey(), signature),
+            Err(ClientError::InvalidSignature(_))
+        ));
+        assert_eq!(missing_signers(&transaction), vec![owner.pubkey()]);
+    }
+
+    #[test]
+    fn test_nonce_transaction() {
+        let owner = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let nonce = Hash::new_unique();
+        let transaction = build_unsigned_nonce_transaction(
+            &[add(&owner.pubkey())],
+            &owner.pubkey(),
+            &nonce_account,
+            &owner.pubkey(),
+            nonce,
+        );
+        assert_eq!(transaction.message.recent_blockhash, nonce);
+        let message = &transaction.message;
+        let first = &message.instructions[0];
+        assert_eq!(message.account_keys[first.program_id_index as usize], solana_program::system_program::id());
+        assert_eq!(message.account_keys[first.accounts[0] as usize], nonce_account);
+        assert_eq!(missing_signers(&transaction), vec![owner.pubkey()]);
+        assert!(matches!(decode_transaction("not base64!"), Err(ClientError::InvalidEncoding)));
+    }
+}
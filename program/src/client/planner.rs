@@ -0,0 +1,253 @@
+//! Planning adds into transactions that fit the packet size, the whitelist capacity and a unit budget
+
+use solana_program::{address_lookup_table::AddressLookupTableAccount, instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use crate::{
+    client::{
+        compute_budget::{reserved_budget_instructions, ComputeBudget, MAX_UNIT_LIMIT},
+        lookup_table::{transaction_size, MAX_TRANSACTION_ACCOUNTS},
+        ClientError,
+    },
+    instruction::add_to_whitelist,
+    state::{TokenWhitelist, MAP_ENTRY_OVERHEAD},
+};
+
+use std::collections::BTreeSet;
+
+/// Estimated units of an add to an empty whitelist
+const UNITS_PER_ADD: u64 = 20_000;
+/// Estimated units an add spends on every entry already in the map, decoded and encoded again
+const UNITS_PER_ENTRY: u64 = 400;
+
+/// What a plan has to respect, see [plan_batches](fn.plan_batches.html)
+#[derive(Clone, Debug)]
+pub struct BatchLimits {
+    pub program_id: Pubkey,
+    pub authority: Pubkey, // signs the adds and pays for the transactions
+    pub whitelist: Pubkey,
+    pub budget: ComputeBudget, // budget instructions reserved in every transaction
+    pub lookup_tables: Vec<AddressLookupTableAccount>, // v0 transactions when not empty, legacy otherwise
+    pub max_adds: usize, // adds per transaction
+    pub max_units: u64, // units per transaction, lowered to a fixed unit limit
+    pub units_per_add: u64,
+    pub units_per_entry: u64,
+}
+
+impl BatchLimits {
+    pub fn new(program_id: Pubkey, authority: Pubkey, whitelist: Pubkey) -> Self {
+        BatchLimits {
+            program_id,
+            authority,
+            whitelist,
+            budget: ComputeBudget::Default,
+            lookup_tables: vec![],
+            max_adds: usize::MAX,
+            max_units: MAX_UNIT_LIMIT as u64,
+            units_per_add: UNITS_PER_ADD,
+            units_per_entry: UNITS_PER_ENTRY,
+        }
+    }
+
+    fn unit_budget(&self) -> u64 {
+        match self.budget {
+            ComputeBudget::Fixed {unit_limit: Some(unit_limit), ..} => self.max_units.min(unit_limit as u64),
+            _ => self.max_units,
+        }
+    }
+
+    fn add(&self, wallet: &Pubkey, amount: u64) -> Instruction {
+        add_to_whitelist(&self.program_id, &self.authority, &self.whitelist, wallet, amount)
+    }
+}
+
+/// Adds sent together in one transaction
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Batch {
+    pub adds: Vec<(Pubkey, u64)>,
+    pub size: usize, // serialized transaction bytes, budget instructions included
+    pub units: u64, // estimated
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlanWarning {
+    /// A new entry past the entries or map bytes left, the program would reject it
+    NoCapacity,
+    /// Its add alone does not fit a transaction
+    TooLarge,
+    /// Its add alone is estimated over the unit budget of a transaction
+    OverUnitBudget,
+}
+
+/// Batches in sending order and the adds left out of them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchPlan {
+    pub batches: Vec<Batch>,
+    pub warnings: Vec<(Pubkey, PlanWarning)>,
+}
+
+/// Splits the adds, in order, into transactions within `limits`. Capacity is tracked across the
+/// whole plan, so an entry that would only be rejected in a later batch is left out with a
+/// warning instead. Repeating a wallet updates its entry and takes no more capacity.
+pub fn plan_batches(
+    current_state: &TokenWhitelist,
+    additions: &[(Pubkey, u64)],
+    limits: &BatchLimits,
+) -> Result<BatchPlan, ClientError> {
+    let budget = reserved_budget_instructions(limits.budget);
+    let unit_budget = limits.unit_budget();
+    let mut keys: BTreeSet<String> = current_state.whitelist_map.keys().cloned().collect();
+    let mut map_bytes = current_state.map_bytes_remaining();
+
+    let mut plan = BatchPlan::default();
+    let mut batch = Batch::default();
+    let mut instructions = budget.clone();
+    for (wallet, amount) in additions {
+        let key = wallet.to_string();
+        let is_new = !keys.contains(&key);
+        let entry_bytes = key.len() + MAP_ENTRY_OVERHEAD;
+        let at_limit = current_state.entry_limit().is_some_and(|limit| keys.len() as u64 >= limit);
+        if is_new && (at_limit || entry_bytes > map_bytes) {
+            plan.warnings.push((*wallet, PlanWarning::NoCapacity));
+            continue;
+        }
+        let units = limits.units_per_add.saturating_add(limits.units_per_entry.saturating_mul(keys.len() as u64));
+        if units > unit_budget {
+            plan.warnings.push((*wallet, PlanWarning::OverUnitBudget));
+            continue;
+        }
+
+        let instruction = limits.add(wallet, *amount);
+        let mut size = fitting_size(limits, &instructions, &instruction)?;
+        let full = batch.adds.len() >= limits.max_adds.max(1) || batch.units.saturating_add(units) > unit_budget;
+        if !batch.adds.is_empty() && (full || size.is_none()) {
+            plan.batches.push(std::mem::take(&mut batch));
+            instructions.truncate(budget.len());
+            size = fitting_size(limits, &instructions, &instruction)?;
+        }
+        let size = match size {
+            Some(size) => size,
+            None => {
+                plan.warnings.push((*wallet, PlanWarning::TooLarge));
+                continue;
+            }
+        };
+
+        instructions.push(instruction);
+        batch.adds.push((*wallet, *amount));
+        batch.size = size;
+        batch.units += units;
+        if is_new {
+            keys.insert(key);
+            map_bytes -= entry_bytes;
+        }
+    }
+    if !batch.adds.is_empty() {
+        plan.batches.push(batch);
+    }
+    Ok(plan)
+}
+
+/// Size of the transaction with the instruction appended, none when it no longer fits
+fn fitting_size(
+    limits: &BatchLimits,
+    instructions: &[Instruction],
+    instruction: &Instruction,
+) -> Result<Option<usize>, ClientError> {
+    let instructions: Vec<Instruction> = instructions.iter().chain(Some(instruction)).cloned().collect();
+    let (size, accounts) = transaction_size(&limits.authority, &instructions, &limits.lookup_tables)?;
+    Ok(Some(size).filter(|_| size <= PACKET_DATA_SIZE && accounts <= MAX_TRANSACTION_ACCOUNTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(max_whitelist_size: u64, existing: u8) -> (TokenWhitelist, BatchLimits) {
+        let state = TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size,
+            whitelist_map: (0..existing).map(|i| (Pubkey::new_from_array([i; 32]).to_string(), 1)).collect(),
+            ..TokenWhitelist::default()
+        };
+        let limits = BatchLimits::new(Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        (state, limits)
+    }
+
+    fn additions(count: usize) -> Vec<(Pubkey, u64)> {
+        (0..count).map(|_| (Pubkey::new_unique(), 100)).collect()
+    }
+
+    fn planned(plan: &BatchPlan) -> Vec<(Pubkey, u64)> {
+        plan.batches.iter().flat_map(|batch| batch.adds.iter().copied()).collect()
+    }
+
+    #[test]
+    fn test_plan_zero_additions() {
+        let (state, limits) = setup(10, 0);
+        assert_eq!(plan_batches(&state, &[], &limits).unwrap(), BatchPlan::default());
+    }
+
+    #[test]
+    fn test_plan_one_huge_batch() {
+        let (state, limits) = setup(1_000, 0);
+        let adds = additions(500);
+        let plan = plan_batches(&state, &adds, &limits).unwrap();
+
+        // the fixed map region runs out long before the 1000 entries
+        let capacity = state.map_bytes_remaining() / (adds[0].0.to_string().len() + MAP_ENTRY_OVERHEAD);
+        assert!(capacity < 500);
+        assert_eq!(planned(&plan), adds[..capacity]);
+        assert_eq!(plan.warnings.len(), 500 - capacity);
+        assert!(plan.warnings.iter().all(|(_, warning)| *warning == PlanWarning::NoCapacity));
+        assert!(plan.batches.len() > 1);
+        for batch in &plan.batches {
+            assert!(batch.size <= PACKET_DATA_SIZE);
+            assert!(batch.units <= limits.max_units);
+        }
+    }
+
+    #[test]
+    fn test_plan_exactly_at_capacity() {
+        let (state, limits) = setup(12, 2);
+        let adds = additions(10);
+        let plan = plan_batches(&state, &adds, &limits).unwrap();
+        assert_eq!(planned(&plan), adds);
+        assert!(plan.warnings.is_empty());
+
+        // one more new entry does not fit, updates of planned and existing ones still do
+        let existing = Pubkey::new_from_array([0; 32]);
+        let extra = Pubkey::new_unique();
+        let mut more = adds.clone();
+        more.extend([(extra, 100), (adds[0].0, 200), (existing, 300)]);
+        let plan = plan_batches(&state, &more, &limits).unwrap();
+        assert_eq!(plan.warnings, vec![(extra, PlanWarning::NoCapacity)]);
+        assert_eq!(planned(&plan).len(), 12);
+    }
+
+    #[test]
+    fn test_plan_unit_budget() {
+        let (state, mut limits) = setup(100, 10);
+        limits.budget = ComputeBudget::Fixed {unit_limit: Some(100_000), unit_price: None};
+        let plan = plan_batches(&state, &additions(10), &limits).unwrap();
+        assert!(plan.warnings.is_empty());
+        for batch in &plan.batches {
+            assert!(batch.units <= 100_000);
+        }
+        // 24_000 units for the first add, 24_400 for the second and so on
+        assert_eq!(plan.batches[0].adds.len(), 4);
+
+        limits.budget = ComputeBudget::Fixed {unit_limit: Some(10_000), unit_price: None};
+        let plan = plan_batches(&state, &additions(2), &limits).unwrap();
+        assert!(plan.batches.is_empty());
+        assert!(plan.warnings.iter().all(|(_, warning)| *warning == PlanWarning::OverUnitBudget));
+    }
+
+    #[test]
+    fn test_plan_max_adds() {
+        let (state, mut limits) = setup(100, 0);
+        limits.max_adds = 3;
+        let plan = plan_batches(&state, &additions(7), &limits).unwrap();
+        let sizes: Vec<usize> = plan.batches.iter().map(|batch| batch.adds.len()).collect();
+        assert_eq!(sizes, vec![3, 3, 1]);
+    }
+}
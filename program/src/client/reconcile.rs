@@ -0,0 +1,361 @@
+//! Syncing an on-chain whitelist to a desired set of entries
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use crate::{
+    client::{
+        compute_budget::{reserved_budget_instructions, ComputeBudget},
+        lookup_table::{fetch_lookup_table, plan_batches},
+        rpc::WhitelistRpc,
+        send_versioned_with_preflight, send_with_preflight, ClientError, PreflightOpts,
+    },
+    instruction::{add_to_whitelist_expecting, remove_from_whitelist_expecting},
+    state::{validate_key, TokenWhitelist},
+};
+
+use std::{collections::BTreeMap, fmt};
+
+/// Options for [reconcile](fn.reconcile.html)
+#[derive(Clone, Copy, Debug)]
+pub struct ReconcileOpts {
+    pub program_id: Pubkey,
+    pub dry_run: bool, // only compute the plan
+    pub max_removal_percent: u64, // of the current entries, above which the plan is refused
+    pub allow_mass_removal: bool, // skip the max_removal_percent check
+    pub batch_size: usize, // instructions per transaction
+    pub compute_budget: ComputeBudget, // applied to every batch
+    pub lookup_table: Option<Pubkey>, // send v0 transactions sized to fit, instead of batch_size
+}
+
+impl ReconcileOpts {
+    pub fn new(program_id: Pubkey) -> Self {
+        ReconcileOpts {
+            program_id,
+            dry_run: false,
+            max_removal_percent: 10,
+            allow_mass_removal: false,
+            batch_size: 5,
+            compute_budget: ComputeBudget::Default,
+            lookup_table: None,
+        }
+    }
+}
+
+/// Options for the official deployment at crate::id()
+impl Default for ReconcileOpts {
+    fn default() -> Self {
+        Self::new(crate::id())
+    }
+}
+
+/// Changes needed to turn the current whitelist into the desired one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReconcilePlan {
+    pub adds: Vec<(Pubkey, u64)>,
+    pub updates: Vec<(Pubkey, u64, u64)>, // account, current and desired allocation
+    pub removes: Vec<(Pubkey, u64)>,
+    pub skipped: Vec<String>, // entries whose key is not a pubkey, they cannot be removed
+    pub signatures: Vec<Signature>, // transactions sent for the plan, empty on a dry run
+    pub expected_nonce: u64, // mutation nonce of the whitelist planned against, 0 when it tracks none
+}
+
+impl ReconcilePlan {
+    pub fn is_empty(&self) -> bool {
+        self.adds.is_empty() && self.updates.is_empty() && self.removes.is_empty()
+    }
+
+    /// Instructions applying the plan, removals first so capacity is freed before adding. Each
+    /// expects the nonce the ones before it leave, so once another writer changed the whitelist
+    /// the rest of the plan fails with StaleWhitelistState instead of overwriting its changes.
+    pub fn instructions(&self, program_id: &Pubkey, owner: &Pubkey, whitelist: &Pubkey) -> Vec<Instruction> {
+        let removes = self.removes.iter()
+            .map(|(account, _)| (*account, None));
+        let updates = self.updates.iter()
+            .map(|&(account, _, amount)| (account, Some(amount)));
+        let adds = self.adds.iter()
+            .map(|&(account, amount)| (account, Some(amount)));
+        removes.chain(updates).chain(adds)
+            .enumerate()
+            .map(|(index, (account, amount))| {
+                let expected_nonce = match self.expected_nonce {
+                    0 => 0,
+                    nonce => nonce + index as u64,
+                };
+                match amount {
+                    None => remove_from_whitelist_expecting(program_id, owner, whitelist, &account, expected_nonce),
+                    Some(amount) => {
+                        add_to_whitelist_expecting(program_id, owner, whitelist, &account, amount, expected_nonce)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ReconcilePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            writeln!(f, "whitelist in sync")?;
+        }
+        for (account, amount) in &self.removes {
+            writeln!(f, "remove {} ({})", account, amount)?;
+        }
+        for (account, current, amount) in &self.updates {
+            writeln!(f, "update {} {} -> {}", account, current, amount)?;
+        }
+        for (account, amount) in &self.adds {
+            writeln!(f, "add    {} {}", account, amount)?;
+        }
+        for key in &self.skipped {
+            writeln!(f, "skip   {} (not a pubkey)", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the minimal set of adds, updates and removes, refusing mass removals
+pub fn plan_reconcile(
+    current: &TokenWhitelist,
+    desired: &BTreeMap<Pubkey, u64>,
+    opts: &ReconcileOpts,
+) -> Result<ReconcilePlan, ClientError> {
+    let mut plan = ReconcilePlan {expected_nonce: current.extension.mutation_nonce, ..ReconcilePlan::default()};
+    let mut current_entries = BTreeMap::new();
+    for (key, &amount) in current.whitelist_map.iter() {
+        match validate_key(key) {
+            Ok(account) => {
+                current_entries.insert(account, amount);
+            }
+            Err(_) => plan.skipped.push(key.clone()),
+        }
+    }
+
+    for (account, &amount) in desired.iter() {
+        match current_entries.get(account) {
+            None => plan.adds.push((*account, amount)),
+            Some(&current) if current != amount => plan.updates.push((*account, current, amount)),
+            Some(_) => {}
+        }
+    }
+    plan.removes = current_entries.into_iter()
+        .filter(|(account, _)| !desired.contains_key(account))
+        .collect();
+
+    let entries = current.whitelist_map.len() as u64;
+    let removals = plan.removes.len() as u64;
+    if !opts.allow_mass_removal && removals * 100 > opts.max_removal_percent * entries {
+        return Err(ClientError::MassRemoval { removals, entries });
+    }
+    Ok(plan)
+}
+
+/// Fetches the whitelist, plans the changes and, unless it is a dry run, sends them in batches.
+/// Re-running after a partial failure only sends what is still missing.
+pub fn reconcile<R: WhitelistRpc>(
+    rpc: &R,
+    owner: &Keypair,
+    whitelist: &Pubkey,
+    desired: &BTreeMap<Pubkey, u64>,
+    opts: ReconcileOpts,
+) -> Result<ReconcilePlan, ClientError> {
+    let data = rpc.get_account(whitelist)?.data;
+    let current = TokenWhitelist::unpack_from_slice(&data)
+        .map_err(|_| ClientError::InvalidAccount(*whitelist))?;
+    let mut plan = plan_reconcile(&current, desired, &opts)?;
+    if opts.dry_run {
+        return Ok(plan);
+    }
+
+    let preflight = PreflightOpts {
+        allow_overwrite: true,
+        compute_budget: opts.compute_budget,
+        ..PreflightOpts::new(opts.program_id)
+    };
+    let instructions = plan.instructions(&opts.program_id, &owner.pubkey(), whitelist);
+    match opts.lookup_table {
+        Some(lookup_table) => {
+            let lookup_tables = [fetch_lookup_table(rpc, &lookup_table)?];
+            let budget = reserved_budget_instructions(opts.compute_budget);
+            for batch in plan_batches(&owner.pubkey(), &budget, &instructions, &lookup_tables)? {
+                let signature = send_versioned_with_preflight(rpc, owner, &batch, &lookup_tables, preflight)?;
+                plan.signatures.push(signature);
+            }
+        }
+        None => {
+            for batch in instructions.chunks(opts.batch_size.max(1)) {
+                let signature = send_with_preflight(rpc, owner, batch, preflight)?;
+                plan.signatures.push(signature);
+            }
+        }
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::mock::MockRpc,
+        error::TokenWhitelistError,
+        instruction::{add_to_whitelist, remove_from_whitelist},
+    };
+
+    fn account(i: u8) -> Pubkey {
+        Pubkey::new_from_array([i; 32])
+    }
+
+    fn current(entries: &[(u8, u64)]) -> TokenWhitelist {
+        TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 100,
+            whitelist_map: entries.iter().map(|&(i, amount)| (account(i).to_string(), amount)).collect(),
+            ..TokenWhitelist::default()
+        }
+    }
+
+    fn desired(entries: &[(u8, u64)]) -> BTreeMap<Pubkey, u64> {
+        entries.iter().map(|&(i, amount)| (account(i), amount)).collect()
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let opts = ReconcileOpts {
+            batch_size: 2,
+            max_removal_percent: 50,
+            ..ReconcileOpts::new(Pubkey::new_unique())
+        };
+        let owner = Keypair::new();
+        let whitelist = Pubkey::new_unique();
+        let rpc = MockRpc::new();
+        rpc.set_whitelist(whitelist, opts.program_id, &TokenWhitelist {
+            init_pubkey: owner.pubkey(),
+            ..current(&[(1, 100), (2, 200), (3, 300)])
+        });
+        let desired = desired(&[(1, 100), (2, 250), (4, 400), (5, 500)]);
+
+        let plan = reconcile(&rpc, &owner, &whitelist, &desired, ReconcileOpts { dry_run: true, ..opts }).unwrap();
+        assert!(plan.signatures.is_empty());
+        assert!(rpc.sent().is_empty());
+
+        // one removal, one update and two adds in batches of two
+        let plan = reconcile(&rpc, &owner, &whitelist, &desired, opts).unwrap();
+        assert_eq!(plan.signatures.len(), 2);
+        let synced = plan_reconcile(&rpc.whitelist(&whitelist), &desired, &opts).unwrap();
+        assert!(synced.is_empty());
+    }
+
+    #[test]
+    fn test_plan_empty_to_full() {
+        let opts = ReconcileOpts::new(Pubkey::new_unique());
+        let plan = plan_reconcile(&current(&[]), &desired(&[(1, 100), (2, 200)]), &opts).unwrap();
+        assert_eq!(plan.adds, vec![(account(1), 100), (account(2), 200)]);
+        assert!(plan.updates.is_empty());
+        assert!(plan.removes.is_empty());
+    }
+
+    #[test]
+    fn test_plan_mixed() {
+        let opts = ReconcileOpts {
+            max_removal_percent: 50,
+            ..ReconcileOpts::new(Pubkey::new_unique())
+        };
+        let plan = plan_reconcile(
+            &current(&[(1, 100), (2, 200), (3, 300)]),
+            &desired(&[(1, 100), (2, 250), (4, 400)]),
+            &opts,
+        ).unwrap();
+        assert_eq!(plan.adds, vec![(account(4), 400)]);
+        assert_eq!(plan.updates, vec![(account(2), 200, 250)]);
+        assert_eq!(plan.removes, vec![(account(3), 300)]);
+
+        let owner = Pubkey::new_unique();
+        let whitelist = Pubkey::new_unique();
+        let instructions = plan.instructions(&opts.program_id, &owner, &whitelist);
+        assert_eq!(
+            instructions,
+            vec![
+                remove_from_whitelist(&opts.program_id, &owner, &whitelist, &account(3)),
+                add_to_whitelist(&opts.program_id, &owner, &whitelist, &account(2), 250),
+                add_to_whitelist(&opts.program_id, &owner, &whitelist, &account(4), 400),
+            ]
+        );
+
+        // planned against a whitelist tracking its writes, each expects the nonce left before it
+        let plan = ReconcilePlan {expected_nonce: 7, ..plan};
+        assert_eq!(
+            plan.instructions(&opts.program_id, &owner, &whitelist),
+            vec![
+                remove_from_whitelist_expecting(&opts.program_id, &owner, &whitelist, &account(3), 7),
+                add_to_whitelist_expecting(&opts.program_id, &owner, &whitelist, &account(2), 250, 8),
+                add_to_whitelist_expecting(&opts.program_id, &owner, &whitelist, &account(4), 400, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_after_other_writer() {
+        let opts = ReconcileOpts::new(Pubkey::new_unique());
+        let owner = Keypair::new();
+        let whitelist = Pubkey::new_unique();
+        let rpc = MockRpc::new();
+        let mut state = TokenWhitelist {init_pubkey: owner.pubkey(), ..current(&[(1, 100)])};
+        state.extension.mutation_nonce = 3;
+        rpc.set_whitelist(whitelist, opts.program_id, &state);
+
+        // two admins plan from the same snapshot, the first to send wins
+        let desired = desired(&[(1, 100), (2, 200)]);
+        let plan = reconcile(&rpc, &owner, &whitelist, &desired, ReconcileOpts { dry_run: true, ..opts }).unwrap();
+        assert_eq!(plan.expected_nonce, 3);
+        let other = add_to_whitelist_expecting(&opts.program_id, &owner.pubkey(), &whitelist, &account(3), 300, 3);
+        send_with_preflight(&rpc, &owner, &[other], PreflightOpts::new(opts.program_id)).unwrap();
+        assert_eq!(rpc.whitelist(&whitelist).extension.mutation_nonce, 4);
+
+        let instructions = plan.instructions(&opts.program_id, &owner.pubkey(), &whitelist);
+        assert!(matches!(
+            send_with_preflight(&rpc, &owner, &instructions, PreflightOpts::new(opts.program_id)),
+            Err(ClientError::Program(TokenWhitelistError::StaleWhitelistState))
+        ));
+
+        // planning again picks up the other change
+        let plan = reconcile(&rpc, &owner, &whitelist, &desired, ReconcileOpts { allow_mass_removal: true, ..opts })
+            .unwrap();
+        assert_eq!(plan.expected_nonce, 4);
+        assert_eq!(plan.removes, vec![(account(3), 300)]);
+        assert!(plan_reconcile(&rpc.whitelist(&whitelist), &desired, &opts).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan_in_sync() {
+        let opts = ReconcileOpts::new(Pubkey::new_unique());
+        let entries = [(1, 100), (2, 200)];
+        let plan = plan_reconcile(&current(&entries), &desired(&entries), &opts).unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.to_string(), "whitelist in sync\n");
+    }
+
+    #[test]
+    fn test_plan_mass_removal() {
+        let mut opts = ReconcileOpts::new(Pubkey::new_unique());
+        let entries: Vec<(u8, u64)> = (1..=20).map(|i| (i, 100)).collect();
+
+        // 2 of 20 is exactly the 10% limit
+        plan_reconcile(&current(&entries), &desired(&entries[2..]), &opts).unwrap();
+
+        let result = plan_reconcile(&current(&entries), &desired(&entries[3..]), &opts);
+        assert!(matches!(result, Err(ClientError::MassRemoval { removals: 3, entries: 20 })));
+
+        opts.allow_mass_removal = true;
+        let plan = plan_reconcile(&current(&entries), &desired(&[]), &opts).unwrap();
+        assert_eq!(plan.removes.len(), 20);
+    }
+
+    #[test]
+    fn test_plan_skips_foreign_keys() {
+        let opts = ReconcileOpts::new(Pubkey::new_unique());
+        let mut whitelist = current(&[(1, 100)]);
+        whitelist.whitelist_map.insert("not a pubkey".to_string(), 5);
+        let plan = plan_reconcile(&whitelist, &desired(&[(1, 100)]), &opts).unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.skipped, vec!["not a pubkey".to_string()]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Sizing a new whitelist account from a simulated GetRequiredRent, and keeping a whitelist
+//! rent exempt after it grew, so it is never collected
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use borsh::BorshDeserialize;
+use solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey, rent::Rent, system_instruction};
+use solana_sdk::{
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+use crate::{
+    client::{rpc::WhitelistRpc, simulate, ClientError},
+    instruction::{get_required_rent, top_up_rent},
+    state::{RequiredRent, TokenWhitelist},
+};
+
+/// Size and rent exempt balance the program at `program_id` asks of a whitelist of up to
+/// `max_whitelist_size` entries, answered by a simulated GetRequiredRent under the cluster rent.
+/// `payer` only pays the fee of the simulated transaction, it must exist but never signs.
+pub fn required_rent<R: WhitelistRpc>(
+    rpc: &R,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    max_whitelist_size: u64,
+) -> Result<RequiredRent, ClientError> {
+    TokenWhitelist::check_max_whitelist_size(max_whitelist_size)?;
+    let instruction = get_required_rent(program_id, max_whitelist_size);
+    let message = Message::new_with_blockhash(&[instruction], Some(payer), &rpc.get_latest_blockhash()?);
+    let result = simulate(rpc, &Transaction::new_unsigned(message).into())?;
+    let return_data = result.return_data.ok_or(ClientError::InvalidReturnData)?;
+    if return_data.program_id != program_id.to_string() {
+        return Err(ClientError::InvalidReturnData);
+    }
+    let data = BASE64_STANDARD.decode(&return_data.data.0).map_err(|_| ClientError::InvalidReturnData)?;
+    RequiredRent::try_from_slice(&data).map_err(|_| ClientError::InvalidReturnData)
+}
+
+/// Creates a `create_account` instruction funded by `payer` allocating `whitelist` to the
+/// program at the size and balance from [required_rent](fn.required_rent.html), ready for an
+/// InitTokenWhitelist of `max_whitelist_size`. The new account signs along with `payer`.
+pub fn create_whitelist_account<R: WhitelistRpc>(
+    rpc: &R,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    whitelist: &Pubkey,
+    max_whitelist_size: u64,
+) -> Result<Instruction, ClientError> {
+    let RequiredRent {space, lamports} = required_rent(rpc, program_id, payer, max_whitelist_size)?;
+    Ok(system_instruction::create_account(payer, whitelist, lamports, space, program_id))
+}
+
+/// Lamports `whitelist` lacks to be rent exempt at its current size under `rent`
+pub fn rent_shortfall<R: WhitelistRpc>(rpc: &R, whitelist: &Pubkey, rent: &Rent) -> Result<u64, ClientError> {
+    let account = rpc.get_account(whitelist)?;
+    Ok(rent.minimum_balance(account.data.len()).saturating_sub(account.lamports))
+}
+
+/// Sends a TopUpRent paid by `payer` when the whitelist is short of rent exemption, returning
+/// None without sending anything otherwise. The program the whitelist belongs to is taken from
+/// its owner, and the program computes the amount again against the cluster rent.
+pub fn ensure_rent_exempt<R: WhitelistRpc>(
+    rpc: &R,
+    payer: &Keypair,
+    whitelist: &Pubkey,
+) -> Result<Option<Signature>, ClientError> {
+    let account = rpc.get_account(whitelist)?;
+    if Rent::default().is_exempt(account.lamports, account.data.len()) {
+        return Ok(None);
+    }
+    let instruction = top_up_rent(&account.owner, &payer.pubkey(), whitelist);
+    let blockhash = rpc.get_latest_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&payer.pubkey()), &[payer], blockhash);
+    rpc.send_and_confirm(&transaction.into()).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::mock::MockRpc, error::TokenWhitelistError, instruction::TokenWhitelistInstruction};
+    use solana_sdk::account::Account;
+
+    fn whitelist_account(rpc: &MockRpc, program_id: &Pubkey, len: usize, lamports: u64) -> Pubkey {
+        let whitelist = Pubkey::new_unique();
+        rpc.set_account(whitelist, Account {lamports, data: vec![0; len], owner: *program_id, ..Account::default()});
+        whitelist
+    }
+
+    #[test]
+    fn test_create_whitelist_account_sized_by_program() {
+        let rpc = MockRpc::new();
+        let (program_id, payer, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let space = TokenWhitelist::space_for(50);
+        let expected = RequiredRent {space: space as u64, lamports: Rent::default().minimum_balance(space)};
+        assert_eq!(required_rent(&rpc, &program_id, &payer, 50).unwrap(), expected);
+
+        let instruction = create_whitelist_account(&rpc, &program_id, &payer, &whitelist, 50).unwrap();
+        assert_eq!(
+            instruction,
+            system_instruction::create_account(&payer, &whitelist, expected.lamports, expected.space, &program_id)
+        );
+        let simulated = rpc.simulated();
+        assert_eq!(simulated.len(), 2);
+        assert_eq!(
+            TokenWhitelistInstruction::unpack(&simulated[1].message.instructions()[0].data).unwrap(),
+            TokenWhitelistInstruction::GetRequiredRent {max_whitelist_size: 50}
+        );
+    }
+
+    #[test]
+    fn test_required_rent_refuses_size_above_ceiling() {
+        let rpc = MockRpc::new();
+        let max_size = TokenWhitelist::MAX_ENTRIES;
+        assert!(matches!(
+            required_rent(&rpc, &Pubkey::new_unique(), &Pubkey::new_unique(), max_size + 1),
+            Err(ClientError::Program(TokenWhitelistError::TokenWhitelistSizeExceeds))
+        ));
+        assert!(matches!(
+            required_rent(&rpc, &Pubkey::new_unique(), &Pubkey::new_unique(), 0),
+            Err(ClientError::Program(TokenWhitelistError::ZeroWhitelistSize))
+        ));
+        assert!(rpc.simulated().is_empty());
+    }
+
+    #[test]
+    fn test_exempt_whitelist_left_alone() {
+        let rpc = MockRpc::new();
+        let rent = Rent::default();
+        let whitelist = whitelist_account(&rpc, &Pubkey::new_unique(), 10240, rent.minimum_balance(10240));
+        assert_eq!(rent_shortfall(&rpc, &whitelist, &rent).unwrap(), 0);
+        assert_eq!(ensure_rent_exempt(&rpc, &Keypair::new(), &whitelist).unwrap(), None);
+        assert!(rpc.sent().is_empty());
+    }
+
+    #[test]
+    fn test_grown_whitelist_topped_up() {
+        let rpc = MockRpc::new();
+        let rent = Rent::default();
+        let program_id = Pubkey::new_unique();
+        // grown from 10240 bytes without the lamports to match
+        let whitelist = whitelist_account(&rpc, &program_id, 20480, rent.minimum_balance(10240));
+        assert_eq!(
+            rent_shortfall(&rpc, &whitelist, &rent).unwrap(),
+            rent.minimum_balance(20480) - rent.minimum_balance(10240)
+        );
+
+        let payer = Keypair::new();
+        assert!(ensure_rent_exempt(&rpc, &payer, &whitelist).unwrap().is_some());
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 1);
+        let message = &sent[0].message;
+        let keys = message.static_account_keys();
+        let instruction = &message.instructions()[0];
+        assert_eq!(keys[instruction.program_id_index as usize], program_id);
+        assert_eq!(TokenWhitelistInstruction::unpack(&instruction.data).unwrap(), TokenWhitelistInstruction::TopUpRent {});
+        assert_eq!(keys[instruction.accounts[0] as usize], payer.pubkey());
+        assert_eq!(keys[instruction.accounts[1] as usize], whitelist);
+    }
+
+    #[test]
+    fn test_missing_whitelist() {
+        let rpc = MockRpc::new();
+        assert!(ensure_rent_exempt(&rpc, &Keypair::new(), &Pubkey::new_unique()).is_err());
+        assert!(rpc.sent().is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+//! The RPC calls the client helpers make, so they run against any client or a mock
+
+use solana_client::{
+    nonblocking::rpc_client::RpcClient as NonblockingRpcClient,
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcTransactionConfig},
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_account_decoder::UiAccountEncoding;
+use solana_program::{hash::Hash, pubkey::Pubkey};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    signature::Signature,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use crate::client::ClientError;
+
+use std::future::Future;
+
+/// How an account is read, see
+/// [get_optional_account_with_config](trait.WhitelistRpc.html#tymethod.get_optional_account_with_config)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReadConfig {
+    /// Commitment of the read, None for the commitment the client was built with
+    pub commitment: Option<CommitmentConfig>,
+    /// The node refuses the read until it reached this slot
+    pub min_context_slot: Option<u64>,
+}
+
+pub trait WhitelistRpc {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Account, ClientError>;
+    /// The account at the client commitment, `None` when it does not exist
+    fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>, ClientError>;
+    /// Like `get_optional_account`, with the slot the node answered at
+    fn get_optional_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, u64), ClientError>;
+    /// Like `get_optional_account_with_slot`, at the commitment and minimum context slot of
+    /// `config`
+    fn get_optional_account_with_config(
+        &self,
+        pubkey: &Pubkey,
+        config: ReadConfig,
+    ) -> Result<(Option<Account>, u64), ClientError>;
+    /// Most recent slot at the client commitment
+    fn get_slot(&self) -> Result<u64, ClientError>;
+    fn get_latest_blockhash(&self) -> Result<Hash, ClientError>;
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool, ClientError>;
+    /// Most recent finalized slot
+    fn get_finalized_slot(&self) -> Result<u64, ClientError>;
+    /// Prioritization fees of recent slots paid to write any of the accounts
+    fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>, ClientError>;
+    fn simulate(&self, transaction: &VersionedTransaction) -> Result<RpcSimulateTransactionResult, ClientError>;
+    /// Broadcasts without waiting for confirmation
+    fn send(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError>;
+    fn send_and_confirm(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError>;
+    /// Result of each transaction once it reaches the client commitment, `None` until then
+    fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<Result<(), TransactionError>>>, ClientError>;
+    /// A confirmed transaction, legacy or v0
+    fn get_transaction(&self, signature: &Signature) -> Result<VersionedTransaction, ClientError>;
+}
+
+impl WhitelistRpc for RpcClient {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Account, ClientError> {
+        Ok(RpcClient::get_account(self, pubkey)?)
+    }
+
+    fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>, ClientError> {
+        Ok(self.get_account_with_commitment(pubkey, self.commitment())?.value)
+    }
+
+    fn get_optional_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, u64), ClientError> {
+        let response = self.get_account_with_commitment(pubkey, self.commitment())?;
+        Ok((response.value, response.context.slot))
+    }
+
+    fn get_optional_account_with_config(
+        &self,
+        pubkey: &Pubkey,
+        config: ReadConfig,
+    ) -> Result<(Option<Account>, u64), ClientError> {
+        let response = self.get_account_with_config(pubkey, account_info_config(config, self.commitment()))?;
+        Ok((response.value, response.context.slot))
+    }
+
+    fn get_slot(&self) -> Result<u64, ClientError> {
+        Ok(RpcClient::get_slot(self)?)
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Hash, ClientError> {
+        Ok(RpcClient::get_latest_blockhash(self)?)
+    }
+
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool, ClientError> {
+        Ok(RpcClient::is_blockhash_valid(self, blockhash, self.commitment())?)
+    }
+
+    fn get_finalized_slot(&self) -> Result<u64, ClientError> {
+        Ok(self.get_slot_with_commitment(CommitmentConfig::finalized())?)
+    }
+
+    fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>, ClientError> {
+        let fees = RpcClient::get_recent_prioritization_fees(self, accounts)?;
+        Ok(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+    }
+
+    fn simulate(&self, transaction: &VersionedTransaction) -> Result<RpcSimulateTransactionResult, ClientError> {
+        Ok(self.simulate_transaction(transaction)?.value)
+    }
+
+    fn send(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError> {
+        Ok(self.send_transaction(transaction)?)
+    }
+
+    fn send_and_confirm(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError> {
+        Ok(self.send_and_confirm_transaction(transaction)?)
+    }
+
+    fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<Result<(), TransactionError>>>, ClientError> {
+        let statuses = RpcClient::get_signature_statuses(self, signatures)?.value;
+        Ok(statuses.into_iter()
+            .map(|status| {
+                status.filter(|status| status.satisfies_commitment(self.commitment()))
+                    .map(|status| status.status)
+            })
+            .collect())
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<VersionedTransaction, ClientError> {
+        let transaction = self.get_transaction_with_config(signature, transaction_config(self.commitment()))?;
+        decode_transaction(signature, transaction)
+    }
+}
+
+/// Blocks the current thread on each call, so it must be used from within a multi-threaded
+/// tokio runtime
+impl WhitelistRpc for NonblockingRpcClient {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Account, ClientError> {
+        Ok(block_on(NonblockingRpcClient::get_account(self, pubkey))?)
+    }
+
+    fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>, ClientError> {
+        Ok(block_on(self.get_account_with_commitment(pubkey, self.commitment()))?.value)
+    }
+
+    fn get_optional_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, u64), ClientError> {
+        let response = block_on(self.get_account_with_commitment(pubkey, self.commitment()))?;
+        Ok((response.value, response.context.slot))
+    }
+
+    fn get_optional_account_with_config(
+        &self,
+        pubkey: &Pubkey,
+        config: ReadConfig,
+    ) -> Result<(Option<Account>, u64), ClientError> {
+        let config = account_info_config(config, self.commitment());
+        let response = block_on(self.get_account_with_config(pubkey, config))?;
+        Ok((response.value, response.context.slot))
+    }
+
+    fn get_slot(&self) -> Result<u64, ClientError> {
+        Ok(block_on(NonblockingRpcClient::get_slot(self))?)
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Hash, ClientError> {
+        Ok(block_on(NonblockingRpcClient::get_latest_blockhash(self))?)
+    }
+
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool, ClientError> {
+        Ok(block_on(NonblockingRpcClient::is_blockhash_valid(self, blockhash, self.commitment()))?)
+    }
+
+    fn get_finalized_slot(&self) -> Result<u64, ClientError> {
+        Ok(block_on(self.get_slot_with_commitment(CommitmentConfig::finalized()))?)
+    }
+
+    fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>, ClientError> {
+        let fees = block_on(NonblockingRpcClient::get_recent_prioritization_fees(self, accounts))?;
+        Ok(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+    }
+
+    fn simulate(&self, transaction: &VersionedTransaction) -> Result<RpcSimulateTransactionResult, ClientError> {
+        Ok(block_on(self.simulate_transaction(transaction))?.value)
+    }
+
+    fn send(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError> {
+        Ok(block_on(self.send_transaction(transaction))?)
+    }
+
+    fn send_and_confirm(&self, transaction: &VersionedTransaction) -> Result<Signature, ClientError> {
+        Ok(block_on(self.send_and_confirm_transaction(transaction))?)
+    }
+
+    fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<Result<(), TransactionError>>>, ClientError> {
+        let statuses = block_on(NonblockingRpcClient::get_signature_statuses(self, signatures))?.value;
+        Ok(statuses.into_iter()
+            .map(|status| {
+                status.filter(|status| status.satisfies_commitment(self.commitment()))
+                    .map(|status| status.status)
+            })
+            .collect())
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<VersionedTransaction, ClientError> {
+        let config = transaction_config(self.commitment());
+        let transaction = block_on(self.get_transaction_with_config(signature, config))?;
+        decode_transaction(signature, transaction)
+    }
+}
+
+fn account_info_config(config: ReadConfig, client_commitment: CommitmentConfig) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: None,
+        commitment: Some(config.commitment.unwrap_or(client_commitment)),
+        min_context_slot: config.min_context_slot,
+    }
+}
+
+fn transaction_config(commitment: CommitmentConfig) -> RpcTransactionConfig {
+    RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+fn decode_transaction(
+    signature: &Signature,
+    transaction: EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<VersionedTransaction, ClientError> {
+    transaction.transaction.transaction.decode().ok_or(ClientError::InvalidTransaction(*signature))
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
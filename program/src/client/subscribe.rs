@@ -0,0 +1,254 @@
+//! Streams of decoded whitelist changes over the pubsub websocket
+
+use futures::{channel::mpsc, Stream, StreamExt};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_config::RpcAccountInfoConfig,
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+use crate::state::TokenWhitelist;
+
+use std::time::Duration;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// One observed change of a whitelist account
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhitelistUpdate {
+    pub slot: u64,
+    pub state: TokenWhitelist,
+    pub changes: Vec<WhitelistChange>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WhitelistChange {
+    /// New entries with their allocation
+    EntriesAdded(Vec<(String, u64)>),
+    /// Dropped entries with the allocation they had
+    EntriesRemoved(Vec<(String, u64)>),
+    /// Entries whose allocation went from the first to the second amount
+    AllocationsChanged(Vec<(String, u64, u64)>),
+}
+
+/// Compares the entries of two snapshots, empty change kinds are left out
+pub fn diff_whitelists(before: &TokenWhitelist, after: &TokenWhitelist) -> Vec<WhitelistChange> {
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for (key, &amount) in after.whitelist_map.iter() {
+        match before.whitelist_map.get(key) {
+            None => added.push((key.clone(), amount)),
+            Some(&previous) if previous != amount => changed.push((key.clone(), previous, amount)),
+            Some(_) => {}
+        }
+    }
+    let removed: Vec<(String, u64)> = before.whitelist_map.iter()
+        .filter(|(key, _)| !after.whitelist_map.contains_key(*key))
+        .map(|(key, &amount)| (key.clone(), amount))
+        .collect();
+
+    let mut changes = Vec::new();
+    if !added.is_empty() {
+        changes.push(WhitelistChange::EntriesAdded(added));
+    }
+    if !removed.is_empty() {
+        changes.push(WhitelistChange::EntriesRemoved(removed));
+    }
+    if !changed.is_empty() {
+        changes.push(WhitelistChange::AllocationsChanged(changed));
+    }
+    changes
+}
+
+/// Subscribes to a whitelist account and yields an update every time its entries change.
+///
+/// The baseline is fetched over `rpc_url` when subscribing and again after every reconnect,
+/// so changes made while the socket was down are reported on the next fetch. Must be called
+/// within a tokio runtime; dropping the stream ends the subscription.
+pub fn subscribe_whitelist(
+    rpc_url: String,
+    ws_url: String,
+    whitelist_pubkey: Pubkey,
+) -> impl Stream<Item = WhitelistUpdate> {
+    let (sender, receiver) = mpsc::unbounded();
+    tokio::spawn(run_subscription(rpc_url, ws_url, whitelist_pubkey, sender));
+    receiver
+}
+
+async fn run_subscription(
+    rpc_url: String,
+    ws_url: String,
+    whitelist_pubkey: Pubkey,
+    sender: mpsc::UnboundedSender<WhitelistUpdate>,
+) {
+    let mut snapshot: Option<TokenWhitelist> = None;
+    follow_account(rpc_url, ws_url, whitelist_pubkey, CommitmentConfig::default(), |feed| {
+        if let AccountFeed::Data {slot, data} = feed {
+            observe(&mut snapshot, slot, data, &sender);
+        }
+        !sender.is_closed()
+    })
+    .await;
+}
+
+/// What [follow_account](fn.follow_account.html) passes on of an account
+pub(crate) enum AccountFeed<'a> {
+    Data {slot: u64, data: &'a [u8]},
+    /// The socket dropped or could not subscribe, changes may be missed until the account is
+    /// fetched again after reconnecting
+    Disconnected,
+}
+
+/// Follows an account over the websocket, reconnecting whenever the socket drops, until `feed`
+/// returns false. The account is fetched after every subscription, subscribing first so
+/// nothing falls between the fetch and the stream; a change made while disconnected reaches
+/// `feed` with that fetch.
+pub(crate) async fn follow_account<F>(
+    rpc_url: String,
+    ws_url: String,
+    pubkey: Pubkey,
+    commitment: CommitmentConfig,
+    mut feed: F,
+) where
+    F: FnMut(AccountFeed) -> bool,
+{
+    let rpc = RpcClient::new_with_commitment(rpc_url, commitment);
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(commitment),
+        ..RpcAccountInfoConfig::default()
+    };
+
+    loop {
+        if let Ok(pubsub) = PubsubClient::new(&ws_url).await {
+            if let Ok((mut notifications, unsubscribe)) = pubsub.account_subscribe(&pubkey, Some(config.clone())).await {
+                let mut open = true;
+                if let Ok(response) = rpc.get_account_with_commitment(&pubkey, commitment).await {
+                    if let Some(account) = response.value {
+                        open = feed(AccountFeed::Data {slot: response.context.slot, data: &account.data});
+                    }
+                }
+                while open {
+                    let response = match notifications.next().await {
+                        Some(response) => response,
+                        None => break,
+                    };
+                    if let Some(data) = response.value.data.decode() {
+                        open = feed(AccountFeed::Data {slot: response.context.slot, data: &data});
+                    }
+                }
+                unsubscribe().await;
+                if !open {
+                    return;
+                }
+            }
+        }
+        if !feed(AccountFeed::Disconnected) {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Decodes account data and sends the diff against the previous snapshot, the first
+/// snapshot only seeds the baseline
+fn observe(
+    snapshot: &mut Option<TokenWhitelist>,
+    slot: u64,
+    data: &[u8],
+    sender: &mpsc::UnboundedSender<WhitelistUpdate>,
+) {
+    let state = match TokenWhitelist::unpack_from_slice(data) {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+    if let Some(previous) = snapshot.replace(state.clone()) {
+        let changes = diff_whitelists(&previous, &state);
+        if !changes.is_empty() {
+            let _ = sender.unbounded_send(WhitelistUpdate { slot, state, changes });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn whitelist(entries: &[(&str, u64)]) -> TokenWhitelist {
+        TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 10,
+            whitelist_map: entries.iter().map(|(key, amount)| (key.to_string(), *amount)).collect(),
+            ..TokenWhitelist::default()
+        }
+    }
+
+    fn pack(state: &TokenWhitelist) -> Vec<u8> {
+        let mut data = vec![0; 5161];
+        state.pack_into_slice(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_diff_whitelists() {
+        let before = whitelist(&[("a", 100), ("b", 200), ("c", 300)]);
+        let after = whitelist(&[("a", 100), ("b", 250), ("d", 400)]);
+        assert_eq!(
+            diff_whitelists(&before, &after),
+            vec![
+                WhitelistChange::EntriesAdded(vec![("d".to_string(), 400)]),
+                WhitelistChange::EntriesRemoved(vec![("c".to_string(), 300)]),
+                WhitelistChange::AllocationsChanged(vec![("b".to_string(), 200, 250)]),
+            ]
+        );
+        assert_eq!(diff_whitelists(&after, &after), vec![]);
+        assert_eq!(
+            diff_whitelists(&whitelist(&[]), &whitelist(&[("a", 1)])),
+            vec![WhitelistChange::EntriesAdded(vec![("a".to_string(), 1)])]
+        );
+    }
+
+    #[test]
+    fn test_observe() {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let mut snapshot = None;
+
+        // the first snapshot only seeds the baseline
+        observe(&mut snapshot, 1, &pack(&whitelist(&[("a", 100)])), &sender);
+        assert!(receiver.try_recv().is_err());
+
+        // an unchanged account, e.g. the fetch after a reconnect, sends nothing
+        observe(&mut snapshot, 2, &pack(&whitelist(&[("a", 100)])), &sender);
+        assert!(receiver.try_recv().is_err());
+
+        observe(&mut snapshot, 3, &pack(&whitelist(&[])), &sender);
+        let update = receiver.try_recv().unwrap();
+        assert_eq!(update.slot, 3);
+        assert_eq!(
+            update.changes,
+            vec![WhitelistChange::EntriesRemoved(vec![("a".to_string(), 100)])]
+        );
+    }
+
+    // Needs a local validator with an initialized whitelist, e.g.
+    // WHITELIST=<pubkey> cargo test --features client -- --ignored
+    #[test]
+    #[ignore]
+    fn test_subscribe_live() {
+        let whitelist_pubkey: Pubkey = std::env::var("WHITELIST").unwrap().parse().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut updates = subscribe_whitelist(
+                "http://127.0.0.1:8899".to_string(),
+                "ws://127.0.0.1:8900".to_string(),
+                whitelist_pubkey,
+            );
+            let update = tokio::time::timeout(Duration::from_secs(60), updates.next())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(!update.changes.is_empty());
+        });
+    }
+}
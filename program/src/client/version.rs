@@ -0,0 +1,65 @@
+//! Detecting the version and capabilities of a deployment from a simulated GetVersion
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use borsh::BorshDeserialize;
+use solana_program::{message::Message, pubkey::Pubkey};
+use solana_sdk::transaction::Transaction;
+use crate::{
+    client::{rpc::WhitelistRpc, simulate, ClientError},
+    error::TokenWhitelistError,
+    instruction::get_version,
+    state::ProgramVersion,
+};
+
+/// Version and feature bits of the program deployed at `program_id`. Deployments predating
+/// GetVersion reject it as an invalid instruction and are reported without any feature bits.
+/// `payer` only pays the fee of the simulated transaction, it must exist but never signs.
+pub fn detect_features<R: WhitelistRpc>(
+    rpc: &R,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+) -> Result<ProgramVersion, ClientError> {
+    let message = Message::new_with_blockhash(&[get_version(program_id)], Some(payer), &rpc.get_latest_blockhash()?);
+    let result = match simulate(rpc, &Transaction::new_unsigned(message).into()) {
+        Err(ClientError::Program(TokenWhitelistError::InvalidInstruction)) => return Ok(ProgramVersion::default()),
+        result => result?,
+    };
+    let return_data = result.return_data.ok_or(ClientError::InvalidReturnData)?;
+    if return_data.program_id != program_id.to_string() {
+        return Err(ClientError::InvalidReturnData);
+    }
+    let data = BASE64_STANDARD.decode(&return_data.data.0).map_err(|_| ClientError::InvalidReturnData)?;
+    ProgramVersion::try_from_slice(&data).map_err(|_| ClientError::InvalidReturnData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::mock::MockRpc, RETURN_DATA, VERSION};
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+    #[test]
+    fn test_detect_features() {
+        let rpc = MockRpc::new();
+        let version = detect_features(&rpc, &Pubkey::new_unique(), &Pubkey::new_unique()).unwrap();
+        assert_eq!(version.semver, VERSION);
+        assert!(version.has(RETURN_DATA));
+    }
+
+    #[test]
+    fn test_detect_features_before_get_version() {
+        let rpc = MockRpc::new();
+        rpc.push_simulation(RpcSimulateTransactionResult {
+            err: Some(TransactionError::InstructionError(0, InstructionError::Custom(0))),
+            logs: Some(vec![]),
+            accounts: None,
+            units_consumed: Some(0),
+            return_data: None,
+            inner_instructions: None,
+        });
+        let version = detect_features(&rpc, &Pubkey::new_unique(), &Pubkey::new_unique()).unwrap();
+        assert_eq!(version, ProgramVersion::default());
+        assert!(!version.has(RETURN_DATA));
+    }
+}
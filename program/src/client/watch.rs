@@ -0,0 +1,329 @@
+//! Change feed of a whitelist for `token-whitelist-cli watch`: every change as a line of text
+//! or JSON, and a resync marker wherever the feed may have missed changes
+
+use futures::{channel::mpsc, Stream};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+use crate::state::TokenWhitelist;
+
+use super::{
+    diff::{diff_whitelists, WhitelistDiff},
+    subscribe::{follow_account, AccountFeed},
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchEvent {
+    /// The account was fetched again after the socket dropped. Changes made meanwhile are
+    /// folded into the next Changed, intermediate states are lost.
+    Resync {slot: u64},
+    Changed {slot: u64, diff: WhitelistDiff},
+}
+
+/// Turns the account data seen by the feed into events, the first state only seeding the
+/// baseline
+#[derive(Debug, Default)]
+pub struct Watcher {
+    snapshot: Option<TokenWhitelist>,
+    resync: bool, // disconnected since the last state seen
+}
+
+impl Watcher {
+    /// Marks the feed as having dropped, the next state seen is preceded by a resync
+    pub fn disconnected(&mut self) {
+        self.resync = self.snapshot.is_some();
+    }
+
+    /// Events of a state seen at `slot`, none for data that does not decode
+    pub fn observe(&mut self, slot: u64, data: &[u8]) -> Vec<WatchEvent> {
+        let state = match TokenWhitelist::unpack_from_slice(data) {
+            Ok(state) => state,
+            Err(_) => return Vec::new(),
+        };
+        let mut events = Vec::new();
+        if std::mem::take(&mut self.resync) {
+            events.push(WatchEvent::Resync {slot});
+        }
+        if let Some(previous) = self.snapshot.replace(state) {
+            let diff = diff_whitelists(&previous, self.snapshot.as_ref().unwrap());
+            if !diff.is_empty() {
+                events.push(WatchEvent::Changed {slot, diff});
+            }
+        }
+        events
+    }
+}
+
+/// Follows a whitelist at `commitment` and yields its events, reconnecting whenever the socket
+/// drops. Must be called within a tokio runtime; dropping the stream ends the subscription.
+pub fn watch_whitelist(
+    rpc_url: String,
+    ws_url: String,
+    whitelist_pubkey: Pubkey,
+    commitment: CommitmentConfig,
+) -> impl Stream<Item = WatchEvent> {
+    let (sender, receiver) = mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut watcher = Watcher::default();
+        follow_account(rpc_url, ws_url, whitelist_pubkey, commitment, |feed| {
+            match feed {
+                AccountFeed::Data {slot, data} => {
+                    for event in watcher.observe(slot, data) {
+                        let _ = sender.unbounded_send(event);
+                    }
+                }
+                AccountFeed::Disconnected => watcher.disconnected(),
+            }
+            !sender.is_closed()
+        })
+        .await;
+    });
+    receiver
+}
+
+/// Lines printed for an event: `+ key amount`, `- key`, `~ key a -> b` and `* field a -> b`
+/// for the header, each after `time`, or one JSON object per line with `json`
+pub fn render(event: &WatchEvent, time: &str, json: bool) -> Vec<String> {
+    let (slot, diff) = match event {
+        WatchEvent::Resync {slot} if json => {
+            return vec![format!(r#"{{"time":"{}","slot":{},"kind":"resync"}}"#, time, slot)];
+        }
+        WatchEvent::Resync {slot} => return vec![format!("{} resync at slot {}, changes may be missing", time, slot)],
+        WatchEvent::Changed {slot, diff} => (slot, diff),
+    };
+    let mut lines = Vec::new();
+    for change in &diff.header {
+        lines.push(if json {
+            format!(
+                r#"{{"time":"{}","slot":{},"kind":"header","field":"{}","before":"{}","after":"{}"}}"#,
+                time,
+                slot,
+                change.field,
+                escape(&change.a),
+                escape(&change.b)
+            )
+        } else {
+            format!("{} * {} {} -> {}", time, change.field, change.a, change.b)
+        });
+    }
+    for (key, amount) in &diff.removed {
+        lines.push(if json {
+            format!(r#"{{"time":"{}","slot":{},"kind":"removed","key":"{}","amount":"{}"}}"#, time, slot, escape(key), amount)
+        } else {
+            format!("{} - {}", time, key)
+        });
+    }
+    for (key, a, b) in &diff.changed {
+        lines.push(if json {
+            format!(
+                r#"{{"time":"{}","slot":{},"kind":"changed","key":"{}","before":"{}","after":"{}"}}"#,
+                time,
+                slot,
+                escape(key),
+                a,
+                b
+            )
+        } else {
+            format!("{} ~ {} {} -> {}", time, key, a, b)
+        });
+    }
+    for (key, amount) in &diff.added {
+        lines.push(if json {
+            format!(r#"{{"time":"{}","slot":{},"kind":"added","key":"{}","amount":"{}"}}"#, time, slot, escape(key), amount)
+        } else {
+            format!("{} + {} {}", time, key, amount)
+        });
+    }
+    lines
+}
+
+/// The current time as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn utc_timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    format_utc(seconds)
+}
+
+/// Websocket url of a cluster rpc url, as the Solana CLI derives it: the ws scheme and the next
+/// port when one is given
+pub fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let host = match host.rsplit_once(':').map(|(name, port)| (name, port.parse::<u16>())) {
+        Some((name, Ok(port))) => format!("{}:{}", name, port.saturating_add(1)),
+        _ => host.to_string(),
+    };
+    format!("{}://{}{}", scheme, host, path)
+}
+
+fn format_utc(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // civil date of a day count since 1970-01-01, over 400 year eras of 146097 days
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+/// Escapes a string for a JSON string literal
+fn escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: &str = "2024-01-02T03:04:05Z";
+
+    fn whitelist(entries: &[(u8, u64)]) -> TokenWhitelist {
+        TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 10,
+            whitelist_map: entries.iter().map(|&(i, amount)| (key(i), amount)).collect(),
+            ..TokenWhitelist::default()
+        }
+    }
+
+    fn key(i: u8) -> String {
+        Pubkey::new_from_array([i; 32]).to_string()
+    }
+
+    fn pack(state: &TokenWhitelist) -> Vec<u8> {
+        let mut data = vec![0; 5161];
+        state.pack_into_slice(&mut data).unwrap();
+        data
+    }
+
+    /// Feeds a sequence of states, None standing for a disconnect, and renders the events
+    fn run(sequence: &[Option<TokenWhitelist>]) -> Vec<String> {
+        let mut watcher = Watcher::default();
+        let mut lines = Vec::new();
+        for (slot, state) in sequence.iter().enumerate() {
+            match state {
+                Some(state) => {
+                    for event in watcher.observe(slot as u64, &pack(state)) {
+                        lines.extend(render(&event, TIME, false));
+                    }
+                }
+                None => watcher.disconnected(),
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn test_render() {
+        let mut after = whitelist(&[(1, 500), (3, 300)]);
+        after.max_whitelist_size = 20;
+        let diff = diff_whitelists(&whitelist(&[(1, 1_000), (2, 200)]), &after);
+        let event = WatchEvent::Changed {slot: 7, diff};
+        assert_eq!(
+            render(&event, TIME, false),
+            vec![
+                format!("{} * max_whitelist_size 10 -> 20", TIME),
+                format!("{} - {}", TIME, key(2)),
+                format!("{} ~ {} 1000 -> 500", TIME, key(1)),
+                format!("{} + {} 300", TIME, key(3)),
+            ]
+        );
+        assert_eq!(
+            render(&event, TIME, true),
+            vec![
+                format!(r#"{{"time":"{}","slot":7,"kind":"header","field":"max_whitelist_size","before":"10","after":"20"}}"#, TIME),
+                format!(r#"{{"time":"{}","slot":7,"kind":"removed","key":"{}","amount":"200"}}"#, TIME, key(2)),
+                format!(r#"{{"time":"{}","slot":7,"kind":"changed","key":"{}","before":"1000","after":"500"}}"#, TIME, key(1)),
+                format!(r#"{{"time":"{}","slot":7,"kind":"added","key":"{}","amount":"300"}}"#, TIME, key(3)),
+            ]
+        );
+        assert_eq!(
+            render(&WatchEvent::Resync {slot: 9}, TIME, true),
+            vec![format!(r#"{{"time":"{}","slot":9,"kind":"resync"}}"#, TIME)]
+        );
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+
+    #[test]
+    fn test_watch_sequence() {
+        let lines = run(&[
+            Some(whitelist(&[(1, 100)])), // baseline, nothing printed
+            Some(whitelist(&[(1, 100)])),
+            Some(whitelist(&[(1, 100), (2, 200)])),
+            Some(whitelist(&[(2, 200)])),
+        ]);
+        assert_eq!(lines, vec![format!("{} + {} 200", TIME, key(2)), format!("{} - {}", TIME, key(1))]);
+    }
+
+    #[test]
+    fn test_resync_after_disconnect() {
+        let lines = run(&[
+            Some(whitelist(&[(1, 100)])),
+            None,
+            None,
+            // the fetch after reconnecting, two changes made while disconnected folded into one
+            Some(whitelist(&[(1, 50), (2, 200)])),
+            Some(whitelist(&[(1, 50), (2, 200)])),
+        ]);
+        assert_eq!(
+            lines,
+            vec![
+                format!("{} resync at slot 3, changes may be missing", TIME),
+                format!("{} ~ {} 100 -> 50", TIME, key(1)),
+                format!("{} + {} 200", TIME, key(2)),
+            ]
+        );
+
+        // a resync is marked even when nothing changed meanwhile, but not before the baseline
+        let lines = run(&[None, Some(whitelist(&[])), None, Some(whitelist(&[]))]);
+        assert_eq!(lines, vec![format!("{} resync at slot 3, changes may be missing", TIME)]);
+    }
+
+    #[test]
+    fn test_undecodable_data_skipped() {
+        let mut watcher = Watcher::default();
+        watcher.observe(1, &pack(&whitelist(&[(1, 100)])));
+        watcher.disconnected();
+        assert_eq!(watcher.observe(2, &[0; 10]), vec![]);
+        // the resync is still pending for the next state that decodes
+        assert_eq!(watcher.observe(3, &pack(&whitelist(&[(1, 100)]))), vec![WatchEvent::Resync {slot: 3}]);
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_704_164_645), TIME);
+    }
+
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(websocket_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8900");
+        assert_eq!(websocket_url("https://api.devnet.solana.com"), "wss://api.devnet.solana.com");
+        assert_eq!(websocket_url("https://rpc.example.com:443/key"), "wss://rpc.example.com:444/key");
+    }
+}
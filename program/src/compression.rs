@@ -0,0 +1,286 @@
+//! Compressed mode: a whitelist whose entries are leaves of a concurrent Merkle tree owned by
+//! spl-account-compression, for rounds too large for the map or an account per entry. The
+//! program appends and replaces leaves and verifies them by CPI, signing as the tree authority
+//! of the whitelist. The instructions of the compression program are encoded here by hand, as
+//! its crate pulls in anchor. Off-chain tooling mirrors a tree with
+//! [TreeMirror](struct.TreeMirror.html) to build proofs from the leaves an indexer saw appended.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    keccak,
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+use crate::merkle::{self, HashKind};
+
+/// spl-account-compression, owner of the tree accounts
+pub const COMPRESSION_PROGRAM_ID: Pubkey = solana_program::pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+/// spl-noop, the compression program logs its changelogs through it for indexers
+pub const NOOP_PROGRAM_ID: Pubkey = solana_program::pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+/// Depth of every compressed tree, room for 2^20 leaves
+pub const TREE_DEPTH: u32 = 20;
+/// Changes to a tree a proof may lag behind and still be accepted
+pub const TREE_BUFFER_SIZE: u32 = 64;
+/// Leaves a compressed tree holds
+pub const TREE_CAPACITY: u64 = 1 << TREE_DEPTH;
+pub const TREE_AUTHORITY_SEED: &[u8] = b"tree-authority";
+
+// account type and header version bytes, then max_buffer_size, max_depth, authority,
+// creation_slot and padding
+const TREE_HEADER_LEN: usize = 2 + 4 + 4 + 32 + 8 + 6;
+// sequence_number, active_index and buffer_size ahead of the changelogs
+const TREE_COUNTERS_LEN: usize = 3 * 8;
+// a changelog, and the rightmost path alike: the nodes of a path, another node, index and padding
+const CHANGELOG_LEN: usize = 32 * TREE_DEPTH as usize + 32 + 4 + 4;
+
+// anchor discriminators, the first 8 bytes of sha256("global:<instruction name>")
+const INIT_EMPTY_MERKLE_TREE: [u8; 8] = [0xbf, 0x0b, 0x77, 0x07, 0xb4, 0x6b, 0xdc, 0x6e];
+const APPEND: [u8; 8] = [0x95, 0x78, 0x12, 0xde, 0xec, 0xe1, 0x58, 0xcb];
+const REPLACE_LEAF: [u8; 8] = [0xcc, 0xa5, 0x4c, 0x64, 0x49, 0x93, 0x00, 0x80];
+const VERIFY_LEAF: [u8; 8] = [0x7c, 0xdc, 0x16, 0xdf, 0x68, 0x0a, 0xfa, 0xe0];
+
+/// Bytes of a tree account, allocated by the client and assigned to the compression program
+/// before InitCompressedWhitelist. Trees are kept without a canopy, every proof is full depth.
+pub const fn tree_space() -> usize {
+    TREE_HEADER_LEN + TREE_COUNTERS_LEN + (TREE_BUFFER_SIZE as usize + 1) * CHANGELOG_LEN
+}
+
+/// Address and bump of the authority signing for the tree of `whitelist`
+pub fn find_tree_authority(program_id: &Pubkey, whitelist: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREE_AUTHORITY_SEED, whitelist.as_ref()], program_id)
+}
+
+/// Leaf of `wallet` with `allocation` at `leaf_index`, hashed as a keccak merkle mode leaf
+pub fn leaf(leaf_index: u32, wallet: &Pubkey, allocation: u64) -> [u8; 32] {
+    merkle::leaf(HashKind::Keccak, leaf_index, wallet, allocation)
+}
+
+/// Root the tree in `data` currently has, None for data too short to be a tree of TREE_DEPTH
+pub fn current_root(data: &[u8]) -> Option<[u8; 32]> {
+    let active_index = data
+        .get(TREE_HEADER_LEN + 8..TREE_HEADER_LEN + 16)
+        .and_then(|active_index| active_index.try_into().ok())
+        .map(u64::from_le_bytes)?;
+    if active_index >= TREE_BUFFER_SIZE as u64 {
+        return None;
+    }
+    let offset = TREE_HEADER_LEN + TREE_COUNTERS_LEN + active_index as usize * CHANGELOG_LEN;
+    data.get(offset..offset + 32).and_then(|root| root.try_into().ok())
+}
+
+fn modify_accounts(tree: &Pubkey, authority: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*tree, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new_readonly(NOOP_PROGRAM_ID, false),
+    ]
+}
+
+/// Proof nodes as the compression program takes them, the key of a read-only account each
+pub fn proof_accounts(proof: &[[u8; 32]]) -> impl Iterator<Item = AccountMeta> + '_ {
+    proof.iter().map(|node| AccountMeta::new_readonly(Pubkey::new_from_array(*node), false))
+}
+
+/// init_empty_merkle_tree of the compression program, at TREE_DEPTH and TREE_BUFFER_SIZE
+pub fn init_empty_merkle_tree(tree: &Pubkey, authority: &Pubkey) -> Instruction {
+    let mut data = INIT_EMPTY_MERKLE_TREE.to_vec();
+    data.extend_from_slice(&TREE_DEPTH.to_le_bytes());
+    data.extend_from_slice(&TREE_BUFFER_SIZE.to_le_bytes());
+    Instruction::new_with_bytes(COMPRESSION_PROGRAM_ID, &data, modify_accounts(tree, authority))
+}
+
+/// append of the compression program, the leaf going to the next free index
+pub fn append(tree: &Pubkey, authority: &Pubkey, leaf: &[u8; 32]) -> Instruction {
+    let mut data = APPEND.to_vec();
+    data.extend_from_slice(leaf);
+    Instruction::new_with_bytes(COMPRESSION_PROGRAM_ID, &data, modify_accounts(tree, authority))
+}
+
+/// replace_leaf of the compression program, `proof` holding the siblings from the leaf up
+pub fn replace_leaf(
+    tree: &Pubkey,
+    authority: &Pubkey,
+    root: &[u8; 32],
+    previous_leaf: &[u8; 32],
+    new_leaf: &[u8; 32],
+    leaf_index: u32,
+    proof: &[[u8; 32]],
+) -> Instruction {
+    let mut data = REPLACE_LEAF.to_vec();
+    data.extend_from_slice(root);
+    data.extend_from_slice(previous_leaf);
+    data.extend_from_slice(new_leaf);
+    data.extend_from_slice(&leaf_index.to_le_bytes());
+    let mut accounts = modify_accounts(tree, authority);
+    accounts.extend(proof_accounts(proof));
+    Instruction::new_with_bytes(COMPRESSION_PROGRAM_ID, &data, accounts)
+}
+
+/// verify_leaf of the compression program, failing unless `proof` leads from `leaf` to
+/// `root` and `root` is one of the recent roots of the tree
+pub fn verify_leaf(tree: &Pubkey, root: &[u8; 32], leaf: &[u8; 32], leaf_index: u32, proof: &[[u8; 32]]) -> Instruction {
+    let mut data = VERIFY_LEAF.to_vec();
+    data.extend_from_slice(root);
+    data.extend_from_slice(leaf);
+    data.extend_from_slice(&leaf_index.to_le_bytes());
+    let mut accounts = vec![AccountMeta::new_readonly(*tree, false)];
+    accounts.extend(proof_accounts(proof));
+    Instruction::new_with_bytes(COMPRESSION_PROGRAM_ID, &data, accounts)
+}
+
+/// Parent of two nodes as the compression program hashes it, in position order
+fn parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    keccak::hashv(&[left, right]).to_bytes()
+}
+
+/// Off-chain copy of a compressed tree over entries in append order, their positions being the
+/// leaf indices. Leaves past the last entry are empty, zero at the bottom level and the parent
+/// of two empty nodes above it, as in the compression program.
+#[derive(Clone, Debug)]
+pub struct TreeMirror {
+    levels: Vec<Vec<[u8; 32]>>, // leaves first, the root level last
+    empty_nodes: Vec<[u8; 32]>, // empty node of each level
+}
+
+impl TreeMirror {
+    pub fn new(entries: &[(Pubkey, u64)]) -> Self {
+        let mut empty_nodes = vec![[0; 32]];
+        for level in 0..TREE_DEPTH as usize {
+            empty_nodes.push(parent(&empty_nodes[level], &empty_nodes[level]));
+        }
+        let leaves = entries.iter()
+            .enumerate()
+            .map(|(leaf_index, (wallet, allocation))| leaf(leaf_index as u32, wallet, *allocation))
+            .collect();
+        let mut levels: Vec<Vec<[u8; 32]>> = vec![leaves];
+        for level in 0..TREE_DEPTH as usize {
+            let next = levels[level]
+                .chunks(2)
+                .map(|pair| parent(&pair[0], pair.get(1).unwrap_or(&empty_nodes[level])))
+                .collect();
+            levels.push(next);
+        }
+        TreeMirror {levels, empty_nodes}
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Root of the tree, the empty node of the top level for a tree without entries
+    pub fn root(&self) -> [u8; 32] {
+        self.levels[TREE_DEPTH as usize].first().copied().unwrap_or(self.empty_nodes[TREE_DEPTH as usize])
+    }
+
+    /// Proof of TREE_DEPTH siblings for the entry at `index`, None past the last entry
+    pub fn proof(&self, index: usize) -> Option<Vec<[u8; 32]>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+        let proof = (0..TREE_DEPTH as usize)
+            .map(|level| {
+                let sibling = (index >> level) ^ 1;
+                self.levels[level].get(sibling).copied().unwrap_or(self.empty_nodes[level])
+            })
+            .collect();
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash;
+
+    fn entries(count: u8) -> Vec<(Pubkey, u64)> {
+        (0..count).map(|i| (Pubkey::new_from_array([i; 32]), 100 * i as u64)).collect()
+    }
+
+    /// Root reached from `leaf` at `index`, folding the proof in position order
+    fn root_from_proof(leaf: [u8; 32], index: usize, proof: &[[u8; 32]]) -> [u8; 32] {
+        proof.iter().enumerate().fold(leaf, |node, (level, sibling)| {
+            if (index >> level) & 1 == 0 {
+                parent(&node, sibling)
+            } else {
+                parent(sibling, &node)
+            }
+        })
+    }
+
+    #[test]
+    fn test_discriminators() {
+        for (name, discriminator) in [
+            ("init_empty_merkle_tree", INIT_EMPTY_MERKLE_TREE),
+            ("append", APPEND),
+            ("replace_leaf", REPLACE_LEAF),
+            ("verify_leaf", VERIFY_LEAF),
+        ] {
+            let preimage = format!("global:{}", name);
+            assert_eq!(hash::hash(preimage.as_bytes()).to_bytes()[..8], discriminator, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_tree_space() {
+        // ConcurrentMerkleTree<20, 64> behind its 56 byte header, without a canopy
+        assert_eq!(tree_space(), 44_280);
+    }
+
+    #[test]
+    fn test_instruction_layout() {
+        let (tree, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let init = init_empty_merkle_tree(&tree, &authority);
+        assert_eq!(init.program_id, COMPRESSION_PROGRAM_ID);
+        assert_eq!(init.data[8..], [20, 0, 0, 0, 64, 0, 0, 0]);
+        assert_eq!(init.accounts, modify_accounts(&tree, &authority));
+
+        let proof = vec![[1; 32], [2; 32]];
+        let verify = verify_leaf(&tree, &[3; 32], &[4; 32], 9, &proof);
+        assert_eq!(verify.data.len(), 8 + 32 + 32 + 4);
+        assert_eq!(verify.data[72..], 9u32.to_le_bytes());
+        assert_eq!(verify.accounts.len(), 3);
+        assert!(!verify.accounts[0].is_writable);
+        assert_eq!(verify.accounts[2].pubkey, Pubkey::new_from_array([2; 32]));
+
+        let replace = replace_leaf(&tree, &authority, &[3; 32], &[4; 32], &[5; 32], 9, &proof);
+        assert_eq!(replace.data.len(), 8 + 3 * 32 + 4);
+        assert_eq!(replace.accounts.len(), 5);
+    }
+
+    #[test]
+    fn test_empty_tree_root() {
+        // empty leaves hashed up level by level
+        let root = (0..TREE_DEPTH).fold([0; 32], |node, _| parent(&node, &node));
+        assert_eq!(TreeMirror::new(&[]).root(), root);
+        assert_eq!(TreeMirror::new(&[]).proof(0), None);
+    }
+
+    #[test]
+    fn test_every_proof_verifies() {
+        for count in [1, 2, 3, 8, 9] {
+            let entries = entries(count);
+            let tree = TreeMirror::new(&entries);
+            for (i, (wallet, allocation)) in entries.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert_eq!(proof.len(), TREE_DEPTH as usize);
+                assert_eq!(root_from_proof(leaf(i as u32, wallet, *allocation), i, &proof), tree.root());
+                assert_ne!(root_from_proof(leaf(i as u32, wallet, allocation + 1), i, &proof), tree.root());
+            }
+            assert_eq!(tree.proof(entries.len()), None);
+        }
+    }
+
+    #[test]
+    fn test_current_root() {
+        let mut data = vec![0; tree_space()];
+        let active_index = TREE_HEADER_LEN + 8..TREE_HEADER_LEN + 16;
+        let offset = TREE_HEADER_LEN + TREE_COUNTERS_LEN + 2 * CHANGELOG_LEN;
+        data[offset..offset + 32].copy_from_slice(&[7; 32]);
+        data[active_index.clone()].copy_from_slice(&2u64.to_le_bytes());
+        assert_eq!(current_root(&data), Some([7; 32]));
+        assert_eq!(current_root(&data[..offset]), None);
+
+        data[active_index].copy_from_slice(&(TREE_BUFFER_SIZE as u64).to_le_bytes());
+        assert_eq!(current_root(&data), None);
+    }
+}
@@ -0,0 +1,393 @@
+//! The whitelist as a state machine: what the owner, the delegate and the participants may do
+//! to a decoded whitelist, and what it leaves behind. Like [rules](../rules/index.html) it reads
+//! no accounts, no sysvars and no signatures. The processor checks the signatures, reads what
+//! an action needs off the accounts into an [AuthorityContext](struct.AuthorityContext.html),
+//! [applies](fn.apply.html) the action and writes the [Effects](struct.Effects.html) back, so
+//! host tests can run every action against every authority and policy without a bank.
+//!
+//! An action refused leaves the state as it was: every check runs before the first change.
+//!
+//! Every instruction changing nothing but the whitelist state is an action. The instructions
+//! moving lamports (CloseWhitelistAccount, ArchiveWhitelist, RefundDeposit), creating accounts
+//! (InitConfig, InitClaimBitmap), invoking other programs (the compressed tree) or mending
+//! state the program cannot decode as written (RepairState, MigrateStateLayout) stay with the
+//! processor, and so does RotateWhitelistedKey, which reads sibling whitelists and token
+//! accounts.
+
+use solana_program::pubkey::Pubkey;
+use crate::{
+    error::TokenWhitelistError,
+    event::WhitelistEvent,
+    lottery::select_winners,
+    rules,
+    state::{
+        AllocationBounds, ConsumeLimits, Delegate, Lottery, Registration, RegistrationRateLimit, TokenGate,
+        TokenWhitelist, MAX_ROUNDS, MAX_SIBLING_WHITELISTS, NO_ATTESTATION, NO_CAMPAIGN,
+    },
+};
+#[cfg(feature = "merkle")]
+use crate::{merkle::HashKind, state::MerkleRoot};
+
+/// Who acts on which whitelist, as the processor read it off the accounts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AuthorityContext {
+    pub signer: Pubkey, // the authority account of the instruction, its signature already checked
+    pub owner_signed: bool, // whether the whitelist owner signed the instruction, on any account
+    pub whitelist: Pubkey,
+    pub program_id: Pubkey,
+}
+
+/// Changes to a whitelist the core decides on, each the state part of one instruction
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// AddToWhitelist, AddToWhitelistForRound and AddToWhitelistWithAttestation, by the owner or
+    /// the delegate. `account_owner` is the program owning the account added.
+    Add {
+        account: Pubkey,
+        account_owner: Pubkey,
+        allocation_amount: u64,
+        round: u8,
+        campaign_id: [u8; 8],
+        expected_nonce: u64,
+        attestation: [u8; 32],
+    },
+    /// RemoveFromWhitelist, by the owner
+    Remove {
+        account: Pubkey,
+        memo: Option<String>,
+        expected_nonce: u64,
+    },
+    /// SetAllocationToZero, by the account itself
+    ZeroAllocation {
+        account: Pubkey,
+        memo: Option<String>,
+    },
+    /// RegisterSelf of the signer at `slot`
+    RegisterSelf {
+        slot: u64,
+    },
+    /// ApproveDelegate, by the owner
+    ApproveDelegate(Delegate),
+    /// RevokeDelegate, by the owner
+    RevokeDelegate,
+    /// ConfigureRegistration, by the owner
+    ConfigureRegistration(Registration),
+    /// SetEnumerationRestricted, by the owner
+    SetEnumerationRestricted(bool),
+    /// SetRegistrationRateLimit, by the owner
+    SetRegistrationRateLimit(RegistrationRateLimit),
+    /// RunLottery, by the owner, drawn against the slot hash the processor read
+    RunLottery(Lottery),
+    /// SetConsumeLimits, by the owner
+    SetConsumeLimits(ConsumeLimits),
+    /// SetAllocationBounds, by the owner
+    SetAllocationBounds(AllocationBounds),
+    /// SetStrictTokenChecks, by the owner
+    SetStrictTokenChecks(bool),
+    /// SetOnlyWallets, by the owner
+    SetOnlyWallets(bool),
+    /// SetSelfZeroAllowed, by the owner
+    SetSelfZeroAllowed(bool),
+    /// SetZeroAllocationAllowed, by the owner
+    SetZeroAllocationAllowed(bool),
+    /// SetAttestation, by the owner or the delegate
+    SetAttestation {
+        account: Pubkey,
+        attestation: [u8; 32],
+    },
+    /// SetAttestationRequired, by the owner
+    SetAttestationRequired(bool),
+    /// SetEntryNoteCommitment, by the owner
+    SetEntryNoteCommitment {
+        account: Pubkey,
+        commitment: [u8; 32],
+    },
+    /// AssertNotInOther, by the owner. The processor checks each sibling is a whitelist of the
+    /// program once the action is applied.
+    SetSiblingWhitelists(Vec<Pubkey>),
+    /// SetOwnerEntryAllowed, by the owner
+    SetOwnerEntryAllowed(bool),
+    /// SetTokenGate, by the owner, None lifting the gate. The processor checks the gating mint
+    /// once the action is applied.
+    SetTokenGate(Option<TokenGate>),
+    /// SetActiveRound, by the owner
+    SetActiveRound(u8),
+    /// SetMerkleRoot, by the owner
+    #[cfg(feature = "merkle")]
+    SetMerkleRoot(MerkleRoot),
+}
+
+/// How the state reaches the account
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Store {
+    #[default]
+    Nothing,
+    State,
+    Removal(String), // the state after drop_key took this key out, see WhitelistAccount::save_removal
+}
+
+/// Entry counted in the stats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Churn {
+    Add,
+    Remove,
+}
+
+/// What an applied action asks of the processor beyond the state it changed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Effects {
+    pub store: Store,
+    pub entries_changed: bool, // the entries hash is stale, a removal store recomputes it itself
+    pub churn: Option<Churn>,
+    pub deposit_lamports: u64, // lamports the signer pays into the whitelist
+    pub events: Vec<WhitelistEvent>, // emitted after the store, before the stats
+}
+
+/// Applies `action` of `context.signer` to `state`, or refuses it with the error the chain
+/// returns and `state` untouched
+pub fn apply(
+    state: &mut TokenWhitelist,
+    action: &Action,
+    context: &AuthorityContext,
+) -> Result<Effects, TokenWhitelistError> {
+    match action {
+        Action::Add {account, account_owner, allocation_amount, round, campaign_id, expected_nonce, attestation} => {
+            let round = rules::check_add(state, &context.signer, *allocation_amount, *round)?;
+            rules::check_mutation_nonce(state, *expected_nonce)?;
+            let key = account.to_string();
+            rules::check_attestation(state, &key, *attestation)?;
+            rules::check_target_identity(state, &context.whitelist, &context.program_id, account)?;
+            rules::check_whitelist_target(state, account, account_owner)?;
+            state.check_new_key(&key)?;
+
+            if context.signer != state.init_pubkey {
+                if let Some(delegate) = state.extension.delegate.as_mut() {
+                    delegate.use_add(*allocation_amount)?;
+                }
+            }
+            state.set_round_allocation(&key, round, *allocation_amount);
+            // adds without a campaign keep the attribution of an entry added for one earlier
+            if *campaign_id != NO_CAMPAIGN {
+                state.set_campaign(&key, *campaign_id);
+            }
+            // and adds without an attestation the attestation it holds
+            let mut events = Vec::new();
+            if *attestation != NO_ATTESTATION {
+                state.set_attestation(&key, *attestation);
+                events.push(WhitelistEvent::AttestationSet {
+                    whitelist: context.whitelist,
+                    account: *account,
+                    attestation: *attestation,
+                });
+            }
+            Ok(Effects {store: Store::State, entries_changed: true, churn: Some(Churn::Add), events, ..Effects::default()})
+        }
+        Action::Remove {account, memo, expected_nonce} => {
+            rules::check_owner(state, &context.signer)?;
+            rules::check_mutation_nonce(state, *expected_nonce)?;
+            let key = account.to_string();
+            let allocation = match state.drop_key(&key) {
+                Some(allocation) => allocation,
+                // nothing to remove, so nothing is written and the nonce stays
+                None => return Ok(Effects::default()),
+            };
+            Ok(Effects {
+                store: Store::Removal(key),
+                churn: Some(Churn::Remove),
+                events: vec![WhitelistEvent::EntryRemoved {account: *account, allocation, memo: memo.clone()}],
+                ..Effects::default()
+            })
+        }
+        Action::ZeroAllocation {account, memo} => {
+            rules::check_initialized(state)?;
+            if context.signer != *account {
+                return Err(TokenWhitelistError::NotOwner);
+            }
+            if state.extension.self_zero_disabled && !context.owner_signed {
+                return Err(TokenWhitelistError::OwnerSignatureRequired);
+            }
+            let key = account.to_string();
+            state.check_new_key(&key)?;
+
+            let allocation = state.whitelist_map.get(&key).copied().unwrap_or(0);
+            state.add_keypair(&key, &0);
+            Ok(Effects {
+                store: Store::State,
+                entries_changed: true,
+                events: vec![WhitelistEvent::AllocationZeroed {account: *account, allocation, memo: memo.clone()}],
+                ..Effects::default()
+            })
+        }
+        Action::RegisterSelf {slot} => {
+            let account = &context.signer;
+            let registration = rules::check_register(state, account, *slot)?;
+            let mut rate_limit = state.extension.registration_rate_limit;
+            rate_limit.register(*slot)?;
+            rules::check_target_identity(state, &context.whitelist, &context.program_id, account)?;
+            let key = account.to_string();
+            state.check_new_key(&key)?;
+
+            state.extension.registration_rate_limit = rate_limit;
+            let deposit_lamports = registration.registration_deposit_lamports;
+            // an earlier entry of the key removed by the owner keeps its deposit to refund
+            if deposit_lamports > 0 {
+                let total = state.extension.registration_deposits.entry(key.clone()).or_insert(0);
+                *total = total.checked_add(deposit_lamports).ok_or(TokenWhitelistError::Overflow)?;
+            }
+            state.add_keypair(&key, &registration.registration_allocation);
+            Ok(Effects {
+                store: Store::State,
+                entries_changed: true,
+                churn: Some(Churn::Add),
+                deposit_lamports,
+                ..Effects::default()
+            })
+        }
+        Action::ApproveDelegate(delegate) => {
+            check_owner_authority(state, context)?;
+            state.extension.delegate = Some(*delegate);
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::RevokeDelegate => {
+            check_owner_authority(state, context)?;
+            state.extension.delegate = None;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::ConfigureRegistration(registration) => {
+            check_owner_authority(state, context)?;
+            state.extension.registration = Some(*registration);
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetEnumerationRestricted(restrict_enumeration) => {
+            check_owner_authority(state, context)?;
+            state.extension.restrict_enumeration = *restrict_enumeration;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetRegistrationRateLimit(registration_rate_limit) => {
+            check_owner_authority(state, context)?;
+            state.extension.registration_rate_limit = *registration_rate_limit;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::RunLottery(lottery) => {
+            check_owner_authority(state, context)?;
+            if state.extension.lottery.is_some() {
+                return Err(TokenWhitelistError::LotteryAlreadyRun);
+            }
+            let selected = select_winners(state.whitelist_map.keys(), &lottery.seed, &lottery.slot_hash, lottery.winners);
+            let losers: Vec<String> = state.whitelist_map.keys()
+                .filter(|key| selected.binary_search(key).is_err())
+                .cloned()
+                .collect();
+            for loser in &losers {
+                state.drop_key(loser);
+            }
+            state.extension.lottery = Some(*lottery);
+            Ok(Effects {store: Store::State, entries_changed: true, ..Effects::default()})
+        }
+        Action::SetConsumeLimits(consume_limits) => {
+            check_owner_authority(state, context)?;
+            state.extension.consume_limits = *consume_limits;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetAllocationBounds(allocation_bounds) => {
+            check_owner_authority(state, context)?;
+            state.extension.allocation_bounds = *allocation_bounds;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetStrictTokenChecks(strict_token_checks) => {
+            check_owner_authority(state, context)?;
+            state.extension.strict_token_checks = *strict_token_checks;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetOnlyWallets(only_wallets) => {
+            check_owner_authority(state, context)?;
+            state.extension.only_wallets = *only_wallets;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetSelfZeroAllowed(self_zero_allowed) => {
+            check_owner_authority(state, context)?;
+            state.extension.self_zero_disabled = !*self_zero_allowed;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetZeroAllocationAllowed(allow_zero_allocation) => {
+            check_owner_authority(state, context)?;
+            state.extension.zero_allocation_disabled = !*allow_zero_allocation;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetAttestation {account, attestation} => {
+            rules::check_set_attestation(state, &context.signer, account, *attestation)?;
+            state.set_attestation(&account.to_string(), *attestation);
+            Ok(Effects {
+                store: Store::State,
+                events: vec![WhitelistEvent::AttestationSet {
+                    whitelist: context.whitelist,
+                    account: *account,
+                    attestation: *attestation,
+                }],
+                ..Effects::default()
+            })
+        }
+        Action::SetAttestationRequired(require_attestation) => {
+            check_owner_authority(state, context)?;
+            state.extension.require_attestation = *require_attestation;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetEntryNoteCommitment {account, commitment} => {
+            check_owner_authority(state, context)?;
+            let key = account.to_string();
+            if !state.whitelist_map.contains_key(&key) {
+                return Err(TokenWhitelistError::AccountNotWhitelisted);
+            }
+            state.set_note_commitment(&key, *commitment);
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetSiblingWhitelists(siblings) => {
+            check_owner_authority(state, context)?;
+            if siblings.len() > MAX_SIBLING_WHITELISTS {
+                return Err(TokenWhitelistError::InvalidInstruction);
+            }
+            for (index, sibling) in siblings.iter().enumerate() {
+                if *sibling == context.whitelist || siblings[..index].contains(sibling) {
+                    return Err(TokenWhitelistError::InvalidInstruction);
+                }
+            }
+            state.extension.sibling_whitelists = siblings.clone();
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetOwnerEntryAllowed(allow_owner_entry) => {
+            check_owner_authority(state, context)?;
+            state.extension.owner_entry_disabled = !*allow_owner_entry;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetTokenGate(token_gate) => {
+            check_owner_authority(state, context)?;
+            state.extension.token_gate = *token_gate;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        Action::SetActiveRound(round) => {
+            check_owner_authority(state, context)?;
+            if *round as usize >= MAX_ROUNDS {
+                return Err(TokenWhitelistError::InvalidRound);
+            }
+            state.extension.active_round = *round;
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+        #[cfg(feature = "merkle")]
+        Action::SetMerkleRoot(merkle_root) => {
+            check_owner_authority(state, context)?;
+            HashKind::from_u8(merkle_root.hash_kind)?;
+            state.extension.merkle_root = Some(*merkle_root);
+            Ok(Effects {store: Store::State, ..Effects::default()})
+        }
+    }
+}
+
+/// The owner check of the owner-only settings, refusing any other signer with InvalidAuthority
+/// where the entry instructions answer TokenWhitelistNotOwner
+fn check_owner_authority(state: &TokenWhitelist, context: &AuthorityContext) -> Result<(), TokenWhitelistError> {
+    rules::check_initialized(state)?;
+    if context.signer != state.init_pubkey {
+        return Err(TokenWhitelistError::InvalidAuthority);
+    }
+    Ok(())
+}
@@ -1,38 +1,331 @@
 use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
 use thiserror::Error;
 use solana_program::{decode_error::DecodeError, program_error::ProgramError};
 
-#[derive(Error, Debug, Copy, Clone, FromPrimitive)]
+use std::{fmt, ops::Range};
+
+/// Who an error is meant for. Validation, capacity and authority errors are the user's to fix
+/// and worth a message in a UI, integration errors point at the transaction the integrator built.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Validation,
+    Capacity,
+    Authority,
+    Integration,
+}
+
+impl ErrorCategory {
+    /// Codes new errors of the category are numbered from. Codes below 100 predate the ranges
+    /// and keep their numbers whatever their category.
+    pub fn codes(self) -> Range<u32> {
+        match self {
+            ErrorCategory::Validation => 100..200,
+            ErrorCategory::Capacity => 200..300,
+            ErrorCategory::Authority => 300..400,
+            ErrorCategory::Integration => 400..500,
+        }
+    }
+
+    pub fn is_user_facing(self) -> bool {
+        self != ErrorCategory::Integration
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Capacity => "capacity",
+            ErrorCategory::Authority => "authority",
+            ErrorCategory::Integration => "integration",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Errors of the program, their codes being what UIs map to messages. A code never changes
+/// once released: new variants take the next free code in the range of their category, see
+/// [ErrorCategory::codes](enum.ErrorCategory.html#method.codes), and tests/fixtures/error_codes.txt
+/// pins every code, name and message.
+#[derive(Error, Debug, Copy, Clone, PartialEq, FromPrimitive)]
 pub enum TokenWhitelistError {
     /// Invalid instruction
     #[error("Invalid Instruction")]
-    InvalidInstruction,
+    InvalidInstruction = 0,
     /// Not Rent Exempt
     #[error("Not Rent Exempt")]
-    NotRentExempt,
+    NotRentExempt = 1,
     /// Token Whitelist Not Initialized
     #[error("Token Whitelist Not Initialized")]
-    TokenWhitelistNotInit,
+    TokenWhitelistNotInit = 2,
     /// Signer Not Token Whitelist Owner
     #[error("Signer Not Token Whitelist Owner")]
-    TokenWhitelistNotOwner,
+    TokenWhitelistNotOwner = 3,
     /// Token Whitelist Size Exceeds
     #[error("Token Whitelist Size Exceeds")]
-    TokenWhitelistSizeExceeds,
+    TokenWhitelistSizeExceeds = 4,
     /// Signer Not Account Owner
     #[error("Signer Not Account Owner")]
-    NotOwner,
+    NotOwner = 5,
     /// Invalid authority provided
     #[error("Invalid authority provided")]
-    InvalidAuthority,
+    InvalidAuthority = 6,
     /// Calculation overflow
     #[error("Calculation overflow")]
-    Overflow,
+    Overflow = 7,
+    /// Whitelist account too small to hold extended state
+    #[error("Whitelist Account Too Small")]
+    AccountTooSmall = 8,
+    /// Delegate has no adds remaining
+    #[error("Delegate Adds Exhausted")]
+    DelegateAddsExhausted = 9,
+    /// Allocation amount exceeds the delegate per-add cap
+    #[error("Delegate Allocation Exceeded")]
+    DelegateAllocationExceeded = 10,
+    /// Self registration is not open
+    #[error("Registration Closed")]
+    RegistrationClosed = 11,
+    /// Self registration is still open
+    #[error("Registration Still Open")]
+    RegistrationOpen = 12,
+    /// Account already registered
+    #[error("Account Already Registered")]
+    AlreadyRegistered = 13,
+    /// No registration deposit to refund
+    #[error("No Deposit To Refund")]
+    NoDepositToRefund = 14,
+    /// Lottery already run
+    #[error("Lottery Already Run")]
+    LotteryAlreadyRun = 15,
+    /// Account not in the whitelist
+    #[error("Account Not Whitelisted")]
+    AccountNotWhitelisted = 16,
+    /// Consume amount exceeds the remaining allocation
+    #[error("Insufficient Allocation")]
+    InsufficientAllocation = 17,
+    /// Consume amount exceeds the per-transaction limit
+    #[error("Consume Limit Exceeded")]
+    ConsumeLimitExceeded = 18,
+    /// Account consumed too recently
+    #[error("Consume Cooldown Active")]
+    ConsumeCooldownActive = 19,
+    /// Explicit allocation decimals differ from the decimals of the allocation mint
+    #[error("Allocation Decimals Mismatch")]
+    AllocationDecimalsMismatch = 20,
+    /// Allocation mint is not an initialized SPL Token mint
+    #[error("Invalid Allocation Mint")]
+    InvalidMint = 21,
+    /// Allocation amount below the configured minimum
+    #[error("Allocation Below Minimum")]
+    AllocationBelowMinimum = 22,
+    /// Allocation amount above the configured maximum
+    #[error("Allocation Above Maximum")]
+    AllocationAboveMaximum = 23,
+    /// Round index past the last round
+    #[error("Invalid Round")]
+    InvalidRound = 24,
+    /// Account missing from the whitelist it is required to be in
+    #[error("Account Not In Required Whitelist")]
+    NotInRequiredWhitelist = 25,
+    /// Account present in the whitelist it is required to be absent from
+    #[error("Account In Excluded Whitelist")]
+    InExcludedWhitelist = 26,
+    /// Memo longer than MAX_MEMO_LEN bytes or not UTF-8
+    #[error("Invalid Memo")]
+    InvalidMemo = 27,
+    /// Whitelist was archived and accepts no further instructions
+    #[error("Whitelist Archived")]
+    WhitelistArchived = 28,
+    /// Self registration deposits are still held by the whitelist
+    #[error("Deposits Outstanding")]
+    DepositsOutstanding = 29,
+    /// Token account or mint carries an extension refused under strict token checks
+    #[error("Restricted Token Extension")]
+    RestrictedTokenExtension = 30,
+    /// Account to initialize holds non-zero data
+    #[error("Account Not Blank")]
+    AccountNotBlank = 31,
+    /// Merkle root hash kind is neither keccak nor sha256
+    #[error("Unknown Hash Kind")]
+    UnknownHashKind = 32,
+    /// Merkle leaf was claimed before
+    #[error("Already Claimed")]
+    AlreadyClaimed = 33,
+    /// Whitelist state read back after packing disagrees with the state packed
+    #[error("State Invariant Violation")]
+    StateInvariantViolation = 34,
+    /// Account added under only_wallets is not a system owned account on the ed25519 curve, or
+    /// the account added is the whitelist itself, the program or, once SetOwnerEntryAllowed
+    /// disallowed it, the whitelist owner
+    #[error("Invalid Whitelist Target")]
+    InvalidWhitelistTarget = 35,
+    /// Token account of the gating mint holds less than the gating minimum
+    #[error("Insufficient Gating Balance")]
+    InsufficientGatingBalance = 100,
+    /// Instruction expected a mutation nonce other than the one of the whitelist, which was
+    /// written since the sender read it
+    #[error("Stale Whitelist State")]
+    StaleWhitelistState = 101,
+    /// Add leaving an entry without attestation, SetAttestation clearing one, or a self
+    /// registration, on a whitelist where SetAttestationRequired made attestations required
+    #[error("Attestation Required")]
+    AttestationRequired = 102,
+    /// RotateWhitelistedKey to an account that already has an entry
+    #[error("Account Already Whitelisted")]
+    AccountAlreadyWhitelisted = 103,
+    /// Add of an account holding an entry in a sibling whitelist linked by AssertNotInOther
+    #[error("Duplicate Across Whitelists")]
+    DuplicateAcrossWhitelists = 104,
+    /// RevealEntryNote of a note and salt not hashing to the note commitment of the entry, or
+    /// of an entry without one
+    #[error("Note Commitment Mismatch")]
+    NoteCommitmentMismatch = 105,
+    /// Note longer than MAX_NOTE_LEN bytes
+    #[error("Note Too Long")]
+    NoteTooLong = 106,
+    /// InitTokenWhitelist or GetRequiredRent of a max_whitelist_size of 0, UNLIMITED_WHITELIST_SIZE
+    /// asking for no limit
+    #[error("Zero Whitelist Size")]
+    ZeroWhitelistSize = 107,
+    /// AppendCompressedEntry on a whitelist whose tree holds compression::TREE_CAPACITY leaves
+    #[error("Compressed Tree Full")]
+    CompressedTreeFull = 200,
+    /// RegisterSelf once the current window of SetRegistrationRateLimit holds its maximum of
+    /// self registrations
+    #[error("Registration Rate Limited")]
+    RegistrationRateLimited = 201,
+    /// SetAllocationToZero on a whitelist with self zeroing disabled, without the owner signature
+    #[error("Owner Signature Required")]
+    OwnerSignatureRequired = 300,
+    /// MigrateStateLayout signed by an account other than the upgrade authority of the program,
+    /// or for a program made immutable, which has none
+    #[error("Signer Not Upgrade Authority")]
+    UpgradeAuthorityMismatch = 301,
+    /// Whitelist has a config account and it was not passed
+    #[error("Config Account Missing")]
+    ConfigAccountMissing = 400,
+    /// InitConfig on a whitelist that already has a config account
+    #[error("Config Already Initialized")]
+    ConfigAlreadyInitialized = 401,
+    /// Whitelist has a token gate and no token account of the gating mint held by the account
+    /// being whitelisted was passed
+    #[error("Gating Account Missing")]
+    GatingAccountMissing = 402,
+    /// Whitelist key that is not the base58 string of a pubkey, see `state::validate_key`
+    #[error("Invalid Whitelist Key")]
+    InvalidWhitelistKey = 403,
+    /// Whitelist has no compressed tree, or the tree account passed is not its tree
+    #[error("Compressed Tree Mismatch")]
+    CompressedTreeMismatch = 404,
+    /// InitCompressedWhitelist on a whitelist that already has a compressed tree
+    #[error("Compressed Tree Already Initialized")]
+    CompressedTreeAlreadyInitialized = 405,
+    /// Companion account passed is not the one linked to the whitelist, see state::LinkedAccounts
+    #[error("Unlinked Companion Account")]
+    UnlinkedCompanionAccount = 406,
+    /// Whitelist has sibling whitelists linked by AssertNotInOther and one was not passed
+    #[error("Sibling Whitelist Missing")]
+    SiblingWhitelistMissing = 407,
+}
+
+impl TokenWhitelistError {
+    /// Code the error is returned under as a `ProgramError::Custom`
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn category(self) -> ErrorCategory {
+        match self {
+            TokenWhitelistError::InvalidInstruction
+            | TokenWhitelistError::NotRentExempt
+            | TokenWhitelistError::TokenWhitelistNotInit
+            | TokenWhitelistError::AccountTooSmall
+            | TokenWhitelistError::AllocationDecimalsMismatch
+            | TokenWhitelistError::InvalidMint
+            | TokenWhitelistError::AccountNotBlank
+            | TokenWhitelistError::UnknownHashKind
+            | TokenWhitelistError::StateInvariantViolation
+            | TokenWhitelistError::ConfigAccountMissing
+            | TokenWhitelistError::ConfigAlreadyInitialized
+            | TokenWhitelistError::GatingAccountMissing
+            | TokenWhitelistError::InvalidWhitelistKey
+            | TokenWhitelistError::CompressedTreeMismatch
+            | TokenWhitelistError::CompressedTreeAlreadyInitialized
+            | TokenWhitelistError::UnlinkedCompanionAccount
+            | TokenWhitelistError::SiblingWhitelistMissing => ErrorCategory::Integration,
+            TokenWhitelistError::RegistrationClosed
+            | TokenWhitelistError::RegistrationOpen
+            | TokenWhitelistError::AlreadyRegistered
+            | TokenWhitelistError::NoDepositToRefund
+            | TokenWhitelistError::LotteryAlreadyRun
+            | TokenWhitelistError::AccountNotWhitelisted
+            | TokenWhitelistError::AllocationBelowMinimum
+            | TokenWhitelistError::AllocationAboveMaximum
+            | TokenWhitelistError::InvalidRound
+            | TokenWhitelistError::NotInRequiredWhitelist
+            | TokenWhitelistError::InExcludedWhitelist
+            | TokenWhitelistError::InvalidMemo
+            | TokenWhitelistError::WhitelistArchived
+            | TokenWhitelistError::DepositsOutstanding
+            | TokenWhitelistError::RestrictedTokenExtension
+            | TokenWhitelistError::AlreadyClaimed
+            | TokenWhitelistError::InvalidWhitelistTarget
+            | TokenWhitelistError::InsufficientGatingBalance
+            | TokenWhitelistError::StaleWhitelistState
+            | TokenWhitelistError::AttestationRequired
+            | TokenWhitelistError::AccountAlreadyWhitelisted
+            | TokenWhitelistError::DuplicateAcrossWhitelists
+            | TokenWhitelistError::NoteCommitmentMismatch
+            | TokenWhitelistError::NoteTooLong
+            | TokenWhitelistError::ZeroWhitelistSize => ErrorCategory::Validation,
+            TokenWhitelistError::TokenWhitelistSizeExceeds
+            | TokenWhitelistError::Overflow
+            | TokenWhitelistError::DelegateAddsExhausted
+            | TokenWhitelistError::DelegateAllocationExceeded
+            | TokenWhitelistError::InsufficientAllocation
+            | TokenWhitelistError::ConsumeLimitExceeded
+            | TokenWhitelistError::ConsumeCooldownActive
+            | TokenWhitelistError::CompressedTreeFull
+            | TokenWhitelistError::RegistrationRateLimited => ErrorCategory::Capacity,
+            TokenWhitelistError::TokenWhitelistNotOwner
+            | TokenWhitelistError::NotOwner
+            | TokenWhitelistError::InvalidAuthority
+            | TokenWhitelistError::OwnerSignatureRequired
+            | TokenWhitelistError::UpgradeAuthorityMismatch => ErrorCategory::Authority,
+        }
+    }
+
+    /// Maps an error code back to the error, if it is one of ours
+    pub fn from_u32(code: u32) -> Option<Self> {
+        <Self as FromPrimitive>::from_u32(code)
+    }
+
+    /// Maps the code of a `ProgramError::Custom` back to the error, if it is one of ours
+    pub fn from_custom_code(code: u32) -> Option<Self> {
+        Self::from_u32(code)
+    }
+
+    /// Decodes a program error returned by the processor
+    pub fn from_program_error(error: &ProgramError) -> Option<Self> {
+        match error {
+            ProgramError::Custom(code) => Self::from_u32(*code),
+            _ => None,
+        }
+    }
+
+    /// Scans transaction logs, latest first, for the runtime's "custom program error: 0x.." line
+    pub fn from_transaction_logs(logs: &[String]) -> Option<Self> {
+        logs.iter().rev().find_map(|log| {
+            let (_, code) = log.split_once("custom program error: 0x")?;
+            Self::from_u32(u32::from_str_radix(code.trim(), 16).ok()?)
+        })
+    }
 }
 
 impl From<TokenWhitelistError> for ProgramError {
     fn from(e: TokenWhitelistError) -> Self {
-        ProgramError::Custom(e as u32)
+        ProgramError::Custom(e.code())
     }
 }
 
@@ -41,3 +334,114 @@ impl<T> DecodeError<T> for TokenWhitelistError {
         "Token Whitelist Error"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every variant with its code and the text the processor logs after "Error: "
+    const VARIANTS: &[(TokenWhitelistError, u32, &str)] = &[
+        (TokenWhitelistError::InvalidInstruction, 0, "Invalid Instruction"),
+        (TokenWhitelistError::NotRentExempt, 1, "Not Rent Exempt"),
+        (TokenWhitelistError::TokenWhitelistNotInit, 2, "Token Whitelist Not Initialized"),
+        (TokenWhitelistError::TokenWhitelistNotOwner, 3, "Signer Not Token Whitelist Owner"),
+        (TokenWhitelistError::TokenWhitelistSizeExceeds, 4, "Token Whitelist Size Exceeds"),
+        (TokenWhitelistError::NotOwner, 5, "Signer Not Account Owner"),
+        (TokenWhitelistError::InvalidAuthority, 6, "Invalid authority provided"),
+        (TokenWhitelistError::Overflow, 7, "Calculation overflow"),
+        (TokenWhitelistError::AccountTooSmall, 8, "Whitelist Account Too Small"),
+        (TokenWhitelistError::DelegateAddsExhausted, 9, "Delegate Adds Exhausted"),
+        (TokenWhitelistError::DelegateAllocationExceeded, 10, "Delegate Allocation Exceeded"),
+        (TokenWhitelistError::RegistrationClosed, 11, "Registration Closed"),
+        (TokenWhitelistError::RegistrationOpen, 12, "Registration Still Open"),
+        (TokenWhitelistError::AlreadyRegistered, 13, "Account Already Registered"),
+        (TokenWhitelistError::NoDepositToRefund, 14, "No Deposit To Refund"),
+        (TokenWhitelistError::LotteryAlreadyRun, 15, "Lottery Already Run"),
+        (TokenWhitelistError::AccountNotWhitelisted, 16, "Account Not Whitelisted"),
+        (TokenWhitelistError::InsufficientAllocation, 17, "Insufficient Allocation"),
+        (TokenWhitelistError::ConsumeLimitExceeded, 18, "Consume Limit Exceeded"),
+        (TokenWhitelistError::ConsumeCooldownActive, 19, "Consume Cooldown Active"),
+        (TokenWhitelistError::AllocationDecimalsMismatch, 20, "Allocation Decimals Mismatch"),
+        (TokenWhitelistError::InvalidMint, 21, "Invalid Allocation Mint"),
+        (TokenWhitelistError::AllocationBelowMinimum, 22, "Allocation Below Minimum"),
+        (TokenWhitelistError::AllocationAboveMaximum, 23, "Allocation Above Maximum"),
+        (TokenWhitelistError::InvalidRound, 24, "Invalid Round"),
+        (TokenWhitelistError::NotInRequiredWhitelist, 25, "Account Not In Required Whitelist"),
+        (TokenWhitelistError::InExcludedWhitelist, 26, "Account In Excluded Whitelist"),
+        (TokenWhitelistError::InvalidMemo, 27, "Invalid Memo"),
+        (TokenWhitelistError::WhitelistArchived, 28, "Whitelist Archived"),
+        (TokenWhitelistError::DepositsOutstanding, 29, "Deposits Outstanding"),
+        (TokenWhitelistError::RestrictedTokenExtension, 30, "Restricted Token Extension"),
+        (TokenWhitelistError::AccountNotBlank, 31, "Account Not Blank"),
+        (TokenWhitelistError::UnknownHashKind, 32, "Unknown Hash Kind"),
+        (TokenWhitelistError::AlreadyClaimed, 33, "Already Claimed"),
+        (TokenWhitelistError::StateInvariantViolation, 34, "State Invariant Violation"),
+        (TokenWhitelistError::InvalidWhitelistTarget, 35, "Invalid Whitelist Target"),
+        (TokenWhitelistError::InsufficientGatingBalance, 100, "Insufficient Gating Balance"),
+        (TokenWhitelistError::StaleWhitelistState, 101, "Stale Whitelist State"),
+        (TokenWhitelistError::AttestationRequired, 102, "Attestation Required"),
+        (TokenWhitelistError::AccountAlreadyWhitelisted, 103, "Account Already Whitelisted"),
+        (TokenWhitelistError::DuplicateAcrossWhitelists, 104, "Duplicate Across Whitelists"),
+        (TokenWhitelistError::NoteCommitmentMismatch, 105, "Note Commitment Mismatch"),
+        (TokenWhitelistError::NoteTooLong, 106, "Note Too Long"),
+        (TokenWhitelistError::ZeroWhitelistSize, 107, "Zero Whitelist Size"),
+        (TokenWhitelistError::CompressedTreeFull, 200, "Compressed Tree Full"),
+        (TokenWhitelistError::RegistrationRateLimited, 201, "Registration Rate Limited"),
+        (TokenWhitelistError::OwnerSignatureRequired, 300, "Owner Signature Required"),
+        (TokenWhitelistError::UpgradeAuthorityMismatch, 301, "Signer Not Upgrade Authority"),
+        (TokenWhitelistError::ConfigAccountMissing, 400, "Config Account Missing"),
+        (TokenWhitelistError::ConfigAlreadyInitialized, 401, "Config Already Initialized"),
+        (TokenWhitelistError::GatingAccountMissing, 402, "Gating Account Missing"),
+        (TokenWhitelistError::InvalidWhitelistKey, 403, "Invalid Whitelist Key"),
+        (TokenWhitelistError::CompressedTreeMismatch, 404, "Compressed Tree Mismatch"),
+        (TokenWhitelistError::CompressedTreeAlreadyInitialized, 405, "Compressed Tree Already Initialized"),
+        (TokenWhitelistError::UnlinkedCompanionAccount, 406, "Unlinked Companion Account"),
+        (TokenWhitelistError::SiblingWhitelistMissing, 407, "Sibling Whitelist Missing"),
+    ];
+
+    #[test]
+    fn test_decode_every_variant() {
+        for &(error, code, text) in VARIANTS {
+            assert_eq!(error as u32, code);
+            assert_eq!(error.to_string(), text);
+            assert_eq!(TokenWhitelistError::from_u32(code).map(|e| e as u32), Some(code));
+            assert_eq!(TokenWhitelistError::from_custom_code(code).map(|e| e as u32), Some(code));
+            assert_eq!(
+                TokenWhitelistError::from_program_error(&error.into()).map(|e| e as u32),
+                Some(code)
+            );
+            let logs = vec![
+                "Program log: Instruction: AddToWhitelist".to_string(),
+                format!("Program log: Error: {}", text),
+                format!("Program 11111111111111111111111111111111 failed: custom program error: 0x{:x}", code),
+            ];
+            assert_eq!(TokenWhitelistError::from_transaction_logs(&logs).map(|e| e as u32), Some(code));
+        }
+        // no variant missing from the table, whatever range its code is in
+        let decoded = (0..1_000).filter(|&code| TokenWhitelistError::from_u32(code).is_some()).count();
+        assert_eq!(decoded, VARIANTS.len());
+    }
+
+    #[test]
+    fn test_codes_within_category_range() {
+        for &(error, code, _) in VARIANTS {
+            assert_eq!(error.code(), code);
+            // codes from before the ranges keep their place below the first range
+            assert!(code < 100 || error.category().codes().contains(&code), "{:?} outside its range", error);
+        }
+        assert_eq!(TokenWhitelistError::InvalidInstruction.category(), ErrorCategory::Integration);
+        assert!(!ErrorCategory::Integration.is_user_facing());
+        assert_eq!(TokenWhitelistError::AllocationBelowMinimum.category(), ErrorCategory::Validation);
+        assert_eq!(TokenWhitelistError::TokenWhitelistSizeExceeds.category(), ErrorCategory::Capacity);
+        assert_eq!(TokenWhitelistError::InvalidAuthority.category(), ErrorCategory::Authority);
+        assert!(ErrorCategory::Authority.is_user_facing());
+    }
+
+    #[test]
+    fn test_decode_foreign_errors() {
+        assert!(TokenWhitelistError::from_program_error(&ProgramError::InvalidArgument).is_none());
+        assert!(TokenWhitelistError::from_transaction_logs(&[]).is_none());
+        let logs = vec!["Program log: Instruction: AddToWhitelist".to_string()];
+        assert!(TokenWhitelistError::from_transaction_logs(&logs).is_none());
+    }
+}
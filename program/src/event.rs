@@ -0,0 +1,245 @@
+// The BorshSchema derive of WhitelistEvent declares a struct per variant, with the fields of the
+// variant, that only its schema uses. It does so inside the impl, out of reach of an attribute
+// on the enum.
+#![cfg_attr(feature = "schema", allow(dead_code))]
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+use solana_program::msg;
+#[cfg(feature = "events")]
+use solana_program::log::sol_log_data;
+
+/// Events of mutations, logged as Borsh encoded program data for indexers. Builds without the
+/// `events` feature log only the memos.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub enum WhitelistEvent {
+    /// An entry left the whitelist, with the allocation it held
+    EntryRemoved {
+        account: Pubkey,
+        allocation: u64,
+        memo: Option<String>,
+    },
+    /// An entry was kept with its allocation reset, holding `allocation` before
+    AllocationZeroed {
+        account: Pubkey,
+        allocation: u64,
+        memo: Option<String>,
+    },
+    /// A whitelist was closed, its lamports reclaimed by the destination, with the entries it
+    /// still held and their total allocation
+    WhitelistClosed {
+        whitelist: Pubkey,
+        destination: Pubkey,
+        lamports: u64,
+        entries_discarded: u32,
+        allocation_discarded: u128,
+    },
+    /// A merkle proof of `wallet` holding `allocation` was verified and its leaf marked claimed
+    MerkleClaimVerified {
+        whitelist: Pubkey,
+        wallet: Pubkey,
+        leaf_index: u32,
+        allocation: u64,
+    },
+    /// RepairState rebuilt the map of a whitelist, see RepairReport
+    StateRepaired {
+        whitelist: Pubkey,
+        entries_recovered: u32,
+        entries_dropped: u32,
+        bytes_dropped: u32,
+    },
+    /// The leaf of `wallet` with `allocation` was appended to the compressed tree of a whitelist
+    /// at `leaf_index`, enough for an indexer to mirror the tree
+    CompressedEntryAppended {
+        whitelist: Pubkey,
+        wallet: Pubkey,
+        leaf_index: u32,
+        allocation: u64,
+    },
+    /// The leaf of `wallet` at `leaf_index` was replaced, holding `allocation` now
+    CompressedEntryReplaced {
+        whitelist: Pubkey,
+        wallet: Pubkey,
+        leaf_index: u32,
+        previous_allocation: u64,
+        allocation: u64,
+    },
+    /// An add or remove left the stats of a whitelist at these counts, see WhitelistStats.
+    /// Logged after the event of the change itself, for alerting on churn spikes.
+    StatsUpdated {
+        whitelist: Pubkey,
+        total_adds: u64,
+        total_removes: u64,
+        last_mutation_slot: u64,
+    },
+    /// The attestation of `account` was set, NO_ATTESTATION when it was cleared
+    AttestationSet {
+        whitelist: Pubkey,
+        account: Pubkey,
+        attestation: [u8; 32],
+    },
+    /// The entry of `previous_account` moved to `account` with RotateWhitelistedKey, holding
+    /// `allocation` in round 0
+    KeyRotated {
+        whitelist: Pubkey,
+        previous_account: Pubkey,
+        account: Pubkey,
+        allocation: u64,
+    },
+    /// RevealEntryNote found `note` to match the note commitment of the entry of `account`
+    EntryNoteRevealed {
+        whitelist: Pubkey,
+        account: Pubkey,
+        note: Vec<u8>,
+    },
+}
+
+impl WhitelistEvent {
+    /// Logs the memo, if any, as text and, with the `events` feature, the event as program data
+    pub fn emit(&self) {
+        if let WhitelistEvent::EntryRemoved {memo: Some(memo), ..}
+        | WhitelistEvent::AllocationZeroed {memo: Some(memo), ..} = self
+        {
+            msg!("Memo: {}", memo);
+        }
+        #[cfg(feature = "events")]
+        sol_log_data(&[&self.try_to_vec().unwrap()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_layout() {
+        let account = Pubkey::new_from_array([7; 32]);
+        let event = WhitelistEvent::EntryRemoved {account, allocation: 5, memo: Some("kyc".to_string())};
+        let mut expect = vec![0];
+        expect.extend_from_slice(account.as_ref());
+        expect.extend_from_slice(&5u64.to_le_bytes());
+        expect.extend_from_slice(&[1, 3, 0, 0, 0]);
+        expect.extend_from_slice(b"kyc");
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_closed_event_layout() {
+        let (whitelist, destination) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let event = WhitelistEvent::WhitelistClosed {
+            whitelist,
+            destination,
+            lamports: 9,
+            entries_discarded: 3,
+            allocation_discarded: 600,
+        };
+        let mut expect = vec![2];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(destination.as_ref());
+        expect.extend_from_slice(&9u64.to_le_bytes());
+        expect.extend_from_slice(&3u32.to_le_bytes());
+        expect.extend_from_slice(&600u128.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_merkle_claim_event_layout() {
+        let (whitelist, wallet) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let event = WhitelistEvent::MerkleClaimVerified {whitelist, wallet, leaf_index: 4, allocation: 7};
+        let mut expect = vec![3];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(wallet.as_ref());
+        expect.extend_from_slice(&4u32.to_le_bytes());
+        expect.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_state_repaired_event_layout() {
+        let whitelist = Pubkey::new_from_array([1; 32]);
+        let event = WhitelistEvent::StateRepaired {whitelist, entries_recovered: 5, entries_dropped: 2, bytes_dropped: 30};
+        let mut expect = vec![4];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(&5u32.to_le_bytes());
+        expect.extend_from_slice(&2u32.to_le_bytes());
+        expect.extend_from_slice(&30u32.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_compressed_entry_event_layout() {
+        let (whitelist, wallet) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let event = WhitelistEvent::CompressedEntryReplaced {
+            whitelist,
+            wallet,
+            leaf_index: 4,
+            previous_allocation: 7,
+            allocation: 9,
+        };
+        let mut expect = vec![6];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(wallet.as_ref());
+        expect.extend_from_slice(&4u32.to_le_bytes());
+        expect.extend_from_slice(&7u64.to_le_bytes());
+        expect.extend_from_slice(&9u64.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_stats_event_layout() {
+        let whitelist = Pubkey::new_from_array([1; 32]);
+        let event = WhitelistEvent::StatsUpdated {whitelist, total_adds: 5, total_removes: 2, last_mutation_slot: 90};
+        let mut expect = vec![7];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(&5u64.to_le_bytes());
+        expect.extend_from_slice(&2u64.to_le_bytes());
+        expect.extend_from_slice(&90u64.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_attestation_event_layout() {
+        let (whitelist, account) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let event = WhitelistEvent::AttestationSet {whitelist, account, attestation: [3; 32]};
+        let mut expect = vec![8];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(account.as_ref());
+        expect.extend_from_slice(&[3; 32]);
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_key_rotated_event_layout() {
+        let (whitelist, previous_account) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let account = Pubkey::new_from_array([3; 32]);
+        let event = WhitelistEvent::KeyRotated {whitelist, previous_account, account, allocation: 40};
+        let mut expect = vec![9];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(previous_account.as_ref());
+        expect.extend_from_slice(account.as_ref());
+        expect.extend_from_slice(&40u64.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+
+    #[test]
+    fn test_note_revealed_event_layout() {
+        let (whitelist, account) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let event = WhitelistEvent::EntryNoteRevealed {whitelist, account, note: b"tier 2".to_vec()};
+        let mut expect = vec![10];
+        expect.extend_from_slice(whitelist.as_ref());
+        expect.extend_from_slice(account.as_ref());
+        expect.extend_from_slice(&[6, 0, 0, 0]);
+        expect.extend_from_slice(b"tier 2");
+        assert_eq!(event.try_to_vec().unwrap(), expect);
+        assert_eq!(WhitelistEvent::try_from_slice(&expect).unwrap(), event);
+    }
+}
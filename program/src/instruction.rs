@@ -1,11 +1,76 @@
 use solana_program::{
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
-    // pubkey::Pubkey,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
 };
 use std::convert::TryInto;
 use std::mem::size_of;
 
-use crate::error::TokenWhitelistError::InvalidInstruction;
+use crate::error::TokenWhitelistError::{self, InvalidInstruction, InvalidMemo, NoteTooLong};
+use crate::state::{TokenWhitelist, WhitelistConfig, NO_CAMPAIGN};
+#[cfg(feature = "compression")]
+use crate::compression;
+
+/// Longest memo in bytes carried by a removal
+pub const MAX_MEMO_LEN: usize = 64;
+/// Memo length byte of a removal carrying an expected nonce but no memo, above MAX_MEMO_LEN
+const NO_MEMO: u8 = u8::MAX;
+/// Longest note in bytes RevealEntryNote discloses
+pub const MAX_NOTE_LEN: usize = 128;
+/// Most instruction data a VerifyMerkleMembershipBatch carries, what a 1232 byte transaction
+/// signed by the payer alone leaves for it beside the payer, whitelist, bitmap and program keys
+pub const MAX_MERKLE_BATCH_DATA_LEN: usize = 992;
+/// Most entries InspectState logs, whatever it is asked for, each one log line of at most 65
+/// bytes, well inside the 10 KB log of a transaction
+pub const MAX_INSPECT_ENTRIES: usize = 32;
+
+/// Proof that `wallet` holds `allocation` at `leaf_index` in the tree committed to by the
+/// merkle root
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleClaim {
+    pub wallet: Pubkey,
+    pub leaf_index: u32, // position of the leaf, the bit claimed in the claim bitmap
+    pub allocation: u64,
+    pub proof: Vec<[u8; 32]>, // siblings from the leaf up to the root
+}
+
+/// Leaf of a compressed whitelist holding `allocation` at `leaf_index`, with its proof, see
+/// compression::TreeMirror
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompressedLeaf {
+    pub leaf_index: u32,
+    pub allocation: u64,
+    pub root: [u8; 32], // recent root of the tree the proof leads to
+    pub proof: Vec<[u8; 32]>, // siblings from the leaf up, compression::TREE_DEPTH of them
+}
+
+/// Settings of a whitelist created by
+/// [init_token_whitelist_with_units](fn.init_token_whitelist_with_units.html)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InitWithUnits {
+    pub max_whitelist_size: u64, // at most TokenWhitelist::MAX_ENTRIES
+    pub allocation_decimals: Option<u8>, // copied from the mint when None and one is passed, else 0
+    pub allocation_mint: Option<Pubkey>, // SPL Token or Token-2022 mint the decimals are checked against
+    pub only_wallets: bool, // see SetOnlyWallets
+    pub self_zero_allowed: bool, // see SetSelfZeroAllowed
+}
+
+impl InitWithUnits {
+    /// A whitelist of `max_whitelist_size` entries allocated in base units, taking any account
+    /// and letting whitelisted accounts zero their allocation, as InitTokenWhitelist creates
+    pub fn new(max_whitelist_size: u64) -> Self {
+        InitWithUnits {
+            max_whitelist_size,
+            allocation_decimals: None,
+            allocation_mint: None,
+            only_wallets: false,
+            self_zero_allowed: true,
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum TokenWhitelistInstruction {
@@ -15,17 +80,26 @@ pub enum TokenWhitelistInstruction {
     /// 0. `[signer]` Owner of the whitelist and signer
     /// 1. `[writable]` Account holding whitelist init info
     InitTokenWhitelist {
-        max_whitelist_size: u64, // max number of whitelist accounts
+        max_whitelist_size: u64, // max number of whitelist accounts, at most TokenWhitelist::MAX_ENTRIES
     },
 
     /// Accounts expected by AddToWhitelist
     ///
-    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 0. `[signer]` Owner of the whitelist or its approved delegate and signer
     /// 1. `[writable]` Account holding whitelist init info
-    /// 2. `[]` Account to be added to the whitelist
+    /// 2. `[]` Account to be added to the whitelist, which need not exist on chain
+    /// 3. `[]` (Optional) Mint of a Token-2022 account added under strict token checks
+    /// 4. `[]` (Optional) Config account of the whitelist, required once InitConfig created it,
+    ///    taking the place of the mint when none is passed
+    /// 5. `[]` (Optional) Token account of the gating mint held by the account to add, required
+    ///    when SetTokenGate gated owner adds, after any of the accounts above
+    /// 6. `[]` (Optional) Sibling whitelists linked by AssertNotInOther, every one of them
+    ///    required once linked, after any of the accounts above
     AddToWhitelist {
         // account_to_add: Pubkey, // token account to be whitelisted
         allocation_amount: u64, // maximum allocation amount in base tokens
+        campaign_id: [u8; 8], // campaign the entry is attributed to, packed only when not NO_CAMPAIGN
+        expected_nonce: u64, // mutation nonce the sender read, 0 to skip the check, packed only when set
     },
 
     /// Accounts expected by RemoveFromWhitelist
@@ -35,6 +109,8 @@ pub enum TokenWhitelistInstruction {
     /// 2. `[]` Account to be removed from the whitelist
     RemoveFromWhitelist {
         // account_to_remove: Pubkey, // token account to be removed from the whitelist
+        memo: Option<String>, // reason logged with the removal, at most MAX_MEMO_LEN bytes
+        expected_nonce: u64, // mutation nonce the sender read, 0 to skip the check, packed only when set
     },
 
     /// Accounts expected: SetAllocationToZero
@@ -42,8 +118,12 @@ pub enum TokenWhitelistInstruction {
     /// 0. `[signer]` Owner of the whitelist and signer
     /// 1. `[writable]` Account holding whitelist init info
     /// 2. `[]` Account to be reset to 0
+    /// 3. `[signer]` (Optional) Owner of the whitelist, required once SetSelfZeroAllowed disabled
+    ///    self zeroing
+    /// 4. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
     SetAllocationToZero {
         // account_to_reset: Pubkey, // token account to be reset to 0
+        memo: Option<String>, // reason logged with the reset, at most MAX_MEMO_LEN bytes
     },
 
     /// Accounts expected: CloseWhitelistAccount
@@ -51,9 +131,632 @@ pub enum TokenWhitelistInstruction {
     /// 0. `[signer]` Owner of the whitelist and signer
     /// 1. `[writable]` Account holding whitelist init info
     /// 2. `[writable]` Destination account to transfer lamports to
+    ///
+    /// Refused while any registration deposit is still to be refunded. The entries and
+    /// allocation discarded are written to the return data as ClosedWhitelist
     CloseWhitelistAccount {
         // dest_account: Pubkey, // token account to be reset to 0
     },
+
+    /// Accounts expected: ApproveDelegate
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Account to be approved as delegate
+    ApproveDelegate {
+        max_adds: u32, // number of adds the delegate can make
+        max_allocation_per_add: u64, // maximum allocation amount per add in base tokens
+    },
+
+    /// Accounts expected: RevokeDelegate
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    RevokeDelegate {},
+
+    /// Accounts expected: ConfigureRegistration
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    ConfigureRegistration {
+        registration_end_slot: u64, // registration is open before this slot
+        registration_allocation: u64, // allocation amount given to self registered accounts
+        registration_deposit_lamports: u64, // refundable deposit paid by self registered accounts
+    },
+
+    /// Accounts expected: RegisterSelf
+    ///
+    /// 0. `[signer, writable]` Account to be added to the whitelist, pays the deposit
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` System program
+    /// 3. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
+    /// 4. `[]` (Optional) Token account of the gating mint held by the account to add, required
+    ///    once SetTokenGate set a gate, after the config account if there is one
+    RegisterSelf {},
+
+    /// Accounts expected: RefundDeposit
+    ///
+    /// 0. `[signer]` Registered account after registration closes, or owner of the whitelist
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` Registered account to receive the deposit
+    RefundDeposit {},
+
+    /// Accounts expected: RunLottery
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` SlotHashes sysvar
+    RunLottery {
+        winners: u64, // number of accounts to keep in the whitelist
+        seed: [u8; 32], // seed mixed with the most recent slot hash to rank accounts
+    },
+
+    /// Accounts expected: SetConsumeLimits
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    SetConsumeLimits {
+        max_consume_per_tx: u64, // maximum amount consumed by one instruction, 0 for unlimited
+        min_slots_between_consumes: u64, // slots an account must wait between consumes, 0 for no cooldown
+    },
+
+    /// Accounts expected: ConsumeAllocation
+    ///
+    /// 0. `[signer]` Whitelisted account consuming its allocation
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
+    ConsumeAllocation {
+        amount: u64, // amount in base tokens to deduct from the allocation
+    },
+
+    /// Accounts expected: ListEntries
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[signer]` (Optional) Owner or delegate of the whitelist, required once
+    ///    SetEnumerationRestricted restricted enumeration
+    ListEntries {
+        offset: u32, // index in key order (base58 strings, not pubkey bytes) of the first entry returned
+        limit: u8, // number of entries returned, at most MAX_LIST_ENTRIES
+    },
+
+    /// Accounts expected: ComputeEntriesHash
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    ComputeEntriesHash {},
+
+    /// Accounts expected by InitTokenWhitelistWithUnits
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Rent sysvar
+    /// 3. `[]` (Optional) SPL Token or Token-2022 mint the decimals are copied from
+    InitTokenWhitelistWithUnits {
+        max_whitelist_size: u64, // max number of whitelist accounts, at most TokenWhitelist::MAX_ENTRIES
+        allocation_decimals: Option<u8>, // decimals of allocation amounts, must match the mint if one is passed
+        only_wallets: bool, // see SetOnlyWallets
+        self_zero_allowed: bool, // see SetSelfZeroAllowed
+        // the flags are packed in a trailing byte, bit 0 only_wallets and bit 1 self zeroing
+        // disabled, left out when neither is set
+    },
+
+    /// Accounts expected: SetAllocationBounds
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    SetAllocationBounds {
+        min_allocation: u64, // smallest allocation amount accepted on add, 0 for unbounded
+        max_allocation: u64, // largest allocation amount accepted on add, 0 for unbounded
+    },
+
+    /// Accounts expected by AddToWhitelistForRound
+    ///
+    /// 0. `[signer]` Owner of the whitelist or its approved delegate and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Account to be added to the whitelist, which need not exist on chain
+    /// 3. `[]` (Optional) Mint of a Token-2022 account added under strict token checks
+    /// 4. `[]` (Optional) Config account of the whitelist, required once InitConfig created it,
+    ///    taking the place of the mint when none is passed
+    /// 5. `[]` (Optional) Token account of the gating mint held by the account to add, required
+    ///    when SetTokenGate gated owner adds, after any of the accounts above
+    /// 6. `[]` (Optional) Sibling whitelists linked by AssertNotInOther, every one of them
+    ///    required once linked, after any of the accounts above
+    AddToWhitelistForRound {
+        allocation_amount: u64, // maximum allocation amount in base tokens for the round
+        round: u8, // round index below MAX_ROUNDS, or ACTIVE_ROUND
+        campaign_id: [u8; 8], // campaign the entry is attributed to, packed only when not NO_CAMPAIGN
+        expected_nonce: u64, // mutation nonce the sender read, 0 to skip the check, packed only when set
+    },
+
+    /// Accounts expected: ConsumeAllocationForRound
+    ///
+    /// 0. `[signer]` Whitelisted account consuming its allocation
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
+    ConsumeAllocationForRound {
+        amount: u64, // amount in base tokens to deduct from the allocation of the round
+        round: u8, // round index below MAX_ROUNDS, or ACTIVE_ROUND
+    },
+
+    /// Accounts expected: SetActiveRound
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    SetActiveRound {
+        round: u8, // round index below MAX_ROUNDS
+    },
+
+    /// Accounts expected: GetAllocation
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[]` Whitelisted account
+    /// 2. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
+    GetAllocation {
+        round: u8, // round index below MAX_ROUNDS, or ACTIVE_ROUND
+    },
+
+    /// Accounts expected: AssertMembershipAcross
+    ///
+    /// 0. `[]` Whitelist the account has to be in
+    /// 1. `[]` Whitelist the account has to be absent from
+    /// 2. `[]` Account checked
+    AssertMembershipAcross {
+        require_in_first: bool, // fail unless the account is in the first whitelist
+        require_absent_in_second: bool, // fail if the account is in the second whitelist
+    },
+
+    /// Accounts expected: ArchiveWhitelist
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info, shrunk to ARCHIVE_SPACE
+    /// 2. `[writable]` Destination account to transfer the freed rent to
+    ArchiveWhitelist {},
+
+    /// Accounts expected: GetVersion
+    ///
+    /// No accounts, the ProgramVersion of the deployed program is written to the return data
+    GetVersion {},
+
+    /// Accounts expected: SetStrictTokenChecks
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    SetStrictTokenChecks {
+        strict_token_checks: bool, // refuse non-transferable and permanent-delegate token accounts on add
+    },
+
+    /// Accounts expected: SetMerkleRoot
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    SetMerkleRoot {
+        root: [u8; 32], // root of the tree over (wallet, allocation) leaves
+        hash_kind: u8, // 0 for keccak, 1 for sha256, see merkle::HashKind
+    },
+
+    /// Accounts expected: VerifyMerkleMembership
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[]` Wallet whose leaf is proven
+    VerifyMerkleMembership {
+        leaf_index: u32, // position of the leaf in the tree
+        allocation: u64, // allocation committed to in the leaf
+        proof: Vec<[u8; 32]>, // siblings from the leaf up to the root, packed after a u8 count
+    },
+
+    /// Accounts expected: VerifyMerkleMembershipBatch
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[writable]` Claim bitmap linked to the whitelist by InitClaimBitmap
+    /// 2. `[signer]` Wallets of the claims, in any order
+    ///
+    /// Verifies every claim and sets the bit of its leaf, failing as a whole if any proof is
+    /// invalid or any leaf was claimed before, in this batch or an earlier transaction
+    VerifyMerkleMembershipBatch {
+        items: Vec<MerkleClaim>, // packed after a u8 count, see MAX_MERKLE_BATCH_DATA_LEN
+    },
+
+    /// Accounts expected: InitClaimBitmap
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info, with a merkle root set
+    /// 2. `[writable]` Zeroed, rent exempt account owned by the program of at least
+    ///    claim_bitmap_len(leaf_count) bytes
+    ///
+    /// Links the bitmap to the whitelist, replacing any bitmap linked before
+    InitClaimBitmap {
+        leaf_count: u32, // leaves of the tree under the current merkle root
+    },
+
+    /// Accounts expected: AssertOwner
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[]` Expected owner of the whitelist, it need not sign
+    ///
+    /// Fails with TokenWhitelistNotOwner unless the whitelist is initialized and owned by the
+    /// expected owner, letting a caller pin the whitelist it was given in one read-only CPI
+    AssertOwner {},
+
+    /// Accounts expected: SetOnlyWallets
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    SetOnlyWallets {
+        only_wallets: bool, // only add system owned accounts on the ed25519 curve, refusing PDAs and token accounts
+    },
+
+    /// Accounts expected: InitConfig
+    ///
+    /// 0. `[signer, writable]` Owner of the whitelist and signer, pays the config rent
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` Config account at the program address of CONFIG_SEED and the whitelist
+    /// 3. `[]` System program
+    ///
+    /// Creates the config account with the current policy of the whitelist and links it to the
+    /// whitelist. The instructions reading or changing policy then require it, and changing
+    /// policy writes only the config.
+    InitConfig {},
+
+    /// Accounts expected: SetSelfZeroAllowed
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    ///
+    /// Whitelists allow self zeroing unless this disables it, SetAllocationToZero then also
+    /// needs the signature of the whitelist owner
+    SetSelfZeroAllowed {
+        self_zero_allowed: bool, // whether an account may zero its own entry on its signature alone
+    },
+
+    /// Accounts expected: ListEntriesByCampaign
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[signer]` (Optional) Owner or delegate of the whitelist, required once
+    ///    SetEnumerationRestricted restricted enumeration
+    ///
+    /// Like ListEntries over the entries attributed to one campaign, the total of the page
+    /// counting them, so an empty page at offset 0 gives the campaign count
+    ListEntriesByCampaign {
+        campaign_id: [u8; 8], // campaign listed, NO_CAMPAIGN for the entries added without one
+        offset: u32, // index in key order, among the campaign entries, of the first entry returned
+        limit: u8, // number of entries returned, at most MAX_LIST_ENTRIES
+    },
+
+    /// Accounts expected: TopUpRent
+    ///
+    /// 0. `[signer, writable]` Funder of the shortfall, anyone may pay
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` System program
+    ///
+    /// Transfers exactly what the whitelist lacks to be rent exempt at its current size,
+    /// succeeding without a transfer when it already is
+    TopUpRent {},
+
+    /// Accounts expected: RepairState
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    ///
+    /// Recovery for a whitelist whose map region no longer decodes, e.g. after a bad length
+    /// prefix was written. The map is rebuilt from the entries that still parse, see
+    /// `TokenWhitelist::recover_from_slice`, and written back with its length prefix and
+    /// entries hash recomputed. The RepairReport goes to the return data and the log, so a
+    /// simulation shows what a repair keeps. Healthy whitelists are left untouched.
+    RepairState {},
+
+    /// Accounts expected: SetZeroAllocationAllowed
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    ///
+    /// Whitelists accept adds of a zero allocation unless this disables them, adds by the owner
+    /// or the delegate then fail with AllocationBelowMinimum. RegisterSelf is not affected.
+    SetZeroAllocationAllowed {
+        allow_zero_allocation: bool, // whether adds may grant a zero allocation
+    },
+
+    /// Accounts expected: SetTokenGate
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Gating mint, omitted when the gate is cleared
+    /// 3. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    ///
+    /// Gates the whitelist on a balance of `gating_mint`: RegisterSelf, and with
+    /// `gate_owner_adds` adds by the owner or the delegate, then need a token account of the
+    /// mint held by the account being whitelisted. A zero `gating_minimum` clears the gate.
+    SetTokenGate {
+        gating_mint: Pubkey,
+        gating_minimum: u64, // smallest balance accepted, in base units of the gating mint
+        gate_owner_adds: bool, // gate adds by the owner and the delegate too
+    },
+
+    /// Accounts expected: MigrateStateLayout
+    ///
+    /// 0. `[signer]` Upgrade authority of the program
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` ProgramData account of the program, at the address the upgradeable loader
+    ///    derives from the program id
+    ///
+    /// Rewrites a whitelist in the current layout: an extension written by an older program is
+    /// packed again with every field it lacked at its default, and the entries hash is
+    /// recomputed. Signed by the upgrade authority rather than the owner, so whoever deploys a
+    /// layout change can carry the accounts over. Programs made immutable have no upgrade
+    /// authority and refuse it. Whitelists already in the current layout are left untouched.
+    MigrateStateLayout {},
+
+    /// Accounts expected: InspectState
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[signer]` (Optional) Owner or delegate of the whitelist, required once
+    ///    SetEnumerationRestricted restricted enumeration
+    ///
+    /// Logs the header of the whitelist and its first entries in key order, one
+    /// `key=allocation` line each, for reading an account from an explorer without client
+    /// tooling. Writes nothing.
+    InspectState {
+        max_entries_to_log: u8, // entries logged, at most MAX_INSPECT_ENTRIES
+    },
+
+    /// Accounts expected: EffectiveAllocation
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[]` Whitelisted account
+    /// 2. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
+    ///
+    /// Returns the u64 (le) the account may consume right now in the active round, see
+    /// `rules::effective_allocation`, failing with AccountNotWhitelisted for an account outside
+    /// the whitelist
+    EffectiveAllocation {},
+
+    /// Accounts expected: InitCompressedWhitelist
+    ///
+    /// 0. `[signer]` Whitelist owner
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` Tree account of compression::tree_space() bytes, assigned to the
+    ///    compression program and not initialized yet
+    /// 3. `[]` Tree authority of the whitelist, see compression::find_tree_authority
+    /// 4. `[]` spl-account-compression program
+    /// 5. `[]` spl-noop program
+    ///
+    /// Initializes an empty tree of compression::TREE_DEPTH under the tree authority of the
+    /// whitelist and records it as the compressed tree of the whitelist. The map keeps working
+    /// beside it, entries of the tree are only reached through the compressed instructions.
+    InitCompressedWhitelist {},
+
+    /// Accounts expected: AppendCompressedEntry
+    ///
+    /// 0. `[signer]` Whitelist owner
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Account being whitelisted
+    /// 3. `[writable]` Compressed tree of the whitelist
+    /// 4. `[]` Tree authority of the whitelist
+    /// 5. `[]` spl-account-compression program
+    /// 6. `[]` spl-noop program
+    ///
+    /// Appends the leaf of the account and its allocation at the next leaf index. The tree
+    /// cannot be searched on-chain, an account appended twice holds two leaves.
+    AppendCompressedEntry {
+        allocation_amount: u64,
+    },
+
+    /// Accounts expected: ReplaceCompressedEntry
+    ///
+    /// 0. `[signer]` Whitelist owner
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Whitelisted account
+    /// 3. `[writable]` Compressed tree of the whitelist
+    /// 4. `[]` Tree authority of the whitelist
+    /// 5. `[]` spl-account-compression program
+    /// 6. `[]` spl-noop program
+    /// 7. `[]` Proof of the leaf, one account per node from the leaf up, its key being the node
+    ///
+    /// Replaces the leaf of the account at `leaf_index` holding `previous_allocation` with one
+    /// holding `allocation_amount`, the proof leading to `root`, a recent root of the tree
+    ReplaceCompressedEntry {
+        leaf_index: u32,
+        previous_allocation: u64,
+        allocation_amount: u64,
+        root: [u8; 32],
+    },
+
+    /// Accounts expected: VerifyCompressedMembership
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[]` Wallet whose leaf is proven
+    /// 2. `[]` Compressed tree of the whitelist
+    /// 3. `[]` spl-account-compression program
+    /// 4. `[]` Proof of the leaf, one account per node from the leaf up, its key being the node
+    ///
+    /// Fails unless the proof leads from the leaf of the wallet and `allocation` at `leaf_index`
+    /// to `root`, a recent root of the tree
+    VerifyCompressedMembership {
+        leaf_index: u32,
+        allocation: u64,
+        root: [u8; 32],
+    },
+
+    /// Accounts expected: GetWhitelistStats
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    ///
+    /// Writes the WhitelistStatsReport of the whitelist, its stats and creation record, Borsh
+    /// encoded, to the return data
+    GetWhitelistStats {},
+
+    /// Accounts expected: AddToWhitelistWithAttestation
+    ///
+    /// 0. `[signer]` Owner of the whitelist or its approved delegate and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Account to be added to the whitelist, which need not exist on chain
+    /// 3. `[]` (Optional) Accounts as for AddToWhitelist
+    ///
+    /// AddToWhitelist recording `attestation` with the entry, e.g. the hash of the KYC record
+    /// the owner checked the account against. NO_ATTESTATION keeps what the entry held.
+    AddToWhitelistWithAttestation {
+        allocation_amount: u64, // maximum allocation amount in base tokens
+        attestation: [u8; 32], // reference to an off-chain attestation, usually a hash of it
+    },
+
+    /// Accounts expected: SetAttestation
+    ///
+    /// 0. `[signer]` Owner of the whitelist or its approved delegate and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Whitelisted account
+    ///
+    /// Replaces the attestation of a whitelisted account, NO_ATTESTATION clearing it unless
+    /// SetAttestationRequired made attestations required. Uses none of the adds of a delegate.
+    SetAttestation {
+        attestation: [u8; 32],
+    },
+
+    /// Accounts expected: SetAttestationRequired
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    ///
+    /// Once attestations are required, adds leaving an entry without one fail with
+    /// AttestationRequired and RegisterSelf is refused. Entries already in the whitelist keep
+    /// their allocation whether attested or not.
+    SetAttestationRequired {
+        require_attestation: bool,
+    },
+
+    /// Accounts expected: ListAttestedEntries
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[signer]` (Optional) Owner or delegate of the whitelist, required once
+    ///    SetEnumerationRestricted restricted enumeration
+    ///
+    /// ListEntries with the attestation of each entry, writing an AttestedEntriesPage
+    ListAttestedEntries {
+        offset: u32, // index in key order of the first entry returned, as for ListEntries
+        limit: u8, // number of entries returned, at most MAX_LIST_ATTESTED_ENTRIES
+    },
+
+    /// Accounts expected: RotateWhitelistedKey
+    ///
+    /// 0. `[signer]` Whitelisted account and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[signer]` Account taking over the entry, not whitelisted yet
+    /// 3. `[]` (Optional) Config account of the whitelist, required once InitConfig created it
+    /// 4. `[]` (Optional) Token account of the gating mint held by the account taking over,
+    ///    required once SetTokenGate set a gate, after the config account if there is one
+    /// 5. `[]` (Optional) Sibling whitelists linked by AssertNotInOther, every one of them
+    ///    required once linked, after any of the accounts above
+    ///
+    /// Moves the entry of a whitelisted account to a new key without the owner: the allocations
+    /// of every round, the campaign, the attestation, the registration deposit and the last
+    /// consume slot. Both accounts sign, so neither can be rotated away or imposed by the other.
+    /// The account taking over is refused where an add of it would be.
+    RotateWhitelistedKey {},
+
+    /// Accounts expected: AssertNotInOther
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` (Optional) Sibling whitelists, at most MAX_SIBLING_WHITELISTS, none unlinking them
+    ///
+    /// Links the sibling whitelists in place of the ones linked before. Adds then take them
+    /// read-only and fail with DuplicateAcrossWhitelists for an account holding an entry in any
+    /// of them, so the same wallet cannot be listed in both a whitelist and its siblings.
+    AssertNotInOther {},
+
+    /// Accounts expected: SetOwnerEntryAllowed
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[writable]` (Optional) Config account of the whitelist, required once InitConfig
+    ///    created it, the policy is then written there and the whitelist left unchanged
+    ///
+    /// Whitelists accept an entry for their owner unless this disables it, adds and self
+    /// registrations of the owner then fail with InvalidWhitelistTarget. An entry the owner
+    /// already holds is kept. Entries for the whitelist itself or the program are refused either
+    /// way.
+    SetOwnerEntryAllowed {
+        allow_owner_entry: bool, // whether the owner may hold an entry
+    },
+
+    /// Accounts expected: GetRequiredRent
+    ///
+    /// No accounts, the RequiredRent of a whitelist of up to `max_whitelist_size` entries is
+    /// written to the return data: the bytes to allocate, see TokenWhitelist::space_for, and
+    /// their rent exempt minimum under the cluster rent. Sizes are checked as InitTokenWhitelist
+    /// checks them, zero fails with ZeroWhitelistSize and sizes above MAX_ENTRIES other than
+    /// UNLIMITED_WHITELIST_SIZE with TokenWhitelistSizeExceeds.
+    GetRequiredRent {
+        max_whitelist_size: u64, // size the whitelist is going to be initialized with
+    },
+
+    /// Accounts expected: SetEntryNoteCommitment
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    /// 2. `[]` Whitelisted account
+    ///
+    /// Attaches to the entry of a whitelisted account a commitment to a note, see
+    /// state::note_commitment, NO_NOTE_COMMITMENT clearing it. Only the commitment goes on
+    /// chain: the note stays with whoever the owner hands it and its salt, the holder of the
+    /// entry usually, until RevealEntryNote discloses it.
+    SetEntryNoteCommitment {
+        commitment: [u8; 32], // keccak hash of the note followed by its salt
+    },
+
+    /// Accounts expected: RevealEntryNote
+    ///
+    /// 0. `[]` Account holding whitelist init info
+    /// 1. `[]` Whitelisted account
+    ///
+    /// Checks `note` and `salt` against the note commitment of the entry and logs the note in
+    /// an EntryNoteRevealed event, failing with NoteCommitmentMismatch when they do not hash to
+    /// it or the entry has none. Changes nothing.
+    RevealEntryNote {
+        note: Vec<u8>, // packed after a u8 length, at most MAX_NOTE_LEN bytes
+        salt: [u8; 16],
+    },
+
+    /// Accounts expected: SetRegistrationRateLimit
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    ///
+    /// Caps RegisterSelf at `max_registrations_per_window` per window of `window_slots` slots,
+    /// failing with RegistrationRateLimited past it. A window starts at the first registration
+    /// after the previous one is over. Adds by the owner or the delegate are not counted. Setting
+    /// the limit starts the count over, a zero maximum lifts it.
+    SetRegistrationRateLimit {
+        max_registrations_per_window: u16, // self registrations allowed in a window, 0 for no limit
+        window_slots: u32, // length of a window in slots, not 0 with a limit
+    },
+
+    /// Accounts expected: SetEnumerationRestricted
+    ///
+    /// 0. `[signer]` Owner of the whitelist and signer
+    /// 1. `[writable]` Account holding whitelist init info
+    ///
+    /// Once restricted, the instructions enumerating entries, ListEntries, ListEntriesByCampaign,
+    /// ListAttestedEntries and InspectState, need the signature of the owner or the delegate.
+    /// Lookups of a single account, GetAllocation or the membership checks, stay open. The
+    /// account data itself remains public.
+    SetEnumerationRestricted {
+        restrict_enumeration: bool,
+    },
 }
 
 impl TokenWhitelistInstruction {
@@ -73,125 +776,2888 @@ impl TokenWhitelistInstruction {
             },
             1 => {
                 // let (account_to_add, _rest) = Self::unpack_pubkey(rest)?;
-                let (allocation_amount, _rest) = rest.split_at(8);
+                let (allocation_amount, rest) = rest.split_at(8);
                 let allocation_amount = allocation_amount
                     .try_into()
                     .ok()
                     .map(u64::from_le_bytes)
                     .ok_or(InvalidInstruction)?;
-                Self::AddToWhitelist {allocation_amount}
+                let (campaign_id, expected_nonce) = Self::unpack_campaign_and_nonce(rest)?;
+                Self::AddToWhitelist {allocation_amount, campaign_id, expected_nonce}
             },
             2 => {
                 // let (account_to_remove, _rest) = Self::unpack_pubkey(rest)?;
-                Self::RemoveFromWhitelist {}
+                let (memo, expected_nonce) = Self::unpack_memo_and_nonce(rest)?;
+                Self::RemoveFromWhitelist {memo, expected_nonce}
             },
             3 => {
                 // let (account_to_reset, _rest) = Self::unpack_pubkey(rest)?;
-                Self::SetAllocationToZero {}
+                Self::SetAllocationToZero {memo: Self::unpack_memo(rest)?}
             },
             4 => {
                 // let (dest_account, _rest) = Self::unpack_pubkey(rest)?;
                 Self::CloseWhitelistAccount {}
             },
-            _ => return Err(InvalidInstruction.into()),
-        })
-    }
-
-    /// Packs a [TokenWhitelistInstruction](enum.TokenWhitelistInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match self {
-            &Self::InitTokenWhitelist {max_whitelist_size} => {
-                buf.push(0);
-                buf.extend_from_slice(&max_whitelist_size.to_le_bytes());
-            }
-            &Self::AddToWhitelist {allocation_amount} => {
-                buf.push(1);
+            5 => {
+                let (max_adds, rest) = rest.split_at(4);
+                let max_adds = max_adds
+                    .try_into()
+                    .ok()
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (max_allocation_per_add, _rest) = rest.split_at(8);
+                let max_allocation_per_add = max_allocation_per_add
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::ApproveDelegate {max_adds, max_allocation_per_add}
+            },
+            6 => {
+                Self::RevokeDelegate {}
+            },
+            7 => {
+                let (registration_end_slot, rest) = rest.split_at(8);
+                let registration_end_slot = registration_end_slot
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (registration_allocation, rest) = rest.split_at(8);
+                let registration_allocation = registration_allocation
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (registration_deposit_lamports, _rest) = rest.split_at(8);
+                let registration_deposit_lamports = registration_deposit_lamports
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::ConfigureRegistration {
+                    registration_end_slot,
+                    registration_allocation,
+                    registration_deposit_lamports,
+                }
+            },
+            8 => {
+                Self::RegisterSelf {}
+            },
+            9 => {
+                Self::RefundDeposit {}
+            },
+            10 => {
+                let (winners, rest) = rest.split_at(8);
+                let winners = winners
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (seed, _rest) = rest.split_at(32);
+                let seed = seed
+                    .try_into()
+                    .ok()
+                    .ok_or(InvalidInstruction)?;
+                Self::RunLottery {winners, seed}
+            },
+            11 => {
+                let (max_consume_per_tx, rest) = rest.split_at(8);
+                let max_consume_per_tx = max_consume_per_tx
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (min_slots_between_consumes, _rest) = rest.split_at(8);
+                let min_slots_between_consumes = min_slots_between_consumes
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetConsumeLimits {max_consume_per_tx, min_slots_between_consumes}
+            },
+            12 => {
+                let (amount, _rest) = rest.split_at(8);
+                let amount = amount
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::ConsumeAllocation {amount}
+            },
+            #[cfg(feature = "pages")]
+            13 => {
+                let (offset, rest) = rest.split_at(4);
+                let offset = offset
+                    .try_into()
+                    .ok()
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let &limit = rest.first().ok_or(InvalidInstruction)?;
+                Self::ListEntries {offset, limit}
+            },
+            14 => {
+                Self::ComputeEntriesHash {}
+            },
+            15 => {
+                let (max_whitelist_size, rest) = rest.split_at(8);
+                let max_whitelist_size = max_whitelist_size
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (allocation_decimals, rest) = match rest {
+                    [0, rest @ ..] => (None, rest),
+                    [1, decimals, rest @ ..] => (Some(*decimals), rest),
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                let flags = match rest {
+                    [] => 0,
+                    [flags] if flags & !0b11 == 0 => *flags,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::InitTokenWhitelistWithUnits {
+                    max_whitelist_size,
+                    allocation_decimals,
+                    only_wallets: flags & 0b01 != 0,
+                    self_zero_allowed: flags & 0b10 == 0,
+                }
+            },
+            16 => {
+                let (min_allocation, rest) = rest.split_at(8);
+                let min_allocation = min_allocation
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (max_allocation, _rest) = rest.split_at(8);
+                let max_allocation = max_allocation
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetAllocationBounds {min_allocation, max_allocation}
+            },
+            17 | 18 => {
+                let (amount, rest) = rest.split_at(8);
+                let amount = amount
+                    .try_into()
+                    .ok()
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (&round, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                match tag {
+                    17 => {
+                        let (campaign_id, expected_nonce) = Self::unpack_campaign_and_nonce(rest)?;
+                        Self::AddToWhitelistForRound {allocation_amount: amount, round, campaign_id, expected_nonce}
+                    },
+                    _ => Self::ConsumeAllocationForRound {amount, round},
+                }
+            },
+            19 => {
+                let &round = rest.first().ok_or(InvalidInstruction)?;
+                Self::SetActiveRound {round}
+            },
+            20 => {
+                let &round = rest.first().ok_or(InvalidInstruction)?;
+                Self::GetAllocation {round}
+            },
+            21 => {
+                let flag = |byte: Option<&u8>| match byte {
+                    Some(0) => Ok(false),
+                    Some(1) => Ok(true),
+                    _ => Err(InvalidInstruction),
+                };
+                let require_in_first = flag(rest.first())?;
+                let require_absent_in_second = flag(rest.get(1))?;
+                Self::AssertMembershipAcross {require_in_first, require_absent_in_second}
+            },
+            22 => {
+                Self::ArchiveWhitelist {}
+            },
+            23 => {
+                Self::GetVersion {}
+            },
+            24 => {
+                let strict_token_checks = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetStrictTokenChecks {strict_token_checks}
+            },
+            #[cfg(feature = "merkle")]
+            25 => {
+                let root = rest
+                    .get(..32)
+                    .and_then(|root| root.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let &hash_kind = rest.get(32).ok_or(InvalidInstruction)?;
+                Self::SetMerkleRoot {root, hash_kind}
+            },
+            #[cfg(feature = "merkle")]
+            26 => {
+                let leaf_index = rest
+                    .get(..4)
+                    .and_then(|leaf_index| leaf_index.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let allocation = rest
+                    .get(4..12)
+                    .and_then(|allocation| allocation.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (proof, rest) = Self::unpack_proof(&rest[12..])?;
+                if !rest.is_empty() {
+                    return Err(InvalidInstruction.into());
+                }
+                Self::VerifyMerkleMembership {leaf_index, allocation, proof}
+            },
+            #[cfg(feature = "merkle")]
+            27 => {
+                let (&count, mut rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let mut items = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let wallet = rest
+                        .get(..32)
+                        .and_then(|wallet| wallet.try_into().ok())
+                        .map(Pubkey::new_from_array)
+                        .ok_or(InvalidInstruction)?;
+                    let leaf_index = rest
+                        .get(32..36)
+                        .and_then(|leaf_index| leaf_index.try_into().ok())
+                        .map(u32::from_le_bytes)
+                        .ok_or(InvalidInstruction)?;
+                    let allocation = rest
+                        .get(36..44)
+                        .and_then(|allocation| allocation.try_into().ok())
+                        .map(u64::from_le_bytes)
+                        .ok_or(InvalidInstruction)?;
+                    let (proof, next) = Self::unpack_proof(&rest[44..])?;
+                    items.push(MerkleClaim {wallet, leaf_index, allocation, proof});
+                    rest = next;
+                }
+                if !rest.is_empty() {
+                    return Err(InvalidInstruction.into());
+                }
+                Self::VerifyMerkleMembershipBatch {items}
+            },
+            #[cfg(feature = "merkle")]
+            28 => {
+                let leaf_count = rest
+                    .get(..4)
+                    .and_then(|leaf_count| leaf_count.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::InitClaimBitmap {leaf_count}
+            },
+            29 => {
+                Self::AssertOwner {}
+            },
+            30 => {
+                let only_wallets = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetOnlyWallets {only_wallets}
+            },
+            31 => {
+                Self::TopUpRent {}
+            },
+            32 => {
+                Self::InitConfig {}
+            },
+            33 => {
+                let self_zero_allowed = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetSelfZeroAllowed {self_zero_allowed}
+            },
+            #[cfg(feature = "pages")]
+            34 => {
+                let campaign_id = rest.get(..8)
+                    .and_then(|campaign_id| campaign_id.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let offset = rest.get(8..12)
+                    .and_then(|offset| offset.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let &limit = rest.get(12).ok_or(InvalidInstruction)?;
+                Self::ListEntriesByCampaign {campaign_id, offset, limit}
+            },
+            35 => {
+                Self::RepairState {}
+            },
+            36 => {
+                let allow_zero_allocation = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetZeroAllocationAllowed {allow_zero_allocation}
+            },
+            37 => {
+                let gating_mint = rest
+                    .get(..32)
+                    .and_then(|gating_mint| gating_mint.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(InvalidInstruction)?;
+                let gating_minimum = rest
+                    .get(32..40)
+                    .and_then(|gating_minimum| gating_minimum.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let gate_owner_adds = match rest.get(40) {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetTokenGate {gating_mint, gating_minimum, gate_owner_adds}
+            },
+            38 => {
+                Self::MigrateStateLayout {}
+            },
+            39 => {
+                let &max_entries_to_log = rest.first().ok_or(InvalidInstruction)?;
+                Self::InspectState {max_entries_to_log}
+            },
+            40 => {
+                Self::EffectiveAllocation {}
+            },
+            #[cfg(feature = "compression")]
+            41 => {
+                Self::InitCompressedWhitelist {}
+            },
+            #[cfg(feature = "compression")]
+            42 => {
+                let allocation_amount = rest
+                    .get(..8)
+                    .and_then(|allocation_amount| allocation_amount.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::AppendCompressedEntry {allocation_amount}
+            },
+            #[cfg(feature = "compression")]
+            43 => {
+                let leaf_index = rest
+                    .get(..4)
+                    .and_then(|leaf_index| leaf_index.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let previous_allocation = rest
+                    .get(4..12)
+                    .and_then(|previous_allocation| previous_allocation.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let allocation_amount = rest
+                    .get(12..20)
+                    .and_then(|allocation_amount| allocation_amount.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let root = rest
+                    .get(20..52)
+                    .and_then(|root| root.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::ReplaceCompressedEntry {leaf_index, previous_allocation, allocation_amount, root}
+            },
+            #[cfg(feature = "compression")]
+            44 => {
+                let leaf_index = rest
+                    .get(..4)
+                    .and_then(|leaf_index| leaf_index.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let allocation = rest
+                    .get(4..12)
+                    .and_then(|allocation| allocation.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let root = rest
+                    .get(12..44)
+                    .and_then(|root| root.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::VerifyCompressedMembership {leaf_index, allocation, root}
+            },
+            45 => {
+                Self::GetWhitelistStats {}
+            },
+            46 => {
+                let allocation_amount = rest
+                    .get(..8)
+                    .and_then(|allocation_amount| allocation_amount.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let attestation = rest
+                    .get(8..40)
+                    .and_then(|attestation| attestation.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::AddToWhitelistWithAttestation {allocation_amount, attestation}
+            },
+            47 => {
+                let attestation = rest
+                    .get(..32)
+                    .and_then(|attestation| attestation.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::SetAttestation {attestation}
+            },
+            48 => {
+                let require_attestation = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetAttestationRequired {require_attestation}
+            },
+            #[cfg(feature = "pages")]
+            49 => {
+                let offset = rest
+                    .get(..4)
+                    .and_then(|offset| offset.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let &limit = rest.get(4).ok_or(InvalidInstruction)?;
+                Self::ListAttestedEntries {offset, limit}
+            },
+            50 => {
+                Self::RotateWhitelistedKey {}
+            },
+            51 => {
+                Self::AssertNotInOther {}
+            },
+            52 => {
+                let allow_owner_entry = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetOwnerEntryAllowed {allow_owner_entry}
+            },
+            53 => {
+                let max_whitelist_size = rest.get(..8)
+                    .and_then(|max_whitelist_size| max_whitelist_size.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::GetRequiredRent {max_whitelist_size}
+            },
+            54 => {
+                let commitment = rest
+                    .get(..32)
+                    .and_then(|commitment| commitment.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::SetEntryNoteCommitment {commitment}
+            },
+            55 => {
+                let (&len, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                if len as usize > MAX_NOTE_LEN {
+                    return Err(NoteTooLong.into());
+                }
+                let note = rest.get(..len as usize).ok_or(InvalidInstruction)?.to_vec();
+                let salt = rest
+                    .get(len as usize..len as usize + 16)
+                    .and_then(|salt| salt.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::RevealEntryNote {note, salt}
+            },
+            56 => {
+                let max_registrations_per_window = rest.get(..2)
+                    .and_then(|max_registrations_per_window| max_registrations_per_window.try_into().ok())
+                    .map(u16::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let window_slots = rest.get(2..6)
+                    .and_then(|window_slots| window_slots.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetRegistrationRateLimit {max_registrations_per_window, window_slots}
+            },
+            57 => {
+                let restrict_enumeration = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetEnumerationRestricted {restrict_enumeration}
+            },
+            _ => return Err(InvalidInstruction.into()),
+        })
+    }
+
+    /// Packs a [TokenWhitelistInstruction](enum.TokenWhitelistInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match *self {
+            Self::InitTokenWhitelist {max_whitelist_size} => {
+                buf.push(0);
+                buf.extend_from_slice(&max_whitelist_size.to_le_bytes());
+            }
+            Self::AddToWhitelist {allocation_amount, campaign_id, expected_nonce} => {
+                buf.push(1);
                 buf.extend_from_slice(&allocation_amount.to_le_bytes());
+                Self::pack_campaign_and_nonce(campaign_id, expected_nonce, &mut buf);
             }
-            &Self::RemoveFromWhitelist{} => {
+            Self::RemoveFromWhitelist {ref memo, expected_nonce} => {
                 buf.push(2);
+                match (memo, expected_nonce) {
+                    (None, 0) => {}
+                    (None, _) => buf.push(NO_MEMO),
+                    (memo, _) => Self::pack_memo(memo, &mut buf),
+                }
+                if expected_nonce != 0 {
+                    buf.extend_from_slice(&expected_nonce.to_le_bytes());
+                }
             }
-            &Self::SetAllocationToZero{} => {
+            Self::SetAllocationToZero {ref memo} => {
                 buf.push(3);
+                Self::pack_memo(memo, &mut buf);
             }
-            &Self::CloseWhitelistAccount{} => {
+            Self::CloseWhitelistAccount{} => {
                 buf.push(4);
             }
+            Self::ApproveDelegate {max_adds, max_allocation_per_add} => {
+                buf.push(5);
+                buf.extend_from_slice(&max_adds.to_le_bytes());
+                buf.extend_from_slice(&max_allocation_per_add.to_le_bytes());
+            }
+            Self::RevokeDelegate{} => {
+                buf.push(6);
+            }
+            Self::ConfigureRegistration {
+                registration_end_slot,
+                registration_allocation,
+                registration_deposit_lamports,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(&registration_end_slot.to_le_bytes());
+                buf.extend_from_slice(&registration_allocation.to_le_bytes());
+                buf.extend_from_slice(&registration_deposit_lamports.to_le_bytes());
+            }
+            Self::RegisterSelf{} => {
+                buf.push(8);
+            }
+            Self::RefundDeposit{} => {
+                buf.push(9);
+            }
+            Self::RunLottery {winners, seed} => {
+                buf.push(10);
+                buf.extend_from_slice(&winners.to_le_bytes());
+                buf.extend_from_slice(&seed);
+            }
+            Self::SetConsumeLimits {max_consume_per_tx, min_slots_between_consumes} => {
+                buf.push(11);
+                buf.extend_from_slice(&max_consume_per_tx.to_le_bytes());
+                buf.extend_from_slice(&min_slots_between_consumes.to_le_bytes());
+            }
+            Self::ConsumeAllocation {amount} => {
+                buf.push(12);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ListEntries {offset, limit} => {
+                buf.push(13);
+                buf.extend_from_slice(&offset.to_le_bytes());
+                buf.push(limit);
+            }
+            Self::ComputeEntriesHash{} => {
+                buf.push(14);
+            }
+            Self::InitTokenWhitelistWithUnits {max_whitelist_size, allocation_decimals, only_wallets, self_zero_allowed} => {
+                buf.push(15);
+                buf.extend_from_slice(&max_whitelist_size.to_le_bytes());
+                match allocation_decimals {
+                    Some(decimals) => buf.extend_from_slice(&[1, decimals]),
+                    None => buf.push(0),
+                }
+                let flags = only_wallets as u8 | (!self_zero_allowed as u8) << 1;
+                if flags != 0 {
+                    buf.push(flags);
+                }
+            }
+            Self::SetAllocationBounds {min_allocation, max_allocation} => {
+                buf.push(16);
+                buf.extend_from_slice(&min_allocation.to_le_bytes());
+                buf.extend_from_slice(&max_allocation.to_le_bytes());
+            }
+            Self::AddToWhitelistForRound {allocation_amount, round, campaign_id, expected_nonce} => {
+                buf.push(17);
+                buf.extend_from_slice(&allocation_amount.to_le_bytes());
+                buf.push(round);
+                Self::pack_campaign_and_nonce(campaign_id, expected_nonce, &mut buf);
+            }
+            Self::ConsumeAllocationForRound {amount, round} => {
+                buf.push(18);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(round);
+            }
+            Self::SetActiveRound {round} => {
+                buf.push(19);
+                buf.push(round);
+            }
+            Self::GetAllocation {round} => {
+                buf.push(20);
+                buf.push(round);
+            }
+            Self::AssertMembershipAcross {require_in_first, require_absent_in_second} => {
+                buf.push(21);
+                buf.push(require_in_first as u8);
+                buf.push(require_absent_in_second as u8);
+            }
+            Self::ArchiveWhitelist {} => {
+                buf.push(22);
+            }
+            Self::GetVersion {} => {
+                buf.push(23);
+            }
+            Self::SetStrictTokenChecks {strict_token_checks} => {
+                buf.push(24);
+                buf.push(strict_token_checks as u8);
+            }
+            Self::SetMerkleRoot {root, hash_kind} => {
+                buf.push(25);
+                buf.extend_from_slice(&root);
+                buf.push(hash_kind);
+            }
+            Self::VerifyMerkleMembership {leaf_index, allocation, ref proof} => {
+                buf.push(26);
+                buf.extend_from_slice(&leaf_index.to_le_bytes());
+                buf.extend_from_slice(&allocation.to_le_bytes());
+                Self::pack_proof(proof, &mut buf);
+            }
+            Self::VerifyMerkleMembershipBatch {ref items} => {
+                buf.push(27);
+                buf.push(items.len() as u8);
+                for item in items {
+                    buf.extend_from_slice(item.wallet.as_ref());
+                    buf.extend_from_slice(&item.leaf_index.to_le_bytes());
+                    buf.extend_from_slice(&item.allocation.to_le_bytes());
+                    Self::pack_proof(&item.proof, &mut buf);
+                }
+            }
+            Self::InitClaimBitmap {leaf_count} => {
+                buf.push(28);
+                buf.extend_from_slice(&leaf_count.to_le_bytes());
+            }
+            Self::AssertOwner {} => {
+                buf.push(29);
+            }
+            Self::SetOnlyWallets {only_wallets} => {
+                buf.push(30);
+                buf.push(only_wallets as u8);
+            }
+            Self::TopUpRent {} => {
+                buf.push(31);
+            }
+            Self::InitConfig {} => {
+                buf.push(32);
+            }
+            Self::SetSelfZeroAllowed {self_zero_allowed} => {
+                buf.push(33);
+                buf.push(self_zero_allowed as u8);
+            }
+            Self::ListEntriesByCampaign {campaign_id, offset, limit} => {
+                buf.push(34);
+                buf.extend_from_slice(&campaign_id);
+                buf.extend_from_slice(&offset.to_le_bytes());
+                buf.push(limit);
+            }
+            Self::RepairState {} => {
+                buf.push(35);
+            }
+            Self::SetZeroAllocationAllowed {allow_zero_allocation} => {
+                buf.push(36);
+                buf.push(allow_zero_allocation as u8);
+            }
+            Self::SetTokenGate {gating_mint, gating_minimum, gate_owner_adds} => {
+                buf.push(37);
+                buf.extend_from_slice(gating_mint.as_ref());
+                buf.extend_from_slice(&gating_minimum.to_le_bytes());
+                buf.push(gate_owner_adds as u8);
+            }
+            Self::MigrateStateLayout {} => {
+                buf.push(38);
+            }
+            Self::InspectState {max_entries_to_log} => {
+                buf.push(39);
+                buf.push(max_entries_to_log);
+            }
+            Self::EffectiveAllocation {} => {
+                buf.push(40);
+            }
+            Self::InitCompressedWhitelist {} => {
+                buf.push(41);
+            }
+            Self::AppendCompressedEntry {allocation_amount} => {
+                buf.push(42);
+                buf.extend_from_slice(&allocation_amount.to_le_bytes());
+            }
+            Self::ReplaceCompressedEntry {leaf_index, previous_allocation, allocation_amount, root} => {
+                buf.push(43);
+                buf.extend_from_slice(&leaf_index.to_le_bytes());
+                buf.extend_from_slice(&previous_allocation.to_le_bytes());
+                buf.extend_from_slice(&allocation_amount.to_le_bytes());
+                buf.extend_from_slice(&root);
+            }
+            Self::VerifyCompressedMembership {leaf_index, allocation, root} => {
+                buf.push(44);
+                buf.extend_from_slice(&leaf_index.to_le_bytes());
+                buf.extend_from_slice(&allocation.to_le_bytes());
+                buf.extend_from_slice(&root);
+            }
+            Self::GetWhitelistStats {} => {
+                buf.push(45);
+            }
+            Self::AddToWhitelistWithAttestation {allocation_amount, attestation} => {
+                buf.push(46);
+                buf.extend_from_slice(&allocation_amount.to_le_bytes());
+                buf.extend_from_slice(&attestation);
+            }
+            Self::SetAttestation {attestation} => {
+                buf.push(47);
+                buf.extend_from_slice(&attestation);
+            }
+            Self::SetAttestationRequired {require_attestation} => {
+                buf.push(48);
+                buf.push(require_attestation as u8);
+            }
+            Self::ListAttestedEntries {offset, limit} => {
+                buf.push(49);
+                buf.extend_from_slice(&offset.to_le_bytes());
+                buf.push(limit);
+            }
+            Self::RotateWhitelistedKey {} => {
+                buf.push(50);
+            }
+            Self::AssertNotInOther {} => {
+                buf.push(51);
+            }
+            Self::SetOwnerEntryAllowed {allow_owner_entry} => {
+                buf.push(52);
+                buf.push(allow_owner_entry as u8);
+            }
+            Self::GetRequiredRent {max_whitelist_size} => {
+                buf.push(53);
+                buf.extend_from_slice(&max_whitelist_size.to_le_bytes());
+            }
+            Self::SetEntryNoteCommitment {commitment} => {
+                buf.push(54);
+                buf.extend_from_slice(&commitment);
+            }
+            Self::RevealEntryNote {ref note, salt} => {
+                buf.push(55);
+                buf.push(note.len() as u8);
+                buf.extend_from_slice(note);
+                buf.extend_from_slice(&salt);
+            }
+            Self::SetRegistrationRateLimit {max_registrations_per_window, window_slots} => {
+                buf.push(56);
+                buf.extend_from_slice(&max_registrations_per_window.to_le_bytes());
+                buf.extend_from_slice(&window_slots.to_le_bytes());
+            }
+            Self::SetEnumerationRestricted {restrict_enumeration} => {
+                buf.push(57);
+                buf.push(restrict_enumeration as u8);
+            }
+        };
+        buf
+    }
+
+    /// A campaign is 8 trailing bytes, adds attributed to no campaign end before it
+    fn unpack_campaign(input: &[u8]) -> Result<[u8; 8], ProgramError> {
+        match input {
+            [] => Ok(NO_CAMPAIGN),
+            input => input.try_into().map_err(|_| InvalidInstruction.into()),
+        }
+    }
+
+    fn pack_campaign(campaign_id: [u8; 8], buf: &mut Vec<u8>) {
+        if campaign_id != NO_CAMPAIGN {
+            buf.extend_from_slice(&campaign_id);
+        }
+    }
+
+    /// An expected nonce follows the campaign, packed even when NO_CAMPAIGN so the two stay apart
+    fn unpack_campaign_and_nonce(input: &[u8]) -> Result<([u8; 8], u64), ProgramError> {
+        if input.len() <= 8 {
+            return Ok((Self::unpack_campaign(input)?, 0));
+        }
+        let (campaign_id, rest) = input.split_at(8);
+        Ok((Self::unpack_campaign(campaign_id)?, Self::unpack_nonce(rest)?))
+    }
+
+    fn pack_campaign_and_nonce(campaign_id: [u8; 8], expected_nonce: u64, buf: &mut Vec<u8>) {
+        if expected_nonce == 0 {
+            return Self::pack_campaign(campaign_id, buf);
+        }
+        buf.extend_from_slice(&campaign_id);
+        buf.extend_from_slice(&expected_nonce.to_le_bytes());
+    }
+
+    /// An expected nonce follows the memo, after a NO_MEMO length byte when there is no memo
+    fn unpack_memo_and_nonce(input: &[u8]) -> Result<(Option<String>, u64), ProgramError> {
+        if let Some((&NO_MEMO, rest)) = input.split_first() {
+            return Ok((None, Self::unpack_nonce(rest)?));
+        }
+        let memo = Self::unpack_memo(input)?;
+        let rest = &input[memo.as_ref().map_or(0, |memo| 1 + memo.len())..];
+        match rest {
+            [] => Ok((memo, 0)),
+            rest => Ok((memo, Self::unpack_nonce(rest)?)),
+        }
+    }
+
+    fn unpack_nonce(input: &[u8]) -> Result<u64, ProgramError> {
+        input.try_into().ok().map(u64::from_le_bytes).ok_or_else(|| InvalidInstruction.into())
+    }
+
+    /// A memo is a length byte followed by UTF-8, legacy instructions end before it
+    fn unpack_memo(input: &[u8]) -> Result<Option<String>, ProgramError> {
+        let (&len, rest) = match input.split_first() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+        if len as usize > MAX_MEMO_LEN {
+            return Err(InvalidMemo.into());
+        }
+        let memo = rest.get(..len as usize).ok_or(InvalidInstruction)?;
+        let memo = std::str::from_utf8(memo).map_err(|_| InvalidMemo)?;
+        Ok(Some(memo.to_string()))
+    }
+
+    /// A proof is a count byte followed by that many 32 byte nodes, returned with the bytes
+    /// after it
+    #[cfg(feature = "merkle")]
+    fn unpack_proof(input: &[u8]) -> Result<(Vec<[u8; 32]>, &[u8]), ProgramError> {
+        let (&count, rest) = input.split_first().ok_or(InvalidInstruction)?;
+        let len = count as usize * 32;
+        if rest.len() < len {
+            return Err(InvalidInstruction.into());
+        }
+        let (nodes, rest) = rest.split_at(len);
+        let proof = nodes
+            .chunks_exact(32)
+            .map(|node| node.try_into().unwrap())
+            .collect();
+        Ok((proof, rest))
+    }
+
+    fn pack_proof(proof: &[[u8; 32]], buf: &mut Vec<u8>) {
+        buf.push(proof.len() as u8);
+        for node in proof {
+            buf.extend_from_slice(node);
+        }
+    }
+
+    fn pack_memo(memo: &Option<String>, buf: &mut Vec<u8>) {
+        if let Some(memo) = memo {
+            buf.push(memo.len() as u8);
+            buf.extend_from_slice(memo.as_bytes());
+        }
+    }
+
+    // fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+    //     if input.len() >= 32 {
+    //         let (key, rest) = input.split_at(32);
+    //         let pk = Pubkey::new(key);
+    //         Ok((pk, rest))
+    //     } else {
+    //         Err(InvalidInstruction.into())
+    //     }
+    // }
+}
+
+/// Creates an `AddToWhitelist` instruction, which also updates the allocation of an existing entry
+pub fn add_to_whitelist(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_add: &Pubkey,
+    allocation_amount: u64,
+) -> Instruction {
+    add_to_whitelist_for_campaign(program_id, authority, token_whitelist, account_to_add, allocation_amount, NO_CAMPAIGN)
+}
+
+/// Creates an `AddToWhitelist` instruction attributing the entry to `campaign_id`
+pub fn add_to_whitelist_for_campaign(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_add: &Pubkey,
+    allocation_amount: u64,
+    campaign_id: [u8; 8],
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id, expected_nonce: 0}.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account_to_add, false),
+        ],
+    )
+}
+
+/// Creates an `AddToWhitelist` instruction failing with StaleWhitelistState unless the mutation
+/// nonce of the whitelist is still `expected_nonce`
+pub fn add_to_whitelist_expecting(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_add: &Pubkey,
+    allocation_amount: u64,
+    expected_nonce: u64,
+) -> Instruction {
+    let instruction = TokenWhitelistInstruction::AddToWhitelist {
+        allocation_amount,
+        campaign_id: NO_CAMPAIGN,
+        expected_nonce,
+    };
+    Instruction::new_with_bytes(
+        *program_id,
+        &instruction.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account_to_add, false),
+        ],
+    )
+}
+
+/// Creates a `RemoveFromWhitelist` instruction carrying `memo`, which must fit MAX_MEMO_LEN bytes
+pub fn remove_from_whitelist_with_memo(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_remove: &Pubkey,
+    memo: &str,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::RemoveFromWhitelist {memo: Some(memo.to_string()), expected_nonce: 0}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account_to_remove, false),
+        ],
+    )
+}
+
+/// Creates a `RemoveFromWhitelist` instruction
+pub fn remove_from_whitelist(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_remove: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::RemoveFromWhitelist {memo: None, expected_nonce: 0}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account_to_remove, false),
+        ],
+    )
+}
+
+/// Creates a `RemoveFromWhitelist` instruction failing with StaleWhitelistState unless the
+/// mutation nonce of the whitelist is still `expected_nonce`
+pub fn remove_from_whitelist_expecting(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_remove: &Pubkey,
+    expected_nonce: u64,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::RemoveFromWhitelist {memo: None, expected_nonce}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account_to_remove, false),
+        ],
+    )
+}
+
+/// Creates a `ListEntries` instruction, meant to be simulated for its return data
+pub fn list_entries(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    offset: u32,
+    limit: u8,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::ListEntries {offset, limit}.pack(),
+        vec![AccountMeta::new_readonly(*token_whitelist, false)],
+    )
+}
+
+/// Creates a `ListEntriesByCampaign` instruction, meant to be simulated for its return data
+pub fn list_entries_by_campaign(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    campaign_id: [u8; 8],
+    offset: u32,
+    limit: u8,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::ListEntriesByCampaign {campaign_id, offset, limit}.pack(),
+        vec![AccountMeta::new_readonly(*token_whitelist, false)],
+    )
+}
+
+/// Creates a `ListAttestedEntries` instruction, meant to be simulated for its return data
+pub fn list_attested_entries(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    offset: u32,
+    limit: u8,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::ListAttestedEntries {offset, limit}.pack(),
+        vec![AccountMeta::new_readonly(*token_whitelist, false)],
+    )
+}
+
+/// Creates an `AddToWhitelistWithAttestation` instruction recording `attestation` with the entry
+pub fn add_to_whitelist_with_attestation(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    account_to_add: &Pubkey,
+    allocation_amount: u64,
+    attestation: [u8; 32],
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::AddToWhitelistWithAttestation {allocation_amount, attestation}.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account_to_add, false),
+        ],
+    )
+}
+
+/// Creates a `SetAttestation` instruction, NO_ATTESTATION clearing the attestation of `account`
+pub fn set_attestation(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    account: &Pubkey,
+    attestation: [u8; 32],
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::SetAttestation {attestation}.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account, false),
+        ],
+    )
+}
+
+/// Creates a `RotateWhitelistedKey` instruction moving the entry of `current_account` to
+/// `new_account`, both of which sign
+pub fn rotate_whitelisted_key(
+    program_id: &Pubkey,
+    current_account: &Pubkey,
+    token_whitelist: &Pubkey,
+    new_account: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::RotateWhitelistedKey {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*current_account, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*new_account, true),
+        ],
+    )
+}
+
+/// Creates an `AssertNotInOther` instruction linking `siblings`, an empty slice unlinking them
+pub fn assert_not_in_other(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    siblings: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*whitelist_owner, true),
+        AccountMeta::new(*token_whitelist, false),
+    ];
+    accounts.extend(siblings.iter().map(|sibling| AccountMeta::new_readonly(*sibling, false)));
+    Instruction::new_with_bytes(*program_id, &TokenWhitelistInstruction::AssertNotInOther {}.pack(), accounts)
+}
+
+/// Creates an `InitTokenWhitelistWithUnits` instruction, copying the decimals of the allocation
+/// mint if given. Sizes `TokenWhitelist::check_max_whitelist_size` refuses are refused, as the
+/// program would.
+pub fn init_token_whitelist_with_units(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    init: &InitWithUnits,
+) -> Result<Instruction, TokenWhitelistError> {
+    TokenWhitelist::check_max_whitelist_size(init.max_whitelist_size)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*whitelist_owner, true),
+        AccountMeta::new(*token_whitelist, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    if let Some(allocation_mint) = init.allocation_mint {
+        accounts.push(AccountMeta::new_readonly(allocation_mint, false));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::InitTokenWhitelistWithUnits {
+            max_whitelist_size: init.max_whitelist_size,
+            allocation_decimals: init.allocation_decimals,
+            only_wallets: init.only_wallets,
+            self_zero_allowed: init.self_zero_allowed,
+        }
+        .pack(),
+        accounts,
+    ))
+}
+
+/// Creates a `GetAllocation` instruction, meant to be simulated for its return data
+pub fn get_allocation(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    account: &Pubkey,
+    round: u8,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::GetAllocation {round}.pack(),
+        vec![
+            AccountMeta::new_readonly(*token_whitelist, false),
+            AccountMeta::new_readonly(*account, false),
+        ],
+    )
+}
+
+/// Creates an `EffectiveAllocation` instruction, meant to be simulated for its return data
+pub fn effective_allocation(program_id: &Pubkey, token_whitelist: &Pubkey, account: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::EffectiveAllocation {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*token_whitelist, false),
+            AccountMeta::new_readonly(*account, false),
+        ],
+    )
+}
+
+/// Creates a `GetWhitelistStats` instruction, meant to be simulated for its return data
+pub fn get_whitelist_stats(program_id: &Pubkey, token_whitelist: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::GetWhitelistStats {}.pack(),
+        vec![AccountMeta::new_readonly(*token_whitelist, false)],
+    )
+}
+
+/// Creates an `AssertMembershipAcross` instruction, failing unless `account` meets the flagged
+/// requirements on both whitelists
+pub fn assert_membership_across(
+    program_id: &Pubkey,
+    first_whitelist: &Pubkey,
+    second_whitelist: &Pubkey,
+    account: &Pubkey,
+    require_in_first: bool,
+    require_absent_in_second: bool,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::AssertMembershipAcross {require_in_first, require_absent_in_second}.pack(),
+        vec![
+            AccountMeta::new_readonly(*first_whitelist, false),
+            AccountMeta::new_readonly(*second_whitelist, false),
+            AccountMeta::new_readonly(*account, false),
+        ],
+    )
+}
+
+/// Creates an `AssertOwner` instruction, failing unless `expected_owner` owns the whitelist
+pub fn assert_owner(program_id: &Pubkey, token_whitelist: &Pubkey, expected_owner: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::AssertOwner {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*token_whitelist, false),
+            AccountMeta::new_readonly(*expected_owner, false),
+        ],
+    )
+}
+
+/// Creates an `InspectState` instruction logging the whitelist and up to `max_entries_to_log`
+/// of its entries
+pub fn inspect_state(program_id: &Pubkey, token_whitelist: &Pubkey, max_entries_to_log: u8) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::InspectState {max_entries_to_log}.pack(),
+        vec![AccountMeta::new_readonly(*token_whitelist, false)],
+    )
+}
+
+/// Creates a `TopUpRent` instruction, `funder` paying whatever keeps the whitelist rent exempt
+pub fn top_up_rent(program_id: &Pubkey, funder: &Pubkey, token_whitelist: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::TopUpRent {}.pack(),
+        vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `RepairState` instruction rebuilding the map of a whitelist that does not decode
+pub fn repair_state(program_id: &Pubkey, whitelist_owner: &Pubkey, token_whitelist: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::RepairState {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+        ],
+    )
+}
+
+/// Creates a `MigrateStateLayout` instruction, passing the ProgramData account of `program_id`
+pub fn migrate_state_layout(program_id: &Pubkey, upgrade_authority: &Pubkey, token_whitelist: &Pubkey) -> Instruction {
+    let (program_data, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::MigrateStateLayout {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*upgrade_authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(program_data, false),
+        ],
+    )
+}
+
+/// Creates an `InitConfig` instruction for the config account at its program address
+pub fn init_config(program_id: &Pubkey, owner: &Pubkey, token_whitelist: &Pubkey) -> Instruction {
+    let (config, _) = WhitelistConfig::find_address(program_id, token_whitelist);
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::InitConfig {}.pack(),
+        vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new(config, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Appends the config account of a whitelist that has one, writable for the instructions
+/// changing policy
+pub fn with_config(mut instruction: Instruction, config: &Pubkey) -> Instruction {
+    let sets_policy = matches!(
+        TokenWhitelistInstruction::unpack(&instruction.data),
+        Ok(TokenWhitelistInstruction::SetConsumeLimits {..}
+            | TokenWhitelistInstruction::SetAllocationBounds {..}
+            | TokenWhitelistInstruction::SetActiveRound {..}
+            | TokenWhitelistInstruction::SetStrictTokenChecks {..}
+            | TokenWhitelistInstruction::SetOnlyWallets {..}
+            | TokenWhitelistInstruction::SetSelfZeroAllowed {..}
+            | TokenWhitelistInstruction::SetZeroAllocationAllowed {..}
+            | TokenWhitelistInstruction::SetTokenGate {..}
+            | TokenWhitelistInstruction::SetOwnerEntryAllowed {..})
+    );
+    instruction.accounts.push(if sets_policy {
+        AccountMeta::new(*config, false)
+    } else {
+        AccountMeta::new_readonly(*config, false)
+    });
+    instruction
+}
+
+/// Appends the owner or the delegate signing for an instruction enumerating entries of a
+/// whitelist restricting enumeration, see SetEnumerationRestricted
+pub fn with_reader(mut instruction: Instruction, reader: &Pubkey) -> Instruction {
+    instruction.accounts.push(AccountMeta::new_readonly(*reader, true));
+    instruction
+}
+
+/// Appends the sibling whitelists linked by AssertNotInOther to an add, after any optional
+/// account it already takes
+pub fn with_sibling_whitelists(mut instruction: Instruction, siblings: &[Pubkey]) -> Instruction {
+    instruction.accounts.extend(siblings.iter().map(|sibling| AccountMeta::new_readonly(*sibling, false)));
+    instruction
+}
+
+/// Creates an `AddToWhitelist` instruction for a token account, passing its mint along for the
+/// strict token checks of Token-2022 accounts
+pub fn add_token_account_to_whitelist(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    token_account: &Pubkey,
+    mint: &Pubkey,
+    allocation_amount: u64,
+) -> Instruction {
+    let mut instruction = add_to_whitelist(program_id, authority, token_whitelist, token_account, allocation_amount);
+    instruction.accounts.push(AccountMeta::new_readonly(*mint, false));
+    instruction
+}
+
+/// Creates an `ArchiveWhitelist` instruction, leaving only the archive record in the whitelist
+pub fn archive_whitelist(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    dest_account: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::ArchiveWhitelist {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new(*dest_account, false),
+        ],
+    )
+}
+
+/// Creates a `GetVersion` instruction, meant to be simulated for its return data
+pub fn get_version(program_id: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::GetVersion {}.pack(),
+        vec![],
+    )
+}
+
+/// Creates a `GetRequiredRent` instruction, meant to be simulated for its return data before
+/// creating the account of a whitelist of up to `max_whitelist_size` entries
+pub fn get_required_rent(program_id: &Pubkey, max_whitelist_size: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::GetRequiredRent {max_whitelist_size}.pack(),
+        vec![],
+    )
+}
+
+/// Creates a `SetEntryNoteCommitment` instruction, see state::note_commitment for computing
+/// `commitment` and NO_NOTE_COMMITMENT for clearing it
+pub fn set_entry_note_commitment(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    account: &Pubkey,
+    commitment: [u8; 32],
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::SetEntryNoteCommitment {commitment}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new_readonly(*account, false),
+        ],
+    )
+}
+
+/// Creates a `RevealEntryNote` instruction, refusing a note longer than MAX_NOTE_LEN bytes
+pub fn reveal_entry_note(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    account: &Pubkey,
+    note: &[u8],
+    salt: [u8; 16],
+) -> Result<Instruction, TokenWhitelistError> {
+    if note.len() > MAX_NOTE_LEN {
+        return Err(NoteTooLong);
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::RevealEntryNote {note: note.to_vec(), salt}.pack(),
+        vec![
+            AccountMeta::new_readonly(*token_whitelist, false),
+            AccountMeta::new_readonly(*account, false),
+        ],
+    ))
+}
+
+/// Creates a `SetRegistrationRateLimit` instruction
+pub fn set_registration_rate_limit(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    max_registrations_per_window: u16,
+    window_slots: u32,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::SetRegistrationRateLimit {max_registrations_per_window, window_slots}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+        ],
+    )
+}
+
+/// Creates a `SetEnumerationRestricted` instruction
+pub fn set_enumeration_restricted(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    restrict_enumeration: bool,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::SetEnumerationRestricted {restrict_enumeration}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+        ],
+    )
+}
+
+/// Creates a `SetMerkleRoot` instruction
+pub fn set_merkle_root(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    root: [u8; 32],
+    hash_kind: u8,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::SetMerkleRoot {root, hash_kind}.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+        ],
+    )
+}
+
+/// Creates a `VerifyMerkleMembership` instruction proving `wallet` holds `allocation` at
+/// `leaf_index`, a proof holds at most 255 nodes
+pub fn verify_merkle_membership(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    wallet: &Pubkey,
+    leaf_index: u32,
+    allocation: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::VerifyMerkleMembership {leaf_index, allocation, proof}.pack(),
+        vec![
+            AccountMeta::new_readonly(*token_whitelist, false),
+            AccountMeta::new_readonly(*wallet, false),
+        ],
+    )
+}
+
+/// Creates a `VerifyMerkleMembershipBatch` instruction, each wallet of `items` has to sign the
+/// transaction. Refuses batches whose data exceeds MAX_MERKLE_BATCH_DATA_LEN.
+pub fn verify_merkle_membership_batch(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    claim_bitmap: &Pubkey,
+    items: Vec<MerkleClaim>,
+) -> Result<Instruction, TokenWhitelistError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_whitelist, false),
+        AccountMeta::new(*claim_bitmap, false),
+    ];
+    for item in &items {
+        if item.proof.len() > u8::MAX as usize {
+            return Err(InvalidInstruction);
+        }
+        if !accounts.iter().any(|meta| meta.pubkey == item.wallet) {
+            accounts.push(AccountMeta::new_readonly(item.wallet, true));
+        }
+    }
+    if items.len() > u8::MAX as usize {
+        return Err(InvalidInstruction);
+    }
+    let data = TokenWhitelistInstruction::VerifyMerkleMembershipBatch {items}.pack();
+    if data.len() > MAX_MERKLE_BATCH_DATA_LEN {
+        return Err(InvalidInstruction);
+    }
+    Ok(Instruction::new_with_bytes(*program_id, &data, accounts))
+}
+
+/// Creates an `InitClaimBitmap` instruction for a tree of `leaf_count` leaves, the bitmap
+/// account having been created with claim_bitmap_len(leaf_count) bytes
+pub fn init_claim_bitmap(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    token_whitelist: &Pubkey,
+    claim_bitmap: &Pubkey,
+    leaf_count: u32,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::InitClaimBitmap {leaf_count}.pack(),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new(*claim_bitmap, false),
+        ],
+    )
+}
+
+/// Creates an `InitCompressedWhitelist` instruction for `tree`, an account of
+/// compression::tree_space() bytes the caller created and assigned to the compression program
+#[cfg(feature = "compression")]
+pub fn init_compressed_whitelist(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    tree: &Pubkey,
+) -> Instruction {
+    let (tree_authority, _) = compression::find_tree_authority(program_id, token_whitelist);
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::InitCompressedWhitelist {}.pack(),
+        vec![
+            AccountMeta::new_readonly(*whitelist_owner, true),
+            AccountMeta::new(*token_whitelist, false),
+            AccountMeta::new(*tree, false),
+            AccountMeta::new_readonly(tree_authority, false),
+            AccountMeta::new_readonly(compression::COMPRESSION_PROGRAM_ID, false),
+            AccountMeta::new_readonly(compression::NOOP_PROGRAM_ID, false),
+        ],
+    )
+}
+
+#[cfg(feature = "compression")]
+fn compressed_write_accounts(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    tree: &Pubkey,
+    account: &Pubkey,
+) -> Vec<AccountMeta> {
+    let (tree_authority, _) = compression::find_tree_authority(program_id, token_whitelist);
+    vec![
+        AccountMeta::new_readonly(*whitelist_owner, true),
+        AccountMeta::new(*token_whitelist, false),
+        AccountMeta::new_readonly(*account, false),
+        AccountMeta::new(*tree, false),
+        AccountMeta::new_readonly(tree_authority, false),
+        AccountMeta::new_readonly(compression::COMPRESSION_PROGRAM_ID, false),
+        AccountMeta::new_readonly(compression::NOOP_PROGRAM_ID, false),
+    ]
+}
+
+/// Creates an `AppendCompressedEntry` instruction adding the leaf of `account_to_add` to the
+/// compressed tree of the whitelist
+#[cfg(feature = "compression")]
+pub fn append_compressed_entry(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    tree: &Pubkey,
+    account_to_add: &Pubkey,
+    allocation_amount: u64,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::AppendCompressedEntry {allocation_amount}.pack(),
+        compressed_write_accounts(program_id, whitelist_owner, token_whitelist, tree, account_to_add),
+    )
+}
+
+/// Creates a `ReplaceCompressedEntry` instruction giving the leaf of `account` described by
+/// `leaf` the allocation `allocation_amount`
+#[cfg(feature = "compression")]
+pub fn replace_compressed_entry(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    tree: &Pubkey,
+    account: &Pubkey,
+    leaf: &CompressedLeaf,
+    allocation_amount: u64,
+) -> Instruction {
+    let mut accounts = compressed_write_accounts(program_id, whitelist_owner, token_whitelist, tree, account);
+    accounts.extend(compression::proof_accounts(&leaf.proof));
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::ReplaceCompressedEntry {
+            leaf_index: leaf.leaf_index,
+            previous_allocation: leaf.allocation,
+            allocation_amount,
+            root: leaf.root,
+        }
+        .pack(),
+        accounts,
+    )
+}
+
+/// Creates a `VerifyCompressedMembership` instruction proving `wallet` holds `leaf`
+#[cfg(feature = "compression")]
+pub fn verify_compressed_membership(
+    program_id: &Pubkey,
+    token_whitelist: &Pubkey,
+    tree: &Pubkey,
+    wallet: &Pubkey,
+    leaf: &CompressedLeaf,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_whitelist, false),
+        AccountMeta::new_readonly(*wallet, false),
+        AccountMeta::new_readonly(*tree, false),
+        AccountMeta::new_readonly(compression::COMPRESSION_PROGRAM_ID, false),
+    ];
+    accounts.extend(compression::proof_accounts(&leaf.proof));
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::VerifyCompressedMembership {
+            leaf_index: leaf.leaf_index,
+            allocation: leaf.allocation,
+            root: leaf.root,
+        }
+        .pack(),
+        accounts,
+    )
+}
+
+/// Creates a `ComputeEntriesHash` instruction, meant to be simulated for its return data
+pub fn compute_entries_hash(program_id: &Pubkey, token_whitelist: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::ComputeEntriesHash {}.pack(),
+        vec![AccountMeta::new_readonly(*token_whitelist, false)],
+    )
+}
+
+/// Creates a `SetTokenGate` instruction, a zero `gating_minimum` clearing the gate
+pub fn set_token_gate(
+    program_id: &Pubkey,
+    whitelist_owner: &Pubkey,
+    token_whitelist: &Pubkey,
+    gating_mint: &Pubkey,
+    gating_minimum: u64,
+    gate_owner_adds: bool,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*whitelist_owner, true),
+        AccountMeta::new(*token_whitelist, false),
+    ];
+    if gating_minimum > 0 {
+        accounts.push(AccountMeta::new_readonly(*gating_mint, false));
+    }
+    Instruction::new_with_bytes(
+        *program_id,
+        &TokenWhitelistInstruction::SetTokenGate {gating_mint: *gating_mint, gating_minimum, gate_owner_adds}.pack(),
+        accounts,
+    )
+}
+
+/// The builders above for the official deployment at crate::id(). Forks and local deployments
+/// pass their own program id to the builders of the parent module instead.
+pub mod official {
+    use super::*;
+
+    /// Defines each builder as the one of the same name in the parent module, called with
+    /// crate::id() as the program id
+    macro_rules! official_builders {
+        ($($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+            $(
+                pub fn $name($($arg: $ty),*) -> $ret {
+                    super::$name(&crate::id(), $($arg),*)
+                }
+            )*
+        };
+    }
+
+    official_builders! {
+        add_to_whitelist(authority: &Pubkey, token_whitelist: &Pubkey, account_to_add: &Pubkey, allocation_amount: u64) -> Instruction;
+        add_to_whitelist_for_campaign(
+            authority: &Pubkey,
+            token_whitelist: &Pubkey,
+            account_to_add: &Pubkey,
+            allocation_amount: u64,
+            campaign_id: [u8; 8]
+        ) -> Instruction;
+        remove_from_whitelist_with_memo(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            account_to_remove: &Pubkey,
+            memo: &str
+        ) -> Instruction;
+        remove_from_whitelist(whitelist_owner: &Pubkey, token_whitelist: &Pubkey, account_to_remove: &Pubkey) -> Instruction;
+        add_to_whitelist_expecting(
+            authority: &Pubkey,
+            token_whitelist: &Pubkey,
+            account_to_add: &Pubkey,
+            allocation_amount: u64,
+            expected_nonce: u64
+        ) -> Instruction;
+        remove_from_whitelist_expecting(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            account_to_remove: &Pubkey,
+            expected_nonce: u64
+        ) -> Instruction;
+        list_entries(token_whitelist: &Pubkey, offset: u32, limit: u8) -> Instruction;
+        list_entries_by_campaign(token_whitelist: &Pubkey, campaign_id: [u8; 8], offset: u32, limit: u8) -> Instruction;
+        list_attested_entries(token_whitelist: &Pubkey, offset: u32, limit: u8) -> Instruction;
+        add_to_whitelist_with_attestation(
+            authority: &Pubkey,
+            token_whitelist: &Pubkey,
+            account_to_add: &Pubkey,
+            allocation_amount: u64,
+            attestation: [u8; 32]
+        ) -> Instruction;
+        set_attestation(authority: &Pubkey, token_whitelist: &Pubkey, account: &Pubkey, attestation: [u8; 32]) -> Instruction;
+        rotate_whitelisted_key(current_account: &Pubkey, token_whitelist: &Pubkey, new_account: &Pubkey) -> Instruction;
+        assert_not_in_other(whitelist_owner: &Pubkey, token_whitelist: &Pubkey, siblings: &[Pubkey]) -> Instruction;
+        init_token_whitelist_with_units(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            init: &InitWithUnits
+        ) -> Result<Instruction, TokenWhitelistError>;
+        get_allocation(token_whitelist: &Pubkey, account: &Pubkey, round: u8) -> Instruction;
+        effective_allocation(token_whitelist: &Pubkey, account: &Pubkey) -> Instruction;
+        get_whitelist_stats(token_whitelist: &Pubkey) -> Instruction;
+        assert_membership_across(
+            first_whitelist: &Pubkey,
+            second_whitelist: &Pubkey,
+            account: &Pubkey,
+            require_in_first: bool,
+            require_absent_in_second: bool
+        ) -> Instruction;
+        assert_owner(token_whitelist: &Pubkey, expected_owner: &Pubkey) -> Instruction;
+        inspect_state(token_whitelist: &Pubkey, max_entries_to_log: u8) -> Instruction;
+        top_up_rent(funder: &Pubkey, token_whitelist: &Pubkey) -> Instruction;
+        repair_state(whitelist_owner: &Pubkey, token_whitelist: &Pubkey) -> Instruction;
+        migrate_state_layout(upgrade_authority: &Pubkey, token_whitelist: &Pubkey) -> Instruction;
+        init_config(owner: &Pubkey, token_whitelist: &Pubkey) -> Instruction;
+        add_token_account_to_whitelist(
+            authority: &Pubkey,
+            token_whitelist: &Pubkey,
+            token_account: &Pubkey,
+            mint: &Pubkey,
+            allocation_amount: u64
+        ) -> Instruction;
+        archive_whitelist(authority: &Pubkey, token_whitelist: &Pubkey, dest_account: &Pubkey) -> Instruction;
+        get_version() -> Instruction;
+        get_required_rent(max_whitelist_size: u64) -> Instruction;
+        set_entry_note_commitment(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            account: &Pubkey,
+            commitment: [u8; 32]
+        ) -> Instruction;
+        reveal_entry_note(
+            token_whitelist: &Pubkey,
+            account: &Pubkey,
+            note: &[u8],
+            salt: [u8; 16]
+        ) -> Result<Instruction, TokenWhitelistError>;
+        set_registration_rate_limit(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            max_registrations_per_window: u16,
+            window_slots: u32
+        ) -> Instruction;
+        set_enumeration_restricted(whitelist_owner: &Pubkey, token_whitelist: &Pubkey, restrict_enumeration: bool) -> Instruction;
+        set_merkle_root(authority: &Pubkey, token_whitelist: &Pubkey, root: [u8; 32], hash_kind: u8) -> Instruction;
+        verify_merkle_membership(
+            token_whitelist: &Pubkey,
+            wallet: &Pubkey,
+            leaf_index: u32,
+            allocation: u64,
+            proof: Vec<[u8; 32]>
+        ) -> Instruction;
+        verify_merkle_membership_batch(
+            token_whitelist: &Pubkey,
+            claim_bitmap: &Pubkey,
+            items: Vec<MerkleClaim>
+        ) -> Result<Instruction, TokenWhitelistError>;
+        init_claim_bitmap(authority: &Pubkey, token_whitelist: &Pubkey, claim_bitmap: &Pubkey, leaf_count: u32) -> Instruction;
+        compute_entries_hash(token_whitelist: &Pubkey) -> Instruction;
+        set_token_gate(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            gating_mint: &Pubkey,
+            gating_minimum: u64,
+            gate_owner_adds: bool
+        ) -> Instruction;
+    }
+
+    #[cfg(feature = "compression")]
+    official_builders! {
+        init_compressed_whitelist(whitelist_owner: &Pubkey, token_whitelist: &Pubkey, tree: &Pubkey) -> Instruction;
+        append_compressed_entry(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            tree: &Pubkey,
+            account_to_add: &Pubkey,
+            allocation_amount: u64
+        ) -> Instruction;
+        replace_compressed_entry(
+            whitelist_owner: &Pubkey,
+            token_whitelist: &Pubkey,
+            tree: &Pubkey,
+            account: &Pubkey,
+            leaf: &CompressedLeaf,
+            allocation_amount: u64
+        ) -> Instruction;
+        verify_compressed_membership(
+            token_whitelist: &Pubkey,
+            tree: &Pubkey,
+            wallet: &Pubkey,
+            leaf: &CompressedLeaf
+        ) -> Instruction;
+    }
+
+    /// Address and bump of the config account of `whitelist`
+    pub fn config_address(whitelist: &Pubkey) -> (Pubkey, u8) {
+        WhitelistConfig::find_address(&crate::id(), whitelist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_init_whitelist() {
+        let whitelist_size: u64 = 50;
+        let check = TokenWhitelistInstruction::InitTokenWhitelist {
+            max_whitelist_size: whitelist_size,
+        };
+        let packed = check.pack();
+        let mut expect = vec![0];
+        expect.extend_from_slice(&whitelist_size.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_official_builders() {
+        let (owner, whitelist, wallet) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(
+            official::add_to_whitelist(&owner, &whitelist, &wallet, 100),
+            add_to_whitelist(&crate::id(), &owner, &whitelist, &wallet, 100)
+        );
+        assert_eq!(official::get_version().program_id, crate::id());
+        assert_eq!(official::config_address(&whitelist), WhitelistConfig::find_address(&crate::id(), &whitelist));
+        assert_eq!(official::init_config(&owner, &whitelist).accounts[2].pubkey, official::config_address(&whitelist).0);
+    }
+
+    #[test]
+    fn test_init_builder_refuses_size_above_ceiling() {
+        let (program_id, owner, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let build = |max_whitelist_size| {
+            init_token_whitelist_with_units(&program_id, &owner, &whitelist, &InitWithUnits::new(max_whitelist_size))
+        };
+        let max_size = TokenWhitelist::MAX_ENTRIES;
+        assert!(build(max_size).is_ok());
+        assert_eq!(build(max_size + 1), Err(TokenWhitelistError::TokenWhitelistSizeExceeds));
+        assert_eq!(build(0), Err(TokenWhitelistError::ZeroWhitelistSize));
+        assert!(build(crate::state::UNLIMITED_WHITELIST_SIZE).is_ok());
+    }
+
+    #[test]
+    fn test_pack_add_to_whitelist() {
+        let allocation: u64 = 250;
+        let check = TokenWhitelistInstruction::AddToWhitelist{
+            allocation_amount: allocation,
+            campaign_id: NO_CAMPAIGN,
+            expected_nonce: 0,
+        };
+        let packed = check.pack();
+        let mut expect = vec![1];
+        expect.extend_from_slice(&allocation.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::AddToWhitelist{
+            allocation_amount: allocation,
+            campaign_id: *b"partner1",
+            expected_nonce: 0,
+        };
+        expect.extend_from_slice(b"partner1");
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        expect.pop();
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn test_pack_add_to_whitelist_expecting_nonce() {
+        let allocation: u64 = 250;
+        let check = TokenWhitelistInstruction::AddToWhitelist{
+            allocation_amount: allocation,
+            campaign_id: NO_CAMPAIGN,
+            expected_nonce: 7,
+        };
+        // the campaign is packed even when NO_CAMPAIGN
+        let mut expect = vec![1];
+        expect.extend_from_slice(&allocation.to_le_bytes());
+        expect.extend_from_slice(&NO_CAMPAIGN);
+        expect.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        let check = TokenWhitelistInstruction::AddToWhitelistForRound{
+            allocation_amount: allocation,
+            round: 1,
+            campaign_id: *b"partner1",
+            expected_nonce: 7,
+        };
+        let mut expect = vec![17];
+        expect.extend_from_slice(&allocation.to_le_bytes());
+        expect.push(1);
+        expect.extend_from_slice(b"partner1");
+        expect.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        expect.pop();
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn test_pack_remove_from_whitelist() {
+        let check = TokenWhitelistInstruction::RemoveFromWhitelist{memo: None, expected_nonce: 0};
+        let packed = check.pack();
+        let expect = vec![2];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::RemoveFromWhitelist{
+            memo: Some("kyc expired".to_string()),
+            expected_nonce: 0,
+        };
+        let packed = check.pack();
+        let mut expect = vec![2, 11];
+        expect.extend_from_slice(b"kyc expired");
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::RemoveFromWhitelist{
+            memo: Some("kyc expired".to_string()),
+            expected_nonce: 7,
+        };
+        expect.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        // without a memo the nonce follows a NO_MEMO length byte
+        let check = TokenWhitelistInstruction::RemoveFromWhitelist{memo: None, expected_nonce: 7};
+        let mut expect = vec![2, NO_MEMO];
+        expect.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        expect.pop();
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn test_pack_set_allocation_zero() {
+        let check = TokenWhitelistInstruction::SetAllocationToZero{memo: None};
+        let packed = check.pack();
+        let expect = vec![3];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::SetAllocationToZero{memo: Some(String::new())};
+        let packed = check.pack();
+        let expect = vec![3, 0];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_unpack_memo_limits() {
+        let memo = "m".repeat(MAX_MEMO_LEN);
+        let mut data = vec![2, MAX_MEMO_LEN as u8];
+        data.extend_from_slice(memo.as_bytes());
+        assert_eq!(
+            TokenWhitelistInstruction::unpack(&data).unwrap(),
+            TokenWhitelistInstruction::RemoveFromWhitelist{memo: Some(memo.clone()), expected_nonce: 0}
+        );
+
+        let mut data = vec![2, MAX_MEMO_LEN as u8 + 1];
+        data.extend_from_slice(memo.as_bytes());
+        data.push(b'm');
+        assert_eq!(TokenWhitelistInstruction::unpack(&data), Err(InvalidMemo.into()));
+
+        assert_eq!(TokenWhitelistInstruction::unpack(&[3, 2, 0xc3, 0x28]), Err(InvalidMemo.into()));
+        assert_eq!(TokenWhitelistInstruction::unpack(&[3, 4, b'a']), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn test_pack_close_whitelist_account() {
+        let check = TokenWhitelistInstruction::CloseWhitelistAccount{};
+        let packed = check.pack();
+        let expect = vec![4];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_approve_delegate() {
+        let max_adds: u32 = 500;
+        let max_allocation_per_add: u64 = 1000;
+        let check = TokenWhitelistInstruction::ApproveDelegate{
+            max_adds,
+            max_allocation_per_add,
+        };
+        let packed = check.pack();
+        let mut expect = vec![5];
+        expect.extend_from_slice(&max_adds.to_le_bytes());
+        expect.extend_from_slice(&max_allocation_per_add.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_revoke_delegate() {
+        let check = TokenWhitelistInstruction::RevokeDelegate{};
+        let packed = check.pack();
+        let expect = vec![6];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_configure_registration() {
+        let registration_end_slot: u64 = 1000;
+        let registration_allocation: u64 = 250;
+        let registration_deposit_lamports: u64 = 10000000;
+        let check = TokenWhitelistInstruction::ConfigureRegistration{
+            registration_end_slot,
+            registration_allocation,
+            registration_deposit_lamports,
+        };
+        let packed = check.pack();
+        let mut expect = vec![7];
+        expect.extend_from_slice(&registration_end_slot.to_le_bytes());
+        expect.extend_from_slice(&registration_allocation.to_le_bytes());
+        expect.extend_from_slice(&registration_deposit_lamports.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_register_self() {
+        let check = TokenWhitelistInstruction::RegisterSelf{};
+        let packed = check.pack();
+        let expect = vec![8];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_refund_deposit() {
+        let check = TokenWhitelistInstruction::RefundDeposit{};
+        let packed = check.pack();
+        let expect = vec![9];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_run_lottery() {
+        let winners: u64 = 50;
+        let seed = [3u8; 32];
+        let check = TokenWhitelistInstruction::RunLottery{
+            winners,
+            seed,
+        };
+        let packed = check.pack();
+        let mut expect = vec![10];
+        expect.extend_from_slice(&winners.to_le_bytes());
+        expect.extend_from_slice(&seed);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_set_consume_limits() {
+        let max_consume_per_tx: u64 = 100;
+        let min_slots_between_consumes: u64 = 10;
+        let check = TokenWhitelistInstruction::SetConsumeLimits{
+            max_consume_per_tx,
+            min_slots_between_consumes,
+        };
+        let packed = check.pack();
+        let mut expect = vec![11];
+        expect.extend_from_slice(&max_consume_per_tx.to_le_bytes());
+        expect.extend_from_slice(&min_slots_between_consumes.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_consume_allocation() {
+        let amount: u64 = 100;
+        let check = TokenWhitelistInstruction::ConsumeAllocation{
+            amount,
+        };
+        let packed = check.pack();
+        let mut expect = vec![12];
+        expect.extend_from_slice(&amount.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[cfg(feature = "pages")]
+    #[test]
+    fn test_pack_list_entries_by_campaign() {
+        let check = TokenWhitelistInstruction::ListEntriesByCampaign{
+            campaign_id: *b"partner1",
+            offset: 40,
+            limit: 20,
+        };
+        let packed = check.pack();
+        let mut expect = vec![34];
+        expect.extend_from_slice(b"partner1");
+        expect.extend_from_slice(&40u32.to_le_bytes());
+        expect.push(20);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        expect.pop();
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect), Err(InvalidInstruction.into()));
+    }
+
+    #[cfg(feature = "pages")]
+    #[test]
+    fn test_pack_list_entries() {
+        let offset: u32 = 40;
+        let limit: u8 = 20;
+        let check = TokenWhitelistInstruction::ListEntries{
+            offset,
+            limit,
+        };
+        let packed = check.pack();
+        let mut expect = vec![13];
+        expect.extend_from_slice(&offset.to_le_bytes());
+        expect.push(limit);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_compute_entries_hash() {
+        let check = TokenWhitelistInstruction::ComputeEntriesHash{};
+        let packed = check.pack();
+        let expect = vec![14];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_init_whitelist_with_units() {
+        let max_whitelist_size: u64 = 50;
+        let check = TokenWhitelistInstruction::InitTokenWhitelistWithUnits{
+            max_whitelist_size,
+            allocation_decimals: Some(6),
+            only_wallets: false,
+            self_zero_allowed: true,
+        };
+        let packed = check.pack();
+        let mut expect = vec![15];
+        expect.extend_from_slice(&max_whitelist_size.to_le_bytes());
+        expect.extend_from_slice(&[1, 6]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::InitTokenWhitelistWithUnits{
+            max_whitelist_size,
+            allocation_decimals: None,
+            only_wallets: false,
+            self_zero_allowed: true,
+        };
+        let packed = check.pack();
+        let mut expect = vec![15];
+        expect.extend_from_slice(&max_whitelist_size.to_le_bytes());
+        expect.push(0);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::InitTokenWhitelistWithUnits{
+            max_whitelist_size,
+            allocation_decimals: None,
+            only_wallets: true,
+            self_zero_allowed: true,
+        };
+        expect.push(1);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        let check = TokenWhitelistInstruction::InitTokenWhitelistWithUnits{
+            max_whitelist_size,
+            allocation_decimals: None,
+            only_wallets: true,
+            self_zero_allowed: false,
+        };
+        expect.pop();
+        expect.push(3);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        expect.pop();
+        expect.push(6);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn test_pack_set_allocation_bounds() {
+        let min_allocation: u64 = 100;
+        let max_allocation: u64 = 5_000;
+        let check = TokenWhitelistInstruction::SetAllocationBounds{
+            min_allocation,
+            max_allocation,
+        };
+        let packed = check.pack();
+        let mut expect = vec![16];
+        expect.extend_from_slice(&min_allocation.to_le_bytes());
+        expect.extend_from_slice(&max_allocation.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_round_instructions() {
+        let allocation_amount: u64 = 1_000;
+        let round: u8 = 2;
+        let check = TokenWhitelistInstruction::AddToWhitelistForRound{
+            allocation_amount,
+            round,
+            campaign_id: NO_CAMPAIGN,
+            expected_nonce: 0,
         };
-        buf
+        let packed = check.pack();
+        let mut expect = vec![17];
+        expect.extend_from_slice(&allocation_amount.to_le_bytes());
+        expect.push(round);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::AddToWhitelistForRound{
+            allocation_amount,
+            round,
+            campaign_id: *b"partner1",
+            expected_nonce: 0,
+        };
+        expect.extend_from_slice(b"partner1");
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+
+        let amount: u64 = 100;
+        let check = TokenWhitelistInstruction::ConsumeAllocationForRound{
+            amount,
+            round,
+        };
+        let packed = check.pack();
+        let mut expect = vec![18];
+        expect.extend_from_slice(&amount.to_le_bytes());
+        expect.push(round);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::SetActiveRound{round};
+        let packed = check.pack();
+        let expect = vec![19, round];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenWhitelistInstruction::GetAllocation{round};
+        let packed = check.pack();
+        let expect = vec![20, round];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
     }
 
-    // fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
-    //     if input.len() >= 32 {
-    //         let (key, rest) = input.split_at(32);
-    //         let pk = Pubkey::new(key);
-    //         Ok((pk, rest))
-    //     } else {
-    //         Err(InvalidInstruction.into())
-    //     }
-    // }
-}
+    #[test]
+    fn test_pack_assert_membership_across() {
+        let check = TokenWhitelistInstruction::AssertMembershipAcross{
+            require_in_first: true,
+            require_absent_in_second: false,
+        };
+        let packed = check.pack();
+        let expect = vec![21, 1, 0];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert!(TokenWhitelistInstruction::unpack(&[21, 2, 0]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[21, 1]).is_err());
+    }
 
     #[test]
-    fn test_pack_init_whitelist() {
-        let whitelist_size: u64 = 50;
-        let check = TokenWhitelistInstruction::InitTokenWhitelist {
-            max_whitelist_size: whitelist_size,
+    fn test_pack_set_strict_token_checks() {
+        let check = TokenWhitelistInstruction::SetStrictTokenChecks{strict_token_checks: true};
+        let packed = check.pack();
+        let expect = vec![24, 1];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[24, 2]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[24]).is_err());
+    }
+
+    #[cfg(feature = "merkle")]
+    #[test]
+    fn test_pack_set_merkle_root() {
+        let check = TokenWhitelistInstruction::SetMerkleRoot{root: [7; 32], hash_kind: 1};
+        let packed = check.pack();
+        let mut expect = vec![25];
+        expect.extend_from_slice(&[7; 32]);
+        expect.push(1);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&expect[..33]).is_err());
+    }
+
+    #[cfg(feature = "merkle")]
+    #[test]
+    fn test_pack_verify_merkle_membership() {
+        let check = TokenWhitelistInstruction::VerifyMerkleMembership{
+            leaf_index: 9,
+            allocation: 500,
+            proof: vec![[1; 32], [2; 32]],
         };
         let packed = check.pack();
-        let mut expect = vec![0];
-        expect.extend_from_slice(&whitelist_size.to_le_bytes());
+        let mut expect = vec![26];
+        expect.extend_from_slice(&9u32.to_le_bytes());
+        expect.extend_from_slice(&500u64.to_le_bytes());
+        expect.push(2);
+        expect.extend_from_slice(&[1; 32]);
+        expect.extend_from_slice(&[2; 32]);
         assert_eq!(packed, expect);
         let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        // the count has to match the nodes that follow
+        assert!(TokenWhitelistInstruction::unpack(&expect[..expect.len() - 1]).is_err());
+        expect.push(0);
+        assert!(TokenWhitelistInstruction::unpack(&expect).is_err());
+
+        let check = TokenWhitelistInstruction::VerifyMerkleMembership{leaf_index: 0, allocation: 1, proof: vec![]};
+        assert_eq!(TokenWhitelistInstruction::unpack(&check.pack()).unwrap(), check);
     }
 
+    #[cfg(feature = "merkle")]
     #[test]
-    fn test_pack_add_to_whitelist() {
-        let allocation: u64 = 250;
-        let check = TokenWhitelistInstruction::AddToWhitelist{
-            allocation_amount: allocation,
+    fn test_pack_verify_merkle_membership_batch() {
+        let items = vec![
+            MerkleClaim {wallet: Pubkey::new_from_array([5; 32]), leaf_index: 3, allocation: 100, proof: vec![[1; 32]]},
+            MerkleClaim {wallet: Pubkey::new_from_array([6; 32]), leaf_index: 4, allocation: 200, proof: vec![]},
+        ];
+        let check = TokenWhitelistInstruction::VerifyMerkleMembershipBatch{items};
+        let packed = check.pack();
+        let mut expect = vec![27, 2];
+        expect.extend_from_slice(&[5; 32]);
+        expect.extend_from_slice(&3u32.to_le_bytes());
+        expect.extend_from_slice(&100u64.to_le_bytes());
+        expect.push(1);
+        expect.extend_from_slice(&[1; 32]);
+        expect.extend_from_slice(&[6; 32]);
+        expect.extend_from_slice(&4u32.to_le_bytes());
+        expect.extend_from_slice(&200u64.to_le_bytes());
+        expect.push(0);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&expect[..expect.len() - 1]).is_err());
+        expect.push(0);
+        assert!(TokenWhitelistInstruction::unpack(&expect).is_err());
+    }
+
+    #[test]
+    fn test_merkle_batch_bounded() {
+        let program_id = Pubkey::new_unique();
+        let (whitelist, bitmap) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let claim = |proof_len| MerkleClaim {
+            wallet: Pubkey::new_unique(),
+            leaf_index: 0,
+            allocation: 1,
+            proof: vec![[0; 32]; proof_len],
         };
+        // one claim with a 20 node proof takes 687 bytes, a second one does not fit
+        let instruction = verify_merkle_membership_batch(&program_id, &whitelist, &bitmap, vec![claim(20)]).unwrap();
+        assert_eq!(instruction.data.len(), 687);
+        assert_eq!(instruction.accounts.len(), 3);
+        assert!(instruction.accounts[1].is_writable);
+        assert!(instruction.accounts[2].is_signer);
+        assert_eq!(
+            verify_merkle_membership_batch(&program_id, &whitelist, &bitmap, vec![claim(20), claim(20)]),
+            Err(InvalidInstruction)
+        );
+    }
+
+    #[cfg(feature = "merkle")]
+    #[test]
+    fn test_pack_init_claim_bitmap() {
+        let check = TokenWhitelistInstruction::InitClaimBitmap{leaf_count: 4096};
         let packed = check.pack();
-        let mut expect = vec![1];
-        expect.extend_from_slice(&allocation.to_le_bytes());
+        let mut expect = vec![28];
+        expect.extend_from_slice(&4096u32.to_le_bytes());
         assert_eq!(packed, expect);
         let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[28, 0, 16]).is_err());
     }
 
     #[test]
-    fn test_pack_remove_from_whitelist() {
-        let check = TokenWhitelistInstruction::RemoveFromWhitelist{};
+    fn test_disabled_features_refuse_their_tags() {
+        let mut disabled = Vec::new();
+        if !cfg!(feature = "pages") {
+            disabled.extend([
+                TokenWhitelistInstruction::ListEntries {offset: 0, limit: 1},
+                TokenWhitelistInstruction::ListEntriesByCampaign {campaign_id: [1; 8], offset: 0, limit: 1},
+            ]);
+        }
+        if !cfg!(feature = "merkle") {
+            disabled.extend([
+                TokenWhitelistInstruction::SetMerkleRoot {root: [1; 32], hash_kind: 0},
+                TokenWhitelistInstruction::VerifyMerkleMembership {leaf_index: 0, allocation: 1, proof: vec![]},
+                TokenWhitelistInstruction::VerifyMerkleMembershipBatch {items: vec![]},
+                TokenWhitelistInstruction::InitClaimBitmap {leaf_count: 8},
+            ]);
+        }
+        for instruction in disabled {
+            assert_eq!(TokenWhitelistInstruction::unpack(&instruction.pack()), Err(InvalidInstruction.into()));
+        }
+    }
+
+    #[test]
+    fn test_pack_set_only_wallets() {
+        let check = TokenWhitelistInstruction::SetOnlyWallets{only_wallets: true};
         let packed = check.pack();
-        let expect = vec![2];
+        let expect = vec![30, 1];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[30, 2]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[30]).is_err());
+    }
+
+    #[test]
+    fn test_pack_set_self_zero_allowed() {
+        let check = TokenWhitelistInstruction::SetSelfZeroAllowed{self_zero_allowed: false};
+        let packed = check.pack();
+        let expect = vec![33, 0];
         assert_eq!(packed, expect);
         let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[33, 2]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[33]).is_err());
     }
 
     #[test]
-    fn test_pack_set_allocation_zero() {
-        let check = TokenWhitelistInstruction::SetAllocationToZero{};
+    fn test_pack_set_zero_allocation_allowed() {
+        let check = TokenWhitelistInstruction::SetZeroAllocationAllowed{allow_zero_allocation: false};
         let packed = check.pack();
-        let expect = vec![3];
+        let expect = vec![36, 0];
         assert_eq!(packed, expect);
         let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[36, 2]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[36]).is_err());
     }
 
     #[test]
-    fn test_pack_close_whitelist_account() {
-        let check = TokenWhitelistInstruction::CloseWhitelistAccount{};
+    fn test_pack_set_token_gate() {
+        let gating_mint = Pubkey::new_from_array([3; 32]);
+        let check = TokenWhitelistInstruction::SetTokenGate {gating_mint, gating_minimum: 1_000, gate_owner_adds: true};
         let packed = check.pack();
-        let expect = vec![4];
+        let mut expect = vec![37];
+        expect.extend_from_slice(&[3; 32]);
+        expect.extend_from_slice(&1_000u64.to_le_bytes());
+        expect.push(1);
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&expect[..41]).is_err());
+        expect[41] = 2;
+        assert!(TokenWhitelistInstruction::unpack(&expect).is_err());
+
+        let (program_id, owner, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(set_token_gate(&program_id, &owner, &whitelist, &gating_mint, 1_000, false).accounts.len(), 3);
+        assert_eq!(set_token_gate(&program_id, &owner, &whitelist, &gating_mint, 0, false).accounts.len(), 2);
+    }
+
+    #[test]
+    fn test_pack_init_config() {
+        let check = TokenWhitelistInstruction::InitConfig{};
+        let packed = check.pack();
+        let expect = vec![32];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_with_config() {
+        let (program_id, owner, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (config, _) = WhitelistConfig::find_address(&program_id, &whitelist);
+        assert_eq!(init_config(&program_id, &owner, &whitelist).accounts[2], AccountMeta::new(config, false));
+
+        let add = with_config(add_to_whitelist(&program_id, &owner, &whitelist, &Pubkey::new_unique(), 100), &config);
+        assert_eq!(add.accounts.len(), 4);
+        assert_eq!(add.accounts[3], AccountMeta::new_readonly(config, false));
+
+        let set_round = Instruction::new_with_bytes(
+            program_id,
+            &TokenWhitelistInstruction::SetActiveRound {round: 1}.pack(),
+            vec![AccountMeta::new_readonly(owner, true), AccountMeta::new(whitelist, false)],
+        );
+        assert_eq!(with_config(set_round, &config).accounts[2], AccountMeta::new(config, false));
+    }
+
+    #[test]
+    fn test_pack_top_up_rent() {
+        let check = TokenWhitelistInstruction::TopUpRent{};
+        let packed = check.pack();
+        let expect = vec![31];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_repair_state() {
+        let check = TokenWhitelistInstruction::RepairState{};
+        let packed = check.pack();
+        let expect = vec![35];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_migrate_state_layout() {
+        let check = TokenWhitelistInstruction::MigrateStateLayout{};
+        let packed = check.pack();
+        let expect = vec![38];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let program_id = Pubkey::new_unique();
+        let instruction = migrate_state_layout(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique());
+        let (program_data, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        assert_eq!(instruction.accounts[2], AccountMeta::new_readonly(program_data, false));
+    }
+
+    #[test]
+    fn test_pack_inspect_state() {
+        let check = TokenWhitelistInstruction::InspectState{max_entries_to_log: 5};
+        let packed = check.pack();
+        let expect = vec![39, 5];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[39]).is_err());
+    }
+
+    #[test]
+    fn test_pack_effective_allocation() {
+        let check = TokenWhitelistInstruction::EffectiveAllocation{};
+        let packed = check.pack();
+        let expect = vec![40];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_get_whitelist_stats() {
+        let check = TokenWhitelistInstruction::GetWhitelistStats{};
+        let packed = check.pack();
+        let expect = vec![45];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_attestation_instructions() {
+        let check = TokenWhitelistInstruction::AddToWhitelistWithAttestation{
+            allocation_amount: 250,
+            attestation: [7; 32],
+        };
+        let mut expect = vec![46];
+        expect.extend_from_slice(&250u64.to_le_bytes());
+        expect.extend_from_slice(&[7; 32]);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..39]).is_err());
+
+        let check = TokenWhitelistInstruction::SetAttestation{attestation: [7; 32]};
+        let mut expect = vec![47];
+        expect.extend_from_slice(&[7; 32]);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..32]).is_err());
+
+        let check = TokenWhitelistInstruction::SetAttestationRequired{require_attestation: true};
+        assert_eq!(check.pack(), vec![48, 1]);
+        assert_eq!(TokenWhitelistInstruction::unpack(&[48, 1]).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&[48, 2]).is_err());
+    }
+
+    #[test]
+    fn test_pack_rotate_whitelisted_key() {
+        let check = TokenWhitelistInstruction::RotateWhitelistedKey{};
+        assert_eq!(check.pack(), vec![50]);
+        assert_eq!(TokenWhitelistInstruction::unpack(&[50]).unwrap(), check);
+
+        let (current, whitelist, new) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = rotate_whitelisted_key(&crate::id(), &current, &whitelist, &new);
+        assert!(instruction.accounts[0].is_signer && instruction.accounts[2].is_signer);
+        assert!(instruction.accounts[1].is_writable && !instruction.accounts[1].is_signer);
+    }
+
+    #[test]
+    fn test_pack_assert_not_in_other() {
+        let check = TokenWhitelistInstruction::AssertNotInOther{};
+        assert_eq!(check.pack(), vec![51]);
+        assert_eq!(TokenWhitelistInstruction::unpack(&[51]).unwrap(), check);
+
+        let (owner, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let siblings = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let instruction = assert_not_in_other(&crate::id(), &owner, &whitelist, &siblings);
+        assert_eq!(instruction.accounts.len(), 4);
+        assert!(instruction.accounts[2..].iter().all(|meta| !meta.is_writable && !meta.is_signer));
+
+        let add = with_sibling_whitelists(add_to_whitelist(&crate::id(), &owner, &whitelist, &owner, 100), &siblings);
+        assert_eq!(add.accounts[3..], instruction.accounts[2..]);
+    }
+
+    #[test]
+    fn test_pack_set_owner_entry_allowed() {
+        let check = TokenWhitelistInstruction::SetOwnerEntryAllowed{allow_owner_entry: false};
+        let packed = check.pack();
+        let expect = vec![52, 0];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[52, 2]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[52]).is_err());
+    }
+
+    #[cfg(feature = "pages")]
+    #[test]
+    fn test_pack_list_attested_entries() {
+        let check = TokenWhitelistInstruction::ListAttestedEntries{offset: 40, limit: 14};
+        let mut expect = vec![49];
+        expect.extend_from_slice(&40u32.to_le_bytes());
+        expect.push(14);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..5]).is_err());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_pack_compressed_instructions() {
+        let check = TokenWhitelistInstruction::InitCompressedWhitelist{};
+        assert_eq!(check.pack(), vec![41]);
+        assert_eq!(TokenWhitelistInstruction::unpack(&[41]).unwrap(), check);
+
+        let check = TokenWhitelistInstruction::AppendCompressedEntry{allocation_amount: 500};
+        let mut expect = vec![42];
+        expect.extend_from_slice(&500u64.to_le_bytes());
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..8]).is_err());
+
+        let check = TokenWhitelistInstruction::ReplaceCompressedEntry{
+            leaf_index: 3,
+            previous_allocation: 500,
+            allocation_amount: 700,
+            root: [7; 32],
+        };
+        let mut expect = vec![43];
+        expect.extend_from_slice(&3u32.to_le_bytes());
+        expect.extend_from_slice(&500u64.to_le_bytes());
+        expect.extend_from_slice(&700u64.to_le_bytes());
+        expect.extend_from_slice(&[7; 32]);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..52]).is_err());
+
+        let check = TokenWhitelistInstruction::VerifyCompressedMembership{leaf_index: 3, allocation: 500, root: [7; 32]};
+        let mut expect = vec![44];
+        expect.extend_from_slice(&3u32.to_le_bytes());
+        expect.extend_from_slice(&500u64.to_le_bytes());
+        expect.extend_from_slice(&[7; 32]);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..44]).is_err());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_builders() {
+        let program_id = Pubkey::new_unique();
+        let (owner, whitelist, tree, wallet) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (tree_authority, _) = compression::find_tree_authority(&program_id, &whitelist);
+        let leaf = CompressedLeaf {leaf_index: 3, allocation: 500, root: [7; 32], proof: vec![[1; 32], [2; 32]]};
+
+        let replace = replace_compressed_entry(&program_id, &owner, &whitelist, &tree, &wallet, &leaf, 700);
+        let keys: Vec<Pubkey> = replace.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys[..5], [owner, whitelist, wallet, tree, tree_authority]);
+        assert_eq!(keys[7..], [Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32])]);
+        assert!(replace.accounts[0].is_signer && replace.accounts[3].is_writable);
+
+        let verify = verify_compressed_membership(&program_id, &whitelist, &tree, &wallet, &leaf);
+        assert_eq!(verify.accounts.len(), 4 + 2);
+        assert!(verify.accounts.iter().all(|meta| !meta.is_signer && !meta.is_writable));
+        assert_eq!(
+            TokenWhitelistInstruction::unpack(&verify.data).unwrap(),
+            TokenWhitelistInstruction::VerifyCompressedMembership {leaf_index: 3, allocation: 500, root: [7; 32]}
+        );
+    }
+
+    #[test]
+    fn test_pack_assert_owner() {
+        let check = TokenWhitelistInstruction::AssertOwner{};
+        let packed = check.pack();
+        let expect = vec![29];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_get_version() {
+        let check = TokenWhitelistInstruction::GetVersion{};
+        let packed = check.pack();
+        let expect = vec![23];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_pack_get_required_rent() {
+        let check = TokenWhitelistInstruction::GetRequiredRent{max_whitelist_size: 50};
+        let packed = check.pack();
+        let mut expect = vec![53];
+        expect.extend_from_slice(&50u64.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..8]).is_err());
+    }
+
+    #[test]
+    fn test_pack_entry_notes() {
+        let check = TokenWhitelistInstruction::SetEntryNoteCommitment{commitment: [7; 32]};
+        let mut expect = vec![54];
+        expect.extend_from_slice(&[7; 32]);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..32]).is_err());
+
+        let check = TokenWhitelistInstruction::RevealEntryNote{note: b"tier 2".to_vec(), salt: [9; 16]};
+        let mut expect = vec![55, 6];
+        expect.extend_from_slice(b"tier 2");
+        expect.extend_from_slice(&[9; 16]);
+        assert_eq!(check.pack(), expect);
+        assert_eq!(TokenWhitelistInstruction::unpack(&expect).unwrap(), check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..22]).is_err());
+
+        let mut data = vec![55, MAX_NOTE_LEN as u8 + 1];
+        data.extend_from_slice(&[0; MAX_NOTE_LEN + 1 + 16]);
+        assert_eq!(TokenWhitelistInstruction::unpack(&data), Err(NoteTooLong.into()));
+    }
+
+    #[test]
+    fn test_pack_set_registration_rate_limit() {
+        let check = TokenWhitelistInstruction::SetRegistrationRateLimit{
+            max_registrations_per_window: 20,
+            window_slots: 150,
+        };
+        let packed = check.pack();
+        let mut expect = vec![56];
+        expect.extend_from_slice(&20u16.to_le_bytes());
+        expect.extend_from_slice(&150u32.to_le_bytes());
         assert_eq!(packed, expect);
         let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+        assert!(TokenWhitelistInstruction::unpack(&expect[..6]).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pack_set_enumeration_restricted() {
+        let check = TokenWhitelistInstruction::SetEnumerationRestricted{restrict_enumeration: true};
+        let packed = check.pack();
+        let expect = vec![57, 1];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert!(TokenWhitelistInstruction::unpack(&[57, 2]).is_err());
+        assert!(TokenWhitelistInstruction::unpack(&[57]).is_err());
+    }
+
+    #[test]
+    fn test_pack_archive_whitelist() {
+        let check = TokenWhitelistInstruction::ArchiveWhitelist{};
+        let packed = check.pack();
+        let expect = vec![22];
+        assert_eq!(packed, expect);
+        let unpacked = TokenWhitelistInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+}
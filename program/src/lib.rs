@@ -1,7 +1,108 @@
+#[cfg(any(test, feature = "test-utils"))]
+pub mod account_fixture;
+pub mod accounts;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod core;
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod lottery;
+#[cfg(feature = "merkle")]
+pub mod merkle;
 pub mod processor;
+pub mod replay;
+pub mod rules;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "simulator")]
+pub mod simulator;
 pub mod state;
+pub mod summary;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+pub mod token;
+pub mod util;
+pub mod whitelist_account;
+#[cfg(feature = "test-utils")]
+pub mod testing;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
+
+// Address of the official deployment. The processor never compares against it, so the same
+// build runs under any program id; see instruction::official for builders that default to it.
+solana_program::declare_id!("B2VhkPRAMWQqteuFMSTMLvrbs2CKaJsj6G9o2N1rX7d6");
+
+/// Capability bits reported by GetVersion in `feature_bits`. Bits of capabilities this build
+/// lacks stay clear, so a client can test for one before sending its instructions.
+pub const BATCH_ADD: u64 = 1 << 0;
+pub const MERKLE_MODE: u64 = 1 << 1;
+pub const PAUSE: u64 = 1 << 2;
+/// Read instructions (GetAllocation, ComputeEntriesHash, GetVersion and, in builds with the
+/// `pages` feature, ListEntries) answer through the return data
+pub const RETURN_DATA: u64 = 1 << 3;
+/// AddToWhitelist and AddToWhitelistForRound take a trailing campaign id
+pub const CAMPAIGNS: u64 = 1 << 4;
+/// Whitelists count their writes in mutation_nonce, and AddToWhitelist, AddToWhitelistForRound
+/// and RemoveFromWhitelist take an expected nonce
+pub const MUTATION_NONCE: u64 = 1 << 5;
+/// Whitelists can keep their entries in a tree of spl-account-compression, see the compression
+/// module
+pub const COMPRESSED_MODE: u64 = 1 << 6;
+/// Whitelists count their adds and removes, read with GetWhitelistStats
+pub const WHITELIST_STATS: u64 = 1 << 7;
+/// Entries carry an attestation, see AddToWhitelistWithAttestation and SetAttestation
+pub const ATTESTATIONS: u64 = 1 << 8;
+/// Whitelisted accounts move their entry to a new key themselves, see RotateWhitelistedKey
+pub const KEY_ROTATION: u64 = 1 << 9;
+/// Adds refuse accounts listed in linked sibling whitelists, see AssertNotInOther
+pub const SIBLING_WHITELISTS: u64 = 1 << 10;
+/// GetRequiredRent answers the size and rent exempt balance of a new whitelist
+pub const REQUIRED_RENT: u64 = 1 << 11;
+/// Whitelists record the slot and signer of their init, returned by GetWhitelistStats after the
+/// stats
+pub const CREATION_RECORD: u64 = 1 << 12;
+/// Entries carry a commitment to a note RevealEntryNote checks, see SetEntryNoteCommitment
+pub const ENTRY_NOTES: u64 = 1 << 13;
+/// RegisterSelf is capped per window of slots, see SetRegistrationRateLimit
+pub const REGISTRATION_RATE_LIMIT: u64 = 1 << 14;
+/// Instructions enumerating entries can be restricted to the owner and the delegate, see
+/// SetEnumerationRestricted
+pub const ENUMERATION_RESTRICTION: u64 = 1 << 15;
+
+/// Capabilities compiled into this build
+pub const FEATURE_BITS: u64 = (if cfg!(feature = "merkle") { MERKLE_MODE } else { 0 })
+    | (if cfg!(feature = "compression") { COMPRESSED_MODE } else { 0 })
+    | RETURN_DATA
+    | CAMPAIGNS
+    | MUTATION_NONCE
+    | WHITELIST_STATS
+    | ATTESTATIONS
+    | KEY_ROTATION
+    | SIBLING_WHITELISTS
+    | REQUIRED_RENT
+    | CREATION_RECORD
+    | ENTRY_NOTES
+    | REGISTRATION_RATE_LIMIT
+    | ENUMERATION_RESTRICTION;
+
+/// Crate version as [major, minor, patch]
+pub const VERSION: [u16; 3] = [
+    parse_version_part(env!("CARGO_PKG_VERSION_MAJOR")),
+    parse_version_part(env!("CARGO_PKG_VERSION_MINOR")),
+    parse_version_part(env!("CARGO_PKG_VERSION_PATCH")),
+];
+
+const fn parse_version_part(part: &str) -> u16 {
+    let digits = part.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        value = value * 10 + (digits[i] - b'0') as u16;
+        i += 1;
+    }
+    value
+}
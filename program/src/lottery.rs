@@ -0,0 +1,71 @@
+use solana_program::hash::hashv;
+use std::collections::BTreeMap;
+
+use crate::state::Lottery;
+
+/// Hash ranking a whitelist key in a lottery draw, lower hashes win
+pub fn lottery_hash(seed: &[u8; 32], slot_hash: &[u8; 32], key: &str) -> [u8; 32] {
+    hashv(&[seed, slot_hash, key.as_bytes()]).to_bytes()
+}
+
+/// Selects the `winners` keys with the lowest lottery hashes, in key order
+pub fn select_winners<'a, I>(keys: I, seed: &[u8; 32], slot_hash: &[u8; 32], winners: u64) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut ranked: Vec<([u8; 32], &String)> = keys
+        .into_iter()
+        .map(|key| (lottery_hash(seed, slot_hash, key), key))
+        .collect();
+    ranked.sort_unstable();
+    ranked.truncate(winners.min(ranked.len() as u64) as usize);
+
+    let mut selected: Vec<String> = ranked.into_iter().map(|(_, key)| key.clone()).collect();
+    selected.sort_unstable();
+    selected
+}
+
+/// Re-runs a recorded draw over the entries present before it and checks that exactly the
+/// selected keys, with unchanged allocations, remain in the whitelist after it
+pub fn verify_lottery(
+    lottery: &Lottery,
+    entries_before: &BTreeMap<String, u64>,
+    entries_after: &BTreeMap<String, u64>,
+) -> bool {
+    let selected = select_winners(entries_before.keys(), &lottery.seed, &lottery.slot_hash, lottery.winners);
+    selected.len() == entries_after.len()
+        && selected
+            .iter()
+            .all(|key| matches!(entries_after.get(key), Some(amount) if entries_before.get(key) == Some(amount)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(count: u8) -> Vec<String> {
+        (0..count).map(|i| format!("wallet-{}", i)).collect()
+    }
+
+    #[test]
+    fn test_select_winners_is_deterministic() {
+        let seed = [7; 32];
+        let slot_hash = [9; 32];
+        let candidates = keys(20);
+
+        let first = select_winners(&candidates, &seed, &slot_hash, 5);
+        let second = select_winners(candidates.iter().rev(), &seed, &slot_hash, 5);
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, second);
+
+        let reseeded = select_winners(&candidates, &[8; 32], &slot_hash, 5);
+        assert_ne!(first, reseeded);
+    }
+
+    #[test]
+    fn test_select_winners_undersubscribed() {
+        let candidates = keys(3);
+        let selected = select_winners(&candidates, &[1; 32], &[2; 32], 10);
+        assert_eq!(selected, candidates);
+    }
+}
@@ -0,0 +1,164 @@
+//! Merkle mode: a whitelist committing to a root over (index, wallet, allocation) leaves instead of
+//! storing every entry. The program verifies proofs with the functions here and off-chain
+//! tooling builds roots and proofs with [MerkleTree](struct.MerkleTree.html), so both hash alike.
+
+use solana_program::{hash, keccak, pubkey::Pubkey};
+use crate::error::TokenWhitelistError;
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Hash function a root was built with, stored next to it as `hash_kind`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HashKind {
+    Keccak = 0,
+    Sha256 = 1,
+}
+
+impl HashKind {
+    pub fn from_u8(hash_kind: u8) -> Result<Self, TokenWhitelistError> {
+        match hash_kind {
+            0 => Ok(HashKind::Keccak),
+            1 => Ok(HashKind::Sha256),
+            _ => Err(TokenWhitelistError::UnknownHashKind),
+        }
+    }
+
+    pub fn hashv(self, vals: &[&[u8]]) -> [u8; 32] {
+        match self {
+            HashKind::Keccak => keccak::hashv(vals).to_bytes(),
+            HashKind::Sha256 => hash::hashv(vals).to_bytes(),
+        }
+    }
+}
+
+/// Leaf committing to `wallet` and its allocation at `leaf_index`, the index tying a claim to
+/// one bit of the claim bitmap
+pub fn leaf(kind: HashKind, leaf_index: u32, wallet: &Pubkey, allocation: u64) -> [u8; 32] {
+    kind.hashv(&[LEAF_PREFIX, &leaf_index.to_le_bytes(), wallet.as_ref(), &allocation.to_le_bytes()])
+}
+
+/// Parent of two nodes, hashed in sorted order so a proof needs no left or right flags
+fn parent(kind: HashKind, a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    kind.hashv(&[NODE_PREFIX, left, right])
+}
+
+/// Whether `proof`, the siblings from the leaf up, leads from `leaf` to `root`
+pub fn verify(kind: HashKind, root: &[u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+    proof.iter().fold(leaf, |node, sibling| parent(kind, &node, sibling)) == *root
+}
+
+/// Tree over entries in the given order, their positions being the leaf indices. An unpaired
+/// node is carried up a level unchanged.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    kind: HashKind,
+    levels: Vec<Vec<[u8; 32]>>, // leaves first, the root level last
+}
+
+impl MerkleTree {
+    pub fn new(kind: HashKind, entries: &[(Pubkey, u64)]) -> Self {
+        let leaves = entries.iter()
+            .enumerate()
+            .map(|(leaf_index, (wallet, allocation))| leaf(kind, leaf_index as u32, wallet, *allocation))
+            .collect();
+        let mut levels: Vec<Vec<[u8; 32]>> = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => parent(kind, a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree {kind, levels}
+    }
+
+    pub fn hash_kind(&self) -> HashKind {
+        self.kind
+    }
+
+    /// Root of the tree, all zeros for a tree without entries
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().unwrap().first().copied().unwrap_or_default()
+    }
+
+    /// Proof for the entry at `index`, None past the last entry
+    pub fn proof(&self, index: usize) -> Option<Vec<[u8; 32]>> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: u8) -> Vec<(Pubkey, u64)> {
+        (0..count).map(|i| (Pubkey::new_from_array([i; 32]), 100 * i as u64)).collect()
+    }
+
+    #[test]
+    fn test_hash_kind() {
+        assert_eq!(HashKind::from_u8(0), Ok(HashKind::Keccak));
+        assert_eq!(HashKind::from_u8(1), Ok(HashKind::Sha256));
+        assert_eq!(HashKind::from_u8(2), Err(TokenWhitelistError::UnknownHashKind));
+        assert_ne!(HashKind::Keccak.hashv(&[b"leaf"]), HashKind::Sha256.hashv(&[b"leaf"]));
+    }
+
+    #[test]
+    fn test_every_proof_verifies() {
+        for kind in [HashKind::Keccak, HashKind::Sha256] {
+            for count in 1..=9 {
+                let entries = entries(count);
+                let tree = MerkleTree::new(kind, &entries);
+                for (i, (wallet, allocation)) in entries.iter().enumerate() {
+                    let proof = tree.proof(i).unwrap();
+                    let leaf_index = i as u32;
+                    assert!(verify(kind, &tree.root(), leaf(kind, leaf_index, wallet, *allocation), &proof));
+                    assert!(!verify(kind, &tree.root(), leaf(kind, leaf_index, wallet, allocation + 1), &proof));
+                    // the same wallet and allocation under another index is another leaf
+                    assert!(!verify(kind, &tree.root(), leaf(kind, leaf_index + 1, wallet, *allocation), &proof));
+                }
+                assert_eq!(tree.proof(entries.len()), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_bound_to_hash_kind() {
+        let entries = entries(5);
+        let keccak_tree = MerkleTree::new(HashKind::Keccak, &entries);
+        let sha256_tree = MerkleTree::new(HashKind::Sha256, &entries);
+        assert_ne!(keccak_tree.root(), sha256_tree.root());
+
+        let (wallet, allocation) = entries[3];
+        let proof = keccak_tree.proof(3).unwrap();
+        assert!(!verify(HashKind::Sha256, &keccak_tree.root(), leaf(HashKind::Sha256, 3, &wallet, allocation), &proof));
+        assert!(!verify(HashKind::Keccak, &sha256_tree.root(), leaf(HashKind::Keccak, 3, &wallet, allocation), &proof));
+    }
+
+    #[test]
+    fn test_empty_and_single_entry() {
+        let kind = HashKind::Sha256;
+        assert_eq!(MerkleTree::new(kind, &[]).root(), [0; 32]);
+        let entries = entries(1);
+        let tree = MerkleTree::new(kind, &entries);
+        assert_eq!(tree.root(), leaf(kind, 0, &entries[0].0, entries[0].1));
+        assert_eq!(tree.proof(0), Some(vec![]));
+    }
+}
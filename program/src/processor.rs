@@ -1,18 +1,45 @@
+use borsh::BorshSerialize;
 use num_traits::FromPrimitive;
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
+    account_info::AccountInfo,
     entrypoint::ProgramResult,
+    log::sol_log,
     msg,
     decode_error::DecodeError,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::{PrintProgramError, ProgramError},
-    program_pack::{IsInitialized},
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{self, clock::Clock, rent::Rent, Sysvar},
 };
+use std::{convert::TryInto, fmt::Write};
 use crate::{
+    accounts::expect_account,
+    core::{Action, AuthorityContext, Churn, Effects, Store},
     error::TokenWhitelistError,
-    instruction::TokenWhitelistInstruction,
-    state::TokenWhitelist,
+    event::WhitelistEvent,
+    instruction::{TokenWhitelistInstruction, MAX_INSPECT_ENTRIES},
+    rules,
+    token,
+    util::{transfer_lamports, upgrade_authority},
+    whitelist_account::WhitelistAccount,
+    state::{
+        hash_entries_map, AllocationBounds, MAX_ROUNDS, ConsumeLimits, Delegate, Lottery, Registration, TokenWhitelist,
+        ProgramVersion, RegistrationRateLimit, RequiredRent, TokenGate, WhitelistArchive, WhitelistConfig, WhitelistPolicy, WhitelistStats,
+        ARCHIVE_SPACE, CONFIG_SEED, CONFIG_SPACE, MAX_SIBLING_WHITELISTS, NO_ATTESTATION, NO_CAMPAIGN,
+    },
+};
+#[cfg(feature = "compression")]
+use crate::{
+    compression::{self, TREE_AUTHORITY_SEED},
+    state::CompressedTree,
+};
+#[cfg(feature = "compression")]
+use solana_program::instruction::Instruction;
+#[cfg(feature = "merkle")]
+use crate::{
+    instruction::MerkleClaim,
+    state::{claim_bitmap_len, ClaimBitmap, MerkleRoot},
 };
 
 pub struct Processor;
@@ -30,28 +57,39 @@ impl Processor {
                 Self::process_init_whitelist(
                     accounts,
                     max_whitelist_size,
+                    0,
+                    None,
+                    false,
+                    true,
                     program_id
                 )
             }
-            TokenWhitelistInstruction::AddToWhitelist {allocation_amount} => {
+            TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id, expected_nonce} => {
                 msg!("Instruction: AddToWhitelist");
                 Self::process_add_whitelist(
                     accounts,
                     allocation_amount,
+                    0,
+                    campaign_id,
+                    expected_nonce,
+                    NO_ATTESTATION,
                     program_id
                 )
             }
-            TokenWhitelistInstruction::RemoveFromWhitelist {} => {
+            TokenWhitelistInstruction::RemoveFromWhitelist {memo, expected_nonce} => {
                 msg!("Instruction: RemoveFromWhitelist");
                 Self::process_remove_whitelist(
                     accounts,
+                    memo,
+                    expected_nonce,
                     program_id
                 )
             }
-            TokenWhitelistInstruction::SetAllocationToZero {} => {
+            TokenWhitelistInstruction::SetAllocationToZero {memo} => {
                 msg!("Instruction: SetAllocationToZero");
                 Self::process_set_allocation_to_zero(
                     accounts,
+                    memo,
                     program_id
                 )
             }
@@ -62,183 +100,2506 @@ impl Processor {
                     program_id
                 )
             }
+            TokenWhitelistInstruction::ApproveDelegate {max_adds, max_allocation_per_add} => {
+                msg!("Instruction: ApproveDelegate");
+                Self::process_approve_delegate(
+                    accounts,
+                    max_adds,
+                    max_allocation_per_add,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RevokeDelegate {} => {
+                msg!("Instruction: RevokeDelegate");
+                Self::process_revoke_delegate(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::ConfigureRegistration {
+                registration_end_slot,
+                registration_allocation,
+                registration_deposit_lamports,
+            } => {
+                msg!("Instruction: ConfigureRegistration");
+                Self::process_configure_registration(
+                    accounts,
+                    Registration {
+                        registration_end_slot,
+                        registration_allocation,
+                        registration_deposit_lamports,
+                    },
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RegisterSelf {} => {
+                msg!("Instruction: RegisterSelf");
+                Self::process_register_self(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RefundDeposit {} => {
+                msg!("Instruction: RefundDeposit");
+                Self::process_refund_deposit(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RunLottery {winners, seed} => {
+                msg!("Instruction: RunLottery");
+                Self::process_run_lottery(
+                    accounts,
+                    winners,
+                    seed,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetConsumeLimits {max_consume_per_tx, min_slots_between_consumes} => {
+                msg!("Instruction: SetConsumeLimits");
+                Self::process_set_consume_limits(
+                    accounts,
+                    ConsumeLimits {
+                        max_consume_per_tx,
+                        min_slots_between_consumes,
+                    },
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::ConsumeAllocation {amount} => {
+                msg!("Instruction: ConsumeAllocation");
+                Self::process_consume_allocation(
+                    accounts,
+                    amount,
+                    0,
+                    program_id
+                )
+            }
+            #[cfg(feature = "pages")]
+            TokenWhitelistInstruction::ListEntries {offset, limit} => {
+                msg!("Instruction: ListEntries");
+                Self::process_list_entries(
+                    accounts,
+                    offset,
+                    limit,
+                    program_id
+                )
+            }
+            #[cfg(feature = "pages")]
+            TokenWhitelistInstruction::ListEntriesByCampaign {campaign_id, offset, limit} => {
+                msg!("Instruction: ListEntriesByCampaign");
+                Self::process_list_entries_by_campaign(
+                    accounts,
+                    campaign_id,
+                    offset,
+                    limit,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::ComputeEntriesHash {} => {
+                msg!("Instruction: ComputeEntriesHash");
+                Self::process_compute_entries_hash(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::InitTokenWhitelistWithUnits {
+                max_whitelist_size,
+                allocation_decimals,
+                only_wallets,
+                self_zero_allowed,
+            } => {
+                msg!("Instruction: InitTokenWhitelistWithUnits");
+                Self::process_init_whitelist_with_units(
+                    accounts,
+                    max_whitelist_size,
+                    allocation_decimals,
+                    only_wallets,
+                    self_zero_allowed,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::AddToWhitelistForRound {
+                allocation_amount,
+                round,
+                campaign_id,
+                expected_nonce,
+            } => {
+                msg!("Instruction: AddToWhitelistForRound");
+                Self::process_add_whitelist(
+                    accounts,
+                    allocation_amount,
+                    round,
+                    campaign_id,
+                    expected_nonce,
+                    NO_ATTESTATION,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::ConsumeAllocationForRound {amount, round} => {
+                msg!("Instruction: ConsumeAllocationForRound");
+                Self::process_consume_allocation(
+                    accounts,
+                    amount,
+                    round,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetActiveRound {round} => {
+                msg!("Instruction: SetActiveRound");
+                Self::process_set_active_round(
+                    accounts,
+                    round,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::GetAllocation {round} => {
+                msg!("Instruction: GetAllocation");
+                Self::process_get_allocation(
+                    accounts,
+                    round,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::AssertMembershipAcross {require_in_first, require_absent_in_second} => {
+                msg!("Instruction: AssertMembershipAcross");
+                Self::process_assert_membership_across(
+                    accounts,
+                    require_in_first,
+                    require_absent_in_second,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetAllocationBounds {min_allocation, max_allocation} => {
+                msg!("Instruction: SetAllocationBounds");
+                Self::process_set_allocation_bounds(
+                    accounts,
+                    AllocationBounds {
+                        min_allocation,
+                        max_allocation,
+                    },
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::ArchiveWhitelist {} => {
+                msg!("Instruction: ArchiveWhitelist");
+                Self::process_archive_whitelist(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::GetVersion {} => {
+                msg!("Instruction: GetVersion");
+                Self::process_get_version()
+            }
+            TokenWhitelistInstruction::SetStrictTokenChecks {strict_token_checks} => {
+                msg!("Instruction: SetStrictTokenChecks");
+                Self::process_set_strict_token_checks(
+                    accounts,
+                    strict_token_checks,
+                    program_id
+                )
+            }
+            #[cfg(feature = "merkle")]
+            TokenWhitelistInstruction::SetMerkleRoot {root, hash_kind} => {
+                msg!("Instruction: SetMerkleRoot");
+                Self::process_set_merkle_root(
+                    accounts,
+                    root,
+                    hash_kind,
+                    program_id
+                )
+            }
+            #[cfg(feature = "merkle")]
+            TokenWhitelistInstruction::VerifyMerkleMembership {leaf_index, allocation, proof} => {
+                msg!("Instruction: VerifyMerkleMembership");
+                Self::process_verify_merkle_membership(
+                    accounts,
+                    leaf_index,
+                    allocation,
+                    &proof,
+                    program_id
+                )
+            }
+            #[cfg(feature = "merkle")]
+            TokenWhitelistInstruction::VerifyMerkleMembershipBatch {items} => {
+                msg!("Instruction: VerifyMerkleMembershipBatch");
+                Self::process_verify_merkle_membership_batch(
+                    accounts,
+                    &items,
+                    program_id
+                )
+            }
+            #[cfg(feature = "merkle")]
+            TokenWhitelistInstruction::InitClaimBitmap {leaf_count} => {
+                msg!("Instruction: InitClaimBitmap");
+                Self::process_init_claim_bitmap(
+                    accounts,
+                    leaf_count,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetOnlyWallets {only_wallets} => {
+                msg!("Instruction: SetOnlyWallets");
+                Self::process_set_only_wallets(
+                    accounts,
+                    only_wallets,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed} => {
+                msg!("Instruction: SetSelfZeroAllowed");
+                Self::process_set_self_zero_allowed(
+                    accounts,
+                    self_zero_allowed,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::InitConfig {} => {
+                msg!("Instruction: InitConfig");
+                Self::process_init_config(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::TopUpRent {} => {
+                msg!("Instruction: TopUpRent");
+                Self::process_top_up_rent(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::AssertOwner {} => {
+                msg!("Instruction: AssertOwner");
+                Self::process_assert_owner(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RepairState {} => {
+                msg!("Instruction: RepairState");
+                Self::process_repair_state(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation} => {
+                msg!("Instruction: SetZeroAllocationAllowed");
+                Self::process_set_zero_allocation_allowed(
+                    accounts,
+                    allow_zero_allocation,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetTokenGate {gating_mint, gating_minimum, gate_owner_adds} => {
+                msg!("Instruction: SetTokenGate");
+                Self::process_set_token_gate(
+                    accounts,
+                    gating_mint,
+                    gating_minimum,
+                    gate_owner_adds,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::MigrateStateLayout {} => {
+                msg!("Instruction: MigrateStateLayout");
+                Self::process_migrate_state_layout(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::InspectState {max_entries_to_log} => {
+                msg!("Instruction: InspectState");
+                Self::process_inspect_state(
+                    accounts,
+                    max_entries_to_log,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::EffectiveAllocation {} => {
+                msg!("Instruction: EffectiveAllocation");
+                Self::process_effective_allocation(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::GetWhitelistStats {} => {
+                msg!("Instruction: GetWhitelistStats");
+                Self::process_get_whitelist_stats(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::AddToWhitelistWithAttestation {allocation_amount, attestation} => {
+                msg!("Instruction: AddToWhitelistWithAttestation");
+                Self::process_add_whitelist(
+                    accounts,
+                    allocation_amount,
+                    0,
+                    NO_CAMPAIGN,
+                    0,
+                    attestation,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetAttestation {attestation} => {
+                msg!("Instruction: SetAttestation");
+                Self::process_set_attestation(
+                    accounts,
+                    attestation,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetAttestationRequired {require_attestation} => {
+                msg!("Instruction: SetAttestationRequired");
+                Self::process_set_attestation_required(
+                    accounts,
+                    require_attestation,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RotateWhitelistedKey {} => {
+                msg!("Instruction: RotateWhitelistedKey");
+                Self::process_rotate_whitelisted_key(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::AssertNotInOther {} => {
+                msg!("Instruction: AssertNotInOther");
+                Self::process_assert_not_in_other(
+                    accounts,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetOwnerEntryAllowed {allow_owner_entry} => {
+                msg!("Instruction: SetOwnerEntryAllowed");
+                Self::process_set_owner_entry_allowed(
+                    accounts,
+                    allow_owner_entry,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::GetRequiredRent {max_whitelist_size} => {
+                msg!("Instruction: GetRequiredRent");
+                Self::process_get_required_rent(max_whitelist_size)
+            }
+            TokenWhitelistInstruction::SetEntryNoteCommitment {commitment} => {
+                msg!("Instruction: SetEntryNoteCommitment");
+                Self::process_set_entry_note_commitment(
+                    accounts,
+                    commitment,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::RevealEntryNote {note, salt} => {
+                msg!("Instruction: RevealEntryNote");
+                Self::process_reveal_entry_note(
+                    accounts,
+                    note,
+                    salt,
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetRegistrationRateLimit {max_registrations_per_window, window_slots} => {
+                msg!("Instruction: SetRegistrationRateLimit");
+                Self::process_set_registration_rate_limit(
+                    accounts,
+                    RegistrationRateLimit {
+                        max_registrations_per_window,
+                        window_slots,
+                        ..RegistrationRateLimit::default()
+                    },
+                    program_id
+                )
+            }
+            TokenWhitelistInstruction::SetEnumerationRestricted {restrict_enumeration} => {
+                msg!("Instruction: SetEnumerationRestricted");
+                Self::process_set_enumeration_restricted(
+                    accounts,
+                    restrict_enumeration,
+                    program_id
+                )
+            }
+            #[cfg(feature = "pages")]
+            TokenWhitelistInstruction::ListAttestedEntries {offset, limit} => {
+                msg!("Instruction: ListAttestedEntries");
+                Self::process_list_attested_entries(
+                    accounts,
+                    offset,
+                    limit,
+                    program_id
+                )
+            }
+            #[cfg(feature = "compression")]
+            TokenWhitelistInstruction::InitCompressedWhitelist {} => {
+                msg!("Instruction: InitCompressedWhitelist");
+                Self::process_init_compressed_whitelist(
+                    accounts,
+                    program_id
+                )
+            }
+            #[cfg(feature = "compression")]
+            TokenWhitelistInstruction::AppendCompressedEntry {allocation_amount} => {
+                msg!("Instruction: AppendCompressedEntry");
+                Self::process_append_compressed_entry(
+                    accounts,
+                    allocation_amount,
+                    program_id
+                )
+            }
+            #[cfg(feature = "compression")]
+            TokenWhitelistInstruction::ReplaceCompressedEntry {leaf_index, previous_allocation, allocation_amount, root} => {
+                msg!("Instruction: ReplaceCompressedEntry");
+                Self::process_replace_compressed_entry(
+                    accounts,
+                    leaf_index,
+                    previous_allocation,
+                    allocation_amount,
+                    root,
+                    program_id
+                )
+            }
+            #[cfg(feature = "compression")]
+            TokenWhitelistInstruction::VerifyCompressedMembership {leaf_index, allocation, root} => {
+                msg!("Instruction: VerifyCompressedMembership");
+                Self::process_verify_compressed_membership(
+                    accounts,
+                    leaf_index,
+                    allocation,
+                    root,
+                    program_id
+                )
+            }
+            // unpack refuses the tags of features left out of this build
+            #[cfg(not(all(feature = "merkle", feature = "pages", feature = "compression")))]
+            _ => Err(TokenWhitelistError::InvalidInstruction.into()),
         }
     }
 
     fn process_init_whitelist(
         accounts: &[AccountInfo],
         max_whitelist_size: u64,
-        _program_id: &Pubkey,
+        allocation_decimals: u8,
+        allocation_mint: Option<Pubkey>,
+        only_wallets: bool,
+        self_zero_allowed: bool,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
-        let whitelist_owner = next_account_info(account_info_iter)?;
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
         if !whitelist_owner.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let token_whitelist_account = next_account_info(account_info_iter)?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
 
-        let sysvar_rent_pubkey = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
-        if !sysvar_rent_pubkey.is_exempt(token_whitelist_account.lamports(), token_whitelist_account.data_len()) {
-            msg!("token whitelist account must be rent exempt");
-            return Err(TokenWhitelistError::NotRentExempt.into());
-        }
+        let sysvar_rent_account = expect_account(account_info_iter, "sysvar_rent_account")?;
+        Self::expect_sysvar(sysvar_rent_account, &sysvar::rent::id())?;
+        let sysvar_rent_pubkey = &Rent::from_account_info(sysvar_rent_account)?;
+        Self::check_rent_exempt(sysvar_rent_pubkey, token_whitelist_account, "token whitelist")?;
 
-        let mut token_whitelist_state = TokenWhitelist::unpack_from_slice(&token_whitelist_account.data.borrow())?;
-        if token_whitelist_state.is_initialized() {
-            msg!("token whitelist already initialized");
-            return Err(ProgramError::AccountAlreadyInitialized);
-        }
+        TokenWhitelist::check_max_whitelist_size(max_whitelist_size).inspect_err(|_| {
+            msg!("max whitelist size must be 1 to {} or unlimited (u64::MAX)", TokenWhitelist::MAX_ENTRIES)
+        })?;
 
-        token_whitelist_state.is_initialized = true;
-        token_whitelist_state.init_pubkey = *whitelist_owner.key;
-        token_whitelist_state.max_whitelist_size = max_whitelist_size;
+        let mut whitelist = WhitelistAccount::load_blank(token_whitelist_account, program_id)?;
+        whitelist.is_initialized = true;
+        whitelist.init_pubkey = *whitelist_owner.key;
+        whitelist.max_whitelist_size = max_whitelist_size;
+        whitelist.extension.allocation_decimals = allocation_decimals;
+        whitelist.extension.allocation_mint = allocation_mint;
+        whitelist.extension.only_wallets = only_wallets;
+        whitelist.extension.self_zero_disabled = !self_zero_allowed;
+        // the owner is the one signer init takes, so the creator until a separate payer is passed
+        whitelist.record_creation(Self::churn_slot(), *whitelist_owner.key);
+        whitelist.update_entries_hash();
+        whitelist.save()
+    }
 
-        token_whitelist_state.pack_into_slice(&mut token_whitelist_account.data.borrow_mut());
+    fn process_init_whitelist_with_units(
+        accounts: &[AccountInfo],
+        max_whitelist_size: u64,
+        allocation_decimals: Option<u8>,
+        only_wallets: bool,
+        self_zero_allowed: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (allocation_decimals, allocation_mint) = match accounts.get(3) {
+            Some(mint_info) => {
+                let mint = token::unpack_mint(mint_info)?;
+                if allocation_decimals.is_some_and(|decimals| decimals != mint.decimals) {
+                    msg!("allocation decimals must match the {} decimals of the mint", mint.decimals);
+                    return Err(TokenWhitelistError::AllocationDecimalsMismatch.into());
+                }
+                (mint.decimals, Some(*mint_info.key))
+            }
+            None => (allocation_decimals.unwrap_or(0), None),
+        };
 
-        Ok(())
+        Self::process_init_whitelist(
+            accounts,
+            max_whitelist_size,
+            allocation_decimals,
+            allocation_mint,
+            only_wallets,
+            self_zero_allowed,
+            program_id
+        )
     }
 
     fn process_add_whitelist(
         accounts: &[AccountInfo],
         allocation_amount: u64,
-        _program_id: &Pubkey,
+        round: u8,
+        campaign_id: [u8; 8],
+        expected_nonce: u64,
+        attestation: [u8; 32],
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
-        let whitelist_owner = next_account_info(account_info_iter)?;
-        if !whitelist_owner.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        let authority = expect_account(account_info_iter, "authority")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let account_to_add = expect_account(account_info_iter, "account_to_add")?;
 
-        let token_whitelist_account = next_account_info(account_info_iter)?;
-        let account_to_add = next_account_info(account_info_iter)?;
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        let config_account = Self::load_config(&mut whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let context = Self::authority_context(authority, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let add = Action::Add {
+            account: *account_to_add.key,
+            account_owner: *account_to_add.owner,
+            allocation_amount,
+            round,
+            campaign_id,
+            expected_nonce,
+            attestation,
+        };
+        let effects = Self::apply(&mut whitelist, &add, &context)?;
 
-        let mut token_whitelist_state = TokenWhitelist::unpack_from_slice(&token_whitelist_account.data.borrow())?;
-        if !token_whitelist_state.is_initialized() {
-            msg!("token whitelist needs to be initialized before attempting to add");
-            return Err(TokenWhitelistError::TokenWhitelistNotInit.into());
-        }
-        if whitelist_owner.key != &token_whitelist_state.init_pubkey {
-            msg!("signer must be whitelist owner");
-            msg!("{}", whitelist_owner.key);
-            msg!("{}", token_whitelist_state.init_pubkey);
-            return Err(TokenWhitelistError::TokenWhitelistNotOwner.into());
+        // the checks reading other accounts, the state is only written once they pass
+        Self::check_not_in_siblings(&whitelist, account_to_add.key, accounts, program_id)?;
+
+        match whitelist.extension.token_gate {
+            Some(token_gate) if token_gate.gate_owner_adds => {
+                token::check_token_gate(&accounts[3..], &token_gate, account_to_add.key)?;
+            }
+            _ => {}
         }
 
-        token_whitelist_state.add_keypair(&account_to_add.key.to_string(), &allocation_amount);
-        token_whitelist_state.pack_into_slice(&mut token_whitelist_account.data.borrow_mut());
+        // only the key of any other account is read, so wallets never funded can be whitelisted
+        if token::is_token_program(account_to_add.owner) {
+            token::check_token_account(
+                account_to_add,
+                account_info_iter.next().filter(|mint| Some(mint.key) != config_account.map(|config| config.key)),
+                whitelist.extension.strict_token_checks,
+            )?;
+        }
 
-        Ok(())
+        Self::commit(whitelist, effects)
     }
 
     fn process_remove_whitelist(
         accounts: &[AccountInfo],
-        _program_id: &Pubkey,
+        memo: Option<String>,
+        expected_nonce: u64,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
-        let whitelist_owner = next_account_info(account_info_iter)?;
-        if !whitelist_owner.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-
-        let token_whitelist_account = next_account_info(account_info_iter)?;
-        let account_to_remove = next_account_info(account_info_iter)?;
-
-        let mut token_whitelist_state = TokenWhitelist::unpack_from_slice(&token_whitelist_account.data.borrow())?;
-        if !token_whitelist_state.is_initialized() {
-            msg!("token whitelist needs to be initialized before attempting to remove");
-            return Err(TokenWhitelistError::TokenWhitelistNotInit.into());
-        }
-        if whitelist_owner.key != &token_whitelist_state.init_pubkey {
-            msg!("signer must be whitelist owner");
-            msg!("{}", whitelist_owner.key);
-            msg!("{}", token_whitelist_state.init_pubkey);
-            return Err(TokenWhitelistError::TokenWhitelistNotOwner.into());
-        }
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let account_to_remove = expect_account(account_info_iter, "account_to_remove")?;
 
-        token_whitelist_state.drop_key(&account_to_remove.key.to_string());
-        token_whitelist_state.pack_into_slice(&mut token_whitelist_account.data.borrow_mut());
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        let context = Self::authority_context(whitelist_owner, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let remove = Action::Remove {account: *account_to_remove.key, memo, expected_nonce};
+        let effects = Self::apply(&mut whitelist, &remove, &context)?;
 
-        Ok(())
+        Self::commit(whitelist, effects)
     }
 
     fn process_set_allocation_to_zero(
         accounts: &[AccountInfo],
-        _program_id: &Pubkey,
+        memo: Option<String>,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
-        let account_owner = next_account_info(account_info_iter)?;
+        let account_owner = expect_account(account_info_iter, "account_owner")?;
         if !account_owner.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let token_whitelist_account = next_account_info(account_info_iter)?;
-        let account_to_reset = next_account_info(account_info_iter)?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let account_to_reset = expect_account(account_info_iter, "account_to_reset")?;
 
-        let mut token_whitelist_state = TokenWhitelist::unpack_from_slice(&token_whitelist_account.data.borrow())?;
-        if !token_whitelist_state.is_initialized() {
-            msg!("token whitelist needs to be initialized before attempting to update");
-            return Err(TokenWhitelistError::TokenWhitelistNotInit.into());
-        }
-        if account_owner.key != account_to_reset.key {
-            msg!("signer must be the owner of the account");
-            msg!("{}", account_owner.key);
-            msg!("{}", account_to_reset.key);
-            return Err(TokenWhitelistError::NotOwner.into());
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        // the config policy only decides for the account itself, any other signer is refused
+        // without reading it
+        if account_owner.key == account_to_reset.key {
+            Self::load_config(&mut whitelist, token_whitelist_account.key, accounts, program_id)?;
         }
+        let context = Self::authority_context(account_owner, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let zero = Action::ZeroAllocation {account: *account_to_reset.key, memo};
+        let effects = Self::apply(&mut whitelist, &zero, &context)?;
 
-        let whitelist_amount: u64 = 0;
-        token_whitelist_state.add_keypair(&account_to_reset.key.to_string(), &whitelist_amount);
-        token_whitelist_state.pack_into_slice(&mut token_whitelist_account.data.borrow_mut());
-
-        Ok(())
+        Self::commit(whitelist, effects)
     }
 
     fn process_close_whitelist_account(
         accounts: &[AccountInfo],
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
-        let authority_account = next_account_info(account_info_iter)?;
-        let token_whitelist_account = next_account_info(account_info_iter)?;
-        let destination_account = next_account_info(account_info_iter)?;
+        let authority_account = expect_account(account_info_iter, "authority_account")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let destination_account = expect_account(account_info_iter, "destination_account")?;
 
-        let token_whitelist_state = TokenWhitelist::unpack_from_slice(&token_whitelist_account.data.borrow())?;
-        if !token_whitelist_state.is_initialized() {
-            msg!("token whitelist needs to be initialized before attempting to close");
-            return Err(TokenWhitelistError::TokenWhitelistNotInit.into());
-        }
+        let whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
 
-        Self::check_authority(authority_account, &token_whitelist_state.init_pubkey)?;
+        Self::check_authority(authority_account, &whitelist.init_pubkey)?;
 
-        let destination_starting_lamports = destination_account.lamports();
-        let account_lamports = token_whitelist_account.lamports();
-        **token_whitelist_account.lamports.borrow_mut() = 0;
-        **destination_account.lamports.borrow_mut() = destination_starting_lamports
-            .checked_add(account_lamports)
-            .ok_or(TokenWhitelistError::Overflow)?;
+        // the lamports all go to the destination, deposits included, so they are refunded first
+        if !whitelist.extension.registration_deposits.is_empty() {
+            msg!("registration deposits need to be refunded before attempting to close");
+            return Err(TokenWhitelistError::DepositsOutstanding.into());
+        }
+
+        // never saved, the data is zeroed before the lamports leave so that no later
+        // instruction of the transaction decodes entries of a closed whitelist
+        let closed = whitelist.closed_totals();
+        let account_lamports = whitelist.close(destination_account)?;
+        WhitelistEvent::WhitelistClosed {
+            whitelist: *token_whitelist_account.key,
+            destination: *destination_account.key,
+            lamports: account_lamports,
+            entries_discarded: closed.entries_discarded,
+            allocation_discarded: closed.allocation_discarded,
+        }.emit();
+        set_return_data(&closed.try_to_vec().unwrap());
 
         Ok(())
     }
 
+    fn process_archive_whitelist(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = expect_account(account_info_iter, "authority_account")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let destination_account = expect_account(account_info_iter, "destination_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        Self::check_authority(authority_account, &token_whitelist_state.init_pubkey)?;
+
+        // an archive accepts no RefundDeposit, so deposits would be kept from their registrants
+        if !token_whitelist_state.extension.registration_deposits.is_empty() {
+            msg!("registration deposits need to be refunded before attempting to archive");
+            return Err(TokenWhitelistError::DepositsOutstanding.into());
+        }
+
+        let archive = WhitelistArchive {
+            init_pubkey: token_whitelist_state.init_pubkey,
+            entries_hash: hash_entries_map(&token_whitelist_state.whitelist_map),
+            archived_slot: Clock::get()?.slot,
+        };
+        let archive_lamports = Rent::get()?.minimum_balance(ARCHIVE_SPACE);
+        let freed_lamports = token_whitelist_account.lamports()
+            .checked_sub(archive_lamports)
+            .ok_or(TokenWhitelistError::NotRentExempt)?;
+
+        token_whitelist_account.realloc(ARCHIVE_SPACE, false)?;
+        archive.pack_into_slice(&mut token_whitelist_account.data.borrow_mut())?;
+
+        transfer_lamports(token_whitelist_account, destination_account, freed_lamports)?;
+
+        Ok(())
+    }
+
+    fn process_approve_delegate(
+        accounts: &[AccountInfo],
+        max_adds: u32,
+        max_allocation_per_add: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let delegate_account = expect_account(account_info_iter, "delegate_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let approve = Action::ApproveDelegate(Delegate {
+            delegate_pubkey: *delegate_account.key,
+            remaining_adds: max_adds,
+            max_allocation_per_add,
+        });
+        Self::apply(&mut token_whitelist_state, &approve, &context)?;
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_revoke_delegate(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::RevokeDelegate, &context)?;
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_configure_registration(
+        accounts: &[AccountInfo],
+        registration: Registration,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::ConfigureRegistration(registration), &context)?;
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_registration_rate_limit(
+        accounts: &[AccountInfo],
+        registration_rate_limit: RegistrationRateLimit,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        // arguments no whitelist accepts are refused whoever signs
+        if registration_rate_limit.max_registrations_per_window > 0 && registration_rate_limit.window_slots == 0 {
+            msg!("a registration rate limit needs a window of at least one slot");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Self::apply(&mut token_whitelist_state, &Action::SetRegistrationRateLimit(registration_rate_limit), &context)?;
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_enumeration_restricted(
+        accounts: &[AccountInfo],
+        restrict_enumeration: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let restrict = Action::SetEnumerationRestricted(restrict_enumeration);
+        Self::apply(&mut token_whitelist_state, &restrict, &context)?;
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_register_self(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let account_to_register = expect_account(account_info_iter, "account_to_register")?;
+        if !account_to_register.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let system_program = expect_account(account_info_iter, "system_program")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+
+        let slot = Clock::get()?.slot;
+        let context = Self::authority_context(
+            account_to_register,
+            &token_whitelist_state,
+            token_whitelist_account.key,
+            accounts,
+            program_id,
+        )?;
+        let effects = Self::apply(&mut token_whitelist_state, &Action::RegisterSelf {slot}, &context)?;
+        if let Some(token_gate) = token_whitelist_state.extension.token_gate {
+            token::check_token_gate(accounts, &token_gate, account_to_register.key)?;
+        }
+
+        if effects.deposit_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    account_to_register.key,
+                    token_whitelist_account.key,
+                    effects.deposit_lamports,
+                ),
+                &[
+                    account_to_register.clone(),
+                    token_whitelist_account.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+
+        if effects.entries_changed {
+            token_whitelist_state.update_entries_hash(token_whitelist_account.data_len());
+        }
+        if effects.churn == Some(Churn::Add) {
+            token_whitelist_state.record_add(token_whitelist_account.data_len(), slot);
+        }
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Self::emit_stats(token_whitelist_account, token_whitelist_state.extension.stats);
+
+        Ok(())
+    }
+
+    fn process_refund_deposit(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = expect_account(account_info_iter, "authority_account")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let registered_account = expect_account(account_info_iter, "registered_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        if authority_account.key == registered_account.key {
+            if !authority_account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if let Some(registration) = token_whitelist_state.extension.registration {
+                if registration.is_open(Clock::get()?.slot) {
+                    msg!("deposit can only be refunded to the account after registration closes");
+                    return Err(TokenWhitelistError::RegistrationOpen.into());
+                }
+            }
+        } else {
+            Self::check_authority(authority_account, &token_whitelist_state.init_pubkey)?;
+        }
+
+        let deposit_lamports = token_whitelist_state.extension.registration_deposits
+            .remove(&registered_account.key.to_string())
+            .ok_or(TokenWhitelistError::NoDepositToRefund)?;
+
+        let whitelist_remaining_lamports = token_whitelist_account.lamports()
+            .checked_sub(deposit_lamports)
+            .ok_or(TokenWhitelistError::Overflow)?;
+        if !Rent::get()?.is_exempt(whitelist_remaining_lamports, token_whitelist_account.data_len()) {
+            msg!("refund would leave token whitelist account below rent exemption");
+            return Err(TokenWhitelistError::NotRentExempt.into());
+        }
+
+        transfer_lamports(token_whitelist_account, registered_account, deposit_lamports)?;
+
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_run_lottery(
+        accounts: &[AccountInfo],
+        winners: u64,
+        seed: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let slot_hashes_account = expect_account(account_info_iter, "slot_hashes_account")?;
+
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        let context = Self::authority_context(whitelist_owner, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let (slot, slot_hash) = Self::most_recent_slot_hash(slot_hashes_account)?;
+        let lottery = Action::RunLottery(Lottery {seed, slot, slot_hash, winners});
+        let effects = Self::apply(&mut whitelist, &lottery, &context)?;
+
+        Self::commit(whitelist, effects)
+    }
+
+    fn process_set_consume_limits(
+        accounts: &[AccountInfo],
+        consume_limits: ConsumeLimits,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetConsumeLimits(consume_limits), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_allocation_bounds(
+        accounts: &[AccountInfo],
+        allocation_bounds: AllocationBounds,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        // arguments no whitelist accepts are refused whoever signs
+        if !allocation_bounds.is_valid() {
+            msg!("minimum allocation must not exceed the maximum allocation");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetAllocationBounds(allocation_bounds), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_strict_token_checks(
+        accounts: &[AccountInfo],
+        strict_token_checks: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetStrictTokenChecks(strict_token_checks), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_only_wallets(
+        accounts: &[AccountInfo],
+        only_wallets: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetOnlyWallets(only_wallets), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_self_zero_allowed(
+        accounts: &[AccountInfo],
+        self_zero_allowed: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetSelfZeroAllowed(self_zero_allowed), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_zero_allocation_allowed(
+        accounts: &[AccountInfo],
+        allow_zero_allocation: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetZeroAllocationAllowed(allow_zero_allocation), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_attestation(
+        accounts: &[AccountInfo],
+        attestation: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority = expect_account(account_info_iter, "authority")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let whitelisted_account = expect_account(account_info_iter, "whitelisted_account")?;
+
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        Self::load_config(&mut whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let context = Self::authority_context(authority, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let set = Action::SetAttestation {account: *whitelisted_account.key, attestation};
+        let effects = Self::apply(&mut whitelist, &set, &context)?;
+
+        Self::commit(whitelist, effects)
+    }
+
+    fn process_set_attestation_required(
+        accounts: &[AccountInfo],
+        require_attestation: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetAttestationRequired(require_attestation), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_entry_note_commitment(
+        accounts: &[AccountInfo],
+        commitment: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let whitelisted_account = expect_account(account_info_iter, "whitelisted_account")?;
+
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        let context = Self::authority_context(whitelist_owner, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let set = Action::SetEntryNoteCommitment {account: *whitelisted_account.key, commitment};
+        let effects = Self::apply(&mut whitelist, &set, &context)?;
+
+        Self::commit(whitelist, effects)
+    }
+
+    fn process_reveal_entry_note(
+        accounts: &[AccountInfo],
+        note: Vec<u8>,
+        salt: [u8; 16],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let whitelisted_account = expect_account(account_info_iter, "whitelisted_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        rules::check_note_reveal(&token_whitelist_state, whitelisted_account.key, &note, &salt)?;
+
+        WhitelistEvent::EntryNoteRevealed {
+            whitelist: *token_whitelist_account.key,
+            account: *whitelisted_account.key,
+            note,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    fn process_rotate_whitelisted_key(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let current_account = expect_account(account_info_iter, "current_account")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let new_account = expect_account(account_info_iter, "new_account")?;
+        if !current_account.is_signer || !new_account.is_signer {
+            msg!("both the whitelisted account and the account taking over its entry must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        Self::load_config(&mut whitelist, token_whitelist_account.key, accounts, program_id)?;
+        rules::check_rotate_key(&whitelist, current_account.key, new_account.key)?;
+        // the new account joins the whitelist, so it passes the checks of an add
+        rules::check_target_identity(&whitelist, token_whitelist_account.key, program_id, new_account.key)?;
+        rules::check_whitelist_target(&whitelist, new_account.key, new_account.owner)?;
+        Self::check_not_in_siblings(&whitelist, new_account.key, accounts, program_id)?;
+        // and, joining without the owner like RegisterSelf, holds the gating mint whenever gated
+        if let Some(token_gate) = whitelist.extension.token_gate {
+            token::check_token_gate(&accounts[3..], &token_gate, new_account.key)?;
+        }
+
+        let (key, new_key) = (current_account.key.to_string(), new_account.key.to_string());
+        let allocation = whitelist.whitelist_map[&key];
+        whitelist.rotate_key(&key, &new_key)?;
+        whitelist.update_entries_hash();
+        whitelist.save()?;
+
+        WhitelistEvent::KeyRotated {
+            whitelist: *token_whitelist_account.key,
+            previous_account: *current_account.key,
+            account: *new_account.key,
+            allocation,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    fn process_assert_not_in_other(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+        let context = Self::authority_context(whitelist_owner, &whitelist, token_whitelist_account.key, accounts, program_id)?;
+        let sibling_accounts = account_info_iter.as_slice();
+        let link = Action::SetSiblingWhitelists(sibling_accounts.iter().map(|account| *account.key).collect());
+        let effects = Self::apply(&mut whitelist, &link, &context)?;
+        // adds read every sibling as a whitelist of this program, nothing else can be linked
+        for sibling_account in sibling_accounts {
+            Self::program_whitelist(sibling_account, program_id)?;
+        }
+
+        Self::commit(whitelist, effects)
+    }
+
+    fn process_set_owner_entry_allowed(
+        accounts: &[AccountInfo],
+        allow_owner_entry: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetOwnerEntryAllowed(allow_owner_entry), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_set_token_gate(
+        accounts: &[AccountInfo],
+        gating_mint: Pubkey,
+        gating_minimum: u64,
+        gate_owner_adds: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let token_gate = (gating_minimum > 0).then_some(TokenGate {gating_mint, gating_minimum, gate_owner_adds});
+        Self::apply(&mut token_whitelist_state, &Action::SetTokenGate(token_gate), &context)?;
+        if token_gate.is_some() {
+            let mint_info = expect_account(account_info_iter, "mint_info")?;
+            if *mint_info.key != gating_mint {
+                msg!("gating mint {} was expected, got {}", gating_mint, mint_info.key);
+                return Err(TokenWhitelistError::InvalidMint.into());
+            }
+            token::unpack_mint(mint_info)?;
+        }
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_init_config(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let config_account = expect_account(account_info_iter, "config_account")?;
+        let system_program = expect_account(account_info_iter, "system_program")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_authority(whitelist_owner, &token_whitelist_state.init_pubkey)?;
+
+        if token_whitelist_state.extension.config_bump.is_some() {
+            msg!("whitelist {} already has a config account", token_whitelist_account.key);
+            return Err(TokenWhitelistError::ConfigAlreadyInitialized.into());
+        }
+        let (config_address, bump) = WhitelistConfig::find_address(program_id, token_whitelist_account.key);
+        if *config_account.key != config_address {
+            msg!("config account must be {}", config_address);
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // allocate and assign rather than create_account, which fails on an address someone funded first
+        let shortfall = Rent::get()?.minimum_balance(CONFIG_SPACE).saturating_sub(config_account.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(whitelist_owner.key, config_account.key, shortfall),
+                &[whitelist_owner.clone(), config_account.clone(), system_program.clone()],
+            )?;
+        }
+        let seeds: &[&[u8]] = &[CONFIG_SEED, token_whitelist_account.key.as_ref(), &[bump]];
+        invoke_signed(
+            &system_instruction::allocate(config_account.key, CONFIG_SPACE as u64),
+            &[config_account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(config_account.key, program_id),
+            &[config_account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+
+        let config = WhitelistConfig {
+            whitelist: *token_whitelist_account.key,
+            policy: WhitelistPolicy::of(&token_whitelist_state.extension),
+        };
+        config.pack_into_slice(&mut config_account.data.borrow_mut())?;
+
+        token_whitelist_state.extension.config_bump = Some(bump);
+        token_whitelist_state.extension.linked_accounts.config = Some(config_address);
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    fn process_top_up_rent(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let funder = expect_account(account_info_iter, "funder")?;
+        if !funder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let system_program = expect_account(account_info_iter, "system_program")?;
+
+        // the state is left alone, an archived or grown whitelist is topped up all the same
+        if token_whitelist_account.owner != program_id {
+            msg!("whitelist {} is not owned by the token whitelist program", token_whitelist_account.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let shortfall = Rent::get()?
+            .minimum_balance(token_whitelist_account.data_len())
+            .saturating_sub(token_whitelist_account.lamports());
+        if shortfall == 0 {
+            return Ok(());
+        }
+        msg!("topping up {} lamports of rent", shortfall);
+
+        invoke(
+            &system_instruction::transfer(funder.key, token_whitelist_account.key, shortfall),
+            &[
+                funder.clone(),
+                token_whitelist_account.clone(),
+                system_program.clone(),
+            ],
+        )
+    }
+
+    #[cfg(feature = "merkle")]
+    fn process_set_merkle_root(
+        accounts: &[AccountInfo],
+        root: [u8; 32],
+        hash_kind: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetMerkleRoot(MerkleRoot {root, hash_kind}), &context)?;
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "merkle")]
+    fn process_verify_merkle_membership(
+        accounts: &[AccountInfo],
+        leaf_index: u32,
+        allocation: u64,
+        proof: &[[u8; 32]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let wallet = expect_account(account_info_iter, "wallet")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        rules::check_merkle_membership(&token_whitelist_state, leaf_index, wallet.key, allocation, proof)
+            .map_err(|error| {
+                msg!("{} with allocation {} is not proven by the merkle root", wallet.key, allocation);
+                error.into()
+            })
+    }
+
+    #[cfg(feature = "merkle")]
+    fn process_verify_merkle_membership_batch(
+        accounts: &[AccountInfo],
+        items: &[MerkleClaim],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let claim_bitmap_account = expect_account(account_info_iter, "claim_bitmap_account")?;
+        let wallet_accounts = account_info_iter.as_slice();
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_claim_bitmap(claim_bitmap_account, token_whitelist_account.key, &token_whitelist_state, program_id)?;
+
+        let mut bitmap_data = claim_bitmap_account.data.borrow_mut();
+        for item in items {
+            if !wallet_accounts.iter().any(|account| account.key == &item.wallet && account.is_signer) {
+                msg!("{} must sign its merkle claim", item.wallet);
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            rules::check_merkle_membership(&token_whitelist_state, item.leaf_index, &item.wallet, item.allocation, &item.proof)
+                .map_err(|error| {
+                    msg!("merkle claim of {} with allocation {} refused", item.wallet, item.allocation);
+                    ProgramError::from(error)
+                })?;
+            // set before the next item is checked, so a leaf repeated in the batch fails
+            ClaimBitmap::set_claimed(&mut bitmap_data, item.leaf_index)
+                .inspect_err(|_| msg!("leaf {} of {} was claimed before", item.leaf_index, item.wallet))?;
+        }
+
+        for item in items {
+            WhitelistEvent::MerkleClaimVerified {
+                whitelist: *token_whitelist_account.key,
+                wallet: item.wallet,
+                leaf_index: item.leaf_index,
+                allocation: item.allocation,
+            }
+            .emit();
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "merkle")]
+    fn process_init_claim_bitmap(
+        accounts: &[AccountInfo],
+        leaf_count: u32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let claim_bitmap_account = expect_account(account_info_iter, "claim_bitmap_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_authority(whitelist_owner, &token_whitelist_state.init_pubkey)?;
+
+        let merkle_root = match token_whitelist_state.extension.merkle_root {
+            Some(merkle_root) => merkle_root,
+            None => {
+                msg!("token whitelist needs a merkle root before attempting to init a claim bitmap");
+                return Err(ProgramError::InvalidArgument);
+            }
+        };
+        if claim_bitmap_account.owner != program_id {
+            msg!("claim bitmap {} is not owned by the token whitelist program", claim_bitmap_account.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if claim_bitmap_account.data_len() < claim_bitmap_len(leaf_count) {
+            msg!("claim bitmap of {} leaves needs {} bytes", leaf_count, claim_bitmap_len(leaf_count));
+            return Err(TokenWhitelistError::AccountTooSmall.into());
+        }
+        Self::check_rent_exempt(&Rent::get()?, claim_bitmap_account, "claim bitmap")?;
+        // also refuses a whitelist, archive or bitmap already written
+        if claim_bitmap_account.data.borrow().iter().any(|byte| *byte != 0) {
+            msg!("claim bitmap account must be zeroed before initialization");
+            return Err(TokenWhitelistError::AccountNotBlank.into());
+        }
+
+        let claim_bitmap = ClaimBitmap {
+            whitelist: *token_whitelist_account.key,
+            root: merkle_root.root,
+            leaf_count,
+        };
+        claim_bitmap.pack_into_slice(&mut claim_bitmap_account.data.borrow_mut())?;
+
+        // replaces the link to a bitmap of an earlier root
+        token_whitelist_state.extension.linked_accounts.claim_bitmap = Some(*claim_bitmap_account.key);
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    /// Checks that the claim bitmap is the one linked to the whitelist and was initialized for its
+    /// current root. Bitmaps initialized before links were stored have none and are checked on
+    /// their data alone.
+    #[cfg(feature = "merkle")]
+    fn check_claim_bitmap(
+        claim_bitmap_account: &AccountInfo,
+        whitelist: &Pubkey,
+        token_whitelist_state: &TokenWhitelist,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        match token_whitelist_state.extension.linked_accounts.claim_bitmap {
+            Some(linked) if *claim_bitmap_account.key != linked => {
+                msg!("claim bitmap must be {}, the one linked to {}", linked, whitelist);
+                return Err(TokenWhitelistError::UnlinkedCompanionAccount.into());
+            }
+            _ => {}
+        }
+        if claim_bitmap_account.owner != program_id {
+            msg!("claim bitmap {} is not owned by the token whitelist program", claim_bitmap_account.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let claim_bitmap = ClaimBitmap::unpack_from_slice(&claim_bitmap_account.data.borrow())?;
+        let root = token_whitelist_state.extension.merkle_root.map(|merkle_root| merkle_root.root);
+        if claim_bitmap.whitelist != *whitelist || Some(claim_bitmap.root) != root {
+            msg!("claim bitmap {} does not track the current merkle root of {}", claim_bitmap_account.key, whitelist);
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    fn process_set_active_round(
+        accounts: &[AccountInfo],
+        round: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        let context =
+            Self::authority_context(whitelist_owner, &token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        let config_account =
+            Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+        Self::apply(&mut token_whitelist_state, &Action::SetActiveRound(round), &context)?;
+        Self::store_policy(&mut token_whitelist_state, token_whitelist_account, config_account)?;
+
+        Ok(())
+    }
+
+    fn process_get_allocation(
+        accounts: &[AccountInfo],
+        round: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let whitelisted_account = expect_account(account_info_iter, "whitelisted_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+
+        let allocation_amount = rules::allocation(&token_whitelist_state, whitelisted_account.key, round)?;
+        set_return_data(&allocation_amount.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn process_effective_allocation(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let whitelisted_account = expect_account(account_info_iter, "whitelisted_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+
+        let slot = Clock::get()?.slot;
+        let allocation_amount = rules::effective_allocation(&token_whitelist_state, whitelisted_account.key, slot)?;
+        set_return_data(&allocation_amount.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn process_get_whitelist_stats(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        set_return_data(&token_whitelist_state.stats_report().try_to_vec().unwrap());
+
+        Ok(())
+    }
+
+    fn process_assert_membership_across(
+        accounts: &[AccountInfo],
+        require_in_first: bool,
+        require_absent_in_second: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let first_whitelist_account = expect_account(account_info_iter, "first_whitelist_account")?;
+        let second_whitelist_account = expect_account(account_info_iter, "second_whitelist_account")?;
+        let subject_account = expect_account(account_info_iter, "subject_account")?;
+
+        let first_whitelist_state = Self::program_whitelist(first_whitelist_account, program_id)?;
+        let second_whitelist_state = Self::program_whitelist(second_whitelist_account, program_id)?;
+
+        rules::check_membership_across(
+            &first_whitelist_state,
+            &second_whitelist_state,
+            subject_account.key,
+            require_in_first,
+            require_absent_in_second,
+        )
+        .map_err(|error| {
+            msg!(
+                "{} fails the membership check across {} and {}",
+                subject_account.key,
+                first_whitelist_account.key,
+                second_whitelist_account.key
+            );
+            error.into()
+        })
+    }
+
+    fn process_assert_owner(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let expected_owner = expect_account(account_info_iter, "expected_owner")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        rules::check_owner(&token_whitelist_state, expected_owner.key).map_err(|error| {
+            msg!("whitelist {} is not owned by {}", token_whitelist_account.key, expected_owner.key);
+            error.into()
+        })
+    }
+
+    fn process_inspect_state(
+        accounts: &[AccountInfo],
+        max_entries_to_log: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_enumeration(&token_whitelist_state, account_info_iter.next())?;
+        let max_size = token_whitelist_state.entry_limit().map_or("unlimited".to_string(), |limit| limit.to_string());
+        msg!(
+            "whitelist {}: owner {}, max size {}",
+            token_whitelist_account.key,
+            token_whitelist_state.init_pubkey,
+            max_size
+        );
+        msg!(
+            "entries {}, map bytes free {}, active round {}, allocation decimals {}",
+            token_whitelist_state.whitelist_map.len(),
+            token_whitelist_state.map_bytes_remaining(),
+            token_whitelist_state.extension.active_round,
+            token_whitelist_state.extension.allocation_decimals
+        );
+
+        // one buffer for every line, the bump allocator never frees a line formatted per entry
+        let logged = (max_entries_to_log as usize).min(MAX_INSPECT_ENTRIES);
+        let mut line = String::with_capacity(64);
+        for (key, amount) in token_whitelist_state.whitelist_map.iter().take(logged) {
+            line.clear();
+            write!(line, "{}={}", key, amount).unwrap();
+            sol_log(&line);
+        }
+        let not_logged = token_whitelist_state.whitelist_map.len().saturating_sub(logged);
+        if not_logged > 0 {
+            msg!("{} more entries not logged", not_logged);
+        }
+
+        Ok(())
+    }
+
+    fn process_repair_state(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        // the owner key is read from data that may be damaged, so only this program's accounts
+        if token_whitelist_account.owner != program_id {
+            msg!("whitelist {} is not owned by the token whitelist program", token_whitelist_account.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (mut repaired_state, report) = TokenWhitelist::recover_from_slice(&token_whitelist_account.data.borrow())?;
+
+        Self::check_authority(whitelist_owner, &repaired_state.init_pubkey)?;
+
+        set_return_data(&report.try_to_vec().unwrap());
+        let healthy = !report.dropped_anything()
+            && TokenWhitelist::unpack_from_slice(&token_whitelist_account.data.borrow())
+                .is_ok_and(|state| state == repaired_state);
+        if healthy {
+            msg!("token whitelist decodes, nothing to repair");
+            return Ok(());
+        }
+
+        Self::store_whitelist(&mut repaired_state, token_whitelist_account)?;
+        msg!(
+            "token whitelist repaired: {} entries recovered, {} dropped",
+            report.entries_recovered,
+            report.entries_dropped
+        );
+        WhitelistEvent::StateRepaired {
+            whitelist: *token_whitelist_account.key,
+            entries_recovered: report.entries_recovered,
+            entries_dropped: report.entries_dropped,
+            bytes_dropped: report.bytes_dropped,
+        }.emit();
+
+        Ok(())
+    }
+
+    fn process_migrate_state_layout(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority = expect_account(account_info_iter, "authority")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let program_data = expect_account(account_info_iter, "program_data")?;
+
+        let mut whitelist = WhitelistAccount::load_mut(token_whitelist_account, program_id)?;
+
+        let upgrade_authority = upgrade_authority(program_data, program_id)?;
+        Self::check_authority_with(authority, |key| match upgrade_authority {
+            Some(expected) if expected == *key => Ok(()),
+            Some(expected) => {
+                msg!("{} is not the upgrade authority {}", key, expected);
+                Err(TokenWhitelistError::UpgradeAuthorityMismatch)
+            }
+            None => {
+                msg!("program is immutable, it has no upgrade authority");
+                Err(TokenWhitelistError::UpgradeAuthorityMismatch)
+            }
+        })?;
+
+        let entries_hash = whitelist.extension.entries_hash;
+        whitelist.update_entries_hash();
+        let current = whitelist.extension.entries_hash == entries_hash
+            && whitelist.extension_is_current(&token_whitelist_account.data.borrow());
+        if current {
+            msg!("token whitelist is in the current layout, nothing to migrate");
+            return Ok(());
+        }
+
+        whitelist.save()?;
+        msg!("token whitelist migrated to the current layout");
+
+        Ok(())
+    }
+
+    /// Reads the policy of a whitelist with a config account into its state, so the rules see the
+    /// config rather than the copy in the whitelist. Returns the config account, None for a
+    /// whitelist without one. It is found among `accounts` by its address, so it can follow
+    /// whatever optional accounts an instruction takes.
+    fn load_config<'a, 'b>(
+        state: &mut TokenWhitelist,
+        whitelist: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        program_id: &Pubkey,
+    ) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+        let bump = match state.extension.config_bump {
+            Some(bump) => bump,
+            None => return Ok(None),
+        };
+        let config_address = match state.extension.linked_accounts.config {
+            Some(config_address) => config_address,
+            // config accounts initialized before links were stored
+            None => WhitelistConfig::address(program_id, whitelist, bump)?,
+        };
+        let config_account = match accounts.iter().find(|account| *account.key == config_address) {
+            Some(config_account) => config_account,
+            None => {
+                msg!("config account {} of the whitelist is missing", config_address);
+                return Err(TokenWhitelistError::ConfigAccountMissing.into());
+            }
+        };
+        if config_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let config = WhitelistConfig::unpack_from_slice(&config_account.data.borrow())?;
+        if config.whitelist != *whitelist {
+            msg!("config account {} belongs to whitelist {}", config_account.key, config.whitelist);
+            return Err(TokenWhitelistError::UnlinkedCompanionAccount.into());
+        }
+        config.policy.apply(&mut state.extension);
+        Ok(Some(config_account))
+    }
+
+    /// Refuses an add of `account` holding an entry in a sibling whitelist linked by
+    /// AssertNotInOther. Like the config account, each sibling is found among `accounts` by its
+    /// address, and one missing fails the add rather than skipping its check.
+    fn check_not_in_siblings(
+        state: &TokenWhitelist,
+        account: &Pubkey,
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        for sibling in &state.extension.sibling_whitelists {
+            let sibling_account = match accounts.iter().find(|info| info.key == sibling) {
+                Some(sibling_account) => sibling_account,
+                None => {
+                    msg!("sibling whitelist {} is missing", sibling);
+                    return Err(TokenWhitelistError::SiblingWhitelistMissing.into());
+                }
+            };
+            let sibling_state = Self::program_whitelist(sibling_account, program_id)?;
+            rules::check_not_in_sibling(&sibling_state, account)
+                .inspect_err(|_| msg!("{} is already whitelisted in sibling whitelist {}", account, sibling))?;
+        }
+        Ok(())
+    }
+
+    /// Writes a policy change to the config account, or to the whitelist when it has none
+    fn store_policy(
+        state: &mut TokenWhitelist,
+        token_whitelist_account: &AccountInfo,
+        config_account: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        match config_account {
+            Some(config_account) => {
+                let config = WhitelistConfig {
+                    whitelist: *token_whitelist_account.key,
+                    policy: WhitelistPolicy::of(&state.extension),
+                };
+                config.pack_into_slice(&mut config_account.data.borrow_mut())
+            }
+            None => Self::store_whitelist(state, token_whitelist_account),
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn process_init_compressed_whitelist(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let tree_account = expect_account(account_info_iter, "tree_account")?;
+        let tree_authority = expect_account(account_info_iter, "tree_authority")?;
+        let compression_program = expect_account(account_info_iter, "compression_program")?;
+        let noop_program = expect_account(account_info_iter, "noop_program")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_authority(whitelist_owner, &token_whitelist_state.init_pubkey)?;
+
+        if token_whitelist_state.extension.compressed_tree.is_some() {
+            msg!("whitelist {} already has a compressed tree", token_whitelist_account.key);
+            return Err(TokenWhitelistError::CompressedTreeAlreadyInitialized.into());
+        }
+        Self::check_compression_programs(compression_program, noop_program)?;
+        let (authority_address, authority_bump) =
+            compression::find_tree_authority(program_id, token_whitelist_account.key);
+        if *tree_authority.key != authority_address {
+            msg!("tree authority must be {}", authority_address);
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // the compression program refuses a tree it does not own or one initialized before
+        Self::invoke_as_tree_authority(
+            &compression::init_empty_merkle_tree(tree_account.key, tree_authority.key),
+            &[tree_account.clone(), tree_authority.clone(), noop_program.clone(), compression_program.clone()],
+            token_whitelist_account.key,
+            authority_bump,
+        )?;
+
+        token_whitelist_state.extension.compressed_tree = Some(CompressedTree {
+            tree: *tree_account.key,
+            authority_bump,
+            leaf_count: 0,
+        });
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    fn process_append_compressed_entry(
+        accounts: &[AccountInfo],
+        allocation_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let account_to_add = expect_account(account_info_iter, "account_to_add")?;
+        let tree_account = expect_account(account_info_iter, "tree_account")?;
+        let tree_authority = expect_account(account_info_iter, "tree_authority")?;
+        let compression_program = expect_account(account_info_iter, "compression_program")?;
+        let noop_program = expect_account(account_info_iter, "noop_program")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_authority(whitelist_owner, &token_whitelist_state.init_pubkey)?;
+        let mut compressed_tree = rules::check_compressed_append(&token_whitelist_state, tree_account.key, allocation_amount)
+            .inspect_err(|error| Self::log_compressed_tree_error(*error, tree_account.key))?;
+        rules::check_target_identity(&token_whitelist_state, token_whitelist_account.key, program_id, account_to_add.key)
+            .inspect_err(|_| msg!("{} is the whitelist, the program or an owner not taking entries", account_to_add.key))?;
+        rules::check_whitelist_target(&token_whitelist_state, account_to_add.key, account_to_add.owner)
+            .inspect_err(|_| {
+                msg!("only wallets may be added, {} is owned by {} or off the curve", account_to_add.key, account_to_add.owner);
+            })?;
+        Self::check_compression_programs(compression_program, noop_program)?;
+        Self::check_tree_authority(tree_authority, token_whitelist_account.key, compressed_tree.authority_bump, program_id)?;
+
+        let leaf_index = compressed_tree.leaf_count;
+        let leaf = compression::leaf(leaf_index, account_to_add.key, allocation_amount);
+        Self::invoke_as_tree_authority(
+            &compression::append(tree_account.key, tree_authority.key, &leaf),
+            &[tree_account.clone(), tree_authority.clone(), noop_program.clone(), compression_program.clone()],
+            token_whitelist_account.key,
+            compressed_tree.authority_bump,
+        )?;
+
+        compressed_tree.leaf_count += 1;
+        token_whitelist_state.extension.compressed_tree = Some(compressed_tree);
+        token_whitelist_state.record_add(token_whitelist_account.data_len(), Self::churn_slot());
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        WhitelistEvent::CompressedEntryAppended {
+            whitelist: *token_whitelist_account.key,
+            wallet: *account_to_add.key,
+            leaf_index,
+            allocation: allocation_amount,
+        }
+        .emit();
+        Self::emit_stats(token_whitelist_account, token_whitelist_state.extension.stats);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    fn process_replace_compressed_entry(
+        accounts: &[AccountInfo],
+        leaf_index: u32,
+        previous_allocation: u64,
+        allocation_amount: u64,
+        root: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let whitelist_owner = expect_account(account_info_iter, "whitelist_owner")?;
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let account = expect_account(account_info_iter, "account")?;
+        let tree_account = expect_account(account_info_iter, "tree_account")?;
+        let tree_authority = expect_account(account_info_iter, "tree_authority")?;
+        let compression_program = expect_account(account_info_iter, "compression_program")?;
+        let noop_program = expect_account(account_info_iter, "noop_program")?;
+        let proof_accounts = account_info_iter.as_slice();
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_authority(whitelist_owner, &token_whitelist_state.init_pubkey)?;
+        let compressed_tree =
+            rules::check_compressed_replace(&token_whitelist_state, tree_account.key, leaf_index, allocation_amount)
+                .inspect_err(|error| Self::log_compressed_tree_error(*error, tree_account.key))?;
+        Self::check_compression_programs(compression_program, noop_program)?;
+        Self::check_tree_authority(tree_authority, token_whitelist_account.key, compressed_tree.authority_bump, program_id)?;
+
+        let previous_leaf = compression::leaf(leaf_index, account.key, previous_allocation);
+        let new_leaf = compression::leaf(leaf_index, account.key, allocation_amount);
+        let proof: Vec<[u8; 32]> = proof_accounts.iter().map(|node| node.key.to_bytes()).collect();
+        let mut account_infos = vec![
+            tree_account.clone(),
+            tree_authority.clone(),
+            noop_program.clone(),
+            compression_program.clone(),
+        ];
+        account_infos.extend(proof_accounts.iter().cloned());
+        Self::invoke_as_tree_authority(
+            &compression::replace_leaf(tree_account.key, tree_authority.key, &root, &previous_leaf, &new_leaf, leaf_index, &proof),
+            &account_infos,
+            token_whitelist_account.key,
+            compressed_tree.authority_bump,
+        )
+        .inspect_err(|_| {
+            msg!("leaf {} of {} with allocation {} is not proven by the compressed tree", leaf_index, account.key, previous_allocation);
+        })?;
+
+        // nothing of the state changed, the write counts the replacement in mutation_nonce
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        WhitelistEvent::CompressedEntryReplaced {
+            whitelist: *token_whitelist_account.key,
+            wallet: *account.key,
+            leaf_index,
+            previous_allocation,
+            allocation: allocation_amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    fn process_verify_compressed_membership(
+        accounts: &[AccountInfo],
+        leaf_index: u32,
+        allocation: u64,
+        root: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+        let wallet = expect_account(account_info_iter, "wallet")?;
+        let tree_account = expect_account(account_info_iter, "tree_account")?;
+        let compression_program = expect_account(account_info_iter, "compression_program")?;
+        let proof_accounts = account_info_iter.as_slice();
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        rules::compressed_tree(&token_whitelist_state, tree_account.key)
+            .inspect_err(|error| Self::log_compressed_tree_error(*error, tree_account.key))?;
+        if *compression_program.key != compression::COMPRESSION_PROGRAM_ID {
+            msg!("compression program must be {}", compression::COMPRESSION_PROGRAM_ID);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let leaf = compression::leaf(leaf_index, wallet.key, allocation);
+        let proof: Vec<[u8; 32]> = proof_accounts.iter().map(|node| node.key.to_bytes()).collect();
+        let mut account_infos = vec![tree_account.clone(), compression_program.clone()];
+        account_infos.extend(proof_accounts.iter().cloned());
+        invoke(&compression::verify_leaf(tree_account.key, &root, &leaf, leaf_index, &proof), &account_infos)
+            .inspect_err(|_| {
+                msg!("{} with allocation {} is not proven by the compressed tree", wallet.key, allocation);
+            })
+    }
+
+    #[cfg(feature = "compression")]
+    fn log_compressed_tree_error(error: TokenWhitelistError, tree: &Pubkey) {
+        if error == TokenWhitelistError::CompressedTreeMismatch {
+            msg!("{} is not the compressed tree of the whitelist", tree);
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn check_compression_programs(compression_program: &AccountInfo, noop_program: &AccountInfo) -> ProgramResult {
+        if *compression_program.key != compression::COMPRESSION_PROGRAM_ID {
+            msg!("compression program must be {}", compression::COMPRESSION_PROGRAM_ID);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *noop_program.key != compression::NOOP_PROGRAM_ID {
+            msg!("noop program must be {}", compression::NOOP_PROGRAM_ID);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    fn check_tree_authority(
+        tree_authority: &AccountInfo,
+        whitelist: &Pubkey,
+        authority_bump: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let authority_address =
+            Pubkey::create_program_address(&[TREE_AUTHORITY_SEED, whitelist.as_ref(), &[authority_bump]], program_id)?;
+        if *tree_authority.key != authority_address {
+            msg!("tree authority must be {}", authority_address);
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    /// Invokes the compression program signed by the tree authority of `whitelist`
+    #[cfg(feature = "compression")]
+    fn invoke_as_tree_authority(
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        whitelist: &Pubkey,
+        authority_bump: u8,
+    ) -> ProgramResult {
+        invoke_signed(instruction, account_infos, &[&[TREE_AUTHORITY_SEED, whitelist.as_ref(), &[authority_bump]]])
+    }
+
+    /// Decodes an initialized whitelist, refusing accounts another program could have written
+    fn program_whitelist(account_info: &AccountInfo, program_id: &Pubkey) -> Result<TokenWhitelist, ProgramError> {
+        WhitelistAccount::load(account_info, program_id).map(WhitelistAccount::into_state)
+    }
+
+    fn process_consume_allocation(
+        accounts: &[AccountInfo],
+        amount: u64,
+        round: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let account_owner = expect_account(account_info_iter, "account_owner")?;
+        if !account_owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let mut token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::load_config(&mut token_whitelist_state, token_whitelist_account.key, accounts, program_id)?;
+
+        // membership first, an account outside the whitelist is refused without reading the clock
+        rules::allocation(&token_whitelist_state, account_owner.key, round)?;
+        let slot = Clock::get()?.slot;
+        let (round, remaining_amount) = rules::check_consume(&token_whitelist_state, account_owner.key, amount, round, slot)?;
+        let key = account_owner.key.to_string();
+        token_whitelist_state.set_round_allocation(&key, round, remaining_amount);
+        token_whitelist_state.update_entries_hash(token_whitelist_account.data_len());
+        token_whitelist_state.extension.last_consume_slots.insert(key, slot);
+        Self::store_whitelist(&mut token_whitelist_state, token_whitelist_account)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pages")]
+    fn process_list_entries(
+        accounts: &[AccountInfo],
+        offset: u32,
+        limit: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_enumeration(&token_whitelist_state, account_info_iter.next())?;
+
+        let page = token_whitelist_state.entries_page(offset, limit)?;
+        set_return_data(&page.try_to_vec().unwrap());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pages")]
+    fn process_list_entries_by_campaign(
+        accounts: &[AccountInfo],
+        campaign_id: [u8; 8],
+        offset: u32,
+        limit: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_enumeration(&token_whitelist_state, account_info_iter.next())?;
+
+        let page = token_whitelist_state.campaign_entries_page(campaign_id, offset, limit)?;
+        set_return_data(&page.try_to_vec().unwrap());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pages")]
+    fn process_list_attested_entries(
+        accounts: &[AccountInfo],
+        offset: u32,
+        limit: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+        Self::check_enumeration(&token_whitelist_state, account_info_iter.next())?;
+
+        let page = token_whitelist_state.attested_entries_page(offset, limit)?;
+        set_return_data(&page.try_to_vec().unwrap());
+
+        Ok(())
+    }
+
+    fn process_compute_entries_hash(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_whitelist_account = expect_account(account_info_iter, "token_whitelist_account")?;
+
+        let token_whitelist_state = Self::program_whitelist(token_whitelist_account, program_id)?;
+
+        set_return_data(&hash_entries_map(&token_whitelist_state.whitelist_map));
+
+        Ok(())
+    }
+
+    fn process_get_version() -> ProgramResult {
+        set_return_data(&ProgramVersion::current().try_to_vec().unwrap());
+
+        Ok(())
+    }
+
+    fn process_get_required_rent(max_whitelist_size: u64) -> ProgramResult {
+        let sized_entries = TokenWhitelist::check_max_whitelist_size(max_whitelist_size).inspect_err(|_| {
+            msg!("max whitelist size must be 1 to {} or unlimited (u64::MAX)", TokenWhitelist::MAX_ENTRIES)
+        })?;
+
+        let space = TokenWhitelist::space_for(sized_entries);
+        let required_rent = RequiredRent {space: space as u64, lamports: Rent::get()?.minimum_balance(space)};
+        set_return_data(&required_rent.try_to_vec().unwrap());
+
+        Ok(())
+    }
+
+    /// Reads the newest entry of the SlotHashes sysvar without deserializing all of it
+    fn most_recent_slot_hash(slot_hashes_info: &AccountInfo) -> Result<(u64, [u8; 32]), ProgramError> {
+        Self::expect_sysvar(slot_hashes_info, &sysvar::slot_hashes::id())?;
+        let data = slot_hashes_info.data.borrow();
+        if data.len() < 48 || data[..8] == [0; 8] {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let slot = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let slot_hash = data[16..48].try_into().unwrap();
+        Ok((slot, slot_hash))
+    }
+
+    /// Refuses an account passed in place of a sysvar, which could hold forged sysvar data
+    fn expect_sysvar(account_info: &AccountInfo, expected_id: &Pubkey) -> ProgramResult {
+        if account_info.key != expected_id {
+            msg!("expected sysvar {}, got {}", expected_id, account_info.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// Refuses an account below the rent exempt minimum of its size, logging the minimum, the
+    /// lamports it holds and the shortfall so the creator can fund it without working them out
+    fn check_rent_exempt(rent: &Rent, account_info: &AccountInfo, name: &str) -> ProgramResult {
+        let required = rent.minimum_balance(account_info.data_len());
+        let provided = account_info.lamports();
+        if provided < required {
+            let shortfall = required.checked_sub(provided).ok_or(TokenWhitelistError::Overflow)?;
+            msg!(
+                "{} account of {} bytes must be rent exempt: {} lamports required, {} provided, {} short",
+                name,
+                account_info.data_len(),
+                required,
+                provided,
+                shortfall
+            );
+            return Err(TokenWhitelistError::NotRentExempt.into());
+        }
+        Ok(())
+    }
+
+    /// Slot the stats of an add or remove are stamped with. The processor run off-chain, as
+    /// replays of recorded instructions do, has no clock and stamps 0.
+    fn churn_slot() -> u64 {
+        Clock::get().map_or(0, |clock| clock.slot)
+    }
+
+    /// Logs the stats an add or remove left, for alerting on churn. Accounts with no room for
+    /// them log nothing.
+    fn emit_stats(token_whitelist_account: &AccountInfo, stats: WhitelistStats) {
+        if TokenWhitelist::tracks_mutations(token_whitelist_account.data_len()) {
+            WhitelistEvent::StatsUpdated {
+                whitelist: *token_whitelist_account.key,
+                total_adds: stats.total_adds,
+                total_removes: stats.total_removes,
+                last_mutation_slot: stats.last_mutation_slot,
+            }
+            .emit();
+        }
+    }
+
+    /// Counts the write in the mutation nonce, packs the state into the whitelist account and
+    /// reads the header back, failing the instruction, and so discarding the write, when it
+    /// disagrees with the state packed
+    fn store_whitelist(state: &mut TokenWhitelist, token_whitelist_account: &AccountInfo) -> ProgramResult {
+        let mut data = token_whitelist_account.data.borrow_mut();
+        state.record_mutation(data.len());
+        state.pack_into_slice(&mut data)?;
+        if let Err(error) = state.check_packed(&data) {
+            msg!("token whitelist state read back after packing is inconsistent");
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// The AuthorityContext of the handlers applying a core::Action, `authority_info` being
+    /// their authority account. Its signature is checked first, as check_authority_with does.
+    fn authority_context(
+        authority_info: &AccountInfo,
+        state: &TokenWhitelist,
+        whitelist: &Pubkey,
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> Result<AuthorityContext, ProgramError> {
+        if !authority_info.is_signer {
+            msg!("Authority signature missing");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(AuthorityContext {
+            signer: *authority_info.key,
+            owner_signed: accounts.iter().any(|account| *account.key == state.init_pubkey && account.is_signer),
+            whitelist: *whitelist,
+            program_id: *program_id,
+        })
+    }
+
+    /// core::apply, logging why an action was refused
+    fn apply(state: &mut TokenWhitelist, action: &Action, context: &AuthorityContext) -> Result<Effects, ProgramError> {
+        let result = crate::core::apply(state, action, context);
+        if let Err(error) = &result {
+            Self::log_refusal(state, action, context, *error);
+        }
+        Ok(result?)
+    }
+
+    fn log_refusal(state: &TokenWhitelist, action: &Action, context: &AuthorityContext, error: TokenWhitelistError) {
+        match (action, error) {
+            (Action::Add {..}, TokenWhitelistError::TokenWhitelistNotOwner) => {
+                msg!("signer must be whitelist owner or delegate");
+                context.signer.log();
+                state.init_pubkey.log();
+            }
+            (Action::Remove {..}, TokenWhitelistError::TokenWhitelistNotOwner) => {
+                msg!("signer must be whitelist owner");
+                context.signer.log();
+                state.init_pubkey.log();
+            }
+            (Action::Add {expected_nonce, ..} | Action::Remove {expected_nonce, ..}, TokenWhitelistError::StaleWhitelistState) => {
+                msg!("expected mutation nonce {}, whitelist is at {}", expected_nonce, state.extension.mutation_nonce);
+            }
+            (Action::Add {account, ..}, TokenWhitelistError::AttestationRequired) => {
+                msg!("attestations are required, {} has none", account);
+            }
+            (Action::Add {account, account_owner, ..}, TokenWhitelistError::InvalidWhitelistTarget) => {
+                if rules::check_target_identity(state, &context.whitelist, &context.program_id, account).is_err() {
+                    msg!("{} is the whitelist, the program or an owner not taking entries", account);
+                } else {
+                    msg!("only wallets may be added, {} is owned by {} or off the curve", account, account_owner);
+                }
+            }
+            (Action::ZeroAllocation {account, ..}, TokenWhitelistError::NotOwner) => {
+                msg!("signer must be the owner of the account");
+                context.signer.log();
+                account.log();
+            }
+            (Action::ZeroAllocation {..}, TokenWhitelistError::OwnerSignatureRequired) => {
+                msg!("self zeroing is disabled, the whitelist owner must sign as well");
+            }
+            (Action::RegisterSelf {..}, TokenWhitelistError::RegistrationRateLimited) => {
+                msg!("self registrations of the current window are used up");
+            }
+            (Action::RegisterSelf {..}, TokenWhitelistError::InvalidWhitelistTarget) => {
+                msg!("{} is the whitelist or an owner not taking entries", context.signer);
+            }
+            (Action::SetAttestation {..}, TokenWhitelistError::TokenWhitelistNotOwner) => {
+                msg!("signer must be whitelist owner or delegate");
+                context.signer.log();
+                state.init_pubkey.log();
+            }
+            (Action::SetEntryNoteCommitment {..}, TokenWhitelistError::AccountNotWhitelisted) => {
+                msg!("only a whitelisted account can carry a note commitment");
+            }
+            (Action::SetSiblingWhitelists(siblings), TokenWhitelistError::InvalidInstruction) => {
+                if siblings.len() > MAX_SIBLING_WHITELISTS {
+                    msg!("at most {} sibling whitelists can be linked", MAX_SIBLING_WHITELISTS);
+                } else {
+                    msg!("a sibling whitelist is the whitelist itself or passed twice");
+                }
+            }
+            (Action::RunLottery(_), TokenWhitelistError::LotteryAlreadyRun) => {
+                msg!("token whitelist lottery can only be run once");
+            }
+            (Action::SetActiveRound(_), TokenWhitelistError::InvalidRound) => {
+                msg!("round must be below {}", MAX_ROUNDS);
+            }
+            #[cfg(feature = "merkle")]
+            (Action::SetMerkleRoot(merkle_root), TokenWhitelistError::UnknownHashKind) => {
+                msg!("hash kind {} is neither keccak (0) nor sha256 (1)", merkle_root.hash_kind);
+            }
+            (_, TokenWhitelistError::InvalidAuthority) => msg!("Invalid authority provided"),
+            _ => {}
+        }
+    }
+
+    /// Writes back what core::apply left in a whitelist account: the entries hash and the
+    /// stats, the state, then the events and the stats event
+    fn commit(mut whitelist: WhitelistAccount, effects: Effects) -> ProgramResult {
+        if effects.entries_changed {
+            whitelist.update_entries_hash();
+        }
+        match effects.churn {
+            Some(Churn::Add) => whitelist.record_add(Self::churn_slot()),
+            Some(Churn::Remove) => whitelist.record_remove(Self::churn_slot()),
+            None => {}
+        }
+        let (token_whitelist_account, stats) = (whitelist.info(), whitelist.extension.stats);
+        match effects.store {
+            Store::Nothing => return Ok(()),
+            Store::State => whitelist.save()?,
+            Store::Removal(key) => whitelist.save_removal(&key)?,
+        }
+
+        for event in &effects.events {
+            event.emit();
+        }
+        if effects.churn.is_some() {
+            Self::emit_stats(token_whitelist_account, stats);
+        }
+        Ok(())
+    }
+
+    /// Checks the signer of an owner-gated handler: first that `authority_info` signed, then
+    /// that it is `expected_authority`, refusing any other key with InvalidAuthority. A caller
+    /// passing no signature gets MissingRequiredSignature whatever the key, so it cannot probe
+    /// which key is expected.
     fn check_authority(
         authority_info: &AccountInfo,
         expected_authority: &Pubkey,
     ) -> ProgramResult {
-        if expected_authority != authority_info.key {
-            msg!("Invalid authority provided");
-            return Err(TokenWhitelistError::InvalidAuthority.into());
+        Self::check_authority_with(authority_info, |key| {
+            if key != expected_authority {
+                msg!("Invalid authority provided");
+                return Err(TokenWhitelistError::InvalidAuthority);
+            }
+            Ok(())
+        })
+    }
+
+    /// Checks the optional reader account of an instruction enumerating entries when the
+    /// whitelist restricts enumeration: it must sign and be the owner or the delegate. A missing
+    /// reader is refused as an unsigned one.
+    fn check_enumeration(state: &TokenWhitelist, reader: Option<&AccountInfo>) -> ProgramResult {
+        if !state.extension.restrict_enumeration {
+            return Ok(());
         }
+        let reader = reader.ok_or_else(|| {
+            msg!("enumeration of this whitelist needs the signature of the owner or the delegate");
+            ProgramError::MissingRequiredSignature
+        })?;
+        Self::check_authority_with(reader, |key| rules::check_reader(state, key))
+    }
+
+    /// check_authority for the handlers whose rules judge the key, as adds accepting the
+    /// delegate and MigrateStateLayout the upgrade authority do: the signature is checked first
+    /// all the same, then `check_key` runs on the signer and its verdict is returned. Further
+    /// authorities, an operator or a multisig, are meant to plug in here.
+    fn check_authority_with<T>(
+        authority_info: &AccountInfo,
+        check_key: impl FnOnce(&Pubkey) -> Result<T, TokenWhitelistError>,
+    ) -> Result<T, ProgramError> {
         if !authority_info.is_signer {
             msg!("Authority signature missing");
             return Err(ProgramError::MissingRequiredSignature);
         }
-        Ok(())
+        Ok(check_key(authority_info.key)?)
     }
 }
 
@@ -247,15 +2608,727 @@ impl PrintProgramError for TokenWhitelistError {
     where
         E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
     {
-        match self {
-            TokenWhitelistError::InvalidInstruction => msg!("Error: Invalid Instruction"),
-            TokenWhitelistError::NotRentExempt => msg!("Error: Not Rent Exempt"),
-            TokenWhitelistError::TokenWhitelistNotInit => msg!("Error: Token Whitelist Not Initialized"),
-            TokenWhitelistError::TokenWhitelistNotOwner => msg!("Error: Signer Not Token Whitelist Owner"),
-            TokenWhitelistError::TokenWhitelistSizeExceeds => msg!("Error: Token Whitelist Size Exceeds"),
-            TokenWhitelistError::NotOwner => msg!("Error: Signer Not Account Owner"),
-            TokenWhitelistError::InvalidAuthority => msg!("Error: Invalid authority provided"),
-            TokenWhitelistError::Overflow => msg!("Error: Calculation overflow"),
+        msg!("Error: {} ({} error {})", self, self.category(), self.code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_fixture::{run_instruction, AccountFixture, FIXTURE_PROGRAM_ID};
+
+    const WHITELIST_ACCOUNT_SPACE: usize = 10240;
+
+    fn account(is_signer: bool) -> AccountFixture {
+        let account = AccountFixture::new(Pubkey::new_unique());
+        if is_signer { account.signer() } else { account }
+    }
+
+    fn whitelist_account(owner: &Pubkey, space: usize) -> AccountFixture {
+        AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(space)
+            .whitelist(&TokenWhitelist {
+                is_initialized: true,
+                init_pubkey: *owner,
+                max_whitelist_size: 50,
+                ..TokenWhitelist::default()
+            })
+    }
+
+    fn process(instruction: TokenWhitelistInstruction, accounts: &[AccountInfo]) -> ProgramResult {
+        Processor::process(&FIXTURE_PROGRAM_ID, accounts, &instruction.pack())
+    }
+
+    fn approve_delegate(
+        owner: &mut AccountFixture,
+        whitelist: &mut AccountFixture,
+        delegate: &mut AccountFixture,
+        max_adds: u32,
+        max_allocation_per_add: u64,
+    ) -> ProgramResult {
+        process(
+            TokenWhitelistInstruction::ApproveDelegate {max_adds, max_allocation_per_add},
+            &[owner.info(), whitelist.info(), delegate.info()],
+        )
+    }
+
+    fn add_to_whitelist(
+        authority: &mut AccountFixture,
+        whitelist: &mut AccountFixture,
+        allocation_amount: u64,
+    ) -> ProgramResult {
+        let mut account_to_add = account(false);
+        process(
+            TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+            &[authority.info(), whitelist.info(), account_to_add.info()],
+        )
+    }
+
+    #[test]
+    fn test_delegate_exhausts_adds() {
+        let mut owner = account(true);
+        let mut delegate = account(true);
+        let mut whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+
+        approve_delegate(&mut owner, &mut whitelist, &mut delegate, 2, 1000).unwrap();
+        add_to_whitelist(&mut delegate, &mut whitelist, 1000).unwrap();
+        assert_eq!(whitelist.state().extension.delegate.unwrap().remaining_adds, 1);
+        add_to_whitelist(&mut delegate, &mut whitelist, 500).unwrap();
+        assert_eq!(
+            add_to_whitelist(&mut delegate, &mut whitelist, 500),
+            Err(TokenWhitelistError::DelegateAddsExhausted.into())
+        );
+        assert_eq!(whitelist.state().whitelist_map.len(), 2);
+
+        // the owner is not bound by the delegate limits
+        add_to_whitelist(&mut owner, &mut whitelist, 5000).unwrap();
+        assert_eq!(whitelist.state().whitelist_map.len(), 3);
+    }
+
+    #[test]
+    fn test_delegate_exceeds_allocation_per_add() {
+        let mut owner = account(true);
+        let mut delegate = account(true);
+        let mut whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+
+        approve_delegate(&mut owner, &mut whitelist, &mut delegate, 500, 1000).unwrap();
+        assert_eq!(
+            add_to_whitelist(&mut delegate, &mut whitelist, 1001),
+            Err(TokenWhitelistError::DelegateAllocationExceeded.into())
+        );
+        let state = whitelist.state();
+        assert!(state.whitelist_map.is_empty());
+        assert_eq!(state.extension.delegate.unwrap().remaining_adds, 500);
+    }
+
+    #[test]
+    fn test_revoke_delegate() {
+        let mut owner = account(true);
+        let mut delegate = account(true);
+        let mut whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+
+        approve_delegate(&mut owner, &mut whitelist, &mut delegate, 500, 1000).unwrap();
+        assert_eq!(
+            process(TokenWhitelistInstruction::RevokeDelegate {}, &[delegate.info(), whitelist.info()]),
+            Err(TokenWhitelistError::InvalidAuthority.into())
+        );
+        process(TokenWhitelistInstruction::RevokeDelegate {}, &[owner.info(), whitelist.info()]).unwrap();
+        assert_eq!(whitelist.state().extension.delegate, None);
+        assert_eq!(
+            add_to_whitelist(&mut delegate, &mut whitelist, 1000),
+            Err(TokenWhitelistError::TokenWhitelistNotOwner.into())
+        );
+    }
+
+    #[test]
+    fn test_approve_delegate_needs_extension_space() {
+        let mut owner = account(true);
+        let mut delegate = account(true);
+        let mut whitelist = whitelist_account(&owner.key, 5161);
+
+        add_to_whitelist(&mut owner, &mut whitelist, 1000).unwrap();
+        assert_eq!(
+            approve_delegate(&mut owner, &mut whitelist, &mut delegate, 500, 1000),
+            Err(TokenWhitelistError::AccountTooSmall.into())
+        );
+    }
+
+    fn slot_hashes_account(slot: u64, slot_hash: [u8; 32]) -> AccountFixture {
+        let mut data = 1u64.to_le_bytes().to_vec();
+        data.extend_from_slice(&slot.to_le_bytes());
+        data.extend_from_slice(&slot_hash);
+        AccountFixture::new(sysvar::slot_hashes::id()).data(data)
+    }
+
+    #[test]
+    fn test_run_lottery() {
+        let mut owner = account(true);
+        let mut whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+        for allocation_amount in 1..=10 {
+            let mut account_to_add = account(false);
+            process(
+                TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id: [1; 8], expected_nonce: 0},
+                &[owner.info(), whitelist.info(), account_to_add.info()],
+            )
+            .unwrap();
+        }
+        let entries_before = whitelist.state().whitelist_map;
+        let seed = [42; 32];
+        let mut slot_hashes = slot_hashes_account(1234, [5; 32]);
+
+        let run_lottery = TokenWhitelistInstruction::RunLottery {winners: 3, seed};
+        let mut replay = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE).data(whitelist.data.clone());
+        process(run_lottery.clone(), &[owner.info(), whitelist.info(), slot_hashes.info()]).unwrap();
+        process(run_lottery.clone(), &[owner.info(), replay.info(), slot_hashes.info()]).unwrap();
+
+        let state = whitelist.state();
+        assert_eq!(state.whitelist_map.len(), 3);
+        // the losers leave nothing behind, their campaigns included
+        assert!(state.extension.entry_campaigns.keys().eq(state.whitelist_map.keys()));
+        assert_eq!(state, replay.state());
+        let lottery = state.extension.lottery.unwrap();
+        assert_eq!(lottery, Lottery {seed, slot: 1234, slot_hash: [5; 32], winners: 3});
+        assert!(crate::lottery::verify_lottery(&lottery, &entries_before, &state.whitelist_map));
+
+        assert_eq!(
+            process(run_lottery, &[owner.info(), whitelist.info(), slot_hashes.info()]),
+            Err(TokenWhitelistError::LotteryAlreadyRun.into())
+        );
+    }
+
+    #[test]
+    fn test_run_lottery_rejects_spoofed_slot_hashes() {
+        let mut owner = account(true);
+        let mut whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+        let mut slot_hashes = slot_hashes_account(1234, [5; 32]);
+        slot_hashes.key = Pubkey::new_unique();
+
+        assert_eq!(
+            process(
+                TokenWhitelistInstruction::RunLottery {winners: 3, seed: [42; 32]},
+                &[owner.info(), whitelist.info(), slot_hashes.info()],
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_assert_membership_across_needs_program_whitelists() {
+        let assert_across = TokenWhitelistInstruction::AssertMembershipAcross {
+            require_in_first: false,
+            require_absent_in_second: false,
+        };
+
+        let mut foreign = whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE);
+        foreign.owner = Pubkey::new_unique();
+        let mut fixtures = vec![
+            whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE),
+            foreign,
+            account(false),
+        ];
+        assert_eq!(run_instruction(&assert_across.pack(), &mut fixtures), Err(ProgramError::IncorrectProgramId));
+
+        let uninitialized = AccountFixture::new(Pubkey::new_unique())
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![
+            uninitialized,
+            whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE),
+            account(false),
+        ];
+        assert_eq!(
+            run_instruction(&assert_across.pack(), &mut fixtures),
+            Err(TokenWhitelistError::TokenWhitelistNotInit.into())
+        );
+    }
+
+    #[test]
+    fn test_assert_owner() {
+        let assert_owner = TokenWhitelistInstruction::AssertOwner {}.pack();
+        let owner = Pubkey::new_unique();
+        // neither account needs to be writable or sign
+        let readonly_whitelist = || {
+            let mut whitelist = whitelist_account(&owner, WHITELIST_ACCOUNT_SPACE);
+            whitelist.is_writable = false;
+            whitelist
+        };
+
+        let mut fixtures = vec![readonly_whitelist(), AccountFixture::new(owner)];
+        assert_eq!(run_instruction(&assert_owner, &mut fixtures), Ok(()));
+
+        let mut fixtures = vec![readonly_whitelist(), account(false)];
+        assert_eq!(
+            run_instruction(&assert_owner, &mut fixtures),
+            Err(TokenWhitelistError::TokenWhitelistNotOwner.into())
+        );
+
+        let uninitialized = AccountFixture::new(Pubkey::new_unique())
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![uninitialized, AccountFixture::new(owner)];
+        assert_eq!(
+            run_instruction(&assert_owner, &mut fixtures),
+            Err(TokenWhitelistError::TokenWhitelistNotInit.into())
+        );
+
+        // a look-alike holding the same bytes under another program
+        let mut look_alike = readonly_whitelist();
+        look_alike.owner = Pubkey::new_unique();
+        let mut fixtures = vec![look_alike, AccountFixture::new(owner)];
+        assert_eq!(run_instruction(&assert_owner, &mut fixtures), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_config_account_required() {
+        let owner = account(true);
+        let mut whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+        let (config_address, bump) = WhitelistConfig::find_address(&FIXTURE_PROGRAM_ID, &whitelist.key);
+        let mut state = whitelist.state();
+        state.extension.config_bump = Some(bump);
+        whitelist = whitelist.whitelist(&state);
+
+        let set_round = TokenWhitelistInstruction::SetActiveRound {round: 1}.pack();
+        let mut fixtures = vec![owner.clone(), whitelist.clone()];
+        assert_eq!(
+            run_instruction(&set_round, &mut fixtures),
+            Err(TokenWhitelistError::ConfigAccountMissing.into())
+        );
+
+        // policy goes to the config account and the whitelist is left as it was
+        let mut config = AccountFixture::new(config_address)
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(CONFIG_SPACE);
+        WhitelistConfig {whitelist: whitelist.key, policy: WhitelistPolicy::of(&state.extension)}
+            .pack_into_slice(&mut config.data)
+            .unwrap();
+        let mut fixtures = vec![owner.clone(), whitelist.clone(), config.clone()];
+        assert_eq!(run_instruction(&set_round, &mut fixtures), Ok(()));
+        assert_eq!(fixtures[1].data, whitelist.data);
+        assert_eq!(WhitelistConfig::unpack_from_slice(&fixtures[2].data).unwrap().policy.active_round, 1);
+
+        // a look-alike at the address owned by another program is refused
+        config.owner = Pubkey::new_unique();
+        let mut fixtures = vec![owner, whitelist, config];
+        assert_eq!(run_instruction(&set_round, &mut fixtures), Err(ProgramError::IncorrectProgramId));
+    }
+
+    /// Instructions only the whitelist owner may send, with the error a different signer gets
+    fn owner_instructions() -> Vec<(TokenWhitelistInstruction, TokenWhitelistError)> {
+        let mut instructions = vec![
+            (
+                TokenWhitelistInstruction::AddToWhitelist {
+                    allocation_amount: 100,
+                    campaign_id: NO_CAMPAIGN,
+                    expected_nonce: 0,
+                },
+                TokenWhitelistError::TokenWhitelistNotOwner,
+            ),
+            (TokenWhitelistInstruction::RemoveFromWhitelist {
+                memo: None,
+                expected_nonce: 0,
+            }, TokenWhitelistError::TokenWhitelistNotOwner),
+            (TokenWhitelistInstruction::CloseWhitelistAccount {}, TokenWhitelistError::InvalidAuthority),
+            (
+                TokenWhitelistInstruction::ApproveDelegate {max_adds: 1, max_allocation_per_add: 100},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (TokenWhitelistInstruction::RevokeDelegate {}, TokenWhitelistError::InvalidAuthority),
+            (
+                TokenWhitelistInstruction::ConfigureRegistration {
+                    registration_end_slot: 100,
+                    registration_allocation: 100,
+                    registration_deposit_lamports: 0,
+                },
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (TokenWhitelistInstruction::RefundDeposit {}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::RunLottery {winners: 1, seed: [1; 32]}, TokenWhitelistError::InvalidAuthority),
+            (
+                TokenWhitelistInstruction::SetConsumeLimits {max_consume_per_tx: 1, min_slots_between_consumes: 1},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::SetAllocationBounds {min_allocation: 1, max_allocation: 100},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::AddToWhitelistForRound {
+                    allocation_amount: 100,
+                    round: 1,
+                    campaign_id: NO_CAMPAIGN,
+                    expected_nonce: 0,
+                },
+                TokenWhitelistError::TokenWhitelistNotOwner,
+            ),
+            (TokenWhitelistInstruction::SetActiveRound {round: 1}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::ArchiveWhitelist {}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::SetStrictTokenChecks {strict_token_checks: true}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::SetOnlyWallets {only_wallets: true}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: false}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::InitConfig {}, TokenWhitelistError::InvalidAuthority),
+            (TokenWhitelistInstruction::RepairState {}, TokenWhitelistError::InvalidAuthority),
+            (
+                TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation: false},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::SetTokenGate {gating_mint: Pubkey::new_unique(), gating_minimum: 0, gate_owner_adds: false},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::AddToWhitelistWithAttestation {allocation_amount: 100, attestation: [1; 32]},
+                TokenWhitelistError::TokenWhitelistNotOwner,
+            ),
+            (
+                TokenWhitelistInstruction::SetAttestation {attestation: [1; 32]},
+                TokenWhitelistError::TokenWhitelistNotOwner,
+            ),
+            (
+                TokenWhitelistInstruction::SetAttestationRequired {require_attestation: true},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (TokenWhitelistInstruction::AssertNotInOther {}, TokenWhitelistError::InvalidAuthority),
+            (
+                TokenWhitelistInstruction::SetOwnerEntryAllowed {allow_owner_entry: false},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::SetEntryNoteCommitment {commitment: [1; 32]},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::SetRegistrationRateLimit {max_registrations_per_window: 1, window_slots: 1},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+            (
+                TokenWhitelistInstruction::SetEnumerationRestricted {restrict_enumeration: true},
+                TokenWhitelistError::InvalidAuthority,
+            ),
+        ];
+        if cfg!(feature = "merkle") {
+            instructions.extend([
+                (TokenWhitelistInstruction::SetMerkleRoot {root: [1; 32], hash_kind: 0}, TokenWhitelistError::InvalidAuthority),
+                (TokenWhitelistInstruction::InitClaimBitmap {leaf_count: 8}, TokenWhitelistError::InvalidAuthority),
+            ]);
+        }
+        instructions
+    }
+
+    /// Authority and whitelist followed by the other accounts the instruction expects
+    fn accounts(
+        instruction: &TokenWhitelistInstruction,
+        authority: AccountFixture,
+        whitelist: AccountFixture,
+    ) -> Vec<AccountFixture> {
+        let mut accounts = vec![authority, whitelist];
+        match instruction {
+            TokenWhitelistInstruction::RunLottery {..} => accounts.push(slot_hashes_account(1, [1; 32])),
+            TokenWhitelistInstruction::RegisterSelf {} => {
+                accounts.push(AccountFixture::new(solana_program::system_program::id()));
+            }
+            TokenWhitelistInstruction::SetAllocationToZero {..} => accounts.push(accounts[0].clone()),
+            TokenWhitelistInstruction::InitConfig {} => {
+                accounts.push(account(false).writable());
+                accounts.push(AccountFixture::new(solana_program::system_program::id()));
+            }
+            TokenWhitelistInstruction::AddToWhitelist {..}
+            | TokenWhitelistInstruction::AddToWhitelistForRound {..}
+            | TokenWhitelistInstruction::AddToWhitelistWithAttestation {..}
+            | TokenWhitelistInstruction::SetAttestation {..}
+            | TokenWhitelistInstruction::SetEntryNoteCommitment {..}
+            | TokenWhitelistInstruction::RemoveFromWhitelist {..}
+            | TokenWhitelistInstruction::CloseWhitelistAccount {}
+            | TokenWhitelistInstruction::ArchiveWhitelist {}
+            | TokenWhitelistInstruction::ApproveDelegate {..}
+            | TokenWhitelistInstruction::InitClaimBitmap {..}
+            | TokenWhitelistInstruction::RefundDeposit {} => accounts.push(account(false).writable()),
+            _ => {}
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_owner_instructions_need_owner() {
+        for (instruction, error) in owner_instructions() {
+            let owner = account(true);
+            let whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+            let mut fixtures = accounts(&instruction, account(true), whitelist);
+            assert_eq!(run_instruction(&instruction.pack(), &mut fixtures), Err(error.into()), "{:?}", instruction);
+        }
+    }
+
+    #[test]
+    fn test_owner_instructions_need_signature() {
+        for (instruction, _) in owner_instructions() {
+            let owner = account(false);
+            let whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+            let mut fixtures = accounts(&instruction, owner, whitelist);
+            assert_eq!(
+                run_instruction(&instruction.pack(), &mut fixtures),
+                Err(ProgramError::MissingRequiredSignature),
+                "{:?}",
+                instruction
+            );
+        }
+    }
+
+    #[test]
+    fn test_owner_instructions_check_signature_first() {
+        // an unsigned stranger gets the error the unsigned owner gets, telling nothing of the key
+        for (instruction, _) in owner_instructions() {
+            let owner = account(true);
+            let whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+            let mut fixtures = accounts(&instruction, account(false), whitelist);
+            assert_eq!(
+                run_instruction(&instruction.pack(), &mut fixtures),
+                Err(ProgramError::MissingRequiredSignature),
+                "{:?}",
+                instruction
+            );
+        }
+    }
+
+    /// Instructions signed by a whitelisted wallet rather than the owner
+    fn wallet_instructions() -> Vec<TokenWhitelistInstruction> {
+        vec![
+            TokenWhitelistInstruction::SetAllocationToZero {memo: None},
+            TokenWhitelistInstruction::RegisterSelf {},
+            TokenWhitelistInstruction::ConsumeAllocation {amount: 1},
+            TokenWhitelistInstruction::ConsumeAllocationForRound {amount: 1, round: 1},
+        ]
+    }
+
+    #[test]
+    fn test_wallet_instructions_need_signature() {
+        for instruction in wallet_instructions() {
+            let whitelist = whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE);
+            let mut fixtures = accounts(&instruction, account(false), whitelist);
+            assert_eq!(
+                run_instruction(&instruction.pack(), &mut fixtures),
+                Err(ProgramError::MissingRequiredSignature),
+                "{:?}",
+                instruction
+            );
+        }
+
+        // refunding to itself, the registered account has to sign
+        let registered = account(false);
+        let whitelist = whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![registered.clone(), whitelist, registered];
+        assert_eq!(
+            run_instruction(&TokenWhitelistInstruction::RefundDeposit {}.pack(), &mut fixtures),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_wallet_instructions_need_own_entry() {
+        let whitelist = whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![account(true), whitelist, account(false)];
+        assert_eq!(
+            run_instruction(&TokenWhitelistInstruction::SetAllocationToZero {memo: None}.pack(), &mut fixtures),
+            Err(TokenWhitelistError::NotOwner.into())
+        );
+
+        let whitelist = whitelist_account(&Pubkey::new_unique(), WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![account(true), whitelist];
+        assert_eq!(
+            run_instruction(&TokenWhitelistInstruction::ConsumeAllocation {amount: 1}.pack(), &mut fixtures),
+            Err(TokenWhitelistError::AccountNotWhitelisted.into())
+        );
+    }
+
+    #[test]
+    fn test_instructions_need_initialized_whitelist() {
+        let instructions = owner_instructions().into_iter()
+            .map(|(instruction, _)| instruction)
+            .chain(wallet_instructions());
+        for instruction in instructions {
+            let whitelist = AccountFixture::new(Pubkey::new_unique())
+                .writable()
+                .owner(FIXTURE_PROGRAM_ID)
+                .data_len(WHITELIST_ACCOUNT_SPACE);
+            let mut fixtures = accounts(&instruction, account(true), whitelist);
+            assert_eq!(
+                run_instruction(&instruction.pack(), &mut fixtures),
+                Err(TokenWhitelistError::TokenWhitelistNotInit.into()),
+                "{:?}",
+                instruction
+            );
+        }
+    }
+
+    #[test]
+    fn test_instructions_reject_archived_whitelist() {
+        let instructions = owner_instructions().into_iter()
+            .map(|(instruction, _)| instruction)
+            .chain(wallet_instructions());
+        for instruction in instructions {
+            let owner = account(true);
+            let mut whitelist = AccountFixture::new(Pubkey::new_unique())
+                .writable()
+                .owner(FIXTURE_PROGRAM_ID)
+                .data_len(ARCHIVE_SPACE);
+            WhitelistArchive {init_pubkey: owner.key, ..WhitelistArchive::default()}
+                .pack_into_slice(&mut whitelist.data)
+                .unwrap();
+            let mut fixtures = accounts(&instruction, owner, whitelist);
+            assert_eq!(
+                run_instruction(&instruction.pack(), &mut fixtures),
+                Err(TokenWhitelistError::WhitelistArchived.into()),
+                "{:?}",
+                instruction
+            );
+        }
+    }
+
+    #[test]
+    fn test_archive_needs_deposits_refunded() {
+        let owner = account(true);
+        let mut state = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: owner.key,
+            max_whitelist_size: 10,
+            ..TokenWhitelist::default()
+        };
+        state.extension.registration_deposits.insert(Pubkey::new_unique().to_string(), 1_000);
+        let whitelist = AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(WHITELIST_ACCOUNT_SPACE)
+            .whitelist(&state);
+        let mut fixtures = vec![owner, whitelist, account(false).writable()];
+        assert_eq!(
+            run_instruction(&TokenWhitelistInstruction::ArchiveWhitelist {}.pack(), &mut fixtures),
+            Err(TokenWhitelistError::DepositsOutstanding.into())
+        );
+    }
+
+    #[test]
+    fn test_init_whitelist_errors() {
+        let init = TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size: 10}.pack();
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let rent_account = AccountFixture::new(sysvar::rent::id()).data(rent_data);
+
+        let owner = account(false);
+        let whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![owner, whitelist, rent_account.clone()];
+        assert_eq!(run_instruction(&init, &mut fixtures), Err(ProgramError::MissingRequiredSignature));
+
+        let owner = account(true);
+        let whitelist = whitelist_account(&owner.key, WHITELIST_ACCOUNT_SPACE)
+            .lamports(rent.minimum_balance(WHITELIST_ACCOUNT_SPACE));
+        let mut fixtures = vec![owner, whitelist, rent_account];
+        assert_eq!(run_instruction(&init, &mut fixtures), Err(ProgramError::AccountAlreadyInitialized));
+    }
+
+    #[test]
+    fn test_init_whitelist_size_ceiling() {
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let rent_account = AccountFixture::new(sysvar::rent::id()).data(rent_data);
+
+        for (max_whitelist_size, result) in [
+            (TokenWhitelist::MAX_ENTRIES + 1, Err(TokenWhitelistError::TokenWhitelistSizeExceeds.into())),
+            (TokenWhitelist::MAX_ENTRIES, Ok(())),
+            (0, Err(TokenWhitelistError::ZeroWhitelistSize.into())),
+            (crate::state::UNLIMITED_WHITELIST_SIZE, Ok(())),
+        ] {
+            let init = TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size}.pack();
+            let whitelist = AccountFixture::new(Pubkey::new_unique())
+                .writable()
+                .owner(FIXTURE_PROGRAM_ID)
+                .lamports(rent.minimum_balance(WHITELIST_ACCOUNT_SPACE))
+                .data_len(WHITELIST_ACCOUNT_SPACE);
+            let mut fixtures = vec![account(true), whitelist, rent_account.clone()];
+            assert_eq!(run_instruction(&init, &mut fixtures), result);
+        }
+    }
+
+    #[test]
+    fn test_init_whitelist_rejects_non_blank_account() {
+        let init = TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size: 10}.pack();
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let rent_account = AccountFixture::new(sysvar::rent::id()).data(rent_data);
+
+        // zero where is_initialized lives, garbage everywhere else
+        let mut data = vec![0xff; WHITELIST_ACCOUNT_SPACE];
+        data[0] = 0;
+        let whitelist = AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .lamports(rent.minimum_balance(WHITELIST_ACCOUNT_SPACE))
+            .data(data.clone());
+        let mut fixtures = vec![account(true), whitelist, rent_account.clone()];
+        assert_eq!(run_instruction(&init, &mut fixtures), Err(TokenWhitelistError::AccountNotBlank.into()));
+        assert_eq!(fixtures[1].data, data);
+
+        let whitelist = AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .lamports(rent.minimum_balance(WHITELIST_ACCOUNT_SPACE))
+            .data_len(WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![account(true), whitelist, rent_account];
+        run_instruction(&init, &mut fixtures).unwrap();
+        assert!(fixtures[1].state().is_initialized);
+    }
+
+    #[test]
+    fn test_oversized_whitelist() {
+        let space = WHITELIST_ACCOUNT_SPACE * 2;
+        let init = TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size: 10}.pack();
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let rent_account = AccountFixture::new(sysvar::rent::id()).data(rent_data);
+
+        // rent exemption covers the whole account, not just the bytes the layout uses
+        let whitelist = AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .lamports(rent.minimum_balance(WHITELIST_ACCOUNT_SPACE))
+            .data_len(space);
+        let mut fixtures = vec![account(true), whitelist, rent_account.clone()];
+        assert_eq!(run_instruction(&init, &mut fixtures), Err(TokenWhitelistError::NotRentExempt.into()));
+
+        let owner = account(true);
+        let whitelist = AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .lamports(rent.minimum_balance(space))
+            .data_len(space);
+        let mut fixtures = vec![owner.clone(), whitelist, rent_account];
+        run_instruction(&init, &mut fixtures).unwrap();
+        let mut whitelist = fixtures.remove(1);
+
+        let (kept, removed) = (Pubkey::new_unique(), Pubkey::new_unique());
+        for key in [kept, removed] {
+            let add = TokenWhitelistInstruction::AddToWhitelist {
+                allocation_amount: 100,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 0,
+            }.pack();
+            let mut fixtures = vec![owner.clone(), whitelist, AccountFixture::new(key)];
+            run_instruction(&add, &mut fixtures).unwrap();
+            whitelist = fixtures.remove(1);
         }
+        let remove = TokenWhitelistInstruction::RemoveFromWhitelist {memo: None, expected_nonce: 0}.pack();
+        let mut fixtures = vec![owner.clone(), whitelist, AccountFixture::new(removed)];
+        run_instruction(&remove, &mut fixtures).unwrap();
+
+        // what follows the packed state is zeroed, as it was on init
+        let mut expected = vec![0; space];
+        let state = fixtures[1].state();
+        assert_eq!(state.whitelist_map.keys().collect::<Vec<_>>(), vec![&kept.to_string()]);
+        state.pack_into_slice(&mut expected).unwrap();
+        assert_eq!(fixtures[1].data, expected);
+    }
+
+    #[test]
+    fn test_init_whitelist_rejects_spoofed_rent() {
+        let init = TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size: 10}.pack();
+        // rent data claiming a zero minimum balance
+        let spoofed_rent = AccountFixture::new(Pubkey::new_unique()).data(vec![0; 17]);
+
+        let owner = account(true);
+        let whitelist = AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(WHITELIST_ACCOUNT_SPACE);
+        let mut fixtures = vec![owner, whitelist, spoofed_rent];
+        assert_eq!(run_instruction(&init, &mut fixtures), Err(ProgramError::InvalidArgument));
+        assert!(!fixtures[1].state().is_initialized);
     }
 }
@@ -0,0 +1,221 @@
+//! Recorded instructions with the accounts they saw before and after, replayed through the
+//! processor to check the same bytes still produce the same accounts
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Epoch,
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+use crate::processor::Processor;
+
+use std::{fmt::Write, str::FromStr};
+
+/// An account of the instruction, in instruction order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub owner: Pubkey,
+    pub pre_lamports: u64,
+    pub pre_data: Vec<u8>,
+    pub post_lamports: u64,
+    pub post_data: Vec<u8>, // not compared once the account is left without lamports
+}
+
+/// One recorded instruction, stored as text:
+///
+/// ```text
+/// # comment
+/// program <pubkey>
+/// instruction <hex>
+/// account <pubkey> <s|-><w|-> <owner> <pre lamports> <post lamports>
+/// pre <hex>
+/// post <hex>
+/// ```
+///
+/// with a `pre` and a `post` line after every `account` line
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayFixture {
+    pub program_id: Pubkey,
+    pub instruction: Vec<u8>,
+    pub accounts: Vec<ReplayAccount>,
+}
+
+impl ReplayFixture {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fixture = ReplayFixture::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |reason: &str| format!("line {}: {}", number + 1, reason);
+            let (field, value) = line.split_once(' ').unwrap_or((line, ""));
+            match field {
+                "program" => fixture.program_id = parse_pubkey(value).ok_or_else(|| fail("invalid program"))?,
+                "instruction" => fixture.instruction = decode_hex(value).ok_or_else(|| fail("invalid hex"))?,
+                "account" => fixture.accounts.push(parse_account(value).ok_or_else(|| fail("invalid account"))?),
+                "pre" | "post" => {
+                    let data = decode_hex(value).ok_or_else(|| fail("invalid hex"))?;
+                    let account = fixture.accounts.last_mut().ok_or_else(|| fail("data before any account"))?;
+                    if field == "pre" {
+                        account.pre_data = data;
+                    } else {
+                        account.post_data = data;
+                    }
+                }
+                _ => return Err(fail("unknown field")),
+            }
+        }
+        Ok(fixture)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "program {}", self.program_id).unwrap();
+        writeln!(text, "instruction {}", encode_hex(&self.instruction)).unwrap();
+        for account in &self.accounts {
+            writeln!(
+                text,
+                "account {} {}{} {} {} {}",
+                account.pubkey,
+                if account.is_signer { 's' } else { '-' },
+                if account.is_writable { 'w' } else { '-' },
+                account.owner,
+                account.pre_lamports,
+                account.post_lamports,
+            ).unwrap();
+            writeln!(text, "{}", format!("pre {}", encode_hex(&account.pre_data)).trim_end()).unwrap();
+            writeln!(text, "{}", format!("post {}", encode_hex(&account.post_data)).trim_end()).unwrap();
+        }
+        text
+    }
+
+    /// Runs the instruction over the pre images, an account repeated in the instruction sharing
+    /// one image like it does on chain, and returns the lamports and data of every account after
+    pub fn replay(&self) -> (ProgramResult, Vec<(u64, Vec<u8>)>) {
+        let mut images: Vec<(Pubkey, u64, Vec<u8>, Pubkey)> = Vec::new();
+        for account in &self.accounts {
+            if !images.iter().any(|(pubkey, ..)| *pubkey == account.pubkey) {
+                images.push((account.pubkey, account.pre_lamports, account.pre_data.clone(), account.owner));
+            }
+        }
+
+        let unique: Vec<AccountInfo> = images.iter_mut()
+            .map(|(pubkey, lamports, data, owner)| {
+                let account = self.accounts.iter().find(|account| account.pubkey == *pubkey).unwrap();
+                AccountInfo::new(
+                    pubkey,
+                    account.is_signer,
+                    account.is_writable,
+                    lamports,
+                    data,
+                    owner,
+                    false,
+                    Epoch::default(),
+                )
+            })
+            .collect();
+        let infos: Vec<AccountInfo> = self.accounts.iter()
+            .map(|account| unique.iter().find(|info| *info.key == account.pubkey).unwrap().clone())
+            .collect();
+
+        let result = Processor::process(&self.program_id, &infos, &self.instruction);
+        let after = infos.iter().map(|info| (info.lamports(), info.data.borrow().to_vec())).collect();
+        (result, after)
+    }
+
+    /// Index and description of the first account the replay leaves different from its post image
+    pub fn mismatch(&self, after: &[(u64, Vec<u8>)]) -> Option<(usize, String)> {
+        self.accounts.iter().zip(after).enumerate().find_map(|(index, (account, (lamports, data)))| {
+            if *lamports != account.post_lamports {
+                return Some((index, format!("lamports {} instead of {}", lamports, account.post_lamports)));
+            }
+            if account.post_lamports != 0 && *data != account.post_data {
+                let offset = data.iter().zip(&account.post_data).position(|(a, b)| a != b)
+                    .unwrap_or_else(|| data.len().min(account.post_data.len()));
+                return Some((index, format!("data differs from byte {}", offset)));
+            }
+            None
+        })
+    }
+}
+
+fn parse_pubkey(value: &str) -> Option<Pubkey> {
+    Pubkey::from_str(value).ok()
+}
+
+fn parse_account(value: &str) -> Option<ReplayAccount> {
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    let (pubkey, flags, owner, pre_lamports, post_lamports) = match fields[..] {
+        [pubkey, flags, owner, pre_lamports, post_lamports] => (pubkey, flags, owner, pre_lamports, post_lamports),
+        _ => return None,
+    };
+    let (is_signer, is_writable) = match flags {
+        "sw" => (true, true),
+        "s-" => (true, false),
+        "-w" => (false, true),
+        "--" => (false, false),
+        _ => return None,
+    };
+    Some(ReplayAccount {
+        pubkey: parse_pubkey(pubkey)?,
+        is_signer,
+        is_writable,
+        owner: parse_pubkey(owner)?,
+        pre_lamports: pre_lamports.parse().ok()?,
+        post_lamports: post_lamports.parse().ok()?,
+        ..ReplayAccount::default()
+    })
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_roundtrip() {
+        let fixture = ReplayFixture {
+            program_id: Pubkey::new_unique(),
+            instruction: vec![2, 3, b'k', b'y', b'c'],
+            accounts: vec![
+                ReplayAccount {
+                    pubkey: Pubkey::new_unique(),
+                    is_signer: true,
+                    pre_lamports: 5,
+                    post_lamports: 5,
+                    ..ReplayAccount::default()
+                },
+                ReplayAccount {
+                    pubkey: Pubkey::new_unique(),
+                    is_writable: true,
+                    owner: Pubkey::new_unique(),
+                    pre_lamports: 10,
+                    pre_data: vec![1, 0xff],
+                    post_lamports: 0,
+                    post_data: vec![0, 0],
+                    ..ReplayAccount::default()
+                },
+            ],
+        };
+        assert_eq!(ReplayFixture::parse(&fixture.to_text()).unwrap(), fixture);
+        assert_eq!(ReplayFixture::parse("pre 00"), Err("line 1: data before any account".to_string()));
+        assert_eq!(ReplayFixture::parse("instruction 0"), Err("line 1: invalid hex".to_string()));
+    }
+}
@@ -0,0 +1,340 @@
+//! Eligibility decisions the processor takes on a decoded whitelist. They read no accounts and no
+//! sysvars, so a backend holding the whitelist state reaches the verdict the chain would, with
+//! the same error, before sending anything.
+
+use solana_program::{program_pack::IsInitialized, pubkey::Pubkey, system_program};
+use crate::{
+    error::TokenWhitelistError,
+    state::{note_commitment, Registration, TokenWhitelist, ACTIVE_ROUND, NO_ATTESTATION, NO_NOTE_COMMITMENT},
+    util,
+};
+#[cfg(feature = "merkle")]
+use crate::merkle::{self, HashKind};
+#[cfg(feature = "compression")]
+use crate::{compression::TREE_CAPACITY, state::CompressedTree};
+
+pub fn check_initialized(state: &TokenWhitelist) -> Result<(), TokenWhitelistError> {
+    if !state.is_initialized() {
+        return Err(TokenWhitelistError::TokenWhitelistNotInit);
+    }
+    Ok(())
+}
+
+/// Checks that `authority` owns the whitelist
+pub fn check_owner(state: &TokenWhitelist, authority: &Pubkey) -> Result<(), TokenWhitelistError> {
+    check_initialized(state)?;
+    if *authority != state.init_pubkey {
+        return Err(TokenWhitelistError::TokenWhitelistNotOwner);
+    }
+    Ok(())
+}
+
+/// Checks that `reader`, the owner or the delegate, may run the instructions listing the entries
+/// of a whitelist restricting enumeration
+pub fn check_reader(state: &TokenWhitelist, reader: &Pubkey) -> Result<(), TokenWhitelistError> {
+    let is_delegate = matches!(state.extension.delegate, Some(delegate) if *reader == delegate.delegate_pubkey);
+    if *reader != state.init_pubkey && !is_delegate {
+        return Err(TokenWhitelistError::InvalidAuthority);
+    }
+    Ok(())
+}
+
+/// Allocation of `account` in `round`, ACTIVE_ROUND included, refused unless it is whitelisted
+pub fn allocation(state: &TokenWhitelist, account: &Pubkey, round: u8) -> Result<u64, TokenWhitelistError> {
+    check_initialized(state)?;
+    let round = state.resolve_round(round)?;
+    state.round_allocation(&account.to_string(), round).ok_or(TokenWhitelistError::AccountNotWhitelisted)
+}
+
+/// Checks the mutation nonce an instruction expects against the one of the whitelist, an
+/// expected nonce of zero checking nothing
+pub fn check_mutation_nonce(state: &TokenWhitelist, expected_nonce: u64) -> Result<(), TokenWhitelistError> {
+    if expected_nonce != 0 && expected_nonce != state.extension.mutation_nonce {
+        return Err(TokenWhitelistError::StaleWhitelistState);
+    }
+    Ok(())
+}
+
+/// Checks an add of `allocation_amount` signed by `authority`, the owner or the delegate, and
+/// returns the resolved round. A delegate add still has to be counted with `Delegate::use_add`.
+/// Self registrations go through check_register instead and may grant a zero allocation
+/// whatever SetZeroAllocationAllowed set.
+pub fn check_add(
+    state: &TokenWhitelist,
+    authority: &Pubkey,
+    allocation_amount: u64,
+    round: u8,
+) -> Result<u8, TokenWhitelistError> {
+    check_initialized(state)?;
+    if *authority != state.init_pubkey {
+        match state.extension.delegate {
+            Some(mut delegate) if *authority == delegate.delegate_pubkey => delegate.use_add(allocation_amount)?,
+            _ => return Err(TokenWhitelistError::TokenWhitelistNotOwner),
+        }
+    }
+    let round = state.resolve_round(round)?;
+    state.extension.allocation_bounds.check(allocation_amount)?;
+    check_zero_allocation(state, allocation_amount)?;
+    Ok(round)
+}
+
+/// Refuses a zero allocation added by the owner or the delegate once SetZeroAllocationAllowed
+/// disabled them, such an add being most likely a forgotten amount
+pub fn check_zero_allocation(state: &TokenWhitelist, allocation_amount: u64) -> Result<(), TokenWhitelistError> {
+    if allocation_amount == 0 && state.extension.zero_allocation_disabled {
+        return Err(TokenWhitelistError::AllocationBelowMinimum);
+    }
+    Ok(())
+}
+
+/// Refuses an add that leaves `key` without attestation once SetAttestationRequired made them
+/// required, `attestation` being the one the add sets and NO_ATTESTATION keeping the stored one
+pub fn check_attestation(state: &TokenWhitelist, key: &str, attestation: [u8; 32]) -> Result<(), TokenWhitelistError> {
+    let attested = attestation != NO_ATTESTATION || state.attestation(key) != NO_ATTESTATION;
+    if state.extension.require_attestation && !attested {
+        return Err(TokenWhitelistError::AttestationRequired);
+    }
+    Ok(())
+}
+
+/// Checks a SetAttestation of `account` signed by `authority`, the owner or the delegate. It
+/// changes no allocation, so it uses none of the adds of the delegate.
+pub fn check_set_attestation(
+    state: &TokenWhitelist,
+    authority: &Pubkey,
+    account: &Pubkey,
+    attestation: [u8; 32],
+) -> Result<(), TokenWhitelistError> {
+    check_initialized(state)?;
+    let is_delegate = matches!(state.extension.delegate, Some(delegate) if *authority == delegate.delegate_pubkey);
+    if *authority != state.init_pubkey && !is_delegate {
+        return Err(TokenWhitelistError::TokenWhitelistNotOwner);
+    }
+    if !state.whitelist_map.contains_key(&account.to_string()) {
+        return Err(TokenWhitelistError::AccountNotWhitelisted);
+    }
+    if state.extension.require_attestation && attestation == NO_ATTESTATION {
+        return Err(TokenWhitelistError::AttestationRequired);
+    }
+    Ok(())
+}
+
+/// Checks a RotateWhitelistedKey moving the entry of `account` to `new_account`, both signers.
+/// The owner is not involved, the entry keeps its allocations whatever the policy.
+pub fn check_rotate_key(
+    state: &TokenWhitelist,
+    account: &Pubkey,
+    new_account: &Pubkey,
+) -> Result<(), TokenWhitelistError> {
+    check_initialized(state)?;
+    if !state.whitelist_map.contains_key(&account.to_string()) {
+        return Err(TokenWhitelistError::AccountNotWhitelisted);
+    }
+    if state.whitelist_map.contains_key(&new_account.to_string()) {
+        return Err(TokenWhitelistError::AccountAlreadyWhitelisted);
+    }
+    Ok(())
+}
+
+/// Checks a RevealEntryNote of `note` and `salt` against the note commitment of the entry of
+/// `account`. An entry without commitment matches no note.
+pub fn check_note_reveal(
+    state: &TokenWhitelist,
+    account: &Pubkey,
+    note: &[u8],
+    salt: &[u8; 16],
+) -> Result<(), TokenWhitelistError> {
+    check_initialized(state)?;
+    let key = account.to_string();
+    if !state.whitelist_map.contains_key(&key) {
+        return Err(TokenWhitelistError::AccountNotWhitelisted);
+    }
+    let commitment = state.entry_note_commitment(&key);
+    if commitment == NO_NOTE_COMMITMENT || commitment != note_commitment(note, salt) {
+        return Err(TokenWhitelistError::NoteCommitmentMismatch);
+    }
+    Ok(())
+}
+
+/// Checks a consume of `amount` by `account` at `slot` and returns the resolved round with the
+/// allocation left in it afterwards
+pub fn check_consume(
+    state: &TokenWhitelist,
+    account: &Pubkey,
+    amount: u64,
+    round: u8,
+    slot: u64,
+) -> Result<(u8, u64), TokenWhitelistError> {
+    check_initialized(state)?;
+    let round = state.resolve_round(round)?;
+    let key = account.to_string();
+    let allocation_amount = state.round_allocation(&key, round).ok_or(TokenWhitelistError::AccountNotWhitelisted)?;
+
+    let last_consume_slot = state.extension.last_consume_slots.get(&key).copied();
+    state.extension.consume_limits.check(amount, slot, last_consume_slot)?;
+
+    let remaining_amount = allocation_amount.checked_sub(amount).ok_or(TokenWhitelistError::InsufficientAllocation)?;
+    Ok((round, remaining_amount))
+}
+
+/// Most `account` may consume at `slot` in the active round: its allocation there, which
+/// consumes already drew down, capped by the consume limit per instruction, and zero while
+/// its consume cooldown runs
+pub fn effective_allocation(state: &TokenWhitelist, account: &Pubkey, slot: u64) -> Result<u64, TokenWhitelistError> {
+    let allocation_amount = allocation(state, account, ACTIVE_ROUND)?;
+    let limits = state.extension.consume_limits;
+    let last_consume_slot = state.extension.last_consume_slots.get(&account.to_string()).copied();
+    if limits.check(0, slot, last_consume_slot).is_err() {
+        return Ok(0);
+    }
+    match limits.max_consume_per_tx {
+        0 => Ok(allocation_amount),
+        max_consume_per_tx => Ok(allocation_amount.min(max_consume_per_tx)),
+    }
+}
+
+/// Checks a self registration of `account` at `slot` and returns the registration terms. A
+/// registration carries no attestation, so none is taken while attestations are required.
+pub fn check_register(
+    state: &TokenWhitelist,
+    account: &Pubkey,
+    slot: u64,
+) -> Result<Registration, TokenWhitelistError> {
+    check_initialized(state)?;
+    let registration = match state.extension.registration {
+        Some(registration) if registration.is_open(slot) => registration,
+        _ => return Err(TokenWhitelistError::RegistrationClosed),
+    };
+    if state.extension.require_attestation {
+        return Err(TokenWhitelistError::AttestationRequired);
+    }
+    if state.whitelist_map.contains_key(&account.to_string()) {
+        return Err(TokenWhitelistError::AlreadyRegistered);
+    }
+    state.extension.allocation_bounds.check(registration.registration_allocation)?;
+    Ok(registration)
+}
+
+/// Checks that an account about to be added can sign for itself when the whitelist only takes
+/// wallets: owned by the system program and on the ed25519 curve. An account never funded
+/// counts as system owned.
+pub fn check_whitelist_target(
+    state: &TokenWhitelist,
+    account: &Pubkey,
+    account_owner: &Pubkey,
+) -> Result<(), TokenWhitelistError> {
+    if state.extension.only_wallets && (*account_owner != system_program::id() || !util::is_on_curve(account)) {
+        return Err(TokenWhitelistError::InvalidWhitelistTarget);
+    }
+    Ok(())
+}
+
+/// Refuses an add of `account` while it holds an entry in `sibling`, one of the whitelists
+/// linked by AssertNotInOther
+pub fn check_not_in_sibling(sibling: &TokenWhitelist, account: &Pubkey) -> Result<(), TokenWhitelistError> {
+    if sibling.whitelist_map.contains_key(&account.to_string()) {
+        return Err(TokenWhitelistError::DuplicateAcrossWhitelists);
+    }
+    Ok(())
+}
+
+/// Checks `account` against two whitelists, see AssertMembershipAcross
+pub fn check_membership_across(
+    first: &TokenWhitelist,
+    second: &TokenWhitelist,
+    account: &Pubkey,
+    require_in_first: bool,
+    require_absent_in_second: bool,
+) -> Result<(), TokenWhitelistError> {
+    check_initialized(first)?;
+    check_initialized(second)?;
+    let key = account.to_string();
+    if require_in_first && !first.whitelist_map.contains_key(&key) {
+        return Err(TokenWhitelistError::NotInRequiredWhitelist);
+    }
+    if require_absent_in_second && second.whitelist_map.contains_key(&key) {
+        return Err(TokenWhitelistError::InExcludedWhitelist);
+    }
+    Ok(())
+}
+
+/// Checks that `proof` leads from the leaf of `account` and `allocation` at `leaf_index` to the
+/// stored merkle root, hashing with the kind stored next to it
+#[cfg(feature = "merkle")]
+pub fn check_merkle_membership(
+    state: &TokenWhitelist,
+    leaf_index: u32,
+    account: &Pubkey,
+    allocation: u64,
+    proof: &[[u8; 32]],
+) -> Result<(), TokenWhitelistError> {
+    check_initialized(state)?;
+    let merkle_root = state.extension.merkle_root.ok_or(TokenWhitelistError::AccountNotWhitelisted)?;
+    let kind = HashKind::from_u8(merkle_root.hash_kind)?;
+    if !merkle::verify(kind, &merkle_root.root, merkle::leaf(kind, leaf_index, account, allocation), proof) {
+        return Err(TokenWhitelistError::AccountNotWhitelisted);
+    }
+    Ok(())
+}
+
+/// The compressed tree of the whitelist, refused unless `tree` is it
+#[cfg(feature = "compression")]
+pub fn compressed_tree(state: &TokenWhitelist, tree: &Pubkey) -> Result<CompressedTree, TokenWhitelistError> {
+    check_initialized(state)?;
+    match state.extension.compressed_tree {
+        Some(compressed_tree) if compressed_tree.tree == *tree => Ok(compressed_tree),
+        _ => Err(TokenWhitelistError::CompressedTreeMismatch),
+    }
+}
+
+/// Checks an append of `allocation_amount` to the compressed tree `tree` and returns the tree,
+/// whose leaf count is the index the leaf gets. The owner is checked by the caller, as for
+/// every owner instruction.
+#[cfg(feature = "compression")]
+pub fn check_compressed_append(
+    state: &TokenWhitelist,
+    tree: &Pubkey,
+    allocation_amount: u64,
+) -> Result<CompressedTree, TokenWhitelistError> {
+    let compressed_tree = compressed_tree(state, tree)?;
+    if compressed_tree.leaf_count as u64 >= TREE_CAPACITY {
+        return Err(TokenWhitelistError::CompressedTreeFull);
+    }
+    state.extension.allocation_bounds.check(allocation_amount)?;
+    check_zero_allocation(state, allocation_amount)?;
+    Ok(compressed_tree)
+}
+
+/// Checks a replacement of the leaf at `leaf_index` of the compressed tree `tree` by one
+/// holding `allocation_amount` and returns the tree. Whether the leaf holds what the
+/// instruction claims is left to the proof the compression program checks.
+#[cfg(feature = "compression")]
+pub fn check_compressed_replace(
+    state: &TokenWhitelist,
+    tree: &Pubkey,
+    leaf_index: u32,
+    allocation_amount: u64,
+) -> Result<CompressedTree, TokenWhitelistError> {
+    let compressed_tree = compressed_tree(state, tree)?;
+    if leaf_index >= compressed_tree.leaf_count {
+        return Err(TokenWhitelistError::AccountNotWhitelisted);
+    }
+    state.extension.allocation_bounds.check(allocation_amount)?;
+    check_zero_allocation(state, allocation_amount)?;
+    Ok(compressed_tree)
+}
+
+/// Refuses an entry for the whitelist itself or the program, keys an operator pasted by mistake
+/// that no participant holds, and for the owner once SetOwnerEntryAllowed disallowed it
+pub fn check_target_identity(
+    state: &TokenWhitelist,
+    whitelist: &Pubkey,
+    program_id: &Pubkey,
+    account: &Pubkey,
+) -> Result<(), TokenWhitelistError> {
+    let owner_refused = state.extension.owner_entry_disabled && *account == state.init_pubkey;
+    if account == whitelist || account == program_id || owner_refused {
+        return Err(TokenWhitelistError::InvalidWhitelistTarget);
+    }
+    Ok(())
+}
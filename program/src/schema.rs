@@ -0,0 +1,86 @@
+//! Borsh schemas of the types the program writes for clients to decode: the return data, the
+//! events and the config account (after its discriminator), rendered as the JSON checked in under
+//! `schemas/`. Instructions are packed by hand, with trailing optional fields and u8 lengths, so
+//! they have no Borsh schema; the hex fixtures under `tests/fixtures/instructions` pin them.
+
+use borsh::{
+    schema::{BorshSchemaContainer, Definition, Fields},
+    BorshSchema,
+};
+use crate::{
+    event::WhitelistEvent,
+    state::{
+        AttestedEntriesPage, ClosedWhitelist, EntriesPage, ProgramVersion, RepairReport, RequiredRent,
+        WhitelistConfig, WhitelistStats, WhitelistStatsReport,
+    },
+};
+
+use std::collections::BTreeMap;
+
+/// Schema container of every wire type, by the name of its file under `schemas/`
+pub fn wire_schemas() -> Vec<(&'static str, BorshSchemaContainer)> {
+    vec![
+        ("attested_entries_page", AttestedEntriesPage::schema_container()),
+        ("closed_whitelist", ClosedWhitelist::schema_container()),
+        ("entries_page", EntriesPage::schema_container()),
+        ("program_version", ProgramVersion::schema_container()),
+        ("repair_report", RepairReport::schema_container()),
+        ("required_rent", RequiredRent::schema_container()),
+        ("whitelist_config", WhitelistConfig::schema_container()),
+        ("whitelist_event", WhitelistEvent::schema_container()),
+        ("whitelist_stats", WhitelistStats::schema_container()),
+        ("whitelist_stats_report", WhitelistStatsReport::schema_container()),
+    ]
+}
+
+/// The container as JSON, one definition per line in declaration order so that a change shows as
+/// a small diff
+pub fn to_json(container: &BorshSchemaContainer) -> String {
+    let definitions: BTreeMap<_, _> = container.definitions.iter().collect();
+    let lines: Vec<String> = definitions
+        .iter()
+        .map(|(declaration, definition)| format!("    {}: {}", quote(declaration), definition_json(definition)))
+        .collect();
+    format!(
+        "{{\n  \"declaration\": {},\n  \"definitions\": {{\n{}\n  }}\n}}\n",
+        quote(&container.declaration),
+        lines.join(",\n")
+    )
+}
+
+fn definition_json(definition: &Definition) -> String {
+    match definition {
+        Definition::Array {length, elements} => {
+            format!(r#"{{"Array": {{"length": {}, "elements": {}}}}}"#, length, quote(elements))
+        }
+        Definition::Sequence {elements} => format!(r#"{{"Sequence": {{"elements": {}}}}}"#, quote(elements)),
+        Definition::Tuple {elements} => {
+            format!(r#"{{"Tuple": {{"elements": {}}}}}"#, list(elements.iter().map(|element| quote(element))))
+        }
+        Definition::Enum {variants} => {
+            let variants = list(variants.iter().map(|(name, declaration)| pair(name, declaration)));
+            format!(r#"{{"Enum": {{"variants": {}}}}}"#, variants)
+        }
+        Definition::Struct {fields: Fields::NamedFields(fields)} => {
+            let fields = list(fields.iter().map(|(name, declaration)| pair(name, declaration)));
+            format!(r#"{{"Struct": {{"named_fields": {}}}}}"#, fields)
+        }
+        Definition::Struct {fields: Fields::UnnamedFields(fields)} => {
+            format!(r#"{{"Struct": {{"unnamed_fields": {}}}}}"#, list(fields.iter().map(|field| quote(field))))
+        }
+        Definition::Struct {fields: Fields::Empty} => r#"{"Struct": {}}"#.to_string(),
+    }
+}
+
+/// Names and declarations are Rust identifiers and type names, nothing in them needs escaping
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text)
+}
+
+fn pair(name: &str, declaration: &str) -> String {
+    format!("[{}, {}]", quote(name), quote(declaration))
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(", "))
+}
@@ -0,0 +1,268 @@
+//! Running the processor off chain over caller-owned buffers, without a bank or the SBF VM,
+//! enabled by the `simulator` feature
+
+use arrayref::array_ref;
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::AccountInfo,
+    clock::{Clock, Epoch},
+    entrypoint::{MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::SystemInstruction,
+    system_program,
+};
+use crate::{event::WhitelistEvent, processor::Processor, state::TokenWhitelist};
+
+use std::{
+    mem::size_of,
+    slice,
+    sync::{Arc, Mutex},
+};
+
+/// Syscall stubs are process wide, simulations take turns installing theirs
+static SIMULATION: Mutex<()> = Mutex::new(());
+
+/// An account of the simulated instruction
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Vec<u8>, // ignored for the whitelist, whose data are the state bytes
+}
+
+/// Accounts of the simulated instruction, in instruction order, and the sysvars it reads
+#[derive(Clone, Debug)]
+pub struct SimAccounts {
+    pub program_id: Pubkey,
+    pub whitelist: Pubkey,
+    pub accounts: Vec<SimAccount>,
+    pub clock: Clock,
+    pub rent: Rent,
+}
+
+/// What a successful instruction left behind
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimOutcome {
+    pub state: Option<TokenWhitelist>, // none once the bytes no longer decode, as after an archive
+    pub accounts: Vec<(u64, Vec<u8>)>, // lamports and data of every account, in instruction order
+    pub events: Vec<WhitelistEvent>,
+    pub return_data: Option<Vec<u8>>,
+    pub logs: Vec<String>,
+}
+
+/// Runs the instruction like the chain would and, if it succeeds, writes the whitelist data it
+/// leaves into `state_bytes`. A failed instruction changes nothing, like a failed transaction.
+/// System transfers are the only cross-program invocation carried out.
+pub fn simulate_instruction(
+    state_bytes: &mut Vec<u8>,
+    accounts_meta: &SimAccounts,
+    data: &[u8],
+) -> Result<SimOutcome, ProgramError> {
+    let mut buffers: Vec<SerializedAccount> = Vec::new();
+    for account in &accounts_meta.accounts {
+        if !buffers.iter().any(|buffer| buffer.pubkey() == account.pubkey) {
+            let data = if account.pubkey == accounts_meta.whitelist { &state_bytes[..] } else { &account.data[..] };
+            buffers.push(SerializedAccount::new(account, data));
+        }
+    }
+
+    let recorder = Arc::new(Mutex::new(Recorder::default()));
+    let result = {
+        let _turn = SIMULATION.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _stubs = StubsGuard::install(SimStubs {
+            program_id: accounts_meta.program_id,
+            clock: accounts_meta.clock.clone(),
+            rent: accounts_meta.rent,
+            recorder: recorder.clone(),
+        });
+        let unique: Vec<AccountInfo> = buffers.iter_mut().map(SerializedAccount::info).collect();
+        let infos: Vec<AccountInfo> = accounts_meta.accounts.iter()
+            .map(|account| unique.iter().find(|info| *info.key == account.pubkey).unwrap().clone())
+            .collect();
+        Processor::process(&accounts_meta.program_id, &infos, data)
+    };
+    result?;
+
+    let accounts: Vec<(u64, Vec<u8>)> = accounts_meta.accounts.iter()
+        .map(|account| {
+            let buffer = buffers.iter().find(|buffer| buffer.pubkey() == account.pubkey).unwrap();
+            (buffer.lamports, buffer.data().to_vec())
+        })
+        .collect();
+    if let Some(buffer) = buffers.iter().find(|buffer| buffer.pubkey() == accounts_meta.whitelist) {
+        *state_bytes = buffer.data().to_vec();
+    }
+    let recorder = Arc::try_unwrap(recorder).ok().unwrap().into_inner().unwrap();
+    Ok(SimOutcome {
+        state: TokenWhitelist::unpack_from_slice(state_bytes).ok(),
+        accounts,
+        events: recorder.events,
+        return_data: recorder.return_data.map(|(_, data)| data),
+        logs: recorder.logs,
+    })
+}
+
+/// An account laid out the way the runtime serializes program input: the original data length
+/// four bytes ahead of the key, the current length eight bytes ahead of the data and room
+/// behind it, which is where `AccountInfo::realloc` reads and writes
+struct SerializedAccount {
+    key: Vec<u64>, // padding, original length as u32, key
+    data: Vec<u64>, // length as u64, data, MAX_PERMITTED_DATA_INCREASE spare bytes
+    lamports: u64,
+    owner: Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+const KEY_OFFSET: usize = 8;
+const DATA_OFFSET: usize = 8;
+
+impl SerializedAccount {
+    fn new(account: &SimAccount, data: &[u8]) -> Self {
+        let mut key = vec![0u64; (KEY_OFFSET + size_of::<Pubkey>()) / 8];
+        let key_bytes = bytes_mut(&mut key);
+        key_bytes[KEY_OFFSET - 4..KEY_OFFSET].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        key_bytes[KEY_OFFSET..].copy_from_slice(account.pubkey.as_ref());
+
+        let mut buffer = vec![0u64; (DATA_OFFSET + data.len() + MAX_PERMITTED_DATA_INCREASE).div_ceil(8)];
+        let data_bytes = bytes_mut(&mut buffer);
+        data_bytes[..DATA_OFFSET].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        data_bytes[DATA_OFFSET..DATA_OFFSET + data.len()].copy_from_slice(data);
+
+        SerializedAccount {
+            key,
+            data: buffer,
+            lamports: account.lamports,
+            owner: account.owner,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }
+    }
+
+    fn pubkey(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![bytes(&self.key), KEY_OFFSET, 32])
+    }
+
+    fn data(&self) -> &[u8] {
+        let bytes = bytes(&self.data);
+        let len = u64::from_le_bytes(*array_ref![bytes, 0, DATA_OFFSET]) as usize;
+        &bytes[DATA_OFFSET..DATA_OFFSET + len]
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        let len = self.data().len();
+        // the key and the data stay inside buffers that outlive the returned info
+        let key = unsafe { &*(self.key.as_ptr().cast::<u8>().add(KEY_OFFSET).cast::<Pubkey>()) };
+        let data = unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr().cast::<u8>().add(DATA_OFFSET), len) };
+        AccountInfo::new(
+            key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            data,
+            &self.owner,
+            false,
+            Epoch::default(),
+        )
+    }
+}
+
+fn bytes(words: &[u64]) -> &[u8] {
+    unsafe { slice::from_raw_parts(words.as_ptr().cast::<u8>(), words.len() * 8) }
+}
+
+fn bytes_mut(words: &mut [u64]) -> &mut [u8] {
+    unsafe { slice::from_raw_parts_mut(words.as_mut_ptr().cast::<u8>(), words.len() * 8) }
+}
+
+#[derive(Default)]
+struct Recorder {
+    logs: Vec<String>,
+    events: Vec<WhitelistEvent>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+}
+
+struct SimStubs {
+    program_id: Pubkey,
+    clock: Clock,
+    rent: Rent,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl SyscallStubs for SimStubs {
+    fn sol_log(&self, message: &str) {
+        self.recorder.lock().unwrap().logs.push(message.to_string());
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let mut recorder = self.recorder.lock().unwrap();
+        recorder.events.extend(fields.iter().filter_map(|field| WhitelistEvent::try_from_slice(field).ok()));
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.recorder.lock().unwrap().return_data = Some((self.program_id, data.to_vec())).filter(|_| !data.is_empty());
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.recorder.lock().unwrap().return_data.clone()
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *var_addr.cast::<Clock>() = self.clock.clone() };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *var_addr.cast::<Rent>() = self.rent };
+        SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        let lamports = match bincode::deserialize(&instruction.data) {
+            Ok(SystemInstruction::Transfer {lamports}) if instruction.program_id == system_program::id() => lamports,
+            _ => return Err(ProgramError::IncorrectProgramId),
+        };
+        let account = |index: usize| {
+            let pubkey = instruction.accounts.get(index).ok_or(ProgramError::NotEnoughAccountKeys)?.pubkey;
+            account_infos.iter().find(|info| *info.key == pubkey).ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        let (from, to) = (account(0)?, account(1)?);
+        if !from.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let from_lamports = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+        **from.lamports.borrow_mut() = from_lamports;
+        let to_lamports = to.lamports().checked_add(lamports).ok_or(ProgramError::ArithmeticOverflow)?;
+        **to.lamports.borrow_mut() = to_lamports;
+        Ok(())
+    }
+}
+
+/// Puts the previous stubs back, even when the processor panics
+struct StubsGuard(Option<Box<dyn SyscallStubs>>);
+
+impl StubsGuard {
+    fn install(stubs: SimStubs) -> Self {
+        StubsGuard(Some(set_syscall_stubs(Box::new(stubs))))
+    }
+}
+
+impl Drop for StubsGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            set_syscall_stubs(previous);
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use solana_program::{
+    keccak,
+    program::MAX_RETURN_DATA,
     program_error::ProgramError,
     program_pack::{IsInitialized},
     pubkey::Pubkey,
 };
+use crate::{error::TokenWhitelistError, FEATURE_BITS, VERSION};
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, str::FromStr};
 use borsh::{BorshDeserialize, BorshSerialize};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
@@ -15,6 +18,61 @@ const MAP_LENGTH: usize = 4;
 const MAP_BYTES: usize = 5116;
 const ACCOUNT_STATE_SPACE: usize =
     INITIALIZED_BYTES + PUBKEY_BYTES + WHITELIST_SIZE_BYTES + MAP_LENGTH + MAP_BYTES; // 5161 bytes
+const EXTENSION_LENGTH: usize = 4;
+
+/// Map bytes of an entry beyond its key, the borsh string length and the u64 allocation
+pub const MAP_ENTRY_OVERHEAD: usize = 4 + 8;
+/// Longest base58 encoding of a pubkey, the keys of the map
+const MAX_KEY_LEN: usize = 44;
+
+/// Number of rounds an entry holds an allocation for
+pub const MAX_ROUNDS: usize = 4;
+/// Round argument selecting the active round
+pub const ACTIVE_ROUND: u8 = 255;
+
+/// Size an archived whitelist is shrunk to, see [WhitelistArchive](struct.WhitelistArchive.html)
+pub const ARCHIVE_SPACE: usize = 96;
+/// First byte of an archived whitelist, where a whitelist holds is_initialized
+const ARCHIVE_DISCRIMINATOR: u8 = 2;
+
+/// Bytes of a claim bitmap before its bits: the discriminator, the whitelist, the merkle root
+/// and the leaf count
+pub const CLAIM_BITMAP_HEADER: usize = 1 + PUBKEY_BYTES + 32 + 4; // 69 bytes
+/// First byte of a claim bitmap, where a whitelist holds is_initialized
+const CLAIM_BITMAP_DISCRIMINATOR: u8 = 3;
+
+/// First seed of a whitelist at a program address, followed by the owner, a label and the bump
+pub const WHITELIST_SEED: &[u8] = b"token_whitelist";
+
+/// First seed of the config account of a whitelist, followed by the whitelist and the bump
+pub const CONFIG_SEED: &[u8] = b"config";
+/// Account size of a whitelist config, leaving room for policy fields added later
+pub const CONFIG_SPACE: usize = 128;
+/// First byte of a whitelist config, where a whitelist holds is_initialized
+const CONFIG_DISCRIMINATOR: u8 = 4;
+
+/// Campaign of entries added without one
+pub const NO_CAMPAIGN: [u8; 8] = [0; 8];
+
+/// Attestation of entries added without one
+pub const NO_ATTESTATION: [u8; 32] = [0; 32];
+
+/// Note commitment of entries without one
+pub const NO_NOTE_COMMITMENT: [u8; 32] = [0; 32];
+
+/// max_whitelist_size of a whitelist limited by the room of its map alone, see
+/// TokenWhitelist::entry_limit
+pub const UNLIMITED_WHITELIST_SIZE: u64 = u64::MAX;
+
+/// Most sibling whitelists AssertNotInOther links, each one an account every add has to pass
+pub const MAX_SIBLING_WHITELISTS: usize = 4;
+
+/// Most entries one ListEntries page holds within the return data cap, after the u32 total and
+/// the u32 vector length
+pub const MAX_LIST_ENTRIES: usize = (MAX_RETURN_DATA - 8) / (PUBKEY_BYTES + 8); // 25 entries
+
+/// Most entries one ListAttestedEntries page holds, each carrying its 32 byte attestation
+pub const MAX_LIST_ATTESTED_ENTRIES: usize = (MAX_RETURN_DATA - 8) / (PUBKEY_BYTES + 8 + 32); // 14 entries
 
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct TokenWhitelist {
@@ -22,6 +80,559 @@ pub struct TokenWhitelist {
     pub init_pubkey: Pubkey,
     pub max_whitelist_size: u64,
     pub whitelist_map: BTreeMap<String, u64>,
+    pub extension: TokenWhitelistExtension,
+}
+
+/// State added after the original layout was deployed. It lives, length prefixed, in the
+/// account bytes following ACCOUNT_STATE_SPACE, so accounts allocated with exactly
+/// ACCOUNT_STATE_SPACE bytes keep working but can only hold the default extension.
+/// New fields must be appended at the end so that older extensions still decode.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize)]
+pub struct TokenWhitelistExtension {
+    pub delegate: Option<Delegate>,
+    pub registration: Option<Registration>,
+    pub registration_deposits: BTreeMap<String, u64>, // lamports deposited by each self registered account
+    pub lottery: Option<Lottery>,
+    pub consume_limits: ConsumeLimits,
+    pub last_consume_slots: BTreeMap<String, u64>, // slot of the latest consume by each account
+    pub entries_hash: [u8; 32], // hash_entries_map of the map, see update_entries_hash
+    pub allocation_decimals: u8, // decimals of allocation amounts, 0 for whitelists initialized without units
+    pub allocation_mint: Option<Pubkey>, // mint the decimals were copied from
+    pub allocation_bounds: AllocationBounds,
+    pub active_round: u8, // round used when an instruction passes ACTIVE_ROUND
+    pub round_allocations: BTreeMap<String, [u64; MAX_ROUNDS - 1]>, // allocations in rounds 1 and up, round 0 lives in the map
+    pub strict_token_checks: bool, // reject token accounts whose tokens cannot move freely, see SetStrictTokenChecks
+    pub merkle_root: Option<MerkleRoot>, // root proofs are verified against, see SetMerkleRoot
+    pub pda_bump: Option<u8>, // bump of a whitelist living at a program address, see signer_seeds
+    pub only_wallets: bool, // refuse adding accounts that cannot sign, see SetOnlyWallets
+    pub config_bump: Option<u8>, // bump of the config account holding the policy once InitConfig ran
+    pub self_zero_disabled: bool, // SetAllocationToZero also needs the owner signature, see SetSelfZeroAllowed
+    pub entry_campaigns: BTreeMap<String, [u8; 8]>, // campaign each entry was added for, NO_CAMPAIGN entries left out
+    pub zero_allocation_disabled: bool, // adds of a zero allocation are refused, see SetZeroAllocationAllowed
+    pub token_gate: Option<TokenGate>, // balance of a mint required to be whitelisted, see SetTokenGate
+    pub mutation_nonce: u64, // writes of the whitelist so far, see record_mutation
+    pub compressed_tree: Option<CompressedTree>, // tree holding the entries in compressed mode, see InitCompressedWhitelist
+    pub stats: WhitelistStats, // adds and removes so far, see record_add and record_remove
+    pub linked_accounts: LinkedAccounts, // companion accounts attached to the whitelist
+    pub entry_attestations: BTreeMap<String, [u8; 32]>, // attestation of each entry, NO_ATTESTATION entries left out
+    pub require_attestation: bool, // adds leaving an entry without attestation are refused, see SetAttestationRequired
+    pub sibling_whitelists: Vec<Pubkey>, // whitelists an added account must be absent from, see AssertNotInOther
+    pub owner_entry_disabled: bool, // adds of the whitelist owner are refused, see SetOwnerEntryAllowed
+    pub created_at_slot: u64, // slot the whitelist was initialized at, see record_creation
+    pub creator: Pubkey, // signer that initialized the whitelist, kept should the owner change
+    pub entry_note_commitments: BTreeMap<String, [u8; 32]>, // commitment to a note of each entry, see note_commitment
+    pub registration_rate_limit: RegistrationRateLimit, // cap on RegisterSelf per slot window, see SetRegistrationRateLimit
+    pub restrict_enumeration: bool, // instructions listing entries need the owner or the delegate, see SetEnumerationRestricted
+}
+
+impl BorshDeserialize for TokenWhitelistExtension {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(TokenWhitelistExtension {
+            delegate: read_or_default(buf)?,
+            registration: read_or_default(buf)?,
+            registration_deposits: read_or_default(buf)?,
+            lottery: read_or_default(buf)?,
+            consume_limits: read_or_default(buf)?,
+            last_consume_slots: read_or_default(buf)?,
+            entries_hash: read_or_default(buf)?,
+            allocation_decimals: read_or_default(buf)?,
+            allocation_mint: read_or_default(buf)?,
+            allocation_bounds: read_or_default(buf)?,
+            active_round: read_or_default(buf)?,
+            round_allocations: read_or_default(buf)?,
+            strict_token_checks: read_or_default(buf)?,
+            merkle_root: read_or_default(buf)?,
+            pda_bump: read_or_default(buf)?,
+            only_wallets: read_or_default(buf)?,
+            config_bump: read_or_default(buf)?,
+            self_zero_disabled: read_or_default(buf)?,
+            entry_campaigns: read_or_default(buf)?,
+            zero_allocation_disabled: read_or_default(buf)?,
+            token_gate: read_or_default(buf)?,
+            mutation_nonce: read_or_default(buf)?,
+            compressed_tree: read_or_default(buf)?,
+            stats: read_or_default(buf)?,
+            linked_accounts: read_or_default(buf)?,
+            entry_attestations: read_or_default(buf)?,
+            require_attestation: read_or_default(buf)?,
+            sibling_whitelists: read_or_default(buf)?,
+            owner_entry_disabled: read_or_default(buf)?,
+            created_at_slot: read_or_default(buf)?,
+            creator: read_or_default(buf)?,
+            entry_note_commitments: read_or_default(buf)?,
+            registration_rate_limit: read_or_default(buf)?,
+            restrict_enumeration: read_or_default(buf)?,
+        })
+    }
+}
+
+/// Signer allowed to add to the whitelist on behalf of the owner, within limits
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct Delegate {
+    pub delegate_pubkey: Pubkey,
+    pub remaining_adds: u32, // decremented on every add made by the delegate
+    pub max_allocation_per_add: u64, // maximum allocation amount the delegate can grant in one add
+}
+
+impl Delegate {
+    /// Checks an add against the delegate limits and uses up one of the remaining adds
+    pub fn use_add(&mut self, allocation_amount: u64) -> Result<(), TokenWhitelistError> {
+        if allocation_amount > self.max_allocation_per_add {
+            return Err(TokenWhitelistError::DelegateAllocationExceeded);
+        }
+        if self.remaining_adds == 0 {
+            return Err(TokenWhitelistError::DelegateAddsExhausted);
+        }
+        self.remaining_adds -= 1;
+        Ok(())
+    }
+}
+
+/// Terms under which accounts can add themselves to the whitelist
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct Registration {
+    pub registration_end_slot: u64, // registration is open before this slot
+    pub registration_allocation: u64, // allocation amount given to self registered accounts
+    pub registration_deposit_lamports: u64, // refundable deposit paid by self registered accounts
+}
+
+impl Registration {
+    pub fn is_open(&self, slot: u64) -> bool {
+        slot < self.registration_end_slot
+    }
+}
+
+/// Cap on self registrations per window of slots, with the count of the current window.
+/// Adds by the owner or the delegate are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct RegistrationRateLimit {
+    pub max_registrations_per_window: u16, // 0 for no limit
+    pub window_slots: u32, // length of a window in slots
+    pub window_start_slot: u64, // slot the current window started at
+    pub registrations_in_window: u16, // self registrations in the current window
+}
+
+impl RegistrationRateLimit {
+    /// Counts a self registration at `slot`. A window starts at the first registration after the
+    /// previous window is over.
+    pub fn register(&mut self, slot: u64) -> Result<(), TokenWhitelistError> {
+        if self.max_registrations_per_window == 0 {
+            return Ok(());
+        }
+        if self.registrations_in_window == 0
+            || slot >= self.window_start_slot.saturating_add(self.window_slots as u64)
+        {
+            self.window_start_slot = slot;
+            self.registrations_in_window = 0;
+        }
+        if self.registrations_in_window >= self.max_registrations_per_window {
+            return Err(TokenWhitelistError::RegistrationRateLimited);
+        }
+        self.registrations_in_window += 1;
+        Ok(())
+    }
+}
+
+/// Record of the one-shot lottery draw, enough to re-verify it off-chain
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct Lottery {
+    pub seed: [u8; 32], // seed supplied by the whitelist owner
+    pub slot: u64, // slot of the slot hash mixed into the draw
+    pub slot_hash: [u8; 32], // most recent slot hash at the time of the draw
+    pub winners: u64, // number of winners requested
+}
+
+/// Throttles on ConsumeAllocation, zero disables a limit
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct ConsumeLimits {
+    pub max_consume_per_tx: u64, // maximum amount consumed by one instruction
+    pub min_slots_between_consumes: u64, // slots an account must wait between consumes
+}
+
+impl ConsumeLimits {
+    /// Checks a consume of `amount` at `slot` by an account that last consumed at
+    /// `last_consume_slot`
+    pub fn check(&self, amount: u64, slot: u64, last_consume_slot: Option<u64>) -> Result<(), TokenWhitelistError> {
+        if self.max_consume_per_tx > 0 && amount > self.max_consume_per_tx {
+            return Err(TokenWhitelistError::ConsumeLimitExceeded);
+        }
+        if let Some(last_consume_slot) = last_consume_slot {
+            if slot < last_consume_slot.saturating_add(self.min_slots_between_consumes) {
+                return Err(TokenWhitelistError::ConsumeCooldownActive);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Balance of a gating mint an account must hold to be whitelisted. RegisterSelf is always
+/// gated, adds by the owner or the delegate only with gate_owner_adds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct TokenGate {
+    pub gating_mint: Pubkey,
+    pub gating_minimum: u64, // smallest balance accepted, in base units of the gating mint
+    pub gate_owner_adds: bool,
+}
+
+/// Range of allocation amounts accepted when adding or updating an entry, zero disables a bound
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct AllocationBounds {
+    pub min_allocation: u64, // smallest allocation amount accepted
+    pub max_allocation: u64, // largest allocation amount accepted
+}
+
+impl AllocationBounds {
+    /// Checks an allocation amount being added, both bounds are inclusive
+    pub fn check(&self, allocation_amount: u64) -> Result<(), TokenWhitelistError> {
+        if allocation_amount < self.min_allocation {
+            return Err(TokenWhitelistError::AllocationBelowMinimum);
+        }
+        if self.max_allocation > 0 && allocation_amount > self.max_allocation {
+            return Err(TokenWhitelistError::AllocationAboveMaximum);
+        }
+        Ok(())
+    }
+
+    /// A minimum above a non-zero maximum would refuse every amount
+    pub fn is_valid(&self) -> bool {
+        self.max_allocation == 0 || self.min_allocation <= self.max_allocation
+    }
+}
+
+/// Root of a Merkle tree over (wallet, allocation) leaves, see the merkle module
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct MerkleRoot {
+    pub root: [u8; 32],
+    pub hash_kind: u8, // HashKind the tree was built with, 0 for keccak and 1 for sha256
+}
+
+/// Concurrent Merkle tree of spl-account-compression holding the entries of a whitelist in
+/// compressed mode, see the compression module
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct CompressedTree {
+    pub tree: Pubkey,
+    pub authority_bump: u8, // bump of the tree authority, see compression::find_tree_authority
+    pub leaf_count: u32, // leaves appended so far, the index the next one gets
+}
+
+/// Churn of a whitelist, informational only: nothing is refused on them. Like mutation_nonce
+/// they stay 0 in accounts with no room for the extension.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct WhitelistStats {
+    pub total_adds: u64, // adds that went through, those updating an entry included
+    pub total_removes: u64, // removes that took an entry out
+    pub last_mutation_slot: u64, // slot of the latest add or remove
+}
+
+/// Return data of GetWhitelistStats: the stats, then when and by whom the whitelist was created,
+/// both 0 for whitelists initialized before they were recorded. The first 24 bytes are the
+/// WhitelistStats earlier programs returned alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct WhitelistStatsReport {
+    pub stats: WhitelistStats,
+    pub created_at_slot: u64,
+    pub creator: Pubkey,
+}
+
+/// Addresses of the companion accounts attached to a whitelist. Handlers taking a companion
+/// refuse any other account in its place with UnlinkedCompanionAccount, so a look-alike account
+/// holding the same data cannot stand in for it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct LinkedAccounts {
+    pub config: Option<Pubkey>, // config account written by InitConfig, see WhitelistConfig::find_address
+    pub claim_bitmap: Option<Pubkey>, // claim bitmap written by the latest InitClaimBitmap
+}
+
+/// Fixed fields of a whitelist and its entry count, see
+/// [unpack_header](struct.TokenWhitelist.html#method.unpack_header)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WhitelistHeader {
+    pub is_initialized: bool,
+    pub init_pubkey: Pubkey,
+    pub max_whitelist_size: u64,
+    pub entries: u32, // entry count the map starts with
+}
+
+/// Page of entries written to the return data by ListEntries and ListEntriesByCampaign
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct EntriesPage {
+    pub total: u32, // number of entries in the whitelist, or in the campaign listed
+    pub entries: Vec<(Pubkey, u64)>, // entries in key order, starting at the requested offset
+}
+
+/// Page of entries with their attestations written to the return data by ListAttestedEntries,
+/// NO_ATTESTATION for entries without one
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct AttestedEntriesPage {
+    pub total: u32, // number of entries in the whitelist
+    pub entries: Vec<(Pubkey, u64, [u8; 32])>, // entries in key order, starting at the requested offset
+}
+
+/// What a whitelist still held when CloseWhitelistAccount discarded it, written to the return data
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct ClosedWhitelist {
+    pub entries_discarded: u32, // entries in the map
+    pub allocation_discarded: u128, // sum of the allocations in the map
+}
+
+/// What RepairState rebuilt a whitelist from, written to the return data and logged
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct RepairReport {
+    pub entries_recovered: u32, // entries in the rebuilt map
+    pub entries_dropped: u32, // entries that were not pubkeys, repeated a key, or were cut off
+    pub bytes_dropped: u32, // non-zero map bytes left after the last entry decoded
+}
+
+impl RepairReport {
+    pub fn dropped_anything(&self) -> bool {
+        self.entries_dropped > 0 || self.bytes_dropped > 0
+    }
+}
+
+/// Version of the deployed program written to the return data by GetVersion
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct ProgramVersion {
+    pub semver: [u16; 3], // crate version as [major, minor, patch]
+    pub feature_bits: u64, // capability bits, see FEATURE_BITS
+}
+
+impl ProgramVersion {
+    /// Version of this build
+    pub fn current() -> Self {
+        ProgramVersion {
+            semver: VERSION,
+            feature_bits: FEATURE_BITS,
+        }
+    }
+
+    pub fn has(&self, feature: u64) -> bool {
+        self.feature_bits & feature == feature
+    }
+}
+
+/// Size and rent exempt balance of a new whitelist written to the return data by
+/// GetRequiredRent
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct RequiredRent {
+    pub space: u64, // bytes to allocate, see TokenWhitelist::space_for
+    pub lamports: u64, // rent exempt minimum of that many bytes under the cluster rent
+}
+
+/// Permanent record left by ArchiveWhitelist: the discriminator, the owner, the entries hash
+/// and the slot of archival, zero padded to ARCHIVE_SPACE bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WhitelistArchive {
+    pub init_pubkey: Pubkey, // owner of the whitelist when archived
+    pub entries_hash: [u8; 32], // hash_entries_map of the final map
+    pub archived_slot: u64,
+}
+
+impl WhitelistArchive {
+    /// Whether the account data is an archive rather than a whitelist
+    pub fn is_archive(src: &[u8]) -> bool {
+        src.len() == ARCHIVE_SPACE && src[0] == ARCHIVE_DISCRIMINATOR
+    }
+
+    pub fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if !Self::is_archive(src) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, ARCHIVE_SPACE];
+        let (_, init_pubkey, entries_hash, archived_slot, _) = array_refs![src, 1, PUBKEY_BYTES, 32, 8, 23];
+        Ok(WhitelistArchive {
+            init_pubkey: Pubkey::new_from_array(*init_pubkey),
+            entries_hash: *entries_hash,
+            archived_slot: u64::from_le_bytes(*archived_slot),
+        })
+    }
+
+    pub fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() != ARCHIVE_SPACE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let dst = array_mut_ref![dst, 0, ARCHIVE_SPACE];
+        let (discriminator, init_pubkey, entries_hash, archived_slot, padding) =
+            mut_array_refs![dst, 1, PUBKEY_BYTES, 32, 8, 23];
+        discriminator[0] = ARCHIVE_DISCRIMINATOR;
+        init_pubkey.copy_from_slice(self.init_pubkey.as_ref());
+        *entries_hash = self.entries_hash;
+        *archived_slot = self.archived_slot.to_le_bytes();
+        *padding = [0; 23];
+        Ok(())
+    }
+}
+
+/// Account size of a claim bitmap over `leaf_count` leaves, one bit per leaf index
+pub const fn claim_bitmap_len(leaf_count: u32) -> usize {
+    CLAIM_BITMAP_HEADER + (leaf_count as usize).div_ceil(8)
+}
+
+/// Header of the account tracking which leaves of a merkle whitelist were claimed, followed by
+/// one bit per leaf index, the lowest bit of the first byte for leaf 0
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClaimBitmap {
+    pub whitelist: Pubkey, // whitelist whose merkle root the bitmap belongs to
+    pub root: [u8; 32], // merkle root at InitClaimBitmap, claims against another root are refused
+    pub leaf_count: u32,
+}
+
+impl ClaimBitmap {
+    pub fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < CLAIM_BITMAP_HEADER || src[0] != CLAIM_BITMAP_DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let header = array_ref![src, 0, CLAIM_BITMAP_HEADER];
+        let (_, whitelist, root, leaf_count) = array_refs![header, 1, PUBKEY_BYTES, 32, 4];
+        let bitmap = ClaimBitmap {
+            whitelist: Pubkey::new_from_array(*whitelist),
+            root: *root,
+            leaf_count: u32::from_le_bytes(*leaf_count),
+        };
+        if src.len() < claim_bitmap_len(bitmap.leaf_count) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(bitmap)
+    }
+
+    /// Writes the header, leaving the bits as they are
+    pub fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() < claim_bitmap_len(self.leaf_count) {
+            return Err(TokenWhitelistError::AccountTooSmall.into());
+        }
+        let header = array_mut_ref![dst, 0, CLAIM_BITMAP_HEADER];
+        let (discriminator, whitelist, root, leaf_count) = mut_array_refs![header, 1, PUBKEY_BYTES, 32, 4];
+        discriminator[0] = CLAIM_BITMAP_DISCRIMINATOR;
+        whitelist.copy_from_slice(self.whitelist.as_ref());
+        *root = self.root;
+        *leaf_count = self.leaf_count.to_le_bytes();
+        Ok(())
+    }
+
+    /// Whether the leaf at `leaf_index` was claimed, refusing indices past the leaf count
+    pub fn is_claimed(src: &[u8], leaf_index: u32) -> Result<bool, ProgramError> {
+        let bitmap = Self::unpack_from_slice(src)?;
+        if leaf_index >= bitmap.leaf_count {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (byte, mask) = Self::claim_bit(leaf_index);
+        Ok(src[byte] & mask != 0)
+    }
+
+    /// Sets the bit of `leaf_index`, failing with AlreadyClaimed if it is set
+    pub fn set_claimed(dst: &mut [u8], leaf_index: u32) -> Result<(), ProgramError> {
+        if Self::is_claimed(dst, leaf_index)? {
+            return Err(TokenWhitelistError::AlreadyClaimed.into());
+        }
+        let (byte, mask) = Self::claim_bit(leaf_index);
+        dst[byte] |= mask;
+        Ok(())
+    }
+
+    fn claim_bit(leaf_index: u32) -> (usize, u8) {
+        (CLAIM_BITMAP_HEADER + leaf_index as usize / 8, 1 << (leaf_index % 8))
+    }
+}
+
+/// Policy fields a config account takes over from the whitelist extension
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct WhitelistPolicy {
+    pub allocation_bounds: AllocationBounds,
+    pub consume_limits: ConsumeLimits,
+    pub active_round: u8,
+    pub strict_token_checks: bool,
+    pub only_wallets: bool,
+    pub self_zero_disabled: bool,
+    pub zero_allocation_disabled: bool,
+    pub token_gate: Option<TokenGate>,
+    pub require_attestation: bool,
+    pub owner_entry_disabled: bool,
+}
+
+impl WhitelistPolicy {
+    pub fn of(extension: &TokenWhitelistExtension) -> Self {
+        WhitelistPolicy {
+            allocation_bounds: extension.allocation_bounds,
+            consume_limits: extension.consume_limits,
+            active_round: extension.active_round,
+            strict_token_checks: extension.strict_token_checks,
+            only_wallets: extension.only_wallets,
+            self_zero_disabled: extension.self_zero_disabled,
+            zero_allocation_disabled: extension.zero_allocation_disabled,
+            token_gate: extension.token_gate,
+            require_attestation: extension.require_attestation,
+            owner_entry_disabled: extension.owner_entry_disabled,
+        }
+    }
+
+    /// Overwrites the policy fields of `extension`, so the rules read the config as they would
+    /// the whitelist
+    pub fn apply(&self, extension: &mut TokenWhitelistExtension) {
+        extension.allocation_bounds = self.allocation_bounds;
+        extension.consume_limits = self.consume_limits;
+        extension.active_round = self.active_round;
+        extension.strict_token_checks = self.strict_token_checks;
+        extension.only_wallets = self.only_wallets;
+        extension.self_zero_disabled = self.self_zero_disabled;
+        extension.zero_allocation_disabled = self.zero_allocation_disabled;
+        extension.token_gate = self.token_gate;
+        extension.require_attestation = self.require_attestation;
+        extension.owner_entry_disabled = self.owner_entry_disabled;
+    }
+}
+
+/// Account at the program address of CONFIG_SEED and a whitelist holding its policy, created by
+/// InitConfig so policy changes write this small account instead of the whitelist. Laid out as
+/// the discriminator followed by the borsh whitelist and policy, zero padded to CONFIG_SPACE.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "schema", derive(borsh::BorshSchema))]
+pub struct WhitelistConfig {
+    pub whitelist: Pubkey,
+    pub policy: WhitelistPolicy,
+}
+
+impl WhitelistConfig {
+    /// Address and bump of the config account of `whitelist`
+    pub fn find_address(program_id: &Pubkey, whitelist: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[CONFIG_SEED, whitelist.as_ref()], program_id)
+    }
+
+    /// Address of the config account of `whitelist` from its stored bump, one
+    /// `create_program_address` instead of searching for the bump again
+    pub fn address(program_id: &Pubkey, whitelist: &Pubkey, bump: u8) -> Result<Pubkey, ProgramError> {
+        Pubkey::create_program_address(&[CONFIG_SEED, whitelist.as_ref(), &[bump]], program_id)
+            .map_err(|_| ProgramError::InvalidSeeds)
+    }
+
+    pub fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        match src.split_first() {
+            Some((&CONFIG_DISCRIMINATOR, mut rest)) => {
+                Self::deserialize(&mut rest).map_err(|_| ProgramError::InvalidAccountData)
+            }
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let data = self.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+        if dst.len() < 1 + data.len() {
+            return Err(TokenWhitelistError::AccountTooSmall.into());
+        }
+        dst[0] = CONFIG_DISCRIMINATOR;
+        dst[1..1 + data.len()].copy_from_slice(&data);
+        Ok(())
+    }
 }
 
 impl IsInitialized for TokenWhitelist {
@@ -31,23 +642,399 @@ impl IsInitialized for TokenWhitelist {
 }
 
 impl TokenWhitelist {
+    /// Entries the map region holds whatever their keys, the ceiling for max_whitelist_size
+    pub const MAX_ENTRIES: u64 = max_entries(); // 91 entries
+    /// Smallest account holding the layout, which then has room for the default extension only
+    pub const MIN_SPACE: usize = ACCOUNT_STATE_SPACE;
+
+    /// Checks a max_whitelist_size asked of a new whitelist and returns the entries to size its
+    /// account for. Zero is refused, UNLIMITED_WHITELIST_SIZE is sized for MAX_ENTRIES and
+    /// other sizes must not exceed MAX_ENTRIES.
+    pub fn check_max_whitelist_size(max_whitelist_size: u64) -> Result<u64, TokenWhitelistError> {
+        match max_whitelist_size {
+            0 => Err(TokenWhitelistError::ZeroWhitelistSize),
+            UNLIMITED_WHITELIST_SIZE => Ok(Self::MAX_ENTRIES),
+            size if size > Self::MAX_ENTRIES => Err(TokenWhitelistError::TokenWhitelistSizeExceeds),
+            size => Ok(size),
+        }
+    }
+
+    /// Most entries the whitelist takes, None when only the room of the map limits them: for
+    /// UNLIMITED_WHITELIST_SIZE, and for zero, which whitelists initialized before init refused
+    /// it may hold and which never limited adds on chain
+    pub fn entry_limit(&self) -> Option<u64> {
+        match self.max_whitelist_size {
+            0 | UNLIMITED_WHITELIST_SIZE => None,
+            size => Some(size),
+        }
+    }
+
+    /// Account size never running out of extension room with up to `max_whitelist_size`
+    /// entries, at most MAX_ENTRIES: every optional extension field set, MAX_SIBLING_WHITELISTS
+    /// siblings, and each entry under a key of MAX_KEY_LEN with a deposit, a consume slot,
+    /// allocations in the later rounds, a campaign and an attestation. Deposits and consume
+    /// slots kept for removed entries are not counted.
+    pub fn space_for(max_whitelist_size: u64) -> usize {
+        let fixed_len = TokenWhitelistExtension {
+            delegate: Some(Delegate::default()),
+            registration: Some(Registration::default()),
+            lottery: Some(Lottery::default()),
+            allocation_mint: Some(Pubkey::default()),
+            merkle_root: Some(MerkleRoot::default()),
+            pda_bump: Some(0),
+            config_bump: Some(0),
+            token_gate: Some(TokenGate::default()),
+            compressed_tree: Some(CompressedTree::default()),
+            linked_accounts: LinkedAccounts {config: Some(Pubkey::default()), claim_bitmap: Some(Pubkey::default())},
+            sibling_whitelists: vec![Pubkey::default(); MAX_SIBLING_WHITELISTS],
+            ..TokenWhitelistExtension::default()
+        }
+        .try_to_vec()
+        .unwrap()
+        .len();
+        let key_len = 4 + MAX_KEY_LEN;
+        // registration_deposits, last_consume_slots, entry_campaigns, round_allocations, entry_attestations and
+        // entry_note_commitments
+        let entry_len = 3 * (key_len + 8) + (key_len + 8 * (MAX_ROUNDS - 1)) + 2 * (key_len + 32);
+        ACCOUNT_STATE_SPACE + EXTENSION_LENGTH + fixed_len + entry_len * max_whitelist_size as usize
+    }
+
     pub fn add_keypair(&mut self, key: &String, value: &u64) {
         self.whitelist_map.insert(key.to_string(), *value);
     }
 
-    pub fn drop_key(&mut self, key: &String) {
-        self.whitelist_map.remove(key);
+    /// Checks a key about to be inserted with [validate_key](fn.validate_key.html). Keys already
+    /// in the map passed it when they were inserted.
+    pub fn check_new_key(&self, key: &str) -> Result<(), TokenWhitelistError> {
+        if !self.whitelist_map.contains_key(key) {
+            validate_key(key)?;
+        }
+        Ok(())
+    }
+
+    /// Keys of the map failing [validate_key](fn.validate_key.html), in key order. Only a
+    /// corrupted account or an import around the insert paths holds any.
+    pub fn invalid_keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.whitelist_map.keys().map(String::as_str).filter(|key| validate_key(key).is_err())
+    }
+
+    /// Takes `key` out of the map with its round allocations, campaign, attestation and note
+    /// commitment, and returns the allocation it held, None for a key that was not whitelisted
+    pub fn drop_key(&mut self, key: &String) -> Option<u64> {
+        self.extension.round_allocations.remove(key);
+        self.extension.entry_campaigns.remove(key);
+        self.extension.entry_attestations.remove(key);
+        self.extension.entry_note_commitments.remove(key);
+        self.whitelist_map.remove(key)
+    }
+
+    /// Moves the entry of `key` to `new_key` with everything kept per key: the allocations of
+    /// every round, the campaign, the attestation, the note commitment, the registration deposit
+    /// and the last consume slot. A deposit `new_key` still holds from an earlier entry is added
+    /// to, and the later of the two consume slots is kept so that rotating does not skip a
+    /// consume cooldown.
+    pub fn rotate_key(&mut self, key: &str, new_key: &str) -> Result<(), TokenWhitelistError> {
+        fn move_value<V>(map: &mut BTreeMap<String, V>, key: &str, new_key: &str) {
+            if let Some(value) = map.remove(key) {
+                map.insert(new_key.to_string(), value);
+            }
+        }
+        move_value(&mut self.whitelist_map, key, new_key);
+        move_value(&mut self.extension.round_allocations, key, new_key);
+        move_value(&mut self.extension.entry_campaigns, key, new_key);
+        move_value(&mut self.extension.entry_attestations, key, new_key);
+        move_value(&mut self.extension.entry_note_commitments, key, new_key);
+        if let Some(deposit) = self.extension.registration_deposits.remove(key) {
+            let total = self.extension.registration_deposits.entry(new_key.to_string()).or_insert(0);
+            *total = total.checked_add(deposit).ok_or(TokenWhitelistError::Overflow)?;
+        }
+        if let Some(slot) = self.extension.last_consume_slots.remove(key) {
+            let last = self.extension.last_consume_slots.entry(new_key.to_string()).or_insert(slot);
+            *last = (*last).max(slot);
+        }
+        Ok(())
+    }
+
+    /// Campaign a whitelisted key was added for, NO_CAMPAIGN if none was given
+    pub fn campaign(&self, key: &str) -> [u8; 8] {
+        self.extension.entry_campaigns.get(key).copied().unwrap_or(NO_CAMPAIGN)
+    }
+
+    /// Attributes a key to a campaign, NO_CAMPAIGN clearing the attribution
+    pub fn set_campaign(&mut self, key: &str, campaign_id: [u8; 8]) {
+        if campaign_id == NO_CAMPAIGN {
+            self.extension.entry_campaigns.remove(key);
+        } else {
+            self.extension.entry_campaigns.insert(key.to_string(), campaign_id);
+        }
+    }
+
+    /// Attestation of a whitelisted key, NO_ATTESTATION if none was set
+    pub fn attestation(&self, key: &str) -> [u8; 32] {
+        self.extension.entry_attestations.get(key).copied().unwrap_or(NO_ATTESTATION)
+    }
+
+    /// Sets the attestation of a key, NO_ATTESTATION clearing it
+    pub fn set_attestation(&mut self, key: &str, attestation: [u8; 32]) {
+        if attestation == NO_ATTESTATION {
+            self.extension.entry_attestations.remove(key);
+        } else {
+            self.extension.entry_attestations.insert(key.to_string(), attestation);
+        }
+    }
+
+    /// Note commitment of a whitelisted key, NO_NOTE_COMMITMENT if none was set
+    pub fn entry_note_commitment(&self, key: &str) -> [u8; 32] {
+        self.extension.entry_note_commitments.get(key).copied().unwrap_or(NO_NOTE_COMMITMENT)
+    }
+
+    /// Sets the note commitment of a key, NO_NOTE_COMMITMENT clearing it
+    pub fn set_note_commitment(&mut self, key: &str, commitment: [u8; 32]) {
+        if commitment == NO_NOTE_COMMITMENT {
+            self.extension.entry_note_commitments.remove(key);
+        } else {
+            self.extension.entry_note_commitments.insert(key.to_string(), commitment);
+        }
+    }
+
+    /// Number of entries per campaign, entries added without one counted under NO_CAMPAIGN
+    pub fn campaign_counts(&self) -> BTreeMap<[u8; 8], u32> {
+        let mut counts = BTreeMap::new();
+        for key in self.whitelist_map.keys() {
+            *counts.entry(self.campaign(key)).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Maps ACTIVE_ROUND to the active round and checks the index
+    pub fn resolve_round(&self, round: u8) -> Result<u8, TokenWhitelistError> {
+        let round = if round == ACTIVE_ROUND { self.extension.active_round } else { round };
+        if round as usize >= MAX_ROUNDS {
+            return Err(TokenWhitelistError::InvalidRound);
+        }
+        Ok(round)
+    }
+
+    /// Allocation of a whitelisted key in a resolved round
+    pub fn round_allocation(&self, key: &str, round: u8) -> Option<u64> {
+        let allocation = *self.whitelist_map.get(key)?;
+        match round {
+            0 => Some(allocation),
+            round => Some(self.extension.round_allocations.get(key)
+                .map_or(0, |allocations| allocations[round as usize - 1])),
+        }
+    }
+
+    /// Sets the allocation of a key in a resolved round, whitelisting it with nothing in the
+    /// other rounds if needed
+    pub fn set_round_allocation(&mut self, key: &str, round: u8, amount: u64) {
+        let allocation = self.whitelist_map.entry(key.to_string()).or_insert(0);
+        if round == 0 {
+            *allocation = amount;
+            return;
+        }
+        let allocations = self.extension.round_allocations.entry(key.to_string()).or_default();
+        allocations[round as usize - 1] = amount;
+        if *allocations == [0; MAX_ROUNDS - 1] {
+            self.extension.round_allocations.remove(key);
+        }
     }
 
     pub fn contains_key(&mut self, key: &String) -> bool {
-        return self.whitelist_map.contains_key(key);
+        self.whitelist_map.contains_key(key)
     }
 
     pub fn get(&mut self, key: &String) -> Option<&u64> {
-        return self.whitelist_map.get(key);
+        self.whitelist_map.get(key)
+    }
+
+    /// Bytes of the fixed map region left for new entries, each taking its key length plus
+    /// MAP_ENTRY_OVERHEAD
+    pub fn map_bytes_remaining(&self) -> usize {
+        MAP_BYTES.saturating_sub(self.map_encoded_len())
+    }
+
+    /// Length of the Borsh encoding of the map: the u32 entry count, then per entry the key
+    /// and MAP_ENTRY_OVERHEAD
+    fn map_encoded_len(&self) -> usize {
+        4 + self.whitelist_map.keys().map(|key| key.len() + MAP_ENTRY_OVERHEAD).sum::<usize>()
+    }
+
+    /// Recomputes entries_hash after the map changed. Accounts of exactly ACCOUNT_STATE_SPACE
+    /// bytes have no room for it, there it stays zeroed.
+    pub fn update_entries_hash(&mut self, account_len: usize) {
+        if account_len > ACCOUNT_STATE_SPACE {
+            self.extension.entries_hash = hash_entries_map(&self.whitelist_map);
+        }
+    }
+
+    /// Whether an account of `account_len` bytes counts its writes in mutation_nonce. One too
+    /// small for the default extension, those of exactly ACCOUNT_STATE_SPACE bytes among them,
+    /// has no room for it, there it stays 0.
+    pub fn tracks_mutations(account_len: usize) -> bool {
+        let default_len = TokenWhitelistExtension::default().try_to_vec().unwrap().len();
+        account_len >= ACCOUNT_STATE_SPACE + EXTENSION_LENGTH + default_len
+    }
+
+    /// Counts a write of the state into an account of `account_len` bytes. Every handler
+    /// writing the whitelist goes through here, so a client that read the nonce can tell
+    /// whether anything was written since, see `rules::check_mutation_nonce`.
+    pub fn record_mutation(&mut self, account_len: usize) {
+        if Self::tracks_mutations(account_len) {
+            self.extension.mutation_nonce = self.extension.mutation_nonce.saturating_add(1);
+        }
+    }
+
+    /// Counts an add at `slot` in the stats of a whitelist stored in `account_len` bytes
+    pub fn record_add(&mut self, account_len: usize, slot: u64) {
+        if Self::tracks_mutations(account_len) {
+            let stats = &mut self.extension.stats;
+            stats.total_adds = stats.total_adds.saturating_add(1);
+            stats.last_mutation_slot = slot;
+        }
+    }
+
+    /// Counts a remove at `slot` in the stats of a whitelist stored in `account_len` bytes
+    pub fn record_remove(&mut self, account_len: usize, slot: u64) {
+        if Self::tracks_mutations(account_len) {
+            let stats = &mut self.extension.stats;
+            stats.total_removes = stats.total_removes.saturating_add(1);
+            stats.last_mutation_slot = slot;
+        }
+    }
+
+    /// Records the creation of a whitelist stored in `account_len` bytes at `slot` by `creator`.
+    /// Only process_init_whitelist calls it, on a blank account, so the record never changes.
+    pub fn record_creation(&mut self, account_len: usize, slot: u64, creator: Pubkey) {
+        if Self::tracks_mutations(account_len) {
+            self.extension.created_at_slot = slot;
+            self.extension.creator = creator;
+        }
+    }
+
+    /// The return data of GetWhitelistStats
+    pub fn stats_report(&self) -> WhitelistStatsReport {
+        WhitelistStatsReport {
+            stats: self.extension.stats,
+            created_at_slot: self.extension.created_at_slot,
+            creator: self.extension.creator,
+        }
+    }
+
+    /// Whether the extension stored in `src` encodes every field of the current layout. One
+    /// written by an older program is shorter than the extension it decodes to, the fields it
+    /// lacks read as their defaults.
+    pub fn extension_is_current(&self, src: &[u8]) -> bool {
+        let stored_len = src
+            .get(ACCOUNT_STATE_SPACE..ACCOUNT_STATE_SPACE + EXTENSION_LENGTH)
+            .map_or(0, count_from_le);
+        stored_len == self.extension.try_to_vec().unwrap().len()
+    }
+
+    /// Seeds of a whitelist at a program address for `invoke_signed`, None unless a bump was
+    /// stored for it. The stored bump spares a `find_program_address` per signing.
+    pub fn signer_seeds<'a>(&'a self, owner: &'a Pubkey, label: &'a [u8]) -> Option<[&'a [u8]; 4]> {
+        let bump = self.extension.pda_bump.as_ref()?;
+        Some([WHITELIST_SEED, owner.as_ref(), label, std::slice::from_ref(bump)])
+    }
+
+    /// Checks that the signer seeds with the stored bump derive `whitelist` under `program_id`,
+    /// with one `create_program_address` instead of searching for the bump again
+    pub fn check_program_address(
+        &self,
+        program_id: &Pubkey,
+        whitelist: &Pubkey,
+        owner: &Pubkey,
+        label: &[u8],
+    ) -> Result<(), ProgramError> {
+        let seeds = self.signer_seeds(owner, label).ok_or(ProgramError::InvalidSeeds)?;
+        match Pubkey::create_program_address(&seeds, program_id) {
+            Ok(address) if address == *whitelist => Ok(()),
+            _ => Err(ProgramError::InvalidSeeds),
+        }
+    }
+
+    /// Entries and their total allocation, as reported when the whitelist is closed
+    pub fn closed_totals(&self) -> ClosedWhitelist {
+        ClosedWhitelist {
+            entries_discarded: self.whitelist_map.len() as u32,
+            allocation_discarded: self.whitelist_map.values().map(|&amount| amount as u128).sum(),
+        }
+    }
+
+    /// Entries in the order they are stored and listed: lexicographic over the base58 strings
+    /// of the keys, which is not the byte order of the pubkeys
+    fn parsed_entries(&self) -> impl Iterator<Item = Result<(Pubkey, u64), ProgramError>> + '_ {
+        self.whitelist_map.iter().map(|(key, amount)| {
+            let key = validate_key(key).map_err(|_| ProgramError::InvalidAccountData)?;
+            Ok((key, *amount))
+        })
+    }
+
+    /// Every entry in key order, the order of the stored map, ListEntries pages and
+    /// entries_hash: base58 strings compared lexicographically
+    pub fn entries_in_key_order(&self) -> Result<Vec<(Pubkey, u64)>, ProgramError> {
+        self.parsed_entries().collect()
+    }
+
+    /// Every entry in pubkey order, the byte order of the 32 byte keys, as a `BTreeMap<Pubkey, _>`
+    /// would hold them
+    pub fn entries_in_pubkey_order(&self) -> Result<Vec<(Pubkey, u64)>, ProgramError> {
+        let mut entries = self.entries_in_key_order()?;
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        Ok(entries)
+    }
+
+    /// Up to `limit` entries, capped at MAX_LIST_ENTRIES, starting at `offset` in key order,
+    /// see [entries_in_key_order](#method.entries_in_key_order)
+    pub fn entries_page(&self, offset: u32, limit: u8) -> Result<EntriesPage, ProgramError> {
+        let entries = self.parsed_entries()
+            .skip(offset as usize)
+            .take((limit as usize).min(MAX_LIST_ENTRIES))
+            .collect::<Result<_, ProgramError>>()?;
+        Ok(EntriesPage {
+            total: self.whitelist_map.len() as u32,
+            entries,
+        })
+    }
+
+    /// Like [entries_page](#method.entries_page) over the entries of one campaign only, `offset`
+    /// and the total counting those entries
+    pub fn campaign_entries_page(&self, campaign_id: [u8; 8], offset: u32, limit: u8) -> Result<EntriesPage, ProgramError> {
+        let mut total = 0;
+        let mut entries = Vec::new();
+        for (key, amount) in self.whitelist_map.iter().filter(|(key, _)| self.campaign(key) == campaign_id) {
+            if total >= offset && entries.len() < (limit as usize).min(MAX_LIST_ENTRIES) {
+                let key = validate_key(key).map_err(|_| ProgramError::InvalidAccountData)?;
+                entries.push((key, *amount));
+            }
+            total += 1;
+        }
+        Ok(EntriesPage {total, entries})
+    }
+
+    /// Like [entries_page](#method.entries_page), each entry with its attestation and at most
+    /// MAX_LIST_ATTESTED_ENTRIES of them
+    pub fn attested_entries_page(&self, offset: u32, limit: u8) -> Result<AttestedEntriesPage, ProgramError> {
+        let entries = self.parsed_entries()
+            .skip(offset as usize)
+            .take((limit as usize).min(MAX_LIST_ATTESTED_ENTRIES))
+            .map(|entry| entry.map(|(key, amount)| (key, amount, self.attestation(&key.to_string()))))
+            .collect::<Result<_, ProgramError>>()?;
+        Ok(AttestedEntriesPage {
+            total: self.whitelist_map.len() as u32,
+            entries,
+        })
     }
 
     pub fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if WhitelistArchive::is_archive(src) {
+            return Err(TokenWhitelistError::WhitelistArchived.into());
+        }
+        if src.len() < ACCOUNT_STATE_SPACE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src_ext = &src[ACCOUNT_STATE_SPACE..];
         let src = array_ref![src, 0, ACCOUNT_STATE_SPACE];
         let (
             is_initialized,
@@ -66,10 +1053,16 @@ impl TokenWhitelist {
 
         let mut btree_map = BTreeMap::<String, u64>::new();
         let btree_map_length = count_from_le(btree_map_len);
+        if btree_map_length > MAP_BYTES {
+            return Err(ProgramError::InvalidAccountData);
+        }
         if btree_map_length > 0 {
-            btree_map = BTreeMap::<String, u64>::try_from_slice(&btree_map_src[0..btree_map_length]).unwrap();
+            btree_map = BTreeMap::<String, u64>::try_from_slice(&btree_map_src[0..btree_map_length])
+                .map_err(|_| ProgramError::InvalidAccountData)?;
         }
 
+        let extension = unpack_extension(src_ext)?;
+
         Ok(TokenWhitelist {
             is_initialized: match is_initialized {
                 [0] => false,
@@ -79,10 +1072,116 @@ impl TokenWhitelist {
             init_pubkey: Pubkey::new_from_array(*init_pubkey),
             max_whitelist_size: u64::from_le_bytes(*max_whitelist_size),
             whitelist_map: btree_map,
+            extension,
         })
     }
 
-    pub fn pack_into_slice(&self, dst: &mut [u8]) {
+    /// Like [unpack_from_slice](#method.unpack_from_slice), failing with InvalidWhitelistKey
+    /// when a key of the map is no pubkey instead of decoding it as any other string. Decoding
+    /// every key costs compute, so the processor keeps to unpack_from_slice and checks keys
+    /// as they are inserted; this is for readers that need every entry to be an account.
+    pub fn unpack_from_slice_strict(src: &[u8]) -> Result<Self, ProgramError> {
+        let state = Self::unpack_from_slice(src)?;
+        if state.invalid_keys().next().is_some() {
+            return Err(TokenWhitelistError::InvalidWhitelistKey.into());
+        }
+        Ok(state)
+    }
+
+    /// Reads the fixed fields and the entry count without decoding the map or the extension, for
+    /// readers that need no entry. The entries themselves are not validated.
+    pub fn unpack_header(src: &[u8]) -> Result<WhitelistHeader, ProgramError> {
+        if WhitelistArchive::is_archive(src) {
+            return Err(TokenWhitelistError::WhitelistArchived.into());
+        }
+        if src.len() < ACCOUNT_STATE_SPACE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, ACCOUNT_STATE_SPACE];
+        let (is_initialized, init_pubkey, max_whitelist_size, btree_map_len, btree_map_src) = array_refs![
+            src,
+            INITIALIZED_BYTES,
+            PUBKEY_BYTES,
+            WHITELIST_SIZE_BYTES,
+            MAP_LENGTH,
+            MAP_BYTES
+        ];
+        let btree_map_length = count_from_le(btree_map_len);
+        if btree_map_length > MAP_BYTES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(WhitelistHeader {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            init_pubkey: Pubkey::new_from_array(*init_pubkey),
+            max_whitelist_size: u64::from_le_bytes(*max_whitelist_size),
+            entries: if btree_map_length >= 4 { count_from_le(btree_map_src) as u32 } else { 0 },
+        })
+    }
+
+    /// Packs the header and the extension into an account of at least ACCOUNT_STATE_SPACE bytes.
+    /// Accounts may be larger than the layout needs: the map region past the map and everything
+    /// past the extension are reserved and written as zeros, so no stale bytes of a longer earlier
+    /// state survive a pack.
+    pub fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let (dst, dst_ext) = dst.split_at_mut(ACCOUNT_STATE_SPACE);
+        let dst = array_mut_ref![dst, 0, ACCOUNT_STATE_SPACE];
+        let (
+            is_initialized_dst,
+            init_pubkey_dst,
+            max_whitelist_size_dst,
+            btree_map_len,
+            btree_map_dst,
+        ) = mut_array_refs![
+            dst,
+            INITIALIZED_BYTES,
+            PUBKEY_BYTES,
+            WHITELIST_SIZE_BYTES,
+            MAP_LENGTH,
+            MAP_BYTES
+        ];
+
+        // checked before writing anything, so a map that does not fit leaves dst as it was
+        let map_len = self.map_encoded_len();
+        if map_len > MAP_BYTES {
+            return Err(TokenWhitelistError::StateInvariantViolation.into());
+        }
+        is_initialized_dst[0] = self.is_initialized as u8;
+        init_pubkey_dst.copy_from_slice(self.init_pubkey.as_ref());
+        *max_whitelist_size_dst = self.max_whitelist_size.to_le_bytes();
+        // encoded straight into the region, no temporary Vec on the small BPF heap
+        let mut cursor: &mut [u8] = &mut btree_map_dst[..];
+        self.whitelist_map.serialize(&mut cursor).map_err(|_| TokenWhitelistError::StateInvariantViolation)?;
+        let written = MAP_BYTES - cursor.len();
+        if written != map_len {
+            return Err(TokenWhitelistError::StateInvariantViolation.into());
+        }
+        btree_map_len[..].copy_from_slice(&transform_u32_to_array_of_u8(written as u32));
+        btree_map_dst[written..].fill(0);
+
+        pack_extension(&self.extension, dst_ext)
+    }
+
+    /// Packs the state into the account it was unpacked from after drop_key took `key` out of
+    /// the map, the one change to the map since. The entries after `key` move down over it in
+    /// the map region instead of the whole map being encoded again, and the entries hash is
+    /// taken over the region, which then holds the Borsh encoding of the map. A region that is
+    /// not the canonical encoding of the map and `key`, e.g. keys out of order or repeated, is
+    /// packed by pack_into_slice instead, as is everything but the map.
+    pub fn pack_removal_into_slice(&mut self, dst: &mut [u8], key: &str) -> Result<(), ProgramError> {
+        let account_len = dst.len();
+        let (start, end, map_len) = match self.encoded_removal(dst, key) {
+            Some(range) => range,
+            None => {
+                self.update_entries_hash(account_len);
+                return self.pack_into_slice(dst);
+            }
+        };
+
+        let (dst, dst_ext) = dst.split_at_mut(ACCOUNT_STATE_SPACE);
         let dst = array_mut_ref![dst, 0, ACCOUNT_STATE_SPACE];
         let (
             is_initialized_dst,
@@ -98,19 +1197,224 @@ impl TokenWhitelist {
             MAP_LENGTH,
             MAP_BYTES
         ];
-        
         is_initialized_dst[0] = self.is_initialized as u8;
         init_pubkey_dst.copy_from_slice(self.init_pubkey.as_ref());
         *max_whitelist_size_dst = self.max_whitelist_size.to_le_bytes();
-        let data_ser = self.whitelist_map.try_to_vec().unwrap();
-        btree_map_len[..].copy_from_slice(&transform_u32_to_array_of_u8(data_ser.len() as u32));
-        btree_map_dst[..data_ser.len()].copy_from_slice(&data_ser);
+        btree_map_dst.copy_within(end..map_len, start);
+        let written = map_len - (end - start);
+        btree_map_dst[written..].fill(0);
+        btree_map_dst[..4].copy_from_slice(&transform_u32_to_array_of_u8(self.whitelist_map.len() as u32));
+        btree_map_len[..].copy_from_slice(&transform_u32_to_array_of_u8(written as u32));
+        if account_len > ACCOUNT_STATE_SPACE {
+            self.extension.entries_hash = keccak::hash(&btree_map_dst[..written]).to_bytes();
+        }
+
+        pack_extension(&self.extension, dst_ext)
+    }
+
+    /// Bytes of the entry of `key` in the map region of `src` and the length of the region, when
+    /// the region is the canonical encoding of the map with `key` added back: as long as that
+    /// encoding, one entry more than the map, keys in strictly increasing order
+    fn encoded_removal(&self, src: &[u8], key: &str) -> Option<(usize, usize, usize)> {
+        let src = src.get(..ACCOUNT_STATE_SPACE)?;
+        let src = array_ref![src, 0, ACCOUNT_STATE_SPACE];
+        let (_, _, _, btree_map_len, btree_map_src) =
+            array_refs![src, INITIALIZED_BYTES, PUBKEY_BYTES, WHITELIST_SIZE_BYTES, MAP_LENGTH, MAP_BYTES];
+        let map_len = count_from_le(btree_map_len);
+        if map_len > MAP_BYTES
+            || map_len != self.map_encoded_len() + key.len() + MAP_ENTRY_OVERHEAD
+            || count_from_le(btree_map_src) != self.whitelist_map.len() + 1
+        {
+            return None;
+        }
+
+        let (mut offset, mut previous, mut entry) = (4, None, None);
+        while offset < map_len {
+            let key_len = count_from_le(btree_map_src.get(offset..offset + 4)?);
+            let entry_key = btree_map_src.get(offset + 4..offset + 4 + key_len)?;
+            let end = offset + key_len + MAP_ENTRY_OVERHEAD;
+            if end > map_len || matches!(previous, Some(previous) if previous >= entry_key) {
+                return None;
+            }
+            if entry_key == key.as_bytes() {
+                entry = Some((offset, end));
+            }
+            previous = Some(entry_key);
+            offset = end;
+        }
+        entry.map(|(start, end)| (start, end, map_len))
+    }
+
+    /// Rebuilds an initialized whitelist whose map region does not decode. The length prefix is
+    /// ignored: entries are read one by one, up to the count the map starts with, and the map
+    /// keeps those whose key is a pubkey, the last one of a repeated key winning as it does
+    /// when the map decodes. Reading stops at the first entry cut off or not valid UTF-8 and at
+    /// zeroed padding. Only the map is rebuilt, an extension that does not decode still fails.
+    /// Entries dropped take their round allocations, campaign, attestation and note commitment
+    /// along, and the entries hash is recomputed.
+    pub fn recover_from_slice(src: &[u8]) -> Result<(Self, RepairReport), ProgramError> {
+        if WhitelistArchive::is_archive(src) {
+            return Err(TokenWhitelistError::WhitelistArchived.into());
+        }
+        if src.len() < ACCOUNT_STATE_SPACE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src_ext = &src[ACCOUNT_STATE_SPACE..];
+        let header = array_ref![src, 0, ACCOUNT_STATE_SPACE];
+        let (is_initialized, init_pubkey, max_whitelist_size, _, btree_map_src) = array_refs![
+            header,
+            INITIALIZED_BYTES,
+            PUBKEY_BYTES,
+            WHITELIST_SIZE_BYTES,
+            MAP_LENGTH,
+            MAP_BYTES
+        ];
+        if *is_initialized != [1] {
+            return Err(TokenWhitelistError::TokenWhitelistNotInit.into());
+        }
+
+        let (whitelist_map, report) = recover_map(btree_map_src);
+        let mut extension = unpack_extension(src_ext)?;
+        extension.round_allocations.retain(|key, _| whitelist_map.contains_key(key));
+        extension.entry_campaigns.retain(|key, _| whitelist_map.contains_key(key));
+        extension.entry_attestations.retain(|key, _| whitelist_map.contains_key(key));
+        extension.entry_note_commitments.retain(|key, _| whitelist_map.contains_key(key));
+
+        let mut state = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: Pubkey::new_from_array(*init_pubkey),
+            max_whitelist_size: u64::from_le_bytes(*max_whitelist_size),
+            whitelist_map,
+            extension,
+        };
+        state.update_entries_hash(src.len());
+        Ok((state, report))
+    }
+
+    /// Re-reads the header just packed into `src` and checks it against this state: initialized,
+    /// a map region within MAP_BYTES, and as many entries counted there as the map holds
+    pub fn check_packed(&self, src: &[u8]) -> Result<(), TokenWhitelistError> {
+        if src.len() < ACCOUNT_STATE_SPACE {
+            return Err(TokenWhitelistError::StateInvariantViolation);
+        }
+        let src = array_ref![src, 0, ACCOUNT_STATE_SPACE];
+        let (is_initialized, _, _, btree_map_len, btree_map_src) = array_refs![
+            src,
+            INITIALIZED_BYTES,
+            PUBKEY_BYTES,
+            WHITELIST_SIZE_BYTES,
+            MAP_LENGTH,
+            MAP_BYTES
+        ];
+        let btree_map_length = count_from_le(btree_map_len);
+        if *is_initialized != [1]
+            || !(MAP_LENGTH..=MAP_BYTES).contains(&btree_map_length)
+            || count_from_le(btree_map_src) != self.whitelist_map.len()
+        {
+            return Err(TokenWhitelistError::StateInvariantViolation);
+        }
+        Ok(())
     }
 }
 
+/// Decode the extension region, treating a missing or empty region as the default extension
+fn unpack_extension(src: &[u8]) -> Result<TokenWhitelistExtension, ProgramError> {
+    if src.len() < EXTENSION_LENGTH {
+        return Ok(TokenWhitelistExtension::default());
+    }
+    let (extension_len, extension_src) = src.split_at(EXTENSION_LENGTH);
+    let extension_length = count_from_le(extension_len);
+    if extension_length > extension_src.len() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    TokenWhitelistExtension::deserialize(&mut &extension_src[..extension_length])
+        .map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// Reads the entries of a map region one by one, see
+/// [recover_from_slice](struct.TokenWhitelist.html#method.recover_from_slice)
+fn recover_map(src: &[u8; MAP_BYTES]) -> (BTreeMap<String, u64>, RepairReport) {
+    let mut whitelist_map = BTreeMap::new();
+    let mut report = RepairReport::default();
+    let (count, mut rest) = src.split_at(4);
+    for _ in 0..count_from_le(count) {
+        if rest.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let mut entry_src = rest;
+        let (key, amount) = match <(String, u64)>::deserialize(&mut entry_src) {
+            Ok(entry) => entry,
+            Err(_) => {
+                report.entries_dropped += 1;
+                break;
+            }
+        };
+        rest = entry_src;
+        if validate_key(&key).is_err() || whitelist_map.insert(key, amount).is_some() {
+            report.entries_dropped += 1;
+        }
+    }
+    report.entries_recovered = whitelist_map.len() as u32;
+    report.bytes_dropped = rest.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1) as u32;
+    (whitelist_map, report)
+}
+
+/// Entries of the longest keys fitting MAP_BYTES after the borsh u32 entry count
+const fn max_entries() -> u64 {
+    ((MAP_BYTES - 4) / (MAX_KEY_LEN + MAP_ENTRY_OVERHEAD)) as u64
+}
+
+/// Pubkey a key of the map stands for, InvalidWhitelistKey unless the key decodes to 32 bytes.
+/// Base58 is one to one, so such a key is the one string its pubkey encodes to and no second
+/// key can stand for the same account.
+pub fn validate_key(key: &str) -> Result<Pubkey, TokenWhitelistError> {
+    Pubkey::from_str(key).map_err(|_| TokenWhitelistError::InvalidWhitelistKey)
+}
+
+/// Keccak over the Borsh encoding of the map, its `(key, amount)` pairs in key order
+pub fn hash_entries_map(whitelist_map: &BTreeMap<String, u64>) -> [u8; 32] {
+    // sized up front, try_to_vec would grow its buffer several times over a near-full map
+    let encoded_len = 4 + whitelist_map.keys().map(|key| key.len() + MAP_ENTRY_OVERHEAD).sum::<usize>();
+    let mut encoded = Vec::with_capacity(encoded_len);
+    whitelist_map.serialize(&mut encoded).unwrap();
+    keccak::hash(&encoded).to_bytes()
+}
+
+/// Commitment SetEntryNoteCommitment stores for `note`, the keccak hash of the note followed by
+/// `salt`, which RevealEntryNote recomputes. The salt keeps short notes from being guessed.
+pub fn note_commitment(note: &[u8], salt: &[u8; 16]) -> [u8; 32] {
+    keccak::hashv(&[note, salt]).to_bytes()
+}
+
+/// Encode the extension region and zero the rest of the account, failing if the account has no
+/// room for a non-default extension
+fn pack_extension(extension: &TokenWhitelistExtension, dst: &mut [u8]) -> Result<(), ProgramError> {
+    let data_ser = extension.try_to_vec().unwrap();
+    if dst.len() < EXTENSION_LENGTH + data_ser.len() {
+        if *extension == TokenWhitelistExtension::default() {
+            dst.fill(0);
+            return Ok(());
+        }
+        return Err(TokenWhitelistError::AccountTooSmall.into());
+    }
+    let (extension_len, extension_dst) = dst.split_at_mut(EXTENSION_LENGTH);
+    extension_len.copy_from_slice(&transform_u32_to_array_of_u8(data_ser.len() as u32));
+    extension_dst[..data_ser.len()].copy_from_slice(&data_ser);
+    extension_dst[data_ser.len()..].fill(0);
+    Ok(())
+}
+
+/// Decode the next extension field, or its default if an older extension ends before it
+fn read_or_default<T: BorshDeserialize + Default>(buf: &mut &[u8]) -> std::io::Result<T> {
+    if buf.is_empty() {
+        return Ok(T::default());
+    }
+    T::deserialize(buf)
+}
+
 /// Get the Borsh container count (le) from buffer
 fn count_from_le(array: &[u8]) -> usize {
-    (array[0] as usize) << 0
+    (array[0] as usize)
         | (array[1] as usize) << 8
         | (array[2] as usize) << 16
         | (array[3] as usize) << 24
@@ -122,5 +1426,699 @@ fn transform_u32_to_array_of_u8(x: u32) -> [u8; 4] {
     let b2: u8 = ((x >> 16) & 0xff) as u8;
     let b3: u8 = ((x >> 8) & 0xff) as u8;
     let b4: u8 = (x & 0xff) as u8;
-    return [b4, b3, b2, b1];
+    [b4, b3, b2, b1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_entries_fill() {
+        // keys as long as a base58 pubkey gets, added until the next one no longer fits
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        while whitelist.map_bytes_remaining() >= MAX_KEY_LEN + MAP_ENTRY_OVERHEAD {
+            let key = format!("{:0>1$}", whitelist.whitelist_map.len(), MAX_KEY_LEN);
+            whitelist.add_keypair(&key, &u64::MAX);
+        }
+        assert_eq!(whitelist.whitelist_map.len() as u64, TokenWhitelist::MAX_ENTRIES);
+
+        let mut data = vec![0; ACCOUNT_STATE_SPACE];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap(), whitelist);
+    }
+
+    #[test]
+    fn test_max_whitelist_size_semantics() {
+        let max = TokenWhitelist::MAX_ENTRIES;
+        assert_eq!(TokenWhitelist::check_max_whitelist_size(0), Err(TokenWhitelistError::ZeroWhitelistSize));
+        assert_eq!(TokenWhitelist::check_max_whitelist_size(1), Ok(1));
+        assert_eq!(TokenWhitelist::check_max_whitelist_size(max), Ok(max));
+        assert_eq!(TokenWhitelist::check_max_whitelist_size(max + 1), Err(TokenWhitelistError::TokenWhitelistSizeExceeds));
+        assert_eq!(TokenWhitelist::check_max_whitelist_size(UNLIMITED_WHITELIST_SIZE), Ok(max));
+
+        // zero, still held by whitelists initialized before init refused it, reads as unlimited
+        let limit = |max_whitelist_size| TokenWhitelist {max_whitelist_size, ..TokenWhitelist::default()}.entry_limit();
+        assert_eq!(limit(0), None);
+        assert_eq!(limit(UNLIMITED_WHITELIST_SIZE), None);
+        assert_eq!(limit(1), Some(1));
+        assert_eq!(limit(max + 1), Some(max + 1));
+    }
+
+    #[test]
+    fn test_space_for_fits_fullest_whitelist() {
+        let size = TokenWhitelist::MAX_ENTRIES;
+        let mut whitelist = TokenWhitelist {is_initialized: true, max_whitelist_size: size, ..TokenWhitelist::default()};
+        let extension = &mut whitelist.extension;
+        extension.delegate = Some(Delegate::default());
+        extension.registration = Some(Registration::default());
+        extension.lottery = Some(Lottery::default());
+        extension.allocation_mint = Some(Pubkey::default());
+        extension.merkle_root = Some(MerkleRoot::default());
+        extension.pda_bump = Some(0);
+        extension.config_bump = Some(0);
+        extension.token_gate = Some(TokenGate::default());
+        extension.compressed_tree = Some(CompressedTree::default());
+        extension.linked_accounts = LinkedAccounts {config: Some(Pubkey::default()), claim_bitmap: Some(Pubkey::default())};
+        extension.sibling_whitelists = vec![Pubkey::default(); MAX_SIBLING_WHITELISTS];
+        for index in 0..size {
+            let key = format!("{:0>1$}", index, MAX_KEY_LEN);
+            whitelist.extension.registration_deposits.insert(key.clone(), u64::MAX);
+            whitelist.extension.last_consume_slots.insert(key.clone(), u64::MAX);
+            whitelist.extension.entry_campaigns.insert(key.clone(), [1; 8]);
+            whitelist.extension.round_allocations.insert(key.clone(), [u64::MAX; MAX_ROUNDS - 1]);
+            whitelist.extension.entry_attestations.insert(key.clone(), [1; 32]);
+            whitelist.extension.entry_note_commitments.insert(key.clone(), [1; 32]);
+            whitelist.add_keypair(&key, &u64::MAX);
+        }
+
+        let mut data = vec![0; TokenWhitelist::space_for(size)];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap(), whitelist);
+        // not a byte to spare
+        data.pop();
+        assert_eq!(whitelist.pack_into_slice(&mut data), Err(TokenWhitelistError::AccountTooSmall.into()));
+
+        assert!(TokenWhitelist::space_for(0) > TokenWhitelist::MIN_SPACE);
+        assert!(TokenWhitelist::space_for(1) < TokenWhitelist::space_for(2));
+    }
+
+    #[test]
+    fn test_check_packed() {
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        whitelist.add_keypair(&Pubkey::new_from_array([1; 32]).to_string(), &100);
+        whitelist.add_keypair(&Pubkey::new_from_array([2; 32]).to_string(), &200);
+        let mut data = vec![0; ACCOUNT_STATE_SPACE];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(whitelist.check_packed(&data), Ok(()));
+
+        let violation = Err(TokenWhitelistError::StateInvariantViolation);
+        // entry count in the map region off by one
+        let count_offset = ACCOUNT_STATE_SPACE - MAP_BYTES;
+        data[count_offset] = 3;
+        assert_eq!(whitelist.check_packed(&data), violation);
+        data[count_offset] = 2;
+        // map length beyond the map region
+        let length_offset = count_offset - MAP_LENGTH;
+        data[length_offset..count_offset].copy_from_slice(&transform_u32_to_array_of_u8(MAP_BYTES as u32 + 1));
+        assert_eq!(whitelist.check_packed(&data), violation);
+        assert_eq!(whitelist.check_packed(&data[..ACCOUNT_STATE_SPACE - 1]), violation);
+
+        let uninitialized = TokenWhitelist {is_initialized: false, ..whitelist.clone()};
+        uninitialized.pack_into_slice(&mut data).unwrap();
+        assert_eq!(uninitialized.check_packed(&data), violation);
+    }
+
+    #[test]
+    fn test_pack_refuses_oversized_map() {
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        while whitelist.map_bytes_remaining() > 0 {
+            let key = format!("{:0>1$}", whitelist.whitelist_map.len(), MAX_KEY_LEN);
+            whitelist.add_keypair(&key, &1);
+        }
+        let mut data = vec![0; ACCOUNT_STATE_SPACE];
+        assert_eq!(whitelist.pack_into_slice(&mut data), Err(TokenWhitelistError::StateInvariantViolation.into()));
+    }
+
+    #[test]
+    fn test_pack_zeroes_reserved_bytes() {
+        // an over-allocated account, its spare bytes dirtied by whatever wrote there before
+        let mut data = vec![0xAB; 10240];
+        let mut whitelist = TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 50,
+            whitelist_map: (0..20u8).map(|i| (Pubkey::new_from_array([i; 32]).to_string(), i as u64)).collect(),
+            ..TokenWhitelist::default()
+        };
+        whitelist.extension.delegate = Some(Delegate::default());
+        whitelist.pack_into_slice(&mut data).unwrap();
+
+        // shrinking the map and the extension leaves nothing of the longer state behind
+        whitelist.whitelist_map = whitelist.whitelist_map.into_iter().take(1).collect();
+        whitelist.extension.delegate = None;
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let map_len = whitelist.whitelist_map.try_to_vec().unwrap().len();
+        let map_start = ACCOUNT_STATE_SPACE - MAP_BYTES;
+        assert!(data[map_start + map_len..ACCOUNT_STATE_SPACE].iter().all(|&byte| byte == 0));
+        let extension_len = whitelist.extension.try_to_vec().unwrap().len();
+        assert!(data[ACCOUNT_STATE_SPACE + EXTENSION_LENGTH + extension_len..].iter().all(|&byte| byte == 0));
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap(), whitelist);
+
+        // an account too short for an extension is zeroed past the header all the same
+        let mut data = vec![0xAB; ACCOUNT_STATE_SPACE + 2];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(data[ACCOUNT_STATE_SPACE..], [0, 0]);
+    }
+
+    #[test]
+    fn test_extension_is_current() {
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        whitelist.extension.token_gate = Some(TokenGate::default());
+        let mut data = vec![0; 10240];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert!(whitelist.extension_is_current(&data));
+
+        // an extension ending before token_gate and every field after it, as a program predating
+        // them wrote it
+        let older = TokenWhitelist {extension: TokenWhitelistExtension::default(), ..whitelist.clone()};
+        let e = &older.extension;
+        let older_len = (
+            &e.delegate,
+            &e.registration,
+            &e.registration_deposits,
+            &e.lottery,
+            &e.consume_limits,
+            &e.last_consume_slots,
+            &e.entries_hash,
+            &e.allocation_decimals,
+            &e.allocation_mint,
+            &e.allocation_bounds,
+            &e.active_round,
+            &e.round_allocations,
+            &e.strict_token_checks,
+            &e.merkle_root,
+            &e.pda_bump,
+            &e.only_wallets,
+            &e.config_bump,
+            &e.self_zero_disabled,
+            &e.entry_campaigns,
+            &e.zero_allocation_disabled,
+        )
+            .try_to_vec()
+            .unwrap()
+            .len();
+        data[ACCOUNT_STATE_SPACE..ACCOUNT_STATE_SPACE + EXTENSION_LENGTH]
+            .copy_from_slice(&transform_u32_to_array_of_u8(older_len as u32));
+        data[ACCOUNT_STATE_SPACE + EXTENSION_LENGTH + older_len..].fill(0);
+        let unpacked = TokenWhitelist::unpack_from_slice(&data).unwrap();
+        assert_eq!(unpacked, older);
+        assert!(!unpacked.extension_is_current(&data));
+
+        // and one with no extension at all
+        assert!(!whitelist.extension_is_current(&data[..ACCOUNT_STATE_SPACE]));
+    }
+
+    #[test]
+    fn test_record_mutation() {
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        let default_len = EXTENSION_LENGTH + TokenWhitelistExtension::default().try_to_vec().unwrap().len();
+        for len in [ACCOUNT_STATE_SPACE, ACCOUNT_STATE_SPACE + default_len - 1] {
+            whitelist.record_mutation(len);
+            assert_eq!(whitelist.extension.mutation_nonce, 0);
+            let mut data = vec![0; len];
+            whitelist.pack_into_slice(&mut data).unwrap();
+        }
+
+        let mut data = vec![0; ACCOUNT_STATE_SPACE + default_len];
+        whitelist.record_mutation(data.len());
+        whitelist.record_mutation(data.len());
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap().extension.mutation_nonce, 2);
+
+        whitelist.extension.mutation_nonce = u64::MAX;
+        whitelist.record_mutation(data.len());
+        assert_eq!(whitelist.extension.mutation_nonce, u64::MAX);
+    }
+
+    #[test]
+    fn test_record_churn() {
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        whitelist.record_add(ACCOUNT_STATE_SPACE, 10);
+        whitelist.record_remove(ACCOUNT_STATE_SPACE, 11);
+        assert_eq!(whitelist.extension.stats, WhitelistStats::default());
+
+        let mut data = vec![0; 10240];
+        whitelist.record_add(data.len(), 10);
+        whitelist.record_add(data.len(), 12);
+        whitelist.record_remove(data.len(), 15);
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(
+            TokenWhitelist::unpack_from_slice(&data).unwrap().extension.stats,
+            WhitelistStats {total_adds: 2, total_removes: 1, last_mutation_slot: 15}
+        );
+    }
+
+    #[test]
+    fn test_record_creation() {
+        let creator = Pubkey::new_unique();
+        let mut whitelist = TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()};
+        whitelist.record_creation(ACCOUNT_STATE_SPACE, 10, creator);
+        assert_eq!(whitelist.stats_report(), WhitelistStatsReport::default());
+
+        let mut data = vec![0; 10240];
+        whitelist.record_creation(data.len(), 10, creator);
+        whitelist.record_add(data.len(), 12);
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let report = TokenWhitelist::unpack_from_slice(&data).unwrap().stats_report();
+        assert_eq!(
+            report,
+            WhitelistStatsReport {
+                stats: WhitelistStats {total_adds: 1, total_removes: 0, last_mutation_slot: 12},
+                created_at_slot: 10,
+                creator,
+            }
+        );
+        // readers of the stats alone decode the start of the report
+        let bytes = report.try_to_vec().unwrap();
+        assert_eq!(WhitelistStats::deserialize(&mut &bytes[..]).unwrap(), report.stats);
+    }
+
+    #[test]
+    fn test_registration_rate_limit() {
+        let mut limit = RegistrationRateLimit::default();
+        for slot in 0..5 {
+            limit.register(slot).unwrap();
+        }
+        assert_eq!(limit, RegistrationRateLimit::default());
+
+        limit = RegistrationRateLimit {max_registrations_per_window: 2, window_slots: 10, ..limit};
+        limit.register(3).unwrap();
+        assert_eq!((limit.window_start_slot, limit.registrations_in_window), (3, 1));
+        limit.register(12).unwrap();
+        assert_eq!(limit.register(12), Err(TokenWhitelistError::RegistrationRateLimited));
+        assert_eq!(limit.registrations_in_window, 2);
+        // the window started at slot 3 is over at 13
+        limit.register(13).unwrap();
+        assert_eq!((limit.window_start_slot, limit.registrations_in_window), (13, 1));
+    }
+
+    #[test]
+    fn test_program_address_bump() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let label = b"public-sale";
+        let (address, bump) =
+            Pubkey::find_program_address(&[WHITELIST_SEED, owner.as_ref(), label], &program_id);
+
+        let mut whitelist = TokenWhitelist {is_initialized: true, init_pubkey: owner, ..TokenWhitelist::default()};
+        assert_eq!(whitelist.signer_seeds(&owner, label), None);
+        assert_eq!(
+            whitelist.check_program_address(&program_id, &address, &owner, label),
+            Err(ProgramError::InvalidSeeds)
+        );
+
+        whitelist.extension.pda_bump = Some(bump);
+        let mut data = vec![0; ACCOUNT_STATE_SPACE + 256];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let whitelist = TokenWhitelist::unpack_from_slice(&data).unwrap();
+        assert_eq!(whitelist.signer_seeds(&owner, label).unwrap()[3], &[bump]);
+        assert_eq!(whitelist.check_program_address(&program_id, &address, &owner, label), Ok(()));
+        // other seeds or another program derive other addresses
+        assert_eq!(
+            whitelist.check_program_address(&program_id, &address, &owner, b"private-sale"),
+            Err(ProgramError::InvalidSeeds)
+        );
+        assert_eq!(
+            whitelist.check_program_address(&Pubkey::new_unique(), &address, &owner, label),
+            Err(ProgramError::InvalidSeeds)
+        );
+
+        // any bump other than the stored canonical one is refused, on or off the curve
+        for stored in (0..=u8::MAX).filter(|&stored| stored != bump) {
+            let mismatched = TokenWhitelist {
+                extension: TokenWhitelistExtension {pda_bump: Some(stored), ..whitelist.extension.clone()},
+                ..whitelist.clone()
+            };
+            assert_eq!(
+                mismatched.check_program_address(&program_id, &address, &owner, label),
+                Err(ProgramError::InvalidSeeds)
+            );
+        }
+    }
+
+    #[test]
+    fn test_whitelist_config() {
+        let config = WhitelistConfig {
+            whitelist: Pubkey::new_from_array([1; 32]),
+            policy: WhitelistPolicy {
+                allocation_bounds: AllocationBounds {min_allocation: 10, max_allocation: 100},
+                consume_limits: ConsumeLimits {max_consume_per_tx: 5, min_slots_between_consumes: 2},
+                active_round: 1,
+                strict_token_checks: true,
+                only_wallets: true,
+                self_zero_disabled: true,
+                zero_allocation_disabled: true,
+                token_gate: Some(TokenGate {gating_mint: Pubkey::new_from_array([2; 32]), gating_minimum: 1_000, gate_owner_adds: true}),
+                require_attestation: true,
+                owner_entry_disabled: true,
+            },
+        };
+        let mut data = vec![0; CONFIG_SPACE];
+        assert_eq!(WhitelistConfig::unpack_from_slice(&data), Err(ProgramError::InvalidAccountData));
+        config.pack_into_slice(&mut data).unwrap();
+        assert_eq!(WhitelistConfig::unpack_from_slice(&data).unwrap(), config);
+        assert_eq!(config.pack_into_slice(&mut [0; 8]), Err(TokenWhitelistError::AccountTooSmall.into()));
+
+        let mut extension = TokenWhitelistExtension::default();
+        config.policy.apply(&mut extension);
+        assert_eq!(WhitelistPolicy::of(&extension), config.policy);
+        assert_eq!(extension.lottery, None);
+
+        let program_id = Pubkey::new_unique();
+        let (address, bump) = WhitelistConfig::find_address(&program_id, &config.whitelist);
+        assert_eq!(WhitelistConfig::address(&program_id, &config.whitelist, bump), Ok(address));
+        assert_ne!(WhitelistConfig::address(&program_id, &Pubkey::new_unique(), bump), Ok(address));
+    }
+
+    #[test]
+    fn test_entry_order() {
+        // byte order 9 < 100 < 255, base58 "7ktZ.." (100) < "JEKN.." (255) < "cGfH.." (9)
+        let low = Pubkey::new_from_array([9; 32]);
+        let mid = Pubkey::new_from_array([100; 32]);
+        let high = Pubkey::new_from_array([255; 32]);
+        let whitelist = TokenWhitelist {
+            whitelist_map: [(low, 1), (mid, 2), (high, 3)].iter()
+                .map(|(key, amount)| (key.to_string(), *amount))
+                .collect(),
+            ..TokenWhitelist::default()
+        };
+        assert_eq!(whitelist.entries_in_key_order().unwrap(), vec![(mid, 2), (high, 3), (low, 1)]);
+        assert_eq!(whitelist.entries_in_pubkey_order().unwrap(), vec![(low, 1), (mid, 2), (high, 3)]);
+
+        // pages follow key order
+        assert_eq!(whitelist.entries_page(0, 2).unwrap().entries, vec![(mid, 2), (high, 3)]);
+        assert_eq!(whitelist.entries_page(2, 2).unwrap().entries, vec![(low, 1)]);
+
+        // and so does a round trip through the account data
+        let mut data = vec![0; ACCOUNT_STATE_SPACE];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let unpacked = TokenWhitelist::unpack_from_slice(&data).unwrap();
+        assert_eq!(unpacked.entries_in_key_order().unwrap(), whitelist.entries_in_key_order().unwrap());
+    }
+
+    #[test]
+    fn test_campaigns() {
+        let keys: Vec<Pubkey> = [9, 100, 255].iter().map(|&byte| Pubkey::new_from_array([byte; 32])).collect();
+        let mut whitelist = TokenWhitelist::default();
+        for key in keys.iter() {
+            whitelist.add_keypair(&key.to_string(), &1);
+        }
+        whitelist.set_campaign(&keys[0].to_string(), [1; 8]);
+        whitelist.set_campaign(&keys[2].to_string(), [1; 8]);
+        assert_eq!(whitelist.campaign_counts(), BTreeMap::from([(NO_CAMPAIGN, 1), ([1; 8], 2)]));
+
+        // key order, 255 before 9
+        let page = whitelist.campaign_entries_page([1; 8], 0, 1).unwrap();
+        assert_eq!(page, EntriesPage {total: 2, entries: vec![(keys[2], 1)]});
+        let page = whitelist.campaign_entries_page([1; 8], 1, 1).unwrap();
+        assert_eq!(page, EntriesPage {total: 2, entries: vec![(keys[0], 1)]});
+        assert_eq!(whitelist.campaign_entries_page(NO_CAMPAIGN, 0, 10).unwrap().entries, vec![(keys[1], 1)]);
+        assert_eq!(whitelist.campaign_entries_page([2; 8], 0, 10).unwrap(), EntriesPage {total: 0, entries: vec![]});
+
+        // the attribution survives the account data and goes with the entry
+        let mut data = vec![0; ACCOUNT_STATE_SPACE + 512];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let mut unpacked = TokenWhitelist::unpack_from_slice(&data).unwrap();
+        assert_eq!(unpacked.campaign(&keys[2].to_string()), [1; 8]);
+        unpacked.drop_key(&keys[2].to_string());
+        assert_eq!(unpacked.campaign(&keys[2].to_string()), NO_CAMPAIGN);
+        assert_eq!(unpacked.campaign_counts(), BTreeMap::from([(NO_CAMPAIGN, 1), ([1; 8], 1)]));
+    }
+
+    #[test]
+    fn test_attestations() {
+        let keys: Vec<Pubkey> = (1..=20).map(|byte| Pubkey::new_from_array([byte; 32])).collect();
+        let mut whitelist = TokenWhitelist::default();
+        for key in keys.iter() {
+            whitelist.add_keypair(&key.to_string(), &1);
+        }
+        let attested = whitelist.entries_in_key_order().unwrap()[1].0;
+        whitelist.set_attestation(&attested.to_string(), [7; 32]);
+
+        let page = whitelist.attested_entries_page(0, u8::MAX).unwrap();
+        assert_eq!(page.total, 20);
+        assert_eq!(page.entries.len(), MAX_LIST_ATTESTED_ENTRIES);
+        assert!(page.try_to_vec().unwrap().len() <= MAX_RETURN_DATA);
+        assert_eq!(page.entries[0].2, NO_ATTESTATION);
+        assert_eq!(page.entries[1], (attested, 1, [7; 32]));
+        assert_eq!(whitelist.attested_entries_page(19, 5).unwrap().entries.len(), 1);
+
+        // the attestation survives the account data and goes with the entry
+        let mut data = vec![0; ACCOUNT_STATE_SPACE + 512];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let mut unpacked = TokenWhitelist::unpack_from_slice(&data).unwrap();
+        assert_eq!(unpacked.attestation(&attested.to_string()), [7; 32]);
+        unpacked.set_attestation(&attested.to_string(), NO_ATTESTATION);
+        assert!(unpacked.extension.entry_attestations.is_empty());
+        whitelist.drop_key(&attested.to_string());
+        assert_eq!(whitelist.attestation(&attested.to_string()), NO_ATTESTATION);
+    }
+
+    #[test]
+    fn test_rotate_key() {
+        let (key, new_key) = (Pubkey::new_from_array([1; 32]).to_string(), Pubkey::new_from_array([2; 32]).to_string());
+        let mut whitelist = TokenWhitelist::default();
+        whitelist.add_keypair(&key, &100);
+        whitelist.extension.round_allocations.insert(key.clone(), [10, 20, 30]);
+        whitelist.set_campaign(&key, [4; 8]);
+        whitelist.set_attestation(&key, [5; 32]);
+        whitelist.set_note_commitment(&key, [6; 32]);
+        whitelist.extension.registration_deposits.insert(key.clone(), 1_000);
+        whitelist.extension.last_consume_slots.insert(key.clone(), 50);
+        // left behind by an earlier entry of the new key
+        whitelist.extension.registration_deposits.insert(new_key.clone(), 500);
+        whitelist.extension.last_consume_slots.insert(new_key.clone(), 40);
+
+        whitelist.rotate_key(&key, &new_key).unwrap();
+        assert_eq!(whitelist.whitelist_map.get(&new_key), Some(&100));
+        assert_eq!(whitelist.round_allocation(&new_key, 3), Some(30));
+        assert_eq!(whitelist.campaign(&new_key), [4; 8]);
+        assert_eq!(whitelist.attestation(&new_key), [5; 32]);
+        assert_eq!(whitelist.entry_note_commitment(&new_key), [6; 32]);
+        assert_eq!(whitelist.extension.registration_deposits.get(&new_key), Some(&1_500));
+        assert_eq!(whitelist.extension.last_consume_slots.get(&new_key), Some(&50));
+
+        let mut rotated_away = TokenWhitelist::default();
+        rotated_away.add_keypair(&new_key, &100);
+        rotated_away.extension.round_allocations.insert(new_key.clone(), [10, 20, 30]);
+        rotated_away.set_campaign(&new_key, [4; 8]);
+        rotated_away.set_attestation(&new_key, [5; 32]);
+        rotated_away.set_note_commitment(&new_key, [6; 32]);
+        rotated_away.extension.registration_deposits.insert(new_key.clone(), 1_500);
+        rotated_away.extension.last_consume_slots.insert(new_key.clone(), 50);
+        assert_eq!(whitelist, rotated_away);
+    }
+
+    #[test]
+    fn test_pack_matches_try_to_vec() {
+        for entries in [0u8, 1, 10, 50, 90] {
+            let whitelist = TokenWhitelist {
+                is_initialized: true,
+                whitelist_map: (0..entries)
+                    .map(|i| (Pubkey::new_from_array([i; 32]).to_string(), i as u64 * 1_000))
+                    .collect(),
+                ..TokenWhitelist::default()
+            };
+            let mut data = vec![0xff; ACCOUNT_STATE_SPACE];
+            whitelist.pack_into_slice(&mut data).unwrap();
+
+            // the layout written through a temporary Vec before the map was encoded in place
+            let encoded = whitelist.whitelist_map.try_to_vec().unwrap();
+            let map_start = INITIALIZED_BYTES + PUBKEY_BYTES + WHITELIST_SIZE_BYTES + MAP_LENGTH;
+            assert_eq!(data[map_start - MAP_LENGTH..map_start], (encoded.len() as u32).to_le_bytes());
+            assert_eq!(data[map_start..map_start + encoded.len()], encoded[..]);
+            assert!(data[map_start + encoded.len()..].iter().all(|&byte| byte == 0));
+        }
+
+        // a map past MAP_BYTES is refused before anything is written
+        let whitelist = TokenWhitelist {
+            whitelist_map: (0..120u8).map(|i| (Pubkey::new_from_array([i; 32]).to_string(), 1)).collect(),
+            ..TokenWhitelist::default()
+        };
+        let mut data = vec![0xff; ACCOUNT_STATE_SPACE];
+        assert_eq!(whitelist.pack_into_slice(&mut data), Err(TokenWhitelistError::StateInvariantViolation.into()));
+        assert!(data.iter().all(|&byte| byte == 0xff));
+    }
+
+    #[test]
+    fn test_pack_removal_into_slice() {
+        let whitelist = TokenWhitelist {
+            is_initialized: true,
+            max_whitelist_size: 100,
+            whitelist_map: (1..=90u8).map(|i| (Pubkey::new_from_array([i; 32]).to_string(), i as u64 * 1_000)).collect(),
+            ..TokenWhitelist::default()
+        };
+        let mut packed = vec![0; 10240];
+        whitelist.pack_into_slice(&mut packed).unwrap();
+        let keys: Vec<String> = whitelist.whitelist_map.keys().cloned().collect();
+
+        // the first, a middle and the last entry in key order land as a full repack would
+        for key in [&keys[0], &keys[45], &keys[89]] {
+            let mut repacked = TokenWhitelist::unpack_from_slice(&packed).unwrap();
+            assert_eq!(repacked.drop_key(key), whitelist.whitelist_map.get(key).copied());
+            let mut expected = packed.clone();
+            repacked.update_entries_hash(expected.len());
+            repacked.pack_into_slice(&mut expected).unwrap();
+
+            let mut removed = TokenWhitelist::unpack_from_slice(&packed).unwrap();
+            removed.drop_key(key);
+            let mut data = packed.clone();
+            removed.pack_removal_into_slice(&mut data, key).unwrap();
+            assert_eq!(data, expected);
+            assert_eq!(removed.extension.entries_hash, hash_entries_map(&removed.whitelist_map));
+            assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap(), removed);
+        }
+
+        // a region out of key order decodes all the same, but is not moved in place
+        let (first, second) = (&keys[0], &keys[1]);
+        let mut region = 2u32.to_le_bytes().to_vec();
+        (second.clone(), 2u64).serialize(&mut region).unwrap();
+        (first.clone(), 1u64).serialize(&mut region).unwrap();
+        let mut data = vec![0; 10240];
+        TokenWhitelist {is_initialized: true, ..TokenWhitelist::default()}.pack_into_slice(&mut data).unwrap();
+        let map_start = ACCOUNT_STATE_SPACE - MAP_BYTES;
+        data[map_start - MAP_LENGTH..map_start].copy_from_slice(&(region.len() as u32).to_le_bytes());
+        data[map_start..map_start + region.len()].copy_from_slice(&region);
+        let mut removed = TokenWhitelist::unpack_from_slice(&data).unwrap();
+        assert_eq!(removed.drop_key(second), Some(2));
+        removed.pack_removal_into_slice(&mut data, second).unwrap();
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap(), removed);
+        assert_eq!(removed.extension.entries_hash, hash_entries_map(&removed.whitelist_map));
+
+        // neither is a key the region does not hold
+        let mut removed = TokenWhitelist::unpack_from_slice(&packed).unwrap();
+        assert_eq!(removed.drop_key(&Pubkey::new_from_array([200; 32]).to_string()), None);
+        let mut data = packed.clone();
+        removed.pack_removal_into_slice(&mut data, &keys[3]).unwrap();
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap(), removed);
+    }
+
+    #[test]
+    fn test_unpack_header() {
+        let mut whitelist = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: Pubkey::new_from_array([7; 32]),
+            max_whitelist_size: 50,
+            ..TokenWhitelist::default()
+        };
+        let mut data = vec![0; ACCOUNT_STATE_SPACE];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        let header = TokenWhitelist::unpack_header(&data).unwrap();
+        assert_eq!(
+            header,
+            WhitelistHeader {is_initialized: true, init_pubkey: whitelist.init_pubkey, max_whitelist_size: 50, entries: 0}
+        );
+
+        for byte in 1..=3 {
+            whitelist.add_keypair(&Pubkey::new_from_array([byte; 32]).to_string(), &1);
+        }
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(TokenWhitelist::unpack_header(&data).unwrap().entries, 3);
+        assert_eq!(whitelist.map_bytes_remaining(), MAP_BYTES - whitelist.whitelist_map.try_to_vec().unwrap().len());
+
+        assert_eq!(TokenWhitelist::unpack_header(&data[..100]), Err(ProgramError::InvalidAccountData));
+        assert_eq!(TokenWhitelist::unpack_header(&[0; ACCOUNT_STATE_SPACE]).unwrap(), WhitelistHeader::default());
+    }
+
+    #[test]
+    fn test_recover_from_slice() {
+        let keys: Vec<String> = (1..=3).map(|byte| Pubkey::new_from_array([byte; 32]).to_string()).collect();
+        let mut whitelist = TokenWhitelist {is_initialized: true, max_whitelist_size: 10, ..TokenWhitelist::default()};
+        for key in keys.iter() {
+            whitelist.add_keypair(key, &1);
+        }
+        // the entry packed last, in key order
+        let last = whitelist.whitelist_map.keys().last().unwrap().clone();
+        whitelist.extension.round_allocations.insert(last.clone(), [5; MAX_ROUNDS - 1]);
+        whitelist.set_campaign(&last, [1; 8]);
+        let mut data = vec![0; ACCOUNT_STATE_SPACE + 1024];
+        whitelist.update_entries_hash(data.len());
+        whitelist.pack_into_slice(&mut data).unwrap();
+
+        // a healthy whitelist is recovered as it decodes
+        let (recovered, report) = TokenWhitelist::recover_from_slice(&data).unwrap();
+        assert_eq!(recovered, whitelist);
+        assert!(!report.dropped_anything());
+
+        // the last entry's key no longer a pubkey, its rounds and campaign go with it
+        let map_len = count_from_le(&data[41..45]);
+        data[45 + map_len - 9] = b'0';
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap().whitelist_map.len(), 3);
+        let (recovered, report) = TokenWhitelist::recover_from_slice(&data).unwrap();
+        assert_eq!(report, RepairReport {entries_recovered: 2, entries_dropped: 1, bytes_dropped: 0});
+        assert_eq!(recovered.whitelist_map.len(), 2);
+        assert!(!recovered.whitelist_map.contains_key(&last));
+        assert!(recovered.extension.round_allocations.is_empty());
+        assert!(recovered.extension.entry_campaigns.is_empty());
+        assert_eq!(recovered.extension.entries_hash, hash_entries_map(&recovered.whitelist_map));
+
+        // an uninitialized or archived account is not repaired
+        data[0] = 0;
+        assert_eq!(
+            TokenWhitelist::recover_from_slice(&data),
+            Err(TokenWhitelistError::TokenWhitelistNotInit.into())
+        );
+        let mut archive = vec![0; ARCHIVE_SPACE];
+        WhitelistArchive::default().pack_into_slice(&mut archive).unwrap();
+        assert_eq!(TokenWhitelist::recover_from_slice(&archive), Err(TokenWhitelistError::WhitelistArchived.into()));
+    }
+
+    #[test]
+    fn test_validate_key() {
+        let pubkey = Pubkey::new_unique();
+        assert_eq!(validate_key(&pubkey.to_string()), Ok(pubkey));
+        assert_eq!(validate_key(&Pubkey::default().to_string()), Ok(Pubkey::default()));
+        let key = pubkey.to_string();
+        for malformed in [
+            "",
+            "wallet-1",
+            &key[1..], // too short to hold 32 bytes
+            format!("1{}", key).as_str(), // a leading 1 is a leading zero byte, 33 bytes
+            format!("{}0", &key[1..]).as_str(), // 0 is outside the base58 alphabet
+            format!(" {}", key).as_str(),
+        ] {
+            assert_eq!(validate_key(malformed), Err(TokenWhitelistError::InvalidWhitelistKey), "{:?}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_unpack_from_slice_strict() {
+        let mut whitelist = TokenWhitelist {is_initialized: true, max_whitelist_size: 10, ..TokenWhitelist::default()};
+        whitelist.add_keypair(&Pubkey::new_unique().to_string(), &1);
+        let mut data = vec![0; ACCOUNT_STATE_SPACE + 1024];
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(TokenWhitelist::unpack_from_slice_strict(&data), Ok(whitelist.clone()));
+        assert_eq!(whitelist.check_new_key("wallet-1"), Err(TokenWhitelistError::InvalidWhitelistKey));
+
+        // a key stored around the insert paths decodes, but not in strict mode
+        whitelist.add_keypair(&"wallet-1".to_string(), &2);
+        whitelist.pack_into_slice(&mut data).unwrap();
+        assert_eq!(whitelist.invalid_keys().collect::<Vec<_>>(), vec!["wallet-1"]);
+        assert_eq!(TokenWhitelist::unpack_from_slice(&data), Ok(whitelist.clone()));
+        assert_eq!(
+            TokenWhitelist::unpack_from_slice_strict(&data),
+            Err(TokenWhitelistError::InvalidWhitelistKey.into())
+        );
+        assert_eq!(whitelist.entries_in_key_order(), Err(ProgramError::InvalidAccountData));
+        // once stored, a key is not checked again
+        assert_eq!(whitelist.check_new_key("wallet-1"), Ok(()));
+    }
+
+    #[test]
+    fn test_claim_bitmap() {
+        assert_eq!(claim_bitmap_len(0), CLAIM_BITMAP_HEADER);
+        assert_eq!(claim_bitmap_len(8), CLAIM_BITMAP_HEADER + 1);
+        assert_eq!(claim_bitmap_len(4096), CLAIM_BITMAP_HEADER + 512);
+
+        let bitmap = ClaimBitmap {whitelist: Pubkey::new_from_array([1; 32]), root: [2; 32], leaf_count: 4096};
+        let mut data = vec![0; claim_bitmap_len(4096)];
+        assert_eq!(ClaimBitmap::unpack_from_slice(&data), Err(ProgramError::InvalidAccountData));
+        bitmap.pack_into_slice(&mut data).unwrap();
+        assert_eq!(ClaimBitmap::unpack_from_slice(&data).unwrap(), bitmap);
+        assert_eq!(ClaimBitmap::unpack_from_slice(&data[..data.len() - 1]), Err(ProgramError::InvalidAccountData));
+
+        // the bits either side of the first byte boundary and the last one
+        for (leaf_index, byte, mask) in [(7, 0, 0x80), (8, 1, 0x01), (4095, 511, 0x80)] {
+            assert!(!ClaimBitmap::is_claimed(&data, leaf_index).unwrap());
+            ClaimBitmap::set_claimed(&mut data, leaf_index).unwrap();
+            assert!(ClaimBitmap::is_claimed(&data, leaf_index).unwrap());
+            assert_eq!(data[CLAIM_BITMAP_HEADER + byte], mask);
+            assert_eq!(
+                ClaimBitmap::set_claimed(&mut data, leaf_index),
+                Err(TokenWhitelistError::AlreadyClaimed.into())
+            );
+        }
+        assert_eq!(data[CLAIM_BITMAP_HEADER..].iter().map(|byte| byte.count_ones()).sum::<u32>(), 3);
+        assert_eq!(ClaimBitmap::is_claimed(&data, 4096), Err(ProgramError::InvalidArgument));
+    }
 }
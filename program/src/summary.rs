@@ -0,0 +1,208 @@
+use solana_program::pubkey::Pubkey;
+use std::{collections::BTreeMap, fmt};
+
+use crate::state::{TokenWhitelist, WhitelistStats, NO_CAMPAIGN};
+
+/// Aggregate view of a whitelist for operators
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhitelistSummary {
+    pub owner: Pubkey,
+    pub entries: usize,
+    pub entry_limit: Option<u64>, // None when max_whitelist_size limits nothing, see TokenWhitelist::entry_limit
+    pub max_whitelist_size: u64,
+    pub total_allocation: u128,
+    pub min_allocation: Option<u64>,
+    pub max_allocation: Option<u64>,
+    pub median_allocation: Option<u64>, // mean of the two middle allocations, rounded down, for even counts
+    pub allocation_decimals: u8,
+    pub allocation_mint: Option<Pubkey>,
+    pub campaigns: BTreeMap<[u8; 8], u32>, // entries per campaign, NO_CAMPAIGN included
+    pub stats: WhitelistStats,
+    pub created_at_slot: u64,
+    pub creator: Pubkey, // the default pubkey for whitelists initialized before creation was recorded
+}
+
+impl WhitelistSummary {
+    /// Entries as a percentage of the entry limit, None when the whitelist has none
+    pub fn utilization(&self) -> Option<f64> {
+        let limit = self.entry_limit?;
+        Some(self.entries as f64 * 100.0 / limit as f64)
+    }
+}
+
+impl TokenWhitelist {
+    pub fn summary(&self) -> WhitelistSummary {
+        let mut allocations: Vec<u64> = self.whitelist_map.values().copied().collect();
+        allocations.sort_unstable();
+
+        let median_allocation = match allocations.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(allocations[len / 2]),
+            len => {
+                let (low, high) = (allocations[len / 2 - 1], allocations[len / 2]);
+                Some(low + (high - low) / 2)
+            }
+        };
+
+        WhitelistSummary {
+            owner: self.init_pubkey,
+            entries: allocations.len(),
+            entry_limit: self.entry_limit(),
+            max_whitelist_size: self.max_whitelist_size,
+            total_allocation: allocations.iter().map(|&amount| amount as u128).sum(),
+            min_allocation: allocations.first().copied(),
+            max_allocation: allocations.last().copied(),
+            median_allocation,
+            allocation_decimals: self.extension.allocation_decimals,
+            allocation_mint: self.extension.allocation_mint,
+            campaigns: self.campaign_counts(),
+            stats: self.extension.stats,
+            created_at_slot: self.extension.created_at_slot,
+            creator: self.extension.creator,
+        }
+    }
+}
+
+impl fmt::Display for WhitelistSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "owner:      {}", self.owner)?;
+        if self.creator != Pubkey::default() {
+            writeln!(f, "created:    slot {} by {}", self.created_at_slot, self.creator)?;
+        }
+        match self.entry_limit {
+            Some(limit) => write!(f, "entries:    {} / {}", self.entries, limit)?,
+            None => write!(f, "entries:    {} / unlimited", self.entries)?,
+        }
+        match self.utilization() {
+            Some(utilization) => writeln!(f, " ({:.2}%)", utilization)?,
+            None => writeln!(f)?,
+        }
+        write!(f, "units:      {} decimals", self.allocation_decimals)?;
+        match self.allocation_mint {
+            Some(mint) => writeln!(f, " (mint {})", mint)?,
+            None => writeln!(f)?,
+        }
+        write!(f, "churn:      {} adds, {} removes", self.stats.total_adds, self.stats.total_removes)?;
+        if self.stats == WhitelistStats::default() {
+            writeln!(f)?;
+        } else {
+            writeln!(f, ", last at slot {}", self.stats.last_mutation_slot)?;
+        }
+        match (self.min_allocation, self.median_allocation, self.max_allocation) {
+            (Some(min), Some(median), Some(max)) => write!(
+                f,
+                "allocation: total {}, min {}, median {}, max {}",
+                self.total_allocation, min, median, max
+            )?,
+            _ => write!(f, "allocation: none")?,
+        }
+        for (campaign_id, count) in self.campaigns.iter().filter(|(campaign_id, _)| **campaign_id != NO_CAMPAIGN) {
+            let hex: String = campaign_id.iter().map(|byte| format!("{:02x}", byte)).collect();
+            write!(f, "\ncampaign {}: {} entries", hex, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::UNLIMITED_WHITELIST_SIZE;
+
+    fn whitelist(allocations: &[u64]) -> TokenWhitelist {
+        let mut token_whitelist = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: Pubkey::new_from_array([1; 32]),
+            max_whitelist_size: 8,
+            ..TokenWhitelist::default()
+        };
+        for (i, amount) in allocations.iter().enumerate() {
+            token_whitelist.add_keypair(&format!("wallet-{}", i), amount);
+        }
+        token_whitelist
+    }
+
+    #[test]
+    fn test_summary_empty() {
+        let summary = whitelist(&[]).summary();
+        assert_eq!(summary.entries, 0);
+        assert_eq!(summary.total_allocation, 0);
+        assert_eq!(summary.min_allocation, None);
+        assert_eq!(summary.max_allocation, None);
+        assert_eq!(summary.median_allocation, None);
+        assert_eq!(summary.utilization(), Some(0.0));
+        assert!(summary.to_string().ends_with("allocation: none"));
+    }
+
+    #[test]
+    fn test_summary_single_entry() {
+        let summary = whitelist(&[250]).summary();
+        assert_eq!(summary.entries, 1);
+        assert_eq!(summary.total_allocation, 250);
+        assert_eq!(summary.min_allocation, Some(250));
+        assert_eq!(summary.max_allocation, Some(250));
+        assert_eq!(summary.median_allocation, Some(250));
+        assert_eq!(summary.utilization(), Some(12.5));
+    }
+
+    #[test]
+    fn test_summary_statistics() {
+        let odd = whitelist(&[300, 100, 200]).summary();
+        assert_eq!(odd.total_allocation, 600);
+        assert_eq!(odd.min_allocation, Some(100));
+        assert_eq!(odd.max_allocation, Some(300));
+        assert_eq!(odd.median_allocation, Some(200));
+
+        let even = whitelist(&[u64::MAX, 1, u64::MAX - 2, 5]).summary();
+        assert_eq!(even.total_allocation, 2 * u64::MAX as u128 - 2 + 6);
+        assert_eq!(even.median_allocation, Some(5 + (u64::MAX - 2 - 5) / 2));
+        assert_eq!(even.utilization(), Some(50.0));
+
+        // legacy whitelists of size zero read as unlimited
+        let unsized_whitelist = TokenWhitelist {max_whitelist_size: 0, ..whitelist(&[1])};
+        assert_eq!(unsized_whitelist.summary().utilization(), None);
+        let unlimited = TokenWhitelist {max_whitelist_size: UNLIMITED_WHITELIST_SIZE, ..whitelist(&[1])};
+        assert_eq!(unlimited.summary().utilization(), None);
+        assert!(unlimited.summary().to_string().contains("entries:    1 / unlimited\n"));
+    }
+
+    #[test]
+    fn test_summary_display() {
+        let summary = whitelist(&[300, 100, 200]).summary();
+        assert_eq!(
+            summary.to_string(),
+            format!(
+                "owner:      {}\nentries:    3 / 8 (37.50%)\nunits:      0 decimals\n\
+                 churn:      0 adds, 0 removes\nallocation: total 600, min 100, median 200, max 300",
+                Pubkey::new_from_array([1; 32])
+            )
+        );
+
+        let mut token_whitelist = whitelist(&[1_500_000]);
+        token_whitelist.extension.allocation_decimals = 6;
+        token_whitelist.extension.allocation_mint = Some(Pubkey::new_from_array([2; 32]));
+        let summary = token_whitelist.summary();
+        assert_eq!(summary.allocation_decimals, 6);
+        assert!(summary.to_string().contains(&format!("units:      6 decimals (mint {})\n", Pubkey::new_from_array([2; 32]))));
+
+        token_whitelist.extension.stats = WhitelistStats {total_adds: 4, total_removes: 1, last_mutation_slot: 77};
+        assert!(token_whitelist.summary().to_string().contains("churn:      4 adds, 1 removes, last at slot 77\n"));
+
+        token_whitelist.extension.created_at_slot = 12;
+        token_whitelist.extension.creator = Pubkey::new_from_array([3; 32]);
+        assert!(token_whitelist.summary().to_string().contains(&format!(
+            "\ncreated:    slot 12 by {}\nentries:",
+            Pubkey::new_from_array([3; 32])
+        )));
+    }
+
+    #[test]
+    fn test_summary_campaigns() {
+        let mut token_whitelist = whitelist(&[100, 200, 300]);
+        token_whitelist.set_campaign("wallet-0", [1; 8]);
+        token_whitelist.set_campaign("wallet-1", [1; 8]);
+        let summary = token_whitelist.summary();
+        assert_eq!(summary.campaigns, BTreeMap::from([(NO_CAMPAIGN, 1), ([1; 8], 2)]));
+        assert!(summary.to_string().ends_with("max 300\ncampaign 0101010101010101: 2 entries"));
+    }
+}
@@ -0,0 +1,451 @@
+//! Canonical encodings for clients written in other languages to test against, rendered as the
+//! JSON checked in as `test_vectors.json`: every instruction variant with sample values and its
+//! packed bytes, and whitelist states with the whole account they pack into. u64 values are
+//! written as decimal strings, JavaScript numbers lose them past 2^53; pubkeys are base58 and
+//! other byte arrays hex. Host side only, built with the `test-vectors` feature.
+
+use solana_program::pubkey::Pubkey;
+use crate::{
+    instruction::{MerkleClaim, TokenWhitelistInstruction},
+    state::{hash_entries_map, note_commitment, TokenWhitelist, TokenWhitelistExtension, WhitelistStats, NO_CAMPAIGN},
+};
+
+/// Size of the account the `extended` state packs into, past the 5161 bytes of the layout
+const EXTENDED_ACCOUNT_SPACE: usize = 6144;
+
+/// A named whitelist state and the size of the account it packs into
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountVector {
+    pub name: &'static str,
+    pub space: usize,
+    pub state: TokenWhitelist,
+}
+
+impl AccountVector {
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; self.space];
+        self.state.pack_into_slice(&mut data).unwrap();
+        data
+    }
+}
+
+/// One sample of every variant, plus one for each trailing field packed only when set: a
+/// campaign, an expected nonce, a memo and the flags of InitTokenWhitelistWithUnits
+pub fn instruction_vectors() -> Vec<TokenWhitelistInstruction> {
+    use TokenWhitelistInstruction::*;
+    let campaign_id = [1, 2, 3, 4, 5, 6, 7, 8];
+    let memo = Some("duplicate".to_string());
+    vec![
+        InitTokenWhitelist {max_whitelist_size: 50},
+        AddToWhitelist {allocation_amount: 250_000_000, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+        AddToWhitelist {allocation_amount: 250_000_000, campaign_id, expected_nonce: 0},
+        AddToWhitelist {allocation_amount: 250_000_000, campaign_id: NO_CAMPAIGN, expected_nonce: 7},
+        AddToWhitelist {allocation_amount: 250_000_000, campaign_id, expected_nonce: 7},
+        RemoveFromWhitelist {memo: None, expected_nonce: 0},
+        RemoveFromWhitelist {memo: None, expected_nonce: 7},
+        RemoveFromWhitelist {memo: memo.clone(), expected_nonce: 7},
+        SetAllocationToZero {memo: None},
+        SetAllocationToZero {memo},
+        CloseWhitelistAccount {},
+        ApproveDelegate {max_adds: 10, max_allocation_per_add: 1_000_000},
+        RevokeDelegate {},
+        ConfigureRegistration {
+            registration_end_slot: 5000,
+            registration_allocation: 2_000_000,
+            registration_deposit_lamports: 10_000_000,
+        },
+        RegisterSelf {},
+        RefundDeposit {},
+        RunLottery {winners: 3, seed: [9; 32]},
+        SetConsumeLimits {max_consume_per_tx: 500, min_slots_between_consumes: 25},
+        ConsumeAllocation {amount: 100},
+        ListEntries {offset: 40, limit: 20},
+        ComputeEntriesHash {},
+        InitTokenWhitelistWithUnits {
+            max_whitelist_size: 50,
+            allocation_decimals: Some(6),
+            only_wallets: false,
+            self_zero_allowed: true,
+        },
+        InitTokenWhitelistWithUnits {
+            max_whitelist_size: 50,
+            allocation_decimals: None,
+            only_wallets: true,
+            self_zero_allowed: false,
+        },
+        SetAllocationBounds {min_allocation: 100, max_allocation: 5_000},
+        AddToWhitelistForRound {allocation_amount: 250_000_000, round: 1, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+        AddToWhitelistForRound {allocation_amount: 250_000_000, round: 1, campaign_id, expected_nonce: 7},
+        ConsumeAllocationForRound {amount: 100, round: 255},
+        SetActiveRound {round: 1},
+        GetAllocation {round: 255},
+        AssertMembershipAcross {require_in_first: true, require_absent_in_second: true},
+        ArchiveWhitelist {},
+        GetVersion {},
+        SetStrictTokenChecks {strict_token_checks: true},
+        SetMerkleRoot {root: [0xab; 32], hash_kind: 1},
+        VerifyMerkleMembership {leaf_index: 7, allocation: 250_000_000, proof: vec![[3; 32], [4; 32]]},
+        VerifyMerkleMembershipBatch {
+            items: vec![
+                MerkleClaim {
+                    wallet: Pubkey::new_from_array([5; 32]),
+                    leaf_index: 1,
+                    allocation: 1000,
+                    proof: vec![[3; 32]],
+                },
+                MerkleClaim {
+                    wallet: Pubkey::new_from_array([6; 32]),
+                    leaf_index: 2,
+                    allocation: 2000,
+                    proof: vec![],
+                },
+            ],
+        },
+        InitClaimBitmap {leaf_count: 4096},
+        AssertOwner {},
+        SetOnlyWallets {only_wallets: true},
+        InitConfig {},
+        SetSelfZeroAllowed {self_zero_allowed: false},
+        ListEntriesByCampaign {campaign_id, offset: 40, limit: 20},
+        TopUpRent {},
+        RepairState {},
+        SetZeroAllocationAllowed {allow_zero_allocation: false},
+        SetTokenGate {gating_mint: Pubkey::new_from_array([3; 32]), gating_minimum: 1_000, gate_owner_adds: true},
+        MigrateStateLayout {},
+        InspectState {max_entries_to_log: 5},
+        EffectiveAllocation {},
+        InitCompressedWhitelist {},
+        AppendCompressedEntry {allocation_amount: 250_000_000},
+        ReplaceCompressedEntry {
+            leaf_index: 7,
+            previous_allocation: 250_000_000,
+            allocation_amount: 100_000_000,
+            root: [3; 32],
+        },
+        VerifyCompressedMembership {leaf_index: 7, allocation: 250_000_000, root: [3; 32]},
+        GetWhitelistStats {},
+        AddToWhitelistWithAttestation {allocation_amount: 250_000_000, attestation: [3; 32]},
+        SetAttestation {attestation: [3; 32]},
+        SetAttestationRequired {require_attestation: true},
+        ListAttestedEntries {offset: 40, limit: 14},
+        RotateWhitelistedKey {},
+        AssertNotInOther {},
+        SetOwnerEntryAllowed {allow_owner_entry: false},
+        GetRequiredRent {max_whitelist_size: 50},
+        SetEntryNoteCommitment {commitment: [3; 32]},
+        RevealEntryNote {note: b"tier 2".to_vec(), salt: [9; 16]},
+        SetRegistrationRateLimit {max_registrations_per_window: 20, window_slots: 150},
+        SetEnumerationRestricted {restrict_enumeration: true},
+    ]
+}
+
+/// Whitelists owned by `[7; 32]`, their entry `i` keyed by `Pubkey::new_from_array([i; 32])` with
+/// allocation `i * 1_000_000` as in the layout fixtures: three in accounts of exactly the layout,
+/// which leaves no room for the extension, and one with extension fields set
+pub fn account_vectors() -> Vec<AccountVector> {
+    let whitelist = |max_whitelist_size: u64, entries: u8| {
+        let mut state = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: Pubkey::new_from_array([7; 32]),
+            max_whitelist_size,
+            ..TokenWhitelist::default()
+        };
+        for i in 1..=entries {
+            state.add_keypair(&Pubkey::new_from_array([i; 32]).to_string(), &(i as u64 * 1_000_000));
+        }
+        state
+    };
+
+    let mut extended = whitelist(50, 3);
+    let key = |i: u8| Pubkey::new_from_array([i; 32]).to_string();
+    extended.extension = TokenWhitelistExtension {
+        entries_hash: hash_entries_map(&extended.whitelist_map),
+        allocation_decimals: 6,
+        active_round: 1,
+        round_allocations: vec![(key(1), [500_000, 0, 0])].into_iter().collect(),
+        entry_campaigns: vec![(key(2), [1, 2, 3, 4, 5, 6, 7, 8])].into_iter().collect(),
+        mutation_nonce: 3,
+        stats: WhitelistStats {total_adds: 3, total_removes: 0, last_mutation_slot: 120},
+        entry_attestations: vec![(key(3), [3; 32])].into_iter().collect(),
+        created_at_slot: 100,
+        creator: Pubkey::new_from_array([7; 32]),
+        entry_note_commitments: vec![(key(1), note_commitment(b"tier 2", &[9; 16]))].into_iter().collect(),
+        ..TokenWhitelistExtension::default()
+    };
+
+    vec![
+        AccountVector {name: "init_only", space: TokenWhitelist::MIN_SPACE, state: whitelist(50, 0)},
+        AccountVector {name: "small_map", space: TokenWhitelist::MIN_SPACE, state: whitelist(50, 3)},
+        AccountVector {name: "near_capacity", space: TokenWhitelist::MIN_SPACE, state: whitelist(100, 90)},
+        AccountVector {name: "extended", space: EXTENDED_ACCOUNT_SPACE, state: extended},
+    ]
+}
+
+/// The extension fields [to_json](fn.to_json.html) writes out, the others of every account
+/// vector are left at their defaults
+pub fn described_extension(extension: &TokenWhitelistExtension) -> TokenWhitelistExtension {
+    TokenWhitelistExtension {
+        entries_hash: extension.entries_hash,
+        allocation_decimals: extension.allocation_decimals,
+        active_round: extension.active_round,
+        round_allocations: extension.round_allocations.clone(),
+        entry_campaigns: extension.entry_campaigns.clone(),
+        mutation_nonce: extension.mutation_nonce,
+        stats: extension.stats,
+        entry_attestations: extension.entry_attestations.clone(),
+        created_at_slot: extension.created_at_slot,
+        creator: extension.creator,
+        entry_note_commitments: extension.entry_note_commitments.clone(),
+        ..TokenWhitelistExtension::default()
+    }
+}
+
+/// The vectors as JSON, one vector per line so that a change shows as a small diff
+pub fn to_json() -> String {
+    let instructions: Vec<String> = instruction_vectors()
+        .iter()
+        .map(|instruction| {
+            format!(
+                "    {{\"instruction\": {}, \"fields\": {}, \"bytes\": {}}}",
+                string(&variant_name(instruction)),
+                object(fields(instruction)),
+                string(&hex(&instruction.pack()))
+            )
+        })
+        .collect();
+    let accounts: Vec<String> = account_vectors()
+        .iter()
+        .map(|vector| {
+            format!(
+                "    {{\"name\": {}, \"space\": {}, \"state\": {}, \"bytes\": {}}}",
+                string(vector.name),
+                vector.space,
+                state_json(&vector.state),
+                string(&hex(&vector.bytes()))
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"instructions\": [\n{}\n  ],\n  \"accounts\": [\n{}\n  ]\n}}\n",
+        instructions.join(",\n"),
+        accounts.join(",\n")
+    )
+}
+
+/// Name of the variant, as its Debug output starts with it
+fn variant_name(instruction: &TokenWhitelistInstruction) -> String {
+    let debug = format!("{:?}", instruction);
+    debug.split([' ', '{']).next().unwrap().to_string()
+}
+
+fn fields(instruction: &TokenWhitelistInstruction) -> Vec<(&'static str, String)> {
+    use TokenWhitelistInstruction::*;
+    match instruction {
+        InitTokenWhitelist {max_whitelist_size} | GetRequiredRent {max_whitelist_size} => {
+            vec![("max_whitelist_size", u64_string(*max_whitelist_size))]
+        }
+        AddToWhitelist {allocation_amount, campaign_id, expected_nonce} => vec![
+            ("allocation_amount", u64_string(*allocation_amount)),
+            ("campaign_id", string(&hex(campaign_id))),
+            ("expected_nonce", u64_string(*expected_nonce)),
+        ],
+        RemoveFromWhitelist {memo, expected_nonce} => vec![
+            ("memo", memo.as_deref().map_or("null".to_string(), string)),
+            ("expected_nonce", u64_string(*expected_nonce)),
+        ],
+        SetAllocationToZero {memo} => vec![("memo", memo.as_deref().map_or("null".to_string(), string))],
+        ApproveDelegate {max_adds, max_allocation_per_add} => vec![
+            ("max_adds", max_adds.to_string()),
+            ("max_allocation_per_add", u64_string(*max_allocation_per_add)),
+        ],
+        ConfigureRegistration {registration_end_slot, registration_allocation, registration_deposit_lamports} => vec![
+            ("registration_end_slot", u64_string(*registration_end_slot)),
+            ("registration_allocation", u64_string(*registration_allocation)),
+            ("registration_deposit_lamports", u64_string(*registration_deposit_lamports)),
+        ],
+        RunLottery {winners, seed} => vec![("winners", u64_string(*winners)), ("seed", string(&hex(seed)))],
+        SetConsumeLimits {max_consume_per_tx, min_slots_between_consumes} => vec![
+            ("max_consume_per_tx", u64_string(*max_consume_per_tx)),
+            ("min_slots_between_consumes", u64_string(*min_slots_between_consumes)),
+        ],
+        ConsumeAllocation {amount} => vec![("amount", u64_string(*amount))],
+        SetRegistrationRateLimit {max_registrations_per_window, window_slots} => vec![
+            ("max_registrations_per_window", max_registrations_per_window.to_string()),
+            ("window_slots", window_slots.to_string()),
+        ],
+        ListEntries {offset, limit} | ListAttestedEntries {offset, limit} => {
+            vec![("offset", offset.to_string()), ("limit", limit.to_string())]
+        }
+        InitTokenWhitelistWithUnits {max_whitelist_size, allocation_decimals, only_wallets, self_zero_allowed} => vec![
+            ("max_whitelist_size", u64_string(*max_whitelist_size)),
+            ("allocation_decimals", allocation_decimals.map_or("null".to_string(), |decimals| decimals.to_string())),
+            ("only_wallets", only_wallets.to_string()),
+            ("self_zero_allowed", self_zero_allowed.to_string()),
+        ],
+        SetAllocationBounds {min_allocation, max_allocation} => vec![
+            ("min_allocation", u64_string(*min_allocation)),
+            ("max_allocation", u64_string(*max_allocation)),
+        ],
+        AddToWhitelistForRound {allocation_amount, round, campaign_id, expected_nonce} => vec![
+            ("allocation_amount", u64_string(*allocation_amount)),
+            ("round", round.to_string()),
+            ("campaign_id", string(&hex(campaign_id))),
+            ("expected_nonce", u64_string(*expected_nonce)),
+        ],
+        ConsumeAllocationForRound {amount, round} => {
+            vec![("amount", u64_string(*amount)), ("round", round.to_string())]
+        }
+        SetActiveRound {round} | GetAllocation {round} => vec![("round", round.to_string())],
+        AssertMembershipAcross {require_in_first, require_absent_in_second} => vec![
+            ("require_in_first", require_in_first.to_string()),
+            ("require_absent_in_second", require_absent_in_second.to_string()),
+        ],
+        SetStrictTokenChecks {strict_token_checks} => vec![("strict_token_checks", strict_token_checks.to_string())],
+        SetMerkleRoot {root, hash_kind} => vec![("root", string(&hex(root))), ("hash_kind", hash_kind.to_string())],
+        VerifyMerkleMembership {leaf_index, allocation, proof} => vec![
+            ("leaf_index", leaf_index.to_string()),
+            ("allocation", u64_string(*allocation)),
+            ("proof", proof_json(proof)),
+        ],
+        VerifyMerkleMembershipBatch {items} => {
+            let items = items.iter().map(|item| {
+                object(vec![
+                    ("wallet", string(&item.wallet.to_string())),
+                    ("leaf_index", item.leaf_index.to_string()),
+                    ("allocation", u64_string(item.allocation)),
+                    ("proof", proof_json(&item.proof)),
+                ])
+            });
+            vec![("items", list(items))]
+        }
+        InitClaimBitmap {leaf_count} => vec![("leaf_count", leaf_count.to_string())],
+        SetOnlyWallets {only_wallets} => vec![("only_wallets", only_wallets.to_string())],
+        SetSelfZeroAllowed {self_zero_allowed} => vec![("self_zero_allowed", self_zero_allowed.to_string())],
+        ListEntriesByCampaign {campaign_id, offset, limit} => vec![
+            ("campaign_id", string(&hex(campaign_id))),
+            ("offset", offset.to_string()),
+            ("limit", limit.to_string()),
+        ],
+        SetZeroAllocationAllowed {allow_zero_allocation} => {
+            vec![("allow_zero_allocation", allow_zero_allocation.to_string())]
+        }
+        SetTokenGate {gating_mint, gating_minimum, gate_owner_adds} => vec![
+            ("gating_mint", string(&gating_mint.to_string())),
+            ("gating_minimum", u64_string(*gating_minimum)),
+            ("gate_owner_adds", gate_owner_adds.to_string()),
+        ],
+        InspectState {max_entries_to_log} => vec![("max_entries_to_log", max_entries_to_log.to_string())],
+        AppendCompressedEntry {allocation_amount} => vec![("allocation_amount", u64_string(*allocation_amount))],
+        ReplaceCompressedEntry {leaf_index, previous_allocation, allocation_amount, root} => vec![
+            ("leaf_index", leaf_index.to_string()),
+            ("previous_allocation", u64_string(*previous_allocation)),
+            ("allocation_amount", u64_string(*allocation_amount)),
+            ("root", string(&hex(root))),
+        ],
+        VerifyCompressedMembership {leaf_index, allocation, root} => vec![
+            ("leaf_index", leaf_index.to_string()),
+            ("allocation", u64_string(*allocation)),
+            ("root", string(&hex(root))),
+        ],
+        AddToWhitelistWithAttestation {allocation_amount, attestation} => vec![
+            ("allocation_amount", u64_string(*allocation_amount)),
+            ("attestation", string(&hex(attestation))),
+        ],
+        SetAttestation {attestation} => vec![("attestation", string(&hex(attestation)))],
+        SetEntryNoteCommitment {commitment} => vec![("commitment", string(&hex(commitment)))],
+        RevealEntryNote {note, salt} => vec![("note", string(&hex(note))), ("salt", string(&hex(salt)))],
+        SetAttestationRequired {require_attestation} => {
+            vec![("require_attestation", require_attestation.to_string())]
+        }
+        SetOwnerEntryAllowed {allow_owner_entry} => vec![("allow_owner_entry", allow_owner_entry.to_string())],
+        SetEnumerationRestricted {restrict_enumeration} => {
+            vec![("restrict_enumeration", restrict_enumeration.to_string())]
+        }
+        CloseWhitelistAccount {}
+        | RevokeDelegate {}
+        | RegisterSelf {}
+        | RefundDeposit {}
+        | ComputeEntriesHash {}
+        | ArchiveWhitelist {}
+        | GetVersion {}
+        | AssertOwner {}
+        | InitConfig {}
+        | TopUpRent {}
+        | RepairState {}
+        | MigrateStateLayout {}
+        | EffectiveAllocation {}
+        | InitCompressedWhitelist {}
+        | GetWhitelistStats {}
+        | RotateWhitelistedKey {}
+        | AssertNotInOther {} => vec![],
+    }
+}
+
+fn state_json(state: &TokenWhitelist) -> String {
+    let entries = state.whitelist_map.iter().map(|(key, amount)| {
+        list(vec![string(key), u64_string(*amount)].into_iter())
+    });
+    let extension = &state.extension;
+    let round_allocations = extension.round_allocations.iter().map(|(key, allocations)| {
+        (key.as_str(), list(allocations.iter().map(|allocation| u64_string(*allocation))))
+    });
+    let entry_campaigns =
+        extension.entry_campaigns.iter().map(|(key, campaign_id)| (key.as_str(), string(&hex(campaign_id))));
+    let entry_attestations =
+        extension.entry_attestations.iter().map(|(key, attestation)| (key.as_str(), string(&hex(attestation))));
+    let entry_note_commitments =
+        extension.entry_note_commitments.iter().map(|(key, commitment)| (key.as_str(), string(&hex(commitment))));
+    let stats = object(vec![
+        ("total_adds", u64_string(extension.stats.total_adds)),
+        ("total_removes", u64_string(extension.stats.total_removes)),
+        ("last_mutation_slot", u64_string(extension.stats.last_mutation_slot)),
+    ]);
+    object(vec![
+        ("is_initialized", state.is_initialized.to_string()),
+        ("init_pubkey", string(&state.init_pubkey.to_string())),
+        ("max_whitelist_size", u64_string(state.max_whitelist_size)),
+        ("entries", list(entries)),
+        (
+            "extension",
+            object(vec![
+                ("entries_hash", string(&hex(&extension.entries_hash))),
+                ("allocation_decimals", extension.allocation_decimals.to_string()),
+                ("active_round", extension.active_round.to_string()),
+                ("round_allocations", object(round_allocations.collect())),
+                ("entry_campaigns", object(entry_campaigns.collect())),
+                ("mutation_nonce", u64_string(extension.mutation_nonce)),
+                ("stats", stats),
+                ("entry_attestations", object(entry_attestations.collect())),
+                ("created_at_slot", u64_string(extension.created_at_slot)),
+                ("creator", string(&extension.creator.to_string())),
+                ("entry_note_commitments", object(entry_note_commitments.collect())),
+            ]),
+        ),
+    ])
+}
+
+fn proof_json(proof: &[[u8; 32]]) -> String {
+    list(proof.iter().map(|node| string(&hex(node))))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn u64_string(value: u64) -> String {
+    string(&value.to_string())
+}
+
+/// Memos, keys and names here are plain ASCII, only quotes and backslashes need escaping
+fn string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(", "))
+}
+
+fn object(fields: Vec<(&str, String)>) -> String {
+    let fields: Vec<String> = fields.into_iter().map(|(name, value)| format!("{}: {}", string(name), value)).collect();
+    format!("{{{}}}", fields.join(", "))
+}
@@ -0,0 +1,66 @@
+//! program-test helpers for integrators, enabled by the `test-utils` feature
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar,
+};
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use crate::{
+    instruction::{add_to_whitelist, TokenWhitelistInstruction},
+    processor::Processor,
+};
+
+solana_program::declare_id!("TWhitest11111111111111111111111111111111111");
+
+/// Whitelist account size, with room for the state extension
+pub const WHITELIST_ACCOUNT_SPACE: usize = 10240;
+
+/// Adds the whitelist program at [id](fn.id.html), from `solr_token_whitelist.so` when
+/// `BPF_OUT_DIR` points at it and from the native processor otherwise
+pub fn add_token_whitelist_program(program_test: &mut ProgramTest) {
+    program_test.add_program("solr_token_whitelist", id(), processor!(Processor::process));
+}
+
+/// Creates a rent exempt whitelist account and initializes it with `payer` as the owner
+pub async fn create_and_init_whitelist(banks: &mut BanksClient, payer: &Keypair, max_size: u64) -> Pubkey {
+    let whitelist = Keypair::new();
+    let create = system_instruction::create_account(
+        &payer.pubkey(),
+        &whitelist.pubkey(),
+        Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+        WHITELIST_ACCOUNT_SPACE as u64,
+        &id(),
+    );
+    let init = Instruction::new_with_bytes(
+        id(),
+        &TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size: max_size}.pack(),
+        vec![
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(whitelist.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    );
+    send(banks, payer, &[create, init], &[&whitelist]).await;
+    whitelist.pubkey()
+}
+
+/// Adds `wallet` with `amount`, or updates its allocation, signed by the whitelist owner
+pub async fn add_entry(banks: &mut BanksClient, owner: &Keypair, whitelist: &Pubkey, wallet: &Pubkey, amount: u64) {
+    let add = add_to_whitelist(&id(), &owner.pubkey(), whitelist, wallet, amount);
+    send(banks, owner, &[add], &[]).await;
+}
+
+async fn send(banks: &mut BanksClient, payer: &Keypair, instructions: &[Instruction], signers: &[&Keypair]) {
+    let blockhash = banks.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, blockhash);
+    banks.process_transaction(transaction).await.unwrap();
+}
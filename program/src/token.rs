@@ -0,0 +1,193 @@
+//! Mints and token accounts of either SPL Token or Token-2022, decoded with their extensions
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::{Account, Mint},
+};
+use crate::{error::TokenWhitelistError, state::TokenGate};
+
+/// Mint extensions keeping holders from moving their tokens freely
+const RESTRICTED_MINT_EXTENSIONS: [ExtensionType; 2] =
+    [ExtensionType::NonTransferable, ExtensionType::PermanentDelegate];
+
+/// Whether the account belongs to SPL Token or Token-2022
+pub fn is_token_program(owner: &Pubkey) -> bool {
+    owner == &spl_token::id() || owner == &spl_token_2022::id()
+}
+
+/// Decodes a mint of either token program, wrapped SOL included
+pub fn unpack_mint(mint_info: &AccountInfo) -> Result<Mint, ProgramError> {
+    if !is_token_program(mint_info.owner) {
+        msg!("mint {} must be owned by a token program", mint_info.key);
+        return Err(TokenWhitelistError::InvalidMint.into());
+    }
+    let data = mint_info.data.borrow();
+    let mint = StateWithExtensions::<Mint>::unpack(&data).map_err(|_| TokenWhitelistError::InvalidMint)?;
+    Ok(mint.base)
+}
+
+/// Decodes a token account of either token program. Under strict checks a non-transferable
+/// account is refused, and so is a Token-2022 account unless `mint_info` is its mint and that
+/// mint is neither non-transferable nor under a permanent delegate.
+pub fn check_token_account(
+    account_info: &AccountInfo,
+    mint_info: Option<&AccountInfo>,
+    strict: bool,
+) -> Result<Account, ProgramError> {
+    let data = account_info.data.borrow();
+    let account = StateWithExtensions::<Account>::unpack(&data).map_err(|_| {
+        msg!("token account {} does not decode", account_info.key);
+        ProgramError::InvalidAccountData
+    })?;
+    if !strict {
+        return Ok(account.base);
+    }
+    if account.get_extension_types()?.contains(&ExtensionType::NonTransferableAccount) {
+        msg!("token account {} is non-transferable", account_info.key);
+        return Err(TokenWhitelistError::RestrictedTokenExtension.into());
+    }
+    if account_info.owner == &spl_token_2022::id() {
+        let mint_info = mint_info.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if mint_info.key != &account.base.mint || mint_info.owner != &spl_token_2022::id() {
+            msg!("mint {} of token account {} must follow it", account.base.mint, account_info.key);
+            return Err(TokenWhitelistError::InvalidMint.into());
+        }
+        let mint_data = mint_info.data.borrow();
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data).map_err(|_| TokenWhitelistError::InvalidMint)?;
+        let extensions = mint.get_extension_types()?;
+        if let Some(extension) = RESTRICTED_MINT_EXTENSIONS.iter().find(|extension| extensions.contains(extension)) {
+            msg!("mint {} has the {:?} extension", mint_info.key, extension);
+            return Err(TokenWhitelistError::RestrictedTokenExtension.into());
+        }
+    }
+    Ok(account.base)
+}
+
+/// Checks that `holder` holds at least the gating minimum, in the first account of `accounts`
+/// that is a token account of the gating mint owned by `holder`. Accounts of other mints, other
+/// owners or other programs are passed over, so the token account may follow any others.
+pub fn check_token_gate(accounts: &[AccountInfo], gate: &TokenGate, holder: &Pubkey) -> ProgramResult {
+    let amount = accounts.iter()
+        .filter(|account_info| is_token_program(account_info.owner))
+        .find_map(|account_info| {
+            let data = account_info.data.borrow();
+            let account = StateWithExtensions::<Account>::unpack(&data).ok()?;
+            (account.base.mint == gate.gating_mint && account.base.owner == *holder).then_some(account.base.amount)
+        });
+    let amount = match amount {
+        Some(amount) => amount,
+        None => {
+            msg!("no token account of the gating mint {} held by {} was passed", gate.gating_mint, holder);
+            return Err(TokenWhitelistError::GatingAccountMissing.into());
+        }
+    };
+    if amount < gate.gating_minimum {
+        msg!("{} holds {} of the gating mint, {} are required", holder, amount, gate.gating_minimum);
+        return Err(TokenWhitelistError::InsufficientGatingBalance.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_fixture::AccountFixture;
+    use solana_program::program_pack::Pack;
+    use spl_token_2022::{extension::StateWithExtensionsMut, state::AccountState};
+
+    fn token_account(owner: Pubkey, mint: &Pubkey, extensions: &[ExtensionType]) -> AccountFixture {
+        let len = ExtensionType::try_calculate_account_len::<Account>(extensions).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+        for extension in extensions {
+            state.init_account_extension_from_type(*extension).unwrap();
+        }
+        state.base = Account {mint: *mint, state: AccountState::Initialized, ..Account::default()};
+        state.pack_base();
+        if !extensions.is_empty() {
+            state.init_account_type().unwrap();
+        }
+        AccountFixture::new(Pubkey::new_unique()).owner(owner).data(data)
+    }
+
+    #[test]
+    fn test_check_legacy_token_account() {
+        let mint = Pubkey::new_unique();
+        let mut account = token_account(spl_token::id(), &mint, &[]);
+        assert_eq!(check_token_account(&account.info(), None, true).unwrap().mint, mint);
+
+        let mut truncated = account.clone().data(account.data[..Account::LEN - 1].to_vec());
+        assert_eq!(check_token_account(&truncated.info(), None, false), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_check_non_transferable_account() {
+        let mint = Pubkey::new_unique();
+        let mut account = token_account(spl_token_2022::id(), &mint, &[ExtensionType::NonTransferableAccount]);
+        assert!(check_token_account(&account.info(), None, false).is_ok());
+        assert_eq!(
+            check_token_account(&account.info(), None, true),
+            Err(TokenWhitelistError::RestrictedTokenExtension.into())
+        );
+    }
+
+    #[test]
+    fn test_check_token_2022_account_needs_mint() {
+        let mint = Pubkey::new_unique();
+        let mut account = token_account(spl_token_2022::id(), &mint, &[]);
+        assert_eq!(check_token_account(&account.info(), None, true), Err(ProgramError::NotEnoughAccountKeys));
+
+        let mut other_mint = AccountFixture::new(Pubkey::new_unique()).owner(spl_token_2022::id());
+        assert_eq!(
+            check_token_account(&account.info(), Some(&other_mint.info()), true),
+            Err(TokenWhitelistError::InvalidMint.into())
+        );
+    }
+
+    fn gating_account(owner: Pubkey, holder: &Pubkey, mint: &Pubkey, amount: u64) -> AccountFixture {
+        let mut account = token_account(owner, mint, &[]);
+        let mut state = StateWithExtensionsMut::<Account>::unpack(&mut account.data).unwrap();
+        state.base.owner = *holder;
+        state.base.amount = amount;
+        state.pack_base();
+        account
+    }
+
+    #[test]
+    fn test_check_token_gate() {
+        let (holder, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let gate = TokenGate {gating_mint: mint, gating_minimum: 1_000, gate_owner_adds: false};
+        for program in [spl_token::id(), spl_token_2022::id()] {
+            let mut exact = gating_account(program, &holder, &mint, 1_000);
+            assert_eq!(check_token_gate(&[exact.info()], &gate, &holder), Ok(()));
+            let mut below = gating_account(program, &holder, &mint, 999);
+            assert_eq!(
+                check_token_gate(&[below.info()], &gate, &holder),
+                Err(TokenWhitelistError::InsufficientGatingBalance.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_token_gate_skips_other_accounts() {
+        let (holder, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let gate = TokenGate {gating_mint: mint, gating_minimum: 1_000, gate_owner_adds: false};
+        let mut other_mint = gating_account(spl_token::id(), &holder, &Pubkey::new_unique(), 5_000);
+        let mut other_holder = gating_account(spl_token::id(), &Pubkey::new_unique(), &mint, 5_000);
+        let mut not_token = gating_account(Pubkey::new_unique(), &holder, &mint, 5_000);
+        assert_eq!(
+            check_token_gate(&[other_mint.info(), other_holder.info(), not_token.info()], &gate, &holder),
+            Err(TokenWhitelistError::GatingAccountMissing.into())
+        );
+
+        let mut held = gating_account(spl_token::id(), &holder, &mint, 5_000);
+        assert_eq!(check_token_gate(&[other_mint.info(), other_holder.info(), held.info()], &gate, &holder), Ok(()));
+    }
+}
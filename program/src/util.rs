@@ -0,0 +1,173 @@
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::error::TokenWhitelistError;
+
+use std::convert::TryInto;
+
+/// Moves `amount` lamports from `from` to `to`. Every instruction moving lamports goes through
+/// here, both balances are checked before either one changes.
+pub fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
+    // a duplicated account is one balance, debiting and crediting it would mint lamports
+    if from.key == to.key {
+        msg!("cannot transfer lamports from {} to itself", from.key);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let from_remaining = from.lamports()
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    let to_total = to.lamports()
+        .checked_add(amount)
+        .ok_or(TokenWhitelistError::Overflow)?;
+    **from.lamports.borrow_mut() = from_remaining;
+    **to.lamports.borrow_mut() = to_total;
+    Ok(())
+}
+
+/// Whether `key` is a point on the ed25519 curve, which a keypair can sign for. Program derived
+/// addresses are off the curve by construction. `Pubkey::is_on_curve` is unavailable on chain,
+/// where the curve25519 syscall validates the point instead.
+pub fn is_on_curve(key: &Pubkey) -> bool {
+    #[cfg(target_os = "solana")]
+    {
+        const CURVE25519_EDWARDS: u64 = 0;
+        let mut validate_result = 0u8;
+        let result = unsafe {
+            solana_program::syscalls::sol_curve_validate_point(
+                CURVE25519_EDWARDS,
+                key.as_ref().as_ptr(),
+                &mut validate_result,
+            )
+        };
+        result == 0
+    }
+    #[cfg(not(target_os = "solana"))]
+    key.is_on_curve()
+}
+
+/// Bincode tag of the ProgramData variant of the upgradeable loader state
+const PROGRAM_DATA_TAG: u32 = 3;
+
+/// Reads the upgrade authority of `program_id` from its ProgramData account, None once the
+/// program was made immutable. The account has to be the one at the address the upgradeable
+/// loader derives from the program id, owned by the loader. The state is read by hand, a u32
+/// variant tag, the slot of the last deploy and an optional authority, to keep bincode out of
+/// the program.
+pub fn upgrade_authority(program_data: &AccountInfo, program_id: &Pubkey) -> Result<Option<Pubkey>, ProgramError> {
+    let (expected, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if *program_data.key != expected {
+        msg!("{} is not the program data account of {}", program_data.key, program_id);
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *program_data.owner != bpf_loader_upgradeable::id() {
+        msg!("program data account {} is not owned by the upgradeable loader", program_data.key);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = program_data.data.borrow();
+    let tag = data.get(..4).and_then(|tag| tag.try_into().ok()).map(u32::from_le_bytes);
+    if tag != Some(PROGRAM_DATA_TAG) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    match data.get(12) {
+        Some(0) => Ok(None),
+        Some(1) => data
+            .get(13..45)
+            .and_then(|authority| authority.try_into().ok())
+            .map(|authority| Some(Pubkey::new_from_array(authority)))
+            .ok_or(ProgramError::InvalidAccountData),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_fixture::AccountFixture;
+
+    fn transfer(from: &mut AccountFixture, to: &mut AccountFixture, amount: u64) -> ProgramResult {
+        transfer_lamports(&from.info(), &to.info(), amount)
+    }
+
+    #[test]
+    fn test_transfer_lamports() {
+        let mut from = AccountFixture::new(Pubkey::new_unique()).lamports(1_000);
+        let mut to = AccountFixture::new(Pubkey::new_unique()).lamports(5);
+        transfer(&mut from, &mut to, 400).unwrap();
+        assert_eq!((from.lamports, to.lamports), (600, 405));
+        transfer(&mut from, &mut to, 600).unwrap();
+        assert_eq!((from.lamports, to.lamports), (0, 1_005));
+        transfer(&mut from, &mut to, 0).unwrap();
+        assert_eq!((from.lamports, to.lamports), (0, 1_005));
+    }
+
+    #[test]
+    fn test_transfer_lamports_bounds() {
+        let mut from = AccountFixture::new(Pubkey::new_unique()).lamports(10);
+        let mut to = AccountFixture::new(Pubkey::new_unique()).lamports(u64::MAX - 10);
+        assert_eq!(transfer(&mut from, &mut to, 11), Err(ProgramError::InsufficientFunds));
+        transfer(&mut from, &mut to, 10).unwrap();
+        assert_eq!((from.lamports, to.lamports), (0, u64::MAX));
+
+        let mut from = AccountFixture::new(Pubkey::new_unique()).lamports(u64::MAX);
+        assert_eq!(transfer(&mut from, &mut to, 1), Err(TokenWhitelistError::Overflow.into()));
+        // neither side changes on failure
+        assert_eq!((from.lamports, to.lamports), (u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn test_transfer_lamports_refuses_aliasing() {
+        let key = Pubkey::new_unique();
+        let mut from = AccountFixture::new(key).lamports(100);
+        let mut to = AccountFixture::new(key).lamports(100);
+        assert_eq!(transfer(&mut from, &mut to, 50), Err(ProgramError::InvalidArgument));
+        assert_eq!((from.lamports, to.lamports), (100, 100));
+    }
+
+    fn program_data(program_id: &Pubkey, authority: Option<&Pubkey>) -> AccountFixture {
+        let (key, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        let mut data = PROGRAM_DATA_TAG.to_le_bytes().to_vec();
+        data.extend_from_slice(&42u64.to_le_bytes());
+        match authority {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            }
+            None => data.extend_from_slice(&[0; 33]),
+        }
+        AccountFixture::new(key).owner(bpf_loader_upgradeable::id()).data(data)
+    }
+
+    #[test]
+    fn test_upgrade_authority() {
+        let (program_id, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut fixture = program_data(&program_id, Some(&authority));
+        assert_eq!(upgrade_authority(&fixture.info(), &program_id), Ok(Some(authority)));
+        let mut fixture = program_data(&program_id, None);
+        assert_eq!(upgrade_authority(&fixture.info(), &program_id), Ok(None));
+    }
+
+    #[test]
+    fn test_upgrade_authority_checks_account() {
+        let (program_id, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // the program data of another program
+        let mut fixture = program_data(&Pubkey::new_unique(), Some(&authority));
+        assert_eq!(upgrade_authority(&fixture.info(), &program_id), Err(ProgramError::InvalidArgument));
+
+        let mut fixture = program_data(&program_id, Some(&authority)).owner(Pubkey::new_unique());
+        assert_eq!(upgrade_authority(&fixture.info(), &program_id), Err(ProgramError::IncorrectProgramId));
+
+        // the Program variant, or the authority cut off
+        let mut fixture = program_data(&program_id, Some(&authority));
+        fixture.data[0] = 2;
+        assert_eq!(upgrade_authority(&fixture.info(), &program_id), Err(ProgramError::InvalidAccountData));
+        let mut fixture = program_data(&program_id, Some(&authority));
+        fixture.data.truncate(40);
+        assert_eq!(upgrade_authority(&fixture.info(), &program_id), Err(ProgramError::InvalidAccountData));
+    }
+}
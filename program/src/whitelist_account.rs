@@ -0,0 +1,330 @@
+//! A whitelist account checked and decoded once, for the processor to work on
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::IsInitialized,
+    pubkey::Pubkey,
+};
+use crate::{
+    error::TokenWhitelistError,
+    state::{TokenWhitelist, WhitelistArchive},
+    util::transfer_lamports,
+};
+
+use std::ops::{Deref, DerefMut};
+
+/// The decoded state of a whitelist account together with the account. The constructors run
+/// every check the account needs before a handler may trust the state: owned by this program,
+/// not an archive, large enough for the layout and, but for [load_blank](#method.load_blank),
+/// initialized. The state is reached through Deref.
+///
+/// Changes reach the account only through [save](#method.save) or
+/// [save_removal](#method.save_removal), which consume the wrapper so a handler packs at most
+/// once. Dropping it without saving writes nothing, which is what a
+/// handler returning an error wants.
+pub struct WhitelistAccount<'a, 'info> {
+    info: &'a AccountInfo<'info>,
+    state: TokenWhitelist,
+}
+
+impl<'a, 'info> WhitelistAccount<'a, 'info> {
+    /// Loads an initialized whitelist for reading
+    pub fn load(info: &'a AccountInfo<'info>, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        Self::check_account(info, program_id)?;
+        let state = TokenWhitelist::unpack_from_slice(&info.data.borrow())?;
+        if !state.is_initialized() {
+            msg!("whitelist {} is not initialized", info.key);
+            return Err(TokenWhitelistError::TokenWhitelistNotInit.into());
+        }
+        Ok(WhitelistAccount {info, state})
+    }
+
+    /// Loads an initialized whitelist the instruction may write to
+    pub fn load_mut(info: &'a AccountInfo<'info>, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        Self::check_writable(info)?;
+        Self::load(info, program_id)
+    }
+
+    /// Takes a zeroed account to initialize, its state the default one
+    pub fn load_blank(info: &'a AccountInfo<'info>, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        Self::check_writable(info)?;
+        Self::check_account(info, program_id)?;
+        let data = info.data.borrow();
+        if data.first() == Some(&1) {
+            msg!("token whitelist already initialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        // leftovers of an earlier owner or a half-written buffer would outlive the packed state
+        if data.iter().any(|byte| *byte != 0) {
+            msg!("token whitelist account must be zeroed before initialization");
+            return Err(TokenWhitelistError::AccountNotBlank.into());
+        }
+        Ok(WhitelistAccount {info, state: TokenWhitelist::default()})
+    }
+
+    pub fn info(&self) -> &'a AccountInfo<'info> {
+        self.info
+    }
+
+    pub fn key(&self) -> &'a Pubkey {
+        self.info.key
+    }
+
+    /// The state, leaving the account as it is
+    pub fn into_state(self) -> TokenWhitelist {
+        self.state
+    }
+
+    /// Recomputes the entries hash over the account as it is sized
+    pub fn update_entries_hash(&mut self) {
+        self.state.update_entries_hash(self.info.data_len());
+    }
+
+    /// Counts an add in the stats, see TokenWhitelist::record_add
+    pub fn record_add(&mut self, slot: u64) {
+        self.state.record_add(self.info.data_len(), slot);
+    }
+
+    /// Counts a remove in the stats, see TokenWhitelist::record_remove
+    pub fn record_remove(&mut self, slot: u64) {
+        self.state.record_remove(self.info.data_len(), slot);
+    }
+
+    /// Records the slot and signer creating the whitelist, see TokenWhitelist::record_creation
+    pub fn record_creation(&mut self, slot: u64, creator: Pubkey) {
+        self.state.record_creation(self.info.data_len(), slot, creator);
+    }
+
+    /// Packs the state into the account and reads the header back, failing the instruction,
+    /// and so discarding the write, when it disagrees with the state packed
+    pub fn save(self) -> ProgramResult {
+        self.write(|state, data| state.pack_into_slice(data))
+    }
+
+    /// Saves the state after drop_key took `key` out of the map, moving the entries after it
+    /// down in place rather than encoding the map again, see
+    /// TokenWhitelist::pack_removal_into_slice. The entries hash is recomputed there.
+    pub fn save_removal(self, key: &str) -> ProgramResult {
+        self.write(|state, data| state.pack_removal_into_slice(data, key))
+    }
+
+    fn write(mut self, pack: impl FnOnce(&mut TokenWhitelist, &mut [u8]) -> ProgramResult) -> ProgramResult {
+        let mut data = self.info.data.borrow_mut();
+        self.state.record_mutation(data.len());
+        pack(&mut self.state, &mut data)?;
+        if let Err(error) = self.state.check_packed(&data) {
+            msg!("token whitelist state read back after packing is inconsistent");
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// Closes the account into `destination` and returns the lamports moved. The data is zeroed
+    /// before any lamport moves: the account stays readable until the transaction ends, and
+    /// an instruction after this one, a CPI consumer included, has to find it uninitialized
+    /// rather than a whitelist without lamports whose entries still decode.
+    pub fn close(self, destination: &AccountInfo<'info>) -> Result<u64, ProgramError> {
+        self.info.data.borrow_mut().fill(0);
+        let lamports = self.info.lamports();
+        transfer_lamports(self.info, destination, lamports)?;
+        Ok(lamports)
+    }
+
+    /// The owner key is read from the data, so the data has to be a whitelist this program wrote
+    fn check_account(info: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+        if info.owner != program_id {
+            msg!("whitelist {} is not owned by the token whitelist program", info.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if WhitelistArchive::is_archive(&info.data.borrow()) {
+            msg!("whitelist {} is archived", info.key);
+            return Err(TokenWhitelistError::WhitelistArchived.into());
+        }
+        if info.data_len() < TokenWhitelist::MIN_SPACE {
+            msg!("whitelist {} holds {} bytes, the layout needs {}", info.key, info.data_len(), TokenWhitelist::MIN_SPACE);
+            return Err(TokenWhitelistError::AccountTooSmall.into());
+        }
+        Ok(())
+    }
+
+    fn check_writable(info: &AccountInfo) -> ProgramResult {
+        if !info.is_writable {
+            msg!("whitelist {} must be writable", info.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+impl Deref for WhitelistAccount<'_, '_> {
+    type Target = TokenWhitelist;
+
+    fn deref(&self) -> &TokenWhitelist {
+        &self.state
+    }
+}
+
+impl DerefMut for WhitelistAccount<'_, '_> {
+    fn deref_mut(&mut self) -> &mut TokenWhitelist {
+        &mut self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        account_fixture::{AccountFixture, FIXTURE_PROGRAM_ID},
+        state::ARCHIVE_SPACE,
+    };
+
+    const WHITELIST_ACCOUNT_SPACE: usize = 10240;
+
+    fn whitelist_fixture() -> AccountFixture {
+        AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(WHITELIST_ACCOUNT_SPACE)
+            .whitelist(&TokenWhitelist {
+                is_initialized: true,
+                init_pubkey: Pubkey::new_unique(),
+                max_whitelist_size: 10,
+                ..TokenWhitelist::default()
+            })
+    }
+
+    fn load(fixture: &mut AccountFixture) -> Result<TokenWhitelist, ProgramError> {
+        let info = fixture.info();
+        WhitelistAccount::load(&info, &FIXTURE_PROGRAM_ID).map(WhitelistAccount::into_state)
+    }
+
+    fn load_mut(fixture: &mut AccountFixture) -> Result<TokenWhitelist, ProgramError> {
+        let info = fixture.info();
+        WhitelistAccount::load_mut(&info, &FIXTURE_PROGRAM_ID).map(WhitelistAccount::into_state)
+    }
+
+    fn load_blank(fixture: &mut AccountFixture) -> Result<TokenWhitelist, ProgramError> {
+        let info = fixture.info();
+        WhitelistAccount::load_blank(&info, &FIXTURE_PROGRAM_ID).map(WhitelistAccount::into_state)
+    }
+
+    #[test]
+    fn test_load() {
+        let mut fixture = whitelist_fixture();
+        let state = fixture.state();
+        assert_eq!(load(&mut fixture), Ok(state.clone()));
+        assert_eq!(load_mut(&mut fixture), Ok(state));
+    }
+
+    #[test]
+    fn test_load_wrong_owner() {
+        let mut fixture = whitelist_fixture().owner(Pubkey::new_unique());
+        assert_eq!(load(&mut fixture), Err(ProgramError::IncorrectProgramId));
+        assert_eq!(load_mut(&mut fixture), Err(ProgramError::IncorrectProgramId));
+        let mut blank = fixture.data_len(WHITELIST_ACCOUNT_SPACE);
+        assert_eq!(load_blank(&mut blank), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_load_archived() {
+        let mut data = vec![0; ARCHIVE_SPACE];
+        WhitelistArchive::default().pack_into_slice(&mut data).unwrap();
+        let mut fixture = whitelist_fixture().data(data);
+        assert_eq!(load(&mut fixture), Err(TokenWhitelistError::WhitelistArchived.into()));
+        assert_eq!(load_blank(&mut fixture), Err(TokenWhitelistError::WhitelistArchived.into()));
+    }
+
+    #[test]
+    fn test_load_too_small() {
+        let mut fixture = whitelist_fixture().data_len(TokenWhitelist::MIN_SPACE - 1);
+        assert_eq!(load(&mut fixture), Err(TokenWhitelistError::AccountTooSmall.into()));
+        assert_eq!(load_blank(&mut fixture), Err(TokenWhitelistError::AccountTooSmall.into()));
+    }
+
+    #[test]
+    fn test_load_undecodable() {
+        let mut fixture = whitelist_fixture();
+        // a map length past the map region
+        fixture.data[41..45].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(load(&mut fixture), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_load_uninitialized() {
+        let mut fixture = whitelist_fixture().data_len(WHITELIST_ACCOUNT_SPACE);
+        assert_eq!(load(&mut fixture), Err(TokenWhitelistError::TokenWhitelistNotInit.into()));
+        assert_eq!(load_blank(&mut fixture), Ok(TokenWhitelist::default()));
+    }
+
+    #[test]
+    fn test_load_mut_read_only() {
+        let mut fixture = whitelist_fixture();
+        fixture.is_writable = false;
+        assert_eq!(load_mut(&mut fixture), Err(ProgramError::InvalidArgument));
+        assert_eq!(load_blank(&mut fixture), Err(ProgramError::InvalidArgument));
+        assert!(load(&mut fixture).is_ok());
+    }
+
+    #[test]
+    fn test_load_blank_initialized() {
+        let mut fixture = whitelist_fixture();
+        assert_eq!(load_blank(&mut fixture), Err(ProgramError::AccountAlreadyInitialized));
+
+        let mut data = vec![0; WHITELIST_ACCOUNT_SPACE];
+        data[WHITELIST_ACCOUNT_SPACE - 1] = 1;
+        let mut fixture = fixture.data(data);
+        assert_eq!(load_blank(&mut fixture), Err(TokenWhitelistError::AccountNotBlank.into()));
+    }
+
+    #[test]
+    fn test_save() {
+        let mut fixture = whitelist_fixture();
+        let wallet = Pubkey::new_unique().to_string();
+        {
+            let info = fixture.info();
+            let mut account = WhitelistAccount::load_mut(&info, &FIXTURE_PROGRAM_ID).unwrap();
+            account.add_keypair(&wallet, &500);
+            account.update_entries_hash();
+            account.save().unwrap();
+        }
+        assert_eq!(fixture.state().whitelist_map.get(&wallet), Some(&500));
+
+        // dropped unsaved, nothing is written
+        let before = fixture.data.clone();
+        {
+            let info = fixture.info();
+            let mut account = WhitelistAccount::load_mut(&info, &FIXTURE_PROGRAM_ID).unwrap();
+            account.drop_key(&wallet);
+        }
+        assert_eq!(fixture.data, before);
+    }
+
+    #[test]
+    fn test_close_zeroes_data_first() {
+        let mut fixture = whitelist_fixture().lamports(1_000);
+        let mut destination = AccountFixture::new(Pubkey::new_unique()).writable();
+        {
+            let info = fixture.info();
+            let destination = destination.info();
+            let account = WhitelistAccount::load_mut(&info, &FIXTURE_PROGRAM_ID).unwrap();
+            assert_eq!(account.close(&destination), Ok(1_000));
+            assert_eq!(destination.lamports(), 1_000);
+        }
+        assert_eq!(fixture.lamports, 0);
+        assert!(fixture.data.iter().all(|byte| *byte == 0));
+        assert_eq!(load(&mut fixture), Err(TokenWhitelistError::TokenWhitelistNotInit.into()));
+
+        // closing into itself is refused, the runtime discarding the zeroed data with the
+        // failed instruction
+        let mut fixture = whitelist_fixture().lamports(1_000);
+        {
+            let info = fixture.info();
+            let account = WhitelistAccount::load_mut(&info, &FIXTURE_PROGRAM_ID).unwrap();
+            assert_eq!(account.close(&info), Err(ProgramError::InvalidArgument));
+        }
+        assert_eq!(fixture.lamports, 1_000);
+    }
+}
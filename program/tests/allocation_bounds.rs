@@ -0,0 +1,117 @@
+mod common;
+
+use solana_program::{instruction::{AccountMeta, InstructionError}, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::TokenWhitelistInstruction,
+};
+
+use common::{custom_error, TestEnv};
+
+const MIN_ALLOCATION: u64 = 100;
+const MAX_ALLOCATION: u64 = 5_000;
+
+async fn setup(min_allocation: u64, max_allocation: u64) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.send_as_owner(TokenWhitelistInstruction::SetAllocationBounds {min_allocation, max_allocation})
+        .await
+        .unwrap();
+    env
+}
+
+async fn allocation(env: &mut TestEnv, wallet: &Pubkey) -> Option<u64> {
+    env.whitelist_state().await.whitelist_map.get(&wallet.to_string()).copied()
+}
+
+#[tokio::test]
+async fn test_add_at_bounds() {
+    let mut env = setup(MIN_ALLOCATION, MAX_ALLOCATION).await;
+
+    let at_min = Pubkey::new_unique();
+    env.add_to_whitelist(&at_min, MIN_ALLOCATION).await.unwrap();
+    assert_eq!(allocation(&mut env, &at_min).await, Some(MIN_ALLOCATION));
+
+    let at_max = Pubkey::new_unique();
+    env.add_to_whitelist(&at_max, MAX_ALLOCATION).await.unwrap();
+    assert_eq!(allocation(&mut env, &at_max).await, Some(MAX_ALLOCATION));
+
+    let wallet = Pubkey::new_unique();
+    assert_eq!(
+        env.add_to_whitelist(&wallet, MIN_ALLOCATION - 1).await,
+        Err(custom_error(TokenWhitelistError::AllocationBelowMinimum))
+    );
+    assert_eq!(
+        env.add_to_whitelist(&wallet, MAX_ALLOCATION + 1).await,
+        Err(custom_error(TokenWhitelistError::AllocationAboveMaximum))
+    );
+    assert_eq!(allocation(&mut env, &wallet).await, None);
+}
+
+#[tokio::test]
+async fn test_update_checked_against_bounds() {
+    let mut env = setup(MIN_ALLOCATION, MAX_ALLOCATION).await;
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 1_000).await.unwrap();
+
+    assert_eq!(
+        env.add_to_whitelist(&wallet, MAX_ALLOCATION + 1).await,
+        Err(custom_error(TokenWhitelistError::AllocationAboveMaximum))
+    );
+    assert_eq!(
+        env.add_to_whitelist(&wallet, MIN_ALLOCATION - 1).await,
+        Err(custom_error(TokenWhitelistError::AllocationBelowMinimum))
+    );
+    env.add_to_whitelist(&wallet, MAX_ALLOCATION).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, Some(MAX_ALLOCATION));
+}
+
+#[tokio::test]
+async fn test_set_allocation_to_zero_exempt() {
+    let mut env = setup(MIN_ALLOCATION, MAX_ALLOCATION).await;
+    let wallet = Keypair::new();
+    env.add_to_whitelist(&wallet.pubkey(), MIN_ALLOCATION).await.unwrap();
+
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::SetAllocationToZero {memo: None},
+        vec![
+            AccountMeta::new_readonly(wallet.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(wallet.pubkey(), false),
+        ],
+    );
+    env.send(&[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet.pubkey()).await, Some(0));
+}
+
+#[tokio::test]
+async fn test_unbounded_by_default() {
+    let mut env = setup(0, 0).await;
+    env.add_to_whitelist(&Pubkey::new_unique(), 0).await.unwrap();
+    env.add_to_whitelist(&Pubkey::new_unique(), u64::MAX).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_allocation_bounds_errors() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert_eq!(
+        env.send_as_owner(TokenWhitelistInstruction::SetAllocationBounds {
+            min_allocation: MAX_ALLOCATION + 1,
+            max_allocation: MAX_ALLOCATION,
+        })
+        .await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidArgument))
+    );
+    // only a minimum
+    env.send_as_owner(TokenWhitelistInstruction::SetAllocationBounds {
+        min_allocation: MIN_ALLOCATION,
+        max_allocation: 0,
+    })
+    .await
+    .unwrap();
+    env.add_to_whitelist(&Pubkey::new_unique(), u64::MAX).await.unwrap();
+}
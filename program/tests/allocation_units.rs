@@ -0,0 +1,125 @@
+mod common;
+
+use solana_program::{program_pack::Pack, pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::{Account, ReadableAccount},
+    signature::Signer,
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{init_token_whitelist_with_units, InitWithUnits},
+    state::TokenWhitelist,
+};
+use spl_token::state::Mint;
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+struct Accounts {
+    whitelist: Pubkey,
+    mint: Pubkey,
+    foreign_mint: Pubkey,
+}
+
+async fn setup() -> (TestEnv, Accounts) {
+    let accounts = Accounts {
+        whitelist: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        foreign_mint: Pubkey::new_unique(),
+    };
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint {
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint_data);
+
+    let mut env = TestEnv::start(|program_test| {
+        let rent = Rent::default();
+        for (mint, owner) in [(accounts.mint, spl_token::id()), (accounts.foreign_mint, Pubkey::new_unique())] {
+            program_test.add_account(mint, Account {
+                lamports: rent.minimum_balance(Mint::LEN),
+                data: mint_data.clone(),
+                owner,
+                ..Account::default()
+            });
+        }
+    })
+    .await;
+    // the program id is only known once started
+    let whitelist = Account {
+        lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+        data: vec![0; WHITELIST_ACCOUNT_SPACE],
+        owner: env.program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&accounts.whitelist, &whitelist.into());
+    (env, accounts)
+}
+
+async fn init(
+    env: &mut TestEnv,
+    whitelist: &Pubkey,
+    allocation_decimals: Option<u8>,
+    mint: Option<&Pubkey>,
+) -> Result<(), TransactionError> {
+    let init = InitWithUnits {allocation_decimals, allocation_mint: mint.copied(), ..InitWithUnits::new(50)};
+    let instruction = init_token_whitelist_with_units(&env.program_id, &env.owner.pubkey(), whitelist, &init).unwrap();
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+async fn state(env: &mut TestEnv, whitelist: &Pubkey) -> TokenWhitelist {
+    let account = env.context.banks_client.get_account(*whitelist).await.unwrap().unwrap();
+    TokenWhitelist::unpack_from_slice(account.data()).unwrap()
+}
+
+#[tokio::test]
+async fn test_decimals_copied_from_mint() {
+    let (mut env, accounts) = setup().await;
+    init(&mut env, &accounts.whitelist, None, Some(&accounts.mint)).await.unwrap();
+
+    let state = state(&mut env, &accounts.whitelist).await;
+    assert!(state.is_initialized);
+    assert_eq!(state.extension.allocation_decimals, 6);
+    assert_eq!(state.extension.allocation_mint, Some(accounts.mint));
+    assert_eq!(state.summary().allocation_decimals, 6);
+}
+
+#[tokio::test]
+async fn test_matching_explicit_decimals() {
+    let (mut env, accounts) = setup().await;
+    init(&mut env, &accounts.whitelist, Some(6), Some(&accounts.mint)).await.unwrap();
+    assert_eq!(state(&mut env, &accounts.whitelist).await.extension.allocation_decimals, 6);
+}
+
+#[tokio::test]
+async fn test_mismatched_decimals_rejected() {
+    let (mut env, accounts) = setup().await;
+    assert_eq!(
+        init(&mut env, &accounts.whitelist, Some(9), Some(&accounts.mint)).await,
+        Err(custom_error(TokenWhitelistError::AllocationDecimalsMismatch))
+    );
+    assert!(!state(&mut env, &accounts.whitelist).await.is_initialized);
+}
+
+#[tokio::test]
+async fn test_mint_of_other_program_rejected() {
+    let (mut env, accounts) = setup().await;
+    assert_eq!(
+        init(&mut env, &accounts.whitelist, None, Some(&accounts.foreign_mint)).await,
+        Err(custom_error(TokenWhitelistError::InvalidMint))
+    );
+}
+
+#[tokio::test]
+async fn test_explicit_decimals_without_mint() {
+    let (mut env, accounts) = setup().await;
+    init(&mut env, &accounts.whitelist, Some(9), None).await.unwrap();
+
+    let state = state(&mut env, &accounts.whitelist).await;
+    assert_eq!(state.extension.allocation_decimals, 9);
+    assert_eq!(state.extension.allocation_mint, None);
+}
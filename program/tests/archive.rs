@@ -0,0 +1,79 @@
+mod common;
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::ReadableAccount,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{archive_whitelist, TokenWhitelistInstruction},
+    state::{hash_entries_map, WhitelistArchive, ARCHIVE_SPACE},
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+async fn archive(env: &mut TestEnv, destination: &Pubkey) -> Result<(), TransactionError> {
+    let instruction = archive_whitelist(&env.program_id, &env.owner.pubkey(), &env.whitelist, destination);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+#[tokio::test]
+async fn test_archive_whitelist() {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    env.add_to_whitelist(&Pubkey::new_unique(), 200).await.unwrap();
+    let entries = env.whitelist_state().await.whitelist_map;
+    let destination = Pubkey::new_unique();
+
+    archive(&mut env, &destination).await.unwrap();
+
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    assert_eq!(account.data().len(), ARCHIVE_SPACE);
+    assert_eq!(account.lamports, rent.minimum_balance(ARCHIVE_SPACE));
+    assert_eq!(
+        env.lamports(&destination).await,
+        rent.minimum_balance(WHITELIST_ACCOUNT_SPACE) - rent.minimum_balance(ARCHIVE_SPACE)
+    );
+
+    let record = WhitelistArchive::unpack_from_slice(account.data()).unwrap();
+    assert_eq!(record.init_pubkey, env.owner.pubkey());
+    assert_eq!(record.entries_hash, hash_entries_map(&entries));
+    assert!(record.archived_slot > 0);
+}
+
+#[tokio::test]
+async fn test_archived_whitelist_rejects_instructions() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let destination = Pubkey::new_unique();
+    archive(&mut env, &destination).await.unwrap();
+
+    assert_eq!(
+        env.add_to_whitelist(&Pubkey::new_unique(), 100).await,
+        Err(custom_error(TokenWhitelistError::WhitelistArchived))
+    );
+    assert_eq!(
+        env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 1}).await,
+        Err(custom_error(TokenWhitelistError::WhitelistArchived))
+    );
+    assert_eq!(
+        archive(&mut env, &destination).await,
+        Err(custom_error(TokenWhitelistError::WhitelistArchived))
+    );
+}
+
+#[tokio::test]
+async fn test_archive_needs_owner() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let impostor = Keypair::new();
+    let instruction = archive_whitelist(&env.program_id, &impostor.pubkey(), &env.whitelist, &impostor.pubkey());
+    assert_eq!(
+        env.send(&[instruction], &[&impostor]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert_eq!(env.whitelist_state().await.max_whitelist_size, 50);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use solana_program::{pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Signer};
+use solr_token_whitelist::{error::TokenWhitelistError, instruction::assert_owner};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+#[tokio::test]
+async fn test_assert_owner_matches() {
+    let mut env = TestEnv::start(|_| {}).await;
+    // no signature from the owner, only its key
+    let instruction = assert_owner(&env.program_id, &env.whitelist, &env.owner.pubkey());
+    env.send(&[instruction], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_assert_owner_mismatch() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let instruction = assert_owner(&env.program_id, &env.whitelist, &Pubkey::new_unique());
+    assert_eq!(
+        env.send(&[instruction], &[]).await,
+        Err(custom_error(TokenWhitelistError::TokenWhitelistNotOwner))
+    );
+}
+
+#[tokio::test]
+async fn test_assert_owner_uninitialized() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let uninitialized = Pubkey::new_unique();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+        data: vec![0; WHITELIST_ACCOUNT_SPACE],
+        owner: env.program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&uninitialized, &account.into());
+
+    // the default key an uninitialized whitelist holds as its owner does not pass either
+    let instruction = assert_owner(&env.program_id, &uninitialized, &Pubkey::default());
+    assert_eq!(
+        env.send(&[instruction], &[]).await,
+        Err(custom_error(TokenWhitelistError::TokenWhitelistNotInit))
+    );
+}
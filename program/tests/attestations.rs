@@ -0,0 +1,200 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{add_to_whitelist_with_attestation, remove_from_whitelist, set_attestation, TokenWhitelistInstruction},
+    state::NO_ATTESTATION,
+};
+
+use common::{custom_error, TestEnv};
+
+async fn attest(
+    env: &mut TestEnv,
+    wallet: &Pubkey,
+    allocation_amount: u64,
+    attestation: [u8; 32],
+) -> Result<(), TransactionError> {
+    let owner = env.owner.insecure_clone();
+    let instruction = add_to_whitelist_with_attestation(
+        &env.program_id,
+        &owner.pubkey(),
+        &env.whitelist,
+        wallet,
+        allocation_amount,
+        attestation,
+    );
+    env.send(&[instruction], &[&owner]).await
+}
+
+async fn require_attestation(env: &mut TestEnv, require_attestation: bool) {
+    env.send_as_owner(TokenWhitelistInstruction::SetAttestationRequired {require_attestation}).await.unwrap();
+    assert_eq!(env.whitelist_state().await.extension.require_attestation, require_attestation);
+}
+
+#[tokio::test]
+async fn test_attestation_optional_by_default() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (plain, attested) = (Pubkey::new_unique(), Pubkey::new_unique());
+    env.add_to_whitelist(&plain, 100).await.unwrap();
+    attest(&mut env, &attested, 200, [5; 32]).await.unwrap();
+
+    let state = env.whitelist_state().await;
+    assert_eq!(state.attestation(&plain.to_string()), NO_ATTESTATION);
+    assert_eq!(state.attestation(&attested.to_string()), [5; 32]);
+    assert_eq!(state.whitelist_map.get(&attested.to_string()), Some(&200));
+
+    // a later add without one keeps the attestation, removing the entry drops it
+    env.add_to_whitelist(&attested, 300).await.unwrap();
+    assert_eq!(env.whitelist_state().await.attestation(&attested.to_string()), [5; 32]);
+    let owner = env.owner.insecure_clone();
+    let remove = remove_from_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &attested);
+    env.send(&[remove], &[&owner]).await.unwrap();
+    assert!(env.whitelist_state().await.extension.entry_attestations.is_empty());
+}
+
+#[tokio::test]
+async fn test_attestation_required() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let listed = Pubkey::new_unique();
+    env.add_to_whitelist(&listed, 100).await.unwrap();
+    require_attestation(&mut env, true).await;
+
+    let wallet = Pubkey::new_unique();
+    assert_eq!(env.add_to_whitelist(&wallet, 100).await, Err(custom_error(TokenWhitelistError::AttestationRequired)));
+    assert_eq!(
+        attest(&mut env, &wallet, 100, NO_ATTESTATION).await,
+        Err(custom_error(TokenWhitelistError::AttestationRequired))
+    );
+    attest(&mut env, &wallet, 100, [1; 32]).await.unwrap();
+    // the attestation stays with the entry, which then takes plain adds
+    env.add_to_whitelist(&wallet, 150).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&wallet.to_string()), Some(&150));
+
+    // an entry added before keeps its allocation but needs an attestation for the next add
+    assert_eq!(env.add_to_whitelist(&listed, 200).await, Err(custom_error(TokenWhitelistError::AttestationRequired)));
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&listed.to_string()), Some(&100));
+
+    require_attestation(&mut env, false).await;
+    env.add_to_whitelist(&listed, 200).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_attestation() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let owner = env.owner.insecure_clone();
+    let wallet = Pubkey::new_unique();
+
+    let update = set_attestation(&env.program_id, &owner.pubkey(), &env.whitelist, &wallet, [2; 32]);
+    assert_eq!(
+        env.send(std::slice::from_ref(&update), &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+
+    env.add_to_whitelist(&wallet, 100).await.unwrap();
+    env.send(&[update], &[&owner]).await.unwrap();
+    let state = env.whitelist_state().await;
+    assert_eq!(state.attestation(&wallet.to_string()), [2; 32]);
+    assert_eq!(state.whitelist_map.get(&wallet.to_string()), Some(&100));
+
+    // clearing is refused while attestations are required
+    require_attestation(&mut env, true).await;
+    let clear = set_attestation(&env.program_id, &owner.pubkey(), &env.whitelist, &wallet, NO_ATTESTATION);
+    assert_eq!(
+        env.send(std::slice::from_ref(&clear), &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::AttestationRequired))
+    );
+    require_attestation(&mut env, false).await;
+    env.send(&[clear], &[&owner]).await.unwrap();
+    assert!(env.whitelist_state().await.extension.entry_attestations.is_empty());
+}
+
+#[tokio::test]
+async fn test_delegate_sets_attestation() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (owner, delegate, stranger) = (env.owner.insecure_clone(), Keypair::new(), Keypair::new());
+    let approve = TokenWhitelistInstruction::ApproveDelegate {max_adds: 1, max_allocation_per_add: 100};
+    let mut approve = env.owner_instruction(approve);
+    approve.accounts.push(AccountMeta::new_readonly(delegate.pubkey(), false));
+    env.send(&[approve], &[&owner]).await.unwrap();
+    require_attestation(&mut env, true).await;
+
+    let wallet = Pubkey::new_unique();
+    let add =
+        add_to_whitelist_with_attestation(&env.program_id, &delegate.pubkey(), &env.whitelist, &wallet, 100, [4; 32]);
+    env.send(&[add], &[&delegate]).await.unwrap();
+
+    // updating the attestation uses none of the adds the delegate has left
+    let update = set_attestation(&env.program_id, &delegate.pubkey(), &env.whitelist, &wallet, [6; 32]);
+    env.send(&[update], &[&delegate]).await.unwrap();
+    let state = env.whitelist_state().await;
+    assert_eq!(state.attestation(&wallet.to_string()), [6; 32]);
+    assert_eq!(state.extension.delegate.unwrap().remaining_adds, 0);
+
+    let update = set_attestation(&env.program_id, &stranger.pubkey(), &env.whitelist, &wallet, [8; 32]);
+    assert_eq!(env.send(&[update], &[&stranger]).await, Err(custom_error(TokenWhitelistError::TokenWhitelistNotOwner)));
+}
+
+#[tokio::test]
+async fn test_register_self_refused_when_required() {
+    let registrant = Keypair::new();
+    let mut env = TestEnv::start(|program_test| {
+        program_test.add_account(registrant.pubkey(), Account::new(1_000_000_000, 0, &system_program::id()));
+    })
+    .await;
+    env.send_as_owner(TokenWhitelistInstruction::ConfigureRegistration {
+        registration_end_slot: 100,
+        registration_allocation: 10,
+        registration_deposit_lamports: 0,
+    })
+    .await
+    .unwrap();
+    require_attestation(&mut env, true).await;
+
+    let register = env.instruction(
+        TokenWhitelistInstruction::RegisterSelf {},
+        vec![
+            AccountMeta::new(registrant.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert_eq!(
+        env.send(&[register], &[&registrant]).await,
+        Err(custom_error(TokenWhitelistError::AttestationRequired))
+    );
+}
+
+#[cfg(feature = "pages")]
+#[tokio::test]
+async fn test_list_attested_entries() {
+    use borsh::BorshDeserialize;
+    use solr_token_whitelist::{
+        instruction::list_attested_entries,
+        state::{AttestedEntriesPage, MAX_LIST_ATTESTED_ENTRIES},
+    };
+
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallets: Vec<Pubkey> = (0..20).map(|_| Pubkey::new_unique()).collect();
+    for (i, wallet) in wallets.iter().enumerate() {
+        attest(&mut env, wallet, 100, [i as u8 % 2; 32]).await.unwrap();
+    }
+
+    let instruction = list_attested_entries(&env.program_id, &env.whitelist, 0, u8::MAX);
+    let return_data = env.simulate(&[instruction], &[]).await.returned().unwrap();
+    let page = AttestedEntriesPage::try_from_slice(&return_data.data).unwrap();
+
+    assert_eq!(page.total, 20);
+    assert_eq!(page.entries.len(), MAX_LIST_ATTESTED_ENTRIES);
+    for (wallet, allocation, attestation) in page.entries {
+        let i = wallets.iter().position(|listed| *listed == wallet).unwrap();
+        assert_eq!(allocation, 100);
+        assert_eq!(attestation, [i as u8 % 2; 32]);
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+use solr_token_whitelist::{
+    instruction::{add_to_whitelist_for_campaign, remove_from_whitelist},
+    state::NO_CAMPAIGN,
+};
+
+use common::TestEnv;
+use std::collections::BTreeMap;
+
+const SPRING: [u8; 8] = *b"spring24";
+const PARTNER: [u8; 8] = *b"partner1";
+
+async fn add_for_campaign(
+    env: &mut TestEnv,
+    wallet: &Pubkey,
+    allocation_amount: u64,
+    campaign_id: [u8; 8],
+) -> Result<(), TransactionError> {
+    let instruction = add_to_whitelist_for_campaign(
+        &env.program_id,
+        &env.owner.pubkey(),
+        &env.whitelist,
+        wallet,
+        allocation_amount,
+        campaign_id,
+    );
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+/// Three SPRING wallets, one PARTNER wallet and one added without a campaign
+async fn mixed_campaigns(env: &mut TestEnv) -> Vec<Pubkey> {
+    let wallets: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+    for (wallet, campaign_id) in wallets.iter().zip([SPRING, SPRING, PARTNER, SPRING, NO_CAMPAIGN]) {
+        add_for_campaign(env, wallet, 1_000, campaign_id).await.unwrap();
+    }
+    wallets
+}
+
+#[tokio::test]
+async fn test_counts_per_campaign() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallets = mixed_campaigns(&mut env).await;
+
+    let state = env.whitelist_state().await;
+    assert_eq!(state.campaign(&wallets[2].to_string()), PARTNER);
+    assert_eq!(state.campaign(&wallets[4].to_string()), NO_CAMPAIGN);
+    assert_eq!(
+        state.summary().campaigns,
+        BTreeMap::from([(NO_CAMPAIGN, 1), (PARTNER, 1), (SPRING, 3)])
+    );
+}
+
+#[tokio::test]
+async fn test_readd_keeps_campaign() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallets = mixed_campaigns(&mut env).await;
+
+    // a later add without a campaign only changes the allocation
+    env.add_to_whitelist(&wallets[0], 2_000).await.unwrap();
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.get(&wallets[0].to_string()), Some(&2_000));
+    assert_eq!(state.campaign(&wallets[0].to_string()), SPRING);
+
+    // one for another campaign moves the entry
+    add_for_campaign(&mut env, &wallets[0], 2_000, PARTNER).await.unwrap();
+    assert_eq!(env.whitelist_state().await.campaign_counts()[&PARTNER], 2);
+}
+
+#[tokio::test]
+async fn test_remove_drops_campaign() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallets = mixed_campaigns(&mut env).await;
+
+    let instruction = remove_from_whitelist(&env.program_id, &env.owner.pubkey(), &env.whitelist, &wallets[2]);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let state = env.whitelist_state().await;
+    assert!(!state.extension.entry_campaigns.contains_key(&wallets[2].to_string()));
+    assert_eq!(state.campaign_counts(), BTreeMap::from([(NO_CAMPAIGN, 1), (SPRING, 3)]));
+}
+
+#[cfg(feature = "pages")]
+#[tokio::test]
+async fn test_list_entries_by_campaign() {
+    use borsh::BorshDeserialize;
+    use solr_token_whitelist::{instruction::list_entries_by_campaign, state::EntriesPage};
+
+    let mut env = TestEnv::start(|_| {}).await;
+    mixed_campaigns(&mut env).await;
+
+    let instruction = list_entries_by_campaign(&env.program_id, &env.whitelist, SPRING, 1, 10);
+    let return_data = env.simulate(&[instruction], &[]).await.returned().unwrap();
+    let page = EntriesPage::try_from_slice(&return_data.data).unwrap();
+
+    let state = env.whitelist_state().await;
+    assert_eq!(page, state.campaign_entries_page(SPRING, 1, 10).unwrap());
+    assert_eq!(page.total, 3);
+    assert_eq!(page.entries.len(), 2);
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program::invoke,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::{processor, tokio};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{get_allocation, TokenWhitelistInstruction},
+    state::TokenWhitelist,
+};
+
+use common::{instruction_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// Builds a CloseWhitelistAccount instruction against the whitelist of `env`
+fn close_instruction(env: &TestEnv, destination: &Pubkey) -> Instruction {
+    env.instruction(
+        TokenWhitelistInstruction::CloseWhitelistAccount {},
+        vec![
+            AccountMeta::new_readonly(env.owner.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
+
+/// A sale program admitting a wallet only when GetAllocation, called through CPI, finds it on
+/// the whitelist. Accounts: whitelist program, whitelist, wallet.
+fn process_consumer(_program_id: &Pubkey, accounts: &[AccountInfo], _input: &[u8]) -> ProgramResult {
+    let (whitelist_program, whitelist, wallet) = (&accounts[0], &accounts[1], &accounts[2]);
+    let instruction = get_allocation(whitelist_program.key, whitelist.key, wallet.key, 0);
+    invoke(&instruction, &[whitelist.clone(), wallet.clone(), whitelist_program.clone()])
+}
+
+fn consumer_instruction(env: &TestEnv, consumer: &Pubkey, wallet: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *consumer,
+        &[],
+        vec![
+            AccountMeta::new_readonly(env.program_id, false),
+            AccountMeta::new_readonly(env.whitelist, false),
+            AccountMeta::new_readonly(*wallet, false),
+        ],
+    )
+}
+
+/// Closes `whitelist` signed by `authority`
+async fn close(
+    env: &mut TestEnv,
+    authority: &Keypair,
+    whitelist: &Pubkey,
+    destination: &Pubkey,
+) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::CloseWhitelistAccount {},
+        vec![
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new(*whitelist, false),
+            AccountMeta::new(*destination, false),
+        ],
+    );
+    env.send(&[instruction], &[authority]).await
+}
+
+/// An account of `owner` whose data decodes as a whitelist of `init_pubkey`
+fn forged_account(env: &mut TestEnv, owner: Pubkey, init_pubkey: Pubkey) -> (Pubkey, u64) {
+    let mut data = vec![0; WHITELIST_ACCOUNT_SPACE];
+    TokenWhitelist {
+        is_initialized: true,
+        init_pubkey,
+        max_whitelist_size: 1,
+        ..TokenWhitelist::default()
+    }
+    .pack_into_slice(&mut data)
+    .unwrap();
+    let pubkey = Pubkey::new_unique();
+    let lamports = Rent::default().minimum_balance(data.len());
+    env.context.set_account(&pubkey, &Account {lamports, data, owner, ..Account::default()}.into());
+    (pubkey, lamports)
+}
+
+#[tokio::test]
+async fn test_close_whitelist() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (owner, whitelist, destination) = (env.owner.insecure_clone(), env.whitelist, Pubkey::new_unique());
+    let lamports = env.lamports(&whitelist).await;
+
+    close(&mut env, &owner, &whitelist, &destination).await.unwrap();
+    assert_eq!(env.lamports(&destination).await, lamports);
+    assert_eq!(env.lamports(&whitelist).await, 0);
+}
+
+// the WhitelistClosed event goes to sol_log_data, which native program tests do not log, its
+// encoding is tested in event.rs
+#[tokio::test]
+async fn test_close_reports_discarded_entries() {
+    use borsh::BorshDeserialize;
+    use solr_token_whitelist::state::ClosedWhitelist;
+
+    let mut env = TestEnv::start(|_| {}).await;
+    for allocation_amount in [100, 200, 300] {
+        env.add_to_whitelist(&Pubkey::new_unique(), allocation_amount).await.unwrap();
+    }
+    let (owner, whitelist, destination) = (env.owner.insecure_clone(), env.whitelist, Pubkey::new_unique());
+
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::CloseWhitelistAccount {},
+        vec![
+            AccountMeta::new_readonly(owner.pubkey(), true),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new(destination, false),
+        ],
+    );
+    let return_data = env.simulate(&[instruction], &[&owner]).await.returned().unwrap();
+
+    let closed = ClosedWhitelist {entries_discarded: 3, allocation_discarded: 600};
+    assert_eq!(return_data.program_id, env.program_id);
+    assert_eq!(ClosedWhitelist::try_from_slice(&return_data.data).unwrap(), closed);
+}
+
+#[tokio::test]
+async fn test_close_foreign_account_with_forged_owner() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let attacker = Keypair::new();
+    // data of another program that happens to carry the attacker key where a whitelist keeps its owner
+    let (victim, lamports) = forged_account(&mut env, spl_token::id(), attacker.pubkey());
+
+    assert_eq!(
+        close(&mut env, &attacker, &victim, &attacker.pubkey()).await,
+        Err(TransactionError::InstructionError(0, InstructionError::IncorrectProgramId))
+    );
+    assert_eq!(env.lamports(&victim).await, lamports);
+    assert_eq!(env.lamports(&attacker.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn test_close_whitelist_with_corrupt_map() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (owner, program_id) = (env.owner.insecure_clone(), env.program_id);
+    let (whitelist, lamports) = forged_account(&mut env, program_id, owner.pubkey());
+    let mut account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    // a map length past the fixed map region
+    account.data[41..45].copy_from_slice(&u32::MAX.to_le_bytes());
+    env.context.set_account(&whitelist, &account.into());
+
+    assert_eq!(
+        close(&mut env, &owner, &whitelist, &owner.pubkey()).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidAccountData))
+    );
+    assert_eq!(env.lamports(&whitelist).await, lamports);
+}
+
+#[tokio::test]
+async fn test_closed_whitelist_unreadable_later_in_transaction() {
+    let consumer = Pubkey::new_unique();
+    let mut env = TestEnv::start(|program_test| {
+        program_test.add_program("whitelist_consumer", consumer, processor!(process_consumer));
+    })
+    .await;
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 100).await.unwrap();
+    let owner = env.owner.insecure_clone();
+
+    let admit = consumer_instruction(&env, &consumer, &wallet);
+    env.send(std::slice::from_ref(&admit), &[]).await.unwrap();
+
+    // the whitelist keeps its data until the transaction ends, zeroed it decodes as no whitelist
+    let instructions = [close_instruction(&env, &owner.pubkey()), admit];
+    assert_eq!(
+        env.send(&instructions, &[&owner]).await,
+        Err(instruction_error(1, TokenWhitelistError::TokenWhitelistNotInit))
+    );
+    let whitelist = env.whitelist;
+    assert_ne!(env.lamports(&whitelist).await, 0);
+
+    // on its own the close goes through and leaves nothing behind
+    close(&mut env, &owner, &whitelist, &owner.pubkey()).await.unwrap();
+    assert_eq!(env.lamports(&whitelist).await, 0);
+}
@@ -0,0 +1,199 @@
+#![allow(dead_code)]
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, ReadableAccount},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transaction_context::TransactionReturnData,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::TokenWhitelistInstruction,
+    processor::Processor,
+    state::{TokenWhitelist, NO_CAMPAIGN},
+};
+
+pub const WHITELIST_ACCOUNT_SPACE: usize = 10240;
+
+/// What a simulated transaction returned
+pub struct Simulation {
+    pub result: Result<(), TransactionError>,
+    pub logs: Vec<String>,
+    pub return_data: Option<TransactionReturnData>,
+}
+
+impl Simulation {
+    /// The return data of a simulation that succeeded, or the error it failed with
+    pub fn returned(self) -> Result<TransactionReturnData, TransactionError> {
+        self.result?;
+        Ok(self.return_data.expect("no return data"))
+    }
+}
+
+/// A started program-test bank holding one initialized whitelist
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub owner: Keypair,
+    pub whitelist: Pubkey,
+}
+
+impl TestEnv {
+    /// Starts a bank with an initialized whitelist, `setup` can add further accounts
+    pub async fn start<F: FnOnce(&mut ProgramTest)>(setup: F) -> Self {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "solr_token_whitelist",
+            program_id,
+            processor!(Processor::process),
+        );
+
+        let owner = Keypair::new();
+        let whitelist = Pubkey::new_unique();
+        let mut data = vec![0; WHITELIST_ACCOUNT_SPACE];
+        TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: owner.pubkey(),
+            max_whitelist_size: 50,
+            ..TokenWhitelist::default()
+        }
+        .pack_into_slice(&mut data)
+        .unwrap();
+        program_test.add_account(
+            whitelist,
+            Account {
+                lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+                data,
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        setup(&mut program_test);
+
+        TestEnv {
+            context: program_test.start_with_context().await,
+            program_id,
+            owner,
+            whitelist,
+        }
+    }
+
+    pub fn instruction(&self, instruction: TokenWhitelistInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction::new_with_bytes(self.program_id, &instruction.pack(), accounts)
+    }
+
+    /// Builds an owner-signed instruction against the whitelist account
+    pub fn owner_instruction(&self, instruction: TokenWhitelistInstruction) -> Instruction {
+        self.instruction(
+            instruction,
+            vec![
+                AccountMeta::new_readonly(self.owner.pubkey(), true),
+                AccountMeta::new(self.whitelist, false),
+            ],
+        )
+    }
+
+    pub async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransactionError> {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    /// Simulates `instructions` signed by the payer and `signers`, for their result, logs and
+    /// return data
+    pub async fn simulate(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Simulation {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        let simulation = self.context.banks_client.simulate_transaction(transaction).await.unwrap();
+        // a transaction the bank refuses to load runs nothing, so has no details
+        let (logs, return_data) = simulation.simulation_details
+            .map_or((vec![], None), |details| (details.logs, details.return_data));
+        Simulation {result: simulation.result.unwrap(), logs, return_data}
+    }
+
+    pub async fn send_as_owner(&mut self, instruction: TokenWhitelistInstruction) -> Result<(), TransactionError> {
+        let instruction = self.owner_instruction(instruction);
+        let owner = self.owner.insecure_clone();
+        self.send(&[instruction], &[&owner]).await
+    }
+
+    pub async fn add_to_whitelist(&mut self, account_to_add: &Pubkey, allocation_amount: u64) -> Result<(), TransactionError> {
+        let instruction = self.instruction(
+            TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+            vec![
+                AccountMeta::new_readonly(self.owner.pubkey(), true),
+                AccountMeta::new(self.whitelist, false),
+                AccountMeta::new_readonly(*account_to_add, false),
+            ],
+        );
+        let owner = self.owner.insecure_clone();
+        self.send(&[instruction], &[&owner]).await
+    }
+
+    /// Starts an env whose whitelist holds a wallet allocated `allocation_amount`, under the
+    /// consume limits given
+    pub async fn with_consume_limits(
+        allocation_amount: u64,
+        max_consume_per_tx: u64,
+        min_slots_between_consumes: u64,
+    ) -> (Self, Keypair) {
+        let mut env = TestEnv::start(|_| {}).await;
+        let wallet = Keypair::new();
+        env.add_to_whitelist(&wallet.pubkey(), allocation_amount).await.unwrap();
+        env.send_as_owner(TokenWhitelistInstruction::SetConsumeLimits {max_consume_per_tx, min_slots_between_consumes})
+            .await
+            .unwrap();
+        (env, wallet)
+    }
+
+    pub async fn lamports(&mut self, pubkey: &Pubkey) -> u64 {
+        self.context
+            .banks_client
+            .get_balance(*pubkey)
+            .await
+            .unwrap()
+    }
+
+    pub async fn whitelist_state(&mut self) -> TokenWhitelist {
+        let account = self.context
+            .banks_client
+            .get_account(self.whitelist)
+            .await
+            .unwrap()
+            .unwrap();
+        TokenWhitelist::unpack_from_slice(account.data()).unwrap()
+    }
+}
+
+/// Error returned when the instruction at `index` fails with a program error
+pub fn instruction_error(index: u8, error: TokenWhitelistError) -> TransactionError {
+    TransactionError::InstructionError(index, InstructionError::Custom(error as u32))
+}
+
+pub fn custom_error(error: TokenWhitelistError) -> TransactionError {
+    instruction_error(0, error)
+}
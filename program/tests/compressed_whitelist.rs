@@ -0,0 +1,217 @@
+#![cfg(feature = "compression")]
+
+mod common;
+
+use solana_program::{
+    instruction::{AccountMeta, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::{tokio, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    compression::{self, TreeMirror, COMPRESSION_PROGRAM_ID, NOOP_PROGRAM_ID, TREE_CAPACITY},
+    error::TokenWhitelistError,
+    instruction::{
+        append_compressed_entry, init_compressed_whitelist, replace_compressed_entry, verify_compressed_membership,
+        CompressedLeaf,
+    },
+    state::CompressedTree,
+};
+
+use common::{custom_error, TestEnv};
+
+/// Adds an empty tree account of the compression program, and the compression and noop
+/// programs themselves when `with_programs`
+fn add_tree(program_test: &mut ProgramTest, tree: Pubkey, with_programs: bool) {
+    if with_programs {
+        program_test.add_program("spl_account_compression", COMPRESSION_PROGRAM_ID, None);
+        program_test.add_program("spl_noop", NOOP_PROGRAM_ID, None);
+    }
+    program_test.add_account(
+        tree,
+        Account {
+            lamports: Rent::default().minimum_balance(compression::tree_space()),
+            data: vec![0; compression::tree_space()],
+            owner: COMPRESSION_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+}
+
+/// Sets the compressed tree of the whitelist directly, as if InitCompressedWhitelist ran
+async fn set_compressed_tree(env: &mut TestEnv, compressed_tree: CompressedTree) {
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    let mut state = env.whitelist_state().await;
+    state.extension.compressed_tree = Some(compressed_tree);
+    state.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&env.whitelist, &account.into());
+}
+
+async fn append(env: &mut TestEnv, tree: &Pubkey, wallet: &Pubkey, allocation: u64) -> Result<(), TransactionError> {
+    let owner = env.owner.insecure_clone();
+    let instruction =
+        append_compressed_entry(&env.program_id, &owner.pubkey(), &env.whitelist, tree, wallet, allocation);
+    env.send(&[instruction], &[&owner]).await
+}
+
+fn leaf(mirror: &TreeMirror, leaf_index: u32, allocation: u64) -> CompressedLeaf {
+    CompressedLeaf {
+        leaf_index,
+        allocation,
+        root: mirror.root(),
+        proof: mirror.proof(leaf_index as usize).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn test_init_checks_programs() {
+    let tree = Pubkey::new_unique();
+    let mut env = TestEnv::start(|program_test| add_tree(program_test, tree, false)).await;
+    let owner = env.owner.insecure_clone();
+
+    let mut instruction = init_compressed_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &tree);
+    instruction.accounts[4] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    assert_eq!(
+        env.send(&[instruction], &[&owner]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::IncorrectProgramId))
+    );
+
+    let mut instruction = init_compressed_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &tree);
+    instruction.accounts[3] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    assert_eq!(
+        env.send(&[instruction], &[&owner]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidSeeds))
+    );
+
+    let intruder = Keypair::new();
+    let instruction = init_compressed_whitelist(&env.program_id, &intruder.pubkey(), &env.whitelist, &tree);
+    assert_eq!(
+        env.send(&[instruction], &[&intruder]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert_eq!(env.whitelist_state().await.extension.compressed_tree, None);
+}
+
+#[tokio::test]
+async fn test_init_once() {
+    let tree = Pubkey::new_unique();
+    let mut env = TestEnv::start(|program_test| add_tree(program_test, tree, false)).await;
+    let (_, authority_bump) = compression::find_tree_authority(&env.program_id, &env.whitelist);
+    set_compressed_tree(&mut env, CompressedTree {tree, authority_bump, leaf_count: 0}).await;
+
+    let owner = env.owner.insecure_clone();
+    let other_tree = Pubkey::new_unique();
+    let instruction = init_compressed_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &other_tree);
+    assert_eq!(
+        env.send(&[instruction], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::CompressedTreeAlreadyInitialized))
+    );
+}
+
+#[tokio::test]
+async fn test_append_checked_before_tree() {
+    let tree = Pubkey::new_unique();
+    let mut env = TestEnv::start(|program_test| add_tree(program_test, tree, false)).await;
+    let wallet = Pubkey::new_unique();
+    assert_eq!(
+        append(&mut env, &tree, &wallet, 100).await,
+        Err(custom_error(TokenWhitelistError::CompressedTreeMismatch))
+    );
+
+    let (_, authority_bump) = compression::find_tree_authority(&env.program_id, &env.whitelist);
+    set_compressed_tree(&mut env, CompressedTree {tree, authority_bump, leaf_count: TREE_CAPACITY as u32}).await;
+    assert_eq!(
+        append(&mut env, &tree, &wallet, 100).await,
+        Err(custom_error(TokenWhitelistError::CompressedTreeFull))
+    );
+    assert_eq!(
+        append(&mut env, &Pubkey::new_unique(), &wallet, 100).await,
+        Err(custom_error(TokenWhitelistError::CompressedTreeMismatch))
+    );
+    assert_eq!(env.whitelist_state().await.extension.mutation_nonce, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs spl_account_compression.so and spl_noop.so in tests/fixtures"]
+async fn test_append_and_verify() {
+    let tree = Pubkey::new_unique();
+    let mut env = TestEnv::start(|program_test| add_tree(program_test, tree, true)).await;
+    let owner = env.owner.insecure_clone();
+    let instruction = init_compressed_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &tree);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let entries: Vec<(Pubkey, u64)> = (1..=3).map(|i| (Pubkey::new_unique(), i * 100)).collect();
+    for (wallet, allocation) in &entries {
+        append(&mut env, &tree, wallet, *allocation).await.unwrap();
+    }
+    let state = env.whitelist_state().await;
+    assert_eq!(state.extension.compressed_tree.unwrap().leaf_count, 3);
+    // compressed entries stay out of the map
+    assert!(state.whitelist_map.is_empty());
+
+    let mirror = TreeMirror::new(&entries);
+    let tree_data = env.context.banks_client.get_account(tree).await.unwrap().unwrap().data;
+    assert_eq!(compression::current_root(&tree_data), Some(mirror.root()));
+
+    let (wallet, allocation) = entries[1];
+    let verify = |leaf: CompressedLeaf| {
+        verify_compressed_membership(&env.program_id, &env.whitelist, &tree, &wallet, &leaf)
+    };
+    let verified = verify(leaf(&mirror, 1, allocation));
+    let inflated = verify(leaf(&mirror, 1, allocation + 1));
+    let moved = verify(leaf(&mirror, 2, allocation));
+    env.send(&[verified], &[]).await.unwrap();
+
+    // a leaf claiming another allocation or index has no proof
+    assert!(env.send(&[inflated], &[]).await.is_err());
+    assert!(env.send(&[moved], &[]).await.is_err());
+}
+
+#[tokio::test]
+#[ignore = "needs spl_account_compression.so and spl_noop.so in tests/fixtures"]
+async fn test_replace_allocation() {
+    let tree = Pubkey::new_unique();
+    let mut env = TestEnv::start(|program_test| add_tree(program_test, tree, true)).await;
+    let owner = env.owner.insecure_clone();
+    let instruction = init_compressed_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &tree);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let mut entries: Vec<(Pubkey, u64)> = (1..=2).map(|i| (Pubkey::new_unique(), i * 100)).collect();
+    for (wallet, allocation) in &entries {
+        append(&mut env, &tree, wallet, *allocation).await.unwrap();
+    }
+    let nonce = env.whitelist_state().await.extension.mutation_nonce;
+
+    let (wallet, allocation) = entries[0];
+    let before = TreeMirror::new(&entries);
+    let replace = replace_compressed_entry(
+        &env.program_id,
+        &owner.pubkey(),
+        &env.whitelist,
+        &tree,
+        &wallet,
+        &leaf(&before, 0, allocation),
+        500,
+    );
+    env.send(&[replace], &[&owner]).await.unwrap();
+    assert_eq!(env.whitelist_state().await.extension.mutation_nonce, nonce + 1);
+
+    entries[0].1 = 500;
+    let after = TreeMirror::new(&entries);
+    let verify = verify_compressed_membership(&env.program_id, &env.whitelist, &tree, &wallet, &leaf(&after, 0, 500));
+    env.send(&[verify], &[]).await.unwrap();
+
+    // replacing past the last leaf is refused before the tree sees it
+    let past_end = CompressedLeaf {leaf_index: 2, ..leaf(&after, 0, 500)};
+    let past_end =
+        replace_compressed_entry(&env.program_id, &owner.pubkey(), &env.whitelist, &tree, &wallet, &past_end, 600);
+    assert_eq!(
+        env.send(&[past_end], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+}
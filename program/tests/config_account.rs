@@ -0,0 +1,193 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{init_config, with_config, TokenWhitelistInstruction},
+    state::{AllocationBounds, WhitelistConfig, CONFIG_SPACE, NO_CAMPAIGN},
+};
+
+use common::{custom_error, TestEnv};
+
+/// The owner pays the rent of the config account
+fn fund_owner(env: &mut TestEnv) {
+    let account = Account {lamports: 1_000_000_000, owner: system_program::id(), ..Account::default()};
+    env.context.set_account(&env.owner.pubkey(), &account.into());
+}
+
+/// Starts a whitelist and gives it a config account, returning the config address
+async fn config_env() -> (TestEnv, Pubkey) {
+    let mut env = TestEnv::start(|_| {}).await;
+    fund_owner(&mut env);
+    let instruction = init_config(&env.program_id, &env.owner.pubkey(), &env.whitelist);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    let (config, _) = WhitelistConfig::find_address(&env.program_id, &env.whitelist);
+    (env, config)
+}
+
+async fn config_state(env: &mut TestEnv, config: &Pubkey) -> WhitelistConfig {
+    let account = env.context.banks_client.get_account(*config).await.unwrap().unwrap();
+    assert_eq!(account.owner, env.program_id);
+    assert_eq!(account.data.len(), CONFIG_SPACE);
+    WhitelistConfig::unpack_from_slice(&account.data).unwrap()
+}
+
+async fn send_with_config(
+    env: &mut TestEnv,
+    instruction: TokenWhitelistInstruction,
+    config: &Pubkey,
+) -> Result<(), TransactionError> {
+    let instruction = with_config(env.owner_instruction(instruction), config);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+async fn add_with_config(
+    env: &mut TestEnv,
+    account_to_add: &Pubkey,
+    allocation_amount: u64,
+    config: &Pubkey,
+) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::AddToWhitelist {allocation_amount, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+        vec![
+            AccountMeta::new_readonly(env.owner.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(*account_to_add, false),
+        ],
+    );
+    let instruction = with_config(instruction, config);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+#[tokio::test]
+async fn test_init_config_copies_policy() {
+    let mut env = TestEnv::start(|_| {}).await;
+    fund_owner(&mut env);
+    env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 2}).await.unwrap();
+    let instruction = init_config(&env.program_id, &env.owner.pubkey(), &env.whitelist);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let (config, bump) = WhitelistConfig::find_address(&env.program_id, &env.whitelist);
+    let state = config_state(&mut env, &config).await;
+    assert_eq!(state.whitelist, env.whitelist);
+    assert_eq!(state.policy.active_round, 2);
+    let extension = env.whitelist_state().await.extension;
+    assert_eq!(extension.config_bump, Some(bump));
+    assert_eq!(extension.linked_accounts.config, Some(config));
+}
+
+#[tokio::test]
+async fn test_setters_write_config_only() {
+    let (mut env, config) = config_env().await;
+    let before = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+
+    let bounds = TokenWhitelistInstruction::SetAllocationBounds {min_allocation: 10, max_allocation: 100};
+    send_with_config(&mut env, bounds, &config).await.unwrap();
+    let strict = TokenWhitelistInstruction::SetStrictTokenChecks {strict_token_checks: true};
+    send_with_config(&mut env, strict, &config).await.unwrap();
+
+    let state = config_state(&mut env, &config).await;
+    assert_eq!(state.policy.allocation_bounds, AllocationBounds {min_allocation: 10, max_allocation: 100});
+    assert!(state.policy.strict_token_checks);
+    let after = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    assert_eq!(after.data, before.data);
+}
+
+#[tokio::test]
+async fn test_add_reads_config_policy() {
+    let (mut env, config) = config_env().await;
+    let bounds = TokenWhitelistInstruction::SetAllocationBounds {min_allocation: 10, max_allocation: 100};
+    send_with_config(&mut env, bounds, &config).await.unwrap();
+
+    let account = Pubkey::new_unique();
+    assert_eq!(
+        add_with_config(&mut env, &account, 500, &config).await,
+        Err(custom_error(TokenWhitelistError::AllocationAboveMaximum))
+    );
+    add_with_config(&mut env, &account, 50, &config).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&account.to_string()), Some(&50));
+}
+
+#[tokio::test]
+async fn test_config_required_once_created() {
+    let (mut env, _) = config_env().await;
+    assert_eq!(
+        env.add_to_whitelist(&Pubkey::new_unique(), 50).await,
+        Err(custom_error(TokenWhitelistError::ConfigAccountMissing))
+    );
+    assert_eq!(
+        env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 1}).await,
+        Err(custom_error(TokenWhitelistError::ConfigAccountMissing))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_forged_config_refused() {
+    let (mut env, config) = config_env().await;
+    // a look-alike of the config lifting the allocation bounds, passed in its place
+    let forged = Pubkey::new_unique();
+    let mut account = env.context.banks_client.get_account(config).await.unwrap().unwrap();
+    env.context.set_account(&forged, &account.clone().into());
+    assert_eq!(
+        add_with_config(&mut env, &Pubkey::new_unique(), 50, &forged).await,
+        Err(custom_error(TokenWhitelistError::ConfigAccountMissing))
+    );
+
+    // the linked config holding the policy of another whitelist
+    let other = WhitelistConfig {whitelist: Pubkey::new_unique(), ..config_state(&mut env, &config).await};
+    other.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&config, &account.into());
+    assert_eq!(
+        add_with_config(&mut env, &Pubkey::new_unique(), 50, &config).await,
+        Err(custom_error(TokenWhitelistError::UnlinkedCompanionAccount))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_init_config_twice() {
+    let (mut env, _) = config_env().await;
+    let instruction = init_config(&env.program_id, &env.owner.pubkey(), &env.whitelist);
+    let owner = env.owner.insecure_clone();
+    assert_eq!(
+        env.send(&[instruction], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::ConfigAlreadyInitialized))
+    );
+}
+
+#[tokio::test]
+async fn test_init_config_requires_owner() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let stranger = Keypair::new();
+    let instruction = init_config(&env.program_id, &stranger.pubkey(), &env.whitelist);
+    assert_eq!(
+        env.send(&[instruction], &[&stranger]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert_eq!(env.whitelist_state().await.extension.config_bump, None);
+}
+
+#[tokio::test]
+async fn test_without_config_policy_stays_in_whitelist() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let bounds = TokenWhitelistInstruction::SetAllocationBounds {min_allocation: 10, max_allocation: 100};
+    env.send_as_owner(bounds).await.unwrap();
+    let state = env.whitelist_state().await;
+    assert_eq!(state.extension.allocation_bounds, AllocationBounds {min_allocation: 10, max_allocation: 100});
+    assert_eq!(state.extension.config_bump, None);
+    assert_eq!(
+        env.add_to_whitelist(&Pubkey::new_unique(), 500).await,
+        Err(custom_error(TokenWhitelistError::AllocationAboveMaximum))
+    );
+}
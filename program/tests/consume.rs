@@ -0,0 +1,102 @@
+mod common;
+
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::TokenWhitelistInstruction,
+};
+
+use common::{custom_error, instruction_error, TestEnv};
+
+const ALLOCATION_AMOUNT: u64 = 1000;
+
+fn consume(env: &TestEnv, wallet: &Keypair, amount: u64) -> Instruction {
+    env.instruction(
+        TokenWhitelistInstruction::ConsumeAllocation {amount},
+        vec![
+            AccountMeta::new_readonly(wallet.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+        ],
+    )
+}
+
+async fn allocation(env: &mut TestEnv, wallet: &Keypair) -> u64 {
+    *env.whitelist_state().await.whitelist_map.get(&wallet.pubkey().to_string()).unwrap()
+}
+
+#[tokio::test]
+async fn test_consume_limit_per_transaction() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 300, 0).await;
+
+    let over_limit = consume(&env, &wallet, 301);
+    assert_eq!(
+        env.send(&[over_limit], &[&wallet]).await,
+        Err(custom_error(TokenWhitelistError::ConsumeLimitExceeded))
+    );
+    let at_limit = consume(&env, &wallet, 300);
+    env.send(&[at_limit], &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, ALLOCATION_AMOUNT - 300);
+
+    // without a cooldown the limit applies per instruction
+    let instructions = [consume(&env, &wallet, 300), consume(&env, &wallet, 300)];
+    env.send(&instructions, &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, ALLOCATION_AMOUNT - 900);
+
+    let over_allocation = consume(&env, &wallet, 101);
+    assert_eq!(
+        env.send(&[over_allocation], &[&wallet]).await,
+        Err(custom_error(TokenWhitelistError::InsufficientAllocation))
+    );
+}
+
+#[tokio::test]
+async fn test_unlimited_consume() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 0, 0).await;
+
+    let instruction = consume(&env, &wallet, ALLOCATION_AMOUNT);
+    env.send(&[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, 0);
+
+    let stranger = Keypair::new();
+    let instruction = consume(&env, &stranger, 1);
+    assert_eq!(
+        env.send(&[instruction], &[&stranger]).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+}
+
+#[tokio::test]
+async fn test_consume_cooldown() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 300, 10).await;
+
+    // a second consume in the same transaction lands in the same slot
+    let instructions = [consume(&env, &wallet, 100), consume(&env, &wallet, 100)];
+    assert_eq!(
+        env.send(&instructions, &[&wallet]).await,
+        Err(instruction_error(1, TokenWhitelistError::ConsumeCooldownActive))
+    );
+
+    let instruction = consume(&env, &wallet, 100);
+    env.send(&[instruction], &[&wallet]).await.unwrap();
+    let last_consume_slot = *env
+        .whitelist_state()
+        .await
+        .extension
+        .last_consume_slots
+        .get(&wallet.pubkey().to_string())
+        .unwrap();
+
+    env.context.warp_to_slot(last_consume_slot + 9).unwrap();
+    let instruction = consume(&env, &wallet, 100);
+    assert_eq!(
+        env.send(&[instruction], &[&wallet]).await,
+        Err(custom_error(TokenWhitelistError::ConsumeCooldownActive))
+    );
+
+    env.context.warp_to_slot(last_consume_slot + 10).unwrap();
+    let instruction = consume(&env, &wallet, 100);
+    env.send(&[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, ALLOCATION_AMOUNT - 200);
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use solr_token_whitelist::{
+    instruction::{
+        get_whitelist_stats, init_token_whitelist_with_units, remove_from_whitelist, InitWithUnits, TokenWhitelistInstruction,
+    },
+    state::{TokenWhitelist, WhitelistStatsReport},
+};
+
+use common::TestEnv;
+
+fn init(env: &TestEnv, owner: &Keypair) -> Instruction {
+    let whitelist = &env.whitelist;
+    init_token_whitelist_with_units(&env.program_id, &owner.pubkey(), whitelist, &InitWithUnits::new(50)).unwrap()
+}
+
+/// An env whose whitelist is a blank account of `space` bytes, initialized by the owner at
+/// slot 50
+async fn created_env(space: usize) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    // the blank account keeps the lamports of the whitelist it replaces and the payer transfers
+    // the rest: lamports changed behind the bank break its capitalization, checked on every warp
+    let whitelist = env.whitelist;
+    let lamports = env.lamports(&whitelist).await;
+    let account = Account {lamports, data: vec![0; space], owner: env.program_id, ..Account::default()};
+    env.context.set_account(&whitelist, &account.into());
+    let shortfall = Rent::default().minimum_balance(space).saturating_sub(lamports);
+    if shortfall > 0 {
+        let payer = env.context.payer.pubkey();
+        env.send(&[system_instruction::transfer(&payer, &whitelist, shortfall)], &[]).await.unwrap();
+    }
+    env.context.warp_to_slot(50).unwrap();
+    let owner = env.owner.insecure_clone();
+    env.send(&[init(&env, &owner)], &[&owner]).await.unwrap();
+    env
+}
+
+/// Simulates GetWhitelistStats for its return data
+async fn simulate_report(env: &mut TestEnv) -> WhitelistStatsReport {
+    let instruction = get_whitelist_stats(&env.program_id, &env.whitelist);
+    let return_data = env.simulate(&[instruction], &[]).await.returned().unwrap();
+    WhitelistStatsReport::try_from_slice(&return_data.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_init_records_creation() {
+    let mut env = created_env(TokenWhitelist::space_for(50)).await;
+    let owner = env.owner.pubkey();
+    let state = env.whitelist_state().await;
+    assert_eq!((state.extension.created_at_slot, state.extension.creator), (50, owner));
+    assert_eq!((state.summary().created_at_slot, state.summary().creator), (50, owner));
+
+    let report = simulate_report(&mut env).await;
+    assert_eq!((report.created_at_slot, report.creator), (50, owner));
+    assert_eq!(report.stats, state.extension.stats);
+}
+
+#[tokio::test]
+async fn test_mutations_keep_creation() {
+    let mut env = created_env(TokenWhitelist::space_for(50)).await;
+    let created = simulate_report(&mut env).await;
+
+    env.context.warp_to_slot(200).unwrap();
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+    env.add_to_whitelist(&first, 100).await.unwrap();
+    env.add_to_whitelist(&second, 100).await.unwrap();
+    env.add_to_whitelist(&first, 300).await.unwrap();
+    let owner = env.owner.insecure_clone();
+    let remove = remove_from_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &second);
+    env.send(&[remove], &[&owner]).await.unwrap();
+    env.send_as_owner(TokenWhitelistInstruction::SetOnlyWallets {only_wallets: true}).await.unwrap();
+    env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 1}).await.unwrap();
+
+    // initializing again is refused rather than recording a second creation
+    let stranger = Keypair::new();
+    assert!(env.send(&[init(&env, &stranger)], &[&stranger]).await.is_err());
+
+    let report = simulate_report(&mut env).await;
+    assert_eq!((report.created_at_slot, report.creator), (created.created_at_slot, created.creator));
+    assert_eq!(report.stats.total_adds, 3);
+    assert_eq!(report.stats.last_mutation_slot, 200);
+}
+
+#[tokio::test]
+async fn test_no_room_no_record() {
+    // an account of exactly the layout has no room for the extension, the record stays zeroed
+    let mut env = created_env(TokenWhitelist::MIN_SPACE).await;
+    assert_eq!(simulate_report(&mut env).await, WhitelistStatsReport::default());
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{effective_allocation, TokenWhitelistInstruction},
+    rules,
+    state::{ConsumeLimits, TokenWhitelist, NO_CAMPAIGN},
+};
+
+use common::{custom_error, TestEnv};
+use std::convert::TryInto;
+
+const ALLOCATION_AMOUNT: u64 = 1_000;
+
+/// Simulates EffectiveAllocation for its return data
+async fn effective(env: &mut TestEnv, wallet: &Pubkey) -> Result<u64, TransactionError> {
+    let instruction = effective_allocation(&env.program_id, &env.whitelist, wallet);
+    let return_data = env.simulate(&[instruction], &[]).await.returned()?;
+    Ok(u64::from_le_bytes(return_data.data.try_into().unwrap()))
+}
+
+async fn consume(env: &mut TestEnv, wallet: &Keypair, amount: u64) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::ConsumeAllocation {amount},
+        vec![
+            AccountMeta::new_readonly(wallet.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+        ],
+    );
+    env.send(&[instruction], &[wallet]).await
+}
+
+#[tokio::test]
+async fn test_effective_allocation() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 0, 0).await;
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(ALLOCATION_AMOUNT));
+
+    // consumes draw it down
+    consume(&mut env, &wallet, 400).await.unwrap();
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(ALLOCATION_AMOUNT - 400));
+
+    assert_eq!(
+        effective(&mut env, &Pubkey::new_unique()).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+}
+
+#[tokio::test]
+async fn test_capped_by_consume_limit() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 300, 0).await;
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(300));
+
+    for _ in 0..3 {
+        consume(&mut env, &wallet, 300).await.unwrap();
+    }
+    // what is left falls below the cap
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(100));
+}
+
+#[tokio::test]
+async fn test_zero_during_cooldown() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 300, 10).await;
+    consume(&mut env, &wallet, 100).await.unwrap();
+    let last_consume_slot = env.whitelist_state().await.extension.last_consume_slots[&wallet.pubkey().to_string()];
+
+    env.context.warp_to_slot(last_consume_slot + 9).unwrap();
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(0));
+    env.context.warp_to_slot(last_consume_slot + 10).unwrap();
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(300));
+}
+
+#[tokio::test]
+async fn test_active_round() {
+    let (mut env, wallet) = TestEnv::with_consume_limits(ALLOCATION_AMOUNT, 0, 0).await;
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::AddToWhitelistForRound {
+            allocation_amount: 250,
+            round: 1,
+            campaign_id: NO_CAMPAIGN,
+            expected_nonce: 0,
+        },
+        vec![
+            AccountMeta::new_readonly(env.owner.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(wallet.pubkey(), false),
+        ],
+    );
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 1}).await.unwrap();
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(250));
+    env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 0}).await.unwrap();
+    assert_eq!(effective(&mut env, &wallet.pubkey()).await, Ok(ALLOCATION_AMOUNT));
+}
+
+#[test]
+fn test_rules_off_chain() {
+    let wallet = Pubkey::new_unique();
+    let mut state = TokenWhitelist {is_initialized: true, max_whitelist_size: 10, ..TokenWhitelist::default()};
+    assert_eq!(
+        rules::effective_allocation(&state, &wallet, 0),
+        Err(TokenWhitelistError::AccountNotWhitelisted)
+    );
+
+    state.add_keypair(&wallet.to_string(), &ALLOCATION_AMOUNT);
+    state.extension.consume_limits = ConsumeLimits {max_consume_per_tx: 300, min_slots_between_consumes: 10};
+    state.extension.last_consume_slots.insert(wallet.to_string(), 100);
+    assert_eq!(rules::effective_allocation(&state, &wallet, 109), Ok(0));
+    assert_eq!(rules::effective_allocation(&state, &wallet, 110), Ok(300));
+
+    state.extension.consume_limits.max_consume_per_tx = 0;
+    assert_eq!(rules::effective_allocation(&state, &wallet, 110), Ok(ALLOCATION_AMOUNT));
+
+    let uninitialized = TokenWhitelist {is_initialized: false, ..state};
+    assert_eq!(
+        rules::effective_allocation(&uninitialized, &wallet, 110),
+        Err(TokenWhitelistError::TokenWhitelistNotInit)
+    );
+}
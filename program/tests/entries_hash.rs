@@ -0,0 +1,56 @@
+#![cfg(feature = "client")]
+
+mod common;
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solr_token_whitelist::{
+    client::entries::hash_entries,
+    instruction::compute_entries_hash,
+    state::TokenWhitelist,
+};
+
+use common::TestEnv;
+use std::{collections::BTreeMap, convert::TryInto};
+
+async fn simulate_entries_hash(env: &mut TestEnv) -> [u8; 32] {
+    let instruction = compute_entries_hash(&env.program_id, &env.whitelist);
+    let return_data = env.simulate(&[instruction], &[]).await.returned().unwrap();
+    assert_eq!(return_data.program_id, env.program_id);
+    return_data.data.try_into().unwrap()
+}
+
+fn entries(state: &TokenWhitelist) -> BTreeMap<Pubkey, u64> {
+    state.whitelist_map.iter()
+        .map(|(key, amount)| (key.parse().unwrap(), *amount))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_on_chain_hash_matches_off_chain() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert_eq!(simulate_entries_hash(&mut env).await, hash_entries(&BTreeMap::new()));
+
+    let mut expected = BTreeMap::new();
+    for batch in [1u64, 6, 20] {
+        for i in 0..batch {
+            let wallet = Pubkey::new_unique();
+            env.add_to_whitelist(&wallet, (i + 1) * 7_000).await.unwrap();
+            expected.insert(wallet, (i + 1) * 7_000);
+        }
+
+        let state = env.whitelist_state().await;
+        assert_eq!(entries(&state), expected);
+        let off_chain = hash_entries(&expected);
+        assert_eq!(simulate_entries_hash(&mut env).await, off_chain);
+        assert_eq!(state.extension.entries_hash, off_chain);
+    }
+
+    // updating an allocation changes the hash as well
+    let (&wallet, _) = expected.iter().next().unwrap();
+    env.add_to_whitelist(&wallet, 1).await.unwrap();
+    expected.insert(wallet, 1);
+    let state = env.whitelist_state().await;
+    assert_eq!(state.extension.entries_hash, hash_entries(&expected));
+    assert_eq!(simulate_entries_hash(&mut env).await, hash_entries(&expected));
+}
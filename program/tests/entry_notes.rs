@@ -0,0 +1,118 @@
+mod common;
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{remove_from_whitelist, reveal_entry_note, set_entry_note_commitment, MAX_NOTE_LEN},
+    state::{note_commitment, NO_NOTE_COMMITMENT},
+};
+
+use common::{custom_error, TestEnv};
+
+const NOTE: &[u8] = b"kyc tier 2, reviewed 2024-03";
+const SALT: [u8; 16] = [9; 16];
+
+async fn commit(
+    env: &mut TestEnv,
+    signer: &Keypair,
+    wallet: &Pubkey,
+    commitment: [u8; 32],
+) -> Result<(), TransactionError> {
+    let instruction = set_entry_note_commitment(&env.program_id, &signer.pubkey(), &env.whitelist, wallet, commitment);
+    env.send(&[instruction], &[signer]).await
+}
+
+async fn reveal(env: &mut TestEnv, wallet: &Pubkey, note: &[u8], salt: [u8; 16]) -> Result<(), TransactionError> {
+    let instruction = reveal_entry_note(&env.program_id, &env.whitelist, wallet, note, salt).unwrap();
+    env.send(&[instruction], &[]).await
+}
+
+/// An env whose whitelist holds `wallet` with a commitment to NOTE under SALT
+async fn committed_env(wallet: &Pubkey) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.add_to_whitelist(wallet, 100).await.unwrap();
+    let owner = env.owner.insecure_clone();
+    commit(&mut env, &owner, wallet, note_commitment(NOTE, &SALT)).await.unwrap();
+    env
+}
+
+#[tokio::test]
+async fn test_reveal_matching_note() {
+    let wallet = Pubkey::new_unique();
+    let mut env = committed_env(&wallet).await;
+    let state = env.whitelist_state().await;
+    assert_eq!(state.entry_note_commitment(&wallet.to_string()), note_commitment(NOTE, &SALT));
+
+    reveal(&mut env, &wallet, NOTE, SALT).await.unwrap();
+    // the reveal writes nothing
+    assert_eq!(env.whitelist_state().await, state);
+}
+
+#[tokio::test]
+async fn test_reveal_wrong_salt_or_note() {
+    let wallet = Pubkey::new_unique();
+    let mut env = committed_env(&wallet).await;
+    let mismatch = Err(custom_error(TokenWhitelistError::NoteCommitmentMismatch));
+    assert_eq!(reveal(&mut env, &wallet, NOTE, [8; 16]).await, mismatch);
+    assert_eq!(reveal(&mut env, &wallet, b"kyc tier 3, reviewed 2024-03", SALT).await, mismatch);
+
+    // an entry without commitment matches nothing, not even the empty note
+    let plain = Pubkey::new_unique();
+    env.add_to_whitelist(&plain, 100).await.unwrap();
+    assert_eq!(reveal(&mut env, &plain, b"", [0; 16]).await, mismatch);
+    assert_eq!(
+        reveal(&mut env, &Pubkey::new_unique(), NOTE, SALT).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+}
+
+#[tokio::test]
+async fn test_oversized_note_refused() {
+    let wallet = Pubkey::new_unique();
+    let mut env = committed_env(&wallet).await;
+    let longest = vec![b'n'; MAX_NOTE_LEN];
+    let owner = env.owner.insecure_clone();
+    commit(&mut env, &owner, &wallet, note_commitment(&longest, &SALT)).await.unwrap();
+    reveal(&mut env, &wallet, &longest, SALT).await.unwrap();
+
+    let too_long = vec![b'n'; MAX_NOTE_LEN + 1];
+    assert_eq!(
+        reveal_entry_note(&env.program_id, &env.whitelist, &wallet, &too_long, SALT),
+        Err(TokenWhitelistError::NoteTooLong)
+    );
+    // packed by hand past the builder, the program refuses it before reading any account
+    let mut data = vec![55, too_long.len() as u8];
+    data.extend_from_slice(&too_long);
+    data.extend_from_slice(&SALT);
+    let instruction = solana_program::instruction::Instruction::new_with_bytes(env.program_id, &data, vec![]);
+    assert_eq!(env.send(&[instruction], &[]).await, Err(custom_error(TokenWhitelistError::NoteTooLong)));
+}
+
+#[tokio::test]
+async fn test_commitment_owner_only_and_follows_entry() {
+    let wallet = Pubkey::new_unique();
+    let mut env = committed_env(&wallet).await;
+    let stranger = Keypair::new();
+    assert_eq!(
+        commit(&mut env, &stranger, &wallet, [1; 32]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    let owner = env.owner.insecure_clone();
+    assert_eq!(
+        commit(&mut env, &owner, &Pubkey::new_unique(), [1; 32]).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+
+    // clearing it, and removing the entry, leaves no commitment behind
+    commit(&mut env, &owner, &wallet, NO_NOTE_COMMITMENT).await.unwrap();
+    assert!(env.whitelist_state().await.extension.entry_note_commitments.is_empty());
+    commit(&mut env, &owner, &wallet, note_commitment(NOTE, &SALT)).await.unwrap();
+    let remove = remove_from_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &wallet);
+    env.send(&[remove], &[&owner]).await.unwrap();
+    assert!(env.whitelist_state().await.extension.entry_note_commitments.is_empty());
+}
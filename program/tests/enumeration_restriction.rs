@@ -0,0 +1,85 @@
+#![cfg(feature = "pages")]
+
+mod common;
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{
+        get_allocation, inspect_state, list_entries, set_enumeration_restricted, with_reader, TokenWhitelistInstruction,
+    },
+};
+
+use common::{custom_error, TestEnv};
+
+/// An env whose whitelist holds three entries, restricting enumeration or not
+async fn setup(restrict_enumeration: bool) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    for i in 1..=3 {
+        env.add_to_whitelist(&Pubkey::new_unique(), i * 100).await.unwrap();
+    }
+    let owner = env.owner.insecure_clone();
+    let restrict = set_enumeration_restricted(&env.program_id, &owner.pubkey(), &env.whitelist, restrict_enumeration);
+    env.send(&[restrict], &[&owner]).await.unwrap();
+    env
+}
+
+fn enumerations(env: &TestEnv) -> Vec<Instruction> {
+    vec![list_entries(&env.program_id, &env.whitelist, 0, 10), inspect_state(&env.program_id, &env.whitelist, 10)]
+}
+
+#[tokio::test]
+async fn test_unrestricted_enumeration_open() {
+    let mut env = setup(false).await;
+    let stranger = Keypair::new();
+    for instruction in enumerations(&env) {
+        env.simulate(std::slice::from_ref(&instruction), &[]).await.result.unwrap();
+        // a reader passed anyway is not checked
+        env.simulate(&[with_reader(instruction, &stranger.pubkey())], &[&stranger]).await.result.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_restricted_enumeration_needs_owner_or_delegate() {
+    let mut env = setup(true).await;
+    let (owner, delegate, stranger) = (env.owner.insecure_clone(), Keypair::new(), Keypair::new());
+    let approve = TokenWhitelistInstruction::ApproveDelegate {max_adds: 1, max_allocation_per_add: 100};
+    let mut approve = env.owner_instruction(approve);
+    approve.accounts.push(AccountMeta::new_readonly(delegate.pubkey(), false));
+    env.send(&[approve], &[&owner]).await.unwrap();
+
+    let unsigned = Err(TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature));
+    for instruction in enumerations(&env) {
+        assert_eq!(env.simulate(std::slice::from_ref(&instruction), &[]).await.result, unsigned);
+        assert_eq!(
+            env.simulate(&[with_reader(instruction.clone(), &stranger.pubkey())], &[&stranger]).await.result,
+            Err(custom_error(TokenWhitelistError::InvalidAuthority))
+        );
+        env.simulate(&[with_reader(instruction.clone(), &owner.pubkey())], &[&owner]).await.result.unwrap();
+        env.simulate(&[with_reader(instruction, &delegate.pubkey())], &[&delegate]).await.result.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_restricted_lookups_stay_open() {
+    let mut env = setup(true).await;
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 100).await.unwrap();
+    let lookup = get_allocation(&env.program_id, &env.whitelist, &wallet, 0);
+    env.simulate(&[lookup], &[]).await.result.unwrap();
+
+    // lifting the restriction opens enumeration again
+    let owner = env.owner.insecure_clone();
+    let lift = set_enumeration_restricted(&env.program_id, &owner.pubkey(), &env.whitelist, false);
+    env.send(&[lift], &[&owner]).await.unwrap();
+    let list = list_entries(&env.program_id, &env.whitelist, 0, 10);
+    env.simulate(&[list], &[]).await.result.unwrap();
+}
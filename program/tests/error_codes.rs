@@ -0,0 +1,26 @@
+use solr_token_whitelist::error::TokenWhitelistError;
+
+/// Highest code scanned for variants, past the last category range
+const MAX_CODE: u32 = 1_000;
+
+fn snapshot() -> String {
+    (0..MAX_CODE)
+        .filter_map(TokenWhitelistError::from_u32)
+        .map(|error| format!("{} {:?} {} {}\n", error.code(), error, error.category(), error))
+        .collect()
+}
+
+#[test]
+fn test_error_codes_match_fixture() {
+    let fixture: String = include_str!("fixtures/error_codes.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let snapshot = snapshot();
+    assert!(
+        snapshot == fixture,
+        "errors differ from fixtures/error_codes.txt, append new errors there deliberately:\n{}",
+        snapshot
+    );
+}
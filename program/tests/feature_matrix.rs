@@ -0,0 +1,31 @@
+use std::{path::Path, process::Command};
+
+/// Feature sets run on top of --no-default-features: none, then each subsystem alone
+const FEATURE_SETS: &[&str] = &["", "merkle", "compression", "events", "pages"];
+
+/// Lints, builds and tests the crate once per feature set, as CI does. Each run compiles the
+/// crate again, so this only runs on request: `cargo test --test feature_matrix -- --ignored`.
+#[test]
+#[ignore = "rebuilds the crate once per feature set"]
+fn test_feature_matrix() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    // the cargo running this test holds the lock on the default target directory
+    let target_dir = Path::new(manifest_dir).join("target").join("feature-matrix");
+    for features in FEATURE_SETS {
+        // code compiled out of a feature set can leave lints the default build never sees
+        let status = Command::new(env!("CARGO"))
+            .current_dir(manifest_dir)
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .args(["clippy", "--all-targets", "--no-default-features", "--features", features, "--", "-D", "warnings"])
+            .status()
+            .unwrap();
+        assert!(status.success(), "clippy failed with features [{}]", features);
+        let status = Command::new(env!("CARGO"))
+            .current_dir(manifest_dir)
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .args(["test", "--no-default-features", "--features", features])
+            .status()
+            .unwrap();
+        assert!(status.success(), "tests failed with features [{}]", features);
+    }
+}
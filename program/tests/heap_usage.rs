@@ -0,0 +1,134 @@
+//! Heap use of the heaviest instruction at the map capacity. On chain the program allocates from
+//! a 32 KiB bump heap that never frees, so running out aborts the transaction with nothing more
+//! than "memory allocation failed". The allocator below meters the processor running natively
+//! the same way: every byte allocated counts, whether or not it was freed since.
+
+mod common;
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+use solr_token_whitelist::{error::TokenWhitelistError, state::TokenWhitelist};
+
+use common::{custom_error, TestEnv};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// System allocator counting what the current thread allocates while metering
+struct HeapMeter;
+
+thread_local! {
+    static METERING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for HeapMeter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // thread locals may already be gone while a thread exits, nothing is metered then
+        let _ = METERING.try_with(|metering| {
+            if metering.get() {
+                ALLOCATED.with(|allocated| allocated.set(allocated.get() + layout.size()));
+            }
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: HeapMeter = HeapMeter;
+
+/// Share of the on-chain heap the heaviest instruction may take, leaving room for the account
+/// infos the entrypoint deserializes, alignment padding, and entries growing in later versions
+#[cfg(feature = "test-utils")]
+const HEAP_BUDGET: usize = solana_program::entrypoint::HEAP_LENGTH * 3 / 4;
+
+/// Runs `f`, returning its result and the bytes it allocated as the bump heap would count them
+#[cfg(feature = "test-utils")]
+fn metered<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    ALLOCATED.with(|allocated| allocated.set(0));
+    METERING.with(|metering| metering.set(true));
+    let result = f();
+    METERING.with(|metering| metering.set(false));
+    (result, ALLOCATED.with(Cell::get))
+}
+
+/// An initialized whitelist one entry short of TokenWhitelist::MAX_ENTRIES, keyed by pubkeys as
+/// long as base58 gets so that the last add fills the map region as far as it can be filled
+fn nearly_full_whitelist(owner: &Pubkey) -> TokenWhitelist {
+    let mut state = TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: *owner,
+        max_whitelist_size: TokenWhitelist::MAX_ENTRIES,
+        ..TokenWhitelist::default()
+    };
+    // keys leading with 0xff bytes encode to the full 44 base58 characters, unlike new_unique
+    // whose counter sits in the leading bytes
+    for index in 0..TokenWhitelist::MAX_ENTRIES - 1 {
+        let mut bytes = [0xff; 32];
+        bytes[31] = index as u8;
+        let key = Pubkey::new_from_array(bytes).to_string();
+        assert_eq!(key.len(), 44);
+        state.add_keypair(&key, &u64::MAX);
+    }
+    state
+}
+
+#[tokio::test]
+async fn test_add_into_nearly_full_whitelist() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    nearly_full_whitelist(&env.owner.pubkey()).pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&env.whitelist, &account.into());
+
+    env.add_to_whitelist(&Pubkey::new_unique(), 1_000).await.unwrap();
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.len() as u64, TokenWhitelist::MAX_ENTRIES);
+    // the map region is what bounds the map, a further add no longer packs
+    assert_eq!(
+        env.add_to_whitelist(&Pubkey::new_unique(), 1_000).await,
+        Err(custom_error(TokenWhitelistError::StateInvariantViolation))
+    );
+}
+
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_add_heap_usage_at_capacity() {
+    use solr_token_whitelist::{
+        account_fixture::{run_instruction, AccountFixture, FIXTURE_PROGRAM_ID},
+        instruction::TokenWhitelistInstruction,
+        state::NO_CAMPAIGN,
+    };
+
+    let owner = Pubkey::new_unique();
+    let mut accounts = [
+        AccountFixture::new(owner).signer(),
+        AccountFixture::new(Pubkey::new_unique())
+            .writable()
+            .owner(FIXTURE_PROGRAM_ID)
+            .data_len(common::WHITELIST_ACCOUNT_SPACE)
+            .whitelist(&nearly_full_whitelist(&owner)),
+        AccountFixture::new(Pubkey::new_unique()),
+    ];
+    let add = TokenWhitelistInstruction::AddToWhitelist {
+        allocation_amount: 1_000,
+        campaign_id: NO_CAMPAIGN,
+        expected_nonce: 0,
+    }.pack();
+
+    let (result, allocated) = metered(|| run_instruction(&add, &mut accounts));
+    result.unwrap();
+    assert_eq!(accounts[1].state().whitelist_map.len() as u64, TokenWhitelist::MAX_ENTRIES);
+    println!("add into {} entries allocated {} bytes", TokenWhitelist::MAX_ENTRIES - 1, allocated);
+    assert!(
+        allocated <= HEAP_BUDGET,
+        "add at capacity allocated {} bytes, over the {} byte budget",
+        allocated,
+        HEAP_BUDGET
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Signer, transaction::Transaction};
+use solr_token_whitelist::{
+    instruction::{inspect_state, MAX_INSPECT_ENTRIES},
+    state::TokenWhitelist,
+};
+
+use common::TestEnv;
+
+/// Sends InspectState, signed by the payer alone, and returns the lines the program logged
+async fn inspect(env: &mut TestEnv, max_entries_to_log: u8) -> Vec<String> {
+    let instruction = inspect_state(&env.program_id, &env.whitelist, max_entries_to_log);
+    let blockhash = env.context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer],
+        blockhash,
+    );
+    let result = env.context.banks_client.process_transaction_with_metadata(transaction).await.unwrap();
+    result.result.unwrap();
+    result.metadata.unwrap().log_messages
+        .into_iter()
+        .filter_map(|log| log.strip_prefix("Program log: ").map(str::to_string))
+        .collect()
+}
+
+/// Sets the whitelist to hold `count` entries, the one at `i` with allocation `i * 100`
+async fn set_entries(env: &mut TestEnv, count: u8) -> TokenWhitelist {
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    let mut state = TokenWhitelist::unpack_from_slice(&account.data).unwrap();
+    for i in 1..=count {
+        state.add_keypair(&Pubkey::new_from_array([i; 32]).to_string(), &(i as u64 * 100));
+    }
+    state.update_entries_hash(account.data.len());
+    state.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&env.whitelist, &account.into());
+    state
+}
+
+fn entry_lines(state: &TokenWhitelist, count: usize) -> Vec<String> {
+    state.whitelist_map.iter().take(count).map(|(key, amount)| format!("{}={}", key, amount)).collect()
+}
+
+#[tokio::test]
+async fn test_inspect_state_logs() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let state = set_entries(&mut env, 3).await;
+    let before = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+
+    let mut expect = vec![
+        "Instruction: InspectState".to_string(),
+        format!("whitelist {}: owner {}, max size 50", env.whitelist, env.owner.pubkey()),
+        format!("entries 3, map bytes free {}, active round 0, allocation decimals 0", state.map_bytes_remaining()),
+    ];
+    expect.extend(entry_lines(&state, 2));
+    expect.push("1 more entries not logged".to_string());
+    assert_eq!(inspect(&mut env, 2).await, expect);
+
+    // nothing written
+    let after = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    assert_eq!(after, before);
+}
+
+#[tokio::test]
+async fn test_inspect_state_bounded() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let state = set_entries(&mut env, 40).await;
+
+    let logs = inspect(&mut env, u8::MAX).await;
+    assert_eq!(logs[3..3 + MAX_INSPECT_ENTRIES], entry_lines(&state, MAX_INSPECT_ENTRIES)[..]);
+    assert_eq!(logs[3 + MAX_INSPECT_ENTRIES..], [format!("{} more entries not logged", 40 - MAX_INSPECT_ENTRIES)]);
+
+    // no entry asked for, the header alone
+    assert_eq!(inspect(&mut env, 0).await[3..], ["40 more entries not logged".to_string()]);
+}
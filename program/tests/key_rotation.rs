@@ -0,0 +1,105 @@
+mod common;
+
+use solana_program::instruction::InstructionError;
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{add_to_whitelist_with_attestation, rotate_whitelisted_key, TokenWhitelistInstruction},
+    state::NO_ATTESTATION,
+};
+
+use common::{custom_error, TestEnv};
+
+#[tokio::test]
+async fn test_rotate_whitelisted_key() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (current, new) = (Keypair::new(), Keypair::new());
+    let owner = env.owner.insecure_clone();
+    let add = add_to_whitelist_with_attestation(
+        &env.program_id,
+        &owner.pubkey(),
+        &env.whitelist,
+        &current.pubkey(),
+        250,
+        [7; 32],
+    );
+    env.send(&[add], &[&owner]).await.unwrap();
+    let before = env.whitelist_state().await;
+
+    let rotate = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &new.pubkey());
+    env.send(&[rotate], &[&current, &new]).await.unwrap();
+
+    let state = env.whitelist_state().await;
+    let (current, new) = (current.pubkey().to_string(), new.pubkey().to_string());
+    assert_eq!(state.whitelist_map.get(&current), None);
+    assert_eq!(state.whitelist_map.get(&new), Some(&250));
+    assert_eq!(state.attestation(&current), NO_ATTESTATION);
+    assert_eq!(state.attestation(&new), [7; 32]);
+    assert_eq!(state.whitelist_map.len(), before.whitelist_map.len());
+    assert_ne!(state.extension.entries_hash, before.extension.entries_hash);
+}
+
+#[tokio::test]
+async fn test_rotate_needs_both_signatures() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (current, new) = (Keypair::new(), Keypair::new());
+    env.add_to_whitelist(&current.pubkey(), 100).await.unwrap();
+
+    // either wallet signing alone is refused
+    for (unsigned, signer) in [(2, &current), (0, &new)] {
+        let mut rotate = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &new.pubkey());
+        rotate.accounts[unsigned].is_signer = false;
+        assert_eq!(
+            env.send(&[rotate], &[signer]).await,
+            Err(TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature))
+        );
+    }
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&current.pubkey().to_string()), Some(&100));
+}
+
+#[tokio::test]
+async fn test_rotate_refused() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (current, new, stranger) = (Keypair::new(), Keypair::new(), Keypair::new());
+    env.add_to_whitelist(&current.pubkey(), 100).await.unwrap();
+    env.add_to_whitelist(&new.pubkey(), 200).await.unwrap();
+
+    // the new wallet holds an entry of its own
+    let rotate = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &new.pubkey());
+    assert_eq!(
+        env.send(&[rotate], &[&current, &new]).await,
+        Err(custom_error(TokenWhitelistError::AccountAlreadyWhitelisted))
+    );
+
+    // and so is a wallet without an entry
+    let unlisted = Keypair::new();
+    let rotate = rotate_whitelisted_key(&env.program_id, &stranger.pubkey(), &env.whitelist, &unlisted.pubkey());
+    assert_eq!(
+        env.send(&[rotate], &[&stranger, &unlisted]).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.get(&current.pubkey().to_string()), Some(&100));
+    assert_eq!(state.whitelist_map.get(&new.pubkey().to_string()), Some(&200));
+}
+
+#[tokio::test]
+async fn test_rotate_to_owner_disallowed() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (current, owner) = (Keypair::new(), env.owner.insecure_clone());
+    env.add_to_whitelist(&current.pubkey(), 100).await.unwrap();
+    env.send_as_owner(TokenWhitelistInstruction::SetOwnerEntryAllowed {allow_owner_entry: false}).await.unwrap();
+
+    // the owner could not be added, so no entry can be rotated to it
+    let rotate = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &owner.pubkey());
+    assert_eq!(
+        env.send(&[rotate], &[&current, &owner]).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&current.pubkey().to_string()), Some(&100));
+}
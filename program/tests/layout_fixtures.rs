@@ -0,0 +1,410 @@
+use solana_program::pubkey::Pubkey;
+use solr_token_whitelist::{
+    instruction::{MerkleClaim, TokenWhitelistInstruction},
+    state::{TokenWhitelist, NO_CAMPAIGN},
+};
+
+use std::collections::BTreeMap;
+
+fn decode_hex(fixture: &str) -> Vec<u8> {
+    let hex: Vec<u8> = fixture.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+        .collect()
+}
+
+fn expected_map(entries: u8) -> BTreeMap<String, u64> {
+    (1..=entries)
+        .map(|i| (Pubkey::new_from_array([i; 32]).to_string(), i as u64 * 1_000_000))
+        .collect()
+}
+
+fn check_account(fixture: &str, max_whitelist_size: u64, entries: u8) {
+    let data = decode_hex(fixture);
+    assert_eq!(data.len(), 5161);
+
+    let state = TokenWhitelist::unpack_from_slice(&data).unwrap();
+    assert!(state.is_initialized);
+    assert_eq!(state.init_pubkey, Pubkey::new_from_array([7; 32]));
+    assert_eq!(state.max_whitelist_size, max_whitelist_size);
+    assert_eq!(state.whitelist_map, expected_map(entries));
+    assert_eq!(state.extension, Default::default());
+
+    let mut repacked = vec![0; data.len()];
+    state.pack_into_slice(&mut repacked).unwrap();
+    assert_eq!(repacked, data);
+}
+
+#[test]
+fn test_account_init_only() {
+    check_account(include_str!("fixtures/account_init_only.hex"), 50, 0);
+}
+
+#[test]
+fn test_account_small_map() {
+    check_account(include_str!("fixtures/account_small_map.hex"), 50, 3);
+}
+
+#[test]
+fn test_account_near_capacity() {
+    check_account(include_str!("fixtures/account_near_capacity.hex"), 100, 90);
+}
+
+#[test]
+fn test_instructions() {
+    let fixtures = [
+        (
+            include_str!("fixtures/instructions/init_token_whitelist.hex"),
+            TokenWhitelistInstruction::InitTokenWhitelist { max_whitelist_size: 50 },
+        ),
+        (
+            include_str!("fixtures/instructions/add_to_whitelist.hex"),
+            TokenWhitelistInstruction::AddToWhitelist {
+                allocation_amount: 250_000_000,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 0,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/remove_from_whitelist.hex"),
+            TokenWhitelistInstruction::RemoveFromWhitelist { memo: None, expected_nonce: 0 },
+        ),
+        (
+            include_str!("fixtures/instructions/set_allocation_to_zero.hex"),
+            TokenWhitelistInstruction::SetAllocationToZero { memo: None },
+        ),
+        (
+            include_str!("fixtures/instructions/close_whitelist_account.hex"),
+            TokenWhitelistInstruction::CloseWhitelistAccount {},
+        ),
+        (
+            include_str!("fixtures/instructions/approve_delegate.hex"),
+            TokenWhitelistInstruction::ApproveDelegate {
+                max_adds: 10,
+                max_allocation_per_add: 1_000_000,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/revoke_delegate.hex"),
+            TokenWhitelistInstruction::RevokeDelegate {},
+        ),
+        (
+            include_str!("fixtures/instructions/configure_registration.hex"),
+            TokenWhitelistInstruction::ConfigureRegistration {
+                registration_end_slot: 5000,
+                registration_allocation: 2_000_000,
+                registration_deposit_lamports: 10_000_000,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/register_self.hex"),
+            TokenWhitelistInstruction::RegisterSelf {},
+        ),
+        (
+            include_str!("fixtures/instructions/refund_deposit.hex"),
+            TokenWhitelistInstruction::RefundDeposit {},
+        ),
+        (
+            include_str!("fixtures/instructions/run_lottery.hex"),
+            TokenWhitelistInstruction::RunLottery { winners: 3, seed: [9; 32] },
+        ),
+        (
+            include_str!("fixtures/instructions/set_consume_limits.hex"),
+            TokenWhitelistInstruction::SetConsumeLimits {
+                max_consume_per_tx: 500,
+                min_slots_between_consumes: 25,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/consume_allocation.hex"),
+            TokenWhitelistInstruction::ConsumeAllocation { amount: 100 },
+        ),
+        (
+            include_str!("fixtures/instructions/list_entries.hex"),
+            TokenWhitelistInstruction::ListEntries { offset: 40, limit: 20 },
+        ),
+        (
+            include_str!("fixtures/instructions/compute_entries_hash.hex"),
+            TokenWhitelistInstruction::ComputeEntriesHash {},
+        ),
+        (
+            include_str!("fixtures/instructions/init_token_whitelist_with_units.hex"),
+            TokenWhitelistInstruction::InitTokenWhitelistWithUnits {
+                max_whitelist_size: 50,
+                allocation_decimals: Some(6),
+                only_wallets: false,
+                self_zero_allowed: true,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/set_allocation_bounds.hex"),
+            TokenWhitelistInstruction::SetAllocationBounds {
+                min_allocation: 100,
+                max_allocation: 5_000,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/add_to_whitelist_for_round.hex"),
+            TokenWhitelistInstruction::AddToWhitelistForRound {
+                allocation_amount: 250_000_000,
+                round: 1,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 0,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/consume_allocation_for_round.hex"),
+            TokenWhitelistInstruction::ConsumeAllocationForRound { amount: 100, round: 255 },
+        ),
+        (
+            include_str!("fixtures/instructions/set_active_round.hex"),
+            TokenWhitelistInstruction::SetActiveRound { round: 1 },
+        ),
+        (
+            include_str!("fixtures/instructions/get_allocation.hex"),
+            TokenWhitelistInstruction::GetAllocation { round: 255 },
+        ),
+        (
+            include_str!("fixtures/instructions/assert_membership_across.hex"),
+            TokenWhitelistInstruction::AssertMembershipAcross {
+                require_in_first: true,
+                require_absent_in_second: true,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/archive_whitelist.hex"),
+            TokenWhitelistInstruction::ArchiveWhitelist {},
+        ),
+        (
+            include_str!("fixtures/instructions/get_version.hex"),
+            TokenWhitelistInstruction::GetVersion {},
+        ),
+        (
+            include_str!("fixtures/instructions/set_strict_token_checks.hex"),
+            TokenWhitelistInstruction::SetStrictTokenChecks {strict_token_checks: true},
+        ),
+        (
+            include_str!("fixtures/instructions/set_merkle_root.hex"),
+            TokenWhitelistInstruction::SetMerkleRoot {root: [0xab; 32], hash_kind: 1},
+        ),
+        (
+            include_str!("fixtures/instructions/verify_merkle_membership.hex"),
+            TokenWhitelistInstruction::VerifyMerkleMembership {
+                leaf_index: 7,
+                allocation: 250_000_000,
+                proof: vec![[3; 32], [4; 32]],
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/verify_merkle_membership_batch.hex"),
+            TokenWhitelistInstruction::VerifyMerkleMembershipBatch {
+                items: vec![
+                    MerkleClaim {
+                        wallet: Pubkey::new_from_array([5; 32]),
+                        leaf_index: 1,
+                        allocation: 1000,
+                        proof: vec![[3; 32]],
+                    },
+                    MerkleClaim {
+                        wallet: Pubkey::new_from_array([6; 32]),
+                        leaf_index: 2,
+                        allocation: 2000,
+                        proof: vec![],
+                    },
+                ],
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/init_claim_bitmap.hex"),
+            TokenWhitelistInstruction::InitClaimBitmap {leaf_count: 4096},
+        ),
+        (
+            include_str!("fixtures/instructions/assert_owner.hex"),
+            TokenWhitelistInstruction::AssertOwner {},
+        ),
+        (
+            include_str!("fixtures/instructions/set_only_wallets.hex"),
+            TokenWhitelistInstruction::SetOnlyWallets {only_wallets: true},
+        ),
+        (
+            include_str!("fixtures/instructions/top_up_rent.hex"),
+            TokenWhitelistInstruction::TopUpRent {},
+        ),
+        (
+            include_str!("fixtures/instructions/init_config.hex"),
+            TokenWhitelistInstruction::InitConfig {},
+        ),
+        (
+            include_str!("fixtures/instructions/set_self_zero_allowed.hex"),
+            TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: false},
+        ),
+        (
+            include_str!("fixtures/instructions/add_to_whitelist_for_campaign.hex"),
+            TokenWhitelistInstruction::AddToWhitelist {
+                allocation_amount: 250_000_000,
+                campaign_id: [1, 2, 3, 4, 5, 6, 7, 8],
+                expected_nonce: 0,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/list_entries_by_campaign.hex"),
+            TokenWhitelistInstruction::ListEntriesByCampaign {
+                campaign_id: [1, 2, 3, 4, 5, 6, 7, 8],
+                offset: 40,
+                limit: 20,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/repair_state.hex"),
+            TokenWhitelistInstruction::RepairState {},
+        ),
+        (
+            include_str!("fixtures/instructions/set_zero_allocation_allowed.hex"),
+            TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation: false},
+        ),
+        (
+            include_str!("fixtures/instructions/set_token_gate.hex"),
+            TokenWhitelistInstruction::SetTokenGate {
+                gating_mint: Pubkey::new_from_array([3; 32]),
+                gating_minimum: 1_000,
+                gate_owner_adds: true,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/migrate_state_layout.hex"),
+            TokenWhitelistInstruction::MigrateStateLayout {},
+        ),
+        (
+            include_str!("fixtures/instructions/inspect_state.hex"),
+            TokenWhitelistInstruction::InspectState {max_entries_to_log: 5},
+        ),
+        (
+            include_str!("fixtures/instructions/effective_allocation.hex"),
+            TokenWhitelistInstruction::EffectiveAllocation {},
+        ),
+        (
+            include_str!("fixtures/instructions/add_to_whitelist_expecting_nonce.hex"),
+            TokenWhitelistInstruction::AddToWhitelist {
+                allocation_amount: 250_000_000,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 7,
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/remove_from_whitelist_expecting_nonce.hex"),
+            TokenWhitelistInstruction::RemoveFromWhitelist { memo: None, expected_nonce: 7 },
+        ),
+        (
+            include_str!("fixtures/instructions/get_whitelist_stats.hex"),
+            TokenWhitelistInstruction::GetWhitelistStats {},
+        ),
+        (
+            include_str!("fixtures/instructions/add_to_whitelist_with_attestation.hex"),
+            TokenWhitelistInstruction::AddToWhitelistWithAttestation {allocation_amount: 250_000_000, attestation: [3; 32]},
+        ),
+        (
+            include_str!("fixtures/instructions/set_attestation.hex"),
+            TokenWhitelistInstruction::SetAttestation {attestation: [3; 32]},
+        ),
+        (
+            include_str!("fixtures/instructions/set_attestation_required.hex"),
+            TokenWhitelistInstruction::SetAttestationRequired {require_attestation: true},
+        ),
+        (
+            include_str!("fixtures/instructions/list_attested_entries.hex"),
+            TokenWhitelistInstruction::ListAttestedEntries {offset: 40, limit: 14},
+        ),
+        (
+            include_str!("fixtures/instructions/rotate_whitelisted_key.hex"),
+            TokenWhitelistInstruction::RotateWhitelistedKey {},
+        ),
+        (
+            include_str!("fixtures/instructions/assert_not_in_other.hex"),
+            TokenWhitelistInstruction::AssertNotInOther {},
+        ),
+        (
+            include_str!("fixtures/instructions/set_owner_entry_allowed.hex"),
+            TokenWhitelistInstruction::SetOwnerEntryAllowed {allow_owner_entry: false},
+        ),
+        (
+            include_str!("fixtures/instructions/get_required_rent.hex"),
+            TokenWhitelistInstruction::GetRequiredRent {max_whitelist_size: 50},
+        ),
+        (
+            include_str!("fixtures/instructions/set_entry_note_commitment.hex"),
+            TokenWhitelistInstruction::SetEntryNoteCommitment {commitment: [3; 32]},
+        ),
+        (
+            include_str!("fixtures/instructions/reveal_entry_note.hex"),
+            TokenWhitelistInstruction::RevealEntryNote {note: b"tier 2".to_vec(), salt: [9; 16]},
+        ),
+        (
+            include_str!("fixtures/instructions/set_registration_rate_limit.hex"),
+            TokenWhitelistInstruction::SetRegistrationRateLimit {max_registrations_per_window: 20, window_slots: 150},
+        ),
+        (
+            include_str!("fixtures/instructions/set_enumeration_restricted.hex"),
+            TokenWhitelistInstruction::SetEnumerationRestricted {restrict_enumeration: true},
+        ),
+        (
+            include_str!("fixtures/instructions/init_compressed_whitelist.hex"),
+            TokenWhitelistInstruction::InitCompressedWhitelist {},
+        ),
+        (
+            include_str!("fixtures/instructions/append_compressed_entry.hex"),
+            TokenWhitelistInstruction::AppendCompressedEntry {allocation_amount: 250_000_000},
+        ),
+        (
+            include_str!("fixtures/instructions/replace_compressed_entry.hex"),
+            TokenWhitelistInstruction::ReplaceCompressedEntry {
+                leaf_index: 7,
+                previous_allocation: 250_000_000,
+                allocation_amount: 100_000_000,
+                root: [3; 32],
+            },
+        ),
+        (
+            include_str!("fixtures/instructions/verify_compressed_membership.hex"),
+            TokenWhitelistInstruction::VerifyCompressedMembership {
+                leaf_index: 7,
+                allocation: 250_000_000,
+                root: [3; 32],
+            },
+        ),
+    ];
+    // the layout holds in every build, though only builds with the feature decode its tags
+    let compiled_in = |instruction: &TokenWhitelistInstruction| {
+        (cfg!(feature = "pages")
+            || !matches!(
+                instruction,
+                TokenWhitelistInstruction::ListEntries {..}
+                    | TokenWhitelistInstruction::ListEntriesByCampaign {..}
+                    | TokenWhitelistInstruction::ListAttestedEntries {..}
+            ))
+            && (cfg!(feature = "merkle")
+                || !matches!(
+                    instruction,
+                    TokenWhitelistInstruction::SetMerkleRoot {..}
+                        | TokenWhitelistInstruction::VerifyMerkleMembership {..}
+                        | TokenWhitelistInstruction::VerifyMerkleMembershipBatch {..}
+                        | TokenWhitelistInstruction::InitClaimBitmap {..}
+                ))
+            && (cfg!(feature = "compression")
+                || !matches!(
+                    instruction,
+                    TokenWhitelistInstruction::InitCompressedWhitelist {..}
+                        | TokenWhitelistInstruction::AppendCompressedEntry {..}
+                        | TokenWhitelistInstruction::ReplaceCompressedEntry {..}
+                        | TokenWhitelistInstruction::VerifyCompressedMembership {..}
+                ))
+    };
+    for (fixture, instruction) in fixtures {
+        let bytes = decode_hex(fixture);
+        assert_eq!(instruction.pack(), bytes);
+        if compiled_in(&instruction) {
+            assert_eq!(TokenWhitelistInstruction::unpack(&bytes).unwrap(), instruction);
+        } else {
+            assert!(TokenWhitelistInstruction::unpack(&bytes).is_err());
+        }
+    }
+}
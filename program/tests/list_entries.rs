@@ -0,0 +1,61 @@
+#![cfg(feature = "pages")]
+
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solr_token_whitelist::{
+    instruction::list_entries,
+    state::{EntriesPage, MAX_LIST_ENTRIES},
+};
+
+use common::TestEnv;
+
+async fn setup(entries: u64) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    for i in 1..=entries {
+        env.add_to_whitelist(&Pubkey::new_unique(), i * 1_000).await.unwrap();
+    }
+    env
+}
+
+async fn list(env: &mut TestEnv, offset: u32, limit: u8) -> EntriesPage {
+    let instruction = list_entries(&env.program_id, &env.whitelist, offset, limit);
+    let return_data = env.simulate(&[instruction], &[]).await.returned().unwrap();
+    assert_eq!(return_data.program_id, env.program_id);
+    EntriesPage::try_from_slice(&return_data.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_page_through_entries() {
+    let mut env = setup(50).await;
+
+    let mut entries = Vec::new();
+    loop {
+        let page = list(&mut env, entries.len() as u32, 20).await;
+        assert_eq!(page.total, 50);
+        if page.entries.is_empty() {
+            break;
+        }
+        entries.extend(page.entries);
+    }
+
+    let expected: Vec<(Pubkey, u64)> = env.whitelist_state().await.whitelist_map.iter()
+        .map(|(key, amount)| (key.parse().unwrap(), *amount))
+        .collect();
+    assert_eq!(entries.len(), 50);
+    assert_eq!(entries, expected);
+}
+
+#[tokio::test]
+async fn test_limit_capped_by_return_data() {
+    let mut env = setup(30).await;
+
+    let page = list(&mut env, 0, u8::MAX).await;
+    assert_eq!(page.total, 30);
+    assert_eq!(page.entries.len(), MAX_LIST_ENTRIES);
+
+    let page = list(&mut env, 28, u8::MAX).await;
+    assert_eq!(page.entries.len(), 2);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey, rent::Rent, sysvar};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{get_required_rent, init_token_whitelist_with_units, inspect_state, InitWithUnits, TokenWhitelistInstruction},
+    state::{RequiredRent, TokenWhitelist, UNLIMITED_WHITELIST_SIZE},
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+const MAX: u64 = TokenWhitelist::MAX_ENTRIES;
+
+/// Every size a path reads, with what init makes of it: the entries its account is sized for,
+/// or the error refusing it
+fn sizes() -> Vec<(u64, Result<u64, TokenWhitelistError>)> {
+    vec![
+        (0, Err(TokenWhitelistError::ZeroWhitelistSize)),
+        (1, Ok(1)),
+        (MAX, Ok(MAX)),
+        (MAX + 1, Err(TokenWhitelistError::TokenWhitelistSizeExceeds)),
+        (UNLIMITED_WHITELIST_SIZE, Ok(MAX)),
+    ]
+}
+
+/// Overwrites the max_whitelist_size of the env whitelist, as a whitelist initialized before
+/// init checked it may hold
+async fn set_max_whitelist_size(env: &mut TestEnv, max_whitelist_size: u64) {
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    let mut state = TokenWhitelist::unpack_from_slice(&account.data).unwrap();
+    state.max_whitelist_size = max_whitelist_size;
+    state.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&env.whitelist, &account.into());
+}
+
+#[tokio::test]
+async fn test_init_per_size() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let owner = env.owner.insecure_clone();
+    for (max_whitelist_size, sized_entries) in sizes() {
+        let whitelist = Pubkey::new_unique();
+        let account = Account {
+            lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+            data: vec![0; WHITELIST_ACCOUNT_SPACE],
+            owner: env.program_id,
+            ..Account::default()
+        };
+        env.context.set_account(&whitelist, &account.into());
+
+        // the builder refuses what the program refuses, packed by hand the program still does
+        let built =
+            init_token_whitelist_with_units(&env.program_id, &owner.pubkey(), &whitelist, &InitWithUnits::new(max_whitelist_size));
+        assert_eq!(built.as_ref().err(), sized_entries.as_ref().err(), "size {}", max_whitelist_size);
+        let init = env.instruction(
+            TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size},
+            vec![
+                AccountMeta::new_readonly(owner.pubkey(), true),
+                AccountMeta::new(whitelist, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+        );
+        let result = env.send(&[init], &[&owner]).await;
+        assert_eq!(result, sized_entries.map(|_| ()).map_err(custom_error), "size {}", max_whitelist_size);
+    }
+}
+
+#[tokio::test]
+async fn test_required_rent_per_size() {
+    let mut env = TestEnv::start(|_| {}).await;
+    for (max_whitelist_size, sized_entries) in sizes() {
+        let required_rent = get_required_rent(&env.program_id, max_whitelist_size);
+        let simulation = env.simulate(&[required_rent], &[]).await;
+        match sized_entries {
+            Ok(entries) => {
+                let return_data = simulation.returned().unwrap();
+                let space = TokenWhitelist::space_for(entries);
+                assert_eq!(
+                    RequiredRent::try_from_slice(&return_data.data).unwrap(),
+                    RequiredRent {space: space as u64, lamports: Rent::default().minimum_balance(space)}
+                );
+            }
+            Err(error) => assert_eq!(simulation.result, Err(custom_error(error))),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_legacy_zero_size_reads_unlimited() {
+    let mut env = TestEnv::start(|_| {}).await;
+    set_max_whitelist_size(&mut env, 0).await;
+    for _ in 0..3 {
+        env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    }
+
+    let state = env.whitelist_state().await;
+    assert_eq!(state.max_whitelist_size, 0);
+    assert_eq!(state.entry_limit(), None);
+    let summary = state.summary();
+    assert_eq!(summary.utilization(), None);
+    assert!(summary.to_string().contains("entries:    3 / unlimited\n"), "{}", summary);
+
+    let inspect = inspect_state(&env.program_id, &env.whitelist, 0);
+    let simulation = env.simulate(&[inspect], &[]).await;
+    simulation.result.unwrap();
+    let logs = simulation.logs;
+    let header = format!("Program log: whitelist {}: owner {}, max size unlimited", env.whitelist, env.owner.pubkey());
+    assert!(logs.contains(&header), "{:?}", logs);
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn test_client_paths_per_size() {
+    use solr_token_whitelist::{
+        client::{
+            check_instructions,
+            planner::{plan_batches, BatchLimits, PlanWarning},
+            PreflightOpts,
+        },
+        instruction::add_to_whitelist,
+    };
+    use std::collections::BTreeMap;
+
+    let (program_id, owner, whitelist) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    // a whitelist of one entry, planned one more or not: 0 is read as unlimited. The preflight,
+    // like the program, bounds entries by the room of the map alone and takes it at every size
+    for (max_whitelist_size, takes_more) in [(0, true), (1, false), (MAX, true), (UNLIMITED_WHITELIST_SIZE, true)] {
+        let mut state = TokenWhitelist {
+            is_initialized: true,
+            init_pubkey: owner,
+            max_whitelist_size,
+            ..TokenWhitelist::default()
+        };
+        state.add_keypair(&Pubkey::new_unique().to_string(), &100);
+
+        let add = add_to_whitelist(&program_id, &owner, &whitelist, &Pubkey::new_unique(), 100);
+        let mut whitelists = BTreeMap::from([(whitelist, state.clone())]);
+        let checked = check_instructions(&mut whitelists, &[add], &PreflightOpts::new(program_id));
+        assert_eq!(checked, Ok(()), "size {}", max_whitelist_size);
+
+        let additions = [(Pubkey::new_unique(), 100), (Pubkey::new_unique(), 200)];
+        let plan = plan_batches(&state, &additions, &BatchLimits::new(program_id, owner, whitelist)).unwrap();
+        let no_capacity: Vec<_> = additions.iter().map(|(wallet, _)| (*wallet, PlanWarning::NoCapacity)).collect();
+        assert_eq!(plan.warnings, if takes_more { vec![] } else { no_capacity }, "size {}", max_whitelist_size);
+    }
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, transaction::TransactionError};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::assert_membership_across,
+    state::TokenWhitelist,
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// Subjects by membership, with the whitelist of the env as the first whitelist
+struct Subjects {
+    in_first: Pubkey,
+    in_second: Pubkey,
+    in_both: Pubkey,
+    in_neither: Pubkey,
+}
+
+async fn setup() -> (TestEnv, Pubkey, Subjects) {
+    let mut env = TestEnv::start(|_| {}).await;
+    let subjects = Subjects {
+        in_first: Pubkey::new_unique(),
+        in_second: Pubkey::new_unique(),
+        in_both: Pubkey::new_unique(),
+        in_neither: Pubkey::new_unique(),
+    };
+    env.add_to_whitelist(&subjects.in_first, 100).await.unwrap();
+    env.add_to_whitelist(&subjects.in_both, 100).await.unwrap();
+
+    let second = Pubkey::new_unique();
+    let state = TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: Pubkey::new_unique(),
+        max_whitelist_size: 50,
+        whitelist_map: vec![
+            (subjects.in_second.to_string(), 0),
+            (subjects.in_both.to_string(), 0),
+        ].into_iter().collect(),
+        ..TokenWhitelist::default()
+    };
+    let program_id = env.program_id;
+    set_whitelist(&mut env, second, program_id, &state);
+    (env, second, subjects)
+}
+
+fn set_whitelist(env: &mut TestEnv, pubkey: Pubkey, owner: Pubkey, state: &TokenWhitelist) {
+    let mut data = vec![0; WHITELIST_ACCOUNT_SPACE];
+    state.pack_into_slice(&mut data).unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+        data,
+        owner,
+        ..Account::default()
+    };
+    env.context.set_account(&pubkey, &account.into());
+}
+
+async fn check(
+    env: &mut TestEnv,
+    second: &Pubkey,
+    subject: &Pubkey,
+    require_in_first: bool,
+    require_absent_in_second: bool,
+) -> Result<(), TransactionError> {
+    let instruction = assert_membership_across(
+        &env.program_id,
+        &env.whitelist,
+        second,
+        subject,
+        require_in_first,
+        require_absent_in_second,
+    );
+    env.send(&[instruction], &[]).await
+}
+
+#[tokio::test]
+async fn test_no_requirements() {
+    let (mut env, second, subjects) = setup().await;
+    for subject in [subjects.in_first, subjects.in_second, subjects.in_both, subjects.in_neither] {
+        assert_eq!(check(&mut env, &second, &subject, false, false).await, Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn test_require_in_first() {
+    let (mut env, second, subjects) = setup().await;
+    assert_eq!(check(&mut env, &second, &subjects.in_first, true, false).await, Ok(()));
+    assert_eq!(check(&mut env, &second, &subjects.in_both, true, false).await, Ok(()));
+    for subject in [subjects.in_second, subjects.in_neither] {
+        assert_eq!(
+            check(&mut env, &second, &subject, true, false).await,
+            Err(custom_error(TokenWhitelistError::NotInRequiredWhitelist))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_require_absent_in_second() {
+    let (mut env, second, subjects) = setup().await;
+    assert_eq!(check(&mut env, &second, &subjects.in_first, false, true).await, Ok(()));
+    assert_eq!(check(&mut env, &second, &subjects.in_neither, false, true).await, Ok(()));
+    for subject in [subjects.in_second, subjects.in_both] {
+        assert_eq!(
+            check(&mut env, &second, &subject, false, true).await,
+            Err(custom_error(TokenWhitelistError::InExcludedWhitelist))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_require_both() {
+    let (mut env, second, subjects) = setup().await;
+    assert_eq!(check(&mut env, &second, &subjects.in_first, true, true).await, Ok(()));
+    assert_eq!(
+        check(&mut env, &second, &subjects.in_both, true, true).await,
+        Err(custom_error(TokenWhitelistError::InExcludedWhitelist))
+    );
+    assert_eq!(
+        check(&mut env, &second, &subjects.in_second, true, true).await,
+        Err(custom_error(TokenWhitelistError::NotInRequiredWhitelist))
+    );
+    assert_eq!(
+        check(&mut env, &second, &subjects.in_neither, true, true).await,
+        Err(custom_error(TokenWhitelistError::NotInRequiredWhitelist))
+    );
+}
+
+#[tokio::test]
+async fn test_whitelists_owned_by_another_program() {
+    let (mut env, _, subjects) = setup().await;
+    let spoofed = Pubkey::new_unique();
+    set_whitelist(&mut env, spoofed, Pubkey::new_unique(), &TokenWhitelist {
+        is_initialized: true,
+        max_whitelist_size: 50,
+        ..TokenWhitelist::default()
+    });
+
+    // an empty look-alike would otherwise satisfy the absence requirement
+    assert_eq!(
+        check(&mut env, &spoofed, &subjects.in_first, true, true).await,
+        Err(TransactionError::InstructionError(0, InstructionError::IncorrectProgramId))
+    );
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{remove_from_whitelist_with_memo, TokenWhitelistInstruction, MAX_MEMO_LEN},
+};
+
+use common::{custom_error, TestEnv};
+
+/// Sends the instruction and returns its logs
+async fn send_with_logs(
+    env: &mut TestEnv,
+    data: Vec<u8>,
+    accounts: Vec<AccountMeta>,
+    signer: &Keypair,
+) -> Result<Vec<String>, TransactionError> {
+    let instruction = solana_program::instruction::Instruction::new_with_bytes(env.program_id, &data, accounts);
+    let blockhash = env.context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer, signer],
+        blockhash,
+    );
+    let result = env.context.banks_client.process_transaction_with_metadata(transaction).await.unwrap();
+    result.result?;
+    Ok(result.metadata.unwrap().log_messages)
+}
+
+async fn remove(env: &mut TestEnv, wallet: &Pubkey, data: Vec<u8>) -> Result<Vec<String>, TransactionError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(env.owner.pubkey(), true),
+        AccountMeta::new(env.whitelist, false),
+        AccountMeta::new_readonly(*wallet, false),
+    ];
+    let owner = env.owner.insecure_clone();
+    send_with_logs(env, data, accounts, &owner).await
+}
+
+fn memo_data(tag: u8, memo: &[u8]) -> Vec<u8> {
+    let mut data = vec![tag, memo.len() as u8];
+    data.extend_from_slice(memo);
+    data
+}
+
+fn memos(logs: &[String]) -> Vec<&str> {
+    logs.iter().filter_map(|log| log.strip_prefix("Program log: Memo: ")).collect()
+}
+
+async fn setup(wallet: &Pubkey) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.add_to_whitelist(wallet, 1_000).await.unwrap();
+    env
+}
+
+#[tokio::test]
+async fn test_remove_logs_memo() {
+    let wallet = Pubkey::new_unique();
+    let mut env = setup(&wallet).await;
+
+    let instruction = remove_from_whitelist_with_memo(
+        &env.program_id,
+        &env.owner.pubkey(),
+        &env.whitelist,
+        &wallet,
+        "kyc expired",
+    );
+    let logs = remove(&mut env, &wallet, instruction.data).await.unwrap();
+    assert_eq!(memos(&logs), vec!["kyc expired"]);
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_legacy_and_empty_memos() {
+    let wallet = Pubkey::new_unique();
+    let mut env = setup(&wallet).await;
+
+    let logs = remove(&mut env, &wallet, TokenWhitelistInstruction::RemoveFromWhitelist {
+        memo: None,
+        expected_nonce: 0,
+    }.pack()).await.unwrap();
+    assert!(memos(&logs).is_empty());
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+
+    env.add_to_whitelist(&wallet, 500).await.unwrap();
+    let logs = remove(&mut env, &wallet, memo_data(2, b"")).await.unwrap();
+    assert_eq!(memos(&logs), vec![""]);
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_memo_limits() {
+    let wallet = Pubkey::new_unique();
+    let mut env = setup(&wallet).await;
+
+    let too_long = "x".repeat(MAX_MEMO_LEN + 1);
+    assert_eq!(
+        remove(&mut env, &wallet, memo_data(2, too_long.as_bytes())).await,
+        Err(custom_error(TokenWhitelistError::InvalidMemo))
+    );
+    assert_eq!(
+        remove(&mut env, &wallet, memo_data(2, &[0xff, 0xfe])).await,
+        Err(custom_error(TokenWhitelistError::InvalidMemo))
+    );
+    assert_eq!(env.whitelist_state().await.whitelist_map.len(), 1);
+
+    let longest = "x".repeat(MAX_MEMO_LEN);
+    let logs = remove(&mut env, &wallet, memo_data(2, longest.as_bytes())).await.unwrap();
+    assert_eq!(memos(&logs), vec![longest.as_str()]);
+}
+
+#[tokio::test]
+async fn test_set_allocation_to_zero_logs_memo() {
+    let wallet = Keypair::new();
+    let mut env = setup(&wallet.pubkey()).await;
+
+    let accounts = vec![
+        AccountMeta::new_readonly(wallet.pubkey(), true),
+        AccountMeta::new(env.whitelist, false),
+        AccountMeta::new_readonly(wallet.pubkey(), false),
+    ];
+    assert_eq!(
+        send_with_logs(&mut env, memo_data(3, &[b'a', 0x80]), accounts.clone(), &wallet).await,
+        Err(custom_error(TokenWhitelistError::InvalidMemo))
+    );
+
+    let logs = send_with_logs(&mut env, memo_data(3, "opted out".as_bytes()), accounts, &wallet).await.unwrap();
+    assert_eq!(memos(&logs), vec!["opted out"]);
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&wallet.pubkey().to_string()), Some(&0));
+}
@@ -0,0 +1,327 @@
+#![cfg(feature = "merkle")]
+
+mod common;
+
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{
+        init_claim_bitmap, verify_merkle_membership, verify_merkle_membership_batch, MerkleClaim,
+        TokenWhitelistInstruction,
+    },
+    merkle::{HashKind, MerkleTree},
+    state::{claim_bitmap_len, ClaimBitmap, MerkleRoot},
+};
+
+use common::{custom_error, TestEnv};
+
+fn entries() -> Vec<(Pubkey, u64)> {
+    (1..=5).map(|i| (Pubkey::new_unique(), i * 1_000)).collect()
+}
+
+async fn set_root(env: &mut TestEnv, tree: &MerkleTree) -> Result<(), TransactionError> {
+    env.send_as_owner(TokenWhitelistInstruction::SetMerkleRoot {root: tree.root(), hash_kind: tree.hash_kind() as u8})
+        .await
+}
+
+async fn verify(
+    env: &mut TestEnv,
+    wallet: &Pubkey,
+    leaf_index: usize,
+    allocation: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<(), TransactionError> {
+    let instruction =
+        verify_merkle_membership(&env.program_id, &env.whitelist, wallet, leaf_index as u32, allocation, proof);
+    env.send(&[instruction], &[]).await
+}
+
+#[tokio::test]
+async fn test_verify_keccak_and_sha256_trees() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let entries = entries();
+    // parallel trees over the same entries, verified in turn by the same program
+    for kind in [HashKind::Keccak, HashKind::Sha256] {
+        let tree = MerkleTree::new(kind, &entries);
+        set_root(&mut env, &tree).await.unwrap();
+        assert_eq!(
+            env.whitelist_state().await.extension.merkle_root,
+            Some(MerkleRoot {root: tree.root(), hash_kind: kind as u8})
+        );
+
+        for (i, (wallet, allocation)) in entries.iter().enumerate() {
+            verify(&mut env, wallet, i, *allocation, tree.proof(i).unwrap()).await.unwrap();
+            assert_eq!(
+                verify(&mut env, wallet, i, allocation + 1, tree.proof(i).unwrap()).await,
+                Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_proof_for_other_hash_kind_rejected() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let entries = entries();
+    let keccak_tree = MerkleTree::new(HashKind::Keccak, &entries);
+    let sha256_tree = MerkleTree::new(HashKind::Sha256, &entries);
+    set_root(&mut env, &sha256_tree).await.unwrap();
+
+    let (wallet, allocation) = entries[2];
+    assert_eq!(
+        verify(&mut env, &wallet, 2, allocation, keccak_tree.proof(2).unwrap()).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+    verify(&mut env, &wallet, 2, allocation, sha256_tree.proof(2).unwrap()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unknown_hash_kind_rejected() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let tree = MerkleTree::new(HashKind::Sha256, &entries());
+    assert_eq!(
+        env.send_as_owner(TokenWhitelistInstruction::SetMerkleRoot {root: tree.root(), hash_kind: 2}).await,
+        Err(custom_error(TokenWhitelistError::UnknownHashKind))
+    );
+    assert_eq!(env.whitelist_state().await.extension.merkle_root, None);
+}
+
+#[tokio::test]
+async fn test_verify_without_root() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert_eq!(
+        verify(&mut env, &Pubkey::new_unique(), 0, 1_000, vec![]).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+}
+
+/// Merkle whitelist of `leaf_count` wallets, of which those at `signing` have keypairs
+struct Claimable {
+    tree: MerkleTree,
+    entries: Vec<(Pubkey, u64)>,
+    keypairs: Vec<(usize, Keypair)>,
+    bitmap: Pubkey,
+}
+
+impl Claimable {
+    /// Sets a sha256 root over the wallets and creates its claim bitmap
+    async fn start(env: &mut TestEnv, leaf_count: usize, signing: &[usize]) -> Self {
+        let keypairs: Vec<(usize, Keypair)> = signing.iter().map(|&index| (index, Keypair::new())).collect();
+        let entries: Vec<(Pubkey, u64)> = (0..leaf_count)
+            .map(|index| {
+                let wallet = keypairs.iter()
+                    .find(|(signing_index, _)| *signing_index == index)
+                    .map_or_else(Pubkey::new_unique, |(_, keypair)| keypair.pubkey());
+                (wallet, 1_000 + index as u64)
+            })
+            .collect();
+        let tree = MerkleTree::new(HashKind::Sha256, &entries);
+        set_root(env, &tree).await.unwrap();
+        let bitmap = create_bitmap(env, leaf_count as u32).await;
+        init_bitmap(env, &bitmap, leaf_count as u32).await.unwrap();
+        Claimable {tree, entries, keypairs, bitmap}
+    }
+
+    fn claim(&self, index: usize) -> MerkleClaim {
+        let (wallet, allocation) = self.entries[index];
+        MerkleClaim {wallet, leaf_index: index as u32, allocation, proof: self.tree.proof(index).unwrap()}
+    }
+
+    fn keypair(&self, index: usize) -> &Keypair {
+        &self.keypairs.iter().find(|(signing_index, _)| *signing_index == index).unwrap().1
+    }
+
+    async fn claim_batch(&self, env: &mut TestEnv, indices: &[usize]) -> Result<(), TransactionError> {
+        let items = indices.iter().map(|&index| self.claim(index)).collect();
+        let instruction = verify_merkle_membership_batch(&env.program_id, &env.whitelist, &self.bitmap, items).unwrap();
+        let signers: Vec<&Keypair> = indices.iter().map(|&index| self.keypair(index)).collect();
+        env.send(&[instruction], &signers).await
+    }
+
+    /// Claimed leaf indices as stored in the bitmap account
+    async fn claimed(&self, env: &mut TestEnv) -> Vec<u32> {
+        let data = env.context.banks_client.get_account(self.bitmap).await.unwrap().unwrap().data;
+        let leaf_count = ClaimBitmap::unpack_from_slice(&data).unwrap().leaf_count;
+        (0..leaf_count).filter(|&index| ClaimBitmap::is_claimed(&data, index).unwrap()).collect()
+    }
+}
+
+/// Zeroed, rent exempt account owned by the program, sized for `leaf_count` leaves
+async fn create_bitmap(env: &mut TestEnv, leaf_count: u32) -> Pubkey {
+    let bitmap = Pubkey::new_unique();
+    let space = claim_bitmap_len(leaf_count);
+    let account = Account {
+        lamports: Rent::default().minimum_balance(space),
+        data: vec![0; space],
+        owner: env.program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&bitmap, &account.into());
+    bitmap
+}
+
+async fn init_bitmap(env: &mut TestEnv, bitmap: &Pubkey, leaf_count: u32) -> Result<(), TransactionError> {
+    let instruction = init_claim_bitmap(&env.program_id, &env.owner.pubkey(), &env.whitelist, bitmap, leaf_count);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+#[tokio::test]
+async fn test_init_claim_bitmap() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let bitmap = create_bitmap(&mut env, 16).await;
+    // no root to track yet
+    assert_eq!(
+        init_bitmap(&mut env, &bitmap, 16).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidArgument))
+    );
+
+    let tree = MerkleTree::new(HashKind::Keccak, &entries());
+    set_root(&mut env, &tree).await.unwrap();
+    assert_eq!(
+        init_bitmap(&mut env, &bitmap, 17).await,
+        Err(custom_error(TokenWhitelistError::AccountTooSmall))
+    );
+    init_bitmap(&mut env, &bitmap, 16).await.unwrap();
+    let data = env.context.banks_client.get_account(bitmap).await.unwrap().unwrap().data;
+    assert_eq!(
+        ClaimBitmap::unpack_from_slice(&data).unwrap(),
+        ClaimBitmap {whitelist: env.whitelist, root: tree.root(), leaf_count: 16}
+    );
+    assert_eq!(init_bitmap(&mut env, &bitmap, 16).await, Err(custom_error(TokenWhitelistError::AccountNotBlank)));
+    assert_eq!(env.whitelist_state().await.extension.linked_accounts.claim_bitmap, Some(bitmap));
+}
+
+#[tokio::test]
+async fn test_claim_and_double_claim() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let claimable = Claimable::start(&mut env, 5, &[0, 2, 4]).await;
+
+    claimable.claim_batch(&mut env, &[0, 2]).await.unwrap();
+    assert_eq!(claimable.claimed(&mut env).await, vec![0, 2]);
+
+    // replayed in a later transaction, alone or beside a fresh claim
+    assert_eq!(claimable.claim_batch(&mut env, &[2]).await, Err(custom_error(TokenWhitelistError::AlreadyClaimed)));
+    assert_eq!(
+        claimable.claim_batch(&mut env, &[4, 0]).await,
+        Err(custom_error(TokenWhitelistError::AlreadyClaimed))
+    );
+    assert_eq!(claimable.claimed(&mut env).await, vec![0, 2]);
+
+    // claimed leaves still prove membership through the single verification
+    let (wallet, allocation) = claimable.entries[0];
+    verify(&mut env, &wallet, 0, allocation, claimable.tree.proof(0).unwrap()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_claim_rejects_duplicate_leaf_in_batch() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let claimable = Claimable::start(&mut env, 5, &[1, 3]).await;
+
+    assert_eq!(
+        claimable.claim_batch(&mut env, &[1, 3, 1]).await,
+        Err(custom_error(TokenWhitelistError::AlreadyClaimed))
+    );
+    assert!(claimable.claimed(&mut env).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_claim_bit_positions() {
+    let mut env = TestEnv::start(|_| {}).await;
+    // the bits either side of the first byte boundary and the last bit of a 4096 leaf tree
+    let claimable = Claimable::start(&mut env, 4096, &[7, 8, 4095]).await;
+
+    for index in [7, 8, 4095] {
+        claimable.claim_batch(&mut env, &[index]).await.unwrap();
+        assert_eq!(
+            claimable.claim_batch(&mut env, &[index]).await,
+            Err(custom_error(TokenWhitelistError::AlreadyClaimed))
+        );
+    }
+    assert_eq!(claimable.claimed(&mut env).await, vec![7, 8, 4095]);
+    let data = env.context.banks_client.get_account(claimable.bitmap).await.unwrap().unwrap().data;
+    let bits = &data[claim_bitmap_len(0)..];
+    assert_eq!((bits[0], bits[1], bits[511]), (0x80, 0x01, 0x80));
+}
+
+#[tokio::test]
+async fn test_claim_fails_atomically() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let claimable = Claimable::start(&mut env, 5, &[0, 3]).await;
+
+    let mut invalid = claimable.claim(3);
+    invalid.allocation += 1;
+    let instruction = verify_merkle_membership_batch(
+        &env.program_id,
+        &env.whitelist,
+        &claimable.bitmap,
+        vec![claimable.claim(0), invalid],
+    )
+    .unwrap();
+    let signers = [claimable.keypair(0), claimable.keypair(3)];
+    assert_eq!(env.send(&[instruction], &signers).await, Err(custom_error(TokenWhitelistError::AccountNotWhitelisted)));
+    assert!(claimable.claimed(&mut env).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_claim_needs_wallet_signatures() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let claimable = Claimable::start(&mut env, 5, &[0]).await;
+
+    let mut instruction =
+        verify_merkle_membership_batch(&env.program_id, &env.whitelist, &claimable.bitmap, vec![claimable.claim(0)])
+            .unwrap();
+    instruction.accounts[2].is_signer = false;
+    assert_eq!(
+        env.send(&[instruction], &[]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature))
+    );
+}
+
+#[tokio::test]
+async fn test_forged_claim_bitmap_refused() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let claimable = Claimable::start(&mut env, 5, &[0]).await;
+    claimable.claim_batch(&mut env, &[0]).await.unwrap();
+
+    // a fresh copy of the bitmap, the same whitelist and root with no leaf claimed
+    let forged = create_bitmap(&mut env, 5).await;
+    let mut account = env.context.banks_client.get_account(forged).await.unwrap().unwrap();
+    let header = ClaimBitmap {whitelist: env.whitelist, root: claimable.tree.root(), leaf_count: 5};
+    header.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&forged, &account.into());
+
+    let instruction =
+        verify_merkle_membership_batch(&env.program_id, &env.whitelist, &forged, vec![claimable.claim(0)]).unwrap();
+    assert_eq!(
+        env.send(&[instruction], &[claimable.keypair(0)]).await,
+        Err(custom_error(TokenWhitelistError::UnlinkedCompanionAccount))
+    );
+
+    // initializing another bitmap moves the link, claims then start over in it
+    let replacement = create_bitmap(&mut env, 5).await;
+    init_bitmap(&mut env, &replacement, 5).await.unwrap();
+    assert_eq!(
+        claimable.claim_batch(&mut env, &[0]).await,
+        Err(custom_error(TokenWhitelistError::UnlinkedCompanionAccount))
+    );
+}
+
+#[tokio::test]
+async fn test_claim_bitmap_bound_to_root() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let claimable = Claimable::start(&mut env, 5, &[0]).await;
+
+    // a new root starts a new distribution, the old bitmap no longer applies
+    set_root(&mut env, &MerkleTree::new(HashKind::Sha256, &claimable.entries[1..])).await.unwrap();
+    assert_eq!(
+        claimable.claim_batch(&mut env, &[0]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidArgument))
+    );
+}
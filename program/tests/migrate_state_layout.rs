@@ -0,0 +1,157 @@
+//! MigrateStateLayout against a ProgramData account of the upgradeable loader. The bank runs
+//! the processor as a builtin, so the ProgramData account is set by hand at the address the
+//! loader derives, in the encoding the loader writes.
+
+mod common;
+
+use solana_program::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use std::convert::TryInto;
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::migrate_state_layout,
+    state::{hash_entries_map, TokenWhitelist},
+};
+
+use common::{custom_error, TestEnv};
+
+/// Offset of the extension length prefix, right after the whitelist header
+const EXTENSION_OFFSET: usize = 1 + 32 + 8 + 4 + 5116;
+
+/// Sets the ProgramData account of `program_id` with `upgrade_authority`, None for a program
+/// made immutable
+fn set_program_data(env: &mut TestEnv, program_id: &Pubkey, upgrade_authority: Option<Pubkey>) {
+    let (program_data, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    let space = UpgradeableLoaderState::size_of_programdata_metadata();
+    let account = Account::new_data_with_space(
+        Rent::default().minimum_balance(space),
+        &UpgradeableLoaderState::ProgramData {slot: 1, upgrade_authority_address: upgrade_authority},
+        space,
+        &bpf_loader_upgradeable::id(),
+    )
+    .unwrap();
+    env.context.set_account(&program_data, &account.into());
+}
+
+async fn whitelist_data(env: &mut TestEnv) -> Vec<u8> {
+    env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap().data
+}
+
+/// A whitelist with entries as a program predating the last fifteen extension fields wrote it,
+/// its extension without zero_allocation_disabled, token_gate, mutation_nonce, compressed_tree,
+/// stats, linked_accounts, entry_attestations, require_attestation, sibling_whitelists,
+/// owner_entry_disabled, created_at_slot, creator, entry_note_commitments,
+/// registration_rate_limit and restrict_enumeration and its entries hash never computed
+async fn older_layout_env() -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    let mut state = TokenWhitelist::unpack_from_slice(&account.data).unwrap();
+    for byte in 1..=3 {
+        state.add_keypair(&Pubkey::new_from_array([byte; 32]).to_string(), &(byte as u64 * 100));
+    }
+    state.pack_into_slice(&mut account.data).unwrap();
+    let extension_len = u32::from_le_bytes(account.data[EXTENSION_OFFSET..EXTENSION_OFFSET + 4].try_into().unwrap());
+    let older_len = extension_len as usize - (1 + 1 + 8 + 1 + 24 + 2 + 4 + 1 + 4 + 1 + 8 + 32 + 4 + 16 + 1);
+    account.data[EXTENSION_OFFSET..EXTENSION_OFFSET + 4].copy_from_slice(&(older_len as u32).to_le_bytes());
+    account.data[EXTENSION_OFFSET + 4 + older_len..].fill(0);
+    env.context.set_account(&env.whitelist, &account.into());
+    env
+}
+
+async fn migrate(env: &mut TestEnv, authority: &Keypair) -> Result<(), TransactionError> {
+    let instruction = migrate_state_layout(&env.program_id, &authority.pubkey(), &env.whitelist);
+    env.send(&[instruction], &[authority]).await
+}
+
+#[tokio::test]
+async fn test_migrate_with_upgrade_authority() {
+    let mut env = older_layout_env().await;
+    let upgrade_authority = Keypair::new();
+    let program_id = env.program_id;
+    set_program_data(&mut env, &program_id, Some(upgrade_authority.pubkey()));
+    let before = env.whitelist_state().await;
+    assert_eq!(before.extension.entries_hash, [0; 32]);
+
+    migrate(&mut env, &upgrade_authority).await.unwrap();
+    let data = whitelist_data(&mut env).await;
+    let after = TokenWhitelist::unpack_from_slice(&data).unwrap();
+    assert_eq!(after.whitelist_map, before.whitelist_map);
+    assert_eq!(after.extension.entries_hash, hash_entries_map(&before.whitelist_map));
+    assert!(after.extension_is_current(&data));
+
+    // a second run finds nothing to migrate
+    migrate(&mut env, &upgrade_authority).await.unwrap();
+    assert_eq!(whitelist_data(&mut env).await, data);
+}
+
+#[tokio::test]
+async fn test_migrate_current_layout_untouched() {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    let upgrade_authority = Keypair::new();
+    let program_id = env.program_id;
+    set_program_data(&mut env, &program_id, Some(upgrade_authority.pubkey()));
+    let before = whitelist_data(&mut env).await;
+
+    migrate(&mut env, &upgrade_authority).await.unwrap();
+    assert_eq!(whitelist_data(&mut env).await, before);
+}
+
+#[tokio::test]
+async fn test_migrate_wrong_authority() {
+    let mut env = older_layout_env().await;
+    let program_id = env.program_id;
+    set_program_data(&mut env, &program_id, Some(Pubkey::new_unique()));
+    let before = whitelist_data(&mut env).await;
+
+    // the owner of the whitelist is no upgrade authority either
+    let owner = env.owner.insecure_clone();
+    for authority in [Keypair::new(), owner] {
+        assert_eq!(
+            migrate(&mut env, &authority).await,
+            Err(custom_error(TokenWhitelistError::UpgradeAuthorityMismatch))
+        );
+    }
+    assert_eq!(whitelist_data(&mut env).await, before);
+}
+
+#[tokio::test]
+async fn test_migrate_finalized_program() {
+    let mut env = older_layout_env().await;
+    let program_id = env.program_id;
+    set_program_data(&mut env, &program_id, None);
+    let before = whitelist_data(&mut env).await;
+
+    assert_eq!(
+        migrate(&mut env, &Keypair::new()).await,
+        Err(custom_error(TokenWhitelistError::UpgradeAuthorityMismatch))
+    );
+    assert_eq!(whitelist_data(&mut env).await, before);
+}
+
+#[tokio::test]
+async fn test_migrate_program_data_of_other_program() {
+    let mut env = older_layout_env().await;
+    let upgrade_authority = Keypair::new();
+    let other_program = Pubkey::new_unique();
+    set_program_data(&mut env, &other_program, Some(upgrade_authority.pubkey()));
+    let before = whitelist_data(&mut env).await;
+
+    let mut instruction = migrate_state_layout(&other_program, &upgrade_authority.pubkey(), &env.whitelist);
+    instruction.program_id = env.program_id;
+    assert_eq!(
+        env.send(&[instruction], &[&upgrade_authority]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidArgument))
+    );
+    assert_eq!(whitelist_data(&mut env).await, before);
+}
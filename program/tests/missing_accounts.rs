@@ -0,0 +1,303 @@
+mod common;
+
+use solana_program::{
+    instruction::{AccountMeta, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    instruction::{MerkleClaim, TokenWhitelistInstruction},
+    state::NO_CAMPAIGN,
+};
+
+use common::{Simulation, TestEnv};
+
+/// Every instruction taking accounts, with the roles its handler names them by, in order
+fn cases() -> Vec<(TokenWhitelistInstruction, &'static [&'static str])> {
+    let mut cases: Vec<(TokenWhitelistInstruction, &'static [&'static str])> = vec![
+        (
+            TokenWhitelistInstruction::InitTokenWhitelist {max_whitelist_size: 10},
+            &["whitelist_owner", "token_whitelist_account", "sysvar_rent_account"],
+        ),
+        (
+            TokenWhitelistInstruction::InitTokenWhitelistWithUnits {
+                max_whitelist_size: 10,
+                allocation_decimals: Some(6),
+                only_wallets: false,
+                self_zero_allowed: true,
+            },
+            &["whitelist_owner", "token_whitelist_account", "sysvar_rent_account"],
+        ),
+        (
+            TokenWhitelistInstruction::AddToWhitelist {allocation_amount: 100, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+            &["authority", "token_whitelist_account", "account_to_add"],
+        ),
+        (
+            TokenWhitelistInstruction::AddToWhitelistForRound {
+                allocation_amount: 100,
+                round: 1,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 0,
+            },
+            &["authority", "token_whitelist_account", "account_to_add"],
+        ),
+        (
+            TokenWhitelistInstruction::RemoveFromWhitelist {memo: None, expected_nonce: 0},
+            &["whitelist_owner", "token_whitelist_account", "account_to_remove"],
+        ),
+        (
+            TokenWhitelistInstruction::SetAllocationToZero {memo: None},
+            &["account_owner", "token_whitelist_account", "account_to_reset"],
+        ),
+        (
+            TokenWhitelistInstruction::CloseWhitelistAccount {},
+            &["authority_account", "token_whitelist_account", "destination_account"],
+        ),
+        (
+            TokenWhitelistInstruction::ArchiveWhitelist {},
+            &["authority_account", "token_whitelist_account", "destination_account"],
+        ),
+        (
+            TokenWhitelistInstruction::ApproveDelegate {max_adds: 1, max_allocation_per_add: 100},
+            &["whitelist_owner", "token_whitelist_account", "delegate_account"],
+        ),
+        (TokenWhitelistInstruction::RevokeDelegate {}, &["whitelist_owner", "token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::ConfigureRegistration {
+                registration_end_slot: 100,
+                registration_allocation: 100,
+                registration_deposit_lamports: 0,
+            },
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (
+            TokenWhitelistInstruction::RegisterSelf {},
+            &["account_to_register", "token_whitelist_account", "system_program"],
+        ),
+        (
+            TokenWhitelistInstruction::RefundDeposit {},
+            &["authority_account", "token_whitelist_account", "registered_account"],
+        ),
+        (
+            TokenWhitelistInstruction::RunLottery {winners: 1, seed: [0; 32]},
+            &["whitelist_owner", "token_whitelist_account", "slot_hashes_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetConsumeLimits {max_consume_per_tx: 1, min_slots_between_consumes: 1},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (TokenWhitelistInstruction::ConsumeAllocation {amount: 1}, &["account_owner", "token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::ConsumeAllocationForRound {amount: 1, round: 1},
+            &["account_owner", "token_whitelist_account"],
+        ),
+        (TokenWhitelistInstruction::ComputeEntriesHash {}, &["token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::SetAllocationBounds {min_allocation: 1, max_allocation: 10},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (TokenWhitelistInstruction::SetActiveRound {round: 1}, &["whitelist_owner", "token_whitelist_account"]),
+        (TokenWhitelistInstruction::GetAllocation {round: 0}, &["token_whitelist_account", "whitelisted_account"]),
+        (
+            TokenWhitelistInstruction::AssertMembershipAcross {require_in_first: true, require_absent_in_second: true},
+            &["first_whitelist_account", "second_whitelist_account", "subject_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetStrictTokenChecks {strict_token_checks: true},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (TokenWhitelistInstruction::SetOnlyWallets {only_wallets: true}, &["whitelist_owner", "token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: false},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation: false},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        // the mint is only read when a gate is set, after the whitelist and owner are checked
+        (
+            TokenWhitelistInstruction::SetTokenGate {
+                gating_mint: Pubkey::new_unique(),
+                gating_minimum: 1,
+                gate_owner_adds: false,
+            },
+            &["whitelist_owner", "token_whitelist_account", "mint_info"],
+        ),
+        (
+            TokenWhitelistInstruction::InitConfig {},
+            &["whitelist_owner", "token_whitelist_account", "config_account", "system_program"],
+        ),
+        (TokenWhitelistInstruction::TopUpRent {}, &["funder", "token_whitelist_account", "system_program"]),
+        (TokenWhitelistInstruction::AssertOwner {}, &["token_whitelist_account", "expected_owner"]),
+        (TokenWhitelistInstruction::RepairState {}, &["whitelist_owner", "token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::MigrateStateLayout {},
+            &["authority", "token_whitelist_account", "program_data"],
+        ),
+        (TokenWhitelistInstruction::InspectState {max_entries_to_log: 1}, &["token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::EffectiveAllocation {},
+            &["token_whitelist_account", "whitelisted_account"],
+        ),
+        (TokenWhitelistInstruction::GetWhitelistStats {}, &["token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::AddToWhitelistWithAttestation {allocation_amount: 100, attestation: [1; 32]},
+            &["authority", "token_whitelist_account", "account_to_add"],
+        ),
+        (
+            TokenWhitelistInstruction::SetAttestation {attestation: [1; 32]},
+            &["authority", "token_whitelist_account", "whitelisted_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetAttestationRequired {require_attestation: true},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (
+            TokenWhitelistInstruction::RotateWhitelistedKey {},
+            &["current_account", "token_whitelist_account", "new_account"],
+        ),
+        (TokenWhitelistInstruction::AssertNotInOther {}, &["whitelist_owner", "token_whitelist_account"]),
+        (
+            TokenWhitelistInstruction::SetOwnerEntryAllowed {allow_owner_entry: false},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetEntryNoteCommitment {commitment: [1; 32]},
+            &["whitelist_owner", "token_whitelist_account", "whitelisted_account"],
+        ),
+        (
+            TokenWhitelistInstruction::RevealEntryNote {note: b"tier 2".to_vec(), salt: [1; 16]},
+            &["token_whitelist_account", "whitelisted_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetRegistrationRateLimit {max_registrations_per_window: 1, window_slots: 1},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+        (
+            TokenWhitelistInstruction::SetEnumerationRestricted {restrict_enumeration: true},
+            &["whitelist_owner", "token_whitelist_account"],
+        ),
+    ];
+    if cfg!(feature = "pages") {
+        cases.extend(vec![
+            (
+                TokenWhitelistInstruction::ListEntries {offset: 0, limit: 1},
+                &["token_whitelist_account"] as &[&str],
+            ),
+            (
+                TokenWhitelistInstruction::ListEntriesByCampaign {campaign_id: [1; 8], offset: 0, limit: 1},
+                &["token_whitelist_account"],
+            ),
+            (TokenWhitelistInstruction::ListAttestedEntries {offset: 0, limit: 1}, &["token_whitelist_account"]),
+        ]);
+    }
+    if cfg!(feature = "merkle") {
+        cases.extend(vec![
+            (
+                TokenWhitelistInstruction::SetMerkleRoot {root: [1; 32], hash_kind: 0},
+                &["whitelist_owner", "token_whitelist_account"] as &[&str],
+            ),
+            (
+                TokenWhitelistInstruction::VerifyMerkleMembership {leaf_index: 0, allocation: 1, proof: vec![]},
+                &["token_whitelist_account", "wallet"],
+            ),
+            (
+                TokenWhitelistInstruction::VerifyMerkleMembershipBatch {
+                    items: vec![MerkleClaim {wallet: Pubkey::new_unique(), leaf_index: 0, allocation: 1, proof: vec![]}],
+                },
+                &["token_whitelist_account", "claim_bitmap_account"],
+            ),
+            (
+                TokenWhitelistInstruction::InitClaimBitmap {leaf_count: 1},
+                &["whitelist_owner", "token_whitelist_account", "claim_bitmap_account"],
+            ),
+        ]);
+    }
+    if cfg!(feature = "compression") {
+        let write_roles: &[&str] = &[
+            "whitelist_owner",
+            "token_whitelist_account",
+            "account_to_add",
+            "tree_account",
+            "tree_authority",
+            "compression_program",
+            "noop_program",
+        ];
+        cases.extend(vec![
+            (
+                TokenWhitelistInstruction::InitCompressedWhitelist {},
+                &[
+                    "whitelist_owner",
+                    "token_whitelist_account",
+                    "tree_account",
+                    "tree_authority",
+                    "compression_program",
+                    "noop_program",
+                ] as &[&str],
+            ),
+            (TokenWhitelistInstruction::AppendCompressedEntry {allocation_amount: 1}, write_roles),
+            (
+                TokenWhitelistInstruction::ReplaceCompressedEntry {
+                    leaf_index: 0,
+                    previous_allocation: 1,
+                    allocation_amount: 2,
+                    root: [1; 32],
+                },
+                &[
+                    "whitelist_owner",
+                    "token_whitelist_account",
+                    "account",
+                    "tree_account",
+                    "tree_authority",
+                    "compression_program",
+                    "noop_program",
+                ],
+            ),
+            (
+                TokenWhitelistInstruction::VerifyCompressedMembership {leaf_index: 0, allocation: 1, root: [1; 32]},
+                &["token_whitelist_account", "wallet", "tree_account", "compression_program"],
+            ),
+        ]);
+    }
+    cases
+}
+
+#[tokio::test]
+async fn test_missing_account_logs_role() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let owner = env.owner.insecure_clone();
+
+    for (instruction, roles) in cases() {
+        // the first account signs unless the handler only reads
+        let signed = !roles[0].ends_with("whitelist_account");
+        let accounts: Vec<AccountMeta> = roles
+            .iter()
+            .enumerate()
+            .map(|(index, role)| match *role {
+                _ if index == 0 && signed => AccountMeta::new_readonly(owner.pubkey(), true),
+                "token_whitelist_account" => AccountMeta::new(env.whitelist, false),
+                _ => AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            })
+            .collect();
+
+        for (provided, missing) in roles.iter().enumerate().rev() {
+            let signers: &[&Keypair] = if signed && provided > 0 { &[&owner] } else { &[] };
+            let simulated = env.instruction(instruction.clone(), accounts[..provided].to_vec());
+            let Simulation {result, logs, ..} = env.simulate(&[simulated], signers).await;
+            assert_eq!(
+                result,
+                Err(TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)),
+                "{:?} with {} accounts",
+                instruction,
+                provided
+            );
+            let expected = format!("Program log: missing account: {}", missing);
+            assert!(logs.contains(&expected), "{:?} with {} accounts logged {:?}", instruction, provided, logs);
+        }
+    }
+}
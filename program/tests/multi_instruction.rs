@@ -0,0 +1,88 @@
+mod common;
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Signer, transaction::TransactionError};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{add_to_whitelist, remove_from_whitelist},
+    state::{hash_entries_map, AllocationBounds, TokenWhitelist},
+};
+
+use common::{instruction_error, TestEnv};
+
+fn add(env: &TestEnv, wallet: &Pubkey, allocation_amount: u64) -> Instruction {
+    add_to_whitelist(&env.program_id, &env.owner.pubkey(), &env.whitelist, wallet, allocation_amount)
+}
+
+fn remove(env: &TestEnv, wallet: &Pubkey) -> Instruction {
+    remove_from_whitelist(&env.program_id, &env.owner.pubkey(), &env.whitelist, wallet)
+}
+
+/// Sends the instructions as one transaction signed by the owner
+async fn send_together(env: &mut TestEnv, instructions: &[Instruction]) -> Result<(), TransactionError> {
+    let owner = env.owner.insecure_clone();
+    env.send(instructions, &[&owner]).await
+}
+
+/// Each instruction sees the state the previous one stored, so the entries hash covers them all
+async fn assert_consistent(env: &mut TestEnv) -> TokenWhitelist {
+    let state = env.whitelist_state().await;
+    assert_eq!(state.extension.entries_hash, hash_entries_map(&state.whitelist_map));
+    state
+}
+
+#[tokio::test]
+async fn test_two_adds_in_one_transaction() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let instructions = [add(&env, &first, 100), add(&env, &second, 200)];
+    send_together(&mut env, &instructions).await.unwrap();
+    let state = assert_consistent(&mut env).await;
+    assert_eq!(state.whitelist_map.len(), 2);
+    assert_eq!(state.whitelist_map.get(&first.to_string()), Some(&100));
+    assert_eq!(state.whitelist_map.get(&second.to_string()), Some(&200));
+}
+
+#[tokio::test]
+async fn test_same_wallet_mutated_twice_in_one_transaction() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallet = Pubkey::new_unique();
+
+    // the second add replaces the allocation the first one stored
+    let instructions = [add(&env, &wallet, 100), add(&env, &wallet, 300)];
+    send_together(&mut env, &instructions).await.unwrap();
+    let state = assert_consistent(&mut env).await;
+    assert_eq!(state.whitelist_map.len(), 1);
+    assert_eq!(state.whitelist_map.get(&wallet.to_string()), Some(&300));
+
+    let other = Pubkey::new_unique();
+    let instructions = [remove(&env, &wallet), add(&env, &other, 50), add(&env, &wallet, 75)];
+    send_together(&mut env, &instructions).await.unwrap();
+    let state = assert_consistent(&mut env).await;
+    assert_eq!(state.whitelist_map.len(), 2);
+    assert_eq!(state.whitelist_map.get(&wallet.to_string()), Some(&75));
+
+    let instructions = [add(&env, &wallet, 10), remove(&env, &wallet)];
+    send_together(&mut env, &instructions).await.unwrap();
+    let state = assert_consistent(&mut env).await;
+    assert_eq!(state.whitelist_map.keys().collect::<Vec<_>>(), vec![&other.to_string()]);
+}
+
+#[tokio::test]
+async fn test_failing_second_add_discards_the_first() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mut account: Account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    let mut state = TokenWhitelist::unpack_from_slice(&account.data).unwrap();
+    state.extension.allocation_bounds = AllocationBounds {min_allocation: 10, max_allocation: 0};
+    state.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&env.whitelist, &account.into());
+
+    let instructions = [add(&env, &Pubkey::new_unique(), 100), add(&env, &Pubkey::new_unique(), 5)];
+    assert_eq!(
+        send_together(&mut env, &instructions).await,
+        Err(instruction_error(1, TokenWhitelistError::AllocationBelowMinimum))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use solana_program::{pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Signer};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{
+        add_to_whitelist_expecting, inspect_state, remove_from_whitelist, remove_from_whitelist_expecting,
+        TokenWhitelistInstruction,
+    },
+    state::TokenWhitelist,
+};
+
+use common::{custom_error, instruction_error, TestEnv};
+
+async fn nonce(env: &mut TestEnv) -> u64 {
+    env.whitelist_state().await.extension.mutation_nonce
+}
+
+#[tokio::test]
+async fn test_second_writer_rejected() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 100).await.unwrap();
+
+    // both admins read the whitelist at the same nonce
+    let read_nonce = nonce(&mut env).await;
+    assert_eq!(read_nonce, 1);
+
+    let (program_id, whitelist, owner) = (env.program_id, env.whitelist, env.owner.insecure_clone());
+    let first = add_to_whitelist_expecting(&program_id, &owner.pubkey(), &whitelist, &wallet, 500, read_nonce);
+    env.send(&[first], &[&owner]).await.unwrap();
+
+    let second = remove_from_whitelist_expecting(&program_id, &owner.pubkey(), &whitelist, &wallet, read_nonce);
+    assert_eq!(
+        env.send(&[second], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::StaleWhitelistState))
+    );
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map[&wallet.to_string()], 500);
+    assert_eq!(state.extension.mutation_nonce, read_nonce + 1);
+
+    // read again, the second admin sees the first one's change and goes ahead
+    let second = remove_from_whitelist_expecting(&program_id, &owner.pubkey(), &whitelist, &wallet, read_nonce + 1);
+    env.send(&[second], &[&owner]).await.unwrap();
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_nonces_chain_within_transaction() {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    let owner = env.owner.insecure_clone();
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let add = |wallet: &Pubkey, expected_nonce| {
+        add_to_whitelist_expecting(&env.program_id, &owner.pubkey(), &env.whitelist, wallet, 100, expected_nonce)
+    };
+    // the second add expects the nonce the first one leaves
+    let stale = [add(&first, 1), add(&second, 1)];
+    let chained = [add(&first, 1), add(&second, 2)];
+    assert_eq!(
+        env.send(&stale, &[&owner]).await,
+        Err(instruction_error(1, TokenWhitelistError::StaleWhitelistState))
+    );
+    assert_eq!(nonce(&mut env).await, 1);
+    env.send(&chained, &[&owner]).await.unwrap();
+    assert_eq!(nonce(&mut env).await, 3);
+}
+
+#[tokio::test]
+async fn test_every_write_counts() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert_eq!(nonce(&mut env).await, 0);
+
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 100).await.unwrap();
+    assert_eq!(nonce(&mut env).await, 1);
+    env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 1}).await.unwrap();
+    assert_eq!(nonce(&mut env).await, 2);
+
+    // failed and read only instructions leave it
+    assert!(env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 9}).await.is_err());
+    let inspect = inspect_state(&env.program_id, &env.whitelist, 1);
+    env.send(&[inspect], &[]).await.unwrap();
+    assert_eq!(nonce(&mut env).await, 2);
+
+    // as does removing a wallet that is not listed, which writes nothing
+    let owner = env.owner.insecure_clone();
+    let remove = remove_from_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &Pubkey::new_unique());
+    env.send(&[remove], &[&owner]).await.unwrap();
+    assert_eq!(nonce(&mut env).await, 2);
+}
+
+#[tokio::test]
+async fn test_min_size_account_untracked() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mut data = vec![0; TokenWhitelist::MIN_SPACE];
+    TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: env.owner.pubkey(),
+        max_whitelist_size: 50,
+        ..TokenWhitelist::default()
+    }
+    .pack_into_slice(&mut data)
+    .unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: env.program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&env.whitelist, &account.into());
+
+    // no room to store it, it stays 0 and clients reading 0 send no expected nonce
+    env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    assert_eq!(nonce(&mut env).await, 0);
+    let (wallet, owner) = (Pubkey::new_unique(), env.owner.insecure_clone());
+    let add = add_to_whitelist_expecting(&env.program_id, &owner.pubkey(), &env.whitelist, &wallet, 100, 1);
+    assert_eq!(
+        env.send(&[add], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::StaleWhitelistState))
+    );
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use solana_program::{program_pack::Pack, pubkey::Pubkey, rent::Rent, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{init_token_whitelist_with_units, InitWithUnits, TokenWhitelistInstruction},
+    state::TokenWhitelist,
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// Starts a whitelist that only accepts wallets
+async fn only_wallets_env() -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.send_as_owner(TokenWhitelistInstruction::SetOnlyWallets {only_wallets: true}).await.unwrap();
+    assert!(env.whitelist_state().await.extension.only_wallets);
+    env
+}
+
+/// Stores an account under a fresh keypair's key, so the key itself is on the curve
+fn set_account(env: &mut TestEnv, owner: Pubkey, data: Vec<u8>) -> Pubkey {
+    let pubkey = Keypair::new().pubkey();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        ..Account::default()
+    };
+    env.context.set_account(&pubkey, &account.into());
+    pubkey
+}
+
+fn program_address(env: &TestEnv) -> Pubkey {
+    let (address, _) = Pubkey::find_program_address(&[b"vault", env.owner.pubkey().as_ref()], &env.program_id);
+    assert!(!address.is_on_curve());
+    address
+}
+
+#[tokio::test]
+async fn test_wallets_accepted() {
+    let mut env = only_wallets_env().await;
+    let funded = set_account(&mut env, system_program::id(), vec![]);
+    let unfunded = Keypair::new().pubkey();
+
+    env.add_to_whitelist(&funded, 1_000).await.unwrap();
+    env.add_to_whitelist(&unfunded, 2_000).await.unwrap();
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.get(&funded.to_string()), Some(&1_000));
+    assert_eq!(state.whitelist_map.get(&unfunded.to_string()), Some(&2_000));
+}
+
+#[tokio::test]
+async fn test_program_address_refused() {
+    let mut env = only_wallets_env().await;
+    let address = program_address(&env);
+    assert_eq!(
+        env.add_to_whitelist(&address, 1_000).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+
+    // a program derived address the system program holds lamports for is still not a wallet
+    let account = Account {lamports: 1_000_000, owner: system_program::id(), ..Account::default()};
+    env.context.set_account(&address, &account.into());
+    assert_eq!(
+        env.add_to_whitelist(&address, 1_000).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_token_account_refused() {
+    let mut env = only_wallets_env().await;
+    let token_account = set_account(&mut env, spl_token::id(), vec![0; spl_token::state::Account::LEN]);
+    assert_eq!(
+        env.add_to_whitelist(&token_account, 1_000).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_flag_off_accepts_program_address() {
+    let mut env = only_wallets_env().await;
+    env.send_as_owner(TokenWhitelistInstruction::SetOnlyWallets {only_wallets: false}).await.unwrap();
+    let address = program_address(&env);
+    env.add_to_whitelist(&address, 1_000).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&address.to_string()), Some(&1_000));
+}
+
+#[tokio::test]
+async fn test_set_only_wallets_requires_owner() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let stranger = Keypair::new();
+    let mut instruction = env.owner_instruction(TokenWhitelistInstruction::SetOnlyWallets {only_wallets: true});
+    instruction.accounts[0].pubkey = stranger.pubkey();
+    assert_eq!(
+        env.send(&[instruction], &[&stranger]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert!(!env.whitelist_state().await.extension.only_wallets);
+}
+
+#[tokio::test]
+async fn test_init_only_wallets() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let program_id = env.program_id;
+    let whitelist = set_account(&mut env, program_id, vec![0; WHITELIST_ACCOUNT_SPACE]);
+    let init = InitWithUnits {allocation_decimals: Some(6), only_wallets: true, ..InitWithUnits::new(50)};
+    let instruction = init_token_whitelist_with_units(&program_id, &env.owner.pubkey(), &whitelist, &init).unwrap();
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    assert!(TokenWhitelist::unpack_from_slice(&account.data).unwrap().extension.only_wallets);
+}
@@ -0,0 +1,84 @@
+//! The program deployed under an address other than the official one, as a fork or a local
+//! validator would deploy it. TestEnv already picks a random program id; these tests go through
+//! the whole lifecycle with the public builders and PDA helpers only.
+
+mod common;
+
+use solana_program::{pubkey::Pubkey, rent::Rent, system_instruction, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    instruction::{add_to_whitelist, init_config, init_token_whitelist_with_units, official, with_config, InitWithUnits},
+    state::{TokenWhitelist, WhitelistConfig},
+};
+
+use common::{TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// Creates and initializes a whitelist owned by `env.owner` under `env.program_id`
+async fn create_whitelist(env: &mut TestEnv) -> Pubkey {
+    let whitelist = Keypair::new();
+    let payer = env.context.payer.pubkey();
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &whitelist.pubkey(),
+            Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+            WHITELIST_ACCOUNT_SPACE as u64,
+            &env.program_id,
+        ),
+        init_token_whitelist_with_units(&env.program_id, &env.owner.pubkey(), &whitelist.pubkey(), &InitWithUnits::new(50))
+            .unwrap(),
+    ];
+    let owner = env.owner.insecure_clone();
+    env.send(&instructions, &[&whitelist, &owner]).await.unwrap();
+    whitelist.pubkey()
+}
+
+async fn whitelist_state(env: &mut TestEnv, whitelist: &Pubkey) -> TokenWhitelist {
+    let account = env.context.banks_client.get_account(*whitelist).await.unwrap().unwrap();
+    assert_eq!(account.owner, env.program_id);
+    TokenWhitelist::unpack_from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_lifecycle_under_other_program_id() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert_ne!(env.program_id, solr_token_whitelist::id());
+    // the owner pays the rent of the config account
+    let funded = Account {lamports: 1_000_000_000, owner: system_program::id(), ..Account::default()};
+    env.context.set_account(&env.owner.pubkey(), &funded.into());
+    let whitelist = create_whitelist(&mut env).await;
+    let owner = env.owner.insecure_clone();
+
+    let (config, _) = WhitelistConfig::find_address(&env.program_id, &whitelist);
+    assert_ne!(config, official::config_address(&whitelist).0);
+    let instruction = init_config(&env.program_id, &owner.pubkey(), &whitelist);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    let account = env.context.banks_client.get_account(config).await.unwrap().unwrap();
+    assert_eq!(account.owner, env.program_id);
+
+    let wallet = Pubkey::new_unique();
+    let instruction = with_config(add_to_whitelist(&env.program_id, &owner.pubkey(), &whitelist, &wallet, 1_000), &config);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    let state = whitelist_state(&mut env, &whitelist).await;
+    assert_eq!(state.init_pubkey, owner.pubkey());
+    assert_eq!(state.whitelist_map.get(&wallet.to_string()), Some(&1_000));
+}
+
+#[tokio::test]
+async fn test_official_builders_miss_other_deployment() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let owner = env.owner.insecure_clone();
+    let before = env.whitelist_state().await;
+
+    // addressed to the official program, which this bank does not hold: the bank refuses to load
+    // the transaction, so it is simulated rather than sent and left waiting for a landing
+    let instruction = official::add_to_whitelist(&owner.pubkey(), &env.whitelist, &Pubkey::new_unique(), 1_000);
+    let simulation = env.simulate(&[instruction], &[&owner]).await;
+    assert_eq!(simulation.result, Err(TransactionError::ProgramAccountNotFound));
+    assert_eq!(env.whitelist_state().await, before);
+}
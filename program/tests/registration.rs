@@ -0,0 +1,216 @@
+mod common;
+
+use solana_program::{
+    instruction::AccountMeta,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::TokenWhitelistInstruction,
+};
+
+use common::{custom_error, TestEnv};
+
+const REGISTRATION_END_SLOT: u64 = 100;
+const REGISTRATION_ALLOCATION: u64 = 250;
+const DEPOSIT_LAMPORTS: u64 = 10_000_000;
+const REGISTRANT_LAMPORTS: u64 = 1_000_000_000;
+
+async fn setup(registrants: usize) -> (TestEnv, Vec<Keypair>) {
+    let registrants: Vec<Keypair> = (0..registrants).map(|_| Keypair::new()).collect();
+    let mut env = TestEnv::start(|program_test| {
+        for registrant in &registrants {
+            program_test.add_account(
+                registrant.pubkey(),
+                Account::new(REGISTRANT_LAMPORTS, 0, &system_program::id()),
+            );
+        }
+    })
+    .await;
+
+    env.send_as_owner(TokenWhitelistInstruction::ConfigureRegistration {
+        registration_end_slot: REGISTRATION_END_SLOT,
+        registration_allocation: REGISTRATION_ALLOCATION,
+        registration_deposit_lamports: DEPOSIT_LAMPORTS,
+    })
+    .await
+    .unwrap();
+    (env, registrants)
+}
+
+async fn register_self(env: &mut TestEnv, registrant: &Keypair) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::RegisterSelf {},
+        vec![
+            AccountMeta::new(registrant.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    env.send(&[instruction], &[registrant]).await
+}
+
+async fn refund_deposit(env: &mut TestEnv, authority: &Keypair, registrant: &Pubkey) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::RefundDeposit {},
+        vec![
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new(*registrant, false),
+        ],
+    );
+    env.send(&[instruction], &[authority]).await
+}
+
+#[tokio::test]
+async fn test_register_and_refund_after_close() {
+    let (mut env, registrants) = setup(1).await;
+    let registrant = &registrants[0];
+    let whitelist = env.whitelist;
+    let whitelist_lamports = env.lamports(&whitelist).await;
+
+    register_self(&mut env, registrant).await.unwrap();
+    assert_eq!(env.lamports(&registrant.pubkey()).await, REGISTRANT_LAMPORTS - DEPOSIT_LAMPORTS);
+    assert_eq!(env.lamports(&whitelist).await, whitelist_lamports + DEPOSIT_LAMPORTS);
+    let state = env.whitelist_state().await;
+    let key = registrant.pubkey().to_string();
+    assert_eq!(state.whitelist_map.get(&key), Some(&REGISTRATION_ALLOCATION));
+    assert_eq!(state.extension.registration_deposits.get(&key), Some(&DEPOSIT_LAMPORTS));
+
+    assert_eq!(
+        register_self(&mut env, registrant).await,
+        Err(custom_error(TokenWhitelistError::AlreadyRegistered))
+    );
+    assert_eq!(
+        refund_deposit(&mut env, registrant, &registrant.pubkey()).await,
+        Err(custom_error(TokenWhitelistError::RegistrationOpen))
+    );
+
+    env.context.warp_to_slot(REGISTRATION_END_SLOT).unwrap();
+    refund_deposit(&mut env, registrant, &registrant.pubkey()).await.unwrap();
+    assert_eq!(env.lamports(&registrant.pubkey()).await, REGISTRANT_LAMPORTS);
+    assert_eq!(env.lamports(&whitelist).await, whitelist_lamports);
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.get(&key), Some(&REGISTRATION_ALLOCATION));
+    assert!(state.extension.registration_deposits.is_empty());
+
+    assert_eq!(
+        refund_deposit(&mut env, registrant, &registrant.pubkey()).await,
+        Err(custom_error(TokenWhitelistError::NoDepositToRefund))
+    );
+    let other = Keypair::new();
+    assert_eq!(
+        register_self(&mut env, &other).await,
+        Err(custom_error(TokenWhitelistError::RegistrationClosed))
+    );
+}
+
+#[tokio::test]
+async fn test_owner_refunds_while_registration_open() {
+    let (mut env, registrants) = setup(2).await;
+    let owner = env.owner.insecure_clone();
+    let (first, second) = (&registrants[0], &registrants[1]);
+    let whitelist = env.whitelist;
+    let whitelist_lamports = env.lamports(&whitelist).await;
+
+    register_self(&mut env, first).await.unwrap();
+    register_self(&mut env, second).await.unwrap();
+    assert_eq!(env.lamports(&whitelist).await, whitelist_lamports + 2 * DEPOSIT_LAMPORTS);
+
+    // a registrant cannot claim someone else's deposit
+    assert_eq!(
+        refund_deposit(&mut env, second, &first.pubkey()).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+
+    refund_deposit(&mut env, &owner, &first.pubkey()).await.unwrap();
+    assert_eq!(env.lamports(&first.pubkey()).await, REGISTRANT_LAMPORTS);
+    assert_eq!(env.lamports(&second.pubkey()).await, REGISTRANT_LAMPORTS - DEPOSIT_LAMPORTS);
+    assert_eq!(env.lamports(&whitelist).await, whitelist_lamports + DEPOSIT_LAMPORTS);
+    assert_eq!(
+        refund_deposit(&mut env, &owner, &first.pubkey()).await,
+        Err(custom_error(TokenWhitelistError::NoDepositToRefund))
+    );
+}
+
+#[tokio::test]
+async fn test_refund_keeps_whitelist_rent_exempt() {
+    let (mut env, registrants) = setup(1).await;
+    let owner = env.owner.insecure_clone();
+    let registrant = &registrants[0];
+    let whitelist = env.whitelist;
+
+    register_self(&mut env, registrant).await.unwrap();
+
+    // drain the deposit out of band so a refund would eat into the rent exempt reserve
+    let mut account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    account.lamports -= DEPOSIT_LAMPORTS;
+    env.context.set_account(&whitelist, &AccountSharedData::from(account));
+
+    assert_eq!(
+        refund_deposit(&mut env, &owner, &registrant.pubkey()).await,
+        Err(custom_error(TokenWhitelistError::NotRentExempt))
+    );
+    assert_eq!(env.lamports(&registrant.pubkey()).await, REGISTRANT_LAMPORTS - DEPOSIT_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_close_needs_deposits_refunded() {
+    let (mut env, registrants) = setup(1).await;
+    let owner = env.owner.insecure_clone();
+    let registrant = &registrants[0];
+    let destination = Pubkey::new_unique();
+    let close = env.instruction(
+        TokenWhitelistInstruction::CloseWhitelistAccount {},
+        vec![
+            AccountMeta::new_readonly(owner.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new(destination, false),
+        ],
+    );
+
+    register_self(&mut env, registrant).await.unwrap();
+    assert_eq!(
+        env.send(std::slice::from_ref(&close), &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::DepositsOutstanding))
+    );
+    assert_eq!(env.lamports(&destination).await, 0);
+
+    refund_deposit(&mut env, &owner, &registrant.pubkey()).await.unwrap();
+    env.send(&[close], &[&owner]).await.unwrap();
+    assert_eq!(env.lamports(&registrant.pubkey()).await, REGISTRANT_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_register_again_after_removal_keeps_both_deposits() {
+    let (mut env, registrants) = setup(1).await;
+    let owner = env.owner.insecure_clone();
+    let registrant = &registrants[0];
+    let whitelist = env.whitelist;
+    let whitelist_lamports = env.lamports(&whitelist).await;
+
+    register_self(&mut env, registrant).await.unwrap();
+    let mut remove = env.owner_instruction(TokenWhitelistInstruction::RemoveFromWhitelist {
+        memo: None,
+        expected_nonce: 0,
+    });
+    remove.accounts.push(AccountMeta::new_readonly(registrant.pubkey(), false));
+    env.send(&[remove], &[&owner]).await.unwrap();
+    register_self(&mut env, registrant).await.unwrap();
+    assert_eq!(env.lamports(&registrant.pubkey()).await, REGISTRANT_LAMPORTS - 2 * DEPOSIT_LAMPORTS);
+    let state = env.whitelist_state().await;
+    let key = registrant.pubkey().to_string();
+    assert_eq!(state.extension.registration_deposits.get(&key), Some(&(2 * DEPOSIT_LAMPORTS)));
+
+    refund_deposit(&mut env, &owner, &registrant.pubkey()).await.unwrap();
+    assert_eq!(env.lamports(&registrant.pubkey()).await, REGISTRANT_LAMPORTS);
+    assert_eq!(env.lamports(&whitelist).await, whitelist_lamports);
+    assert!(env.whitelist_state().await.extension.registration_deposits.is_empty());
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use solana_program::{instruction::{AccountMeta, InstructionError}, pubkey::Pubkey, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{set_registration_rate_limit, TokenWhitelistInstruction},
+    state::RegistrationRateLimit,
+};
+
+use common::{custom_error, TestEnv};
+
+const WINDOW_SLOTS: u32 = 100;
+
+/// An env with registration open until slot 10_000 and at most `max_registrations_per_window`
+/// self registrations per window of WINDOW_SLOTS
+async fn setup(max_registrations_per_window: u16) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.send_as_owner(TokenWhitelistInstruction::ConfigureRegistration {
+        registration_end_slot: 10_000,
+        registration_allocation: 250,
+        registration_deposit_lamports: 0,
+    })
+    .await
+    .unwrap();
+    env.send_as_owner(TokenWhitelistInstruction::SetRegistrationRateLimit {
+        max_registrations_per_window,
+        window_slots: WINDOW_SLOTS,
+    })
+    .await
+    .unwrap();
+    env
+}
+
+async fn register_self(env: &mut TestEnv) -> Result<(), TransactionError> {
+    let registrant = Keypair::new();
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::RegisterSelf {},
+        vec![
+            AccountMeta::new(registrant.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    env.send(&[instruction], &[&registrant]).await
+}
+
+#[tokio::test]
+async fn test_window_caps_then_resets() {
+    let mut env = setup(2).await;
+    env.context.warp_to_slot(50).unwrap();
+    register_self(&mut env).await.unwrap();
+    register_self(&mut env).await.unwrap();
+    let limited = Err(custom_error(TokenWhitelistError::RegistrationRateLimited));
+    assert_eq!(register_self(&mut env).await, limited);
+
+    // the window started at slot 50 is not over before slot 150
+    env.context.warp_to_slot(149).unwrap();
+    assert_eq!(register_self(&mut env).await, limited);
+    let limit = env.whitelist_state().await.extension.registration_rate_limit;
+    assert_eq!((limit.window_start_slot, limit.registrations_in_window), (50, 2));
+
+    env.context.warp_to_slot(150).unwrap();
+    register_self(&mut env).await.unwrap();
+    let state = env.whitelist_state().await;
+    let limit = state.extension.registration_rate_limit;
+    assert_eq!((limit.window_start_slot, limit.registrations_in_window), (150, 1));
+    assert_eq!(state.whitelist_map.len(), 3);
+}
+
+#[tokio::test]
+async fn test_owner_adds_not_counted() {
+    let mut env = setup(1).await;
+    register_self(&mut env).await.unwrap();
+    for _ in 0..3 {
+        env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    }
+    assert_eq!(register_self(&mut env).await, Err(custom_error(TokenWhitelistError::RegistrationRateLimited)));
+    assert_eq!(env.whitelist_state().await.extension.registration_rate_limit.registrations_in_window, 1);
+}
+
+#[tokio::test]
+async fn test_setting_the_limit() {
+    let mut env = setup(1).await;
+    register_self(&mut env).await.unwrap();
+    let owner = env.owner.insecure_clone();
+    let stranger = Keypair::new();
+    let set = |signer: &Keypair, max_registrations_per_window, window_slots| {
+        set_registration_rate_limit(
+            &env.program_id,
+            &signer.pubkey(),
+            &env.whitelist,
+            max_registrations_per_window,
+            window_slots,
+        )
+    };
+    let (by_stranger, zero_window, lifted) = (set(&stranger, 5, 10), set(&owner, 5, 0), set(&owner, 0, 0));
+    assert_eq!(env.send(&[by_stranger], &[&stranger]).await, Err(custom_error(TokenWhitelistError::InvalidAuthority)));
+    assert_eq!(
+        env.send(&[zero_window], &[&owner]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidArgument))
+    );
+
+    // a zero maximum lifts the limit and clears the count
+    env.send(&[lifted], &[&owner]).await.unwrap();
+    assert_eq!(env.whitelist_state().await.extension.registration_rate_limit, RegistrationRateLimit::default());
+    for _ in 0..3 {
+        register_self(&mut env).await.unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::instruction::top_up_rent;
+
+use common::{TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+const FUNDER_LAMPORTS: u64 = 1_000_000_000;
+
+/// A system account holding FUNDER_LAMPORTS, the fee being paid by the context payer
+fn funder(env: &mut TestEnv) -> Keypair {
+    let funder = Keypair::new();
+    let account = Account {lamports: FUNDER_LAMPORTS, owner: system_program::id(), ..Account::default()};
+    env.context.set_account(&funder.pubkey(), &account.into());
+    funder
+}
+
+/// Replaces the whitelist account with one of `len` bytes holding `lamports`, its state kept
+async fn set_whitelist(env: &mut TestEnv, len: usize, lamports: u64) {
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    account.data.resize(len, 0);
+    account.lamports = lamports;
+    env.context.set_account(&env.whitelist, &account.into());
+}
+
+async fn top_up(env: &mut TestEnv, funder: &Keypair) -> Result<(), TransactionError> {
+    let instruction = top_up_rent(&env.program_id, &funder.pubkey(), &env.whitelist);
+    env.send(&[instruction], &[funder]).await
+}
+
+#[tokio::test]
+async fn test_top_up_restores_exemption() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let minimum = rent.minimum_balance(WHITELIST_ACCOUNT_SPACE);
+    set_whitelist(&mut env, WHITELIST_ACCOUNT_SPACE, minimum - 12_345).await;
+    let (whitelist, funder) = (env.whitelist, funder(&mut env));
+
+    top_up(&mut env, &funder).await.unwrap();
+    assert_eq!(env.lamports(&whitelist).await, minimum);
+    assert_eq!(env.lamports(&funder.pubkey()).await, FUNDER_LAMPORTS - 12_345);
+
+    // the whitelist still works after
+    env.add_to_whitelist(&Pubkey::new_unique(), 100).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.len(), 1);
+}
+
+#[tokio::test]
+async fn test_top_up_grown_whitelist() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let grown = WHITELIST_ACCOUNT_SPACE * 2;
+    // grown without the lamports to match its new size
+    set_whitelist(&mut env, grown, rent.minimum_balance(WHITELIST_ACCOUNT_SPACE)).await;
+    let (whitelist, funder) = (env.whitelist, funder(&mut env));
+
+    top_up(&mut env, &funder).await.unwrap();
+    assert_eq!(env.lamports(&whitelist).await, rent.minimum_balance(grown));
+    assert_eq!(
+        env.lamports(&funder.pubkey()).await,
+        FUNDER_LAMPORTS - (rent.minimum_balance(grown) - rent.minimum_balance(WHITELIST_ACCOUNT_SPACE))
+    );
+}
+
+#[tokio::test]
+async fn test_top_up_exempt_whitelist_is_noop() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let whitelist = env.whitelist;
+    let before = env.lamports(&whitelist).await;
+    let funder = funder(&mut env);
+
+    top_up(&mut env, &funder).await.unwrap();
+    assert_eq!(env.lamports(&whitelist).await, before);
+    assert_eq!(env.lamports(&funder.pubkey()).await, FUNDER_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_top_up_refuses_foreign_account() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let foreign = Pubkey::new_unique();
+    let account = Account {lamports: 1, data: vec![0; 64], owner: Pubkey::new_unique(), ..Account::default()};
+    env.context.set_account(&foreign, &account.into());
+    let funder = funder(&mut env);
+
+    let instruction = top_up_rent(&env.program_id, &funder.pubkey(), &foreign);
+    assert_eq!(
+        env.send(&[instruction], &[&funder]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::IncorrectProgramId))
+    );
+    assert_eq!(env.lamports(&funder.pubkey()).await, FUNDER_LAMPORTS);
+}
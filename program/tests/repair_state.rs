@@ -0,0 +1,198 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{repair_state, TokenWhitelistInstruction},
+    state::{hash_entries_map, RepairReport, TokenWhitelist},
+};
+
+use common::{custom_error, TestEnv};
+
+/// Offsets of the map length prefix and of the map region in the whitelist header
+const MAP_LENGTH_OFFSET: usize = 1 + 32 + 8;
+const MAP_OFFSET: usize = MAP_LENGTH_OFFSET + 4;
+
+/// A whitelist holding three entries, returned in key order
+async fn whitelist_env() -> (TestEnv, Vec<(String, u64)>) {
+    let mut env = TestEnv::start(|_| {}).await;
+    for amount in [100, 200, 300] {
+        env.add_to_whitelist(&Pubkey::new_unique(), amount).await.unwrap();
+    }
+    let entries = env.whitelist_state().await.whitelist_map.into_iter().collect();
+    (env, entries)
+}
+
+async fn whitelist_data(env: &mut TestEnv) -> Vec<u8> {
+    env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap().data
+}
+
+async fn set_whitelist_data(env: &mut TestEnv, data: Vec<u8>) {
+    let mut account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    account.data = data;
+    env.context.set_account(&env.whitelist, &account.into());
+}
+
+/// Writes `entries` as the map, in the given order and repeats included, with its length prefix
+fn write_map(data: &mut [u8], entries: &[(String, u64)]) -> usize {
+    let mut map = (entries.len() as u32).to_le_bytes().to_vec();
+    for (key, amount) in entries {
+        map.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        map.extend_from_slice(key.as_bytes());
+        map.extend_from_slice(&amount.to_le_bytes());
+    }
+    data[MAP_LENGTH_OFFSET..MAP_OFFSET].copy_from_slice(&(map.len() as u32).to_le_bytes());
+    data[MAP_OFFSET..MAP_OFFSET + map.len()].copy_from_slice(&map);
+    map.len()
+}
+
+/// Simulates RepairState for its report. The StateRepaired event goes to sol_log_data, which
+/// native program tests do not log, its encoding is tested in event.rs.
+async fn simulate_repair(env: &mut TestEnv) -> RepairReport {
+    let owner = env.owner.insecure_clone();
+    let instruction = repair_state(&env.program_id, &owner.pubkey(), &env.whitelist);
+    let return_data = env.simulate(&[instruction], &[&owner]).await.returned().unwrap();
+    RepairReport::try_from_slice(&return_data.data).unwrap()
+}
+
+/// Simulates then sends RepairState, returning its report
+async fn repair(env: &mut TestEnv) -> RepairReport {
+    let report = simulate_repair(env).await;
+    env.send_as_owner(TokenWhitelistInstruction::RepairState {}).await.unwrap();
+    report
+}
+
+#[tokio::test]
+async fn test_healthy_whitelist_untouched() {
+    let (mut env, _) = whitelist_env().await;
+    let before = whitelist_data(&mut env).await;
+
+    let report = simulate_repair(&mut env).await;
+    assert_eq!(report, RepairReport {entries_recovered: 3, entries_dropped: 0, bytes_dropped: 0});
+    env.send_as_owner(TokenWhitelistInstruction::RepairState {}).await.unwrap();
+    assert_eq!(whitelist_data(&mut env).await, before);
+}
+
+#[tokio::test]
+async fn test_bad_length_prefix() {
+    let (mut env, entries) = whitelist_env().await;
+    let healthy = whitelist_data(&mut env).await;
+    let mut data = healthy.clone();
+    data[MAP_LENGTH_OFFSET..MAP_OFFSET].copy_from_slice(&5_000u32.to_le_bytes());
+    set_whitelist_data(&mut env, data.clone()).await;
+    assert!(TokenWhitelist::unpack_from_slice(&data).is_err());
+    assert_eq!(
+        env.add_to_whitelist(&Pubkey::new_unique(), 400).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidAccountData))
+    );
+
+    let report = repair(&mut env).await;
+    assert_eq!(report, RepairReport {entries_recovered: 3, entries_dropped: 0, bytes_dropped: 0});
+    // the repair counts as a write of the whitelist, nothing else differs
+    let mut expected = TokenWhitelist::unpack_from_slice(&healthy).unwrap();
+    expected.extension.mutation_nonce += 1;
+    assert_eq!(env.whitelist_state().await, expected);
+    assert_eq!(env.whitelist_state().await.whitelist_map.into_iter().collect::<Vec<_>>(), entries);
+
+    // usable again
+    env.add_to_whitelist(&Pubkey::new_unique(), 400).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_truncated_entry() {
+    let (mut env, entries) = whitelist_env().await;
+    let mut data = whitelist_data(&mut env).await;
+    let map_len = write_map(&mut data, &entries);
+    // the last entry is cut off halfway through its key
+    let cut = map_len - 8 - entries[2].0.len() / 2;
+    data[MAP_OFFSET + cut..MAP_OFFSET + map_len].fill(0);
+    set_whitelist_data(&mut env, data).await;
+
+    let report = repair(&mut env).await;
+    assert_eq!(report.entries_recovered, 2);
+    assert_eq!(report.entries_dropped, 1);
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.into_iter().collect::<Vec<_>>(), entries[..2]);
+    assert_eq!(state.extension.entries_hash, hash_entries_map(&entries[..2].iter().cloned().collect()));
+}
+
+#[tokio::test]
+async fn test_entry_past_map_region() {
+    let (mut env, entries) = whitelist_env().await;
+    let mut data = whitelist_data(&mut env).await;
+    let map_len = write_map(&mut data, &entries);
+    // a fourth entry claiming a key longer than what is left of the region
+    data[MAP_OFFSET..MAP_OFFSET + 4].copy_from_slice(&4u32.to_le_bytes());
+    data[MAP_OFFSET + map_len..MAP_OFFSET + map_len + 4].copy_from_slice(&10_000u32.to_le_bytes());
+    data[MAP_OFFSET + map_len + 4..MAP_OFFSET + map_len + 8].copy_from_slice(b"7ktZ");
+    set_whitelist_data(&mut env, data).await;
+
+    let report = repair(&mut env).await;
+    assert_eq!(report, RepairReport {entries_recovered: 3, entries_dropped: 1, bytes_dropped: 8});
+    assert_eq!(env.whitelist_state().await.whitelist_map.into_iter().collect::<Vec<_>>(), entries);
+}
+
+#[tokio::test]
+async fn test_duplicate_key_bytes() {
+    let (mut env, entries) = whitelist_env().await;
+    let mut data = whitelist_data(&mut env).await;
+    let repeated = (entries[0].0.clone(), 999);
+    write_map(&mut data, &[entries[0].clone(), entries[1].clone(), repeated, entries[2].clone()]);
+    set_whitelist_data(&mut env, data).await;
+
+    let report = repair(&mut env).await;
+    assert_eq!(report, RepairReport {entries_recovered: 3, entries_dropped: 1, bytes_dropped: 0});
+    // the later allocation wins, as it did when the map was decoded
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.get(&entries[0].0), Some(&999));
+    assert_eq!(state.whitelist_map.len(), 3);
+    let data = whitelist_data(&mut env).await;
+    assert_eq!(data[MAP_OFFSET..MAP_OFFSET + 4], 3u32.to_le_bytes());
+}
+
+#[tokio::test]
+async fn test_key_not_a_pubkey() {
+    let (mut env, entries) = whitelist_env().await;
+    let mut data = whitelist_data(&mut env).await;
+    let malformed = [("not a pubkey".to_string(), 5), (format!("1{}", entries[1].0), 6)];
+    let mut stored = entries.clone();
+    stored.extend(malformed.iter().cloned());
+    stored.sort();
+    write_map(&mut data, &stored);
+    set_whitelist_data(&mut env, data.clone()).await;
+    // the map decodes, only the strict mode refuses it
+    assert_eq!(TokenWhitelist::unpack_from_slice(&data).unwrap().whitelist_map.len(), 5);
+    assert_eq!(
+        TokenWhitelist::unpack_from_slice_strict(&data),
+        Err(TokenWhitelistError::InvalidWhitelistKey.into())
+    );
+
+    let report = repair(&mut env).await;
+    assert_eq!(report, RepairReport {entries_recovered: 3, entries_dropped: 2, bytes_dropped: 0});
+    let data = whitelist_data(&mut env).await;
+    let state = TokenWhitelist::unpack_from_slice_strict(&data).unwrap();
+    assert_eq!(state.whitelist_map.into_iter().collect::<Vec<_>>(), entries);
+}
+
+#[tokio::test]
+async fn test_repair_requires_owner() {
+    let (mut env, _) = whitelist_env().await;
+    let mut data = whitelist_data(&mut env).await;
+    data[MAP_LENGTH_OFFSET..MAP_OFFSET].copy_from_slice(&5_000u32.to_le_bytes());
+    set_whitelist_data(&mut env, data.clone()).await;
+
+    let stranger = Keypair::new();
+    let instruction = repair_state(&env.program_id, &stranger.pubkey(), &env.whitelist);
+    assert_eq!(
+        env.send(&[instruction], &[&stranger]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert_eq!(whitelist_data(&mut env).await, data);
+}
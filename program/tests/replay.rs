@@ -0,0 +1,26 @@
+use solr_token_whitelist::replay::ReplayFixture;
+
+use std::{fs, path::Path};
+
+/// Every recorded case must leave its accounts byte for byte as recorded
+#[test]
+fn test_replay_recorded_instructions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+    let mut paths: Vec<_> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let fixture = ReplayFixture::parse(&fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|error| panic!("{}: {}", name, error));
+        let (result, after) = fixture.replay();
+        assert_eq!(result, Ok(()), "{}", name);
+        if let Some((index, mismatch)) = fixture.mismatch(&after) {
+            panic!("{}: account {} {}", name, index, mismatch);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{get_required_rent, init_token_whitelist_with_units, InitWithUnits},
+    state::{RequiredRent, TokenWhitelist},
+};
+
+use common::{custom_error, TestEnv};
+
+fn init(env: &TestEnv, whitelist: &Pubkey, max_whitelist_size: u64) -> Instruction {
+    let owner = env.owner.pubkey();
+    let init = InitWithUnits::new(max_whitelist_size);
+    init_token_whitelist_with_units(&env.program_id, &owner, whitelist, &init).unwrap()
+}
+
+#[tokio::test]
+async fn test_get_required_rent() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let required_rent = get_required_rent(&env.program_id, 50);
+    let return_data = env.simulate(&[required_rent], &[]).await.returned().unwrap();
+    assert_eq!(return_data.program_id, env.program_id);
+
+    let required_rent = RequiredRent::try_from_slice(&return_data.data).unwrap();
+    let space = TokenWhitelist::space_for(50);
+    assert_eq!(required_rent, RequiredRent {space: space as u64, lamports: Rent::default().minimum_balance(space)});
+
+    // an account created to the answer initializes
+    let (whitelist, owner) = (Keypair::new(), env.owner.insecure_clone());
+    let payer = env.context.payer.pubkey();
+    let create = system_instruction::create_account(
+        &payer,
+        &whitelist.pubkey(),
+        required_rent.lamports,
+        required_rent.space,
+        &env.program_id,
+    );
+    env.send(&[create], &[&whitelist]).await.unwrap();
+    let init = init(&env, &whitelist.pubkey(), 50);
+    env.send(&[init], &[&owner]).await.unwrap();
+
+    let above_ceiling = get_required_rent(&env.program_id, TokenWhitelist::MAX_ENTRIES + 1);
+    assert_eq!(
+        env.simulate(&[above_ceiling], &[]).await.result,
+        Err(custom_error(TokenWhitelistError::TokenWhitelistSizeExceeds))
+    );
+}
+
+#[tokio::test]
+async fn test_init_logs_shortfall() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let whitelist = Pubkey::new_unique();
+    let space = TokenWhitelist::space_for(50);
+    let required = Rent::default().minimum_balance(space);
+    let account = Account {
+        lamports: required - 1_000,
+        data: vec![0; space],
+        owner: env.program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&whitelist, &account.into());
+
+    let owner = env.owner.insecure_clone();
+    let init = init(&env, &whitelist, 50);
+    let simulation = env.simulate(&[init], &[&owner]).await;
+    assert_eq!(simulation.result, Err(custom_error(TokenWhitelistError::NotRentExempt)));
+    let logs = simulation.logs;
+    let expected = format!(
+        "Program log: token whitelist account of {} bytes must be rent exempt: {} lamports required, {} provided, 1000 short",
+        space,
+        required,
+        required - 1_000
+    );
+    assert!(logs.contains(&expected), "{:?}", logs);
+}
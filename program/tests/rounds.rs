@@ -0,0 +1,161 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{get_allocation, TokenWhitelistInstruction},
+    state::{ACTIVE_ROUND, NO_CAMPAIGN},
+};
+
+use common::{custom_error, TestEnv};
+use std::convert::TryInto;
+
+const GUARANTEED_ROUND: u8 = 0;
+const FCFS_ROUND: u8 = 1;
+
+async fn add_for_round(env: &mut TestEnv, wallet: &Pubkey, allocation_amount: u64, round: u8) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::AddToWhitelistForRound {
+            allocation_amount,
+            round,
+            campaign_id: NO_CAMPAIGN,
+            expected_nonce: 0,
+        },
+        vec![
+            AccountMeta::new_readonly(env.owner.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(*wallet, false),
+        ],
+    );
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+async fn consume(env: &mut TestEnv, wallet: &Keypair, amount: u64, round: u8) -> Result<(), TransactionError> {
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::ConsumeAllocationForRound {amount, round},
+        vec![
+            AccountMeta::new_readonly(wallet.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+        ],
+    );
+    env.send(&[instruction], &[wallet]).await
+}
+
+async fn allocation(env: &mut TestEnv, wallet: &Pubkey, round: u8) -> Result<u64, TransactionError> {
+    let instruction = get_allocation(&env.program_id, &env.whitelist, wallet, round);
+    let return_data = env.simulate(&[instruction], &[]).await.returned()?;
+    Ok(u64::from_le_bytes(return_data.data.try_into().unwrap()))
+}
+
+async fn setup(wallet: &Pubkey) -> TestEnv {
+    let mut env = TestEnv::start(|_| {}).await;
+    add_for_round(&mut env, wallet, 1_000, GUARANTEED_ROUND).await.unwrap();
+    add_for_round(&mut env, wallet, 300, FCFS_ROUND).await.unwrap();
+    env
+}
+
+#[tokio::test]
+async fn test_allocations_per_round() {
+    let wallet = Keypair::new();
+    let mut env = setup(&wallet.pubkey()).await;
+
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), GUARANTEED_ROUND).await, Ok(1_000));
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), FCFS_ROUND).await, Ok(300));
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), 3).await, Ok(0));
+    // round 0 stays in the original map
+    let state = env.whitelist_state().await;
+    assert_eq!(state.whitelist_map.get(&wallet.pubkey().to_string()), Some(&1_000));
+
+    // an entry only added for a later round is whitelisted with nothing in round 0
+    let late = Pubkey::new_unique();
+    add_for_round(&mut env, &late, 50, 2).await.unwrap();
+    assert_eq!(allocation(&mut env, &late, GUARANTEED_ROUND).await, Ok(0));
+    assert_eq!(allocation(&mut env, &late, 2).await, Ok(50));
+}
+
+#[tokio::test]
+async fn test_consume_follows_active_round() {
+    let wallet = Keypair::new();
+    let mut env = setup(&wallet.pubkey()).await;
+
+    consume(&mut env, &wallet, 400, ACTIVE_ROUND).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), GUARANTEED_ROUND).await, Ok(600));
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), ACTIVE_ROUND).await, Ok(600));
+
+    env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: FCFS_ROUND}).await.unwrap();
+    assert_eq!(
+        consume(&mut env, &wallet, 301, ACTIVE_ROUND).await,
+        Err(custom_error(TokenWhitelistError::InsufficientAllocation))
+    );
+    consume(&mut env, &wallet, 300, ACTIVE_ROUND).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), FCFS_ROUND).await, Ok(0));
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), GUARANTEED_ROUND).await, Ok(600));
+
+    // an explicit round ignores the active one
+    consume(&mut env, &wallet, 600, GUARANTEED_ROUND).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet.pubkey(), GUARANTEED_ROUND).await, Ok(0));
+
+    // legacy ConsumeAllocation keeps using round 0
+    let instruction = env.instruction(
+        TokenWhitelistInstruction::ConsumeAllocation {amount: 1},
+        vec![
+            AccountMeta::new_readonly(wallet.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+        ],
+    );
+    assert_eq!(
+        env.send(&[instruction], &[&wallet]).await,
+        Err(custom_error(TokenWhitelistError::InsufficientAllocation))
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_rounds() {
+    let wallet = Keypair::new();
+    let mut env = setup(&wallet.pubkey()).await;
+
+    assert_eq!(
+        env.send_as_owner(TokenWhitelistInstruction::SetActiveRound {round: 4}).await,
+        Err(custom_error(TokenWhitelistError::InvalidRound))
+    );
+    assert_eq!(
+        add_for_round(&mut env, &wallet.pubkey(), 1, 4).await,
+        Err(custom_error(TokenWhitelistError::InvalidRound))
+    );
+    assert_eq!(
+        consume(&mut env, &wallet, 1, 10).await,
+        Err(custom_error(TokenWhitelistError::InvalidRound))
+    );
+    assert_eq!(
+        allocation(&mut env, &wallet.pubkey(), 4).await,
+        Err(custom_error(TokenWhitelistError::InvalidRound))
+    );
+    assert_eq!(
+        allocation(&mut env, &Pubkey::new_unique(), FCFS_ROUND).await,
+        Err(custom_error(TokenWhitelistError::AccountNotWhitelisted))
+    );
+}
+
+#[tokio::test]
+async fn test_remove_clears_every_round() {
+    let wallet = Keypair::new();
+    let mut env = setup(&wallet.pubkey()).await;
+
+    let mut instruction = env.owner_instruction(TokenWhitelistInstruction::RemoveFromWhitelist {
+        memo: None,
+        expected_nonce: 0,
+    });
+    instruction.accounts.push(AccountMeta::new_readonly(wallet.pubkey(), false));
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let state = env.whitelist_state().await;
+    assert!(state.whitelist_map.is_empty());
+    assert!(state.extension.round_allocations.is_empty());
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use solana_program::{clock::Clock, instruction::{AccountMeta, Instruction}, pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{get_allocation, TokenWhitelistInstruction},
+    rules,
+    state::{AllocationBounds, ConsumeLimits, Delegate, TokenWhitelist, ACTIVE_ROUND, NO_CAMPAIGN},
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// Whitelist states the matrix runs every instruction against
+fn states(owner: &Pubkey, delegate: &Pubkey, wallet: &Pubkey) -> Vec<TokenWhitelist> {
+    let delegates = [
+        None,
+        Some(Delegate {delegate_pubkey: *delegate, remaining_adds: 1, max_allocation_per_add: 200}),
+        Some(Delegate {delegate_pubkey: *delegate, remaining_adds: 0, max_allocation_per_add: 200}),
+    ];
+    let bounds = [AllocationBounds::default(), AllocationBounds {min_allocation: 10, max_allocation: 120}];
+    // no limits, a per-instruction limit, a cooldown that has not run out
+    let limits = [
+        (ConsumeLimits::default(), None),
+        (ConsumeLimits {max_consume_per_tx: 50, min_slots_between_consumes: 0}, None),
+        (ConsumeLimits {max_consume_per_tx: 0, min_slots_between_consumes: u64::MAX}, Some(0)),
+    ];
+
+    let mut states = Vec::new();
+    for delegate in delegates.iter() {
+        for allocation_bounds in bounds.iter() {
+            for (consume_limits, last_consume_slot) in limits.iter() {
+                let mut state = TokenWhitelist {
+                    is_initialized: true,
+                    init_pubkey: *owner,
+                    max_whitelist_size: 50,
+                    ..TokenWhitelist::default()
+                };
+                state.set_round_allocation(&wallet.to_string(), 0, 100);
+                state.set_round_allocation(&wallet.to_string(), 1, 40);
+                state.extension.delegate = *delegate;
+                state.extension.allocation_bounds = *allocation_bounds;
+                state.extension.consume_limits = *consume_limits;
+                if let Some(slot) = last_consume_slot {
+                    state.extension.last_consume_slots.insert(wallet.to_string(), *slot);
+                }
+                // every other state reads round 1, every third refuses zero allocations
+                state.extension.active_round = (states.len() % 2) as u8;
+                state.extension.zero_allocation_disabled = states.len() % 3 == 0;
+                states.push(state);
+            }
+        }
+    }
+    states
+}
+
+enum Action {
+    Add {allocation_amount: u64},
+    Consume {amount: u64},
+    GetAllocation,
+}
+
+async fn set_state(env: &mut TestEnv, state: &TokenWhitelist) {
+    let mut data = vec![0; WHITELIST_ACCOUNT_SPACE];
+    state.pack_into_slice(&mut data).unwrap();
+    let lamports = Rent::default().minimum_balance(data.len());
+    let account = Account {lamports, data, owner: env.program_id, ..Account::default()};
+    env.context.set_account(&env.whitelist, &account.into());
+}
+
+fn instruction(env: &TestEnv, action: &Action, signer: &Pubkey, subject: &Pubkey) -> Instruction {
+    match *action {
+        Action::Add {allocation_amount} => env.instruction(
+            TokenWhitelistInstruction::AddToWhitelistForRound {
+                allocation_amount,
+                round: ACTIVE_ROUND,
+                campaign_id: NO_CAMPAIGN,
+                expected_nonce: 0,
+            },
+            vec![
+                AccountMeta::new_readonly(*signer, true),
+                AccountMeta::new(env.whitelist, false),
+                AccountMeta::new_readonly(*subject, false),
+            ],
+        ),
+        Action::Consume {amount} => env.instruction(
+            TokenWhitelistInstruction::ConsumeAllocationForRound {amount, round: ACTIVE_ROUND},
+            vec![AccountMeta::new_readonly(*signer, true), AccountMeta::new(env.whitelist, false)],
+        ),
+        Action::GetAllocation => get_allocation(&env.program_id, &env.whitelist, subject, ACTIVE_ROUND),
+    }
+}
+
+/// Verdict of the rules module and, when it lets the instruction through, the allocation the
+/// subject should be left with
+fn verdict(
+    state: &TokenWhitelist,
+    action: &Action,
+    signer: &Pubkey,
+    subject: &Pubkey,
+    slot: u64,
+) -> Result<Option<u64>, TokenWhitelistError> {
+    match *action {
+        Action::Add {allocation_amount} => {
+            rules::check_add(state, signer, allocation_amount, ACTIVE_ROUND).map(|_| Some(allocation_amount))
+        }
+        Action::Consume {amount} => {
+            rules::check_consume(state, signer, amount, ACTIVE_ROUND, slot).map(|(_, remaining)| Some(remaining))
+        }
+        Action::GetAllocation => rules::allocation(state, subject, ACTIVE_ROUND).map(|_| None),
+    }
+}
+
+#[tokio::test]
+async fn test_rules_match_processor() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (owner, delegate, wallet, stranger) = (env.owner.insecure_clone(), Keypair::new(), Keypair::new(), Keypair::new());
+    let actions = vec![
+        (Action::Add {allocation_amount: 5}, &owner, Pubkey::new_unique()),
+        (Action::Add {allocation_amount: 0}, &owner, Pubkey::new_unique()),
+        (Action::Add {allocation_amount: 100}, &delegate, Pubkey::new_unique()),
+        (Action::Add {allocation_amount: 150}, &delegate, Pubkey::new_unique()),
+        (Action::Add {allocation_amount: 50}, &stranger, Pubkey::new_unique()),
+        (Action::Consume {amount: 30}, &wallet, wallet.pubkey()),
+        (Action::Consume {amount: 80}, &wallet, wallet.pubkey()),
+        (Action::Consume {amount: 10}, &stranger, stranger.pubkey()),
+        (Action::GetAllocation, &stranger, wallet.pubkey()),
+        (Action::GetAllocation, &stranger, stranger.pubkey()),
+    ];
+
+    let mut outcomes = [0, 0];
+    for state in states(&owner.pubkey(), &delegate.pubkey(), &wallet.pubkey()) {
+        for (action, signer, subject) in actions.iter() {
+            set_state(&mut env, &state).await;
+            let instruction = instruction(&env, action, &signer.pubkey(), subject);
+            let signers: &[&Keypair] = if let Action::GetAllocation = action { &[] } else { &[*signer] };
+            let result = env.send(&[instruction], signers).await;
+            let slot = env.context.banks_client.get_sysvar::<Clock>().await.unwrap().slot;
+            let expected = verdict(&state, action, &signer.pubkey(), subject, slot);
+
+            assert_eq!(result, expected.map(|_| ()).map_err(custom_error), "{:?}", state.extension);
+            if let Ok(Some(allocation)) = expected {
+                let round = state.resolve_round(ACTIVE_ROUND).unwrap();
+                let landed = env.whitelist_state().await.round_allocation(&subject.to_string(), round);
+                assert_eq!(landed, Some(allocation));
+            }
+            outcomes[result.is_ok() as usize] += 1;
+        }
+    }
+    // the matrix exercises both verdicts
+    assert!(outcomes.iter().all(|count| *count > 0));
+}
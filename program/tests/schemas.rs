@@ -0,0 +1,41 @@
+#![cfg(feature = "schema")]
+
+use solr_token_whitelist::schema::{to_json, wire_schemas};
+
+use std::{fs, path::PathBuf};
+
+fn schema_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("schemas").join(format!("{}.json", name))
+}
+
+/// Fails on any drift between the types and the checked-in schemas, which regenerate with
+/// `cargo test --features schema --test schemas -- --ignored`
+#[test]
+fn test_schemas_match_checked_in() {
+    for (name, container) in wire_schemas() {
+        let checked_in = fs::read_to_string(schema_path(name)).unwrap_or_default();
+        assert!(
+            to_json(&container) == checked_in,
+            "schemas/{}.json is out of date, regenerate it:\n{}",
+            name,
+            to_json(&container)
+        );
+    }
+
+    // a type dropped from wire_schemas leaves no stale file behind
+    let mut files: Vec<String> = fs::read_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("schemas"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    let expected: Vec<String> = wire_schemas().iter().map(|(name, _)| format!("{}.json", name)).collect();
+    assert_eq!(files, expected);
+}
+
+#[test]
+#[ignore = "rewrites schemas/*.json"]
+fn regenerate_schemas() {
+    for (name, container) in wire_schemas() {
+        fs::write(schema_path(name), to_json(&container)).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    rent::Rent,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{init_token_whitelist_with_units, InitWithUnits, TokenWhitelistInstruction},
+    state::TokenWhitelist,
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// SetAllocationToZero of `wallet` on its own entry, the whitelist owner signing too if `cosigned`
+fn zero_instruction(env: &TestEnv, wallet: &Keypair, cosigned: bool) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(wallet.pubkey(), true),
+        AccountMeta::new(env.whitelist, false),
+        AccountMeta::new_readonly(wallet.pubkey(), false),
+    ];
+    if cosigned {
+        accounts.push(AccountMeta::new_readonly(env.owner.pubkey(), true));
+    }
+    env.instruction(TokenWhitelistInstruction::SetAllocationToZero {memo: None}, accounts)
+}
+
+async fn whitelisted_wallet(env: &mut TestEnv) -> Keypair {
+    let wallet = Keypair::new();
+    env.add_to_whitelist(&wallet.pubkey(), 1_000).await.unwrap();
+    wallet
+}
+
+async fn allocation(env: &mut TestEnv, wallet: &Keypair) -> Option<u64> {
+    env.whitelist_state().await.whitelist_map.get(&wallet.pubkey().to_string()).copied()
+}
+
+#[tokio::test]
+async fn test_self_zero_allowed_by_default() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert!(!env.whitelist_state().await.extension.self_zero_disabled);
+    let wallet = whitelisted_wallet(&mut env).await;
+
+    let instruction = zero_instruction(&env, &wallet, false);
+    env.send(&[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, Some(0));
+}
+
+#[tokio::test]
+async fn test_self_zero_disabled_needs_owner() {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.send_as_owner(TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: false}).await.unwrap();
+    assert!(env.whitelist_state().await.extension.self_zero_disabled);
+    let wallet = whitelisted_wallet(&mut env).await;
+
+    let instruction = zero_instruction(&env, &wallet, false);
+    assert_eq!(
+        env.send(&[instruction], &[&wallet]).await,
+        Err(custom_error(TokenWhitelistError::OwnerSignatureRequired))
+    );
+    assert_eq!(allocation(&mut env, &wallet).await, Some(1_000));
+
+    let instruction = zero_instruction(&env, &wallet, true);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&wallet, &owner]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, Some(0));
+}
+
+#[tokio::test]
+async fn test_self_zero_enabled_again() {
+    let mut env = TestEnv::start(|_| {}).await;
+    env.send_as_owner(TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: false}).await.unwrap();
+    env.send_as_owner(TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: true}).await.unwrap();
+    let wallet = whitelisted_wallet(&mut env).await;
+
+    let instruction = zero_instruction(&env, &wallet, false);
+    env.send(&[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(allocation(&mut env, &wallet).await, Some(0));
+}
+
+#[tokio::test]
+async fn test_set_self_zero_allowed_requires_owner() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let stranger = Keypair::new();
+    let mut instruction =
+        env.owner_instruction(TokenWhitelistInstruction::SetSelfZeroAllowed {self_zero_allowed: false});
+    instruction.accounts[0].pubkey = stranger.pubkey();
+    assert_eq!(
+        env.send(&[instruction], &[&stranger]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert!(!env.whitelist_state().await.extension.self_zero_disabled);
+}
+
+#[tokio::test]
+async fn test_init_self_zero_disabled() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let program_id = env.program_id;
+    let whitelist = Keypair::new().pubkey();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+        data: vec![0; WHITELIST_ACCOUNT_SPACE],
+        owner: program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&whitelist, &account.into());
+    let init = InitWithUnits {self_zero_allowed: false, ..InitWithUnits::new(50)};
+    let instruction = init_token_whitelist_with_units(&program_id, &env.owner.pubkey(), &whitelist, &init).unwrap();
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    assert!(TokenWhitelist::unpack_from_slice(&account.data).unwrap().extension.self_zero_disabled);
+}
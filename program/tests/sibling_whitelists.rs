@@ -0,0 +1,128 @@
+mod common;
+
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{add_to_whitelist, assert_not_in_other, rotate_whitelisted_key, with_sibling_whitelists},
+    state::TokenWhitelist,
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+/// The whitelist of the env, "community", and a sibling "partners" whitelist holding `listed`
+async fn setup(listed: &Pubkey) -> (TestEnv, Pubkey) {
+    let mut env = TestEnv::start(|_| {}).await;
+    let partners = Pubkey::new_unique();
+    let state = TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: env.owner.pubkey(),
+        max_whitelist_size: 50,
+        whitelist_map: vec![(listed.to_string(), 100)].into_iter().collect(),
+        ..TokenWhitelist::default()
+    };
+    let mut data = vec![0; WHITELIST_ACCOUNT_SPACE];
+    state.pack_into_slice(&mut data).unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(WHITELIST_ACCOUNT_SPACE),
+        data,
+        owner: env.program_id,
+        ..Account::default()
+    };
+    env.context.set_account(&partners, &account.into());
+    (env, partners)
+}
+
+async fn link(env: &mut TestEnv, siblings: &[Pubkey]) -> Result<(), TransactionError> {
+    let owner = env.owner.insecure_clone();
+    let instruction = assert_not_in_other(&env.program_id, &owner.pubkey(), &env.whitelist, siblings);
+    env.send(&[instruction], &[&owner]).await
+}
+
+async fn add(env: &mut TestEnv, account: &Pubkey, siblings: &[Pubkey]) -> Result<(), TransactionError> {
+    let owner = env.owner.insecure_clone();
+    let instruction = add_to_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, account, 100);
+    env.send(&[with_sibling_whitelists(instruction, siblings)], &[&owner]).await
+}
+
+#[tokio::test]
+async fn test_add_refused_when_in_sibling() {
+    let listed = Pubkey::new_unique();
+    let (mut env, partners) = setup(&listed).await;
+    // unlinked, the same wallet can be added to both
+    env.add_to_whitelist(&listed, 100).await.unwrap();
+
+    link(&mut env, &[partners]).await.unwrap();
+    assert_eq!(env.whitelist_state().await.extension.sibling_whitelists, vec![partners]);
+    let (absent, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+    add(&mut env, &absent, &[partners]).await.unwrap();
+    assert_eq!(
+        add(&mut env, &listed, &[partners]).await,
+        Err(custom_error(TokenWhitelistError::DuplicateAcrossWhitelists))
+    );
+
+    // leaving the sibling out fails instead of skipping its check
+    assert_eq!(
+        env.add_to_whitelist(&other, 100).await,
+        Err(custom_error(TokenWhitelistError::SiblingWhitelistMissing))
+    );
+
+    let state = env.whitelist_state().await;
+    assert!(state.whitelist_map.contains_key(&absent.to_string()));
+    assert!(!state.whitelist_map.contains_key(&other.to_string()));
+
+    link(&mut env, &[]).await.unwrap();
+    env.add_to_whitelist(&other, 100).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rotate_refused_when_in_sibling() {
+    let (current, listed) = (Keypair::new(), Keypair::new());
+    let (mut env, partners) = setup(&listed.pubkey()).await;
+    env.add_to_whitelist(&current.pubkey(), 100).await.unwrap();
+    link(&mut env, &[partners]).await.unwrap();
+
+    let rotate = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &listed.pubkey());
+    assert_eq!(
+        env.send(&[with_sibling_whitelists(rotate, &[partners])], &[&current, &listed]).await,
+        Err(custom_error(TokenWhitelistError::DuplicateAcrossWhitelists))
+    );
+    let rotate = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &listed.pubkey());
+    assert_eq!(
+        env.send(&[rotate], &[&current, &listed]).await,
+        Err(custom_error(TokenWhitelistError::SiblingWhitelistMissing))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.contains_key(&current.pubkey().to_string()));
+}
+
+#[tokio::test]
+async fn test_link_refused() {
+    let (mut env, partners) = setup(&Pubkey::new_unique()).await;
+    let whitelist = env.whitelist;
+    assert_eq!(
+        link(&mut env, &[whitelist]).await,
+        Err(custom_error(TokenWhitelistError::InvalidInstruction))
+    );
+    assert_eq!(
+        link(&mut env, &[partners, partners]).await,
+        Err(custom_error(TokenWhitelistError::InvalidInstruction))
+    );
+    // an account that is no whitelist of the program
+    assert_eq!(
+        link(&mut env, &[Pubkey::new_unique()]).await,
+        Err(TransactionError::InstructionError(0, InstructionError::IncorrectProgramId))
+    );
+
+    let stranger = Keypair::new();
+    let instruction = assert_not_in_other(&env.program_id, &stranger.pubkey(), &env.whitelist, &[partners]);
+    assert_eq!(
+        env.send(&[instruction], &[&stranger]).await,
+        Err(custom_error(TokenWhitelistError::InvalidAuthority))
+    );
+    assert!(env.whitelist_state().await.extension.sibling_whitelists.is_empty());
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "simulator")]
+
+use solana_program::{clock::Clock, pubkey::Pubkey, rent::Rent};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    event::WhitelistEvent,
+    instruction::TokenWhitelistInstruction,
+    simulator::{simulate_instruction, SimAccount, SimAccounts},
+    state::{TokenWhitelist, WhitelistArchive, ARCHIVE_SPACE, NO_CAMPAIGN},
+};
+
+fn setup(owner: &Pubkey) -> (Vec<u8>, SimAccounts) {
+    let mut state_bytes = vec![0; 10240];
+    TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: *owner,
+        max_whitelist_size: 10,
+        ..TokenWhitelist::default()
+    }
+    .pack_into_slice(&mut state_bytes)
+    .unwrap();
+    let program_id = Pubkey::new_unique();
+    let whitelist = Pubkey::new_unique();
+    let accounts = SimAccounts {
+        program_id,
+        whitelist,
+        accounts: vec![
+            SimAccount {pubkey: *owner, is_signer: true, ..SimAccount::default()},
+            SimAccount {
+                pubkey: whitelist,
+                is_writable: true,
+                lamports: Rent::default().minimum_balance(state_bytes.len()),
+                owner: program_id,
+                ..SimAccount::default()
+            },
+        ],
+        clock: Clock {slot: 42, ..Clock::default()},
+        rent: Rent::default(),
+    };
+    (state_bytes, accounts)
+}
+
+fn with(accounts: &SimAccounts, extra: SimAccount) -> SimAccounts {
+    let mut accounts = accounts.clone();
+    accounts.accounts.push(extra);
+    accounts
+}
+
+#[test]
+fn test_simulate_add_and_remove() {
+    let owner = Pubkey::new_unique();
+    let (mut state_bytes, accounts) = setup(&owner);
+    let wallet = SimAccount {pubkey: Pubkey::new_unique(), ..SimAccount::default()};
+    let accounts = with(&accounts, wallet.clone());
+
+    let add = TokenWhitelistInstruction::AddToWhitelist {
+        allocation_amount: 500,
+        campaign_id: NO_CAMPAIGN,
+        expected_nonce: 0,
+    }.pack();
+    let outcome = simulate_instruction(&mut state_bytes, &accounts, &add).unwrap();
+    assert_eq!(outcome.state.unwrap().whitelist_map.get(&wallet.pubkey.to_string()), Some(&500));
+    assert_eq!(outcome.logs, vec!["Instruction: AddToWhitelist"]);
+
+    let remove = TokenWhitelistInstruction::RemoveFromWhitelist {memo: None, expected_nonce: 0}.pack();
+    let outcome = simulate_instruction(&mut state_bytes, &accounts, &remove).unwrap();
+    assert!(outcome.state.unwrap().whitelist_map.is_empty());
+    assert_eq!(
+        outcome.events,
+        vec![
+            WhitelistEvent::EntryRemoved {account: wallet.pubkey, allocation: 500, memo: None},
+            WhitelistEvent::StatsUpdated {
+                whitelist: accounts.whitelist,
+                total_adds: 1,
+                total_removes: 1,
+                last_mutation_slot: 42,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_failed_simulation_changes_nothing() {
+    let (mut state_bytes, mut accounts) = setup(&Pubkey::new_unique());
+    accounts.accounts[0].pubkey = Pubkey::new_unique();
+    let accounts = with(&accounts, SimAccount {pubkey: Pubkey::new_unique(), ..SimAccount::default()});
+    let before = state_bytes.clone();
+
+    let add = TokenWhitelistInstruction::AddToWhitelist {
+        allocation_amount: 500,
+        campaign_id: NO_CAMPAIGN,
+        expected_nonce: 0,
+    }.pack();
+    assert_eq!(
+        simulate_instruction(&mut state_bytes, &accounts, &add),
+        Err(TokenWhitelistError::TokenWhitelistNotOwner.into())
+    );
+    assert_eq!(state_bytes, before);
+}
+
+#[test]
+fn test_simulate_archive_shrinks_state() {
+    let owner = Pubkey::new_unique();
+    let (mut state_bytes, accounts) = setup(&owner);
+    let destination = Pubkey::new_unique();
+    let accounts = with(&accounts, SimAccount {pubkey: destination, is_writable: true, ..SimAccount::default()});
+
+    let outcome =
+        simulate_instruction(&mut state_bytes, &accounts, &TokenWhitelistInstruction::ArchiveWhitelist {}.pack())
+            .unwrap();
+    assert_eq!(state_bytes.len(), ARCHIVE_SPACE);
+    assert!(outcome.state.is_none());
+    let archive = WhitelistArchive::unpack_from_slice(&state_bytes).unwrap();
+    assert_eq!(archive.init_pubkey, owner);
+    assert_eq!(archive.archived_slot, 42);
+    assert_eq!(
+        outcome.accounts[2].0,
+        Rent::default().minimum_balance(10240) - Rent::default().minimum_balance(ARCHIVE_SPACE)
+    );
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "simulator")]
+
+mod common;
+
+use solana_program::{
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    instruction::{add_to_whitelist, get_allocation, remove_from_whitelist_with_memo, TokenWhitelistInstruction},
+    simulator::{simulate_instruction, SimAccount, SimAccounts},
+};
+
+use common::TestEnv;
+
+/// The instruction over the accounts as the bank holds them now
+async fn sim_accounts(env: &mut TestEnv, instruction: &Instruction) -> SimAccounts {
+    let mut accounts = Vec::new();
+    for meta in &instruction.accounts {
+        let account = env.context.banks_client.get_account(meta.pubkey).await.unwrap().unwrap_or_default();
+        accounts.push(SimAccount {
+            pubkey: meta.pubkey,
+            is_signer: meta.is_signer,
+            is_writable: meta.is_writable,
+            lamports: account.lamports,
+            owner: account.owner,
+            data: account.data,
+        });
+    }
+    SimAccounts {
+        program_id: env.program_id,
+        whitelist: env.whitelist,
+        accounts,
+        clock: env.context.banks_client.get_sysvar::<Clock>().await.unwrap(),
+        rent: env.context.banks_client.get_rent().await.unwrap(),
+    }
+}
+
+/// Simulates and then sends the instruction, both must agree on the outcome
+async fn run_both(env: &mut TestEnv, state_bytes: &mut Vec<u8>, instruction: Instruction, signer: &Keypair) {
+    let accounts = sim_accounts(env, &instruction).await;
+    let simulated = simulate_instruction(state_bytes, &accounts, &instruction.data)
+        .map(|_| ())
+        .map_err(|error| TransactionError::InstructionError(0, InstructionError::from(u64::from(error))));
+    let data = instruction.data.clone();
+    assert_eq!(env.send(&[instruction], &[signer]).await, simulated, "{:?}", data);
+}
+
+#[tokio::test]
+async fn test_simulator_matches_program_test() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mut state_bytes = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap().data;
+    let (program_id, whitelist, owner) = (env.program_id, env.whitelist, env.owner.insecure_clone());
+    let (first, second) = (Keypair::new(), Keypair::new());
+    let bounds = TokenWhitelistInstruction::SetAllocationBounds {min_allocation: 10, max_allocation: 600};
+
+    let sequence = vec![
+        (add_to_whitelist(&program_id, &owner.pubkey(), &whitelist, &first.pubkey(), 500), &owner),
+        (add_to_whitelist(&program_id, &owner.pubkey(), &whitelist, &second.pubkey(), 700), &owner),
+        (add_to_whitelist(&program_id, &first.pubkey(), &whitelist, &Pubkey::new_unique(), 1), &first),
+        (remove_from_whitelist_with_memo(&program_id, &owner.pubkey(), &whitelist, &first.pubkey(), "kyc"), &owner),
+        (env.owner_instruction(bounds), &owner),
+        (add_to_whitelist(&program_id, &owner.pubkey(), &whitelist, &first.pubkey(), 900), &owner),
+    ];
+    for (instruction, signer) in sequence {
+        run_both(&mut env, &mut state_bytes, instruction, signer).await;
+    }
+    let account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    assert_eq!(account.data, state_bytes);
+
+    // return data of a read instruction
+    let read = get_allocation(&program_id, &whitelist, &second.pubkey(), 0);
+    let accounts = sim_accounts(&mut env, &read).await;
+    let outcome = simulate_instruction(&mut state_bytes, &accounts, &read.data).unwrap();
+    let return_data = env.simulate(&[read], &[]).await.returned().unwrap();
+    assert_eq!(outcome.return_data, Some(return_data.data));
+}
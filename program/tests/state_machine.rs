@@ -0,0 +1,388 @@
+use solana_program::{pubkey::Pubkey, system_program};
+use solana_sdk::signature::{Keypair, Signer};
+use solr_token_whitelist::{
+    core::{apply, Action, AuthorityContext, Churn, Effects, Store},
+    error::TokenWhitelistError,
+    state::{
+        AllocationBounds, ConsumeLimits, Delegate, Lottery, Registration, RegistrationRateLimit, TokenGate,
+        TokenWhitelist, ACTIVE_ROUND, NO_ATTESTATION, NO_CAMPAIGN,
+    },
+};
+#[cfg(feature = "merkle")]
+use solr_token_whitelist::state::MerkleRoot;
+
+const SLOT: u64 = 10;
+const NONCE: u64 = 7;
+const MEMBER_ALLOCATION: u64 = 100;
+const REGISTRATION: Registration = Registration {
+    registration_end_slot: 100,
+    registration_allocation: 50,
+    registration_deposit_lamports: 1_000,
+};
+
+/// Policy switches of the states the matrix runs every action against, one bit each
+#[derive(Clone, Copy, Debug)]
+struct Flags(u16);
+
+impl Flags {
+    const COUNT: u16 = 9;
+
+    fn delegate_exhausted(self) -> bool { self.0 & 1 != 0 }
+    fn registration_open(self) -> bool { self.0 & 1 << 1 != 0 }
+    fn require_attestation(self) -> bool { self.0 & 1 << 2 != 0 }
+    fn self_zero_disabled(self) -> bool { self.0 & 1 << 3 != 0 }
+    fn zero_allocation_disabled(self) -> bool { self.0 & 1 << 4 != 0 }
+    fn owner_entry_disabled(self) -> bool { self.0 & 1 << 5 != 0 }
+    fn only_wallets(self) -> bool { self.0 & 1 << 6 != 0 }
+    fn rate_limited(self) -> bool { self.0 & 1 << 7 != 0 }
+    fn stale_nonce(self) -> bool { self.0 & 1 << 8 != 0 }
+}
+
+/// The keys of the matrix: the owner, the delegate, a whitelisted member and a stranger to it
+struct Keys {
+    owner: Pubkey,
+    delegate: Pubkey,
+    member: Pubkey,
+    stranger: Pubkey,
+    off_curve: Pubkey, // a program address, which no wallet can sign for
+    whitelist: Pubkey,
+    program_id: Pubkey,
+}
+
+impl Keys {
+    fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        Keys {
+            owner: Keypair::new().pubkey(),
+            delegate: Keypair::new().pubkey(),
+            member: Keypair::new().pubkey(),
+            stranger: Keypair::new().pubkey(),
+            off_curve: Pubkey::find_program_address(&[b"vault"], &program_id).0,
+            whitelist: Pubkey::new_unique(),
+            program_id,
+        }
+    }
+
+    fn context(&self, signer: Pubkey, owner_signed: bool) -> AuthorityContext {
+        AuthorityContext {signer, owner_signed, whitelist: self.whitelist, program_id: self.program_id}
+    }
+}
+
+fn state(keys: &Keys, flags: Flags) -> TokenWhitelist {
+    let mut state = TokenWhitelist {
+        is_initialized: true,
+        init_pubkey: keys.owner,
+        max_whitelist_size: 50,
+        ..TokenWhitelist::default()
+    };
+    state.add_keypair(&keys.member.to_string(), &MEMBER_ALLOCATION);
+    state.extension.delegate = Some(Delegate {
+        delegate_pubkey: keys.delegate,
+        remaining_adds: if flags.delegate_exhausted() { 0 } else { 1 },
+        max_allocation_per_add: 1_000,
+    });
+    state.extension.registration = Some(Registration {
+        registration_end_slot: if flags.registration_open() { REGISTRATION.registration_end_slot } else { SLOT },
+        ..REGISTRATION
+    });
+    if flags.rate_limited() {
+        state.extension.registration_rate_limit = RegistrationRateLimit {
+            max_registrations_per_window: 1,
+            window_slots: 100,
+            window_start_slot: 0,
+            registrations_in_window: 1,
+        };
+    }
+    state.extension.require_attestation = flags.require_attestation();
+    state.extension.self_zero_disabled = flags.self_zero_disabled();
+    state.extension.zero_allocation_disabled = flags.zero_allocation_disabled();
+    state.extension.owner_entry_disabled = flags.owner_entry_disabled();
+    state.extension.only_wallets = flags.only_wallets();
+    state.extension.mutation_nonce = NONCE;
+    state
+}
+
+fn add(account: Pubkey, allocation_amount: u64, expected_nonce: u64, attestation: [u8; 32]) -> Action {
+    Action::Add {
+        account,
+        account_owner: system_program::id(),
+        allocation_amount,
+        round: ACTIVE_ROUND,
+        campaign_id: NO_CAMPAIGN,
+        expected_nonce,
+        attestation,
+    }
+}
+
+/// Every action, expecting the nonce the flags call for
+fn actions(keys: &Keys, flags: Flags) -> Vec<Action> {
+    let nonce = if flags.stale_nonce() { NONCE - 1 } else { NONCE };
+    vec![
+        add(Keypair::new().pubkey(), 200, nonce, NO_ATTESTATION),
+        add(keys.member, 0, nonce, NO_ATTESTATION),
+        add(keys.owner, 200, nonce, NO_ATTESTATION),
+        add(keys.off_curve, 200, nonce, NO_ATTESTATION),
+        add(Keypair::new().pubkey(), 200, nonce, [7; 32]),
+        Action::Remove {account: keys.member, memo: Some("left".to_string()), expected_nonce: nonce},
+        Action::Remove {account: keys.stranger, memo: None, expected_nonce: nonce},
+        Action::ZeroAllocation {account: keys.member, memo: None},
+        Action::RegisterSelf {slot: SLOT},
+        Action::ApproveDelegate(Delegate {
+            delegate_pubkey: keys.stranger,
+            remaining_adds: 5,
+            max_allocation_per_add: 10,
+        }),
+        Action::RevokeDelegate,
+        Action::ConfigureRegistration(REGISTRATION),
+        Action::SetEnumerationRestricted(true),
+        Action::SetRegistrationRateLimit(RegistrationRateLimit {
+            max_registrations_per_window: 3,
+            window_slots: 10,
+            ..RegistrationRateLimit::default()
+        }),
+        Action::RunLottery(Lottery {seed: [1; 32], slot: SLOT, slot_hash: [2; 32], winners: 0}),
+        Action::SetConsumeLimits(ConsumeLimits {max_consume_per_tx: 10, min_slots_between_consumes: 2}),
+        Action::SetAllocationBounds(AllocationBounds {min_allocation: 1, max_allocation: 500}),
+        Action::SetStrictTokenChecks(true),
+        Action::SetOnlyWallets(!flags.only_wallets()),
+        Action::SetSelfZeroAllowed(flags.self_zero_disabled()),
+        Action::SetZeroAllocationAllowed(flags.zero_allocation_disabled()),
+        Action::SetAttestation {account: keys.member, attestation: [5; 32]},
+        Action::SetAttestationRequired(!flags.require_attestation()),
+        Action::SetEntryNoteCommitment {account: keys.member, commitment: [3; 32]},
+        Action::SetSiblingWhitelists(vec![Pubkey::new_unique()]),
+        Action::SetOwnerEntryAllowed(flags.owner_entry_disabled()),
+        Action::SetTokenGate(Some(TokenGate {gating_mint: Pubkey::new_unique(), gating_minimum: 1, gate_owner_adds: true})),
+        Action::SetActiveRound(1),
+        #[cfg(feature = "merkle")]
+        Action::SetMerkleRoot(MerkleRoot {root: [4; 32], hash_kind: 0}),
+    ]
+}
+
+/// The properties every applied action holds to, whatever the authority and the policy
+fn check_effects(before: &TokenWhitelist, after: &TokenWhitelist, action: &Action, effects: &Effects) {
+    // an action may store a state it left as it was, a setting set again, but never the reverse
+    if effects.store == Store::Nothing {
+        assert_eq!(after, before, "{:?}", action);
+    }
+    if effects.entries_changed {
+        assert_eq!(effects.store, Store::State, "{:?}", action);
+    }
+    match effects.churn {
+        Some(Churn::Add) => assert!(after.whitelist_map.len() >= before.whitelist_map.len(), "{:?}", action),
+        Some(Churn::Remove) => assert_eq!(after.whitelist_map.len() + 1, before.whitelist_map.len(), "{:?}", action),
+        None => (),
+    }
+    if let Store::Removal(key) = &effects.store {
+        assert!(before.whitelist_map.contains_key(key) && !after.whitelist_map.contains_key(key));
+    }
+    match action {
+        Action::Add {..} | Action::Remove {..} | Action::ZeroAllocation {..} => {
+            assert_eq!(effects.deposit_lamports, 0, "{:?}", action);
+        }
+        Action::RegisterSelf {..} => {
+            assert_eq!(effects.deposit_lamports, REGISTRATION.registration_deposit_lamports);
+        }
+        // the draw keeps the winners only, and counts no churn
+        Action::RunLottery(lottery) => {
+            assert!(after.whitelist_map.keys().all(|key| before.whitelist_map.contains_key(key)));
+            assert!(after.whitelist_map.len() as u64 <= lottery.winners, "{:?}", action);
+            assert_eq!(after.extension.lottery, Some(*lottery));
+            assert_eq!(effects, &Effects {store: Store::State, entries_changed: true, ..Effects::default()});
+        }
+        Action::SetAttestation {..} => {
+            assert_eq!(after.whitelist_map, before.whitelist_map, "{:?}", action);
+            assert_eq!(effects.store, Store::State);
+            assert_eq!(effects.events.len(), 1);
+        }
+        // the settings leave the entries alone
+        _ => {
+            assert_eq!(after.whitelist_map, before.whitelist_map, "{:?}", action);
+            assert_eq!(effects, &Effects {store: Store::State, ..Effects::default()});
+        }
+    }
+}
+
+/// Who may do what: the verdicts the authority and the flags settle on their own, and what an
+/// action let through leaves in the state
+fn check_verdict(
+    keys: &Keys,
+    flags: Flags,
+    context: &AuthorityContext,
+    action: &Action,
+    result: &Result<Effects, TokenWhitelistError>,
+    before: &TokenWhitelist,
+    after: &TokenWhitelist,
+) {
+    let signer = context.signer;
+    let owner = signer == keys.owner;
+    match action {
+        Action::Add {account, allocation_amount, attestation, ..} => {
+            if !owner && signer != keys.delegate {
+                assert_eq!(result, &Err(TokenWhitelistError::TokenWhitelistNotOwner), "{:?}", action);
+            } else if flags.stale_nonce() {
+                assert!(result.is_err(), "{:?}", action);
+            } else if let Ok(effects) = result {
+                assert_eq!(effects.churn, Some(Churn::Add));
+                assert_eq!(after.whitelist_map.get(&account.to_string()), Some(allocation_amount));
+                let adds_left = |state: &TokenWhitelist| state.extension.delegate.unwrap().remaining_adds;
+                assert_eq!(adds_left(after) + !owner as u32, adds_left(before), "{:?}", action);
+                assert!(!flags.zero_allocation_disabled() || *allocation_amount > 0);
+                assert!(!flags.owner_entry_disabled() || *account != keys.owner);
+                assert!(!flags.only_wallets() || *account != keys.off_curve);
+                assert!(!flags.require_attestation() || after.attestation(&account.to_string()) != NO_ATTESTATION);
+                assert_eq!(effects.events.is_empty(), *attestation == NO_ATTESTATION);
+            }
+        }
+        Action::Remove {account, ..} => {
+            if !owner {
+                assert_eq!(result, &Err(TokenWhitelistError::TokenWhitelistNotOwner), "{:?}", action);
+            } else if flags.stale_nonce() {
+                assert_eq!(result, &Err(TokenWhitelistError::StaleWhitelistState), "{:?}", action);
+            } else if *account == keys.member {
+                assert_eq!(result.as_ref().unwrap().churn, Some(Churn::Remove));
+            } else {
+                assert_eq!(result, &Ok(Effects::default()), "{:?}", action);
+            }
+        }
+        Action::ZeroAllocation {account, ..} => {
+            if signer != *account {
+                assert_eq!(result, &Err(TokenWhitelistError::NotOwner), "{:?}", action);
+            } else if flags.self_zero_disabled() && !context.owner_signed {
+                assert_eq!(result, &Err(TokenWhitelistError::OwnerSignatureRequired), "{:?}", action);
+            } else {
+                assert!(result.is_ok(), "{:?}", action);
+                assert_eq!(after.whitelist_map.get(&account.to_string()), Some(&0));
+            }
+        }
+        Action::RegisterSelf {..} => {
+            let expected = if !flags.registration_open() {
+                Err(TokenWhitelistError::RegistrationClosed)
+            } else if flags.require_attestation() {
+                Err(TokenWhitelistError::AttestationRequired)
+            } else if before.whitelist_map.contains_key(&signer.to_string()) {
+                Err(TokenWhitelistError::AlreadyRegistered)
+            } else if flags.rate_limited() {
+                Err(TokenWhitelistError::RegistrationRateLimited)
+            } else if flags.owner_entry_disabled() && owner {
+                Err(TokenWhitelistError::InvalidWhitelistTarget)
+            } else {
+                Ok(())
+            };
+            assert_eq!(result.as_ref().map(|_| ()).map_err(|error| *error), expected, "{:?}", context);
+            if expected.is_ok() {
+                assert_eq!(after.whitelist_map.get(&signer.to_string()), Some(&REGISTRATION.registration_allocation));
+            }
+        }
+        Action::SetAttestation {account, attestation} => {
+            if !owner && signer != keys.delegate {
+                assert_eq!(result, &Err(TokenWhitelistError::TokenWhitelistNotOwner), "{:?}", action);
+            } else {
+                assert!(result.is_ok(), "{:?}", action);
+                assert_eq!(after.attestation(&account.to_string()), *attestation);
+            }
+        }
+        _ => {
+            let expected = if owner { Ok(()) } else { Err(TokenWhitelistError::InvalidAuthority) };
+            assert_eq!(result.as_ref().map(|_| ()).map_err(|error| *error), expected, "{:?}", action);
+        }
+    }
+}
+
+#[test]
+fn test_every_action_of_every_authority() {
+    let keys = Keys::new();
+    let authorities = [keys.owner, keys.delegate, keys.member, keys.stranger];
+
+    let mut outcomes = vec![[0; 2]; actions(&keys, Flags(0)).len()];
+    for flags in (0..1 << Flags::COUNT).map(Flags) {
+        let before = state(&keys, flags);
+        for signer in authorities.iter() {
+            // the owner signs whatever it authorizes, anyone else may have it cosign or not
+            let cosigns: &[bool] = if *signer == keys.owner { &[true] } else { &[false, true] };
+            for owner_signed in cosigns.iter() {
+                let context = keys.context(*signer, *owner_signed);
+                for (index, action) in actions(&keys, flags).iter().enumerate() {
+                    let mut after = before.clone();
+                    let result = apply(&mut after, action, &context);
+                    match &result {
+                        // a refused action leaves the state as it was
+                        Err(_) => assert_eq!(after, before, "{:?} by {:?}", action, context),
+                        Ok(effects) => check_effects(&before, &after, action, effects),
+                    }
+                    check_verdict(&keys, flags, &context, action, &result, &before, &after);
+                    outcomes[index][result.is_ok() as usize] += 1;
+                }
+            }
+        }
+    }
+    // the matrix lets every action through and refuses every one somewhere
+    assert!(outcomes.iter().flatten().all(|count| *count > 0), "{:?}", outcomes);
+}
+
+#[test]
+fn test_uninitialized_whitelist_refuses_every_action() {
+    let keys = Keys::new();
+    let before = TokenWhitelist {init_pubkey: keys.owner, ..TokenWhitelist::default()};
+    for signer in [keys.owner, keys.member, keys.stranger] {
+        for action in actions(&keys, Flags(1 << 1)) {
+            let mut after = before.clone();
+            let result = apply(&mut after, &action, &keys.context(signer, true));
+            assert_eq!(result, Err(TokenWhitelistError::TokenWhitelistNotInit), "{:?}", action);
+            assert_eq!(after, before);
+        }
+    }
+}
+
+/// Runs a seeded sequence of random actions of random authorities against one whitelist,
+/// checking what must hold after every step
+#[test]
+fn test_random_walk_keeps_invariants() {
+    let keys = Keys::new();
+    let wallets: Vec<Pubkey> = (0..6).map(|_| Keypair::new().pubkey()).collect();
+    let mut authorities = vec![keys.owner, keys.delegate, keys.stranger];
+    authorities.extend(wallets.iter());
+    let mut state = state(&keys, Flags(1 << 1));
+    state.extension.registration_rate_limit =
+        RegistrationRateLimit {max_registrations_per_window: 2, window_slots: 4, ..RegistrationRateLimit::default()};
+
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = |bound: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % bound as u64) as usize
+    };
+    for step in 0..5_000u64 {
+        let wallet = wallets[next(wallets.len())];
+        let nonce = if next(4) == 0 { next(3) as u64 } else { 0 };
+        let action = match next(9) {
+            0 | 1 => add(wallet, next(3) as u64 * 100, nonce, NO_ATTESTATION),
+            2 => Action::Remove {account: wallet, memo: None, expected_nonce: nonce},
+            3 => Action::ZeroAllocation {account: wallet, memo: None},
+            4 | 5 => Action::RegisterSelf {slot: step / 3},
+            6 => Action::ApproveDelegate(Delegate {
+                delegate_pubkey: keys.delegate,
+                remaining_adds: 3,
+                max_allocation_per_add: 200,
+            }),
+            7 => Action::RevokeDelegate,
+            _ => Action::SetEnumerationRestricted(next(2) == 0),
+        };
+        let context = keys.context(authorities[next(authorities.len())], next(2) == 0);
+
+        let before = state.clone();
+        match apply(&mut state, &action, &context) {
+            Err(_) => assert_eq!(state, before, "step {}: {:?}", step, action),
+            Ok(effects) => check_effects(&before, &state, &action, &effects),
+        }
+        let rate_limit = state.extension.registration_rate_limit;
+        assert!(rate_limit.registrations_in_window <= rate_limit.max_registrations_per_window);
+        // only the owner hands the delegate more adds
+        if let (Some(was), Some(is)) = (before.extension.delegate, state.extension.delegate) {
+            assert!(is.remaining_adds <= was.remaining_adds || matches!(action, Action::ApproveDelegate(_)));
+        }
+        assert_eq!(state.init_pubkey, keys.owner);
+        assert_eq!(state.invalid_keys().count(), 0);
+    }
+}
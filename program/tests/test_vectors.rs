@@ -0,0 +1,53 @@
+#![cfg(feature = "test-vectors")]
+
+use solr_token_whitelist::{
+    instruction::TokenWhitelistInstruction,
+    state::TokenWhitelist,
+    test_vectors::{account_vectors, described_extension, instruction_vectors, to_json},
+};
+
+use std::{collections::BTreeSet, fs, path::PathBuf};
+
+fn vectors_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_vectors.json")
+}
+
+/// Fails on any drift between the encodings and the checked-in vectors, which regenerate with
+/// `cargo test --features test-vectors --test test_vectors -- --ignored`
+#[test]
+fn test_vectors_match_checked_in() {
+    let checked_in = fs::read_to_string(vectors_path()).unwrap_or_default();
+    assert!(to_json() == checked_in, "test_vectors.json is out of date, regenerate it:\n{}", to_json());
+}
+
+#[test]
+fn test_every_tag_covered() {
+    let tags: BTreeSet<u8> = instruction_vectors().iter().map(|instruction| instruction.pack()[0]).collect();
+    let last = *tags.iter().next_back().unwrap();
+    assert_eq!(tags, (0..=last).collect());
+    // the tag after the last one is no instruction yet, so a new variant needs a vector
+    assert!(TokenWhitelistInstruction::unpack(&[last + 1]).is_err());
+}
+
+#[test]
+fn test_vectors_decode() {
+    for instruction in instruction_vectors() {
+        // builds without a subsystem refuse its tags but pack them all the same
+        if let Ok(unpacked) = TokenWhitelistInstruction::unpack(&instruction.pack()) {
+            assert_eq!(unpacked, instruction);
+        }
+    }
+    for vector in account_vectors() {
+        let bytes = vector.bytes();
+        assert_eq!(bytes.len(), vector.space);
+        assert_eq!(TokenWhitelist::unpack_from_slice(&bytes).unwrap(), vector.state, "{}", vector.name);
+        // the JSON describes every field set
+        assert_eq!(described_extension(&vector.state.extension), vector.state.extension, "{}", vector.name);
+    }
+}
+
+#[test]
+#[ignore = "rewrites test_vectors.json"]
+fn regenerate_test_vectors() {
+    fs::write(vectors_path(), to_json()).unwrap();
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "test-utils")]
+
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, ProgramTest};
+use solana_sdk::{account::ReadableAccount, signature::Signer};
+use solr_token_whitelist::{
+    state::TokenWhitelist,
+    testing::{add_entry, add_token_whitelist_program, create_and_init_whitelist},
+};
+
+#[tokio::test]
+async fn test_helpers_stand_up_whitelist() {
+    let mut program_test = ProgramTest::default();
+    add_token_whitelist_program(&mut program_test);
+    let (mut banks, payer, _) = program_test.start().await;
+
+    let whitelist = create_and_init_whitelist(&mut banks, &payer, 10).await;
+    let wallet = Pubkey::new_unique();
+    add_entry(&mut banks, &payer, &whitelist, &wallet, 500).await;
+
+    let account = banks.get_account(whitelist).await.unwrap().unwrap();
+    let state = TokenWhitelist::unpack_from_slice(account.data()).unwrap();
+    assert_eq!(state.init_pubkey, payer.pubkey());
+    assert_eq!(state.max_whitelist_size, 10);
+    assert_eq!(state.whitelist_map.get(&wallet.to_string()), Some(&500));
+}
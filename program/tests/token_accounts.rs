@@ -0,0 +1,185 @@
+mod common;
+
+use solana_program::{instruction::InstructionError, program_option::COption, pubkey::Pubkey, rent::Rent};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    signature::Signer,
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{
+        add_token_account_to_whitelist,
+        add_to_whitelist,
+        init_token_whitelist_with_units,
+        InitWithUnits,
+        TokenWhitelistInstruction,
+    },
+    state::TokenWhitelist,
+};
+use spl_token_2022::{
+    extension::{
+        non_transferable::NonTransferable,
+        permanent_delegate::PermanentDelegate,
+        ExtensionType,
+        StateWithExtensionsMut,
+    },
+    state::{Account, AccountState, Mint},
+};
+
+use common::{custom_error, TestEnv, WHITELIST_ACCOUNT_SPACE};
+
+fn token_account_data(mint: &Pubkey, is_native: COption<u64>, extensions: &[ExtensionType]) -> Vec<u8> {
+    let mut data = vec![0; ExtensionType::try_calculate_account_len::<Account>(extensions).unwrap()];
+    let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+    for extension in extensions {
+        state.init_account_extension_from_type(*extension).unwrap();
+    }
+    state.base = Account {
+        mint: *mint,
+        owner: Pubkey::new_unique(),
+        state: AccountState::Initialized,
+        is_native,
+        ..Account::default()
+    };
+    state.pack_base();
+    if !extensions.is_empty() {
+        state.init_account_type().unwrap();
+    }
+    data
+}
+
+fn mint_data(extensions: &[ExtensionType]) -> Vec<u8> {
+    let mut data = vec![0; ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap()];
+    let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+    for extension in extensions {
+        match extension {
+            ExtensionType::NonTransferable => {
+                state.init_extension::<NonTransferable>(true).unwrap();
+            }
+            ExtensionType::PermanentDelegate => {
+                state.init_extension::<PermanentDelegate>(true).unwrap();
+            }
+            _ => unreachable!(),
+        }
+    }
+    state.base = Mint {decimals: 9, is_initialized: true, ..Mint::default()};
+    state.pack_base();
+    if !extensions.is_empty() {
+        state.init_account_type().unwrap();
+    }
+    data
+}
+
+fn set_account(env: &mut TestEnv, owner: Pubkey, data: Vec<u8>) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    let account = SolanaAccount {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        ..SolanaAccount::default()
+    };
+    env.context.set_account(&pubkey, &account.into());
+    pubkey
+}
+
+/// A Token-2022 mint with the extensions and an account of it with the account extensions
+fn token_2022_account(
+    env: &mut TestEnv,
+    mint_extensions: &[ExtensionType],
+    extensions: &[ExtensionType],
+) -> (Pubkey, Pubkey) {
+    let mint = set_account(env, spl_token_2022::id(), mint_data(mint_extensions));
+    let account = set_account(env, spl_token_2022::id(), token_account_data(&mint, COption::None, extensions));
+    (account, mint)
+}
+
+async fn add(env: &mut TestEnv, account: &Pubkey, mint: Option<&Pubkey>) -> Result<(), TransactionError> {
+    let (program_id, owner, whitelist) = (env.program_id, env.owner.pubkey(), env.whitelist);
+    let instruction = match mint {
+        Some(mint) => add_token_account_to_whitelist(&program_id, &owner, &whitelist, account, mint, 100),
+        None => add_to_whitelist(&program_id, &owner, &whitelist, account, 100),
+    };
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await
+}
+
+async fn strict(env: &mut TestEnv) {
+    env.send_as_owner(TokenWhitelistInstruction::SetStrictTokenChecks {strict_token_checks: true}).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_add_legacy_wrapped_sol_account() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let data = token_account_data(&spl_token::native_mint::id(), COption::Some(2_039_280), &[]);
+    let account = set_account(&mut env, spl_token::id(), data);
+    strict(&mut env).await;
+
+    add(&mut env, &account, None).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&account.to_string()), Some(&100));
+}
+
+#[tokio::test]
+async fn test_add_token_2022_account() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (plain, _) = token_2022_account(&mut env, &[], &[]);
+    let (non_transferable, _) =
+        token_2022_account(&mut env, &[ExtensionType::NonTransferable], &[ExtensionType::NonTransferableAccount]);
+
+    add(&mut env, &plain, None).await.unwrap();
+    add(&mut env, &non_transferable, None).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.len(), 2);
+
+    let garbage = set_account(&mut env, spl_token_2022::id(), vec![1; 100]);
+    assert_eq!(
+        add(&mut env, &garbage, None).await,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidAccountData))
+    );
+}
+
+#[tokio::test]
+async fn test_strict_checks_on_token_2022_account() {
+    let mut env = TestEnv::start(|_| {}).await;
+    strict(&mut env).await;
+
+    let (plain, mint) = token_2022_account(&mut env, &[], &[]);
+    assert_eq!(
+        add(&mut env, &plain, None).await,
+        Err(TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys))
+    );
+    let (_, other_mint) = token_2022_account(&mut env, &[], &[]);
+    assert_eq!(add(&mut env, &plain, Some(&other_mint)).await, Err(custom_error(TokenWhitelistError::InvalidMint)));
+    add(&mut env, &plain, Some(&mint)).await.unwrap();
+
+    let (non_transferable, mint) =
+        token_2022_account(&mut env, &[ExtensionType::NonTransferable], &[ExtensionType::NonTransferableAccount]);
+    assert_eq!(
+        add(&mut env, &non_transferable, Some(&mint)).await,
+        Err(custom_error(TokenWhitelistError::RestrictedTokenExtension))
+    );
+    let (delegated, mint) = token_2022_account(&mut env, &[ExtensionType::PermanentDelegate], &[]);
+    assert_eq!(
+        add(&mut env, &delegated, Some(&mint)).await,
+        Err(custom_error(TokenWhitelistError::RestrictedTokenExtension))
+    );
+    assert_eq!(env.whitelist_state().await.whitelist_map.len(), 1);
+}
+
+#[tokio::test]
+async fn test_init_with_token_2022_mint() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mint = set_account(&mut env, spl_token_2022::id(), mint_data(&[ExtensionType::PermanentDelegate]));
+    let program_id = env.program_id;
+    let whitelist = set_account(&mut env, program_id, vec![0; WHITELIST_ACCOUNT_SPACE]);
+
+    let init = InitWithUnits {allocation_mint: Some(mint), ..InitWithUnits::new(50)};
+    let instruction = init_token_whitelist_with_units(&program_id, &env.owner.pubkey(), &whitelist, &init).unwrap();
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+
+    let account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    let state = TokenWhitelist::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(state.extension.allocation_decimals, 9);
+    assert_eq!(state.extension.allocation_mint, Some(mint));
+}
@@ -0,0 +1,237 @@
+mod common;
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{add_to_whitelist, rotate_whitelisted_key, set_token_gate, TokenWhitelistInstruction},
+    state::TokenGate,
+};
+use spl_token::state::{Account, AccountState, Mint};
+
+use common::{custom_error, TestEnv};
+
+const GATING_MINIMUM: u64 = 1_000;
+
+fn set_account(env: &mut TestEnv, owner: Pubkey, data: Vec<u8>) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    let account = SolanaAccount {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        ..SolanaAccount::default()
+    };
+    env.context.set_account(&pubkey, &account.into());
+    pubkey
+}
+
+fn create_mint(env: &mut TestEnv) -> Pubkey {
+    let mut data = vec![0; Mint::LEN];
+    Mint {decimals: 6, is_initialized: true, mint_authority: COption::None, ..Mint::default()}.pack_into_slice(&mut data);
+    set_account(env, spl_token::id(), data)
+}
+
+/// A token account of `mint` owned by `holder` holding `amount`
+fn create_token_account(env: &mut TestEnv, mint: &Pubkey, holder: &Pubkey, amount: u64) -> Pubkey {
+    let mut data = vec![0; Account::LEN];
+    Account {mint: *mint, owner: *holder, amount, state: AccountState::Initialized, ..Account::default()}
+        .pack_into_slice(&mut data);
+    set_account(env, spl_token::id(), data)
+}
+
+/// A whitelist gated on a fresh mint, registration open with no deposit
+async fn gated_env(gate_owner_adds: bool) -> (TestEnv, Pubkey) {
+    let mut env = TestEnv::start(|_| {}).await;
+    let mint = create_mint(&mut env);
+    let owner = env.owner.insecure_clone();
+    let instruction = set_token_gate(&env.program_id, &owner.pubkey(), &env.whitelist, &mint, GATING_MINIMUM, gate_owner_adds);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    env.send_as_owner(TokenWhitelistInstruction::ConfigureRegistration {
+        registration_end_slot: 1_000,
+        registration_allocation: 250,
+        registration_deposit_lamports: 0,
+    })
+    .await
+    .unwrap();
+    (env, mint)
+}
+
+/// A funded registrant, with a token account of `mint` holding `amount` unless None
+fn registrant(env: &mut TestEnv, mint: &Pubkey, amount: Option<u64>) -> (Keypair, Option<Pubkey>) {
+    let registrant = Keypair::new();
+    let account = SolanaAccount::new(1_000_000_000, 0, &system_program::id());
+    env.context.set_account(&registrant.pubkey(), &account.into());
+    let token_account = amount.map(|amount| create_token_account(env, mint, &registrant.pubkey(), amount));
+    (registrant, token_account)
+}
+
+fn register_instruction(env: &TestEnv, registrant: &Pubkey, token_account: Option<&Pubkey>) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*registrant, true),
+        AccountMeta::new(env.whitelist, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(token_account.map(|token_account| AccountMeta::new_readonly(*token_account, false)));
+    env.instruction(TokenWhitelistInstruction::RegisterSelf {}, accounts)
+}
+
+async fn register(env: &mut TestEnv, registrant: &Keypair, token_account: Option<&Pubkey>) -> Result<(), TransactionError> {
+    let instruction = register_instruction(env, &registrant.pubkey(), token_account);
+    env.send(&[instruction], &[registrant]).await
+}
+
+async fn is_whitelisted(env: &mut TestEnv, account: &Pubkey) -> bool {
+    env.whitelist_state().await.whitelist_map.contains_key(&account.to_string())
+}
+
+#[tokio::test]
+async fn test_set_token_gate() {
+    let (mut env, mint) = gated_env(false).await;
+    let gate = TokenGate {gating_mint: mint, gating_minimum: GATING_MINIMUM, gate_owner_adds: false};
+    assert_eq!(env.whitelist_state().await.extension.token_gate, Some(gate));
+
+    // cleared by a zero minimum, no mint passed
+    let instruction = set_token_gate(&env.program_id, &env.owner.pubkey(), &env.whitelist, &mint, 0, false);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    assert_eq!(env.whitelist_state().await.extension.token_gate, None);
+}
+
+#[tokio::test]
+async fn test_set_token_gate_needs_mint() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let owner = env.owner.insecure_clone();
+    // the mint passed differs from the one in the instruction
+    let mint = create_mint(&mut env);
+    let mut instruction = set_token_gate(&env.program_id, &owner.pubkey(), &env.whitelist, &mint, GATING_MINIMUM, false);
+    instruction.accounts[2].pubkey = create_mint(&mut env);
+    assert_eq!(env.send(&[instruction], &[&owner]).await, Err(custom_error(TokenWhitelistError::InvalidMint)));
+
+    // not a mint
+    let not_mint = set_account(&mut env, system_program::id(), vec![0; Mint::LEN]);
+    let instruction = set_token_gate(&env.program_id, &owner.pubkey(), &env.whitelist, &not_mint, GATING_MINIMUM, false);
+    assert_eq!(env.send(&[instruction], &[&owner]).await, Err(custom_error(TokenWhitelistError::InvalidMint)));
+    assert_eq!(env.whitelist_state().await.extension.token_gate, None);
+}
+
+#[tokio::test]
+async fn test_register_at_minimum() {
+    let (mut env, mint) = gated_env(false).await;
+    let (registrant, token_account) = registrant(&mut env, &mint, Some(GATING_MINIMUM));
+    register(&mut env, &registrant, token_account.as_ref()).await.unwrap();
+    assert!(is_whitelisted(&mut env, &registrant.pubkey()).await);
+}
+
+#[tokio::test]
+async fn test_register_below_minimum() {
+    let (mut env, mint) = gated_env(false).await;
+    let (registrant, token_account) = registrant(&mut env, &mint, Some(GATING_MINIMUM - 1));
+    assert_eq!(
+        register(&mut env, &registrant, token_account.as_ref()).await,
+        Err(custom_error(TokenWhitelistError::InsufficientGatingBalance))
+    );
+    assert!(!is_whitelisted(&mut env, &registrant.pubkey()).await);
+}
+
+#[tokio::test]
+async fn test_register_without_gating_account() {
+    let (mut env, mint) = gated_env(false).await;
+    let (registrant, _) = registrant(&mut env, &mint, None);
+    assert_eq!(
+        register(&mut env, &registrant, None).await,
+        Err(custom_error(TokenWhitelistError::GatingAccountMissing))
+    );
+
+    // an account of another mint, or of the gating mint held by someone else, proves nothing
+    let other_mint = create_mint(&mut env);
+    let other_mint_account = create_token_account(&mut env, &other_mint, &registrant.pubkey(), GATING_MINIMUM);
+    assert_eq!(
+        register(&mut env, &registrant, Some(&other_mint_account)).await,
+        Err(custom_error(TokenWhitelistError::GatingAccountMissing))
+    );
+    let borrowed = create_token_account(&mut env, &mint, &Pubkey::new_unique(), GATING_MINIMUM);
+    assert_eq!(
+        register(&mut env, &registrant, Some(&borrowed)).await,
+        Err(custom_error(TokenWhitelistError::GatingAccountMissing))
+    );
+}
+
+#[tokio::test]
+async fn test_owner_adds_ungated_by_default() {
+    let (mut env, _) = gated_env(false).await;
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 100).await.unwrap();
+    assert!(is_whitelisted(&mut env, &wallet).await);
+}
+
+#[tokio::test]
+async fn test_owner_adds_gated() {
+    let (mut env, mint) = gated_env(true).await;
+    let owner = env.owner.insecure_clone();
+    let wallet = Pubkey::new_unique();
+    assert_eq!(
+        env.add_to_whitelist(&wallet, 100).await,
+        Err(custom_error(TokenWhitelistError::GatingAccountMissing))
+    );
+
+    let add = |env: &TestEnv, token_account: &Pubkey| {
+        let mut instruction = add_to_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, &wallet, 100);
+        instruction.accounts.push(AccountMeta::new_readonly(*token_account, false));
+        instruction
+    };
+    let below = create_token_account(&mut env, &mint, &wallet, GATING_MINIMUM - 1);
+    let instruction = add(&env, &below);
+    assert_eq!(
+        env.send(&[instruction], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::InsufficientGatingBalance))
+    );
+
+    let at_minimum = create_token_account(&mut env, &mint, &wallet, GATING_MINIMUM);
+    let instruction = add(&env, &at_minimum);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    assert!(is_whitelisted(&mut env, &wallet).await);
+}
+
+#[tokio::test]
+async fn test_rotate_gated() {
+    let (mut env, mint) = gated_env(false).await;
+    let (current, new) = (Keypair::new(), Keypair::new());
+    env.add_to_whitelist(&current.pubkey(), 100).await.unwrap();
+
+    // the account taking over joins without the owner, gated like a registrant
+    let rotate = |env: &TestEnv, token_account: Option<&Pubkey>| {
+        let mut instruction = rotate_whitelisted_key(&env.program_id, &current.pubkey(), &env.whitelist, &new.pubkey());
+        instruction.accounts.extend(token_account.map(|token_account| AccountMeta::new_readonly(*token_account, false)));
+        instruction
+    };
+    let instruction = rotate(&env, None);
+    assert_eq!(
+        env.send(&[instruction], &[&current, &new]).await,
+        Err(custom_error(TokenWhitelistError::GatingAccountMissing))
+    );
+    // the gating balance of the account giving up the entry does not count
+    let held_by_current = create_token_account(&mut env, &mint, &current.pubkey(), GATING_MINIMUM);
+    let instruction = rotate(&env, Some(&held_by_current));
+    assert_eq!(
+        env.send(&[instruction], &[&current, &new]).await,
+        Err(custom_error(TokenWhitelistError::GatingAccountMissing))
+    );
+
+    let held_by_new = create_token_account(&mut env, &mint, &new.pubkey(), GATING_MINIMUM);
+    let instruction = rotate(&env, Some(&held_by_new));
+    env.send(&[instruction], &[&current, &new]).await.unwrap();
+    assert!(!is_whitelisted(&mut env, &current.pubkey()).await);
+    assert!(is_whitelisted(&mut env, &new.pubkey()).await);
+}
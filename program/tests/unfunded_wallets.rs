@@ -0,0 +1,75 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Signer};
+use solr_token_whitelist::{
+    instruction::{add_to_whitelist, remove_from_whitelist, TokenWhitelistInstruction},
+    state::{TokenWhitelist, NO_CAMPAIGN},
+};
+
+use common::TestEnv;
+
+/// A wallet key no transaction ever funded
+async fn unfunded_wallet(env: &mut TestEnv) -> Pubkey {
+    let wallet = Pubkey::new_unique();
+    assert_eq!(env.context.banks_client.get_account(wallet).await.unwrap(), None);
+    wallet
+}
+
+async fn assert_still_unfunded(env: &mut TestEnv, wallet: &Pubkey) {
+    assert_eq!(env.context.banks_client.get_account(*wallet).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_add_and_remove_unfunded_wallet() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let wallet = unfunded_wallet(&mut env).await;
+
+    env.add_to_whitelist(&wallet, 1_000).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&wallet.to_string()), Some(&1_000));
+    assert_still_unfunded(&mut env, &wallet).await;
+
+    let instruction = remove_from_whitelist(&env.program_id, &env.owner.pubkey(), &env.whitelist, &wallet);
+    let owner = env.owner.insecure_clone();
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+    assert_still_unfunded(&mut env, &wallet).await;
+}
+
+#[tokio::test]
+async fn test_add_unfunded_wallet_under_token_validation() {
+    let mut env = TestEnv::start(|_| {}).await;
+    // strict token checks and an allocation mint only concern accounts a token program owns
+    let mut account: Account = env.context.banks_client.get_account(env.whitelist).await.unwrap().unwrap();
+    let mut state = TokenWhitelist::unpack_from_slice(&account.data).unwrap();
+    state.extension.strict_token_checks = true;
+    state.extension.allocation_decimals = 6;
+    state.extension.allocation_mint = Some(Pubkey::new_unique());
+    state.pack_into_slice(&mut account.data).unwrap();
+    env.context.set_account(&env.whitelist, &account.into());
+
+    let wallet = unfunded_wallet(&mut env).await;
+    let (program_id, owner, whitelist) = (env.program_id, env.owner.insecure_clone(), env.whitelist);
+    let for_round = env.instruction(
+        TokenWhitelistInstruction::AddToWhitelistForRound {
+            allocation_amount: 500,
+            round: 1,
+            campaign_id: NO_CAMPAIGN,
+            expected_nonce: 0,
+        },
+        vec![
+            AccountMeta::new_readonly(owner.pubkey(), true),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(wallet, false),
+        ],
+    );
+    env.send(&[add_to_whitelist(&program_id, &owner.pubkey(), &whitelist, &wallet, 1_000), for_round], &[&owner])
+        .await
+        .unwrap();
+
+    let state = env.whitelist_state().await;
+    assert_eq!(state.round_allocation(&wallet.to_string(), 0), Some(1_000));
+    assert_eq!(state.round_allocation(&wallet.to_string(), 1), Some(500));
+    assert_still_unfunded(&mut env, &wallet).await;
+}
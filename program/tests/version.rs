@@ -0,0 +1,51 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program_test::tokio;
+use solr_token_whitelist::{
+    instruction::get_version,
+    state::ProgramVersion,
+    ATTESTATIONS, BATCH_ADD, CAMPAIGNS, COMPRESSED_MODE, CREATION_RECORD, ENTRY_NOTES, ENUMERATION_RESTRICTION,
+    FEATURE_BITS, KEY_ROTATION, MERKLE_MODE, MUTATION_NONCE, PAUSE, REGISTRATION_RATE_LIMIT, REQUIRED_RENT, RETURN_DATA,
+    SIBLING_WHITELISTS, VERSION, WHITELIST_STATS,
+};
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn test_get_version() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let return_data = env.simulate(&[get_version(&env.program_id)], &[]).await.returned().unwrap();
+    assert_eq!(return_data.program_id, env.program_id);
+
+    let version = ProgramVersion::try_from_slice(&return_data.data).unwrap();
+    assert_eq!(version, ProgramVersion {semver: VERSION, feature_bits: FEATURE_BITS});
+}
+
+#[test]
+fn test_version_matches_crate() {
+    let semver: Vec<String> = VERSION.iter().map(u16::to_string).collect();
+    assert_eq!(semver.join("."), env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn test_feature_bits_reflect_build() {
+    assert!(ProgramVersion::current().has(RETURN_DATA));
+    assert!(ProgramVersion::current().has(CAMPAIGNS));
+    assert!(ProgramVersion::current().has(MUTATION_NONCE));
+    assert!(ProgramVersion::current().has(WHITELIST_STATS));
+    assert!(ProgramVersion::current().has(ATTESTATIONS));
+    assert!(ProgramVersion::current().has(KEY_ROTATION));
+    assert!(ProgramVersion::current().has(SIBLING_WHITELISTS));
+    assert!(ProgramVersion::current().has(REQUIRED_RENT));
+    assert!(ProgramVersion::current().has(CREATION_RECORD));
+    assert!(ProgramVersion::current().has(ENTRY_NOTES));
+    assert!(ProgramVersion::current().has(REGISTRATION_RATE_LIMIT));
+    assert!(ProgramVersion::current().has(ENUMERATION_RESTRICTION));
+    assert_eq!(ProgramVersion::current().has(MERKLE_MODE), cfg!(feature = "merkle"));
+    assert_eq!(ProgramVersion::current().has(COMPRESSED_MODE), cfg!(feature = "compression"));
+    // no batch add or pause in this program yet
+    for feature in [BATCH_ADD, PAUSE] {
+        assert!(!ProgramVersion::current().has(feature));
+    }
+}
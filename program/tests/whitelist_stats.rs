@@ -0,0 +1,65 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+use solr_token_whitelist::{
+    instruction::{add_to_whitelist, get_whitelist_stats, remove_from_whitelist},
+    state::{WhitelistStats, WhitelistStatsReport},
+};
+
+use common::TestEnv;
+
+/// Simulates GetWhitelistStats for the stats of its return data
+async fn simulate_stats(env: &mut TestEnv) -> WhitelistStats {
+    let instruction = get_whitelist_stats(&env.program_id, &env.whitelist);
+    let return_data = env.simulate(&[instruction], &[]).await.returned().unwrap();
+    WhitelistStatsReport::try_from_slice(&return_data.data).unwrap().stats
+}
+
+async fn remove(env: &mut TestEnv, wallet: &Pubkey) {
+    let owner = env.owner.insecure_clone();
+    let instruction = remove_from_whitelist(&env.program_id, &owner.pubkey(), &env.whitelist, wallet);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stats_follow_churn() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert_eq!(simulate_stats(&mut env).await, WhitelistStats::default());
+
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+    env.context.warp_to_slot(100).unwrap();
+    env.add_to_whitelist(&first, 100).await.unwrap();
+    env.add_to_whitelist(&second, 100).await.unwrap();
+    // an update counts as an add
+    env.add_to_whitelist(&first, 300).await.unwrap();
+    assert_eq!(
+        simulate_stats(&mut env).await,
+        WhitelistStats {total_adds: 3, total_removes: 0, last_mutation_slot: 100}
+    );
+
+    env.context.warp_to_slot(200).unwrap();
+    remove(&mut env, &first).await;
+    // the same key added and removed in a loop, as a compromised key would
+    for _ in 0..3 {
+        env.add_to_whitelist(&first, 100).await.unwrap();
+        remove(&mut env, &first).await;
+    }
+    assert_eq!(
+        simulate_stats(&mut env).await,
+        WhitelistStats {total_adds: 6, total_removes: 4, last_mutation_slot: 200}
+    );
+
+    // removing an account outside the whitelist takes nothing out, failed adds count nothing
+    env.context.warp_to_slot(300).unwrap();
+    remove(&mut env, &Pubkey::new_unique()).await;
+    let intruder = Keypair::new();
+    let instruction = add_to_whitelist(&env.program_id, &intruder.pubkey(), &env.whitelist, &first, 100);
+    assert!(env.send(&[instruction], &[&intruder]).await.is_err());
+    let stats = simulate_stats(&mut env).await;
+    assert_eq!(stats, WhitelistStats {total_adds: 6, total_removes: 4, last_mutation_slot: 200});
+    assert_eq!(env.whitelist_state().await.extension.stats, stats);
+    assert_eq!(env.whitelist_state().await.summary().stats, stats);
+}
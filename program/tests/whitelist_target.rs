@@ -0,0 +1,70 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, system_program};
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+use solr_token_whitelist::{error::TokenWhitelistError, instruction::TokenWhitelistInstruction};
+
+use common::{custom_error, TestEnv};
+
+async fn set_owner_entry_allowed(env: &mut TestEnv, allow_owner_entry: bool) {
+    env.send_as_owner(TokenWhitelistInstruction::SetOwnerEntryAllowed {allow_owner_entry}).await.unwrap();
+    assert_eq!(env.whitelist_state().await.extension.owner_entry_disabled, !allow_owner_entry);
+}
+
+#[tokio::test]
+async fn test_whitelist_and_program_refused() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let (whitelist, program_id) = (env.whitelist, env.program_id);
+    for target in [whitelist, program_id] {
+        assert_eq!(
+            env.add_to_whitelist(&target, 100).await,
+            Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+        );
+    }
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+}
+
+#[tokio::test]
+async fn test_owner_entry_disallowed() {
+    let mut env = TestEnv::start(|_| {}).await;
+    let owner = env.owner.pubkey();
+    assert!(!env.whitelist_state().await.extension.owner_entry_disabled);
+    env.send_as_owner(TokenWhitelistInstruction::ConfigureRegistration {
+        registration_end_slot: 100,
+        registration_allocation: 250,
+        registration_deposit_lamports: 0,
+    })
+    .await
+    .unwrap();
+
+    set_owner_entry_allowed(&mut env, false).await;
+    let owner_keypair = env.owner.insecure_clone();
+    let register = env.instruction(
+        TokenWhitelistInstruction::RegisterSelf {},
+        vec![
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert_eq!(
+        env.send(&[register], &[&owner_keypair]).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+    assert_eq!(
+        env.add_to_whitelist(&owner, 100).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+    assert!(env.whitelist_state().await.whitelist_map.is_empty());
+
+    // allowed again, the owner is added like any wallet and keeps the entry once disallowed
+    set_owner_entry_allowed(&mut env, true).await;
+    env.add_to_whitelist(&owner, 100).await.unwrap();
+    set_owner_entry_allowed(&mut env, false).await;
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&owner.to_string()), Some(&100));
+    assert_eq!(
+        env.add_to_whitelist(&owner, 200).await,
+        Err(custom_error(TokenWhitelistError::InvalidWhitelistTarget))
+    );
+}
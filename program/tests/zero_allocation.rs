@@ -0,0 +1,137 @@
+mod common;
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey, system_program};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use solr_token_whitelist::{
+    error::TokenWhitelistError,
+    instruction::{init_config, with_config, TokenWhitelistInstruction},
+    state::{WhitelistConfig, NO_CAMPAIGN},
+};
+
+use common::{custom_error, TestEnv};
+
+async fn disable_zero_allocation(env: &mut TestEnv) {
+    env.send_as_owner(TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation: false}).await.unwrap();
+    assert!(env.whitelist_state().await.extension.zero_allocation_disabled);
+}
+
+#[tokio::test]
+async fn test_zero_allocation_allowed_by_default() {
+    let mut env = TestEnv::start(|_| {}).await;
+    assert!(!env.whitelist_state().await.extension.zero_allocation_disabled);
+
+    let wallet = Pubkey::new_unique();
+    env.add_to_whitelist(&wallet, 0).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&wallet.to_string()), Some(&0));
+}
+
+#[tokio::test]
+async fn test_zero_allocation_disabled() {
+    let mut env = TestEnv::start(|_| {}).await;
+    disable_zero_allocation(&mut env).await;
+
+    let wallet = Pubkey::new_unique();
+    assert_eq!(
+        env.add_to_whitelist(&wallet, 0).await,
+        Err(custom_error(TokenWhitelistError::AllocationBelowMinimum))
+    );
+    env.add_to_whitelist(&wallet, 1).await.unwrap();
+    // nor can an existing entry be overwritten with zero
+    assert_eq!(
+        env.add_to_whitelist(&wallet, 0).await,
+        Err(custom_error(TokenWhitelistError::AllocationBelowMinimum))
+    );
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&wallet.to_string()), Some(&1));
+
+    env.send_as_owner(TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation: true}).await.unwrap();
+    env.add_to_whitelist(&wallet, 0).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_zero_allocation_disabled_for_delegate() {
+    let mut env = TestEnv::start(|_| {}).await;
+    disable_zero_allocation(&mut env).await;
+    let delegate = Keypair::new();
+    let approve = TokenWhitelistInstruction::ApproveDelegate {max_adds: 5, max_allocation_per_add: 100};
+    let mut approve = env.owner_instruction(approve);
+    approve.accounts.push(AccountMeta::new_readonly(delegate.pubkey(), false));
+    let owner = env.owner.insecure_clone();
+    env.send(&[approve], &[&owner]).await.unwrap();
+
+    let add = env.instruction(
+        TokenWhitelistInstruction::AddToWhitelist {allocation_amount: 0, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+        vec![
+            AccountMeta::new_readonly(delegate.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        ],
+    );
+    assert_eq!(
+        env.send(&[add], &[&delegate]).await,
+        Err(custom_error(TokenWhitelistError::AllocationBelowMinimum))
+    );
+}
+
+#[tokio::test]
+async fn test_register_self_exempt() {
+    let registrant = Keypair::new();
+    let mut env = TestEnv::start(|program_test| {
+        program_test.add_account(registrant.pubkey(), Account::new(1_000_000_000, 0, &system_program::id()));
+    })
+    .await;
+    disable_zero_allocation(&mut env).await;
+    env.send_as_owner(TokenWhitelistInstruction::ConfigureRegistration {
+        registration_end_slot: 100,
+        registration_allocation: 0,
+        registration_deposit_lamports: 0,
+    })
+    .await
+    .unwrap();
+
+    let register = env.instruction(
+        TokenWhitelistInstruction::RegisterSelf {},
+        vec![
+            AccountMeta::new(registrant.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    env.send(&[register], &[&registrant]).await.unwrap();
+    assert_eq!(env.whitelist_state().await.whitelist_map.get(&registrant.pubkey().to_string()), Some(&0));
+}
+
+#[tokio::test]
+async fn test_zero_allocation_policy_in_config() {
+    let mut env = TestEnv::start(|_| {}).await;
+    // the owner pays the rent of the config account
+    let funded = Account {lamports: 1_000_000_000, owner: system_program::id(), ..Account::default()};
+    env.context.set_account(&env.owner.pubkey(), &funded.into());
+    let owner = env.owner.insecure_clone();
+    let instruction = init_config(&env.program_id, &owner.pubkey(), &env.whitelist);
+    env.send(&[instruction], &[&owner]).await.unwrap();
+    let (config, _) = WhitelistConfig::find_address(&env.program_id, &env.whitelist);
+
+    let disable = env.owner_instruction(TokenWhitelistInstruction::SetZeroAllocationAllowed {allow_zero_allocation: false});
+    env.send(&[with_config(disable, &config)], &[&owner]).await.unwrap();
+    let account = env.context.banks_client.get_account(config).await.unwrap().unwrap();
+    assert!(WhitelistConfig::unpack_from_slice(&account.data).unwrap().policy.zero_allocation_disabled);
+    // the whitelist copy of the policy is left as it was
+    assert!(!env.whitelist_state().await.extension.zero_allocation_disabled);
+
+    let add = env.instruction(
+        TokenWhitelistInstruction::AddToWhitelist {allocation_amount: 0, campaign_id: NO_CAMPAIGN, expected_nonce: 0},
+        vec![
+            AccountMeta::new_readonly(owner.pubkey(), true),
+            AccountMeta::new(env.whitelist, false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        ],
+    );
+    assert_eq!(
+        env.send(&[with_config(add, &config)], &[&owner]).await,
+        Err(custom_error(TokenWhitelistError::AllocationBelowMinimum))
+    );
+}